@@ -0,0 +1,145 @@
+//! Read-only mode for viewer users on shared PCs
+//!
+//! An administrator enables it with a machine-wide policy file
+//! (`%ProgramData%\ShoruiChecker\policy.json`, `/etc/shoruichecker/policy.json`
+//! elsewhere) that ordinary users cannot change. In read-only mode analyzing
+//! and browsing stay available, but nothing is written into the documents
+//! (embedding or sidecars), files are not moved, and guidelines and check
+//! settings cannot be edited. Users listed as editors keep full access. A
+//! policy file that exists but cannot be read or parsed counts as read-only
+//! for everyone, so a damaged policy never lifts the restriction.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::tr;
+
+const POLICY_FILE: &str = "policy.json";
+
+/// Admin-managed policy (policy.json)
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessPolicy {
+    #[serde(default)]
+    pub read_only: bool,
+    /// Windows user names that keep full access in read-only mode
+    #[serde(default)]
+    pub editors: Vec<String>,
+}
+
+/// Operations disabled in read-only mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// Embedding results into PDFs or writing sidecars
+    Embed,
+    /// Moving documents between folders
+    Move,
+    /// Editing guidelines and project check settings
+    EditGuidelines,
+}
+
+impl Operation {
+    fn label(self) -> String {
+        match self {
+            Operation::Embed => tr("access.embed", &[]),
+            Operation::Move => tr("access.move", &[]),
+            Operation::EditGuidelines => tr("access.edit_guidelines", &[]),
+        }
+    }
+}
+
+/// Access of the current user, for the frontend to hide disabled actions
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct AccessStatus {
+    pub read_only: bool,
+    pub user: String,
+    pub policy_path: String,
+}
+
+pub fn policy_path() -> PathBuf {
+    let dir = if cfg!(windows) {
+        std::env::var_os("PROGRAMDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+            .join("ShoruiChecker")
+    } else {
+        PathBuf::from("/etc/shoruichecker")
+    };
+    dir.join(POLICY_FILE)
+}
+
+/// Read-only for everyone, for a policy that cannot be read
+fn locked_policy() -> AccessPolicy {
+    AccessPolicy {
+        read_only: true,
+        editors: Vec::new(),
+    }
+}
+
+/// Policy from the contents of policy.json (read-only when unparseable)
+pub fn parse_policy(contents: &str) -> AccessPolicy {
+    serde_json::from_str(contents).unwrap_or_else(|_| locked_policy())
+}
+
+/// The policy, or full access when there is none
+pub fn load_policy() -> AccessPolicy {
+    match fs::read_to_string(policy_path()) {
+        Ok(contents) => parse_policy(&contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => AccessPolicy::default(),
+        Err(_) => locked_policy(),
+    }
+}
+
+/// Account name from the OS (environment variables can be changed by the user)
+fn current_user() -> String {
+    whoami::username()
+}
+
+/// Whether the policy restricts a user
+pub fn read_only_for(policy: &AccessPolicy, user: &str) -> bool {
+    policy.read_only && !policy.editors.iter().any(|e| e.trim().eq_ignore_ascii_case(user))
+}
+
+pub fn is_read_only() -> bool {
+    read_only_for(&load_policy(), &current_user())
+}
+
+/// Refuse an operation disabled in read-only mode
+pub fn ensure_allowed(operation: Operation) -> Result<(), String> {
+    if is_read_only() {
+        return Err(tr("access.read_only", &[&operation.label()]));
+    }
+    Ok(())
+}
+
+/// 現在のユーザーの権限（閲覧専用モードかどうか）
+#[tauri::command]
+pub fn get_access_status() -> AccessStatus {
+    AccessStatus {
+        read_only: is_read_only(),
+        user: current_user(),
+        policy_path: policy_path().to_string_lossy().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editors_keep_full_access_in_read_only_mode() {
+        let policy: AccessPolicy =
+            serde_json::from_str(r#"{ "read_only": true, "editors": ["Tanaka"] }"#).unwrap();
+        assert!(read_only_for(&policy, "suzuki"));
+        assert!(!read_only_for(&policy, "tanaka"));
+        assert!(!read_only_for(&AccessPolicy::default(), "suzuki"));
+    }
+
+    #[test]
+    fn a_broken_policy_is_read_only() {
+        assert!(read_only_for(&parse_policy("{ \"read_only\": fal"), "tanaka"));
+        assert!(!read_only_for(&parse_policy("{}"), "tanaka"));
+    }
+}
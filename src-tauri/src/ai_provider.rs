@@ -0,0 +1,479 @@
+//! Selectable AI providers
+//!
+//! Every AI request (prompt plus files in the sandboxed temp dir, text out)
+//! goes through an [`AiProvider`]. The provider is chosen in the settings
+//! (`ai_backend`) and can be switched at runtime with `set_provider`;
+//! `SHORUICHECKER_AI_BACKEND` overrides the setting, e.g. for tests.
+//!
+//! With `provider_fallback` configured, a failed request (expired login,
+//! quota, network) is retried with the next available provider of the chain;
+//! every hop is logged. Timeouts, aborted batches and a shutdown end the
+//! chain instead.
+
+use std::cell::Cell;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::batch::current_batch_aborted;
+use crate::claude_api::{self, run_claude, DEFAULT_CLAUDE_MODEL};
+use crate::error::{AppError, AppResult};
+use crate::events::emit_app_log;
+use crate::gemini_api::{self, run_gemini_api};
+use crate::gemini_cli::{run_gemini_cli, GeminiRequest};
+use crate::messages::tr;
+use crate::mock_backend::{load_fixtures, run_mock, MOCK_ENV};
+use crate::openai_compat::{self, run_openai_compat, OpenAiCompatSettings};
+use crate::settings::{load_settings, save_settings, AiBackend};
+use crate::shutdown::is_shutting_down;
+
+/// Overrides the configured backend (`gemini`, `gemini_api`, `claude`, `openai` or `mock`)
+pub const BACKEND_ENV: &str = "SHORUICHECKER_AI_BACKEND";
+
+thread_local! {
+    /// Backend of the provider run last on this thread: in a fallback chain
+    /// the one that answered, or the last one that failed
+    static ATTEMPTED_BACKEND: Cell<Option<AiBackend>> = const { Cell::new(None) };
+}
+
+/// Remember the backend a request is sent to
+pub fn note_attempted_backend(backend: AiBackend) {
+    ATTEMPTED_BACKEND.with(|b| b.set(Some(backend)));
+}
+
+/// Backend the last request on this thread was sent to
+pub fn attempted_backend() -> Option<AiBackend> {
+    ATTEMPTED_BACKEND.with(|b| b.get())
+}
+
+/// Answers AI requests
+pub trait AiProvider: Send + Sync {
+    fn backend(&self) -> AiBackend;
+
+    /// Whether the provider is set up (key or server configured)
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// Run a request; attached file names are relative to `temp_dir`
+    fn run(&self, temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String>;
+}
+
+/// Gemini CLI run in the sandboxed temp dir
+pub struct GeminiCliProvider;
+
+impl AiProvider for GeminiCliProvider {
+    fn backend(&self) -> AiBackend {
+        AiBackend::Gemini
+    }
+
+    fn run(&self, temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+        run_gemini_cli(temp_dir, request)
+    }
+}
+
+/// Gemini REST API with Files API uploads
+pub struct GeminiApiProvider;
+
+impl AiProvider for GeminiApiProvider {
+    fn backend(&self) -> AiBackend {
+        AiBackend::GeminiApi
+    }
+
+    fn is_available(&self) -> bool {
+        gemini_api::api_key().is_some()
+    }
+
+    fn run(&self, temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+        run_gemini_api(temp_dir, request)
+    }
+}
+
+/// Anthropic Messages API
+pub struct ClaudeApiProvider;
+
+impl AiProvider for ClaudeApiProvider {
+    fn backend(&self) -> AiBackend {
+        AiBackend::Claude
+    }
+
+    fn is_available(&self) -> bool {
+        claude_api::api_key().is_some()
+    }
+
+    fn run(&self, temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+        run_claude(temp_dir, request)
+    }
+}
+
+/// OpenAI-compatible server (on-prem LLM)
+pub struct OpenAiCompatProvider;
+
+impl AiProvider for OpenAiCompatProvider {
+    fn backend(&self) -> AiBackend {
+        AiBackend::OpenAiCompat
+    }
+
+    fn is_available(&self) -> bool {
+        load_settings()
+            .openai_compat
+            .is_some_and(|s| s.is_configured())
+    }
+
+    fn run(&self, temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+        run_openai_compat(temp_dir, request)
+    }
+}
+
+/// Canned responses from fixtures
+pub struct MockProvider;
+
+impl AiProvider for MockProvider {
+    fn backend(&self) -> AiBackend {
+        AiBackend::Mock
+    }
+
+    fn run(&self, _temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+        run_mock(request)
+    }
+}
+
+/// Providers tried in order; unavailable ones after the first are skipped
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn AiProvider>>,
+}
+
+impl FallbackProvider {
+    pub fn new(providers: Vec<Box<dyn AiProvider>>) -> Self {
+        Self { providers }
+    }
+}
+
+impl AiProvider for FallbackProvider {
+    fn backend(&self) -> AiBackend {
+        self.providers
+            .first()
+            .map(|p| p.backend())
+            .unwrap_or_default()
+    }
+
+    fn run(&self, temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+        let mut failed: Option<(AiBackend, AppError)> = None;
+        for (i, provider) in self.providers.iter().enumerate() {
+            if i > 0 && !provider.is_available() {
+                continue;
+            }
+            if let Some((backend, error)) = &failed {
+                emit_app_log(
+                    &tr(
+                        "provider.fallback",
+                        &[&backend_label(*backend), error, &backend_label(provider.backend())],
+                    ),
+                    "warn",
+                );
+            }
+            note_attempted_backend(provider.backend());
+            match provider.run(temp_dir, request) {
+                Ok(text) => {
+                    if failed.is_some() {
+                        emit_app_log(
+                            &tr("provider.fallback_done", &[&backend_label(provider.backend())]),
+                            "success",
+                        );
+                    }
+                    return Ok(text);
+                }
+                // A timeout is not a provider problem, and an aborted batch
+                // or a shutdown must not start the next provider
+                Err(e @ AppError::Timeout(_)) => return Err(e),
+                Err(e) if current_batch_aborted() || is_shutting_down() => return Err(e),
+                Err(e) => failed = Some((provider.backend(), e)),
+            }
+        }
+        Err(failed
+            .map(|(_, e)| e)
+            .unwrap_or_else(|| AppError::Process("使用できるAIがありません".to_string())))
+    }
+}
+
+pub fn backend_label(backend: AiBackend) -> &'static str {
+    match backend {
+        AiBackend::Gemini => "Gemini CLI",
+        AiBackend::GeminiApi => "Gemini API",
+        AiBackend::Claude => "Claude API",
+        AiBackend::OpenAiCompat => "社内LLMサーバー",
+        AiBackend::Mock => "モック",
+    }
+}
+
+/// The selected backend followed by the configured fallbacks (without
+/// duplicates; the mock backend is never a fallback and never falls back)
+pub fn fallback_chain(primary: AiBackend, fallback: &[AiBackend]) -> Vec<AiBackend> {
+    let mut chain = vec![primary];
+    if primary == AiBackend::Mock {
+        return chain;
+    }
+    for backend in fallback {
+        if *backend != AiBackend::Mock && !chain.contains(backend) {
+            chain.push(*backend);
+        }
+    }
+    chain
+}
+
+pub fn parse_backend(name: &str) -> Option<AiBackend> {
+    match name.trim().to_lowercase().as_str() {
+        "gemini" => Some(AiBackend::Gemini),
+        "gemini_api" => Some(AiBackend::GeminiApi),
+        "claude" => Some(AiBackend::Claude),
+        "openai" => Some(AiBackend::OpenAiCompat),
+        "mock" => Some(AiBackend::Mock),
+        _ => None,
+    }
+}
+
+fn env_backend() -> Option<AiBackend> {
+    std::env::var(BACKEND_ENV).ok().and_then(|b| parse_backend(&b))
+}
+
+/// Whether the environment forces the mock backend: then no request reaches
+/// a real provider, whatever the settings ask for
+fn mock_forced() -> bool {
+    forces_mock(
+        std::env::var(MOCK_ENV).ok().as_deref(),
+        std::env::var(BACKEND_ENV).ok().as_deref(),
+    )
+}
+
+/// `SHORUICHECKER_MOCK=1` (or `true`), or `SHORUICHECKER_AI_BACKEND=mock`
+fn forces_mock(mock: Option<&str>, backend: Option<&str>) -> bool {
+    matches!(mock.map(str::trim), Some("1" | "true"))
+        || backend.and_then(parse_backend) == Some(AiBackend::Mock)
+}
+
+/// Backend in use: the environment overrides, else the setting
+pub fn active_backend() -> AiBackend {
+    if mock_forced() {
+        return AiBackend::Mock;
+    }
+    env_backend().unwrap_or_else(|| load_settings().ai_backend)
+}
+
+pub fn provider_for(backend: AiBackend) -> Box<dyn AiProvider> {
+    if mock_forced() {
+        return Box::new(MockProvider);
+    }
+    match backend {
+        AiBackend::Gemini => Box::new(GeminiCliProvider),
+        AiBackend::GeminiApi => Box::new(GeminiApiProvider),
+        AiBackend::Claude => Box::new(ClaudeApiProvider),
+        AiBackend::OpenAiCompat => Box::new(OpenAiCompatProvider),
+        AiBackend::Mock => Box::new(MockProvider),
+    }
+}
+
+pub fn current_provider() -> Box<dyn AiProvider> {
+    if mock_forced() {
+        return Box::new(MockProvider);
+    }
+    let chain = fallback_chain(active_backend(), &load_settings().provider_fallback);
+    if chain.len() == 1 {
+        return provider_for(chain[0]);
+    }
+    Box::new(FallbackProvider::new(chain.into_iter().map(provider_for).collect()))
+}
+
+#[derive(Clone, Serialize)]
+pub struct ProviderStatus {
+    /// Provider in the settings
+    pub provider: AiBackend,
+    /// Provider answering requests (differs when overridden by the environment)
+    pub active: AiBackend,
+    pub claude_model: String,
+    pub claude_key_configured: bool,
+    pub gemini_key_configured: bool,
+    pub openai_compat: Option<OpenAiCompatSettings>,
+    pub openai_key_configured: bool,
+    /// Providers tried when the selected one fails
+    pub fallback: Vec<AiBackend>,
+    /// Fixture file or folder of the mock backend
+    pub mock_fixtures: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ProviderRequest {
+    pub provider: AiBackend,
+    /// Claude model (None: keep the current one)
+    #[serde(default)]
+    pub claude_model: Option<String>,
+    /// Claude API key to store (None: keep the stored one, "": remove it)
+    #[serde(default)]
+    pub claude_api_key: Option<String>,
+    /// Gemini API key to store (same as above)
+    #[serde(default)]
+    pub gemini_api_key: Option<String>,
+    /// Server of the OpenAI-compatible provider (None: keep the current one)
+    #[serde(default)]
+    pub openai_compat: Option<OpenAiCompatSettings>,
+    /// Key of the OpenAI-compatible server (same as the other keys)
+    #[serde(default)]
+    pub openai_api_key: Option<String>,
+    /// Fallback order (None: keep the current one, empty: no fallback)
+    #[serde(default)]
+    pub fallback: Option<Vec<AiBackend>>,
+    /// Fixtures of the mock backend (None: keep the current ones, "": built-in answers)
+    #[serde(default)]
+    pub mock_fixtures: Option<String>,
+}
+
+/// 解析に使うAI（Gemini CLI / Gemini API / Claude API / 社内LLMサーバー / モック）
+#[tauri::command]
+pub fn get_provider() -> ProviderStatus {
+    let settings = load_settings();
+    ProviderStatus {
+        provider: settings.ai_backend,
+        active: active_backend(),
+        claude_model: settings
+            .claude_model
+            .unwrap_or_else(|| DEFAULT_CLAUDE_MODEL.to_string()),
+        claude_key_configured: claude_api::api_key().is_some(),
+        gemini_key_configured: gemini_api::api_key().is_some(),
+        openai_compat: settings.openai_compat,
+        openai_key_configured: openai_compat::api_key().is_some(),
+        fallback: settings.provider_fallback,
+        mock_fixtures: settings.mock_fixtures,
+    }
+}
+
+/// 解析に使うAIを切り替え（再起動・再ビルド不要）
+#[tauri::command]
+pub fn set_provider(request: ProviderRequest) -> Result<ProviderStatus, String> {
+    if let Some(key) = &request.claude_api_key {
+        claude_api::store_api_key(Some(key.as_str()))?;
+    }
+    if let Some(key) = &request.gemini_api_key {
+        gemini_api::store_api_key(Some(key.as_str()))?;
+    }
+    if let Some(key) = &request.openai_api_key {
+        openai_compat::store_api_key(Some(key.as_str()))?;
+    }
+    let key_missing = match request.provider {
+        AiBackend::Claude => claude_api::api_key().is_none(),
+        AiBackend::GeminiApi => gemini_api::api_key().is_none(),
+        // Local servers usually run without a key
+        AiBackend::Gemini | AiBackend::OpenAiCompat | AiBackend::Mock => false,
+    };
+    if key_missing {
+        return Err("このAIを使うにはAPIキーを設定してください".to_string());
+    }
+    let mut settings = load_settings();
+    if let Some(server) = request.openai_compat {
+        settings.openai_compat = Some(OpenAiCompatSettings {
+            base_url: server.base_url.trim().to_string(),
+            model: server.model.trim().to_string(),
+        })
+        .filter(|s| s.is_configured());
+    }
+    if request.provider == AiBackend::OpenAiCompat
+        && !settings.openai_compat.as_ref().is_some_and(|s| s.is_configured())
+    {
+        return Err("LLMサーバーのURLとモデルを設定してください".to_string());
+    }
+    if let Some(path) = request.mock_fixtures {
+        let path = Some(path.trim().to_string()).filter(|p| !p.is_empty());
+        if let Some(path) = &path {
+            load_fixtures(path)?;
+        }
+        settings.mock_fixtures = path;
+    }
+    settings.ai_backend = request.provider;
+    if let Some(fallback) = request.fallback {
+        settings.provider_fallback = fallback;
+    }
+    if let Some(model) = request.claude_model {
+        settings.claude_model = Some(model.trim().to_string()).filter(|m| !m.is_empty());
+    }
+    save_settings(&settings)?;
+    Ok(get_provider())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers_match_their_backend() {
+        assert_eq!(parse_backend(" Claude "), Some(AiBackend::Claude));
+        assert_eq!(parse_backend("openai"), Some(AiBackend::OpenAiCompat));
+        assert_eq!(parse_backend("palm"), None);
+        assert_eq!(parse_backend("gemini_api"), Some(AiBackend::GeminiApi));
+        for backend in [
+            AiBackend::Gemini,
+            AiBackend::GeminiApi,
+            AiBackend::Claude,
+            AiBackend::OpenAiCompat,
+            AiBackend::Mock,
+        ] {
+            assert_eq!(provider_for(backend).backend(), backend);
+        }
+    }
+
+    #[test]
+    fn either_variable_forces_the_mock() {
+        assert!(forces_mock(Some("1"), None));
+        assert!(forces_mock(Some(" true "), Some("claude")));
+        assert!(forces_mock(None, Some("mock")));
+        assert!(!forces_mock(Some("0"), Some("gemini")));
+        assert!(!forces_mock(None, None));
+    }
+
+    struct Stub {
+        backend: AiBackend,
+        available: bool,
+        answer: Option<&'static str>,
+    }
+
+    impl AiProvider for Stub {
+        fn backend(&self) -> AiBackend {
+            self.backend
+        }
+
+        fn is_available(&self) -> bool {
+            self.available
+        }
+
+        fn run(&self, _temp_dir: &Path, _request: &GeminiRequest<'_>) -> AppResult<String> {
+            self.answer
+                .map(str::to_string)
+                .ok_or_else(|| AppError::Process(format!("{:?} failed", self.backend)))
+        }
+    }
+
+    fn stub(backend: AiBackend, available: bool, answer: Option<&'static str>) -> Box<dyn AiProvider> {
+        Box::new(Stub { backend, available, answer })
+    }
+
+    #[test]
+    fn fallback_tries_the_next_available_provider() {
+        let request = GeminiRequest::text("チェックして", "gemini-2.5-pro");
+        let chain = FallbackProvider::new(vec![
+            stub(AiBackend::Gemini, true, None),
+            stub(AiBackend::Claude, false, Some("claude")),
+            stub(AiBackend::OpenAiCompat, true, Some("local")),
+        ]);
+        assert_eq!(chain.backend(), AiBackend::Gemini);
+        assert_eq!(chain.run(Path::new("."), &request).unwrap(), "local");
+
+        let failing = FallbackProvider::new(vec![
+            stub(AiBackend::Gemini, true, None),
+            stub(AiBackend::Claude, true, None),
+        ]);
+        assert!(failing.run(Path::new("."), &request).unwrap_err().to_string().contains("Claude failed"));
+
+        assert_eq!(
+            fallback_chain(AiBackend::Gemini, &[AiBackend::Gemini, AiBackend::Mock, AiBackend::Claude]),
+            vec![AiBackend::Gemini, AiBackend::Claude]
+        );
+        assert_eq!(fallback_chain(AiBackend::Mock, &[AiBackend::Claude]), vec![AiBackend::Mock]);
+    }
+}
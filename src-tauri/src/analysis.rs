@@ -1,19 +1,82 @@
-use std::fs;
+use std::collections::VecDeque;
 use std::path::Path;
-use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
 
 use serde::Serialize;
 use tauri::{AppHandle, Emitter};
 
-use crate::events::emit_log;
-use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir, run_gemini_with_prompt};
-use crate::guidelines::{detect_document_type, get_relevant_guidelines, load_guidelines_json};
+use crate::approval::{record_written_result, released_paths, store_or_hold, StoreOutcome};
+use crate::archive::file_sha256;
+use crate::batch::{abort_error, aborted_message, begin_batch, BatchGuard, BatchSummary};
+use crate::classify::{classify_document, DocumentMetadata};
+use crate::cli_setup::cached_cli_version;
+use crate::consensus::with_consensus;
+use crate::events::{emit_app_log, emit_log};
+use crate::expected_values::{
+    append_expected_check, document_text, expected_values_section, resolve_expected_values,
+    ExpectedValue, ExpectedValuesSource,
+};
+use crate::gemini_cli::{
+    cleanup_temp_dir, create_temp_dir, run_gemini, run_gemini_in_temp, sanitize_file_name,
+    stage_into_temp, with_output_stream, GeminiRequest, TEMP_DIR_PREFIX,
+};
+use crate::guidelines::{
+    detect_document_type_in, extract_guideline_ids, get_relevant_guidelines_for_types,
+    match_type_rules, record_guideline_usage,
+};
 use crate::history::{
-    build_history_context, create_history_entry, load_history, save_history,
-    AnalysisHistoryEntry,
+    build_history_context, create_history_entry, load_history, new_entry_id,
+    record_history_entry, update_history, AnalysisHistoryEntry,
+};
+use crate::hooks::{
+    configured_hooks, latest_reports, run_stage_hooks, AnalysisHook, HookOutcome, HookPayload,
+    HookStage,
+};
+use crate::intake_dedup::release_file;
+use crate::messages::tr;
+use crate::notes::notes_section;
+use crate::postprocess::apply_output_rules;
+use crate::project_settings::{
+    anchor_priority, load_project_settings, matching_references, project_folder_for,
+};
+use crate::rag::{build_rag_context, index_analyzed_document, query_text_for};
+use crate::recovery::{begin_job, finish_job};
+use crate::result_cache::{cache_result, cached_result, forget_cached_results, prompt_hash};
+use crate::result_store::load_result_data;
+use crate::spec_clauses::clause_section;
+use crate::seal::{compare_seals, extract_seals, seal_dir_for, SealImpression};
+use crate::processes::with_child_scope;
+use crate::pdf_text::{
+    detect_language, extract_page_texts, extract_pages_to_pdf, has_enough_text, route_pages,
+    DocumentLanguage, DocumentRoute,
 };
-use crate::pdf_embed::embed_result_in_pdf_with_instruction;
-use crate::settings::{load_settings, DEFAULT_MODEL};
+use crate::settings::{
+    find_preset, load_settings, ResultStorage, DEFAULT_MAX_PARALLEL_ANALYSES, DEFAULT_MODEL,
+    DEFAULT_TEXT_MODEL,
+};
+use crate::shutdown::is_shutting_down;
+use crate::structured_report::{
+    emit_analysis_report, emit_compare_matrix, parse_compare_table, result_verdict, CompareMatrix,
+    CompareMatrixRow,
+};
+use crate::survey::append_survey_check;
+use crate::tasks::{run_blocking, spawn_blocking};
+use crate::traffic_guard::{append_placement_check, is_traffic_guard_document};
+use crate::usage::with_usage_project;
+
+/// テキストモードで参照資料ごとにプロンプトへ含める最大文字数
+const MAX_REFERENCE_TEXT_CHARS: usize = 20_000;
+
+/// 照合モードで1回のCLI呼び出しに渡す最大書類数（超える場合は基準書類と他をグループに分ける）
+const MAX_COMPARE_FILES_PER_CALL: usize = 5;
+
+/// Raw result of one comparison call
+struct CompareOutput {
+    result: String,
+    guidelines_section: String,
+}
 
 #[derive(Clone, Serialize)]
 struct AnalysisResult {
@@ -21,6 +84,354 @@ struct AnalysisResult {
     path: String,
     result: Option<String>,
     error: Option<String>,
+    /// Not started because the batch was aborted
+    skipped: bool,
+}
+
+/// Analyses running at once across all batches, and the files waiting
+struct AnalysisSlots {
+    running: usize,
+    /// Tickets of waiting analyses, first in line first
+    queue: VecDeque<u64>,
+    next_ticket: u64,
+}
+
+static ANALYSIS_SLOTS: Mutex<AnalysisSlots> = Mutex::new(AnalysisSlots {
+    running: 0,
+    queue: VecDeque::new(),
+    next_ticket: 0,
+});
+
+/// Signalled when a slot frees up or the queue changes
+static SLOTS_CHANGED: Condvar = Condvar::new();
+
+/// How often waiting analyses check whether their batch was aborted
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn lock_slots() -> MutexGuard<'static, AnalysisSlots> {
+    ANALYSIS_SLOTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn max_parallel_analyses() -> usize {
+    load_settings()
+        .max_parallel_analyses
+        .unwrap_or(DEFAULT_MAX_PARALLEL_ANALYSES)
+        .max(1)
+}
+
+/// A running analysis; frees its slot when dropped
+struct AnalysisSlot;
+
+impl Drop for AnalysisSlot {
+    fn drop(&mut self) {
+        let mut slots = lock_slots();
+        slots.running = slots.running.saturating_sub(1);
+        SLOTS_CHANGED.notify_all();
+    }
+}
+
+/// Wait for one of `limit` slots, first come first served
+///
+/// `on_queued` gets the place in line (1 = next) whenever it changes. None
+/// when `cancelled` turns true before a slot frees up.
+fn acquire_slot(
+    limit: usize,
+    cancelled: &dyn Fn() -> bool,
+    on_queued: &mut dyn FnMut(usize),
+) -> Option<AnalysisSlot> {
+    let mut slots = lock_slots();
+    let ticket = slots.next_ticket;
+    slots.next_ticket += 1;
+    slots.queue.push_back(ticket);
+    let mut reported = 0;
+    loop {
+        let position = slots.queue.iter().position(|t| *t == ticket).unwrap_or(0);
+        if position == 0 && slots.running < limit {
+            slots.queue.pop_front();
+            slots.running += 1;
+            SLOTS_CHANGED.notify_all();
+            return Some(AnalysisSlot);
+        }
+        if cancelled() {
+            slots.queue.retain(|t| *t != ticket);
+            SLOTS_CHANGED.notify_all();
+            return None;
+        }
+        if position + 1 != reported {
+            reported = position + 1;
+            drop(slots);
+            on_queued(reported);
+            slots = lock_slots();
+            continue;
+        }
+        slots = SLOTS_CHANGED
+            .wait_timeout(slots, SLOT_POLL_INTERVAL)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+}
+
+/// Run an analysis once a slot is free (`max_parallel_analyses`)
+///
+/// Waiting files are reported as `analysis-progress` events with their
+/// `queue_position`; 0 means the analysis has started. None when the batch
+/// is aborted or the app quits while waiting.
+fn with_analysis_slot<R>(
+    app: &AppHandle,
+    file_name: &str,
+    aborted: &AtomicBool,
+    f: impl FnOnce() -> R,
+) -> Option<R> {
+    let progress = |position: usize| {
+        let _ = app.emit(
+            "analysis-progress",
+            serde_json::json!({
+                "file_name": file_name,
+                "completed": false,
+                "queue_position": position
+            }),
+        );
+    };
+    let cancelled = || aborted.load(Ordering::SeqCst) || is_shutting_down();
+    let _slot = acquire_slot(max_parallel_analyses(), &cancelled, &mut |p| progress(p))?;
+    progress(0);
+    Some(f())
+}
+
+/// プロンプト用のガイドラインセクション（項目IDの引用を指示）
+fn format_guidelines_section(guidelines: &str) -> String {
+    format!(
+        "\n## 該当ガイドライン\n{}\n※ ガイドライン項目に該当する指摘には、その項目の先頭にあるID（[Gxxxxxx] の形式）をそのまま併記すること\n",
+        guidelines
+    )
+}
+
+/// プロンプト用のファイル表示名（一時コピー名が異なる場合は併記）
+fn display_file_name(file_name: &str, temp_name: &str) -> String {
+    if file_name == temp_name {
+        file_name.to_string()
+    } else {
+        format!("{}（添付名: {}）", file_name, temp_name)
+    }
+}
+
+/// 該当する常備参照資料を一時ディレクトリへコピーし、添付名とプロンプト用セクションを返す
+///
+/// 見つからない参照資料はスキップする。
+fn stage_reference_documents(
+    temp_dir: &Path,
+    project_folder: &str,
+    doc_types: &[String],
+    targets: &[String],
+) -> (Vec<String>, String) {
+    let settings = load_project_settings(project_folder);
+    let mut temp_names = Vec::new();
+    let mut lines = Vec::new();
+    for reference in matching_references(&settings, doc_types, targets) {
+        let file_name = Path::new(&reference.path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "reference.pdf".to_string());
+        if let Ok(temp_name) = stage_into_temp(temp_dir, &reference.path, &file_name) {
+            lines.push(format!("- {}（添付名: {}）", reference.label, temp_name));
+            temp_names.push(temp_name);
+        }
+    }
+
+    let section = if lines.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n## 参照資料\n以下は照合の基準となる常備参照資料です。対象書類がこれらと整合しているか確認してください（参照資料自体への指摘は不要）：\n{}\n",
+            lines.join("\n")
+        )
+    };
+    (temp_names, section)
+}
+
+/// テキストモードでの送り方をページごとの抽出結果から決める
+///
+/// テキストモードが無効、または抽出に失敗した場合はPDF添付での解析になる。
+fn document_route(path: &str) -> DocumentRoute {
+    if !load_settings().text_mode {
+        return DocumentRoute::Vision;
+    }
+    extract_page_texts(path)
+        .map(route_pages)
+        .unwrap_or(DocumentRoute::Vision)
+}
+
+/// 書類タイプと分類情報: プロジェクトの独自ルールに一致すればその種類を優先し、
+/// それ以外は1ページ目の事前分類（DBに保存）、分類できなかった場合だけ
+/// 組み込みキーワードで判定
+fn document_profile(
+    project_folder: &str,
+    path: &str,
+    file_name: &str,
+) -> (Vec<String>, Option<DocumentMetadata>) {
+    let mut metadata = classify_document(path, project_folder);
+    let rule_types = match_type_rules(
+        &load_project_settings(project_folder).document_type_rules,
+        file_name,
+    );
+    if let Some(first) = rule_types.first() {
+        if let Some(metadata) = metadata.as_mut() {
+            metadata.doc_type = Some(first.clone());
+        }
+        return (rule_types, metadata);
+    }
+    let types = match metadata.as_ref().and_then(|m| m.doc_type.clone()) {
+        Some(doc_type) => vec![doc_type],
+        None => detect_document_type_in(project_folder, file_name),
+    };
+    (types, metadata)
+}
+
+/// 事前分類で読み取った種類・日付・当事者のプロンプト節（分類情報がなければ空）
+fn metadata_section(documents: &[(String, Option<DocumentMetadata>)]) -> String {
+    let lines: Vec<String> = documents
+        .iter()
+        .filter_map(|(name, metadata)| {
+            let metadata = metadata.as_ref()?;
+            let mut fields = Vec::new();
+            if let Some(doc_type) = &metadata.doc_type {
+                fields.push(format!("種類: {}", doc_type));
+            }
+            if let Some(date) = &metadata.date {
+                fields.push(format!("日付: {}", date));
+            }
+            if !metadata.parties.is_empty() {
+                fields.push(format!("当事者: {}", metadata.parties.join("、")));
+            }
+            (!fields.is_empty()).then(|| format!("- {}: {}", name, fields.join(" / ")))
+        })
+        .collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\n\n## 事前分類（1ページ目から自動判定・参考情報）\n{}\n書類の内容と食い違う場合は書類の内容を優先してください。",
+        lines.join("\n")
+    )
+}
+
+/// 書類の主な言語（テキストを抽出できない場合は日本語とみなす）
+fn document_language(path: &str, route: &DocumentRoute) -> DocumentLanguage {
+    let text = match route {
+        DocumentRoute::Text(pages) | DocumentRoute::Hybrid { text_pages: pages, .. } => pages
+            .iter()
+            .map(|(_, text)| text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        DocumentRoute::Vision => extract_page_texts(path)
+            .map(|pages| pages.join("\n"))
+            .unwrap_or_default(),
+    };
+    detect_language(&text)
+}
+
+/// 日本語以外の書類について読み取り方を指示するプロンプト節（すべて日本語なら空）
+fn language_section(documents: &[(String, DocumentLanguage)]) -> String {
+    let foreign: Vec<&(String, DocumentLanguage)> = documents
+        .iter()
+        .filter(|(_, lang)| *lang != DocumentLanguage::Japanese)
+        .collect();
+    if foreign.is_empty() {
+        return String::new();
+    }
+    let lines = foreign
+        .iter()
+        .map(|(name, lang)| format!("- {}: 主に{}", name, lang.label()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let vietnamese_note = if foreign.iter().any(|(_, lang)| *lang == DocumentLanguage::Vietnamese) {
+        "ベトナム語の声調記号付きの綴りも正確に扱うこと。"
+    } else {
+        ""
+    };
+    format!(
+        "\n\n## 書類の言語\n以下の書類は日本語以外で書かれています。原文の言語のまま正確に読み取り、人名・会社名・数値・日付は原文表記のまま引用してください（必要に応じて日本語訳を併記）。{}回答は必ず日本語で行ってください。\n{}",
+        vietnamese_note, lines
+    )
+}
+
+/// 照合結果に印影照合（切り出した印影の決定的な比較）の結果を追記
+fn append_seal_check(result: String, paths: &[String], file_names: &[String]) -> String {
+    let documents: Vec<(String, Vec<SealImpression>)> = paths
+        .iter()
+        .zip(file_names)
+        .map(|(path, name)| {
+            let seals = extract_seals(path, Some(&seal_dir_for(path))).unwrap_or_default();
+            (name.clone(), seals)
+        })
+        .collect();
+    let findings = compare_seals(&documents);
+    if findings.is_empty() {
+        return result;
+    }
+    format!("{}\n\n## 印影照合（自動判定）\n{}", result.trim_end(), findings.join("\n"))
+}
+
+/// スキャンページだけを抜き出したPDFを一時ディレクトリに作成し、添付名を返す
+fn stage_scanned_pages(temp_dir: &Path, path: &str, file_name: &str, pages: &[u32]) -> Option<String> {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "document".to_string());
+    let temp_name = sanitize_file_name(&format!("{}_scan.pdf", stem));
+    extract_pages_to_pdf(path, pages, &temp_dir.join(&temp_name)).ok()?;
+    Some(temp_name)
+}
+
+/// テキストモードで使うモデル
+fn text_model() -> String {
+    load_settings()
+        .text_model
+        .unwrap_or_else(|| DEFAULT_TEXT_MODEL.to_string())
+}
+
+/// 抽出テキストのプロンプト用セクション（ページ番号は元書類の番号）
+fn format_document_text(pages: &[(u32, String)]) -> String {
+    let mut section = String::from("\n## 書類本文（PDFから抽出したテキスト）\n");
+    for (number, page) in pages {
+        section.push_str(&format!("### {}ページ\n{}\n", number, page.trim()));
+    }
+    section
+}
+
+/// 該当する常備参照資料のテキストをプロンプトに直接含める（テキストモード用）
+///
+/// テキストを抽出できない参照資料はスキップする。
+fn inline_reference_documents(project_folder: &str, doc_types: &[String], targets: &[String]) -> String {
+    let settings = load_project_settings(project_folder);
+    let mut sections = Vec::new();
+    for reference in matching_references(&settings, doc_types, targets) {
+        if let Some(pages) = extract_page_texts(&reference.path)
+            .ok()
+            .filter(|pages| has_enough_text(pages))
+        {
+            let text: String = pages.join("\n").chars().take(MAX_REFERENCE_TEXT_CHARS).collect();
+            sections.push(format!("### {}\n{}", reference.label, text));
+        }
+    }
+
+    if sections.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n## 参照資料\n以下は照合の基準となる常備参照資料です。対象書類がこれらと整合しているか確認してください（参照資料自体への指摘は不要）：\n{}\n",
+            sections.join("\n")
+        )
+    }
+}
+
+/// Result of a single-file analysis
+struct SingleAnalysis {
+    result: String,
+    /// Taken from the result cache without running the AI
+    cached: bool,
+    /// What became of the result (None: not stored again)
+    stored: Option<StoreOutcome>,
 }
 
 /// 単一PDFを解析する内部関数
@@ -29,26 +440,52 @@ fn analyze_single_pdf(
     task_id: &str,
     model: &str,
     custom_instruction: &str,
+    expected: &[ExpectedValue],
+) -> Result<SingleAnalysis, String> {
+    with_usage_project(&project_folder_for(path), || {
+        analyze_single_pdf_with(path, task_id, model, custom_instruction, expected, true)
+    })
+}
+
+/// 結果を保存せずに単一PDFを再解析（鮮度確認・ゴールデンサンプル照合用）
+pub fn reanalyze_without_saving(
+    path: &str,
+    task_id: &str,
+    model: &str,
+    custom_instruction: &str,
 ) -> Result<String, String> {
+    with_usage_project(&project_folder_for(path), || {
+        analyze_single_pdf_with(path, task_id, model, custom_instruction, &[], false)
+    })
+    .map(|analysis| analysis.result)
+}
+
+/// `persist` が false の場合は履歴・索引・結果の保存を行わず、結果キャッシュも使わない
+fn analyze_single_pdf_with(
+    path: &str,
+    task_id: &str,
+    model: &str,
+    custom_instruction: &str,
+    expected: &[ExpectedValue],
+    persist: bool,
+) -> Result<SingleAnalysis, String> {
     let pdf_path = Path::new(path);
     let file_name = pdf_path
         .file_name()
         .map(|s| s.to_string_lossy().to_string())
         .unwrap_or_else(|| "unknown.pdf".to_string());
+    // Content before the result is embedded (result cache key)
+    let file_hash = if persist { file_sha256(pdf_path).ok() } else { None };
 
     // Get project folder (parent directory)
-    let project_folder = pdf_path
-        .parent()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|| ".".to_string());
+    let project_folder = project_folder_for(path);
+    let query = format!("{}\n{}", file_name, query_text_for(path));
 
-    // Load history for this project
-    let history = load_history(&project_folder);
-    let history_context = build_history_context(&history);
-
-    // Load relevant guidelines only (based on file name)
-    let guidelines_section = get_relevant_guidelines(&project_folder, &file_name)
-        .map(|g| format!("\n## 該当ガイドライン\n{}\n", g))
+    // Load relevant guidelines only (based on the document type)
+    let (doc_types, metadata) = document_profile(&project_folder, path, &file_name);
+    let metadata_section = metadata_section(&[(file_name.clone(), metadata.clone())]);
+    let guidelines_section = get_relevant_guidelines_for_types(&project_folder, &doc_types)
+        .map(|g| format_guidelines_section(&g))
         .unwrap_or_default();
 
     // Build custom instruction section
@@ -60,20 +497,98 @@ fn analyze_single_pdf(
             custom_instruction
         )
     };
+    // Ground truth from the ledger is stated as authoritative reference data,
+    // user notes on the files as premises
+    let custom_section = format!(
+        "{}{}{}",
+        custom_section,
+        expected_values_section(expected),
+        notes_section(&project_folder, &[path.to_string()])
+    );
+
+    // テキストモード: 文字を抽出できるページはテキストで渡し、スキャンページだけ画像解析する
+    let route = document_route(path);
+    let language_section = language_section(&[(file_name.clone(), document_language(path, &route))]);
+    let text_model = text_model();
+    let model = if matches!(route, DocumentRoute::Text(_)) {
+        text_model.as_str()
+    } else {
+        model
+    };
 
     // Create temp directory for this task
-    let temp_dir = create_temp_dir(&format!(".shoruichecker_temp_{}", task_id))
+    let temp_dir = create_temp_dir(&format!("{}{}", TEMP_DIR_PREFIX, task_id))
         .map_err(|e| e.to_string())?;
 
-    // Copy PDF to temp directory
-    let dest_path = temp_dir.join(&file_name);
-    fs::copy(path, &dest_path).map_err(|e| format!("ファイルコピーエラー: {}", e))?;
+    let targets = [path.to_string()];
+    let (temp_name, attachments, reference_section, source_line, source_section) = match &route {
+        DocumentRoute::Text(pages) => (
+            file_name.clone(),
+            Vec::new(),
+            inline_reference_documents(&project_folder, &doc_types, &targets),
+            "以下の書類本文（PDFから抽出したテキスト）を読み、整合性をチェックしてください。".to_string(),
+            format_document_text(pages),
+        ),
+        route => {
+            // Hybrid: only the scanned pages are attached (falls back to the whole PDF)
+            let scanned = match route {
+                DocumentRoute::Hybrid {
+                    text_pages,
+                    scanned_pages,
+                } => stage_scanned_pages(&temp_dir, path, &file_name, scanned_pages)
+                    .map(|name| (name, text_pages, scanned_pages)),
+                _ => None,
+            };
+            let (temp_name, source_line, source_section) = match scanned {
+                Some((name, text_pages, scanned_pages)) => (
+                    name,
+                    format!(
+                        "添付のPDF（元書類の {} ページのスキャン画像）と、以下の書類本文（その他のページから抽出したテキスト）を合わせて一つの書類として読み、整合性をチェックしてください。",
+                        scanned_pages
+                            .iter()
+                            .map(|p| p.to_string())
+                            .collect::<Vec<_>>()
+                            .join("、")
+                    ),
+                    format_document_text(text_pages),
+                ),
+                None => {
+                    // Stage PDF for the CLI (linked or copied under a name safe for the CLI)
+                    let temp_name = match stage_into_temp(&temp_dir, path, &file_name) {
+                        Ok(name) => name,
+                        Err(e) => {
+                            cleanup_temp_dir(&temp_dir);
+                            return Err(e.to_string());
+                        }
+                    };
+                    (
+                        temp_name,
+                        "添付のPDF書類の内容を読み取り、整合性をチェックしてください。".to_string(),
+                        String::new(),
+                    )
+                }
+            };
+
+            // Attach standing reference documents for this document type
+            let (reference_names, reference_section) =
+                stage_reference_documents(&temp_dir, &project_folder, &doc_types, &targets);
+            let mut attachments = vec![temp_name.clone()];
+            attachments.extend(reference_names);
+            (temp_name, attachments, reference_section, source_line, source_section)
+        }
+    };
+    // Specification clauses relevant to the document, cited in the findings
+    let reference_section = format!(
+        "{}{}",
+        reference_section,
+        clause_section(&project_folder, &query)
+    );
 
     // Build prompt with history context and custom instruction
-    let prompt = format!(
+    let build_prompt = |history_context: &str| format!(
         r#"あなたは日本語で回答するアシスタントです。必ず日本語で回答してください。
 
-添付のPDF書類の内容を読み取り、整合性をチェックしてください。
+{}{}{}
 
 ## 注意事項
 - 文字は正確に読み取ること（特に地名、人名、会社名）
@@ -95,96 +610,166 @@ fn analyze_single_pdf(
 
 ### 測量図面の場合
 - 縦断図と横断図の計画高・地盤高の照合
-{}
+{}{}
 ## 出力形式
 - まず書類タイプを判定して報告
 - 整合している項目は「✓」で示す
 - 問題がある項目は「⚠」で具体的に指摘
+- 有効期限のある書類（保険証券・資格証など）は「有効期限: YYYY年M月D日」の行で期限を記載
 - 過去の解析履歴がある場合、それとの整合性も確認すること
 {}{}
-ファイル: {}"#,
+ファイル: {}{}"#,
+        source_line,
+        language_section,
+        metadata_section,
         guidelines_section,
+        reference_section,
         custom_section,
         history_context,
-        file_name
+        display_file_name(&file_name, &temp_name),
+        source_section
     );
+    // Local checks applied to the AI output (also to cached results)
+    let finish = |result: String| {
+        let text = document_text(&targets);
+        let result = append_expected_check(result, text.as_deref(), expected);
+        let result = if is_traffic_guard_document(&doc_types) {
+            append_placement_check(result, &targets)
+        } else {
+            result
+        };
+        apply_output_rules(&project_folder, result)
+    };
 
-    let pdfs = vec![file_name.clone()];
-    let output = run_gemini_with_prompt(&temp_dir, &prompt, model, Some(&pdfs));
-    cleanup_temp_dir(&temp_dir);
+    // The history context is not part of the cache key: it grows with every
+    // analysis, including the ones of this file
+    let prompt_key = prompt_hash(model, &build_prompt(""));
+    let (output, cached) = match file_hash.as_deref().and_then(|hash| cached_result(hash, &prompt_key)) {
+        // Stored for this file already: nothing to record again
+        Some(cached) if load_result_data(path).is_some() => {
+            cleanup_temp_dir(&temp_dir);
+            return Ok(SingleAnalysis {
+                result: finish(cached),
+                cached: true,
+                stored: None,
+            });
+        }
+        // Same content under another path: recorded like a new result
+        Some(cached) => {
+            cleanup_temp_dir(&temp_dir);
+            (Ok(cached), true)
+        }
+        None => {
+            // Relevant passages from the project index, falling back to recent history
+            let history_context = build_rag_context(&project_folder, &query, &[path.to_string()])
+                .or_else(|| load_history(&project_folder).ok().map(|h| build_history_context(&h)))
+                .unwrap_or_default();
+            let prompt = build_prompt(&history_context);
 
-    match output {
-        Ok(result) => {
-            // Save to history
-            let entry = create_history_entry(&file_name, path, &result);
-            let mut history = load_history(&project_folder);
-            // Remove old entry for same file if exists
-            history.entries.retain(|e| e.file_name != file_name);
-            history.entries.push(entry);
-            // Keep only last 50 entries
-            if history.entries.len() > 50 {
-                history.entries = history.entries.split_off(history.entries.len() - 50);
+            let request = if attachments.is_empty() {
+                GeminiRequest::text(&prompt, model)
+            } else {
+                GeminiRequest::text_with_files(&prompt, model, &attachments)
             }
-            let _ = save_history(&history);
+            .streamed();
+            let output = run_gemini(&temp_dir, &request).map(|result| with_consensus(&temp_dir, &request, result));
+            cleanup_temp_dir(&temp_dir);
+            (output, false)
+        }
+    };
 
-            // Embed result and custom instruction in PDF metadata (optional, ignore errors)
-            let _ = embed_result_in_pdf_with_instruction(path, &result, custom_instruction);
+    match output {
+        Ok(output) if !persist => Ok(SingleAnalysis {
+            result: finish(output),
+            cached,
+            stored: None,
+        }),
+        Ok(output) => {
+            let result = finish(output.clone());
+
+            // Save to history, with the file hash after embedding for freshness checks;
+            // projects with approval keep the result as a draft there
+            let guideline_ids = extract_guideline_ids(&guidelines_section);
+            let mut entry = create_history_entry(&file_name, path, &result);
+            entry.cli_version = cached_cli_version();
+            let stored = store_or_hold(
+                &project_folder,
+                &mut entry,
+                &result,
+                custom_instruction,
+                &guideline_ids,
+                metadata.as_ref(),
+            );
+            match &stored {
+                StoreOutcome::Written(_) => {
+                    record_written_result(&project_folder, path, &result, &guideline_ids, metadata.as_ref())
+                }
+                StoreOutcome::Held => emit_app_log(&tr("analysis.awaiting_approval", &[&file_name]), "info"),
+                StoreOutcome::Failed(e) => emit_app_log(&tr("analysis.not_stored", &[&file_name, e]), "warn"),
+            }
+            cache_result(&[file_hash, entry.file_hash.clone()], &prompt_key, model, &output);
+            let _ = update_history(&project_folder, |history| record_history_entry(history, entry));
 
-            Ok(result)
+            Ok(SingleAnalysis {
+                result,
+                cached,
+                stored: Some(stored),
+            })
+        }
+        Err(error) => {
+            // A re-sent copy of a file that failed is detected again
+            release_file(Path::new(path));
+            Err(error.to_string())
         }
-        Err(error) => Err(error.to_string()),
     }
 }
 
-/// 複数PDFをまとめて照合解析
-fn analyze_compare_pdfs(paths: &[String], model: &str, custom_instruction: &str) -> Result<String, String> {
-    let temp_dir = create_temp_dir(".shoruichecker_temp_compare")
+/// One comparison call of the CLI over the given PDFs (nothing is stored)
+fn compare_call(
+    paths: &[String],
+    model: &str,
+    custom_instruction: &str,
+    expected: &[ExpectedValue],
+) -> Result<CompareOutput, String> {
+    let temp_dir = create_temp_dir(&format!("{}compare", TEMP_DIR_PREFIX))
         .map_err(|e| e.to_string())?;
 
     // Get project folder from first file
     let project_folder = paths
         .first()
-        .and_then(|p| Path::new(p).parent())
-        .map(|p| p.to_string_lossy().to_string())
+        .map(|p| project_folder_for(p))
         .unwrap_or_else(|| ".".to_string());
 
-    // Load history
-    let history = load_history(&project_folder);
-    let history_context = build_history_context(&history);
+    // Relevant passages from the project index, falling back to recent history
+    let query = paths
+        .iter()
+        .map(|p| query_text_for(p))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let history_context = build_rag_context(&project_folder, &query, paths)
+        .or_else(|| load_history(&project_folder).ok().map(|h| build_history_context(&h)))
+        .unwrap_or_default();
 
     // Load relevant guidelines for all files
     let mut all_types: Vec<String> = Vec::new();
+    let mut metadata: Vec<(String, Option<DocumentMetadata>)> = Vec::new();
     for path in paths {
         let name = Path::new(path)
             .file_name()
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_default();
-        for t in detect_document_type(&name) {
+        let (types, document_metadata) = document_profile(&project_folder, path, &name);
+        for t in types {
             if !all_types.contains(&t) {
                 all_types.push(t);
             }
         }
+        metadata.push((name, document_metadata));
     }
-    let guidelines_section = if let Some(guidelines) = load_guidelines_json(&project_folder) {
-        let mut relevant = Vec::new();
-        if !guidelines.common.is_empty() {
-            relevant.push("【共通】".to_string());
-            relevant.extend(guidelines.common.iter().take(5).cloned());
-        }
-        for doc_type in &all_types {
-            if let Some(items) = guidelines.categories.get(doc_type) {
-                relevant.push(format!("【{}】", doc_type));
-                relevant.extend(items.iter().take(5).cloned());
-            }
-        }
-        if relevant.is_empty() {
-            String::new()
-        } else {
-            format!("\n## 該当ガイドライン\n{}\n", relevant.join("\n"))
-        }
-    } else {
-        String::new()
-    };
+    let metadata_section = metadata_section(&metadata);
+    let guidelines_section = get_relevant_guidelines_for_types(&project_folder, &all_types)
+        .map(|g| format_guidelines_section(&g))
+        .unwrap_or_default();
 
     // Build custom instruction section
     let custom_section = if custom_instruction.is_empty() {
@@ -195,118 +780,652 @@ fn analyze_compare_pdfs(paths: &[String], model: &str, custom_instruction: &str)
             custom_instruction
         )
     };
+    // Ground truth from the ledger is stated as authoritative reference data,
+    // user notes on the files as premises
+    let custom_section = format!(
+        "{}{}{}",
+        custom_section,
+        expected_values_section(expected),
+        notes_section(&project_folder, paths)
+    );
 
-    // Copy all PDFs
-    let mut copied_files: Vec<String> = Vec::new();
+    // Copy all PDFs (under names safe for the CLI)
     let mut file_names: Vec<String> = Vec::new();
+    let mut temp_names: Vec<String> = Vec::new();
     for (i, path) in paths.iter().enumerate() {
         let pdf_path = Path::new(path);
         let file_name = pdf_path
             .file_name()
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_else(|| format!("file_{}.pdf", i));
-        file_names.push(file_name.clone());
 
-        let dest_path = temp_dir.join(&file_name);
-        fs::copy(path, &dest_path).map_err(|e| format!("ファイルコピーエラー: {}", e))?;
-        copied_files.push(dest_path.to_string_lossy().to_string());
+        match stage_into_temp(&temp_dir, path, &file_name) {
+            Ok(temp_name) => temp_names.push(temp_name),
+            Err(e) => {
+                cleanup_temp_dir(&temp_dir);
+                return Err(e.to_string());
+            }
+        }
+        file_names.push(file_name);
     }
 
+    let languages: Vec<(String, DocumentLanguage)> = paths
+        .iter()
+        .zip(&file_names)
+        .map(|(path, name)| (name.clone(), document_language(path, &DocumentRoute::Vision)))
+        .collect();
+    let language_section = language_section(&languages);
+
+    // Attach standing reference documents not already selected
+    let (reference_names, reference_section) =
+        stage_reference_documents(&temp_dir, &project_folder, &all_types, paths);
+    // Specification clauses relevant to the anchor document
+    let clause_query = format!(
+        "{}\n{}",
+        file_names.join("\n"),
+        paths.first().map(|p| query_text_for(p)).unwrap_or_default()
+    );
+    let reference_section = format!(
+        "{}{}",
+        reference_section,
+        clause_section(&project_folder, &clause_query)
+    );
+
     // Build comparison prompt with history and custom instruction
     let prompt = format!(
         r#"あなたは日本語で回答するアシスタントです。必ず日本語で回答してください。
 
 添付の複数PDF書類を照合し、書類間の整合性をチェックしてください。
+最初の書類を基準書類とし、他の各書類の記載が基準書類と一致しているかを照合してください。
 
 ## 照合対象ファイル
-{}
+{}{}{}
 
 ## チェックポイント
+- 基準書類の当事者・金額・日付・数量と、他の各書類の記載が一致しているか
 - 書類間で当事者名（発注者・受注者・会社名）が一致しているか
 - 金額が書類間で整合しているか（見積書と契約書の金額一致等）
 - 日付の整合性（契約日、工期、納期等）
 - 数量・単価の整合性
 - 印影・署名の有無
 - 過去の解析履歴との整合性
-{}
+{}{}
 ## 出力形式
 1. 各書類の概要を簡潔に説明
 2. 書類間で整合している項目は「✓」で示す
 3. 不整合や矛盾がある項目は「⚠」で具体的に指摘
-4. 総合判定（整合/要確認/不整合）
+4. 基準書類と各書類の照合表（| 書類 | 判定 | 指摘 |）
+5. 総合判定（整合/要確認/不整合）
 {}{}"#,
-        file_names.join("\n"),
+        file_names
+            .iter()
+            .zip(&temp_names)
+            .enumerate()
+            .map(|(i, (name, temp_name))| {
+                let line = display_file_name(name, temp_name);
+                if i == 0 {
+                    format!("{}（基準書類）", line)
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        language_section,
+        metadata_section,
         guidelines_section,
+        reference_section,
         custom_section,
         history_context
     );
 
-    let output = run_gemini_with_prompt(&temp_dir, &prompt, model, Some(&file_names));
+    let mut attachments = temp_names;
+    attachments.extend(reference_names);
+    let request = GeminiRequest::text_with_files(&prompt, model, &attachments).streamed();
+    let output = with_usage_project(&project_folder, || run_gemini(&temp_dir, &request));
     cleanup_temp_dir(&temp_dir);
 
-    match output {
-        Ok(result) => {
-            // Save comparison result to history for each file
-            let mut history = load_history(&project_folder);
-            let comparison_summary = format!("【照合解析】対象: {}", file_names.join(", "));
-            for (i, path) in paths.iter().enumerate() {
-                let file_name = &file_names[i];
-                let entry = AnalysisHistoryEntry {
-                    file_name: file_name.clone(),
-                    file_path: path.clone(),
-                    analyzed_at: chrono::Local::now()
-                        .format("%Y-%m-%d %H:%M:%S")
-                        .to_string(),
-                    document_type: Some("照合解析".to_string()),
-                    summary: comparison_summary.clone(),
-                    issues: result
-                        .lines()
-                        .filter(|line| line.contains("⚠"))
-                        .map(|s| s.trim().to_string())
-                        .collect(),
-                };
-                history.entries.retain(|e| e.file_name != *file_name);
-                history.entries.push(entry);
-            }
-            if history.entries.len() > 50 {
-                history.entries = history.entries.split_off(history.entries.len() - 50);
+    Ok(CompareOutput {
+        result: output.map_err(|e| e.to_string())?,
+        guidelines_section,
+    })
+}
+
+/// 複数PDFをまとめて照合解析
+fn analyze_compare_pdfs(
+    paths: &[String],
+    model: &str,
+    custom_instruction: &str,
+    expected: &[ExpectedValue],
+) -> Result<String, String> {
+    let output = compare_call(paths, model, custom_instruction, expected)?;
+    Ok(persist_compare_result(
+        paths,
+        &output.guidelines_section,
+        output.result,
+        custom_instruction,
+        expected,
+    ))
+}
+
+/// Add the local checks to a comparison result and store it for all compared files
+fn persist_compare_result(
+    paths: &[String],
+    guidelines_section: &str,
+    result: String,
+    custom_instruction: &str,
+    expected: &[ExpectedValue],
+) -> String {
+    let project_folder = paths
+        .first()
+        .map(|p| project_folder_for(p))
+        .unwrap_or_else(|| ".".to_string());
+    let file_names: Vec<String> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            Path::new(path)
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| format!("file_{}.pdf", i))
+        })
+        .collect();
+
+    let result = append_seal_check(result, paths, &file_names);
+    let result = append_expected_check(result, document_text(paths).as_deref(), expected);
+    let doc_types: Vec<Vec<String>> = paths
+        .iter()
+        .zip(&file_names)
+        .map(|(path, name)| document_profile(&project_folder, path, name).0)
+        .collect();
+    // 縦断図・横断図の同一測点の高さを数値で照合
+    let survey_drawings: Vec<(String, String)> = paths
+        .iter()
+        .zip(&file_names)
+        .zip(&doc_types)
+        .filter(|(_, types)| types.iter().any(|t| t == "測量図面"))
+        .map(|((path, name), _)| (name.clone(), path.clone()))
+        .collect();
+    let result = append_survey_check(result, &survey_drawings);
+    // 配置実績表と伝票の人数・日付・時間を機械照合
+    let guard_documents: Vec<String> = paths
+        .iter()
+        .zip(&doc_types)
+        .filter(|(_, types)| is_traffic_guard_document(types))
+        .map(|(path, _)| path.clone())
+        .collect();
+    let result = if guard_documents.is_empty() {
+        result
+    } else {
+        append_placement_check(result, &guard_documents)
+    };
+    let result = apply_output_rules(&project_folder, result);
+    let guideline_ids = extract_guideline_ids(guidelines_section);
+
+    // Store comparison result and instruction for all related PDFs (or keep
+    // it as a draft in their history) and save it to history for each file
+    let comparison_summary = format!("【照合解析】対象: {}", file_names.join(", "));
+    let mut entries = Vec::new();
+    let mut written = false;
+    for (i, path) in paths.iter().enumerate() {
+        let file_name = &file_names[i];
+        let mut entry = AnalysisHistoryEntry {
+            id: new_entry_id(),
+            file_name: file_name.clone(),
+            file_path: path.clone(),
+            analyzed_at: chrono::Local::now()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            document_type: Some("照合解析".to_string()),
+            summary: comparison_summary.clone(),
+            issues: result
+                .lines()
+                .filter(|line| line.contains("⚠"))
+                .map(|s| s.trim().to_string())
+                .collect(),
+            resolved_issues: Vec::new(),
+            cli_version: cached_cli_version(),
+            file_hash: None,
+            reviewed_at: None,
+            mail: None,
+            pending: None,
+            approved_at: None,
+            store_error: None,
+        };
+        match store_or_hold(&project_folder, &mut entry, &result, custom_instruction, &guideline_ids, None) {
+            StoreOutcome::Written(_) => {
+                index_analyzed_document(&project_folder, path, &result);
+                written = true;
             }
-            let _ = save_history(&history);
+            StoreOutcome::Held => {}
+            StoreOutcome::Failed(e) => emit_app_log(&tr("analysis.not_stored", &[file_name, &e]), "warn"),
+        }
+        entries.push(entry);
+    }
+    // Counted once per comparison, however many documents it was written to
+    if written {
+        record_guideline_usage(&project_folder, &guideline_ids, &result);
+    }
+    let _ = update_history(&project_folder, |history| {
+        for entry in entries {
+            record_history_entry(history, entry);
+        }
+    });
 
-            // Embed comparison result and instruction in all related PDFs
-            for path in paths {
-                let _ = embed_result_in_pdf_with_instruction(path, &result, custom_instruction);
+    result
+}
+
+/// Index of the anchor document: the one whose type ranks best in
+/// `priority` (the first document when no type is listed)
+pub fn select_anchor(doc_types: &[Vec<String>], priority: &[String]) -> usize {
+    doc_types
+        .iter()
+        .enumerate()
+        .filter_map(|(i, types)| {
+            types
+                .iter()
+                .filter_map(|t| priority.iter().position(|p| p == t))
+                .min()
+                .map(|rank| (rank, i))
+        })
+        .min()
+        .map(|(_, i)| i)
+        .unwrap_or(0)
+}
+
+/// Move the anchor document of a comparison set to the front
+fn order_by_anchor(mut paths: Vec<String>) -> Vec<String> {
+    let Some(first) = paths.first() else {
+        return paths;
+    };
+    let project_folder = project_folder_for(first);
+    let priority = anchor_priority(&load_project_settings(&project_folder));
+    let doc_types: Vec<Vec<String>> = paths
+        .iter()
+        .map(|path| {
+            let name = Path::new(path)
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            document_profile(&project_folder, path, &name).0
+        })
+        .collect();
+    let anchor = paths.remove(select_anchor(&doc_types, &priority));
+    paths.insert(0, anchor);
+    paths
+}
+
+/// Split a comparison set that is too large for one call
+///
+/// The first document is the anchor: every chunk compares it with up to
+/// `max_files - 1` of the others. Sets within the limit stay one chunk.
+pub fn partition_compare_set(paths: &[String], max_files: usize) -> Vec<Vec<String>> {
+    if paths.len() <= max_files || max_files < 2 {
+        return vec![paths.to_vec()];
+    }
+    let (anchor, others) = paths.split_first().expect("non-empty set");
+    others
+        .chunks(max_files - 1)
+        .map(|chunk| std::iter::once(anchor.clone()).chain(chunk.iter().cloned()).collect())
+        .collect()
+}
+
+/// Verdict of the documents of a comparison group whose call failed
+const FAILED_GROUP_VERDICT: &str = "解析失敗";
+
+/// The more severe of two verdicts (`-`: none)
+fn worse_verdict<'a>(a: &'a str, b: &'a str) -> &'a str {
+    let rank = |v: &str| {
+        ["-", "整合", "要確認", "不整合", FAILED_GROUP_VERDICT]
+            .iter()
+            .position(|r| *r == v)
+            .unwrap_or(0)
+    };
+    if rank(b) > rank(a) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Matrix of the compared documents from the chunk results, one row per document
+///
+/// The anchor comes first with the most severe verdict of its groups. The
+/// other documents take their row of the group's comparison table when the
+/// model wrote one, otherwise the group verdict; documents of failed groups
+/// are marked as such.
+pub fn compare_matrix(chunks: &[Vec<String>], results: &[Result<String, String>]) -> CompareMatrix {
+    let name_of = |path: &str| {
+        Path::new(path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string())
+    };
+    let findings_about = |result: &str, name: &str| -> Vec<String> {
+        let stem = Path::new(name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string());
+        result
+            .lines()
+            .filter(|l| l.contains("⚠") && l.contains(&stem))
+            .map(|l| l.trim().to_string())
+            .collect()
+    };
+    let anchor = chunks
+        .first()
+        .and_then(|c| c.first())
+        .map(|p| name_of(p))
+        .unwrap_or_default();
+    let mut anchor_row = CompareMatrixRow {
+        document: anchor.clone(),
+        group: 0,
+        verdict: "-".to_string(),
+        findings: Vec::new(),
+    };
+    let mut rows = Vec::new();
+    for (i, (chunk, result)) in chunks.iter().zip(results).enumerate() {
+        let result = match result {
+            Ok(result) => result,
+            Err(_) => {
+                anchor_row.verdict = worse_verdict(&anchor_row.verdict, "要確認").to_string();
+                for path in chunk.iter().skip(1) {
+                    rows.push(CompareMatrixRow {
+                        document: name_of(path),
+                        group: i + 1,
+                        verdict: FAILED_GROUP_VERDICT.to_string(),
+                        findings: Vec::new(),
+                    });
+                }
+                continue;
+            }
+        };
+        let verdict = result_verdict(result).unwrap_or("-");
+        anchor_row.verdict = worse_verdict(&anchor_row.verdict, verdict).to_string();
+        for finding in findings_about(result, &anchor) {
+            if !anchor_row.findings.contains(&finding) {
+                anchor_row.findings.push(finding);
             }
+        }
+        let table = parse_compare_table(result);
+        for path in chunk.iter().skip(1) {
+            let name = name_of(path);
+            let row = match table.iter().find(|r| r.document == name) {
+                Some(row) => CompareMatrixRow { group: i + 1, ..row.clone() },
+                None => CompareMatrixRow {
+                    findings: findings_about(result, &name),
+                    document: name,
+                    group: i + 1,
+                    verdict: verdict.to_string(),
+                },
+            };
+            rows.push(row);
+        }
+    }
+    rows.insert(0, anchor_row);
+    CompareMatrix { anchor, rows }
+}
+
+/// Combined narrative of the chunk results (text model, no attachments)
+fn synthesize_compare_results(anchor: &str, sections: &str, model: &str) -> Result<String, String> {
+    let prompt = format!(
+        r#"あなたは日本語で回答するアシスタントです。必ず日本語で回答してください。
+
+書類が多いため、基準書類「{}」と他の書類をグループに分けて照合しました。
+以下の各グループの照合結果を統合し、書類全体の照合結果としてまとめてください。
+
+## 出力形式
+1. 書類全体で整合している項目は「✓」で示す
+2. 不整合や矛盾がある項目は「⚠」で、該当する書類名とともに具体的に指摘（重複はまとめる）
+3. グループをまたいだ矛盾（書類Bと書類Cの金額が異なる等）があれば指摘
+4. 解析に失敗したグループの書類は照合できていないことを「⚠」で明記
+5. 総合判定（整合/要確認/不整合）
+
+{}"#,
+        anchor, sections
+    );
+    let request = GeminiRequest::text(&prompt, model);
+    run_gemini_in_temp(&format!("{}compare_merge", TEMP_DIR_PREFIX), &request).map_err(|e| e.to_string())
+}
+
+/// Merge chunked comparison results and store them for all files
+///
+/// Failed groups stay in the matrix and the narrative as failed. The
+/// synthesis call waits for an analysis slot like the group calls and falls
+/// back to the chunk results as they are when it fails.
+fn finish_chunked_compare(
+    app: &AppHandle,
+    aborted: &AtomicBool,
+    paths: &[String],
+    chunks: &[Vec<String>],
+    outputs: Vec<Result<CompareOutput, String>>,
+    custom_instruction: &str,
+    expected: &[ExpectedValue],
+) -> Result<String, String> {
+    let results: Vec<Result<String, String>> = outputs
+        .iter()
+        .map(|o| o.as_ref().map(|o| o.result.clone()).map_err(String::clone))
+        .collect();
+    let matrix = compare_matrix(chunks, &results).to_markdown();
+    let anchor = paths
+        .first()
+        .and_then(|p| Path::new(p).file_name())
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let sections = results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| match r {
+            Ok(result) => format!("### 照合グループ {}\n{}", i + 1, result),
+            Err(e) => format!("### 照合グループ {}\n⚠ 解析に失敗しました: {}", i + 1, e),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let project_folder = paths.first().map(|p| project_folder_for(p)).unwrap_or_default();
+    let narrative = with_analysis_slot(app, "照合結果の統合", aborted, || {
+        with_usage_project(&project_folder, || {
+            synthesize_compare_results(&anchor, &sections, &text_model())
+        })
+    })
+    .ok_or_else(aborted_message)?
+    .unwrap_or_else(|_| sections.replace("### 照合グループ", "## 照合グループ"));
 
-            Ok(result)
+    let mut guidelines_sections: Vec<&str> = Vec::new();
+    for output in outputs.iter().flatten() {
+        if !guidelines_sections.contains(&output.guidelines_section.as_str()) {
+            guidelines_sections.push(&output.guidelines_section);
         }
-        Err(error) => Err(error.to_string()),
     }
+    Ok(persist_compare_result(
+        paths,
+        &guidelines_sections.join("\n"),
+        format!("{}\n{}", matrix, narrative),
+        custom_instruction,
+        expected,
+    ))
 }
 
 /// PDFを解析 (Gemini CLI使用)
+///
+/// 変更のないPDFは結果キャッシュから返す（`force` で解析し直す）
 #[tauri::command]
 pub async fn analyze_pdfs(
     app: AppHandle,
     paths: Vec<String>,
     mode: String,
     custom_instruction: Option<String>,
+    preset: Option<String>,
+    expected_values: Option<ExpectedValuesSource>,
+    force: Option<bool>,
 ) -> Result<String, String> {
     if paths.is_empty() {
-        return Err("ファイルが指定されていません".to_string());
+        return Err(tr("analysis.no_files", &[]));
+    }
+    if is_shutting_down() {
+        return Err(tr("shutdown.in_progress", &[]));
+    }
+    // Re-analysis on request: cached results of these files are dropped
+    if force.unwrap_or(false) {
+        forget_cached_results(&paths);
+    }
+    let expected = resolve_expected_values(expected_values)?;
+
+    let (mode, model, custom) =
+        resolve_preset(preset.as_deref(), mode, &custom_instruction.unwrap_or_default())?;
+
+    // Journal the job so it can be resumed if the app dies mid-analysis
+    let job_id = begin_job(&paths, &mode, &custom);
+    let batch = begin_batch(&job_id, &mode, paths.len());
+
+    let hooks = configured_hooks();
+    let mut payload = HookPayload {
+        stage: HookStage::Pre,
+        batch_id: job_id.clone(),
+        mode: mode.clone(),
+        paths: paths.clone(),
+        custom_instruction: custom.clone(),
+        outcome: None,
+    };
+    if let Err(e) = run_hooks(&app, &hooks, payload.clone()).await {
+        finish_job(&job_id);
+        let message = tr("hook.cancelled", &[&e]);
+        emit_log(&app, &message, "error");
+        return Err(message);
     }
 
+    let _ = app.emit(
+        "batch-started",
+        serde_json::json!({ "batch_id": job_id, "total": paths.len(), "mode": mode }),
+    );
+    let result = run_analysis(&app, &batch, paths, &mode, &model, &custom, &expected).await;
+    finish_job(&job_id);
+
+    payload.stage = HookStage::Post;
+    payload.outcome = Some(HookOutcome {
+        success: result.is_ok(),
+        result: result.clone().ok(),
+        error: result.clone().err(),
+        reports: Vec::new(),
+    });
+    let _ = run_hooks(&app, &hooks, payload).await;
+    result
+}
+
+/// Compare a large set in anchor-based groups, run as separate tasks, then merge
+async fn run_chunked_compare(
+    app: &AppHandle,
+    batch: &BatchGuard,
+    paths: Vec<String>,
+    model: &str,
+    custom: &str,
+    expected: &[ExpectedValue],
+) -> Result<String, String> {
+    let chunks = partition_compare_set(&paths, MAX_COMPARE_FILES_PER_CALL);
+    emit_log(app, &tr("analysis.compare_chunked", &[&chunks.len()]), "info");
+
+    let mut handles = vec![];
+    for (i, chunk) in chunks.iter().enumerate() {
+        let (chunk, model, custom) = (chunk.clone(), model.to_string(), custom.to_string());
+        let expected = expected.to_vec();
+        let batch_id = batch.id().to_string();
+        let aborted = batch.abort_flag();
+        let label = format!("照合解析 グループ {}/{}", i + 1, chunks.len());
+        let (app, group) = (app.clone(), label.clone());
+        handles.push(spawn_blocking("analysis", &label, move || {
+            if aborted.load(Ordering::SeqCst) {
+                return Err(aborted_message());
+            }
+            with_analysis_slot(&app, &group, &aborted, || {
+                with_child_scope(&batch_id, || {
+                    with_output_stream(&batch_id, &group, || compare_call(&chunk, &model, &custom, &expected))
+                })
+            })
+            .unwrap_or_else(|| Err(aborted_message()))
+        }));
+    }
+
+    // A failed group does not discard the others; it is marked in the result
+    let mut outputs = Vec::new();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let output = handle
+            .await
+            .map_err(|e| format!("バックグラウンド処理エラー: {}", e))
+            .and_then(|r| r);
+        if let Err(e) = &output {
+            emit_log(app, &tr("analysis.compare_group_failed", &[&(i + 1), e]), "warn");
+        }
+        outputs.push(output);
+    }
+    let aborted = batch.abort_flag();
+    if aborted.load(Ordering::SeqCst) {
+        return Err(aborted_message());
+    }
+    if outputs.iter().all(Result::is_err) {
+        return Err(outputs.into_iter().find_map(Result::err).unwrap_or_else(aborted_message));
+    }
+    let results: Vec<Result<String, String>> = outputs
+        .iter()
+        .map(|o| o.as_ref().map(|o| o.result.clone()).map_err(String::clone))
+        .collect();
+    emit_compare_matrix(app, &compare_matrix(&chunks, &results));
+
+    emit_log(app, &tr("analysis.compare_merging", &[]), "wave");
+    let (custom, expected) = (custom.to_string(), expected.to_vec());
+    let batch_id = batch.id().to_string();
+    let app = app.clone();
+    run_blocking("analysis", "照合結果の統合", move || {
+        with_child_scope(&batch_id, || {
+            finish_chunked_compare(&app, &aborted, &paths, &chunks, outputs, &custom, &expected)
+        })
+    })
+    .await
+    .and_then(|r| r)
+}
+
+/// Run the hooks of a stage in the background, logging failed optional hooks
+///
+/// Post payloads get the files' reports from history here, after the
+/// analysis has recorded them; drafts waiting for approval are left out
+/// (their Post hooks run from `approve_result`).
+async fn run_hooks(app: &AppHandle, hooks: &[AnalysisHook], mut payload: HookPayload) -> Result<(), String> {
+    if !hooks.iter().any(|h| h.stage == payload.stage) {
+        return Ok(());
+    }
+    let hooks = hooks.to_vec();
+    let warnings = run_blocking("hook", "hooks", move || {
+        if let Some(outcome) = payload.outcome.as_mut() {
+            payload.paths = released_paths(&payload.paths);
+            if payload.paths.is_empty() {
+                return Ok(Vec::new());
+            }
+            outcome.reports = latest_reports(&payload.paths);
+        }
+        run_stage_hooks(&hooks, &payload)
+    })
+    .await
+    .and_then(|r| r)?;
+    for warning in warnings {
+        emit_log(app, &tr("hook.warning", &[&warning]), "warn");
+    }
+    Ok(())
+}
+
+async fn run_analysis(
+    app: &AppHandle,
+    batch: &BatchGuard,
+    paths: Vec<String>,
+    mode: &str,
+    model: &str,
+    custom: &str,
+    expected: &[ExpectedValue],
+) -> Result<String, String> {
     let total = paths.len();
-    let model = load_settings()
-        .model
-        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
-    let custom = custom_instruction.unwrap_or_default();
 
     // 照合モード
     if mode == "compare" {
         emit_log(
-            &app,
-            &format!("=== PDF照合解析開始 ({} ファイル) ===", total),
+            app,
+            &tr("analysis.compare_start", &[&total]),
             "info",
         );
         for path in &paths {
@@ -314,24 +1433,68 @@ pub async fn analyze_pdfs(
                 .file_name()
                 .map(|s| s.to_string_lossy().to_string())
                 .unwrap_or_else(|| "unknown.pdf".to_string());
-            emit_log(&app, &format!("  - {}", file_name), "info");
+            emit_log(app, &format!("  - {}", file_name), "info");
         }
         if !custom.is_empty() {
             emit_log(
-                &app,
-                &format!("カスタム指示: {}", custom.lines().next().unwrap_or("")),
+                app,
+                &tr("analysis.custom_instruction", &[&custom.lines().next().unwrap_or("")]),
                 "info",
             );
         }
-        emit_log(&app, &format!("{} で照合中...", model), "wave");
+        emit_log(app, &tr("analysis.comparing", &[&model]), "wave");
+
+        // The contract etc. is the reference the other documents are checked against
+        // Classifying the documents may ask the AI, so it waits for a slot too
+        let (app_clone, aborted) = (app.clone(), batch.abort_flag());
+        let paths = run_blocking("analysis", "基準書類の判定", move || {
+            with_analysis_slot(&app_clone, "基準書類の判定", &aborted, || order_by_anchor(paths))
+                .ok_or_else(aborted_message)
+        })
+        .await
+        .and_then(|r| r)
+        .map_err(|e| batch.error_for(e))?;
+        let anchor = Path::new(&paths[0])
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        emit_log(app, &tr("analysis.compare_anchor", &[&anchor]), "info");
 
-        match analyze_compare_pdfs(&paths, &model, &custom) {
+        let result = if total > MAX_COMPARE_FILES_PER_CALL {
+            run_chunked_compare(app, batch, paths, model, custom, expected).await
+        } else {
+            let (anchor, stream_name) = (anchor.clone(), anchor.clone());
+            let (model, custom, expected) = (model.to_string(), custom.to_string(), expected.to_vec());
+            let label = format!("照合解析 ({} ファイル)", total);
+            let batch_id = batch.id().to_string();
+            let (app_clone, aborted) = (app.clone(), batch.abort_flag());
+            run_blocking("analysis", &label, move || {
+                with_analysis_slot(&app_clone, &stream_name, &aborted, || {
+                    with_child_scope(&batch_id, || {
+                        with_output_stream(&batch_id, &stream_name, || {
+                            analyze_compare_pdfs(&paths, &model, &custom, &expected)
+                        })
+                    })
+                })
+                .unwrap_or_else(|| Err(aborted_message()))
+            })
+            .await
+            .and_then(|r| r)
+            .inspect(|result| {
+                let rows = parse_compare_table(result);
+                if !rows.is_empty() {
+                    emit_compare_matrix(app, &CompareMatrix { anchor, rows });
+                }
+            })
+        }
+        .map_err(|e| batch.error_for(e));
+        match result {
             Ok(result) => {
-                emit_log(&app, "✓ 照合完了", "success");
+                emit_log(app, &tr("analysis.compare_done", &[]), "success");
                 Ok(result)
             }
             Err(e) => {
-                emit_log(&app, &format!("照合エラー: {}", e), "error");
+                emit_log(app, &tr("analysis.compare_error", &[&e]), "error");
                 Err(e)
             }
         }
@@ -339,14 +1502,14 @@ pub async fn analyze_pdfs(
     // 個別モード
     else {
         emit_log(
-            &app,
-            &format!("=== PDF個別解析開始 ({} ファイル) ===", total),
+            app,
+            &tr("analysis.single_start", &[&total]),
             "info",
         );
         if !custom.is_empty() {
             emit_log(
-                &app,
-                &format!("カスタム指示: {}", custom.lines().next().unwrap_or("")),
+                app,
+                &tr("analysis.custom_instruction", &[&custom.lines().next().unwrap_or("")]),
                 "info",
             );
         }
@@ -358,30 +1521,57 @@ pub async fn analyze_pdfs(
                 .map(|s| s.to_string_lossy().to_string())
                 .unwrap_or_else(|| "unknown.pdf".to_string());
 
-            emit_log(&app, &format!("{} を解析中...", file_name), "wave");
+            emit_log(app, &tr("analysis.analyzing", &[&file_name]), "wave");
 
-            match analyze_single_pdf(path, "single", &model, &custom) {
-                Ok(result) => {
-                    emit_log(&app, "✓ 解析完了", "success");
+            let (path, model, custom) = (path.clone(), model.to_string(), custom.to_string());
+            let report_path = path.clone();
+            let expected = expected.to_vec();
+            let batch_id = batch.id().to_string();
+            let (app_clone, name, aborted) = (app.clone(), file_name.clone(), batch.abort_flag());
+            let result = run_blocking("analysis", &file_name, move || {
+                with_analysis_slot(&app_clone, &name, &aborted, || {
+                    with_child_scope(&batch_id, || {
+                        with_output_stream(&batch_id, &name, || {
+                            analyze_single_pdf(&path, "single", &model, &custom, &expected)
+                        })
+                    })
+                })
+                .unwrap_or_else(|| Err(aborted_message()))
+            })
+            .await
+            .and_then(|r| r)
+            .map_err(|e| batch.error_for(e));
+            match result {
+                Ok(SingleAnalysis { result, cached, .. }) => {
+                    if cached {
+                        emit_log(app, &tr("analysis.cached", &[&file_name]), "info");
+                    }
+                    emit_analysis_report(app, &report_path, &result);
+                    emit_log(app, &tr("analysis.done", &[]), "success");
                     Ok(result)
                 }
                 Err(e) => {
-                    emit_log(&app, &format!("解析エラー: {}", e), "error");
+                    emit_log(app, &tr("analysis.error", &[&e]), "error");
                     Err(e)
                 }
             }
         } else {
             emit_log(
-                &app,
-                &format!("{} で {} ファイルを並列解析中...", model, total),
+                app,
+                &tr("analysis.parallel", &[&model, &total]),
                 "wave",
             );
+            let limit = max_parallel_analyses();
+            if total > limit {
+                emit_log(app, &tr("analysis.queued", &[&limit, &(total - limit)]), "info");
+            }
 
             let mut handles = vec![];
 
             for (i, path) in paths.into_iter().enumerate() {
-                let model_clone = model.clone();
-                let custom_clone = custom.clone();
+                let model_clone = model.to_string();
+                let custom_clone = custom.to_string();
+                let expected_clone = expected.to_vec();
                 let task_id = format!("task_{}", i);
                 let app_clone = app.clone();
                 let file_name = Path::new(&path)
@@ -389,14 +1579,50 @@ pub async fn analyze_pdfs(
                     .map(|s| s.to_string_lossy().to_string())
                     .unwrap_or_else(|| format!("file_{}.pdf", i));
 
-                let handle = thread::spawn(move || {
-                    let result = analyze_single_pdf(&path, &task_id, &model_clone, &custom_clone);
+                let label = file_name.clone();
+                let batch_id = batch.id().to_string();
+                let aborted = batch.abort_flag();
+                let completed = batch.completed_counter();
+                let handle = spawn_blocking("analysis", &label, move || {
+                    // Files not yet started when the batch is aborted are skipped
+                    let result = if aborted.load(Ordering::SeqCst) {
+                        None
+                    } else {
+                        with_analysis_slot(&app_clone, &file_name, &aborted, || {
+                            with_child_scope(&batch_id, || {
+                                with_output_stream(&batch_id, &file_name, || {
+                                    analyze_single_pdf(&path, &task_id, &model_clone, &custom_clone, &expected_clone)
+                                })
+                            })
+                        })
+                    };
+                    let Some(result) = result else {
+                        return AnalysisResult {
+                            file_name,
+                            path,
+                            result: None,
+                            error: Some(aborted_message()),
+                            skipped: true,
+                        };
+                    };
+                    let cached = matches!(&result, Ok(analysis) if analysis.cached);
+                    let result = result
+                        .map(|analysis| analysis.result)
+                        .map_err(|e| abort_error(&aborted, e));
+                    completed.fetch_add(1, Ordering::SeqCst);
+                    if let Ok(result) = &result {
+                        if cached {
+                            emit_log(&app_clone, &tr("analysis.cached", &[&file_name]), "info");
+                        }
+                        emit_analysis_report(&app_clone, &path, result);
+                    }
                     let _ = app_clone.emit(
                         "analysis-progress",
                         serde_json::json!({
                             "file_name": file_name.clone(),
                             "completed": true,
-                            "success": result.is_ok()
+                            "success": result.is_ok(),
+                            "cached": cached
                         }),
                     );
                     AnalysisResult {
@@ -404,56 +1630,167 @@ pub async fn analyze_pdfs(
                         path,
                         result: result.clone().ok(),
                         error: result.err(),
+                        skipped: false,
                     }
                 });
-                handles.push(handle);
+                handles.push((label, handle));
             }
 
-            // Collect results
+            // Collect results (each file on its own: a failed task does not lose the others)
             let mut results: Vec<AnalysisResult> = vec![];
-            for handle in handles {
-                if let Ok(result) = handle.join() {
-                    results.push(result);
+            for (file_name, handle) in handles {
+                match handle.await {
+                    Ok(result) => results.push(result),
+                    Err(e) => results.push(AnalysisResult {
+                        file_name,
+                        path: String::new(),
+                        result: None,
+                        error: Some(format!("バックグラウンド処理エラー: {}", e)),
+                        skipped: false,
+                    }),
                 }
             }
 
             // Format combined results
             let mut output = String::new();
             let success_count = results.iter().filter(|r| r.result.is_some()).count();
+            let skipped: Vec<String> = results
+                .iter()
+                .filter(|r| r.skipped)
+                .map(|r| r.file_name.clone())
+                .collect();
 
             for r in &results {
                 output.push_str(&format!("\n## 📄 {}\n", r.file_name));
                 output.push_str("---\n");
                 if let Some(ref res) = r.result {
                     output.push_str(res);
+                } else if r.skipped {
+                    output.push_str("⏹ 中断のため未解析");
                 } else if let Some(ref err) = r.error {
                     output.push_str(&format!("⚠ エラー: {}", err));
                 }
                 output.push_str("\n\n");
             }
 
-            emit_log(
-                &app,
-                &format!("✓ 解析完了 ({}/{})", success_count, total),
-                "success",
+            let _ = app.emit(
+                "batch-summary",
+                BatchSummary {
+                    batch_id: batch.id().to_string(),
+                    total,
+                    succeeded: success_count,
+                    failed: total - success_count - skipped.len(),
+                    skipped: skipped.clone(),
+                    aborted: batch.is_aborted(),
+                },
             );
+            if batch.is_aborted() {
+                emit_log(
+                    app,
+                    &tr("analysis.aborted_summary", &[&success_count, &total, &skipped.len()]),
+                    "warn",
+                );
+            } else {
+                emit_log(
+                    app,
+                    &tr("analysis.done_count", &[&success_count, &total]),
+                    "success",
+                );
+            }
             Ok(output)
         }
     }
 }
 
-/// ヘッドレスモード: GUIなしでPDFを解析
-pub fn analyze_headless(path: &str) -> Result<(), String> {
-    let model = load_settings()
+/// プリセット（ID または名前）を適用し、モード・モデル・指示を決定
+pub(crate) fn resolve_preset(
+    preset: Option<&str>,
+    mode: String,
+    custom: &str,
+) -> Result<(String, String, String), String> {
+    let settings = load_settings();
+    let default_model = settings
         .model
+        .clone()
         .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let Some(key) = preset.filter(|k| !k.is_empty()) else {
+        return Ok((mode, default_model, custom.to_string()));
+    };
+    let preset = find_preset(&settings.presets, key)
+        .ok_or_else(|| tr("preset.not_found", &[&key]))?;
+    Ok((
+        preset.mode.clone(),
+        preset.model.clone().unwrap_or(default_model),
+        preset.instruction_with(custom),
+    ))
+}
+
+/// ヘッドレスモード: GUIなしでPDFを解析（preset はプリセットのIDまたは名前、
+/// expected_csv は基準値のCSV）
+pub fn analyze_headless(path: &str, preset: Option<&str>, expected_csv: Option<&str>) -> Result<(), String> {
+    let (_, model, custom) = resolve_preset(preset, "single".to_string(), "")?;
+    let expected = resolve_expected_values(expected_csv.map(|p| ExpectedValuesSource::Csv {
+        csv_path: p.to_string(),
+    }))?;
 
     println!("解析中: {}", path);
 
-    match analyze_single_pdf(path, "headless", &model, "") {
+    let job_id = begin_job(&[path.to_string()], "single", &custom);
+    let hooks = configured_hooks();
+    let mut payload = HookPayload {
+        stage: HookStage::Pre,
+        batch_id: job_id.clone(),
+        mode: "single".to_string(),
+        paths: vec![path.to_string()],
+        custom_instruction: custom.clone(),
+        outcome: None,
+    };
+    match run_stage_hooks(&hooks, &payload) {
+        Ok(warnings) => warnings.iter().for_each(|w| eprintln!("⚠ {}", w)),
+        Err(e) => {
+            finish_job(&job_id);
+            let message = tr("hook.cancelled", &[&e]);
+            eprintln!("{}", message);
+            return Err(message);
+        }
+    }
+    let analysis = analyze_single_pdf(path, "headless", &model, &custom, &expected);
+    finish_job(&job_id);
+    let (result, stored) = match analysis {
+        Ok(analysis) => (Ok(analysis.result), analysis.stored),
+        Err(e) => (Err(e), None),
+    };
+
+    // A draft waiting for approval gets its Post hooks from `approve_result`;
+    // a result that could not be written gets none
+    payload.stage = HookStage::Post;
+    payload.paths = released_paths(&payload.paths);
+    payload.outcome = Some(HookOutcome {
+        success: result.is_ok(),
+        result: result.clone().ok(),
+        error: result.clone().err(),
+        reports: latest_reports(&payload.paths),
+    });
+    let warnings = if payload.paths.is_empty() {
+        Vec::new()
+    } else {
+        run_stage_hooks(&hooks, &payload).unwrap_or_default()
+    };
+    warnings.iter().for_each(|w| eprintln!("⚠ {}", w));
+
+    match result {
         Ok(result) => {
             println!("\n{}", result);
-            println!("\n✓ 結果をPDFに埋め込みました");
+            match stored {
+                Some(StoreOutcome::Written(ResultStorage::Embed)) => println!("\n✓ 結果をPDFに埋め込みました"),
+                Some(StoreOutcome::Written(ResultStorage::Sidecar)) => println!("\n✓ 結果をサイドカーに保存しました"),
+                Some(StoreOutcome::Written(ResultStorage::Both)) => {
+                    println!("\n✓ 結果をPDFに埋め込み、サイドカーにも保存しました")
+                }
+                Some(StoreOutcome::Held) => println!("\n結果は承認待ちです（承認するまでPDFには書き込みません）"),
+                Some(StoreOutcome::Failed(e)) => eprintln!("\n⚠ 結果を書き込めませんでした（履歴にのみ残ります）: {}", e),
+                None => println!("\n✓ 前回から変更がないため、保存済みの結果を使用しました"),
+            }
             Ok(())
         }
         Err(e) => {
@@ -462,3 +1799,100 @@ pub fn analyze_headless(path: &str) -> Result<(), String> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("/p/書類{}.pdf", i)).collect()
+    }
+
+    #[test]
+    fn analyses_beyond_the_limit_wait_in_line() {
+        let first = acquire_slot(1, &|| false, &mut |_| panic!("first file must not wait"))
+            .expect("free slot");
+        let (tx, rx) = std::sync::mpsc::channel();
+        let waiting = std::thread::spawn(move || {
+            acquire_slot(1, &|| false, &mut |p| tx.send(p).unwrap()).is_some()
+        });
+        assert_eq!(rx.recv().unwrap(), 1);
+
+        // An aborted batch leaves the queue without running
+        let mut positions = Vec::new();
+        let deadline = std::time::Instant::now() + Duration::from_millis(300);
+        let cancelled = acquire_slot(1, &|| std::time::Instant::now() > deadline, &mut |p| positions.push(p));
+        assert!(cancelled.is_none());
+        assert_eq!(positions, vec![2]);
+
+        drop(first);
+        assert!(waiting.join().unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn guideline_instruction_cites_no_real_item_id() {
+        // An example ID could be copied into the findings and counted as a hit
+        let section = format_guidelines_section("- [G12ab34] 収入印紙の有無");
+        assert_eq!(extract_guideline_ids(&section), vec!["G12ab34"]);
+    }
+
+    #[test]
+    fn large_compare_sets_are_split_around_the_anchor() {
+        assert_eq!(partition_compare_set(&paths(5), 5), vec![paths(5)]);
+
+        let chunks = partition_compare_set(&paths(15), 5);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|c| c[0] == "/p/書類0.pdf" && c.len() <= 5));
+        let others: Vec<&String> = chunks.iter().flat_map(|c| c.iter().skip(1)).collect();
+        assert_eq!(others.len(), 14);
+    }
+
+    #[test]
+    fn anchor_is_the_best_ranked_type() {
+        let priority: Vec<String> = ["契約書", "見積書"].iter().map(|s| s.to_string()).collect();
+        let types = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let set = vec![types(&["請求書"]), types(&["見積書"]), types(&["契約書"]), types(&["請求書"])];
+        assert_eq!(select_anchor(&set, &priority), 2);
+        let set = vec![types(&["請求書"]), types(&["見積書"]), types(&["見積書"])];
+        assert_eq!(select_anchor(&set, &priority), 1);
+        let set = vec![types(&["請求書"]), types(&[])];
+        assert_eq!(select_anchor(&set, &priority), 0);
+    }
+
+    #[test]
+    fn compare_matrix_lists_documents_with_group_verdicts() {
+        let chunks = vec![
+            vec!["/p/契約書.pdf".to_string(), "/p/見積書.pdf".to_string()],
+            vec!["/p/契約書.pdf".to_string(), "/p/請求書.pdf".to_string()],
+            vec!["/p/契約書.pdf".to_string(), "/p/納品書.pdf".to_string()],
+        ];
+        let results = vec![
+            Ok("✓ 金額一致\n総合判定: 整合".to_string()),
+            Ok("⚠ 請求書の金額が契約書と異なる\n**総合判定**: 不整合".to_string()),
+            Err("タイムアウト".to_string()),
+        ];
+        let matrix = compare_matrix(&chunks, &results);
+        assert_eq!(matrix.rows.len(), 4);
+        let markdown = matrix.to_markdown();
+        assert!(markdown.contains("基準: 契約書.pdf"));
+        assert!(markdown.contains("| 契約書.pdf | 基準 | 不整合 | 1 |"));
+        assert!(markdown.contains("| 見積書.pdf | 1 | 整合 | 0 |"));
+        assert!(markdown.contains("| 請求書.pdf | 2 | 不整合 | 1 |"));
+        assert!(markdown.contains("| 納品書.pdf | 3 | 解析失敗 | 0 |"));
+    }
+
+    #[test]
+    fn compare_matrix_prefers_the_group_table_row() {
+        let chunks = vec![vec![
+            "/p/契約書.pdf".to_string(),
+            "/p/見積書.pdf".to_string(),
+            "/p/請求書.pdf".to_string(),
+        ]];
+        let results = vec![Ok("| 書類 | 判定 | 指摘 |\n|---|---|---|\n| 見積書.pdf | 整合 | なし |\n| 請求書.pdf | 不整合 | 金額相違 |\n\n総合判定: 不整合".to_string())];
+        let markdown = compare_matrix(&chunks, &results).to_markdown();
+        assert!(markdown.contains("| 見積書.pdf | 1 | 整合 | 0 |"));
+        assert!(markdown.contains("| 請求書.pdf | 1 | 不整合 | 1 |"));
+    }
+}
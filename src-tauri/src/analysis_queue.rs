@@ -0,0 +1,278 @@
+//! Sequential analysis queue
+//!
+//! For slow networks, analyses can be queued instead of run in parallel:
+//! `enqueue_analysis` adds a job and a single worker runs the jobs first in,
+//! first out, one at a time. Single-file analyses are queued one job per file
+//! so even a large selection never sends two files at once. Waiting jobs can
+//! be reordered or removed; every change is sent as a `queue-updated` event
+//! with the whole queue.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::analysis::analyze_pdfs;
+use crate::events::emit_log;
+use crate::messages::tr;
+use crate::shutdown::is_shutting_down;
+use crate::tasks;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// An analysis waiting in (or taken from) the queue
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QueuedAnalysis {
+    pub id: u64,
+    pub paths: Vec<String>,
+    pub mode: String,
+    #[serde(default)]
+    pub custom_instruction: Option<String>,
+    #[serde(default)]
+    pub preset: Option<String>,
+    pub enqueued_at: String,
+}
+
+impl QueuedAnalysis {
+    fn label(&self) -> String {
+        let first = self
+            .paths
+            .first()
+            .and_then(|p| Path::new(p).file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match self.paths.len() {
+            0 | 1 => first,
+            n => tr("queue.label_many", &[&first, &(n - 1)]),
+        }
+    }
+}
+
+/// The job being analyzed and the jobs waiting, next first
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct AnalysisQueue {
+    pub running: Option<QueuedAnalysis>,
+    pub waiting: Vec<QueuedAnalysis>,
+}
+
+struct QueueState {
+    running: Option<QueuedAnalysis>,
+    waiting: VecDeque<QueuedAnalysis>,
+    next_id: u64,
+    /// A worker is taking jobs from the queue
+    worker: bool,
+}
+
+static QUEUE: Mutex<QueueState> = Mutex::new(QueueState {
+    running: None,
+    waiting: VecDeque::new(),
+    next_id: 1,
+    worker: false,
+});
+
+fn lock_queue() -> MutexGuard<'static, QueueState> {
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn snapshot(state: &QueueState) -> AnalysisQueue {
+    AnalysisQueue {
+        running: state.running.clone(),
+        waiting: state.waiting.iter().cloned().collect(),
+    }
+}
+
+fn emit_queue(app: &AppHandle, queue: &AnalysisQueue) {
+    let _ = app.emit("queue-updated", queue);
+}
+
+/// Split a request into queue jobs: one per file for single analyses, one
+/// for the whole set otherwise
+pub fn split_jobs(paths: Vec<String>, mode: &str) -> Vec<Vec<String>> {
+    if mode == "single" {
+        paths.into_iter().map(|p| vec![p]).collect()
+    } else {
+        vec![paths]
+    }
+}
+
+/// Put the listed jobs first, in the given order; the others keep their
+/// order after them
+pub fn reorder_jobs(waiting: &mut VecDeque<QueuedAnalysis>, ids: &[u64]) -> Result<(), String> {
+    if let Some(id) = ids.iter().find(|id| !waiting.iter().any(|job| job.id == **id)) {
+        return Err(tr("queue.not_found", &[id]));
+    }
+    let mut rest: Vec<QueuedAnalysis> = waiting.drain(..).collect();
+    let mut ordered = Vec::with_capacity(rest.len());
+    for id in ids {
+        if let Some(i) = rest.iter().position(|job| job.id == *id) {
+            ordered.push(rest.remove(i));
+        }
+    }
+    ordered.extend(rest);
+    waiting.extend(ordered);
+    Ok(())
+}
+
+/// Run the queued jobs one at a time until the queue is empty
+async fn process_queue(app: AppHandle) {
+    loop {
+        let (job, queue) = {
+            let mut state = lock_queue();
+            state.running = None;
+            let next = if is_shutting_down() {
+                None
+            } else {
+                state.waiting.pop_front()
+            };
+            match next {
+                Some(job) => {
+                    state.running = Some(job.clone());
+                    (job, snapshot(&state))
+                }
+                None => {
+                    state.worker = false;
+                    let queue = snapshot(&state);
+                    drop(state);
+                    emit_queue(&app, &queue);
+                    return;
+                }
+            }
+        };
+        emit_queue(&app, &queue);
+
+        let label = job.label();
+        let analyzed = analyze_pdfs(
+            app.clone(),
+            job.paths,
+            job.mode,
+            job.custom_instruction,
+            job.preset,
+            None,
+            None,
+        )
+        .await;
+        if let Err(e) = analyzed {
+            emit_log(&app, &tr("queue.failed", &[&label, &e]), "error");
+        }
+    }
+}
+
+/// 解析をキューに追加（順番に1件ずつ解析）し、追加したジョブを返す
+#[tauri::command]
+pub fn enqueue_analysis(
+    app: AppHandle,
+    paths: Vec<String>,
+    mode: String,
+    custom_instruction: Option<String>,
+    preset: Option<String>,
+) -> Result<Vec<QueuedAnalysis>, String> {
+    if paths.is_empty() {
+        return Err(tr("analysis.no_files", &[]));
+    }
+    if is_shutting_down() {
+        return Err(tr("shutdown.in_progress", &[]));
+    }
+    let now = Local::now().format(TIMESTAMP_FORMAT).to_string();
+    let (added, queue, start_worker) = {
+        let mut state = lock_queue();
+        let mut added = Vec::new();
+        for paths in split_jobs(paths, &mode) {
+            let job = QueuedAnalysis {
+                id: state.next_id,
+                paths,
+                mode: mode.clone(),
+                custom_instruction: custom_instruction.clone(),
+                preset: preset.clone(),
+                enqueued_at: now.clone(),
+            };
+            state.next_id += 1;
+            state.waiting.push_back(job.clone());
+            added.push(job);
+        }
+        let start_worker = !state.worker;
+        state.worker = true;
+        (added, snapshot(&state), start_worker)
+    };
+
+    emit_log(&app, &tr("queue.enqueued", &[&added.len(), &queue.waiting.len()]), "info");
+    emit_queue(&app, &queue);
+    if start_worker {
+        tasks::spawn("analysis", &tr("queue.worker_label", &[]), process_queue(app));
+    }
+    Ok(added)
+}
+
+/// 解析キュー（解析中のジョブと待機中のジョブ）
+#[tauri::command]
+pub fn get_queue() -> AnalysisQueue {
+    snapshot(&lock_queue())
+}
+
+/// 待機中のジョブの順番を変更（指定したジョブを指定順で先頭に）
+#[tauri::command]
+pub fn reorder_queue(app: AppHandle, ids: Vec<u64>) -> Result<AnalysisQueue, String> {
+    let queue = {
+        let mut state = lock_queue();
+        reorder_jobs(&mut state.waiting, &ids)?;
+        snapshot(&state)
+    };
+    emit_queue(&app, &queue);
+    Ok(queue)
+}
+
+/// 待機中のジョブをキューから削除（解析中のジョブはジョブ一覧から中止）
+#[tauri::command]
+pub fn remove_from_queue(app: AppHandle, id: u64) -> Result<AnalysisQueue, String> {
+    let queue = {
+        let mut state = lock_queue();
+        let index = state
+            .waiting
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| tr("queue.not_found", &[&id]))?;
+        state.waiting.remove(index);
+        snapshot(&state)
+    };
+    emit_queue(&app, &queue);
+    Ok(queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: u64) -> QueuedAnalysis {
+        QueuedAnalysis {
+            id,
+            paths: vec![format!("C:\\案件\\{}.pdf", id)],
+            mode: "single".to_string(),
+            custom_instruction: None,
+            preset: None,
+            enqueued_at: "2026-04-01 10:00:00".to_string(),
+        }
+    }
+
+    fn ids(waiting: &VecDeque<QueuedAnalysis>) -> Vec<u64> {
+        waiting.iter().map(|j| j.id).collect()
+    }
+
+    #[test]
+    fn single_analyses_are_queued_per_file() {
+        let paths = vec!["a.pdf".to_string(), "b.pdf".to_string()];
+        assert_eq!(split_jobs(paths.clone(), "single").len(), 2);
+        assert_eq!(split_jobs(paths.clone(), "compare"), vec![paths]);
+    }
+
+    #[test]
+    fn reordering_moves_listed_jobs_first() {
+        let mut waiting: VecDeque<QueuedAnalysis> = (1..=4).map(job).collect();
+        reorder_jobs(&mut waiting, &[3, 1]).unwrap();
+        assert_eq!(ids(&waiting), vec![3, 1, 2, 4]);
+
+        assert!(reorder_jobs(&mut waiting, &[4, 9]).is_err());
+        assert_eq!(ids(&waiting), vec![3, 1, 2, 4]);
+    }
+}
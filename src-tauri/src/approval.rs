@@ -0,0 +1,283 @@
+//! Review gate before results are written into the documents
+//!
+//! In projects with `require_approval` a new result is kept as a draft on its
+//! history entry: nothing is embedded into the PDF, no sidecar is written and
+//! nothing is derived from it (expiry reminders, guideline statistics, search
+//! index, document fields). `approve_result` writes the draft into the
+//! document, records those and fires the `result-approved` notification. The Post hooks
+//! and the intake mail of a draft are also held back and run on approval. A
+//! newer analysis of the same file replaces an unapproved draft.
+
+use std::path::Path;
+
+use chrono::Local;
+use tauri::{AppHandle, Emitter};
+
+use crate::archive::file_sha256;
+use crate::audit::record_access;
+use crate::classify::DocumentMetadata;
+use crate::document_fields::record_fields;
+use crate::events::emit_log;
+use crate::expiry::record_expiries;
+use crate::guidelines::record_guideline_usage;
+use crate::history::{
+    load_all_histories, load_history, update_history, AnalysisHistory, AnalysisHistoryEntry,
+    PendingResult,
+};
+use crate::hooks::{configured_hooks, latest_reports, run_stage_hooks, HookOutcome, HookPayload, HookStage};
+use crate::intake::notify_project;
+use crate::messages::tr;
+use crate::project_settings::{load_project_settings, project_folder_for};
+use crate::rag::index_analyzed_document;
+use crate::result_store::store_result;
+use crate::settings::ResultStorage;
+use crate::tasks;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Whether results of the project wait for approval
+pub fn requires_approval(project_folder: &str) -> bool {
+    load_project_settings(project_folder).require_approval
+}
+
+/// What became of a new result
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StoreOutcome {
+    /// Written into the document: PDF metadata and/or sidecar
+    Written(ResultStorage),
+    /// Kept as a draft until approved
+    Held,
+    /// Not written (read-only mode, a failed write); only in the history
+    Failed(String),
+}
+
+/// Write a result into its document, or keep it as a draft on the entry when
+/// the project requires approval
+///
+/// The entry's file hash is taken afterwards (for freshness checks). The
+/// caller records what is derived from a written result
+/// (`record_written_result`); a draft keeps what that needs.
+pub fn store_or_hold(
+    project_folder: &str,
+    entry: &mut AnalysisHistoryEntry,
+    result: &str,
+    custom_instruction: &str,
+    guideline_ids: &[String],
+    metadata: Option<&DocumentMetadata>,
+) -> StoreOutcome {
+    let path = entry.file_path.clone();
+    let outcome = if requires_approval(project_folder) {
+        entry.pending = Some(PendingResult {
+            result: result.to_string(),
+            instruction: custom_instruction.to_string(),
+            notify_intake: false,
+            guideline_ids: guideline_ids.to_vec(),
+            metadata: metadata.cloned(),
+        });
+        StoreOutcome::Held
+    } else {
+        // Store result and custom instruction (PDF metadata and/or sidecar)
+        match store_result(&path, result, custom_instruction) {
+            Ok(storage) => StoreOutcome::Written(storage),
+            Err(e) => {
+                entry.store_error = Some(e.clone());
+                StoreOutcome::Failed(e)
+            }
+        }
+    };
+    entry.file_hash = file_sha256(Path::new(&path)).ok();
+    outcome
+}
+
+/// Record what is derived from a result written into its document: expiry
+/// reminders, guideline statistics, search index and document fields
+pub fn record_written_result(
+    project_folder: &str,
+    path: &str,
+    result: &str,
+    guideline_ids: &[String],
+    metadata: Option<&DocumentMetadata>,
+) {
+    record_expiries(project_folder, path, result);
+    record_guideline_usage(project_folder, guideline_ids, result);
+    index_analyzed_document(project_folder, path, result);
+    record_fields(project_folder, path, result, metadata);
+}
+
+fn latest_entry(path: &str) -> Option<AnalysisHistoryEntry> {
+    load_history(&project_folder_for(path))
+        .ok()
+        .and_then(|h| h.entries.into_iter().find(|e| e.file_path == path))
+}
+
+/// Whether the latest analysis of a file is a draft waiting for approval
+pub fn is_held(path: &str) -> bool {
+    latest_entry(path).is_some_and(|e| e.pending.is_some())
+}
+
+/// Files of a batch whose results were written (drafts and results that
+/// could not be written left out)
+pub fn released_paths(paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .filter(|p| !latest_entry(p).is_some_and(|e| e.pending.is_some() || e.store_error.is_some()))
+        .cloned()
+        .collect()
+}
+
+/// Send the intake mail of a file when its draft is approved
+pub fn notify_on_approval(path: &str) -> Result<(), String> {
+    update_history(&project_folder_for(path), |history| {
+        if let Some(pending) = history
+            .entries
+            .iter_mut()
+            .find(|e| e.file_path == path)
+            .and_then(|e| e.pending.as_mut())
+        {
+            pending.notify_intake = true;
+        }
+    })
+}
+
+/// Run what was held back with the draft: the Post hooks and the intake mail
+fn run_held_actions(app: AppHandle, entry_id: String, project_folder: String, path: String, pending: PendingResult) {
+    let label = path.clone();
+    tasks::spawn_blocking("hook", &label, move || {
+        let paths = vec![path.clone()];
+        let payload = HookPayload {
+            stage: HookStage::Post,
+            batch_id: entry_id,
+            mode: "single".to_string(),
+            custom_instruction: pending.instruction,
+            outcome: Some(HookOutcome {
+                success: true,
+                result: Some(pending.result),
+                error: None,
+                reports: latest_reports(&paths),
+            }),
+            paths,
+        };
+        if let Ok(warnings) = run_stage_hooks(&configured_hooks(), &payload) {
+            for warning in warnings {
+                emit_log(&app, &tr("hook.warning", &[&warning]), "warn");
+            }
+        }
+        if pending.notify_intake {
+            if let Err(e) = notify_project(Path::new(&project_folder), Path::new(&path)) {
+                emit_log(&app, &tr("intake.notify_failed", &[&path, &e]), "warn");
+            }
+        }
+    });
+}
+
+/// Entries with a draft result, newest first
+pub fn pending_entries(histories: &[AnalysisHistory]) -> Vec<AnalysisHistoryEntry> {
+    let mut pending: Vec<AnalysisHistoryEntry> = histories
+        .iter()
+        .flat_map(|h| h.entries.iter())
+        .filter(|e| e.pending.is_some())
+        .cloned()
+        .collect();
+    pending.sort_by(|a, b| b.analyzed_at.cmp(&a.analyzed_at));
+    pending
+}
+
+/// 承認待ちの解析結果（新しい順）
+#[tauri::command]
+pub fn get_pending_results() -> Vec<AnalysisHistoryEntry> {
+    record_access("get_pending_results", "");
+    pending_entries(&load_all_histories())
+}
+
+/// 承認待ちの解析結果を承認し、原本（またはサイドカー）に書き込む
+#[tauri::command]
+pub fn approve_result(app: AppHandle, entry_id: String) -> Result<AnalysisHistoryEntry, String> {
+    let (project_folder, file_path, pending) = load_all_histories()
+        .into_iter()
+        .find_map(|h| {
+            let entry = h.entries.into_iter().find(|e| e.id == entry_id)?;
+            Some((h.project_folder, entry.file_path, entry.pending?))
+        })
+        .ok_or_else(|| format!("承認待ちの解析結果が見つかりません: {}", entry_id))?;
+
+    store_result(&file_path, &pending.result, &pending.instruction)?;
+    record_written_result(
+        &project_folder,
+        &file_path,
+        &pending.result,
+        &pending.guideline_ids,
+        pending.metadata.as_ref(),
+    );
+    let file_hash = file_sha256(Path::new(&file_path)).ok();
+    let approved = update_history(&project_folder, |history| {
+        let entry = history.entries.iter_mut().find(|e| e.id == entry_id)?;
+        entry.pending = None;
+        entry.file_hash = file_hash;
+        entry.approved_at = Some(Local::now().format(TIMESTAMP_FORMAT).to_string());
+        Some(entry.clone())
+    })?
+    .ok_or_else(|| format!("承認待ちの解析結果が見つかりません: {}", entry_id))?;
+
+    emit_log(&app, &format!("✓ {} の解析結果を承認しました", approved.file_name), "success");
+    let _ = app.emit("result-approved", &approved);
+    let _ = app.emit(
+        "show-notification",
+        serde_json::json!({
+            "title": "解析結果を承認しました",
+            "body": approved.file_name,
+            "path": approved.file_path
+        }),
+    );
+    run_held_actions(app, entry_id, project_folder, file_path, pending);
+    Ok(approved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+    use crate::history::create_history_entry;
+    use crate::project_settings::{save_project_settings, ProjectSettings};
+    use crate::result_store::load_result_data;
+
+    #[test]
+    fn drafts_are_not_written_until_approved() {
+        let dir = create_temp_dir(".shoruichecker_test_approval").expect("create dir");
+        let folder = dir.to_string_lossy().to_string();
+        let pdf = dir.join("請求書.pdf").to_string_lossy().to_string();
+        std::fs::write(&pdf, b"%PDF-1.4").unwrap();
+        let settings = ProjectSettings {
+            protect_originals: true,
+            require_approval: true,
+            ..Default::default()
+        };
+        save_project_settings(&folder, &settings).expect("save");
+
+        let mut entry = create_history_entry("請求書.pdf", &pdf, "⚠ 金額が異なる");
+        assert_eq!(store_or_hold(&folder, &mut entry, "⚠ 金額が異なる", "", &[], None), StoreOutcome::Held);
+        assert_eq!(entry.pending.as_ref().map(|p| p.result.as_str()), Some("⚠ 金額が異なる"));
+        assert!(load_result_data(&pdf).is_none());
+
+        let history = AnalysisHistory {
+            project_folder: folder.clone(),
+            entries: vec![entry],
+            ..Default::default()
+        };
+        assert_eq!(pending_entries(&[history]).len(), 1);
+
+        let settings = ProjectSettings {
+            require_approval: false,
+            ..settings
+        };
+        save_project_settings(&folder, &settings).expect("save");
+        let mut entry = create_history_entry("請求書.pdf", &pdf, "✓ 問題なし");
+        assert_eq!(
+            store_or_hold(&folder, &mut entry, "✓ 問題なし", "", &[], None),
+            StoreOutcome::Written(ResultStorage::Sidecar)
+        );
+        assert!(entry.pending.is_none());
+        assert_eq!(load_result_data(&pdf).map(|d| d.result).as_deref(), Some("✓ 問題なし"));
+
+        cleanup_temp_dir(&dir);
+    }
+}
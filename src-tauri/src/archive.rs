@@ -0,0 +1,391 @@
+//! Portable archives of stored results
+//!
+//! Bundles the stored result of every PDF in a folder into a ZIP with one
+//! JSON file per document and a `manifest.json`, so check evidence can be
+//! handed to the 発注者 or archived without the app. Archives can be
+//! re-imported into regenerated copies of the PDFs (re-scans, re-exports).
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::audit::record_access;
+use crate::pdf_embed::PdfEmbeddedData;
+use crate::result_store::{load_result_data, store_result};
+use crate::storage::ensure_space_for;
+use crate::tasks::run_blocking;
+
+/// Archive format version written to the manifest
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Minimum file-name similarity for matching a regenerated PDF
+pub const MIN_NAME_SIMILARITY: f64 = 0.8;
+
+/// One document in the archive
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    pub file_name: String,
+    /// SHA-256 of the PDF at export time (hex)
+    pub sha256: String,
+    pub size: u64,
+    /// Path of the result JSON inside the archive
+    pub result_file: String,
+    /// Date of the stored result
+    pub analyzed_at: String,
+}
+
+/// `manifest.json` of an archive
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub exported_at: String,
+    pub source_folder: String,
+    pub files: Vec<ArchiveFile>,
+}
+
+/// SHA-256 of a file (hex)
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// PDFs directly under the folder, sorted by name
+pub fn list_pdfs(folder: &Path) -> Vec<PathBuf> {
+    let mut pdfs: Vec<PathBuf> = fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.is_file()
+                        && p.extension()
+                            .map(|e| e.eq_ignore_ascii_case("pdf"))
+                            .unwrap_or(false)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    pdfs.sort();
+    pdfs
+}
+
+fn write_entry(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    content: &[u8],
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    zip.start_file(name, options)
+        .map_err(|e| e.to_string())
+        .and_then(|_| zip.write_all(content).map_err(|e| e.to_string()))
+        .map_err(|e| format!("アーカイブ書き込みエラー: {}", e))
+}
+
+/// Write the archive of all stored results in `folder`; returns the manifest
+pub fn write_archive(folder: &Path, out_path: &Path) -> Result<ArchiveManifest, String> {
+    let mut manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        source_folder: folder.to_string_lossy().to_string(),
+        files: Vec::new(),
+    };
+
+    ensure_space_for(out_path, 0)?;
+    let file = File::create(out_path).map_err(|e| format!("アーカイブ作成エラー: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for pdf in list_pdfs(folder) {
+        let Some(data) = load_result_data(&pdf.to_string_lossy()) else {
+            continue;
+        };
+        let file_name = pdf
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let sha256 = file_sha256(&pdf).map_err(|e| format!("{}: {}", file_name, e))?;
+        let size = fs::metadata(&pdf).map(|m| m.len()).unwrap_or(0);
+        let result_file = format!("results/{}.json", file_name);
+
+        let json = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
+        write_entry(&mut zip, &result_file, json.as_bytes(), options)?;
+
+        manifest.files.push(ArchiveFile {
+            file_name,
+            sha256,
+            size,
+            result_file,
+            analyzed_at: data.date,
+        });
+    }
+
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    write_entry(&mut zip, MANIFEST_NAME, json.as_bytes(), options)?;
+    zip.finish().map_err(|e| format!("アーカイブ書き込みエラー: {}", e))?;
+    Ok(manifest)
+}
+
+/// Read the manifest and all result files of an archive
+pub fn read_archive(
+    path: &Path,
+) -> Result<(ArchiveManifest, Vec<(ArchiveFile, PdfEmbeddedData)>), String> {
+    let file = File::open(path).map_err(|e| format!("アーカイブ読み込みエラー: {}", e))?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("アーカイブ読み込みエラー: {}", e))?;
+
+    let mut read_entry = |name: &str| -> Result<String, String> {
+        let mut content = String::new();
+        zip.by_name(name)
+            .map_err(|e| format!("{}: {}", name, e))?
+            .read_to_string(&mut content)
+            .map_err(|e| format!("{}: {}", name, e))?;
+        Ok(content)
+    };
+
+    let manifest: ArchiveManifest = serde_json::from_str(&read_entry(MANIFEST_NAME)?)
+        .map_err(|e| format!("マニフェストが不正です: {}", e))?;
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err("新しいバージョンで作成されたアーカイブです。アプリを更新してください".to_string());
+    }
+
+    let mut results = Vec::new();
+    for entry in &manifest.files {
+        let data: PdfEmbeddedData = serde_json::from_str(&read_entry(&entry.result_file)?)
+            .map_err(|e| format!("{}: {}", entry.result_file, e))?;
+        results.push((entry.clone(), data));
+    }
+    Ok((manifest, results))
+}
+
+/// How a PDF was matched to an archived result
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    /// Same content (hash) as at export time
+    Unchanged,
+    /// Matched by name only; the content changed since the check
+    ContentChanged,
+}
+
+/// A PDF that received an archived result
+#[derive(Clone, Serialize)]
+pub struct ReimportedFile {
+    pub file_name: String,
+    pub archived_file_name: String,
+    pub kind: MatchKind,
+    pub similarity: f64,
+    pub analyzed_at: String,
+}
+
+/// Outcome of a re-import
+#[derive(Clone, Serialize, Default)]
+pub struct ReimportReport {
+    pub imported: Vec<ReimportedFile>,
+    /// PDFs without a matching archived result
+    pub unmatched: Vec<String>,
+    /// PDFs skipped because they already have a stored result
+    pub skipped: Vec<String>,
+}
+
+/// Name stem used for similarity: no extension, case, spaces or separators
+fn normalize_name(name: &str) -> Vec<char> {
+    let stem = Path::new(name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    stem.to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '_' | '-' | '(' | ')' | '（' | '）' | '.'))
+        .collect()
+}
+
+/// File-name similarity in `0.0..=1.0` (Dice coefficient of character bigrams)
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_name(a), normalize_name(b));
+    if a == b {
+        return 1.0;
+    }
+    if a.len() < 2 || b.len() < 2 {
+        return 0.0;
+    }
+    let bigrams = |s: &[char]| -> Vec<(char, char)> { s.windows(2).map(|w| (w[0], w[1])).collect() };
+    let (a, mut b) = (bigrams(&a), bigrams(&b));
+    let total = a.len() + b.len();
+    let mut common = 0;
+    for pair in &a {
+        if let Some(i) = b.iter().position(|p| p == pair) {
+            b.swap_remove(i);
+            common += 1;
+        }
+    }
+    2.0 * common as f64 / total as f64
+}
+
+/// Find the archived result for a PDF: same hash first, then most similar name
+pub fn match_archived<'a>(
+    file_name: &str,
+    sha256: &str,
+    candidates: &'a [ArchiveFile],
+) -> Option<(&'a ArchiveFile, MatchKind, f64)> {
+    if let Some(file) = candidates.iter().find(|f| f.sha256 == sha256) {
+        return Some((file, MatchKind::Unchanged, name_similarity(file_name, &file.file_name)));
+    }
+    candidates
+        .iter()
+        .map(|f| (f, name_similarity(file_name, &f.file_name)))
+        .filter(|(_, score)| *score >= MIN_NAME_SIMILARITY)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(f, score)| (f, MatchKind::ContentChanged, score))
+}
+
+/// Attach archived results to the PDFs in `folder` that have none
+///
+/// Results matched by name only are stored with a leading warning so the
+/// content change stays visible until the document is re-analyzed.
+pub fn reimport_archive(folder: &Path, archive_path: &Path) -> Result<ReimportReport, String> {
+    let (_, results) = read_archive(archive_path)?;
+    let mut remaining: Vec<ArchiveFile> = results.iter().map(|(f, _)| f.clone()).collect();
+    let mut report = ReimportReport::default();
+
+    for pdf in list_pdfs(folder) {
+        let pdf_path = pdf.to_string_lossy().to_string();
+        let file_name = pdf
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if load_result_data(&pdf_path).is_some() {
+            report.skipped.push(file_name);
+            continue;
+        }
+        let sha256 = file_sha256(&pdf).map_err(|e| format!("{}: {}", file_name, e))?;
+        let Some((archived, kind, similarity)) = match_archived(&file_name, &sha256, &remaining)
+        else {
+            report.unmatched.push(file_name);
+            continue;
+        };
+        let archived = archived.clone();
+        let Some((_, data)) = results.iter().find(|(f, _)| f.result_file == archived.result_file)
+        else {
+            continue;
+        };
+
+        let result = match kind {
+            MatchKind::Unchanged => data.result.clone(),
+            MatchKind::ContentChanged => format!(
+                "⚠ 再取り込み: {} のチェック結果（{}）以降に内容が変更されています。再解析してください\n\n{}",
+                archived.file_name, data.date, data.result
+            ),
+        };
+        store_result(&pdf_path, &result, data.instruction.as_deref().unwrap_or(""))?;
+
+        remaining.retain(|f| f.result_file != archived.result_file);
+        report.imported.push(ReimportedFile {
+            file_name,
+            archived_file_name: archived.file_name,
+            kind,
+            similarity,
+            analyzed_at: data.date.clone(),
+        });
+    }
+    Ok(report)
+}
+
+/// フォルダの解析結果をZIPにまとめて出力（出力した件数を返す）
+#[tauri::command]
+pub async fn export_embedded_archive(folder: String, out_path: String) -> Result<usize, String> {
+    record_access("export_embedded_archive", &folder);
+    let label = folder.clone();
+    run_blocking("archive", &label, move || {
+        write_archive(Path::new(&folder), Path::new(&out_path)).map(|m| m.files.len())
+    })
+    .await
+    .and_then(|r| r)
+}
+
+/// 書き出したアーカイブの解析結果を作り直したPDFに取り込む
+#[tauri::command]
+pub async fn reimport_embedded_archive(
+    folder: String,
+    archive_path: String,
+) -> Result<ReimportReport, String> {
+    let label = folder.clone();
+    run_blocking("archive", &label, move || {
+        reimport_archive(Path::new(&folder), Path::new(&archive_path))
+    })
+    .await
+    .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+    use crate::result_store::write_sidecar;
+
+    #[test]
+    fn archive_roundtrip_includes_only_analyzed_pdfs() {
+        let dir = create_temp_dir(".shoruichecker_test_archive").expect("create dir");
+        let analyzed = dir.join("契約書.pdf");
+        fs::write(&analyzed, b"%PDF-1.4 contract").unwrap();
+        fs::write(dir.join("未解析.pdf"), b"%PDF-1.4").unwrap();
+        let data = PdfEmbeddedData {
+            result: "✓ 問題なし".to_string(),
+            instruction: None,
+            date: "2026-01-01 10:00:00".to_string(),
+        };
+        write_sidecar(&analyzed.to_string_lossy(), &data).unwrap();
+
+        let out = dir.join("out.zip");
+        let manifest = write_archive(&dir, &out).expect("export");
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].sha256, file_sha256(&analyzed).unwrap());
+
+        let (read_manifest, results) = read_archive(&out).expect("read");
+        assert_eq!(read_manifest.files[0].file_name, "契約書.pdf");
+        assert_eq!(results[0].1.result, "✓ 問題なし");
+
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn name_similarity_tolerates_rescan_suffixes() {
+        assert_eq!(name_similarity("出来形管理図.pdf", "出来形管理図 .PDF"), 1.0);
+        let rescanned = name_similarity("出来形管理図_舗装工.pdf", "出来形管理図_舗装工(1).pdf");
+        assert!(rescanned >= MIN_NAME_SIMILARITY);
+        assert!(name_similarity("契約書.pdf", "請求書.pdf") < MIN_NAME_SIMILARITY);
+    }
+
+    #[test]
+    fn match_prefers_hash_then_name() {
+        let file = |name: &str, hash: &str| ArchiveFile {
+            file_name: name.to_string(),
+            sha256: hash.to_string(),
+            size: 0,
+            result_file: format!("results/{}.json", name),
+            analyzed_at: String::new(),
+        };
+        let candidates = vec![file("見積書.pdf", "aa"), file("出来形管理図.pdf", "bb")];
+
+        let (f, kind, _) = match_archived("renamed.pdf", "aa", &candidates).unwrap();
+        assert_eq!((f.file_name.as_str(), kind), ("見積書.pdf", MatchKind::Unchanged));
+
+        let (f, kind, _) = match_archived("出来形管理図(1).pdf", "cc", &candidates).unwrap();
+        assert_eq!((f.file_name.as_str(), kind), ("出来形管理図.pdf", MatchKind::ContentChanged));
+
+        assert!(match_archived("写真帳.pdf", "cc", &candidates).is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! Assignment workflow on findings
+//!
+//! Findings can be assigned to a person with a due date and completed later.
+//! Assignments are stored in `shoruichecker/assignments.json` in the config
+//! directory and keyed by a finding ID derived from project, file and issue
+//! text, so they survive re-analysis while the same issue is still reported.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::file_lock::write_atomic;
+use crate::history::path_hash;
+
+/// Serializes read-modify-write access to the assignments file
+static ASSIGNMENTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// A finding assigned to a person
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub finding_id: String,
+    pub project_folder: String,
+    pub file_name: String,
+    pub issue: String,
+    pub assignee: String,
+    /// `%Y-%m-%d`
+    pub due_date: Option<String>,
+    pub assigned_at: String,
+    pub completed_at: Option<String>,
+}
+
+/// Stable ID of a finding
+pub fn finding_id(project_folder: &str, file_name: &str, issue: &str) -> String {
+    format!(
+        "F{:016x}",
+        path_hash(&format!("{}\n{}\n{}", project_folder, file_name, issue.trim()))
+    )
+}
+
+pub fn get_assignments_path() -> PathBuf {
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("assignments.json")
+}
+
+pub fn load_assignments() -> Vec<Assignment> {
+    fs::read_to_string(get_assignments_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_assignments(assignments: &[Assignment]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(assignments).map_err(|e| e.to_string())?;
+    write_atomic(&get_assignments_path(), json)
+}
+
+/// Open items of an assignee, earliest due date first (no due date last)
+pub fn open_items_for(assignments: &[Assignment], assignee: &str) -> Vec<Assignment> {
+    let mut items: Vec<Assignment> = assignments
+        .iter()
+        .filter(|a| a.assignee == assignee && a.completed_at.is_none())
+        .cloned()
+        .collect();
+    items.sort_by(|a, b| match (&a.due_date, &b.due_date) {
+        (Some(x), Some(y)) => x.cmp(y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.assigned_at.cmp(&b.assigned_at),
+    });
+    items
+}
+
+/// 指摘を担当者に割り当て（割り当て済みなら担当者・期限を更新）
+#[tauri::command]
+pub fn assign_finding(
+    project_folder: String,
+    file_name: String,
+    issue: String,
+    assignee: String,
+    due_date: Option<String>,
+) -> Result<Assignment, String> {
+    if assignee.trim().is_empty() {
+        return Err("担当者を指定してください".to_string());
+    }
+    if let Some(date) = &due_date {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| format!("期限の日付が不正です: {}", date))?;
+    }
+
+    let id = finding_id(&project_folder, &file_name, &issue);
+    let assignment = Assignment {
+        finding_id: id.clone(),
+        project_folder,
+        file_name,
+        issue: issue.trim().to_string(),
+        assignee: assignee.trim().to_string(),
+        due_date,
+        assigned_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        completed_at: None,
+    };
+
+    let _guard = ASSIGNMENTS_LOCK.lock();
+    let mut assignments = load_assignments();
+    assignments.retain(|a| a.finding_id != id);
+    assignments.push(assignment.clone());
+    save_assignments(&assignments)?;
+    Ok(assignment)
+}
+
+/// 割り当てた指摘を完了にする
+#[tauri::command]
+pub fn complete_finding(finding_id: String) -> Result<(), String> {
+    let _guard = ASSIGNMENTS_LOCK.lock();
+    let mut assignments = load_assignments();
+    let assignment = assignments
+        .iter_mut()
+        .find(|a| a.finding_id == finding_id)
+        .ok_or_else(|| "割り当てが見つかりません".to_string())?;
+    assignment.completed_at = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+    save_assignments(&assignments)
+}
+
+/// 担当者の未完了の指摘一覧
+#[tauri::command]
+pub fn get_my_open_items(assignee: String) -> Vec<Assignment> {
+    open_items_for(&load_assignments(), &assignee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(id: &str, assignee: &str, due: Option<&str>, done: bool) -> Assignment {
+        Assignment {
+            finding_id: id.to_string(),
+            project_folder: "/p".to_string(),
+            file_name: "a.pdf".to_string(),
+            issue: "⚠ 金額不整合".to_string(),
+            assignee: assignee.to_string(),
+            due_date: due.map(|d| d.to_string()),
+            assigned_at: "2026-01-01 10:00:00".to_string(),
+            completed_at: done.then(|| "2026-01-02 10:00:00".to_string()),
+        }
+    }
+
+    #[test]
+    fn finding_id_is_stable_and_ignores_surrounding_whitespace() {
+        let a = finding_id("/p", "a.pdf", "⚠ 金額不整合");
+        assert_eq!(a, finding_id("/p", "a.pdf", "  ⚠ 金額不整合 "));
+        assert_ne!(a, finding_id("/p", "b.pdf", "⚠ 金額不整合"));
+        assert!(a.starts_with('F'));
+    }
+
+    #[test]
+    fn open_items_sorted_by_due_date() {
+        let assignments = vec![
+            assignment("1", "田中", None, false),
+            assignment("2", "田中", Some("2026-02-10"), false),
+            assignment("3", "田中", Some("2026-02-01"), false),
+            assignment("4", "田中", Some("2026-01-01"), true),
+            assignment("5", "佐藤", Some("2026-01-01"), false),
+        ];
+        let ids: Vec<_> = open_items_for(&assignments, "田中")
+            .into_iter()
+            .map(|a| a.finding_id)
+            .collect();
+        assert_eq!(ids, vec!["3", "2", "1"]);
+    }
+}
@@ -0,0 +1,145 @@
+//! Access audit log
+//!
+//! Viewing and exporting analysis results is recorded (command, file, time,
+//! OS user) for ISO/internal audits. Records are appended as JSON lines to
+//! `shoruichecker/audit.log` in the config directory; the file is never
+//! rewritten or truncated by the app. This is separate from the UI log
+//! (`emit_log`), which is not persisted.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::export::csv_field;
+use crate::file_lock::lock_file;
+use crate::storage::ensure_space_for;
+
+/// One audited access
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// `%Y-%m-%d %H:%M:%S`
+    pub at: String,
+    /// OS (Windows) user name
+    pub user: String,
+    /// Command that read or exported results
+    pub command: String,
+    /// File or folder accessed (empty for all projects)
+    pub target: String,
+}
+
+const CSV_HEADER: [&str; 4] = ["日時", "ユーザー", "操作", "対象"];
+
+/// Get the audit log path
+pub fn get_audit_log_path() -> PathBuf {
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("audit.log")
+}
+
+/// Logged-in OS user
+pub fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn append_record(path: &Path, record: &AuditRecord) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    let _lock = lock_file(path)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("監査ログの書き込みエラー: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("監査ログの書き込みエラー: {}", e))
+}
+
+/// Record an access to results
+///
+/// Best effort: a failing audit write must not block the user's command.
+pub fn record_access(command: &str, target: &str) {
+    let record = AuditRecord {
+        at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        user: current_user(),
+        command: command.to_string(),
+        target: target.to_string(),
+    };
+    let _ = append_record(&get_audit_log_path(), &record);
+}
+
+/// Records of the audit log, optionally only those at or after `since`
+///
+/// `since` is compared as a prefix-ordered date (`2026-04` or
+/// `2026-04-01 09:00:00`). Unreadable lines are skipped.
+pub fn read_records(path: &Path, since: Option<&str>) -> Vec<AuditRecord> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+        .filter(|r| since.is_none_or(|s| r.at.as_str() >= s))
+        .collect()
+}
+
+/// Render records as CSV with a UTF-8 BOM (same format as the issue export)
+pub fn records_to_csv(records: &[AuditRecord]) -> String {
+    let mut csv = String::from("\u{feff}");
+    csv.push_str(&CSV_HEADER.join(","));
+    csv.push_str("\r\n");
+    for record in records {
+        let fields = [&record.at, &record.user, &record.command, &record.target];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&line.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// 監査ログをCSVに書き出し（since 以降のみ、書き出した件数を返す）
+#[tauri::command]
+pub fn export_audit_log(out_path: String, since: Option<String>) -> Result<usize, String> {
+    let records = read_records(&get_audit_log_path(), since.as_deref());
+    let csv = records_to_csv(&records);
+    ensure_space_for(Path::new(&out_path), csv.len() as u64)?;
+    fs::write(&out_path, csv)
+        .map_err(|e| format!("CSV保存エラー: {}", e))?;
+    record_access("export_audit_log", &out_path);
+    Ok(records.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    fn record(at: &str, command: &str) -> AuditRecord {
+        AuditRecord {
+            at: at.to_string(),
+            user: "yamada".to_string(),
+            command: command.to_string(),
+            target: "C:/工事/契約書.pdf".to_string(),
+        }
+    }
+
+    #[test]
+    fn records_are_appended_and_filtered_by_date() {
+        let dir = create_temp_dir(".shoruichecker_test_audit").expect("create dir");
+        let path = dir.join("audit.log");
+        append_record(&path, &record("2026-03-31 18:00:00", "read_pdf_result")).unwrap();
+        append_record(&path, &record("2026-04-01 09:00:00", "export_issues_csv")).unwrap();
+
+        assert_eq!(read_records(&path, None).len(), 2);
+        let since = read_records(&path, Some("2026-04"));
+        assert_eq!(since, vec![record("2026-04-01 09:00:00", "export_issues_csv")]);
+
+        let csv = records_to_csv(&since);
+        assert!(csv.starts_with("\u{feff}日時,ユーザー,操作,対象\r\n"));
+        assert!(csv.contains("yamada,export_issues_csv,C:/工事/契約書.pdf"));
+
+        cleanup_temp_dir(&dir);
+    }
+}
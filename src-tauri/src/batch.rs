@@ -0,0 +1,230 @@
+//! Abortable analysis batches
+//!
+//! Every `analyze_pdfs` run is a batch identified by its job ID. Aborting a
+//! batch stops it from starting further files and kills the CLI processes of
+//! the files in flight; files that already finished keep their stored
+//! results and history, and the run ends with a partial summary.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Local;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::events::emit_log;
+use crate::messages::tr;
+use crate::processes::{current_child_scope, kill_children_in_scope};
+
+// Global state for running batches
+static BATCHES: Mutex<Option<HashMap<String, BatchEntry>>> = Mutex::new(None);
+
+struct BatchEntry {
+    mode: String,
+    total: usize,
+    started_at: String,
+    aborted: Arc<AtomicBool>,
+    completed: Arc<AtomicUsize>,
+}
+
+/// Progress of a running batch
+#[derive(Clone, Serialize)]
+pub struct BatchStatus {
+    pub batch_id: String,
+    pub mode: String,
+    pub total: usize,
+    /// Files finished (parallel single analysis; other modes finish at once)
+    pub completed: usize,
+    pub started_at: String,
+    pub aborted: bool,
+}
+
+/// Error recorded for files stopped by an abort
+pub fn aborted_message() -> String {
+    tr("batch.aborted", &[])
+}
+
+/// Keeps a batch registered until dropped (run finished)
+pub struct BatchGuard {
+    id: String,
+    aborted: Arc<AtomicBool>,
+    completed: Arc<AtomicUsize>,
+}
+
+impl BatchGuard {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Abort flag to check from worker threads
+    pub fn abort_flag(&self) -> Arc<AtomicBool> {
+        self.aborted.clone()
+    }
+
+    /// Counter of finished files to increment from worker threads
+    pub fn completed_counter(&self) -> Arc<AtomicUsize> {
+        self.completed.clone()
+    }
+
+    /// See [`abort_error`]
+    pub fn error_for(&self, error: String) -> String {
+        abort_error(&self.aborted, error)
+    }
+}
+
+/// Error of a file that failed after the batch was aborted (killed CLI)
+pub fn abort_error(aborted: &AtomicBool, error: String) -> String {
+    if aborted.load(Ordering::SeqCst) {
+        aborted_message()
+    } else {
+        error
+    }
+}
+
+impl Drop for BatchGuard {
+    fn drop(&mut self) {
+        let mut batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(map) = batches.as_mut() {
+            map.remove(&self.id);
+        }
+    }
+}
+
+/// Register a running batch of `total` files
+pub fn begin_batch(id: &str, mode: &str, total: usize) -> BatchGuard {
+    let aborted = Arc::new(AtomicBool::new(false));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let mut batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    batches.get_or_insert_with(HashMap::new).insert(
+        id.to_string(),
+        BatchEntry {
+            mode: mode.to_string(),
+            total,
+            started_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            aborted: aborted.clone(),
+            completed: completed.clone(),
+        },
+    );
+    BatchGuard {
+        id: id.to_string(),
+        aborted,
+        completed,
+    }
+}
+
+/// Running batches, oldest first
+pub fn running_batches() -> Vec<BatchStatus> {
+    let batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<BatchStatus> = batches
+        .iter()
+        .flatten()
+        .map(|(id, entry)| BatchStatus {
+            batch_id: id.clone(),
+            mode: entry.mode.clone(),
+            total: entry.total,
+            completed: entry.completed.load(Ordering::SeqCst),
+            started_at: entry.started_at.clone(),
+            aborted: entry.aborted.load(Ordering::SeqCst),
+        })
+        .collect();
+    list.sort_by(|a, b| a.started_at.cmp(&b.started_at).then(a.batch_id.cmp(&b.batch_id)));
+    list
+}
+
+/// Set the abort flag of a running batch; false if it is not running
+pub fn request_abort(id: &str) -> bool {
+    let batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    match batches.as_ref().and_then(|map| map.get(id)) {
+        Some(entry) => {
+            entry.aborted.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Whether the batch this thread works for (its child scope) was aborted
+pub fn current_batch_aborted() -> bool {
+    let Some(id) = current_child_scope() else {
+        return false;
+    };
+    let batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    batches
+        .as_ref()
+        .and_then(|map| map.get(&id))
+        .is_some_and(|entry| entry.aborted.load(Ordering::SeqCst))
+}
+
+/// Final summary of a batch, emitted as `batch-summary`
+#[derive(Clone, Serialize)]
+pub struct BatchSummary {
+    pub batch_id: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Files not started because the batch was aborted
+    pub skipped: Vec<String>,
+    pub aborted: bool,
+}
+
+/// 実行中のバッチ解析を中断（未開始のファイルは解析せず、実行中の解析は停止する）
+///
+/// 停止した外部プロセスの数を返す。完了済みのファイルの結果は保存されたまま残る。
+#[tauri::command]
+pub fn abort_batch(batch_id: String) -> Result<usize, String> {
+    if !request_abort(&batch_id) {
+        return Err(tr("batch.not_found", &[&batch_id]));
+    }
+    Ok(kill_children_in_scope(&batch_id))
+}
+
+/// Payload of the `analysis-cancelled` event
+#[derive(Clone, Serialize)]
+pub struct AnalysisCancelled {
+    pub analysis_id: String,
+    /// External processes killed
+    pub killed: usize,
+}
+
+/// 解析をキャンセル（analysis_id は batch-started イベントの batch_id）
+///
+/// `abort_batch` と同じく実行中のプロセスを停止し、`analysis-cancelled` を通知する。
+#[tauri::command]
+pub fn cancel_analysis(app: AppHandle, analysis_id: String) -> Result<usize, String> {
+    let killed = abort_batch(analysis_id.clone())?;
+    emit_log(&app, &tr("batch.cancelled", &[&analysis_id]), "warn");
+    let _ = app.emit(
+        "analysis-cancelled",
+        AnalysisCancelled {
+            analysis_id,
+            killed,
+        },
+    );
+    Ok(killed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abort_sets_flag_until_batch_ends() {
+        let batch = begin_batch("batch-test", "single", 3);
+        assert!(!batch.is_aborted());
+        batch.completed_counter().fetch_add(1, Ordering::SeqCst);
+        let status = running_batches()
+            .into_iter()
+            .find(|b| b.batch_id == "batch-test")
+            .expect("running");
+        assert_eq!((status.total, status.completed), (3, 1));
+        assert!(request_abort("batch-test"));
+        assert!(batch.is_aborted());
+        drop(batch);
+        assert!(!request_abort("batch-test"));
+    }
+}
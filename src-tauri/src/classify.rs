@@ -0,0 +1,328 @@
+//! AI classification of documents into structured metadata
+//!
+//! Before the full analysis the first page is sent to the cheap text model,
+//! which assigns a document type (one of the known types), the document
+//! date and the parties. The result is stored in the database keyed by file
+//! path and content hash, so guideline and prompt selection do not depend
+//! on file names and unchanged files are classified only once. Embedding a
+//! result into the PDF moves the stored metadata to the new hash. Project
+//! type rules still win over the model's type.
+
+use std::path::Path;
+
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::file_sha256;
+use crate::audit::record_access;
+use crate::database::open_db;
+use crate::encryption::{open_text, seal_text};
+use crate::gemini_cli::{
+    cleanup_temp_dir, create_temp_dir, run_gemini_with_prompt, TEMP_DIR_PREFIX,
+};
+use crate::guidelines::KNOWN_DOCUMENT_TYPES;
+use crate::pdf_text::{extract_page_texts, extract_pages_to_pdf, is_text_page};
+use crate::project_settings::{load_project_settings, project_folder_for};
+use crate::settings::{load_settings, DEFAULT_TEXT_MODEL};
+use crate::tasks::run_blocking;
+
+/// First-page text sent for classification is cut to this many chars
+const MAX_CLASSIFY_TEXT_CHARS: usize = 3000;
+
+/// Structured metadata assigned by the classification pass
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    /// One of the classification candidates (None: その他)
+    pub doc_type: Option<String>,
+    /// Document date as YYYY-MM-DD when the model could read one
+    pub date: Option<String>,
+    /// Companies/persons named as parties (発注者・受注者・提出先など)
+    pub parties: Vec<String>,
+}
+
+/// Raw model answer before normalization
+#[derive(Deserialize)]
+struct RawClassification {
+    #[serde(default)]
+    doc_type: Option<String>,
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    parties: Vec<String>,
+}
+
+/// Types the model may choose from: built-in types plus the project's rule types
+pub fn classification_candidates(folder: &str) -> Vec<String> {
+    let mut candidates: Vec<String> = KNOWN_DOCUMENT_TYPES.iter().map(|t| t.to_string()).collect();
+    for rule in load_project_settings(folder).document_type_rules {
+        if !candidates.contains(&rule.doc_type) {
+            candidates.push(rule.doc_type);
+        }
+    }
+    candidates
+}
+
+/// Prompt asking for the type, date and parties as a JSON object
+pub fn build_classification_prompt(candidates: &[String], first_page_text: Option<&str>) -> String {
+    let source = match first_page_text {
+        Some(text) => format!(
+            "以下は書類の1ページ目から抽出したテキストです。\n---\n{}\n---",
+            text.chars().take(MAX_CLASSIFY_TEXT_CHARS).collect::<String>()
+        ),
+        None => "添付のPDFは書類の1ページ目です。".to_string(),
+    };
+    format!(
+        r#"あなたは建設工事の書類を分類するアシスタントです。
+{}
+
+この書類について次のJSONだけを出力してください（説明文は不要）。
+{{"doc_type": "種類", "date": "YYYY-MM-DD", "parties": ["当事者名"]}}
+- doc_type: 次の候補から1つだけ選ぶ。該当しない場合は「その他」
+  候補: {}
+- date: 書類の作成日・契約日など書類の日付。読み取れない場合は null
+- parties: 発注者・受注者・提出先など書類に記載された会社名・氏名（なければ空配列）"#,
+        source,
+        candidates.join("、")
+    )
+}
+
+/// Candidate named in an answer (the longest one when several match)
+fn match_candidate(answer: &str, candidates: &[String]) -> Option<String> {
+    candidates
+        .iter()
+        .filter(|c| answer.contains(c.as_str()))
+        .max_by_key(|c| c.chars().count())
+        .cloned()
+}
+
+/// Normalize a date answer to YYYY-MM-DD (None if unreadable)
+fn normalize_date(date: &str) -> Option<String> {
+    let digits: Vec<u32> = date
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    digits.windows(3).find_map(|w| match *w {
+        [y, m, d] if y >= 1900 && (1..=12).contains(&m) && (1..=31).contains(&d) => {
+            Some(format!("{:04}-{:02}-{:02}", y, m, d))
+        }
+        _ => None,
+    })
+}
+
+/// Metadata from the model output
+///
+/// Falls back to reading a bare type name when the model did not answer
+/// with JSON.
+pub fn parse_classification(output: &str, candidates: &[String]) -> Option<DocumentMetadata> {
+    let json = output
+        .find('{')
+        .zip(output.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<RawClassification>(&output[start..=end]).ok());
+
+    match json {
+        Some(raw) => Some(DocumentMetadata {
+            doc_type: raw
+                .doc_type
+                .as_deref()
+                .and_then(|t| match_candidate(t, candidates)),
+            date: raw.date.as_deref().and_then(normalize_date),
+            parties: raw
+                .parties
+                .into_iter()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }),
+        None => {
+            let answer = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+            Some(DocumentMetadata {
+                doc_type: match_candidate(answer, candidates),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Stored metadata of a file, if it was classified with the same content
+pub fn load_stored_metadata(conn: &Connection, file_path: &str, file_hash: &str) -> Option<DocumentMetadata> {
+    conn.query_row(
+        "SELECT doc_type, document_date, parties FROM document_metadata
+         WHERE file_path = ?1 AND file_hash = ?2",
+        params![file_path, file_hash],
+        |row| {
+            let parties: String = row.get(2)?;
+            Ok(DocumentMetadata {
+                doc_type: row.get(0)?,
+                date: row.get(1)?,
+                parties: open_text(parties)
+                    .ok()
+                    .and_then(|p| serde_json::from_str(&p).ok())
+                    .unwrap_or_default(),
+            })
+        },
+    )
+    .optional()
+    .ok()
+    .flatten()
+}
+
+/// Store (replace) the metadata of a file
+pub fn store_metadata(
+    conn: &Connection,
+    file_path: &str,
+    file_hash: &str,
+    metadata: &DocumentMetadata,
+) -> Result<(), String> {
+    let parties = seal_text(&serde_json::to_string(&metadata.parties).map_err(|e| e.to_string())?)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO document_metadata
+         (file_path, file_hash, doc_type, document_date, parties, classified_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            file_path,
+            file_hash,
+            metadata.doc_type,
+            metadata.date,
+            parties,
+            Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+        ],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("分類情報の保存エラー: {}", e))
+}
+
+/// Move the stored metadata of a file to a new content hash (after only
+/// its embedded metadata changed)
+pub fn rekey_metadata(
+    conn: &Connection,
+    file_path: &str,
+    old_hash: &str,
+    new_hash: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE document_metadata SET file_hash = ?3 WHERE file_path = ?1 AND file_hash = ?2",
+        params![file_path, old_hash, new_hash],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("分類情報の保存エラー: {}", e))
+}
+
+/// Classify a document from its first page with the text model
+fn classify_with_ai(path: &str, folder: &str) -> Option<DocumentMetadata> {
+    let candidates = classification_candidates(folder);
+    let first_page = extract_page_texts(path)
+        .ok()
+        .and_then(|pages| pages.into_iter().next())
+        .filter(|text| is_text_page(text));
+
+    let temp_dir = create_temp_dir(&format!("{}classify", TEMP_DIR_PREFIX)).ok()?;
+    let attachments = if first_page.is_none() {
+        let name = "first_page.pdf".to_string();
+        if extract_pages_to_pdf(path, &[1], &temp_dir.join(&name)).is_err() {
+            cleanup_temp_dir(&temp_dir);
+            return None;
+        }
+        vec![name]
+    } else {
+        Vec::new()
+    };
+
+    let prompt = build_classification_prompt(&candidates, first_page.as_deref());
+    let model = load_settings()
+        .text_model
+        .unwrap_or_else(|| DEFAULT_TEXT_MODEL.to_string());
+    let pdfs = (!attachments.is_empty()).then_some(attachments.as_slice());
+    let output = run_gemini_with_prompt(&temp_dir, &prompt, &model, pdfs);
+    cleanup_temp_dir(&temp_dir);
+
+    output
+        .ok()
+        .and_then(|out| parse_classification(&out, &candidates))
+}
+
+/// Metadata of a document: stored result for unchanged files, otherwise a
+/// fresh classification pass (stored for next time)
+pub fn classify_document(path: &str, folder: &str) -> Option<DocumentMetadata> {
+    let hash = file_sha256(Path::new(path)).ok()?;
+    let conn = open_db().ok();
+    if let Some(stored) = conn
+        .as_ref()
+        .and_then(|c| load_stored_metadata(c, path, &hash))
+    {
+        return Some(stored);
+    }
+
+    let metadata = classify_with_ai(path, folder)?;
+    if let Some(conn) = &conn {
+        let _ = store_metadata(conn, path, &hash, &metadata);
+    }
+    Some(metadata)
+}
+
+/// 書類の分類情報（種類・日付・当事者）を取得（未分類なら分類する）
+#[tauri::command]
+pub async fn get_document_metadata(path: String) -> Result<DocumentMetadata, String> {
+    record_access("get_document_metadata", &path);
+    let label = path.clone();
+    run_blocking("classify", &label, move || {
+        classify_document(&path, &project_folder_for(&path))
+            .ok_or_else(|| format!("書類を分類できませんでした: {}", path))
+    })
+    .await
+    .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    fn candidates() -> Vec<String> {
+        ["契約書", "交通誘導員", "交通誘導員配置実績"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_classification_reads_json_answer() {
+        let output = "```json\n{\"doc_type\": \"交通誘導員配置実績\", \"date\": \"令和6年 2024/4/1\", \"parties\": [\" 山田建設 \", \"\"]}\n```";
+        let metadata = parse_classification(output, &candidates()).expect("metadata");
+        assert_eq!(metadata.doc_type.as_deref(), Some("交通誘導員配置実績"));
+        assert_eq!(metadata.date.as_deref(), Some("2024-04-01"));
+        assert_eq!(metadata.parties, vec!["山田建設".to_string()]);
+
+        let other = parse_classification("{\"doc_type\": \"その他\", \"date\": null}", &candidates())
+            .expect("metadata");
+        assert_eq!(other.doc_type, None);
+        assert_eq!(other.date, None);
+    }
+
+    #[test]
+    fn parse_classification_falls_back_to_type_name() {
+        let metadata = parse_classification("\n契約書です\n", &candidates()).expect("metadata");
+        assert_eq!(metadata.doc_type.as_deref(), Some("契約書"));
+        assert!(metadata.parties.is_empty());
+    }
+
+    #[test]
+    fn stored_metadata_is_keyed_by_hash() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let metadata = DocumentMetadata {
+            doc_type: Some("契約書".to_string()),
+            date: Some("2024-04-01".to_string()),
+            parties: vec!["山田建設".to_string()],
+        };
+        store_metadata(&conn, "C:/p/a.pdf", "h1", &metadata).expect("store");
+        assert_eq!(load_stored_metadata(&conn, "C:/p/a.pdf", "h1"), Some(metadata));
+        assert_eq!(load_stored_metadata(&conn, "C:/p/a.pdf", "h2"), None);
+
+        rekey_metadata(&conn, "C:/p/a.pdf", "h1", "h2").expect("rekey");
+        assert_eq!(load_stored_metadata(&conn, "C:/p/a.pdf", "h2"), Some(metadata));
+        assert_eq!(load_stored_metadata(&conn, "C:/p/a.pdf", "h1"), None);
+    }
+}
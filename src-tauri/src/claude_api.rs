@@ -0,0 +1,200 @@
+//! Claude API (Anthropic Messages API) client
+//!
+//! Sends the prompt with the attached files of a request: PDFs as document
+//! blocks, images as image blocks and other files as text. The API key is
+//! kept in the OS keychain (`ANTHROPIC_API_KEY` overrides it).
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::gemini_cli::GeminiRequest;
+use crate::keychain::{read_secret, replace_secret};
+use crate::settings::load_settings;
+use crate::usage::note_token_usage;
+
+const API_URL: &str = "https://api.anthropic.com/v1/messages";
+const API_VERSION: &str = "2023-06-01";
+const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+
+/// Model used when the request names a Gemini model
+pub const DEFAULT_CLAUDE_MODEL: &str = "claude-sonnet-4-5";
+
+const MAX_TOKENS: u32 = 8192;
+
+/// Long PDFs take minutes to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+const KEYRING_USER: &str = "claude-api-key";
+
+/// API key from the environment or the keychain
+pub fn api_key() -> Option<String> {
+    std::env::var(API_KEY_ENV)
+        .ok()
+        .or_else(|| read_secret(KEYRING_USER))
+        .filter(|k| !k.trim().is_empty())
+}
+
+/// Store the API key (None or empty removes it)
+pub fn store_api_key(key: Option<&str>) -> Result<(), String> {
+    replace_secret(KEYRING_USER, key.map(str::trim))
+}
+
+/// Claude model for a request: Claude model names are used as given,
+/// others (the Gemini defaults) are replaced by the configured model
+pub fn claude_model(requested: &str) -> String {
+    if requested.starts_with("claude") {
+        return requested.to_string();
+    }
+    load_settings()
+        .claude_model
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_CLAUDE_MODEL.to_string())
+}
+
+fn media_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name).extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "pdf" => Some("application/pdf"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Content blocks of a request: the files first, then the prompt
+pub fn content_blocks(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<Vec<Value>> {
+    let mut blocks = Vec::new();
+    for name in request.files.unwrap_or_default() {
+        let path = temp_dir.join(name);
+        let block = match media_type(name) {
+            Some(media_type) => {
+                let data = BASE64.encode(fs::read(&path)?);
+                let kind = if media_type == "application/pdf" { "document" } else { "image" };
+                json!({
+                    "type": kind,
+                    "source": { "type": "base64", "media_type": media_type, "data": data }
+                })
+            }
+            None => {
+                let text = fs::read_to_string(&path)?;
+                json!({ "type": "text", "text": format!("--- {} ---\n{}", name, text) })
+            }
+        };
+        blocks.push(block);
+    }
+    blocks.push(json!({ "type": "text", "text": request.prompt }));
+    Ok(blocks)
+}
+
+/// Request body of the Messages API
+pub fn request_body(model: &str, blocks: Vec<Value>, json_output: bool) -> Value {
+    let mut body = json!({
+        "model": model,
+        "max_tokens": MAX_TOKENS,
+        "messages": [{ "role": "user", "content": blocks }]
+    });
+    if json_output {
+        body["system"] = json!("Respond with a single JSON value only, without explanations or code fences.");
+    }
+    body
+}
+
+/// Text of a Messages API response
+pub fn response_text(response: &Value) -> AppResult<String> {
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+        return Err(AppError::Process(format!("Claude API エラー: {}", message)));
+    }
+    let text: Vec<&str> = response
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter(|b| b.get("type").and_then(Value::as_str) == Some("text"))
+                .filter_map(|b| b.get("text").and_then(Value::as_str))
+                .collect()
+        })
+        .unwrap_or_default();
+    if text.is_empty() {
+        return Err(AppError::Process("Claude API の応答にテキストがありません".to_string()));
+    }
+    Ok(text.join("\n"))
+}
+
+/// Run a request against the Claude API
+pub fn run_claude(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+    let key = api_key().ok_or_else(|| AppError::Process("Claude API キーが設定されていません".to_string()))?;
+    let model = claude_model(request.model);
+    let body = request_body(
+        &model,
+        content_blocks(temp_dir, request)?,
+        request.output_format == "json",
+    );
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let response = agent
+        .post(API_URL)
+        .set("x-api-key", &key)
+        .set("anthropic-version", API_VERSION)
+        .send_json(body);
+    let response: Value = match response {
+        Ok(r) => r.into_json()?,
+        // Error bodies carry the message
+        Err(ureq::Error::Status(code, r)) => r
+            .into_json()
+            .unwrap_or_else(|_| json!({ "error": { "message": format!("HTTP {}", code) } })),
+        Err(e) => return Err(AppError::Process(format!("Claude API に接続できません: {}", e))),
+    };
+    note_token_usage(
+        &model,
+        response["usage"]["input_tokens"].as_i64(),
+        response["usage"]["output_tokens"].as_i64(),
+    );
+    response_text(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn files_become_document_image_and_text_blocks() {
+        let dir = create_temp_dir(".shoruichecker_test_claude").expect("create dir");
+        fs::write(dir.join("a.pdf"), b"%PDF").unwrap();
+        fs::write(dir.join("b.PNG"), b"png").unwrap();
+        fs::write(dir.join("notes.txt"), "メモ").unwrap();
+        let files = vec!["a.pdf".to_string(), "b.PNG".to_string(), "notes.txt".to_string()];
+        let request = GeminiRequest::text_with_files("チェックして", "gemini-2.5-pro", &files);
+        let blocks = content_blocks(&dir, &request).expect("blocks");
+        cleanup_temp_dir(&dir);
+
+        assert_eq!(blocks[0]["type"], "document");
+        assert_eq!(blocks[0]["source"]["data"], BASE64.encode(b"%PDF"));
+        assert_eq!(blocks[1]["source"]["media_type"], "image/png");
+        assert_eq!(blocks[2]["text"], "--- notes.txt ---\nメモ");
+        assert_eq!(blocks[3]["text"], "チェックして");
+        assert_eq!(claude_model("claude-opus-4-1"), "claude-opus-4-1");
+    }
+
+    #[test]
+    fn response_text_joins_text_blocks_and_reports_errors() {
+        let ok = json!({ "content": [
+            { "type": "text", "text": "## 書類タイプ" },
+            { "type": "tool_use", "id": "x" },
+            { "type": "text", "text": "✓ 問題なし" }
+        ]});
+        assert_eq!(response_text(&ok).unwrap(), "## 書類タイプ\n✓ 問題なし");
+        let error = json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } });
+        assert!(response_text(&error).unwrap_err().to_string().contains("Overloaded"));
+        assert!(response_text(&json!({ "content": [] })).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! First-run setup of the Gemini CLI
+//!
+//! Detects a missing CLI, installs it globally with npm while streaming the
+//! installer output as progress events. The login itself is started with
+//! `open_gemini_auth`.
+
+use std::cmp::Ordering;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+#[cfg(target_os = "windows")]
+use crate::CREATE_NO_WINDOW;
+
+use crate::doctor::{command_output, gemini_auth_source, npm_cmd};
+use crate::events::emit_log;
+use crate::gemini_cli::gemini_cmd_path;
+use crate::processes::{isolate_process_group, register_child};
+use crate::tasks::run_blocking;
+
+/// npm package of the Gemini CLI
+pub const GEMINI_CLI_PACKAGE: &str = "@google/gemini-cli";
+
+/// Oldest CLI version known to work with the generated PowerShell invocation
+pub const MIN_GEMINI_CLI_VERSION: &str = "0.1.18";
+
+/// Installed CLI version, looked up once (`gemini --version` starts node)
+static CLI_VERSION: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+/// Installation state shown on the setup screen
+#[derive(Clone, Serialize)]
+pub struct GeminiCliStatus {
+    pub installed: bool,
+    pub version: Option<String>,
+    pub npm_available: bool,
+    pub authenticated: bool,
+}
+
+/// Progress event payload (`gemini-install-progress`)
+#[derive(Clone, Serialize)]
+struct InstallProgress {
+    /// "start", "output", "done" or "error"
+    stage: &'static str,
+    message: String,
+}
+
+/// Extract the version number from `gemini --version` output
+pub fn parse_cli_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|w| w.trim_start_matches('v'))
+        .find(|w| {
+            let parts: Vec<&str> = w.split('.').collect();
+            parts.len() >= 2 && parts.iter().take(3).all(|p| p.parse::<u32>().is_ok())
+        })
+        .map(|w| w.to_string())
+}
+
+/// Installed Gemini CLI version, if any
+pub fn installed_cli_version() -> Option<String> {
+    command_output(&gemini_cmd_path(), &["--version"]).and_then(|out| parse_cli_version(&out))
+}
+
+/// Installed CLI version, cached until the next install or update
+pub fn cached_cli_version() -> Option<String> {
+    let mut cache = CLI_VERSION.lock().unwrap_or_else(|e| e.into_inner());
+    cache.get_or_insert_with(installed_cli_version).clone()
+}
+
+fn reset_cli_version_cache() {
+    *CLI_VERSION.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Compare dotted version numbers numerically (`0.10.0` > `0.9.2`)
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| -> Vec<u32> {
+        v.split(['.', '-'])
+            .map_while(|p| p.parse::<u32>().ok())
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+    for i in 0..a.len().max(b.len()) {
+        let ord = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Whether a CLI version is older than the known-good minimum
+pub fn is_outdated(version: &str) -> bool {
+    compare_versions(version, MIN_GEMINI_CLI_VERSION) == Ordering::Less
+}
+
+/// Warn on startup when the installed CLI is older than the minimum
+pub fn check_cli_version_on_startup(app: &AppHandle) {
+    let Some(version) = cached_cli_version() else {
+        return;
+    };
+    if is_outdated(&version) {
+        let message = format!(
+            "Gemini CLI {} は古いバージョンです（{} 以上を推奨）。設定画面から更新してください",
+            version, MIN_GEMINI_CLI_VERSION
+        );
+        emit_log(app, &message, "error");
+        let _ = app.emit(
+            "show-notification",
+            serde_json::json!({
+                "title": "Gemini CLI の更新が必要です",
+                "body": message,
+                "path": ""
+            }),
+        );
+    }
+}
+
+/// npm package spec (`@google/gemini-cli` or `@google/gemini-cli@<version>`)
+pub fn gemini_package_spec(version: Option<&str>) -> String {
+    match version {
+        Some(v) => format!("{}@{}", GEMINI_CLI_PACKAGE, v),
+        None => GEMINI_CLI_PACKAGE.to_string(),
+    }
+}
+
+fn emit_progress(app: &AppHandle, stage: &'static str, message: &str) {
+    let _ = app.emit(
+        "gemini-install-progress",
+        InstallProgress {
+            stage,
+            message: message.to_string(),
+        },
+    );
+}
+
+/// Forward the lines of a child output stream as progress events
+fn forward_lines(app: AppHandle, stream: impl Read + Send + 'static) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if !line.trim().is_empty() {
+                emit_progress(&app, "output", line.trim());
+            }
+        }
+    })
+}
+
+/// Run `npm install -g <package_spec>` streaming its output as progress events
+pub fn npm_global_install(app: &AppHandle, package_spec: &str) -> Result<String, String> {
+    if command_output(npm_cmd(), &["--version"]).is_none() {
+        let message = "npm が見つかりません。先に Node.js（LTS）をインストールしてください";
+        emit_progress(app, "error", message);
+        return Err(message.to_string());
+    }
+
+    emit_progress(app, "start", &format!("npm install -g {}", package_spec));
+    emit_log(app, &format!("{} をインストール中...", package_spec), "wave");
+
+    let mut cmd = Command::new(npm_cmd());
+    cmd.args(["install", "-g", package_spec])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    isolate_process_group(&mut cmd);
+
+    let mut child = cmd.spawn().map_err(|e| format!("npm の起動に失敗: {}", e))?;
+    let _guard = register_child(child.id(), "npm install");
+    let readers: Vec<_> = [
+        child.stdout.take().map(|s| forward_lines(app.clone(), s)),
+        child.stderr.take().map(|s| forward_lines(app.clone(), s)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let status = child.wait().map_err(|e| e.to_string())?;
+    for reader in readers {
+        let _ = reader.join();
+    }
+    reset_cli_version_cache();
+
+    if !status.success() {
+        let message = format!(
+            "インストールに失敗しました（{}）。管理者権限やネットワーク設定を確認してください",
+            status
+        );
+        emit_progress(app, "error", &message);
+        emit_log(app, &message, "error");
+        return Err(message);
+    }
+
+    let version = installed_cli_version()
+        .ok_or_else(|| "インストール後に gemini コマンドが見つかりません（PATH を確認してください）".to_string());
+    match &version {
+        Ok(v) => {
+            emit_progress(app, "done", v);
+            emit_log(app, &format!("✓ Gemini CLI {} をインストールしました", v), "success");
+        }
+        Err(e) => emit_progress(app, "error", e),
+    }
+    version
+}
+
+/// Gemini CLI の導入状況
+#[tauri::command]
+pub fn get_gemini_cli_status() -> GeminiCliStatus {
+    let version = installed_cli_version();
+    GeminiCliStatus {
+        installed: version.is_some(),
+        version,
+        npm_available: command_output(npm_cmd(), &["--version"]).is_some(),
+        authenticated: gemini_auth_source(dirs::home_dir().as_deref(), |k| std::env::var(k).ok())
+            .is_some(),
+    }
+}
+
+/// Gemini CLI をインストール（進捗は gemini-install-progress イベント）
+#[tauri::command]
+pub async fn install_gemini_cli(app: AppHandle) -> Result<String, String> {
+    run_blocking("setup", "Gemini CLI インストール", move || {
+        match installed_cli_version() {
+            Some(version) => Ok(version),
+            None => npm_global_install(&app, &gemini_package_spec(None)),
+        }
+    })
+    .await
+    .and_then(|r| r)
+}
+
+/// Gemini CLI を更新（version 省略時は最新版）
+#[tauri::command]
+pub async fn update_gemini_cli(app: AppHandle, version: Option<String>) -> Result<String, String> {
+    let spec = gemini_package_spec(Some(version.as_deref().unwrap_or("latest")));
+    let app_clone = app.clone();
+    let installed = run_blocking("setup", "Gemini CLI 更新", move || {
+        npm_global_install(&app_clone, &spec)
+    })
+    .await
+    .and_then(|r| r)?;
+    if is_outdated(&installed) {
+        emit_log(
+            &app,
+            &format!("Gemini CLI {} は推奨バージョン {} より古いままです", installed, MIN_GEMINI_CLI_VERSION),
+            "error",
+        );
+    }
+    Ok(installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cli_version_handles_common_formats() {
+        assert_eq!(parse_cli_version("0.1.18"), Some("0.1.18".to_string()));
+        assert_eq!(parse_cli_version("gemini v0.2.1\n"), Some("0.2.1".to_string()));
+        assert_eq!(parse_cli_version("command not found"), None);
+    }
+
+    #[test]
+    fn compare_versions_is_numeric() {
+        assert_eq!(compare_versions("0.10.0", "0.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("0.1.18", "0.1.18"), Ordering::Equal);
+        assert_eq!(compare_versions("0.1", "0.1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.1.9-nightly", "0.1.18"), Ordering::Less);
+        assert!(is_outdated("0.1.5"));
+        assert!(!is_outdated("1.0.0"));
+    }
+
+    #[test]
+    fn package_spec_pins_version() {
+        assert_eq!(gemini_package_spec(None), "@google/gemini-cli");
+        assert_eq!(gemini_package_spec(Some("0.1.18")), "@google/gemini-cli@0.1.18");
+    }
+}
@@ -0,0 +1,85 @@
+//! Clipboard intake
+//!
+//! Documents often arrive as screenshots pasted into Teams. The current
+//! clipboard bitmap is saved as a PNG into the project folder and analyzed
+//! like a scanned document (image analysis; the result goes to a sidecar).
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use image::{ImageFormat, RgbaImage};
+use tauri::AppHandle;
+
+use crate::analysis::analyze_pdfs;
+use crate::events::emit_log;
+use crate::messages::tr;
+use crate::settings::load_settings;
+use crate::storage::ensure_space_for;
+use crate::tasks::run_blocking;
+
+/// File name of a saved clipboard image
+pub fn clipboard_file_name(now: DateTime<Local>) -> String {
+    format!("クリップボード_{}.png", now.format("%Y%m%d_%H%M%S"))
+}
+
+/// Save RGBA pixels as a PNG into `folder`; returns the file path
+pub fn save_rgba_png(folder: &Path, width: u32, height: u32, rgba: Vec<u8>) -> Result<PathBuf, String> {
+    let image = RgbaImage::from_raw(width, height, rgba)
+        .ok_or_else(|| "クリップボードの画像データが不正です".to_string())?;
+    let path = folder.join(clipboard_file_name(Local::now()));
+    ensure_space_for(&path, image.as_raw().len() as u64)?;
+    image
+        .save_with_format(&path, ImageFormat::Png)
+        .map_err(|e| format!("画像の保存エラー: {}", e))?;
+    Ok(path)
+}
+
+/// Save the current clipboard bitmap into `folder`
+fn save_clipboard_image(folder: &Path) -> Result<PathBuf, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("クリップボードを開けません: {}", e))?;
+    let image = clipboard
+        .get_image()
+        .map_err(|_| tr("clipboard.no_image", &[]))?;
+    save_rgba_png(
+        folder,
+        image.width as u32,
+        image.height as u32,
+        image.bytes.into_owned(),
+    )
+}
+
+/// クリップボードの画像（スクリーンショット）を保存して解析（folder 省略時は監視フォルダ）
+#[tauri::command]
+pub async fn analyze_clipboard_image(app: AppHandle, folder: Option<String>) -> Result<String, String> {
+    let folder = folder
+        .or_else(|| load_settings().watch_folder)
+        .ok_or_else(|| tr("clipboard.no_folder", &[]))?;
+    let path = run_blocking("clipboard", "クリップボード画像", move || {
+        save_clipboard_image(Path::new(&folder))
+    })
+    .await
+    .and_then(|r| r)?;
+    let path = path.to_string_lossy().to_string();
+    emit_log(&app, &tr("clipboard.saved", &[&path]), "info");
+    analyze_pdfs(app, vec![path], "single".to_string(), None, None, None, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn clipboard_pixels_are_saved_as_png() {
+        let dir = create_temp_dir(".shoruichecker_test_clipboard").expect("create dir");
+        let rgba = [255u8, 0, 0, 255].repeat(6);
+        let path = save_rgba_png(&dir, 3, 2, rgba).expect("save");
+        assert!(path.file_name().unwrap().to_string_lossy().starts_with("クリップボード_"));
+        let saved = image::open(&path).expect("open png");
+        assert_eq!((saved.width(), saved.height()), (3, 2));
+
+        assert!(save_rgba_png(&dir, 3, 3, vec![0; 4]).is_err());
+        cleanup_temp_dir(&dir);
+    }
+}
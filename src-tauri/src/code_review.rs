@@ -1,16 +1,133 @@
 //! Code review module using ai-code-review crate
+//!
+//! The watched repository can describe its architecture in `ARCHITECTURE.md`
+//! (root or `docs/`) and `.review-config.json` (module responsibilities,
+//! conventions, extra context files). Both are included in workspace review
+//! prompts so placement questions are judged against the actual architecture;
+//! the save watcher's reviewer takes no extra context. Context files must lie
+//! inside the watched folder.
+//!
+//! The save watcher is listed in the task registry while it runs (cancelling
+//! it there stops the watcher), and each review result is handled as a
+//! blocking task.
 
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 
 use ai_code_review::{Backend, CodeReviewer, PromptType};
+use serde::Deserialize;
+use tauri::async_runtime::JoinHandle;
 use tauri::{AppHandle, Emitter};
 
-use crate::events::{CodeReviewEvent, LogEvent};
+use crate::events::{emit_log, CodeReviewEvent};
+use crate::review_digest::{add_pending_review, digest_enabled, PendingReview};
+use crate::review_metrics::record_review;
 use crate::settings::{load_settings, save_settings};
+use crate::tasks;
+
+/// A running save watcher and the task it is listed as
+struct RunningReviewer {
+    reviewer: CodeReviewer,
+    task: JoinHandle<()>,
+    generation: u64,
+}
 
 /// Global state for the code reviewer
-static CODE_REVIEWER: Mutex<Option<CodeReviewer>> = Mutex::new(None);
+static CODE_REVIEWER: Mutex<Option<RunningReviewer>> = Mutex::new(None);
+
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Held by the watcher's task: stops the watcher when the task is cancelled
+/// (a newer watcher of another generation is left alone)
+struct StopWhenCancelled(u64);
+
+impl Drop for StopWhenCancelled {
+    fn drop(&mut self) {
+        let running = {
+            let mut running = CODE_REVIEWER.lock().unwrap_or_else(|e| e.into_inner());
+            if running.as_ref().is_some_and(|r| r.generation == self.0) {
+                running.take()
+            } else {
+                None
+            }
+        };
+        if let Some(mut running) = running {
+            let _ = running.reviewer.stop();
+        }
+    }
+}
+
+/// Architecture documents looked up in the watched folder, first found wins
+const ARCHITECTURE_FILES: &[&str] = &["ARCHITECTURE.md", "docs/ARCHITECTURE.md"];
+const REVIEW_CONFIG_FILE: &str = ".review-config.json";
+/// Source files reviewed (on save and by `review_workspace`)
+pub const REVIEW_EXTENSIONS: &[&str] = &["rs", "ts", "tsx", "js", "py"];
+/// Context longer than this is cut so the changed code still fits the prompt
+const MAX_CONTEXT_CHARS: usize = 20_000;
+
+/// `.review-config.json` in the watched folder
+#[derive(Deserialize, Default)]
+struct ReviewConfig {
+    /// Path (file or folder) → responsibility
+    #[serde(default)]
+    modules: BTreeMap<String, String>,
+    #[serde(default)]
+    conventions: Vec<String>,
+    /// Further documents included as they are (relative to the folder)
+    #[serde(default)]
+    context_files: Vec<String>,
+}
+
+/// Architecture context of a repository for the review prompt
+pub fn load_architecture_context(root: &Path) -> Option<String> {
+    let mut sections = Vec::new();
+    if let Some(doc) = ARCHITECTURE_FILES
+        .iter()
+        .find_map(|name| fs::read_to_string(root.join(name)).ok())
+    {
+        sections.push(format!("### アーキテクチャ\n{}", doc.trim()));
+    }
+
+    let config: ReviewConfig = fs::read_to_string(root.join(REVIEW_CONFIG_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    if !config.modules.is_empty() {
+        let lines: Vec<String> = config
+            .modules
+            .iter()
+            .map(|(path, role)| format!("- {}: {}", path, role))
+            .collect();
+        sections.push(format!("### モジュールの責務\n{}", lines.join("\n")));
+    }
+    if !config.conventions.is_empty() {
+        let lines: Vec<String> = config.conventions.iter().map(|c| format!("- {}", c)).collect();
+        sections.push(format!("### 規約\n{}", lines.join("\n")));
+    }
+    // Only files inside the folder: the config comes from the watched repo
+    let canonical_root = root.canonicalize().ok();
+    for file in &config.context_files {
+        let inside = match (&canonical_root, root.join(file).canonicalize()) {
+            (Some(base), Ok(path)) => path.starts_with(base).then_some(path),
+            _ => None,
+        };
+        if let Some(text) = inside.and_then(|path| fs::read_to_string(path).ok()) {
+            sections.push(format!("### {}\n{}", file, text.trim()));
+        }
+    }
+
+    if sections.is_empty() {
+        return None;
+    }
+    let context = format!(
+        "## このリポジトリの設計\n変更が置かれたファイル・モジュールが適切かは、以下の設計と規約に照らして判断してください。\n\n{}",
+        sections.join("\n\n")
+    );
+    Some(context.chars().take(MAX_CONTEXT_CHARS).collect())
+}
 
 #[tauri::command]
 pub fn get_code_watch_folder() -> Option<String> {
@@ -55,6 +172,14 @@ pub fn stop_code_watching() -> Result<(), String> {
     stop_code_watcher()
 }
 
+/// レビューに含める設計情報（ARCHITECTURE.md / .review-config.json）
+#[tauri::command]
+pub fn get_review_context() -> Option<String> {
+    load_settings()
+        .code_watch_folder
+        .and_then(|folder| load_architecture_context(Path::new(&folder)))
+}
+
 /// Start the code watcher using CodeReviewer
 pub(crate) fn start_code_watcher(app: AppHandle, folder: &str) -> Result<(), String> {
     // Stop existing watcher first
@@ -67,11 +192,12 @@ pub(crate) fn start_code_watcher(app: AppHandle, folder: &str) -> Result<(), Str
 
     let log_path = folder_path.join(".code-reviews.log");
     let app_clone = app.clone();
+    let repo_folder = folder.to_string();
 
     let mut reviewer = CodeReviewer::new(folder_path)
         .map_err(|e| e.to_string())?
         .with_backend(Backend::Gemini)
-        .with_extensions(&["rs", "ts", "tsx", "js", "py"])
+        .with_extensions(REVIEW_EXTENSIONS)
         .with_prompt_type(PromptType::Default)
         .with_log_file(&log_path)
         .on_review(move |result| {
@@ -82,51 +208,116 @@ pub(crate) fn start_code_watcher(app: AppHandle, folder: &str) -> Result<(), Str
                 timestamp: result.timestamp.clone(),
                 has_issues: result.has_issues,
             };
-
-            // Emit review complete event
-            let _ = app_clone.emit("code-review-complete", event.clone());
-
-            // Emit log event
-            let _ = app_clone.emit(
-                "log",
-                LogEvent {
-                    message: format!(
-                        "✓ レビュー完了: {} {}",
-                        result.name,
-                        if result.has_issues { "(問題あり)" } else { "" }
-                    ),
-                    level: if result.has_issues { "info" } else { "success" }.to_string(),
-                },
-            );
-
-            // Show notification only if issues found
-            if result.has_issues {
-                let _ = app_clone.emit(
-                    "show-notification",
-                    serde_json::json!({
-                        "title": "コードレビュー",
-                        "body": format!("{}: 問題が検出されました", result.name),
-                        "path": result.path.to_string_lossy().to_string()
-                    }),
-                );
-            }
+            let (app, repo_folder) = (app_clone.clone(), repo_folder.clone());
+            let label = event.name.clone();
+            tasks::spawn_blocking("code_review", &label, move || handle_review(&app, &repo_folder, event));
         });
 
     reviewer.start().map_err(|e| e.to_string())?;
 
+    // Listed as a task while it runs; the task only waits to be cancelled
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let task = tasks::spawn("code_review", folder, async move {
+        let _stop = StopWhenCancelled(generation);
+        std::future::pending::<()>().await
+    });
+
     // Store the reviewer
     let mut handle = CODE_REVIEWER.lock().map_err(|e| e.to_string())?;
-    *handle = Some(reviewer);
+    *handle = Some(RunningReviewer {
+        reviewer,
+        task,
+        generation,
+    });
 
     Ok(())
 }
 
+/// Record a review result and tell the frontend
+fn handle_review(app: &AppHandle, repo_folder: &str, event: CodeReviewEvent) {
+    // Emit review complete event
+    let _ = app.emit("code-review-complete", event.clone());
+    record_review(repo_folder, &event.path, &event.name, event.has_issues, &event.review_result);
+
+    // Emit log event
+    emit_log(
+        app,
+        &format!(
+            "✓ レビュー完了: {} {}",
+            event.name,
+            if event.has_issues { "(問題あり)" } else { "" }
+        ),
+        if event.has_issues { "info" } else { "success" },
+    );
+
+    // Show notification only if issues found; in digest mode they
+    // are collected for the daily summary instead
+    if event.has_issues && digest_enabled() {
+        let _ = add_pending_review(PendingReview {
+            path: event.path,
+            name: event.name,
+            timestamp: event.timestamp,
+            review: event.review_result,
+        });
+    } else if event.has_issues {
+        let _ = app.emit(
+            "show-notification",
+            serde_json::json!({
+                "title": "コードレビュー",
+                "body": format!("{}: 問題が検出されました", event.name),
+                "path": event.path
+            }),
+        );
+    }
+}
+
+/// Folder the code watcher is running on
+pub fn running_code_watcher() -> Option<String> {
+    let running = CODE_REVIEWER.lock().ok()?.is_some();
+    running.then(|| load_settings().code_watch_folder).flatten()
+}
+
 /// Stop the code watcher
-fn stop_code_watcher() -> Result<(), String> {
-    let mut handle = CODE_REVIEWER.lock().map_err(|e| e.to_string())?;
-    if let Some(mut reviewer) = handle.take() {
+pub(crate) fn stop_code_watcher() -> Result<(), String> {
+    // Taken out first: the task's guard locks the state when it is dropped
+    let running = CODE_REVIEWER.lock().map_err(|e| e.to_string())?.take();
+    if let Some(mut running) = running {
         // Ignore NotRunning error
-        let _ = reviewer.stop();
+        let _ = running.reviewer.stop();
+        running.task.abort();
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn architecture_context_combines_document_and_config() {
+        let dir = create_temp_dir(".shoruichecker_test_review_context").expect("create dir");
+        assert_eq!(load_architecture_context(&dir), None);
+
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/ARCHITECTURE.md"), "Tauriコマンドは各モジュールに置く\n").unwrap();
+        fs::write(dir.join("CONVENTIONS.md"), "エラーは String で返す").unwrap();
+        let outside = dir.parent().expect("parent").join(".shoruichecker_test_review_outside.md");
+        fs::write(&outside, "外部の文書").unwrap();
+        fs::write(
+            dir.join(REVIEW_CONFIG_FILE),
+            r#"{"modules": {"src/history.rs": "解析履歴の保存"}, "conventions": ["日本語のUI文言"], "context_files": ["CONVENTIONS.md", "missing.md", "../.shoruichecker_test_review_outside.md"]}"#,
+        )
+        .unwrap();
+
+        let context = load_architecture_context(&dir).expect("context");
+        assert!(context.contains("Tauriコマンドは各モジュールに置く"));
+        assert!(context.contains("- src/history.rs: 解析履歴の保存"));
+        assert!(context.contains("- 日本語のUI文言"));
+        assert!(context.contains("### CONVENTIONS.md\nエラーは String で返す"));
+        assert!(!context.contains("missing.md"));
+        assert!(!context.contains("外部の文書"));
+        let _ = fs::remove_file(&outside);
+        cleanup_temp_dir(&dir);
+    }
+}
@@ -0,0 +1,258 @@
+//! Consensus mode for high-stakes contracts
+//!
+//! Contracts whose amount reaches the configured threshold are analyzed a
+//! second time with another backend or model, on the same prompt and files.
+//! The findings and key fields of both runs are compared; findings only one
+//! model reported and fields the models read differently are appended to the
+//! result as ⚠ lines, so the document shows up for human review.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai_provider::{backend_label, provider_for};
+use crate::archive::name_similarity;
+use crate::document_fields::{designated_amount, field_kind, parse_amount, FieldKind};
+use crate::events::emit_app_log;
+use crate::gemini_cli::{run_gemini_with, GeminiRequest};
+use crate::messages::tr;
+use crate::settings::{load_settings, save_settings, AiBackend};
+use crate::structured_report::{parse_report, AnalysisReport};
+
+/// Findings at least this similar are taken as the same finding
+const SAME_FINDING_SIMILARITY: f64 = 0.5;
+
+/// Second opinion on contracts above an amount
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConsensusSettings {
+    /// Smallest contract amount (yen) analyzed by two models
+    pub min_amount: i64,
+    /// Backend of the second analysis
+    pub backend: AiBackend,
+    /// Model of the second analysis (None: the model of the first one)
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// A field the two models read differently
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FieldConflict {
+    pub label: String,
+    pub primary: String,
+    pub secondary: String,
+}
+
+/// Disagreements between the two analyses
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ConsensusDiff {
+    pub only_primary: Vec<String>,
+    pub only_secondary: Vec<String>,
+    pub field_conflicts: Vec<FieldConflict>,
+}
+
+impl ConsensusDiff {
+    pub fn is_agreed(&self) -> bool {
+        self.only_primary.is_empty() && self.only_secondary.is_empty() && self.field_conflicts.is_empty()
+    }
+}
+
+/// Contract amount of a result (its designated total field)
+fn contract_amount(report: &AnalysisReport) -> Option<i64> {
+    let amounts: Vec<(&str, i64)> = report
+        .fields
+        .iter()
+        .filter(|f| field_kind(&f.label, &f.value) == FieldKind::Amount)
+        .filter_map(|f| parse_amount(&f.value).map(|amount| (f.label.as_str(), amount)))
+        .collect();
+    designated_amount(&amounts)
+}
+
+/// Whether a result is a contract at or above the threshold
+pub fn needs_consensus(result: &str, settings: &ConsensusSettings) -> bool {
+    let report = parse_report("", result);
+    let is_contract = report
+        .document_type
+        .as_deref()
+        .is_some_and(|t| t.contains("契約"));
+    is_contract && contract_amount(&report).is_some_and(|amount| amount >= settings.min_amount)
+}
+
+fn unmatched(findings: &[String], others: &[String]) -> Vec<String> {
+    findings
+        .iter()
+        .filter(|f| {
+            !others
+                .iter()
+                .any(|o| name_similarity(f, o) >= SAME_FINDING_SIMILARITY)
+        })
+        .cloned()
+        .collect()
+}
+
+fn same_value(kind: FieldKind, a: &str, b: &str) -> bool {
+    match (kind, parse_amount(a), parse_amount(b)) {
+        (FieldKind::Amount, Some(a), Some(b)) => a == b,
+        _ => {
+            let normalize = |s: &str| s.split_whitespace().collect::<String>();
+            normalize(a) == normalize(b)
+        }
+    }
+}
+
+/// Findings and amount/date/party fields on which the two results differ
+pub fn compare_results(primary: &str, secondary: &str) -> ConsensusDiff {
+    let (primary, secondary) = (parse_report("", primary), parse_report("", secondary));
+    let texts = |report: &AnalysisReport| -> Vec<String> {
+        report.findings.iter().map(|f| f.text.clone()).collect()
+    };
+    let (a, b) = (texts(&primary), texts(&secondary));
+    let field_conflicts = primary
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let kind = field_kind(&field.label, &field.value);
+            if kind == FieldKind::Text {
+                return None;
+            }
+            let other = secondary.fields.iter().find(|o| o.label == field.label)?;
+            (!same_value(kind, &field.value, &other.value)).then(|| FieldConflict {
+                label: field.label.clone(),
+                primary: field.value.clone(),
+                secondary: other.value.clone(),
+            })
+        })
+        .collect();
+    ConsensusDiff {
+        only_primary: unmatched(&a, &b),
+        only_secondary: unmatched(&b, &a),
+        field_conflicts,
+    }
+}
+
+/// Result section listing the disagreements (⚠ lines count as findings)
+pub fn consensus_section(diff: &ConsensusDiff, primary_label: &str, secondary_label: &str) -> String {
+    if diff.is_agreed() {
+        return format!(
+            "\n\n## 複数モデル照合\n✓ {} と {} の指摘・主要項目が一致しました",
+            primary_label, secondary_label
+        );
+    }
+    let mut section = format!(
+        "\n\n## 複数モデル照合（要人手確認）\n{} と {} の解析結果が一致しない点があります。原本で確認してください。\n",
+        primary_label, secondary_label
+    );
+    for finding in &diff.only_primary {
+        section.push_str(&format!("- ⚠ [{}のみ指摘] {}\n", primary_label, finding));
+    }
+    for finding in &diff.only_secondary {
+        section.push_str(&format!("- ⚠ [{}のみ指摘] {}\n", secondary_label, finding));
+    }
+    for conflict in &diff.field_conflicts {
+        section.push_str(&format!(
+            "- ⚠ [読み取り相違] {}: {}「{}」／{}「{}」\n",
+            conflict.label, primary_label, conflict.primary, secondary_label, conflict.secondary
+        ));
+    }
+    section.trim_end().to_string()
+}
+
+/// Add the consensus check to a single-file result when the settings ask
+/// for it; a failed second analysis is noted in the result instead
+pub fn with_consensus(temp_dir: &Path, request: &GeminiRequest<'_>, result: String) -> String {
+    let Some(settings) = load_settings().consensus else {
+        return result;
+    };
+    if !needs_consensus(&result, &settings) {
+        return result;
+    }
+    let model = settings.model.as_deref().unwrap_or(request.model);
+    let secondary_label = format!("{}（{}）", backend_label(settings.backend), model);
+    emit_app_log(&tr("consensus.start", &[&secondary_label]), "info");
+    // Only the first analysis is streamed to the result panel
+    let second_request = GeminiRequest {
+        model,
+        stream: false,
+        ..*request
+    };
+    match run_gemini_with(provider_for(settings.backend).as_ref(), temp_dir, &second_request) {
+        Ok(second) => {
+            let diff = compare_results(&result, &second);
+            let section = consensus_section(&diff, "1回目の解析", &secondary_label);
+            format!("{}{}", result, section)
+        }
+        Err(e) => {
+            emit_app_log(&tr("consensus.failed", &[&secondary_label, &e]), "warn");
+            format!(
+                "{}\n\n## 複数モデル照合\n⚠ {} での照合解析に失敗したため、二重チェックできていません",
+                result, secondary_label
+            )
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_consensus_settings() -> Option<ConsensusSettings> {
+    load_settings().consensus
+}
+
+/// 高額契約の複数モデル照合を設定（None で無効）
+#[tauri::command]
+pub fn set_consensus_settings(consensus: Option<ConsensusSettings>) -> Result<(), String> {
+    if let Some(consensus) = &consensus {
+        if consensus.min_amount <= 0 {
+            return Err("対象とする契約金額を指定してください".to_string());
+        }
+        if !provider_for(consensus.backend).is_available() {
+            return Err(format!(
+                "{} は利用できません（APIキーや接続先を設定してください）",
+                backend_label(consensus.backend)
+            ));
+        }
+    }
+    let mut settings = load_settings();
+    settings.consensus = consensus.map(|c| ConsensusSettings {
+        model: c.model.filter(|m| !m.trim().is_empty()),
+        ..c
+    });
+    save_settings(&settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIMARY: &str = "## 書類タイプ\n工事請負契約書\n\n契約金額: 12,000,000円\n工期: 2026年4月1日～2026年9月30日\n\n- ⚠ 収入印紙の貼付が確認できない\n- ⚠ 工期の終期が見積書と異なる";
+    const SECONDARY: &str = "## 書類タイプ\n工事請負契約書\n\n契約金額: 1,200万円\n工期: 2026年4月1日～2026年10月31日\n\n- ⚠ 工期の終期が見積書と異なっている\n- ⚠ 発注者の押印がない";
+
+    #[test]
+    fn only_contracts_above_the_amount_need_consensus() {
+        let settings = ConsensusSettings {
+            min_amount: 10_000_000,
+            backend: AiBackend::Claude,
+            model: None,
+        };
+        assert!(needs_consensus(PRIMARY, &settings));
+        let small = ConsensusSettings {
+            min_amount: 50_000_000,
+            ..settings.clone()
+        };
+        assert!(!needs_consensus(PRIMARY, &small));
+        let invoice = PRIMARY.replace("工事請負契約書", "請求書");
+        assert!(!needs_consensus(&invoice, &settings));
+    }
+
+    #[test]
+    fn disagreements_are_listed_for_review() {
+        let diff = compare_results(PRIMARY, SECONDARY);
+        assert_eq!(diff.only_primary, vec!["収入印紙の貼付が確認できない".to_string()]);
+        assert_eq!(diff.only_secondary, vec!["発注者の押印がない".to_string()]);
+        // 12,000,000円 and 1,200万円 are the same amount; the periods differ
+        let labels: Vec<&str> = diff.field_conflicts.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["工期"]);
+
+        let section = consensus_section(&diff, "1回目の解析", "Claude API");
+        assert!(section.contains("要人手確認"));
+        assert!(section.contains("⚠ [Claude APIのみ指摘] 発注者の押印がない"));
+        assert!(compare_results(PRIMARY, PRIMARY).is_agreed());
+    }
+}
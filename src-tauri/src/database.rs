@@ -0,0 +1,200 @@
+//! SQLite database for indexes and structured data
+//!
+//! Stored at `shoruichecker/shoruichecker.db` in the config directory.
+//! Schema changes are applied as numbered migrations tracked in
+//! `PRAGMA user_version`.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rusqlite::Connection;
+
+/// Schema migrations, applied in order. Never edit an existing entry.
+const MIGRATIONS: &[&str] = &[
+    // 1: RAG passage index
+    "CREATE TABLE rag_chunks (
+        id INTEGER PRIMARY KEY,
+        project_folder TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        chunk_index INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        embedding BLOB NOT NULL
+    );
+    CREATE INDEX idx_rag_chunks_project ON rag_chunks(project_folder);
+    CREATE INDEX idx_rag_chunks_file ON rag_chunks(file_path);",
+    // 2: Document classification (type, date, parties)
+    "CREATE TABLE document_metadata (
+        file_path TEXT PRIMARY KEY,
+        file_hash TEXT NOT NULL,
+        doc_type TEXT,
+        document_date TEXT,
+        parties TEXT NOT NULL,
+        classified_at TEXT NOT NULL
+    );",
+    // 3: User notes on files
+    "CREATE TABLE file_notes (
+        id INTEGER PRIMARY KEY,
+        file_path TEXT NOT NULL,
+        project_folder TEXT NOT NULL,
+        note TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_file_notes_file ON file_notes(file_path);
+    CREATE INDEX idx_file_notes_project ON file_notes(project_folder);",
+    // 4: Query index of the analysis history (mirrors the JSON history files)
+    "CREATE TABLE history_entries (
+        project_folder TEXT NOT NULL,
+        entry_id TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        file_name TEXT NOT NULL,
+        document_type TEXT,
+        analyzed_at TEXT NOT NULL,
+        summary TEXT NOT NULL,
+        issue_count INTEGER NOT NULL,
+        PRIMARY KEY (project_folder, entry_id)
+    );
+    CREATE INDEX idx_history_entries_analyzed ON history_entries(analyzed_at);
+    CREATE TABLE history_findings (
+        id INTEGER PRIMARY KEY,
+        project_folder TEXT NOT NULL,
+        entry_id TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        file_name TEXT NOT NULL,
+        document_type TEXT,
+        analyzed_at TEXT NOT NULL,
+        severity TEXT NOT NULL,
+        status TEXT NOT NULL,
+        issue TEXT NOT NULL
+    );
+    CREATE INDEX idx_history_findings_project ON history_findings(project_folder);
+    CREATE INDEX idx_history_findings_analyzed ON history_findings(analyzed_at);",
+    // 5: Expiry dates of documents (insurance policies, certificates)
+    "CREATE TABLE document_expiries (
+        file_path TEXT NOT NULL,
+        project_folder TEXT NOT NULL,
+        label TEXT NOT NULL,
+        expires_on TEXT NOT NULL,
+        recorded_at TEXT NOT NULL,
+        notified_at TEXT,
+        PRIMARY KEY (file_path, label)
+    );
+    CREATE INDEX idx_document_expiries_date ON document_expiries(expires_on);",
+    // 6: Extracted fields of documents (value and parties sealed)
+    "CREATE TABLE document_fields (
+        id INTEGER PRIMARY KEY,
+        file_path TEXT NOT NULL,
+        project_folder TEXT NOT NULL,
+        document_type TEXT,
+        label TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        value TEXT NOT NULL,
+        parties TEXT NOT NULL,
+        recorded_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_document_fields_file ON document_fields(file_path);
+    CREATE INDEX idx_document_fields_label ON document_fields(label);",
+    // 7: Contents of incoming PDFs already processed (duplicate detection)
+    "CREATE TABLE processed_files (
+        file_hash TEXT PRIMARY KEY,
+        first_path TEXT NOT NULL,
+        first_seen_at TEXT NOT NULL,
+        last_path TEXT NOT NULL,
+        last_seen_at TEXT NOT NULL,
+        duplicates INTEGER NOT NULL DEFAULT 0
+    );",
+    // 8: Outcomes of code reviews (review metrics)
+    "CREATE TABLE code_reviews (
+        id INTEGER PRIMARY KEY,
+        repo_folder TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        file_name TEXT NOT NULL,
+        reviewed_at TEXT NOT NULL,
+        has_issues INTEGER NOT NULL,
+        issue_count INTEGER NOT NULL
+    );
+    CREATE INDEX idx_code_reviews_repo ON code_reviews(repo_folder, reviewed_at);",
+    // 9: AI requests with their size and token counts (usage stats)
+    "CREATE TABLE ai_usage (
+        id INTEGER PRIMARY KEY,
+        project_folder TEXT,
+        backend TEXT NOT NULL,
+        model TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        prompt_chars INTEGER NOT NULL,
+        attachments INTEGER NOT NULL,
+        response_chars INTEGER NOT NULL,
+        input_tokens INTEGER,
+        output_tokens INTEGER,
+        success INTEGER NOT NULL
+    );
+    CREATE INDEX idx_ai_usage_started ON ai_usage(started_at);",
+    // 10: Single-file results by file content and prompt (result cache)
+    "CREATE TABLE analysis_cache (
+        file_hash TEXT NOT NULL,
+        prompt_hash TEXT NOT NULL,
+        model TEXT NOT NULL,
+        result TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (file_hash, prompt_hash)
+    );",
+    // 11: Embedding scheme of each RAG passage (older ones are re-indexed)
+    "ALTER TABLE rag_chunks ADD COLUMN embedding_version INTEGER NOT NULL DEFAULT 1;",
+    // 12: Projects whose history is mirrored into the history tables
+    "CREATE TABLE history_index_state (
+        project_folder TEXT PRIMARY KEY,
+        indexed_at TEXT NOT NULL
+    );",
+];
+
+/// Get the database file path
+pub fn get_db_path() -> PathBuf {
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("shoruichecker.db")
+}
+
+/// Open the app database, creating and migrating it as needed
+pub fn open_db() -> Result<Connection, String> {
+    let path = get_db_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let conn = Connection::open(&path).map_err(|e| format!("DB接続エラー: {}", e))?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+        .map_err(|e| e.to_string())?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// Apply pending migrations
+pub fn migrate(conn: &Connection) -> Result<(), String> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version.max(0) as usize) {
+        conn.execute_batch(sql)
+            .map_err(|e| format!("DBマイグレーションエラー ({}): {}", i + 1, e))?;
+        conn.pragma_update(None, "user_version", (i + 1) as i64)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_is_idempotent() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("first migrate");
+        migrate(&conn).expect("second migrate");
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .expect("version");
+        assert_eq!(version, MIGRATIONS.len() as i64);
+    }
+}
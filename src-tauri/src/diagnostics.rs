@@ -0,0 +1,290 @@
+//! Diagnostic bundles of failed AI requests
+//!
+//! When a request fails, the circumstances are recorded for support: prompt
+//! size, model, backend, CLI version, exit code, stderr and OS. Prompt and
+//! document contents are never stored, and paths, the user name and API keys
+//! are redacted. Bundles are kept in `shoruichecker/diagnostics.json` in the
+//! config directory (newest `MAX_BUNDLES`); the ID is appended to the error so
+//! users can export the matching bundle with `export_diagnostics`.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::Local;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{current_user, record_access};
+use crate::cli_setup::cached_cli_version;
+use crate::error::AppError;
+use crate::file_lock::write_atomic;
+use crate::gemini_cli::GeminiRequest;
+use crate::messages::tr;
+use crate::settings::AiBackend;
+use crate::storage::ensure_space_for;
+
+/// Bundles kept; older ones are dropped
+const MAX_BUNDLES: usize = 50;
+
+/// stderr beyond this is cut (the end is kept, it holds the actual error)
+const MAX_STDERR_CHARS: usize = 8000;
+
+/// Serializes read-modify-write access to the diagnostics file
+static DIAGNOSTICS_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// Exit code and stderr of the last failed CLI run on this thread
+    static LAST_PROCESS_FAILURE: RefCell<Option<(Option<i32>, String)>> = const { RefCell::new(None) };
+}
+
+/// Size and type of an attached file (never its name or content)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AttachmentInfo {
+    pub extension: String,
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiagnosticBundle {
+    /// `D%Y%m%d%H%M%S%3f`
+    pub id: String,
+    pub created_at: String,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub backend: AiBackend,
+    pub model: String,
+    pub output_format: String,
+    pub prompt_chars: usize,
+    pub prompt_bytes: usize,
+    pub attachments: Vec<AttachmentInfo>,
+    pub cli_version: Option<String>,
+    /// None: no CLI process ran or it was terminated
+    pub exit_code: Option<i32>,
+    pub stderr: Option<String>,
+    pub error: String,
+}
+
+pub fn get_diagnostics_path() -> PathBuf {
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("diagnostics.json")
+}
+
+pub fn load_bundles() -> Vec<DiagnosticBundle> {
+    fs::read_to_string(get_diagnostics_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Remember the exit code and stderr of a failed CLI run for the bundle
+pub fn note_process_failure(exit_code: Option<i32>, stderr: &str) {
+    LAST_PROCESS_FAILURE.with(|f| *f.borrow_mut() = Some((exit_code, stderr.to_string())));
+}
+
+/// Forget a failure noted by an earlier request on this thread
+pub fn clear_process_failure() {
+    LAST_PROCESS_FAILURE.with(|f| *f.borrow_mut() = None);
+}
+
+/// stderr of the last failed CLI run on this thread (kept for the bundle)
+pub fn last_process_stderr() -> Option<String> {
+    LAST_PROCESS_FAILURE.with(|f| f.borrow().as_ref().map(|(_, stderr)| stderr.clone()))
+}
+
+fn take_process_failure() -> Option<(Option<i32>, String)> {
+    LAST_PROCESS_FAILURE.with(|f| f.borrow_mut().take())
+}
+
+/// Remove what identifies the user or grants access: API keys, tokens,
+/// credentials in URLs, the temp dir, the home directory and the user name
+pub fn redact(text: &str, temp_dir: Option<&Path>) -> String {
+    let mut text = text.to_string();
+    if let Some(dir) = temp_dir {
+        text = text.replace(&*dir.to_string_lossy(), "<temp>");
+    }
+    if let Some(home) = dirs::home_dir() {
+        text = text.replace(&*home.to_string_lossy(), "<home>");
+    }
+    let patterns = [
+        (r"AIza[0-9A-Za-z_\-]{20,}", "<api-key>"),
+        (r"sk-[0-9A-Za-z_\-]{16,}", "<api-key>"),
+        (r"(?i)(bearer\s+)\S+", "$1<redacted>"),
+        (r"(?i)((?:token|api[_-]?key|password)\s*[:=]\s*)\S+", "$1<redacted>"),
+        (r"(://)[^/@\s]+@", "$1<redacted>@"),
+    ];
+    for (pattern, replacement) in patterns {
+        let re = Regex::new(pattern).expect("valid pattern");
+        text = re.replace_all(&text, replacement).to_string();
+    }
+    let user = current_user();
+    if user.len() > 2 && user != "unknown" {
+        text = text.replace(&user, "<user>");
+    }
+    text
+}
+
+fn tail(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+    let rest: String = text.chars().skip(count - max_chars).collect();
+    format!("…{}", rest)
+}
+
+fn attachments(temp_dir: &Path, request: &GeminiRequest<'_>) -> Vec<AttachmentInfo> {
+    request
+        .files
+        .unwrap_or_default()
+        .iter()
+        .map(|name| AttachmentInfo {
+            extension: Path::new(name)
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            size: fs::metadata(temp_dir.join(name)).map(|m| m.len()).unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Bundle of a failed request (redacted, without the CLI version)
+pub fn build_bundle(
+    temp_dir: &Path,
+    request: &GeminiRequest<'_>,
+    backend: AiBackend,
+    error: &AppError,
+) -> DiagnosticBundle {
+    let (exit_code, stderr) = match take_process_failure() {
+        Some((code, stderr)) => (code, Some(stderr)),
+        None => (None, None),
+    };
+    // The message of a failed CLI run also carries its stdout, which can
+    // quote the documents: keep only the status next to the stderr
+    let error = match (error, &stderr) {
+        (AppError::Process(_), Some(_)) => exit_code
+            .map(|c| format!("exit code {}", c))
+            .unwrap_or_else(|| "terminated".to_string()),
+        _ => tail(&redact(&error.to_string(), Some(temp_dir)), MAX_STDERR_CHARS),
+    };
+    let now = Local::now();
+    DiagnosticBundle {
+        id: now.format("D%Y%m%d%H%M%S%3f").to_string(),
+        created_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        backend,
+        model: request.model.to_string(),
+        output_format: request.output_format.to_string(),
+        prompt_chars: request.prompt.chars().count(),
+        prompt_bytes: request.prompt.len(),
+        attachments: attachments(temp_dir, request),
+        cli_version: None,
+        exit_code,
+        stderr: stderr.map(|s| tail(&redact(&s, Some(temp_dir)), MAX_STDERR_CHARS)),
+        error,
+    }
+}
+
+fn store_bundle(bundle: DiagnosticBundle) -> Result<(), String> {
+    let _lock = DIAGNOSTICS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut bundles = load_bundles();
+    bundles.push(bundle);
+    if bundles.len() > MAX_BUNDLES {
+        bundles.drain(..bundles.len() - MAX_BUNDLES);
+    }
+    let json = serde_json::to_string_pretty(&bundles).map_err(|e| e.to_string())?;
+    write_atomic(&get_diagnostics_path(), json)
+}
+
+/// Record a failed request of the given backend (the provider that actually
+/// failed); the returned error carries the bundle ID
+pub fn record_failure(
+    temp_dir: &Path,
+    request: &GeminiRequest<'_>,
+    backend: AiBackend,
+    error: AppError,
+) -> AppError {
+    let mut bundle = build_bundle(temp_dir, request, backend, &error);
+    if backend == AiBackend::Gemini {
+        bundle.cli_version = cached_cli_version();
+    }
+    let id = bundle.id.clone();
+    if store_bundle(bundle).is_err() {
+        return error;
+    }
+    let suffix = tr("diagnostics.id", &[&id]);
+    match error {
+        AppError::Io(msg) => AppError::Io(format!("{} {}", msg, suffix)),
+        AppError::Process(msg) => AppError::Process(format!("{} {}", msg, suffix)),
+        AppError::Json(msg) => AppError::Json(format!("{} {}", msg, suffix)),
+        AppError::Pdf(msg) => AppError::Pdf(format!("{} {}", msg, suffix)),
+        AppError::Timeout(msg) => AppError::Timeout(format!("{} {}", msg, suffix)),
+    }
+}
+
+/// 記録済みの診断情報（新しい順）
+#[tauri::command]
+pub fn list_diagnostics() -> Vec<DiagnosticBundle> {
+    let mut bundles = load_bundles();
+    bundles.reverse();
+    bundles
+}
+
+/// 解析エラーの診断情報を1つのファイルに書き出し（サポートへの送付用）
+#[tauri::command]
+pub fn export_diagnostics(entry_id: String, out_path: String) -> Result<(), String> {
+    let bundle = load_bundles()
+        .into_iter()
+        .find(|b| b.id == entry_id)
+        .ok_or_else(|| format!("診断情報が見つかりません: {}", entry_id))?;
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    ensure_space_for(Path::new(&out_path), json.len() as u64)?;
+    fs::write(&out_path, json).map_err(|e| format!("診断情報の保存エラー: {}", e))?;
+    record_access("export_diagnostics", &out_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_removes_keys_credentials_and_temp_paths() {
+        let temp = Path::new("/tmp/shoruichecker/.shoruichecker_temp_1");
+        let text = "GEMINI_API_KEY=AIzaSyA1234567890abcdefghijkl failed at \
+                    /tmp/shoruichecker/.shoruichecker_temp_1/prompt.txt \
+                    postgres://admin:secret@db/shorui Authorization: Bearer abc.def";
+        let redacted = redact(text, Some(temp));
+        assert!(!redacted.contains("AIzaSy"));
+        assert!(!redacted.contains("secret"));
+        assert!(!redacted.contains("abc.def"));
+        assert!(redacted.contains("<temp>/prompt.txt"));
+        assert!(redacted.contains("postgres://<redacted>@db/shorui"));
+    }
+
+    #[test]
+    fn bundle_records_sizes_and_the_process_failure() {
+        let dir = crate::gemini_cli::create_temp_dir(".shoruichecker_test_diag").expect("create dir");
+        fs::write(dir.join("a.PDF"), b"%PDF-1.7").unwrap();
+        let files = vec!["a.PDF".to_string()];
+        let request = GeminiRequest::text_with_files("契約書を確認", "gemini-2.5-pro", &files);
+        note_process_failure(Some(41), "Error: quota exceeded");
+        let error = AppError::Process("exit code 41: Error: quota exceeded\n契約金額 1,200,000円".to_string());
+        let bundle = build_bundle(&dir, &request, AiBackend::Gemini, &error);
+        crate::gemini_cli::cleanup_temp_dir(&dir);
+
+        assert_eq!((bundle.prompt_chars, bundle.prompt_bytes), (6, 18));
+        assert_eq!(bundle.attachments, vec![AttachmentInfo { extension: "pdf".to_string(), size: 8 }]);
+        assert_eq!(bundle.exit_code, Some(41));
+        assert_eq!(bundle.stderr.as_deref(), Some("Error: quota exceeded"));
+        // stdout in the error message is not kept
+        assert_eq!(bundle.error, "exit code 41");
+        // The failure is consumed by the bundle
+        assert_eq!(take_process_failure(), None);
+        assert_eq!(tail("abcdef", 3), "…def");
+    }
+}
@@ -0,0 +1,270 @@
+//! Environment diagnostics (doctor)
+//!
+//! Checks everything an analysis depends on and returns a checklist with a
+//! hint for each problem, for the settings screen and `shoruichecker doctor`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use serde::Serialize;
+
+#[cfg(target_os = "windows")]
+use crate::CREATE_NO_WINDOW;
+
+use crate::cli_setup::{is_outdated, parse_cli_version, MIN_GEMINI_CLI_VERSION};
+use crate::gemini_cli::{gemini_cmd_path, is_onedrive_path, temp_root};
+use crate::settings::{get_settings_path, load_settings};
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// One item of the diagnostics checklist
+#[derive(Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What the user should do when the check is not ok
+    pub hint: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>, hint: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+            hint: hint.map(|h| h.to_string()),
+        }
+    }
+}
+
+/// Run a command and return its trimmed stdout if it succeeded
+pub fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// npm executable name for this platform
+pub fn npm_cmd() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "npm.cmd"
+    } else {
+        "npm"
+    }
+}
+
+/// Check item for a tool found by running `<tool> --version`
+pub fn version_check(name: &str, version: Option<String>, hint: &str) -> DiagnosticCheck {
+    match version {
+        Some(v) if !v.is_empty() => DiagnosticCheck::new(name, CheckStatus::Ok, v, None),
+        _ => DiagnosticCheck::new(name, CheckStatus::Error, "見つかりません", Some(hint)),
+    }
+}
+
+/// Whether the gemini CLI has credentials (OAuth login or API key)
+pub fn gemini_auth_source(home: Option<&Path>, env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    for key in ["GEMINI_API_KEY", "GOOGLE_API_KEY"] {
+        if env(key).map(|v| !v.is_empty()).unwrap_or(false) {
+            return Some(format!("環境変数 {}", key));
+        }
+    }
+    let creds = home?.join(".gemini").join("oauth_creds.json");
+    creds.is_file().then(|| "Googleアカウントでログイン済み".to_string())
+}
+
+fn check_auth() -> DiagnosticCheck {
+    let home = dirs::home_dir();
+    match gemini_auth_source(home.as_deref(), |k| std::env::var(k).ok()) {
+        Some(source) => DiagnosticCheck::new("Gemini 認証", CheckStatus::Ok, source, None),
+        None => DiagnosticCheck::new(
+            "Gemini 認証",
+            CheckStatus::Error,
+            "認証情報がありません",
+            Some("コマンドプロンプトで gemini を起動し、Googleアカウントでログインしてください"),
+        ),
+    }
+}
+
+/// Check that files can be created and removed in `dir`
+pub fn check_writable(name: &str, dir: &Path) -> DiagnosticCheck {
+    let probe = dir.join(".shoruichecker_write_test");
+    let result = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => DiagnosticCheck::new(name, CheckStatus::Ok, dir.to_string_lossy(), None),
+        Err(e) => DiagnosticCheck::new(
+            name,
+            CheckStatus::Error,
+            format!("{}: {}", dir.display(), e),
+            Some("フォルダのアクセス権を確認してください"),
+        ),
+    }
+}
+
+/// Check the temp root: writable and not synced by OneDrive
+fn check_temp_root() -> DiagnosticCheck {
+    let root = temp_root();
+    let check = check_writable("作業フォルダ", &root);
+    if check.status == CheckStatus::Ok && is_onedrive_path(&root, |k| std::env::var(k).ok()) {
+        return DiagnosticCheck::new(
+            "作業フォルダ",
+            CheckStatus::Warning,
+            format!("{}（OneDrive の同期対象）", root.display()),
+            Some("同期対象外のローカルフォルダを設定画面で指定してください"),
+        );
+    }
+    check
+}
+
+/// Check that the watch folder is configured and reachable
+pub fn check_watch_folder(folder: Option<&str>) -> DiagnosticCheck {
+    const NAME: &str = "監視フォルダ";
+    match folder {
+        None => DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Warning,
+            "未設定",
+            Some("設定画面で監視フォルダを選択してください"),
+        ),
+        Some(folder) if Path::new(folder).is_dir() => {
+            DiagnosticCheck::new(NAME, CheckStatus::Ok, folder, None)
+        }
+        Some(folder) => DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Error,
+            format!("{}（アクセスできません）", folder),
+            Some("ネットワークドライブの接続やフォルダの移動・削除を確認してください"),
+        ),
+    }
+}
+
+/// Run all checks
+pub fn run_diagnostics() -> Vec<DiagnosticCheck> {
+    let config_dir = get_settings_path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let mut gemini = version_check(
+        "Gemini CLI",
+        command_output(&gemini_cmd_path(), &["--version"]),
+        "Gemini CLI をインストールしてください（npm install -g @google/gemini-cli）",
+    );
+    if gemini.status == CheckStatus::Ok
+        && parse_cli_version(&gemini.detail).is_some_and(|v| is_outdated(&v))
+    {
+        gemini.status = CheckStatus::Warning;
+        gemini.hint = Some(format!(
+            "{} 以上に更新してください（設定画面の「Gemini CLI を更新」）",
+            MIN_GEMINI_CLI_VERSION
+        ));
+    }
+
+    vec![
+        gemini,
+        version_check(
+            "Node.js",
+            command_output("node", &["--version"]),
+            "https://nodejs.org から Node.js（LTS）をインストールしてください",
+        ),
+        version_check(
+            "npm",
+            command_output(npm_cmd(), &["--version"]),
+            "Node.js を再インストールすると npm も入ります",
+        ),
+        check_auth(),
+        check_writable("設定フォルダへの書き込み", &config_dir),
+        check_temp_root(),
+        check_watch_folder(load_settings().watch_folder.as_deref()),
+    ]
+}
+
+/// 動作環境を診断
+#[tauri::command]
+pub fn diagnose_environment() -> Vec<DiagnosticCheck> {
+    run_diagnostics()
+}
+
+/// Print the checklist for `shoruichecker doctor`; returns false on errors
+pub fn run_doctor_cli() -> bool {
+    let checks = run_diagnostics();
+    for check in &checks {
+        let mark = match check.status {
+            CheckStatus::Ok => "[OK]  ",
+            CheckStatus::Warning => "[WARN]",
+            CheckStatus::Error => "[NG]  ",
+        };
+        println!("{} {}: {}", mark, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("       → {}", hint);
+        }
+    }
+    checks.iter().all(|c| c.status != CheckStatus::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn version_check_reports_missing_tool() {
+        let check = version_check("node", None, "install");
+        assert_eq!(check.status, CheckStatus::Error);
+        assert_eq!(check.hint.as_deref(), Some("install"));
+
+        let check = version_check("node", Some("v20.11.0".to_string()), "install");
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert_eq!(check.detail, "v20.11.0");
+    }
+
+    #[test]
+    fn auth_source_prefers_api_key_then_oauth_file() {
+        let dir = create_temp_dir(".shoruichecker_test_doctor").expect("dir");
+        let no_env = |_: &str| None;
+        assert!(gemini_auth_source(Some(&dir), no_env).is_none());
+
+        fs::create_dir_all(dir.join(".gemini")).unwrap();
+        fs::write(dir.join(".gemini").join("oauth_creds.json"), "{}").unwrap();
+        assert!(gemini_auth_source(Some(&dir), no_env).is_some());
+
+        let env = |k: &str| (k == "GEMINI_API_KEY").then(|| "key".to_string());
+        assert_eq!(
+            gemini_auth_source(None, env).as_deref(),
+            Some("環境変数 GEMINI_API_KEY")
+        );
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn writable_and_watch_folder_checks() {
+        let dir = create_temp_dir(".shoruichecker_test_doctor").expect("dir");
+        assert_eq!(check_writable("config", &dir).status, CheckStatus::Ok);
+
+        let folder = dir.to_string_lossy().to_string();
+        assert_eq!(check_watch_folder(Some(folder.as_str())).status, CheckStatus::Ok);
+        assert_eq!(check_watch_folder(None).status, CheckStatus::Warning);
+        let missing = dir.join("missing").to_string_lossy().to_string();
+        assert_eq!(check_watch_folder(Some(missing.as_str())).status, CheckStatus::Error);
+        cleanup_temp_dir(&dir);
+    }
+}
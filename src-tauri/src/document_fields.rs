@@ -0,0 +1,436 @@
+//! Extracted fields of every analyzed document, queryable across projects
+//!
+//! After each analysis the `項目: 値` fields of the result are stored in the
+//! database with a kind (amount, date, party, text) and the document's
+//! parties. Queries filter by party, label, kind, type and project, so lists
+//! like "この受注者の全契約金額" can be built over all projects. Values are
+//! sealed like other database text; filtering happens after opening them.
+
+use chrono::{Local, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::record_access;
+use crate::classify::DocumentMetadata;
+use crate::database::open_db;
+use crate::encryption::{open_text, seal_text};
+use crate::expiry::dates_in;
+use crate::structured_report::extract_fields;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Labels of fields holding an amount of money
+const AMOUNT_LABEL_MARKERS: &[&str] = &["金額", "代金", "価格", "費用", "税", "単価", "合計", "小計"];
+/// Labels of fields naming a company or person
+const PARTY_LABEL_MARKERS: &[&str] = &[
+    "発注者", "受注者", "請負者", "注文者", "元請", "下請", "提出先", "提出者", "会社名", "商号",
+    "宛先", "代表者", "氏名",
+];
+const DATE_LABEL_MARKERS: &[&str] = &["日", "期間", "期限", "工期"];
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Amount,
+    Date,
+    Party,
+    Text,
+}
+
+impl FieldKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldKind::Amount => "amount",
+            FieldKind::Date => "date",
+            FieldKind::Party => "party",
+            FieldKind::Text => "text",
+        }
+    }
+
+    fn parse(s: &str) -> FieldKind {
+        match s {
+            "amount" => FieldKind::Amount,
+            "date" => FieldKind::Date,
+            "party" => FieldKind::Party,
+            _ => FieldKind::Text,
+        }
+    }
+}
+
+/// Labels of the amount that stands for a whole document, best first
+const TOTAL_LABEL_PRIORITY: &[&str] = &[
+    "請負代金額", "契約金額", "税込合計", "合計金額", "総額", "合計", "請求金額", "見積金額",
+];
+
+/// Digits (with thousands separators and a decimal part) starting at `i`
+fn read_number(chars: &[char], i: &mut usize) -> Option<f64> {
+    let mut number = String::new();
+    while let Some(&c) = chars.get(*i) {
+        let decimal_point = c == '.' && chars.get(*i + 1).is_some_and(|n| n.is_ascii_digit());
+        if !(c.is_ascii_digit() || c == ',' || decimal_point) {
+            break;
+        }
+        if c != ',' {
+            number.push(c);
+        }
+        *i += 1;
+    }
+    number.parse().ok()
+}
+
+/// Amount in yen written in a value ("1,234,567円", "￥1,234,567-",
+/// "120万円", "1億2,000万円", "1.5億円")
+///
+/// Percentages (税率 10%) are skipped; the first other number is the amount.
+pub fn parse_amount(value: &str) -> Option<i64> {
+    let chars: Vec<char> = value
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            '，' => ',',
+            '．' => '.',
+            '％' => '%',
+            _ => c,
+        })
+        .collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        // Compound amounts: each 億/万 part followed directly by the next
+        let mut total = 0.0;
+        loop {
+            let number = read_number(&chars, &mut i)?;
+            let unit = match chars.get(i) {
+                Some('億') => 100_000_000.0,
+                Some('万') => 10_000.0,
+                _ => 1.0,
+            };
+            total += number * unit;
+            if unit > 1.0 && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                i += 1;
+                continue;
+            }
+            break;
+        }
+        if chars.get(i) == Some(&'%') {
+            continue;
+        }
+        return Some(total.round() as i64);
+    }
+    None
+}
+
+/// Amount standing for a whole document among its (label, amount) fields
+///
+/// The first field of the best label in `TOTAL_LABEL_PRIORITY` (so 小計 and
+/// 消費税 are not added to 合計), else the first amount of the document.
+pub fn designated_amount(amounts: &[(&str, i64)]) -> Option<i64> {
+    TOTAL_LABEL_PRIORITY
+        .iter()
+        .find_map(|marker| amounts.iter().find(|(label, _)| label.contains(marker)))
+        .or_else(|| amounts.first())
+        .map(|(_, amount)| *amount)
+}
+
+/// Kind of a field from its label and value
+pub fn field_kind(label: &str, value: &str) -> FieldKind {
+    let has_digit = value.chars().any(|c| c.is_ascii_digit() || ('０'..='９').contains(&c));
+    if PARTY_LABEL_MARKERS.iter().any(|m| label.contains(m)) {
+        FieldKind::Party
+    } else if has_digit
+        && (AMOUNT_LABEL_MARKERS.iter().any(|m| label.contains(m)) || value.contains('円'))
+        && parse_amount(value).is_some()
+    {
+        FieldKind::Amount
+    } else if DATE_LABEL_MARKERS.iter().any(|m| label.contains(m)) && !dates_in(value).is_empty() {
+        FieldKind::Date
+    } else {
+        FieldKind::Text
+    }
+}
+
+/// A stored field of a document
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FieldRow {
+    pub file_path: String,
+    pub file_name: String,
+    pub project_folder: String,
+    pub document_type: Option<String>,
+    pub label: String,
+    pub kind: FieldKind,
+    pub value: String,
+    /// Yen, for amount fields
+    pub amount: Option<i64>,
+    /// YYYY-MM-DD, for date fields (last date of a period)
+    pub date: Option<String>,
+    /// Parties of the document (party fields and classification)
+    pub parties: Vec<String>,
+}
+
+/// Filters of a field query (all optional)
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FieldQuery {
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub document_type: Option<String>,
+    /// Substring of the field label (e.g. 契約金額)
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub kind: Option<FieldKind>,
+    /// Substring of one of the document's parties
+    #[serde(default)]
+    pub party: Option<String>,
+}
+
+/// Fields matching a query, with the sum of one designated amount per document
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldQueryResult {
+    pub rows: Vec<FieldRow>,
+    pub total_amount: i64,
+}
+
+/// Replace the stored fields of a document
+pub fn store_fields(
+    conn: &Connection,
+    project_folder: &str,
+    file_path: &str,
+    result: &str,
+    metadata: Option<&DocumentMetadata>,
+) -> Result<(), String> {
+    let document_type = metadata.and_then(|m| m.doc_type.as_deref());
+    let fields = extract_fields(result);
+    let mut parties: Vec<String> = metadata.map(|m| m.parties.clone()).unwrap_or_default();
+    for field in &fields {
+        if field_kind(&field.label, &field.value) == FieldKind::Party && !parties.contains(&field.value) {
+            parties.push(field.value.clone());
+        }
+    }
+    let sealed_parties = seal_text(&serde_json::to_string(&parties).map_err(|e| e.to_string())?)?;
+    let now = Local::now().format(TIMESTAMP_FORMAT).to_string();
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM document_fields WHERE file_path = ?1", params![file_path])
+        .map_err(|e| e.to_string())?;
+    for field in &fields {
+        tx.execute(
+            "INSERT INTO document_fields
+             (file_path, project_folder, document_type, label, kind, value, parties, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                file_path,
+                project_folder,
+                document_type,
+                field.label,
+                field_kind(&field.label, &field.value).as_str(),
+                seal_text(&field.value)?,
+                sealed_parties,
+                now
+            ],
+        )
+        .map_err(|e| format!("抽出項目の保存エラー: {}", e))?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Record the fields of an analysis result (best effort)
+pub fn record_fields(
+    project_folder: &str,
+    file_path: &str,
+    result: &str,
+    metadata: Option<&DocumentMetadata>,
+) {
+    if let Ok(conn) = open_db() {
+        let _ = store_fields(&conn, project_folder, file_path, result, metadata);
+    }
+}
+
+/// (file_path, project_folder, document_type, label, kind, sealed value, sealed parties)
+type StoredField = (String, String, Option<String>, String, String, String, String);
+
+fn field_row(stored: StoredField) -> Option<FieldRow> {
+    let (file_path, project_folder, document_type, label, kind, value, parties) = stored;
+    let value = open_text(value).ok()?;
+    let kind = FieldKind::parse(&kind);
+    let file_name = std::path::Path::new(&file_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Some(FieldRow {
+        amount: (kind == FieldKind::Amount).then(|| parse_amount(&value)).flatten(),
+        date: (kind == FieldKind::Date)
+            .then(|| dates_in(&value).last().map(NaiveDate::to_string))
+            .flatten(),
+        parties: open_text(parties)
+            .ok()
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default(),
+        file_path,
+        file_name,
+        project_folder,
+        document_type,
+        label,
+        kind,
+        value,
+    })
+}
+
+/// Fields matching a query, ordered by project and file
+pub fn find_fields(conn: &Connection, query: &FieldQuery) -> Result<FieldQueryResult, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, project_folder, document_type, label, kind, value, parties
+             FROM document_fields
+             WHERE (?1 IS NULL OR project_folder = ?1)
+               AND (?2 IS NULL OR document_type = ?2)
+               AND (?3 IS NULL OR kind = ?3)
+               AND (?4 IS NULL OR instr(label, ?4) > 0)
+             ORDER BY project_folder, file_path, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let stored: Vec<StoredField> = stmt
+        .query_map(
+            params![
+                query.project,
+                query.document_type,
+                query.kind.map(FieldKind::as_str),
+                query.label
+            ],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .filter_map(Result::ok)
+        .collect();
+    let party = query.party.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    let rows: Vec<FieldRow> = stored
+        .into_iter()
+        .filter_map(field_row)
+        .filter(|row| party.is_none_or(|party| row.parties.iter().any(|p| p.contains(party))))
+        .collect();
+    let total_amount = rows
+        .chunk_by(|a, b| a.file_path == b.file_path)
+        .filter_map(|document| {
+            let amounts: Vec<(&str, i64)> = document
+                .iter()
+                .filter_map(|r| r.amount.map(|amount| (r.label.as_str(), amount)))
+                .collect();
+            designated_amount(&amounts)
+        })
+        .sum();
+    Ok(FieldQueryResult { total_amount, rows })
+}
+
+/// 抽出項目を全工事から検索（例: 受注者を指定して契約金額の一覧と合計）
+#[tauri::command]
+pub fn query_document_fields(query: FieldQuery) -> Result<FieldQueryResult, String> {
+    record_access("query_document_fields", query.project.as_deref().unwrap_or(""));
+    find_fields(&open_db()?, &query)
+}
+
+/// 登録されている当事者（発注者・受注者など）の一覧
+#[tauri::command]
+pub fn list_document_parties() -> Result<Vec<String>, String> {
+    record_access("list_document_parties", "");
+    let result = find_fields(&open_db()?, &FieldQuery::default())?;
+    let mut parties: Vec<String> = result.rows.into_iter().flat_map(|r| r.parties).collect();
+    parties.sort();
+    parties.dedup();
+    Ok(parties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    #[test]
+    fn field_kinds_and_amounts() {
+        assert_eq!(parse_amount("1,234,567円（税込）"), Some(1_234_567));
+        assert_eq!(parse_amount("￥１，２００，０００-"), Some(1_200_000));
+        assert_eq!(parse_amount("120万円"), Some(1_200_000));
+        assert_eq!(parse_amount("1億2,000万円"), Some(120_000_000));
+        assert_eq!(parse_amount("1.5億円"), Some(150_000_000));
+        assert_eq!(parse_amount("10%"), None);
+        assert_eq!(parse_amount("消費税（１０％）100,000円"), Some(100_000));
+        assert_eq!(field_kind("消費税率", "10%"), FieldKind::Text);
+        assert_eq!(field_kind("請負代金額", "1,100,000円"), FieldKind::Amount);
+        assert_eq!(field_kind("受注者", "山田建設株式会社"), FieldKind::Party);
+        assert_eq!(field_kind("工期", "2024年4月1日〜2024年9月30日"), FieldKind::Date);
+        assert_eq!(field_kind("工事名", "○○線道路改良工事"), FieldKind::Text);
+    }
+
+    #[test]
+    fn amounts_are_listed_per_party_across_projects() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let contract = |amount: &str, contractor: &str| {
+            format!("## 契約書\n- 受注者: {}\n- 請負代金額: {}\n- 工期: 2024年4月1日〜2024年9月30日", contractor, amount)
+        };
+        store_fields(&conn, "C:/p1", "C:/p1/契約書.pdf", &contract("1,100,000円", "山田建設株式会社"), None)
+            .expect("store");
+        store_fields(&conn, "C:/p2", "C:/p2/契約書.pdf", &contract("2,200,000円", "山田建設株式会社"), None)
+            .expect("store");
+        let metadata = DocumentMetadata {
+            parties: vec!["佐藤工業".to_string()],
+            ..Default::default()
+        };
+        store_fields(&conn, "C:/p3", "C:/p3/契約書.pdf", "- 請負代金額: 500,000円", Some(&metadata))
+            .expect("store");
+
+        let query = FieldQuery {
+            party: Some("山田建設".to_string()),
+            kind: Some(FieldKind::Amount),
+            ..Default::default()
+        };
+        let result = find_fields(&conn, &query).expect("query");
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.total_amount, 3_300_000);
+        assert_eq!(result.rows[0].project_folder, "C:/p1");
+
+        let by_classification = find_fields(
+            &conn,
+            &FieldQuery { party: Some("佐藤".to_string()), ..Default::default() },
+        )
+        .expect("query");
+        assert_eq!(by_classification.rows.len(), 1);
+
+        // Re-analysis replaces the fields of the file
+        store_fields(&conn, "C:/p1", "C:/p1/契約書.pdf", "- 請負代金額: 1,000円", None)
+            .expect("store");
+        let dates = find_fields(
+            &conn,
+            &FieldQuery { kind: Some(FieldKind::Date), ..Default::default() },
+        )
+        .expect("query");
+        assert_eq!(dates.rows.len(), 1);
+        assert_eq!(dates.rows[0].date.as_deref(), Some("2024-09-30"));
+    }
+
+    #[test]
+    fn totals_take_one_amount_per_document() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let invoice = "- 小計: 1,000,000円\n- 消費税: 100,000円\n- 合計: 1,100,000円";
+        store_fields(&conn, "C:/p1", "C:/p1/請求書.pdf", invoice, None).expect("store");
+        store_fields(&conn, "C:/p1", "C:/p1/見積書.pdf", "- 見積金額: 500,000円", None).expect("store");
+
+        let query = FieldQuery { kind: Some(FieldKind::Amount), ..Default::default() };
+        let result = find_fields(&conn, &query).expect("query");
+        assert_eq!(result.rows.len(), 4);
+        assert_eq!(result.total_amount, 1_600_000);
+    }
+}
@@ -0,0 +1,169 @@
+//! Upgrade of embedded results after a format change
+//!
+//! PDFs analyzed with an older app carry their result in an older embedded
+//! format (see `EMBED_SCHEMA_VERSION`). The upgrade walks a folder and its
+//! subfolders and rewrites the metadata of those PDFs in the current format,
+//! keeping the result, instruction and analysis date. Files that cannot be
+//! rewritten (原本保護, locked or broken PDFs) are reported with the reason.
+//! Sidecars have no format version and are left alone.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::access::{ensure_allowed, Operation};
+use crate::archive::file_sha256;
+use crate::audit::record_access;
+use crate::history::{load_history, update_history, AnalysisHistoryEntry};
+use crate::pdf_embed::{
+    is_older_schema, read_embedded_data_from_pdf, read_embedded_version, write_embedded_data,
+};
+use crate::project_settings::project_folder_for;
+use crate::tasks::run_blocking;
+
+/// A PDF that could not be upgraded
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct UpgradeFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Outcome of an upgrade
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct UpgradeReport {
+    /// PDFs rewritten in the current format
+    pub upgraded: Vec<String>,
+    /// PDFs already in the current (or a newer) format
+    pub current: usize,
+    /// PDFs without an embedded result
+    pub without_result: usize,
+    pub failed: Vec<UpgradeFailure>,
+}
+
+/// PDFs in the folder and its subfolders (hidden folders skipped), sorted
+fn collect_pdfs(folder: &Path, pdfs: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if !hidden {
+                collect_pdfs(&path, pdfs);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+        {
+            pdfs.push(path);
+        }
+    }
+}
+
+/// Rewrite one PDF in the current format and keep its history entries
+/// pointing at the new file content (for freshness checks)
+fn upgrade_pdf(path: &str) -> Result<(), String> {
+    let data = read_embedded_data_from_pdf(path).ok_or("埋め込みデータを読み取れません")?;
+    let old_hash = file_sha256(Path::new(path)).ok();
+    write_embedded_data(path, &data)?;
+    let new_hash = file_sha256(Path::new(path)).ok();
+    let is_previous = |entry: &AnalysisHistoryEntry| {
+        entry.file_path == path && old_hash.is_some() && entry.file_hash == old_hash
+    };
+    let project_folder = project_folder_for(path);
+    if load_history(&project_folder)?.entries.iter().any(&is_previous) {
+        update_history(&project_folder, |history| {
+            for entry in history.entries.iter_mut().filter(|e| is_previous(e)) {
+                entry.file_hash = new_hash.clone();
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Upgrade the embedded results of every PDF under `folder`
+pub fn upgrade_folder(folder: &Path) -> UpgradeReport {
+    let mut pdfs = Vec::new();
+    collect_pdfs(folder, &mut pdfs);
+    pdfs.sort();
+
+    let mut report = UpgradeReport::default();
+    for pdf in pdfs {
+        let path = pdf.to_string_lossy().to_string();
+        match read_embedded_version(&path) {
+            Err(error) => report.failed.push(UpgradeFailure { path, error }),
+            Ok(None) => report.without_result += 1,
+            Ok(Some(version)) if !is_older_schema(&version) => report.current += 1,
+            Ok(Some(_)) => match upgrade_pdf(&path) {
+                Ok(()) => report.upgraded.push(path),
+                Err(error) => report.failed.push(UpgradeFailure { path, error }),
+            },
+        }
+    }
+    report
+}
+
+/// フォルダ内（サブフォルダを含む）のPDFに埋め込まれた解析結果を現在の形式に更新
+#[tauri::command]
+pub async fn upgrade_embedded_results(folder: String) -> Result<UpgradeReport, String> {
+    ensure_allowed(Operation::Embed)?;
+    if !Path::new(&folder).is_dir() {
+        return Err(format!("フォルダが見つかりません: {}", folder));
+    }
+    record_access("upgrade_embedded_results", &folder);
+    let label = folder.clone();
+    run_blocking("embed_upgrade", &label, move || upgrade_folder(Path::new(&folder))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+    use crate::pdf_embed::{base64_encode, EMBED_SCHEMA_VERSION};
+    use crate::report::write_text_pdf;
+    use lopdf::{Document, Object, StringFormat};
+
+    /// A PDF with a result embedded the way version 1.0 did
+    fn write_v1_pdf(path: &Path, result: &str) {
+        write_text_pdf(path, "請求書", None).expect("pdf");
+        let mut doc = Document::load(path).expect("load");
+        let mut info = lopdf::Dictionary::new();
+        let text = |s: &str| Object::String(s.as_bytes().to_vec(), StringFormat::Literal);
+        info.set("ShoruiCheckerResult", text(&base64_encode(result)));
+        info.set("ShoruiCheckerDate", text("2025-06-01 09:00:00"));
+        info.set("ShoruiCheckerVersion", text("1.0"));
+        let info_id = doc.add_object(Object::Dictionary(info));
+        doc.trailer.set("Info", Object::Reference(info_id));
+        doc.save(path).expect("save");
+    }
+
+    #[test]
+    fn old_results_are_rewritten_keeping_their_date() {
+        let dir = create_temp_dir(".shoruichecker_test_embed_upgrade").expect("create dir");
+        fs::create_dir_all(dir.join("2025")).unwrap();
+        let old = dir.join("2025").join("請求書.pdf");
+        write_v1_pdf(&old, "- ⚠ 金額が見積書と異なる");
+        write_text_pdf(&dir.join("未解析.pdf"), "未解析", None).expect("pdf");
+        fs::write(dir.join("壊れ.pdf"), b"%PDF-broken").unwrap();
+
+        let report = upgrade_folder(&dir);
+        assert_eq!(report.upgraded, vec![old.to_string_lossy().to_string()]);
+        assert_eq!(report.without_result, 1);
+        let broken = dir.join("壊れ.pdf").to_string_lossy().to_string();
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, broken);
+        let old = old.to_string_lossy().to_string();
+        assert_eq!(read_embedded_version(&old).unwrap().as_deref(), Some(EMBED_SCHEMA_VERSION));
+        let data = read_embedded_data_from_pdf(&old).expect("data");
+        assert_eq!(data.date, "2025-06-01 09:00:00");
+        assert_eq!(data.result, "- ⚠ 金額が見積書と異なる");
+
+        let again = upgrade_folder(&dir);
+        assert!(again.upgraded.is_empty());
+        assert_eq!(again.current, 1);
+        cleanup_temp_dir(&dir);
+    }
+}
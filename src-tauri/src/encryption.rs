@@ -0,0 +1,291 @@
+//! Optional at-rest encryption of results
+//!
+//! History files, sidecars and the text columns of the database contain
+//! contract amounts and personal names. With `encrypt_at_rest` enabled they
+//! are written encrypted with AES-256-GCM. The key is generated on first
+//! use and kept in the OS keychain (Windows Credential Manager), never in
+//! the config directory. Readers decrypt transparently and still accept
+//! plaintext written before the setting was enabled.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use serde::Serialize;
+
+use crate::database::open_db;
+use crate::history::{load_all_histories, save_history};
+use crate::keychain::{load_secret, read_secret, store_secret};
+use crate::settings::{load_settings, save_settings};
+
+/// Marks encrypted files: magic, 12-byte nonce, ciphertext
+const MAGIC: &[u8] = b"SHORUIENC1";
+const NONCE_LEN: usize = 12;
+
+/// Marks encrypted text (database columns): prefix + base64 of the file format
+const TEXT_PREFIX: &str = "enc1:";
+
+const KEYRING_USER: &str = "result-encryption-key";
+
+// Key loaded from the keychain (cached for the process lifetime)
+static KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+
+/// Key from the keychain, generated and stored on first use
+fn encryption_key() -> Result<[u8; 32], String> {
+    let mut cached = KEY.lock().map_err(|e| e.to_string())?;
+    if let Some(key) = *cached {
+        return Ok(key);
+    }
+    let encoded = match load_secret(KEYRING_USER)? {
+        Some(encoded) => encoded,
+        None => {
+            let encoded = general_purpose::STANDARD.encode(Aes256Gcm::generate_key(OsRng));
+            store_secret(KEYRING_USER, &encoded)?;
+            encoded
+        }
+    };
+    let bytes = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("暗号化キーが不正です: {}", e))?;
+    let key: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| "暗号化キーの長さが不正です".to_string())?;
+    *cached = Some(key);
+    Ok(key)
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn encrypt_with(key: &[u8; 32], plain: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain)
+        .map_err(|_| "暗号化エラー".to_string())?;
+    let mut data = MAGIC.to_vec();
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+pub fn decrypt_with(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, String> {
+    let body = data
+        .strip_prefix(MAGIC)
+        .filter(|b| b.len() > NONCE_LEN)
+        .ok_or_else(|| "暗号化データが不正です".to_string())?;
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "復号できません（キーが異なるか、データが破損しています）".to_string())
+}
+
+pub fn encryption_enabled() -> bool {
+    load_settings().encrypt_at_rest
+}
+
+/// Bytes as they should be stored (encrypted if enabled)
+pub fn seal_bytes(plain: &[u8]) -> Result<Vec<u8>, String> {
+    if encryption_enabled() {
+        encrypt_with(&encryption_key()?, plain)
+    } else {
+        Ok(plain.to_vec())
+    }
+}
+
+/// Stored bytes as plaintext (decrypted if encrypted)
+pub fn open_bytes(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    if is_encrypted(&data) {
+        decrypt_with(&encryption_key()?, &data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Text as it should be stored in a database column
+pub fn seal_text(text: &str) -> Result<String, String> {
+    if encryption_enabled() {
+        let data = encrypt_with(&encryption_key()?, text.as_bytes())?;
+        Ok(format!("{}{}", TEXT_PREFIX, general_purpose::STANDARD.encode(data)))
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+/// Stored database text as plaintext
+pub fn open_text(stored: String) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(TEXT_PREFIX) else {
+        return Ok(stored);
+    };
+    let data = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("暗号化データが不正です: {}", e))?;
+    let plain = decrypt_with(&encryption_key()?, &data)?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// Read a (possibly encrypted) text file
+pub fn read_string(path: &Path) -> Option<String> {
+    let data = open_bytes(fs::read(path).ok()?).ok()?;
+    String::from_utf8(data).ok()
+}
+
+/// Rewrite stored data in the current mode (encrypted or plaintext)
+///
+/// Sidecars are spread over the project folders and are converted the next
+/// time they are written.
+fn reencrypt_stored_data() -> Result<usize, String> {
+    let mut converted = 0;
+    for history in load_all_histories() {
+        save_history(&history)?;
+        converted += 1;
+    }
+
+    let conn = open_db()?;
+    let rows: Vec<(i64, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT id, text FROM rag_chunks")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(Result::ok).collect()
+    };
+    for (id, text) in rows {
+        conn.execute(
+            "UPDATE rag_chunks SET text = ?1 WHERE id = ?2",
+            rusqlite::params![seal_text(&open_text(text)?)?, id],
+        )
+        .map_err(|e| e.to_string())?;
+        converted += 1;
+    }
+
+    let rows: Vec<(String, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT file_path, parties FROM document_metadata")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(Result::ok).collect()
+    };
+    for (file_path, parties) in rows {
+        conn.execute(
+            "UPDATE document_metadata SET parties = ?1 WHERE file_path = ?2",
+            rusqlite::params![seal_text(&open_text(parties)?)?, file_path],
+        )
+        .map_err(|e| e.to_string())?;
+        converted += 1;
+    }
+    let rows: Vec<(i64, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT id, note FROM file_notes")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(Result::ok).collect()
+    };
+    for (id, note) in rows {
+        conn.execute(
+            "UPDATE file_notes SET note = ?1 WHERE id = ?2",
+            rusqlite::params![seal_text(&open_text(note)?)?, id],
+        )
+        .map_err(|e| e.to_string())?;
+        converted += 1;
+    }
+    let rows: Vec<(i64, String, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT id, value, parties FROM document_fields")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(Result::ok).collect()
+    };
+    for (id, value, parties) in rows {
+        conn.execute(
+            "UPDATE document_fields SET value = ?1, parties = ?2 WHERE id = ?3",
+            rusqlite::params![seal_text(&open_text(value)?)?, seal_text(&open_text(parties)?)?, id],
+        )
+        .map_err(|e| e.to_string())?;
+        converted += 1;
+    }
+    let rows: Vec<(i64, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT rowid, result FROM analysis_cache")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(Result::ok).collect()
+    };
+    for (rowid, result) in rows {
+        conn.execute(
+            "UPDATE analysis_cache SET result = ?1 WHERE rowid = ?2",
+            rusqlite::params![seal_text(&open_text(result)?)?, rowid],
+        )
+        .map_err(|e| e.to_string())?;
+        converted += 1;
+    }
+    Ok(converted)
+}
+
+#[derive(Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    /// Whether a key exists in the keychain
+    pub key_present: bool,
+}
+
+/// 保存データの暗号化の状態
+#[tauri::command]
+pub fn get_encryption_status() -> EncryptionStatus {
+    EncryptionStatus {
+        enabled: encryption_enabled(),
+        key_present: read_secret(KEYRING_USER).is_some(),
+    }
+}
+
+/// 履歴・DB・サイドカーの暗号化を切り替え、既存の履歴とDBを変換（変換件数を返す）
+#[tauri::command]
+pub fn set_encrypt_at_rest(enabled: bool) -> Result<usize, String> {
+    if enabled {
+        // Fail before changing anything if the keychain is unavailable
+        encryption_key()?;
+    }
+    let mut settings = load_settings();
+    settings.encrypt_at_rest = enabled;
+    save_settings(&settings)?;
+    reencrypt_stored_data()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_roundtrip_and_tamper_detection() {
+        let key = [7u8; 32];
+        let plain = "請負代金額 11,000,000円 山田太郎".as_bytes();
+        let data = encrypt_with(&key, plain).expect("encrypt");
+        assert!(is_encrypted(&data));
+        assert_ne!(&data[MAGIC.len() + NONCE_LEN..], plain);
+        assert_eq!(decrypt_with(&key, &data).expect("decrypt"), plain);
+
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt_with(&key, &tampered).is_err());
+        assert!(decrypt_with(&[8u8; 32], &data).is_err());
+    }
+
+    #[test]
+    fn plaintext_passes_through_readers() {
+        assert_eq!(open_bytes(b"{}".to_vec()).unwrap(), b"{}");
+        assert_eq!(open_text("本文".to_string()).unwrap(), "本文");
+    }
+}
@@ -7,6 +7,8 @@ pub enum AppError {
     Process(String),
     Json(String),
     Pdf(String),
+    /// The AI request did not finish in time and was aborted (can be retried)
+    Timeout(String),
 }
 
 impl fmt::Display for AppError {
@@ -16,6 +18,7 @@ impl fmt::Display for AppError {
             AppError::Process(msg) => write!(f, "Process error: {}", msg),
             AppError::Json(msg) => write!(f, "JSON error: {}", msg),
             AppError::Pdf(msg) => write!(f, "PDF error: {}", msg),
+            AppError::Timeout(msg) => write!(f, "Timeout: {}", msg),
         }
     }
 }
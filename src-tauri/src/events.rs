@@ -1,10 +1,24 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::Local;
 use serde::Serialize;
 use tauri::{AppHandle, Emitter};
 
+/// Log events kept for `get_recent_logs`
+const MAX_RECENT_LOGS: usize = 500;
+
+/// Handle for code that runs without one (e.g. inside an AI provider)
+static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+
+/// Latest log events, so a reopened window can backfill its console
+static RECENT_LOGS: Mutex<VecDeque<LogEvent>> = Mutex::new(VecDeque::new());
+
 #[derive(Clone, Serialize)]
 pub struct LogEvent {
     pub message: String,
     pub level: String,
+    pub timestamp: String,
 }
 
 #[derive(Clone, Serialize)]
@@ -13,6 +27,16 @@ pub struct PdfDetectedEvent {
     pub name: String,
 }
 
+/// Output of a running analysis (`analysis-stream`)
+#[derive(Clone, Serialize)]
+pub struct AnalysisStreamEvent {
+    pub analysis_id: String,
+    pub file_name: String,
+    pub chunk: String,
+    /// First chunk of a CLI run; a retried run starts the text over
+    pub first: bool,
+}
+
 #[derive(Clone, Serialize)]
 pub struct CodeReviewEvent {
     pub path: String,
@@ -22,9 +46,75 @@ pub struct CodeReviewEvent {
     pub has_issues: bool,
 }
 
-pub fn emit_log(app: &AppHandle, message: &str, level: &str) {
-    let _ = app.emit("log", LogEvent {
+fn record_log(message: &str, level: &str) -> LogEvent {
+    let event = LogEvent {
         message: message.to_string(),
         level: level.to_string(),
-    });
+        timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    let mut logs = RECENT_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    if logs.len() >= MAX_RECENT_LOGS {
+        logs.pop_front();
+    }
+    logs.push_back(event.clone());
+    event
+}
+
+pub fn emit_log(app: &AppHandle, message: &str, level: &str) {
+    let _ = app.emit("log", record_log(message, level));
+}
+
+pub fn set_app_handle(app: &AppHandle) {
+    let _ = APP_HANDLE.set(app.clone());
+}
+
+/// Log without an AppHandle (only buffered before setup and in tests)
+pub fn emit_app_log(message: &str, level: &str) {
+    match APP_HANDLE.get() {
+        Some(app) => emit_log(app, message, level),
+        None => {
+            record_log(message, level);
+        }
+    }
+}
+
+/// Emit an event without an AppHandle (dropped before setup)
+pub fn emit_app_event<S: Serialize + Clone>(event: &str, payload: S) {
+    if let Some(app) = APP_HANDLE.get() {
+        let _ = app.emit(event, payload);
+    }
+}
+
+/// Buffered log events, oldest first (the last `limit` when given)
+pub fn recent_logs(limit: Option<usize>) -> Vec<LogEvent> {
+    let logs = RECENT_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    let skip = limit.map(|n| logs.len().saturating_sub(n)).unwrap_or(0);
+    logs.iter().skip(skip).cloned().collect()
+}
+
+/// 直近のログ（ウィンドウを開き直したときにコンソールを復元する）
+#[tauri::command]
+pub fn get_recent_logs(limit: Option<usize>) -> Vec<LogEvent> {
+    recent_logs(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_logs_keep_the_latest_events() {
+        emit_app_log("recent-first", "info");
+        emit_app_log("recent-second", "warn");
+        let logs = recent_logs(None);
+        let position = |m: &str| logs.iter().position(|l| l.message == m).expect("logged");
+        assert!(position("recent-first") < position("recent-second"));
+        assert_eq!(logs[position("recent-second")].level, "warn");
+        assert_eq!(recent_logs(Some(1)).len(), 1);
+
+        for i in 0..MAX_RECENT_LOGS {
+            emit_app_log(&format!("line {}", i), "info");
+        }
+        assert_eq!(recent_logs(None).len(), MAX_RECENT_LOGS);
+    }
 }
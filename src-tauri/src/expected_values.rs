@@ -0,0 +1,238 @@
+//! Expected values (ground truth) attached to an analysis request
+//!
+//! Values exported from Kintone/Excel as CSV, or sent by the frontend as
+//! field → value pairs, are authoritative reference data. The prompt
+//! receives them as 基準値, and after the analysis each value is looked up
+//! in the document text so a value the document does not contain is flagged
+//! even if the model missed it.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pdf_text::{extract_page_texts, has_enough_text};
+
+/// One authoritative value
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExpectedValue {
+    pub field: String,
+    pub value: String,
+}
+
+/// Where the expected values of a request come from
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ExpectedValuesSource {
+    /// CSV file exported from Kintone/Excel (UTF-8)
+    Csv { csv_path: String },
+    /// Field → value pairs, e.g. fetched from an API by the frontend
+    Values(BTreeMap<String, String>),
+}
+
+/// Header of the vertical CSV layout (one `項目,値` pair per row)
+const VERTICAL_HEADERS: [(&str, &str); 2] = [("項目", "値"), ("field", "value")];
+
+/// Split a CSV line (double-quoted fields may contain commas and `""`)
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Parse expected values from CSV text
+///
+/// Two layouts are accepted: a `項目,値` (or `field,value`) header followed
+/// by one pair per row, or a header row of field names followed by exactly
+/// one record (a Kintone/Excel export of a single row).
+pub fn parse_expected_csv(text: &str) -> Result<Vec<ExpectedValue>, String> {
+    let rows: Vec<Vec<String>> = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(split_csv_line)
+        .collect();
+    let Some((header, records)) = rows.split_first() else {
+        return Err("CSVが空です".to_string());
+    };
+
+    let vertical = header.len() == 2
+        && VERTICAL_HEADERS
+            .iter()
+            .any(|(f, v)| header[0].eq_ignore_ascii_case(f) && header[1].eq_ignore_ascii_case(v));
+    let pairs: Vec<(String, String)> = if vertical {
+        records
+            .iter()
+            .filter(|r| r.len() >= 2)
+            .map(|r| (r[0].clone(), r[1].clone()))
+            .collect()
+    } else {
+        match records {
+            [record] => header.iter().cloned().zip(record.iter().cloned()).collect(),
+            [] => return Err("CSVにデータ行がありません".to_string()),
+            _ => {
+                return Err(format!(
+                    "CSVに複数のレコードがあります ({} 行)。1件分だけを出力してください",
+                    records.len()
+                ))
+            }
+        }
+    };
+
+    Ok(pairs
+        .into_iter()
+        .filter(|(field, value)| !field.is_empty() && !value.is_empty())
+        .map(|(field, value)| ExpectedValue { field, value })
+        .collect())
+}
+
+/// Expected values of a request (empty without a source)
+pub fn resolve_expected_values(source: Option<ExpectedValuesSource>) -> Result<Vec<ExpectedValue>, String> {
+    match source {
+        None => Ok(Vec::new()),
+        Some(ExpectedValuesSource::Csv { csv_path }) => {
+            let bytes = fs::read(&csv_path).map_err(|e| format!("CSV読み込みエラー: {}", e))?;
+            let text = String::from_utf8(bytes)
+                .map_err(|_| "CSVはUTF-8で保存してください".to_string())?;
+            parse_expected_csv(&text)
+        }
+        Some(ExpectedValuesSource::Values(values)) => Ok(values
+            .into_iter()
+            .filter(|(field, value)| !field.trim().is_empty() && !value.trim().is_empty())
+            .map(|(field, value)| ExpectedValue { field, value })
+            .collect()),
+    }
+}
+
+/// Prompt section with the expected values (empty without values)
+pub fn expected_values_section(values: &[ExpectedValue]) -> String {
+    if values.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<String> = values
+        .iter()
+        .map(|v| format!("- {}: {}", v.field, v.value))
+        .collect();
+    format!(
+        "\n## 基準値（台帳データ・正とする値）\n以下は台帳から取得した正しい値です。書類の記載と照合し、異なる場合や記載がない場合は「⚠」で具体的に指摘してください。\n{}\n",
+        lines.join("\n")
+    )
+}
+
+/// Text for matching: full-width ASCII to half-width, without spaces and
+/// number/currency separators
+fn normalize_for_match(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| match c {
+            '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            _ => c,
+        })
+        .filter(|c| !c.is_whitespace() && !matches!(c, ',' | '，' | '¥' | '￥' | '円'))
+        .collect()
+}
+
+/// Whether the document text contains the value (numbers must not be part
+/// of a longer number)
+pub fn text_contains_value(text: &str, value: &str) -> bool {
+    let (text, value) = (normalize_for_match(text), normalize_for_match(value));
+    if value.is_empty() || value.len() > text.len() {
+        return false;
+    }
+    (0..=text.len() - value.len()).any(|start| {
+        let end = start + value.len();
+        text[start..end] == value[..]
+            && !(value[0].is_ascii_digit() && start > 0 && text[start - 1].is_ascii_digit())
+            && !(value[value.len() - 1].is_ascii_digit()
+                && end < text.len()
+                && text[end].is_ascii_digit())
+    })
+}
+
+/// Text of the documents for the value check (None if any is a scan)
+pub fn document_text(paths: &[String]) -> Option<String> {
+    let mut texts = Vec::new();
+    for path in paths {
+        let pages = extract_page_texts(path).ok()?;
+        if !has_enough_text(&pages) {
+            return None;
+        }
+        texts.push(pages.join("\n"));
+    }
+    Some(texts.join("\n"))
+}
+
+/// Append the deterministic check of the expected values to a result
+pub fn append_expected_check(result: String, text: Option<&str>, values: &[ExpectedValue]) -> String {
+    if values.is_empty() {
+        return result;
+    }
+    let lines: Vec<String> = match text {
+        None => vec!["（テキストを抽出できない書類のため自動照合は行っていません）".to_string()],
+        Some(text) => values
+            .iter()
+            .map(|v| {
+                if text_contains_value(text, &v.value) {
+                    format!("✓ {}: {}", v.field, v.value)
+                } else {
+                    format!("⚠ 基準値が書類中に見つかりません: {} = {}", v.field, v.value)
+                }
+            })
+            .collect(),
+    };
+    format!("{}\n\n## 基準値照合（自動判定）\n{}", result.trim_end(), lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_expected_csv_accepts_both_layouts() {
+        let vertical = "\u{feff}項目,値\n工事名,\"市道1号線 舗装工事\"\n請負代金額,\"11,000,000\"\n";
+        assert_eq!(
+            parse_expected_csv(vertical).unwrap(),
+            vec![
+                ExpectedValue { field: "工事名".into(), value: "市道1号線 舗装工事".into() },
+                ExpectedValue { field: "請負代金額".into(), value: "11,000,000".into() },
+            ]
+        );
+
+        let horizontal = "工事名,受注者,工期終了\n舗装工事,山田建設,2026-03-31\n";
+        assert_eq!(parse_expected_csv(horizontal).unwrap().len(), 3);
+        assert!(parse_expected_csv("工事名,受注者\nA,B\nC,D\n").is_err());
+    }
+
+    #[test]
+    fn text_contains_value_normalizes_and_respects_number_boundaries() {
+        let text = "請負代金額 ￥１１，０００，０００円\n受注者 山田 建設";
+        assert!(text_contains_value(text, "11,000,000"));
+        assert!(text_contains_value(text, "山田建設"));
+        assert!(!text_contains_value(text, "1,000,000"));
+        assert!(!text_contains_value(text, "田中建設"));
+    }
+
+    #[test]
+    fn append_expected_check_flags_missing_values() {
+        let values = vec![
+            ExpectedValue { field: "請負代金額".into(), value: "11,000,000".into() },
+            ExpectedValue { field: "受注者".into(), value: "田中建設".into() },
+        ];
+        let result = append_expected_check("✓ 問題なし".to_string(), Some("金額 11,000,000円 山田建設"), &values);
+        assert!(result.contains("✓ 請負代金額: 11,000,000"));
+        assert!(result.contains("⚠ 基準値が書類中に見つかりません: 受注者 = 田中建設"));
+    }
+}
@@ -0,0 +1,295 @@
+//! Expiry dates of documents (保険証券の有効期限, 資格証の期限...)
+//!
+//! After each analysis the expiry-bearing fields of the result are stored in
+//! the database. The scheduler checks them across all projects and warns a
+//! configurable number of days before a document expires, so renewed
+//! certificates can be requested and re-analyzed in time.
+
+use chrono::{Duration, Local, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::database::open_db;
+use crate::events::emit_log;
+use crate::messages::tr;
+use crate::settings::load_settings;
+use crate::structured_report::extract_fields;
+
+/// Days before expiry a warning is shown (unless configured)
+pub const DEFAULT_EXPIRY_WARNING_DAYS: u32 = 30;
+
+/// Field labels that carry an expiry date
+const EXPIRY_LABEL_MARKERS: &[&str] = &["期限", "有効期間", "保険期間", "満了", "失効"];
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A stored expiry date of a document
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DocumentExpiry {
+    pub file_path: String,
+    pub project_folder: String,
+    /// Field label of the result (有効期限, 資格証の期限...)
+    pub label: String,
+    /// YYYY-MM-DD
+    pub expires_on: String,
+    /// Days from today (negative when already expired)
+    pub days_left: i64,
+}
+
+/// Dates written in a value, in order (西暦 or 令和/平成)
+pub fn dates_in(value: &str) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    let chars: Vec<char> = value.chars().collect();
+    let mut numbers: Vec<(usize, u32)> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            if let Ok(n) = digits.parse() {
+                numbers.push((start, n));
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    let era_offset = |pos: usize| {
+        let before: String = chars[pos.saturating_sub(2)..pos].iter().collect();
+        if before.ends_with("令和") {
+            Some(2018)
+        } else if before.ends_with("平成") {
+            Some(1988)
+        } else {
+            None
+        }
+    };
+
+    let mut k = 0;
+    while k + 2 < numbers.len() {
+        let (pos, y) = numbers[k];
+        let year = match era_offset(pos) {
+            Some(offset) => y + offset,
+            None => y,
+        };
+        let (m, d) = (numbers[k + 1].1, numbers[k + 2].1);
+        match NaiveDate::from_ymd_opt(year as i32, m, d).filter(|_| year >= 1900) {
+            Some(date) => {
+                dates.push(date);
+                k += 3;
+            }
+            None => k += 1,
+        }
+    }
+    dates
+}
+
+/// Expiry fields of an analysis result: (label, last date of the value)
+///
+/// A period (`2025年4月1日〜2026年3月31日`) expires at its end.
+pub fn expiry_fields(result: &str) -> Vec<(String, NaiveDate)> {
+    extract_fields(result)
+        .into_iter()
+        .filter(|f| EXPIRY_LABEL_MARKERS.iter().any(|m| f.label.contains(m)))
+        .filter_map(|f| dates_in(&f.value).last().map(|date| (f.label, *date)))
+        .collect()
+}
+
+/// Replace the stored expiry dates of a file
+///
+/// The notification state is kept for dates that did not change, so a
+/// re-analysis does not warn again about the same expiry.
+pub fn store_expiries(
+    conn: &Connection,
+    project_folder: &str,
+    file_path: &str,
+    fields: &[(String, NaiveDate)],
+) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let labels: Vec<&str> = fields.iter().map(|(label, _)| label.as_str()).collect();
+    let stored: Vec<String> = {
+        let mut stmt = tx
+            .prepare("SELECT label FROM document_expiries WHERE file_path = ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![file_path], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.filter_map(Result::ok).collect()
+    };
+    for label in stored.iter().filter(|l| !labels.contains(&l.as_str())) {
+        tx.execute(
+            "DELETE FROM document_expiries WHERE file_path = ?1 AND label = ?2",
+            params![file_path, label],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    let now = Local::now().format(TIMESTAMP_FORMAT).to_string();
+    for (label, date) in fields {
+        tx.execute(
+            "INSERT INTO document_expiries (file_path, project_folder, label, expires_on, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(file_path, label) DO UPDATE SET
+                 project_folder = excluded.project_folder,
+                 notified_at = CASE WHEN expires_on = excluded.expires_on THEN notified_at ELSE NULL END,
+                 expires_on = excluded.expires_on,
+                 recorded_at = excluded.recorded_at",
+            params![file_path, project_folder, label, date.format("%Y-%m-%d").to_string(), now],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Record the expiry dates found in an analysis result (best effort)
+pub fn record_expiries(project_folder: &str, file_path: &str, result: &str) {
+    if let Ok(conn) = open_db() {
+        let _ = store_expiries(&conn, project_folder, file_path, &expiry_fields(result));
+    }
+}
+
+/// Documents expiring on or before `today + within_days` (expired ones included)
+///
+/// `only_unnotified` limits the list to expiries not warned about yet.
+pub fn expiring_documents(
+    conn: &Connection,
+    today: NaiveDate,
+    within_days: u32,
+    only_unnotified: bool,
+) -> Result<Vec<DocumentExpiry>, String> {
+    let limit = (today + Duration::days(i64::from(within_days))).format("%Y-%m-%d").to_string();
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, project_folder, label, expires_on FROM document_expiries
+             WHERE expires_on <= ?1 AND (?2 = 0 OR notified_at IS NULL)
+             ORDER BY expires_on, file_path",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![limit, only_unnotified], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, String>(3)?))
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|(file_path, project_folder, label, expires_on)| {
+            let date = NaiveDate::parse_from_str(&expires_on, "%Y-%m-%d").ok()?;
+            Some(DocumentExpiry {
+                file_path,
+                project_folder,
+                label,
+                expires_on,
+                days_left: (date - today).num_days(),
+            })
+        })
+        .collect())
+}
+
+fn mark_notified(conn: &Connection, expiry: &DocumentExpiry) -> Result<(), String> {
+    conn.execute(
+        "UPDATE document_expiries SET notified_at = ?1 WHERE file_path = ?2 AND label = ?3",
+        params![
+            Local::now().format(TIMESTAMP_FORMAT).to_string(),
+            expiry.file_path,
+            expiry.label
+        ],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+fn warning_days() -> u32 {
+    load_settings()
+        .expiry_warning_days
+        .unwrap_or(DEFAULT_EXPIRY_WARNING_DAYS)
+}
+
+/// Warn once about each document expiring soon (run by the scheduler)
+pub fn warn_expiring_documents(app: &AppHandle) {
+    let Ok(conn) = open_db() else {
+        return;
+    };
+    let today = Local::now().date_naive();
+    let Ok(expiring) = expiring_documents(&conn, today, warning_days(), true) else {
+        return;
+    };
+    for expiry in expiring {
+        let file_name = std::path::Path::new(&expiry.file_path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| expiry.file_path.clone());
+        let message = if expiry.days_left < 0 {
+            tr("expiry.expired", &[&file_name, &expiry.label, &expiry.expires_on])
+        } else {
+            tr("expiry.soon", &[&file_name, &expiry.label, &expiry.expires_on, &expiry.days_left])
+        };
+        emit_log(app, &message, "warn");
+        let _ = app.emit(
+            "show-notification",
+            serde_json::json!({
+                "title": tr("expiry.title", &[]),
+                "body": message,
+                "path": expiry.file_path
+            }),
+        );
+        let _ = mark_notified(&conn, &expiry);
+    }
+}
+
+/// 期限が近い（または切れた）書類の一覧（全工事）
+#[tauri::command]
+pub fn get_expiring_documents(within_days: Option<u32>) -> Result<Vec<DocumentExpiry>, String> {
+    let conn = open_db()?;
+    let days = within_days.unwrap_or_else(warning_days);
+    expiring_documents(&conn, Local::now().date_naive(), days, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn expiry_fields_read_the_end_of_periods_and_japanese_eras() {
+        let result = "- 保険期間: 2025年4月1日〜2026年3月31日\n- 資格証の有効期限：令和8年5月10日\n- 契約金額: 1,000,000円";
+        let fields = expiry_fields(result);
+        assert_eq!(
+            fields,
+            vec![
+                ("保険期間".to_string(), date("2026-03-31")),
+                ("資格証の有効期限".to_string(), date("2026-05-10")),
+            ]
+        );
+    }
+
+    #[test]
+    fn expiring_documents_are_warned_once_per_date() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let today = date("2026-04-01");
+        store_expiries(&conn, "/p", "/p/保険.pdf", &[("有効期限".to_string(), date("2026-04-20"))]).unwrap();
+        store_expiries(&conn, "/p", "/p/資格.pdf", &[("有効期限".to_string(), date("2026-12-31"))]).unwrap();
+
+        let soon = expiring_documents(&conn, today, 30, true).unwrap();
+        assert_eq!(soon.len(), 1);
+        assert_eq!(soon[0].days_left, 19);
+        mark_notified(&conn, &soon[0]).unwrap();
+        assert!(expiring_documents(&conn, today, 30, true).unwrap().is_empty());
+
+        // Same date again: still notified. A renewed date is warned about anew.
+        store_expiries(&conn, "/p", "/p/保険.pdf", &[("有効期限".to_string(), date("2026-04-20"))]).unwrap();
+        assert!(expiring_documents(&conn, today, 30, true).unwrap().is_empty());
+        store_expiries(&conn, "/p", "/p/保険.pdf", &[("有効期限".to_string(), date("2026-04-25"))]).unwrap();
+        assert_eq!(expiring_documents(&conn, today, 30, true).unwrap().len(), 1);
+
+        store_expiries(&conn, "/p", "/p/保険.pdf", &[]).unwrap();
+        assert_eq!(expiring_documents(&conn, today, 365, false).unwrap().len(), 1);
+    }
+}
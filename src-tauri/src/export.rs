@@ -0,0 +1,137 @@
+//! Flat data exports for external analysis (Excel pivot tables etc.)
+
+use std::fs;
+use std::path::Path;
+
+use crate::audit::record_access;
+use crate::history::{load_all_histories, AnalysisHistory};
+use crate::storage::ensure_space_for;
+
+/// One finding of one analyzed document
+pub struct IssueRow {
+    pub project: String,
+    pub file_name: String,
+    pub document_type: String,
+    pub severity: &'static str,
+    pub status: &'static str,
+    pub date: String,
+    pub issue: String,
+}
+
+const CSV_HEADER: [&str; 7] = ["プロジェクト", "ファイル名", "書類タイプ", "重要度", "状態", "日付", "指摘"];
+
+/// Severity of a finding from its wording and markers
+pub fn issue_severity(issue: &str) -> &'static str {
+    if ["✗", "❌", "重大", "エラー", "誤り"].iter().any(|m| issue.contains(m)) {
+        "高"
+    } else if ["⚠", "警告", "不整合", "矛盾"].iter().any(|m| issue.contains(m)) {
+        "中"
+    } else {
+        "低"
+    }
+}
+
+/// Flatten histories into one row per finding (open and resolved)
+pub fn collect_issue_rows(histories: &[AnalysisHistory]) -> Vec<IssueRow> {
+    let mut rows = Vec::new();
+    for history in histories {
+        for entry in &history.entries {
+            let open = entry.issues.iter().map(|i| (i, "未解決"));
+            let resolved = entry.resolved_issues.iter().map(|i| (i, "解消"));
+            for (issue, status) in open.chain(resolved) {
+                rows.push(IssueRow {
+                    project: history.project_folder.clone(),
+                    file_name: entry.file_name.clone(),
+                    document_type: entry.document_type.clone().unwrap_or_default(),
+                    severity: issue_severity(issue),
+                    status,
+                    date: entry.analyzed_at.clone(),
+                    issue: issue.clone(),
+                });
+            }
+        }
+    }
+    rows.sort_by(|a, b| (&a.project, &a.date).cmp(&(&b.project, &b.date)));
+    rows
+}
+
+/// Quote a CSV field when needed (RFC 4180)
+pub fn csv_field(value: &str) -> String {
+    if value.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render rows as CSV with a UTF-8 BOM so Excel detects the encoding
+pub fn issues_to_csv(rows: &[IssueRow]) -> String {
+    let mut csv = String::from("\u{feff}");
+    csv.push_str(&CSV_HEADER.join(","));
+    csv.push_str("\r\n");
+    for row in rows {
+        let fields = [
+            row.project.as_str(),
+            row.file_name.as_str(),
+            row.document_type.as_str(),
+            row.severity,
+            row.status,
+            row.date.as_str(),
+            row.issue.as_str(),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&line.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// 全プロジェクトの指摘をCSVに書き出し（書き出した行数を返す）
+#[tauri::command]
+pub fn export_issues_csv(path: String) -> Result<usize, String> {
+    record_access("export_issues_csv", &path);
+    let rows = collect_issue_rows(&load_all_histories());
+    let csv = issues_to_csv(&rows);
+    ensure_space_for(Path::new(&path), csv.len() as u64)?;
+    fs::write(&path, csv).map_err(|e| format!("CSV保存エラー: {}", e))?;
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::create_history_entry;
+
+    #[test]
+    fn issue_rows_include_open_and_resolved() {
+        let mut entry = create_history_entry("契約書.pdf", "/p/契約書.pdf", "契約書\n⚠ 金額不整合");
+        entry.resolved_issues = vec!["✗ 押印漏れ".to_string()];
+        let history = AnalysisHistory {
+            project_folder: "/p".to_string(),
+            entries: vec![entry],
+            ..Default::default()
+        };
+
+        let rows = collect_issue_rows(&[history]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].severity, rows[0].status), ("中", "未解決"));
+        assert_eq!((rows[1].severity, rows[1].status), ("高", "解消"));
+        assert_eq!(rows[0].document_type, "契約書");
+    }
+
+    #[test]
+    fn csv_quotes_special_fields() {
+        let row = IssueRow {
+            project: "/p".to_string(),
+            file_name: "a.pdf".to_string(),
+            document_type: String::new(),
+            severity: "中",
+            status: "未解決",
+            date: "2026-01-01 10:00:00".to_string(),
+            issue: "⚠ 金額 \"1,000円\" が不一致".to_string(),
+        };
+        let csv = issues_to_csv(&[row]);
+        assert!(csv.starts_with('\u{feff}'));
+        assert!(csv.contains("\"⚠ 金額 \"\"1,000円\"\" が不一致\"\r\n"));
+    }
+}
@@ -0,0 +1,92 @@
+//! Opening documents from the app
+//!
+//! Opening a document or revealing it in the file manager marks its history
+//! entry as reviewed, so the dashboard can tell which findings nobody has
+//! looked at yet.
+
+use std::path::Path;
+use std::process::Command;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+#[cfg(target_os = "windows")]
+use crate::CREATE_NO_WINDOW;
+
+use crate::audit::record_access;
+use crate::history::{mark_entry_reviewed, update_history};
+use crate::messages::tr;
+use crate::project_settings::project_folder_for;
+
+/// Mark the history entry of a document as reviewed (no-op without entry)
+pub fn mark_reviewed(path: &str) -> Result<(), String> {
+    update_history(&project_folder_for(path), |history| {
+        mark_entry_reviewed(history, path);
+    })
+}
+
+fn spawn_detached(mut cmd: Command) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    cmd.spawn()
+        .map(|_| ())
+        .map_err(|e| tr("file.launch_error", &[&e]))
+}
+
+fn ensure_exists(path: &str) -> Result<(), String> {
+    if Path::new(path).exists() {
+        Ok(())
+    } else {
+        Err(tr("file.not_found", &[&path]))
+    }
+}
+
+/// エクスプローラーでファイルの場所を開く（履歴を確認済みにする）
+#[tauri::command]
+pub fn reveal_in_explorer(path: String) -> Result<(), String> {
+    ensure_exists(&path)?;
+    #[cfg(target_os = "windows")]
+    let cmd = {
+        // explorer only accepts the path quoted after the comma
+        let mut cmd = Command::new("explorer");
+        cmd.raw_arg(format!("/select,\"{}\"", path));
+        cmd
+    };
+    #[cfg(target_os = "macos")]
+    let cmd = {
+        let mut cmd = Command::new("open");
+        cmd.args(["-R", path.as_str()]);
+        cmd
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let cmd = {
+        let folder = Path::new(&path)
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".".to_string());
+        let mut cmd = Command::new("xdg-open");
+        cmd.arg(folder);
+        cmd
+    };
+    spawn_detached(cmd)?;
+    mark_reviewed(&path)
+}
+
+/// 既定のアプリでファイルを開く（履歴を確認済みにする）
+#[tauri::command]
+pub fn open_file(path: String) -> Result<(), String> {
+    ensure_exists(&path)?;
+    record_access("open_file", &path);
+    // explorer (not `cmd /c start`) so `&` etc. in file names are not interpreted
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let mut cmd = Command::new(program);
+    cmd.arg(&path);
+    spawn_detached(cmd)?;
+    mark_reviewed(&path)
+}
@@ -0,0 +1,268 @@
+//! Windows file associations
+//!
+//! Registers the app per user (HKCU\Software\Classes, no admin rights) as
+//! the "開く" handler of exported `.shorui` reports and as an additional
+//! "書類チェッカーで解析" verb on PDFs. Double-clicking a report starts the
+//! app with the file as argument, and the frontend opens it in the viewer.
+//! The `shoruichecker://` URL scheme of record links (QR codes on printed
+//! reports) is registered the same way.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "windows")]
+use crate::CREATE_NO_WINDOW;
+
+use crate::audit::record_access;
+use crate::history::{analyses_of_file, load_history, AnalysisHistoryEntry};
+use crate::project_settings::project_folder_for;
+use crate::record_link::RECORD_SCHEME;
+use crate::result_store::load_result_data;
+
+/// Extension of exported report files
+pub const REPORT_EXTENSION: &str = "shorui";
+
+/// ProgID of the report file type
+const REPORT_PROG_ID: &str = "ShoruiChecker.Report";
+
+const CLASSES_KEY: &str = r"HKCU\Software\Classes";
+
+/// Verb key added to PDFs (does not replace the default PDF viewer)
+const PDF_VERB_KEY: &str = r"SystemFileAssociations\.pdf\shell\ShoruiChecker";
+
+const REPORT_FORMAT_VERSION: u32 = 1;
+
+/// An exported report (`<name>.shorui`, JSON)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShoruiReport {
+    pub format_version: u32,
+    pub file_name: String,
+    pub file_path: String,
+    pub exported_at: String,
+    pub result: String,
+    #[serde(default)]
+    pub instruction: Option<String>,
+    /// Analyses of the file recorded in the history (oldest first)
+    #[serde(default)]
+    pub analyses: Vec<AnalysisHistoryEntry>,
+}
+
+/// A registry value written by the association (`None` name = default value)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryValue {
+    pub key: String,
+    pub name: Option<String>,
+    pub data: String,
+}
+
+fn value(key: &str, name: Option<&str>, data: String) -> RegistryValue {
+    RegistryValue {
+        key: format!(r"{}\{}", CLASSES_KEY, key),
+        name: name.map(str::to_string),
+        data,
+    }
+}
+
+/// Registry values of both associations for the given executable
+pub fn association_values(exe: &Path) -> Vec<RegistryValue> {
+    let exe = exe.to_string_lossy();
+    let open_command = format!("\"{}\" \"%1\"", exe);
+    vec![
+        value(&format!(".{}", REPORT_EXTENSION), None, REPORT_PROG_ID.to_string()),
+        value(REPORT_PROG_ID, None, "書類チェッカー レポート".to_string()),
+        value(&format!(r"{}\DefaultIcon", REPORT_PROG_ID), None, format!("\"{}\",0", exe)),
+        value(&format!(r"{}\shell\open\command", REPORT_PROG_ID), None, open_command.clone()),
+        value(PDF_VERB_KEY, None, "書類チェッカーで解析".to_string()),
+        value(PDF_VERB_KEY, Some("Icon"), format!("\"{}\",0", exe)),
+        value(&format!(r"{}\command", PDF_VERB_KEY), None, open_command.clone()),
+        value(RECORD_SCHEME, None, "URL:書類チェッカー 解析記録".to_string()),
+        value(RECORD_SCHEME, Some("URL Protocol"), String::new()),
+        value(&format!(r"{}\shell\open\command", RECORD_SCHEME), None, open_command),
+    ]
+}
+
+/// Keys removed when unregistering (the `.shorui` key only if it points to us)
+fn association_keys() -> Vec<String> {
+    [REPORT_PROG_ID, PDF_VERB_KEY, RECORD_SCHEME]
+        .iter()
+        .map(|key| format!(r"{}\{}", CLASSES_KEY, key))
+        .collect()
+}
+
+/// `reg add` arguments writing one value
+pub fn reg_add_args(value: &RegistryValue) -> Vec<String> {
+    let mut args = vec!["add".to_string(), value.key.clone()];
+    match &value.name {
+        Some(name) => args.extend(["/v".to_string(), name.clone()]),
+        None => args.push("/ve".to_string()),
+    }
+    args.extend([
+        "/t".to_string(),
+        "REG_SZ".to_string(),
+        "/d".to_string(),
+        value.data.clone(),
+        "/f".to_string(),
+    ]);
+    args
+}
+
+fn run_reg(args: &[String]) -> Result<String, String> {
+    if !cfg!(target_os = "windows") {
+        return Err("ファイルの関連付けは Windows でのみ設定できます".to_string());
+    }
+    let mut cmd = Command::new("reg");
+    cmd.args(args);
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    let output = cmd.output().map_err(|e| format!("reg の起動に失敗しました: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "レジストリの更新に失敗しました: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn current_exe() -> Result<PathBuf, String> {
+    std::env::current_exe().map_err(|e| format!("実行ファイルのパスを取得できません: {}", e))
+}
+
+#[derive(Clone, Serialize)]
+pub struct FileAssociationStatus {
+    /// `.shorui` opens with this app
+    pub reports: bool,
+    /// The PDF verb is registered
+    pub pdf_verb: bool,
+    /// `shoruichecker://` links open this app
+    pub record_links: bool,
+}
+
+/// ファイルの関連付けの状態
+#[tauri::command]
+pub fn get_file_association_status() -> FileAssociationStatus {
+    let query = |key: &str| {
+        run_reg(&["query".to_string(), format!(r"{}\{}", CLASSES_KEY, key), "/ve".to_string()])
+    };
+    FileAssociationStatus {
+        reports: query(&format!(".{}", REPORT_EXTENSION))
+            .map(|out| out.contains(REPORT_PROG_ID))
+            .unwrap_or(false),
+        pdf_verb: query(PDF_VERB_KEY).is_ok(),
+        record_links: query(RECORD_SCHEME).is_ok(),
+    }
+}
+
+/// .shorui レポートとPDFの右クリックメニューにアプリを登録
+#[tauri::command]
+pub fn register_file_associations() -> Result<FileAssociationStatus, String> {
+    let exe = current_exe()?;
+    for value in association_values(&exe) {
+        run_reg(&reg_add_args(&value))?;
+    }
+    Ok(get_file_association_status())
+}
+
+/// ファイルの関連付けを解除
+#[tauri::command]
+pub fn unregister_file_associations() -> Result<FileAssociationStatus, String> {
+    let status = get_file_association_status();
+    let mut keys = association_keys();
+    if status.reports {
+        keys.push(format!(r"{}\.{}", CLASSES_KEY, REPORT_EXTENSION));
+    }
+    for key in keys {
+        // Missing keys are fine: they were never registered
+        let _ = run_reg(&["delete".to_string(), key, "/f".to_string()]);
+    }
+    Ok(get_file_association_status())
+}
+
+/// Report of a PDF from its stored result and history
+pub fn build_report(pdf_path: &str) -> Result<ShoruiReport, String> {
+    let data = load_result_data(pdf_path).ok_or_else(|| format!("解析結果がありません: {}", pdf_path))?;
+    let history = load_history(&project_folder_for(pdf_path))?;
+    Ok(ShoruiReport {
+        format_version: REPORT_FORMAT_VERSION,
+        file_name: Path::new(pdf_path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        file_path: pdf_path.to_string(),
+        exported_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        result: data.result,
+        instruction: data.instruction,
+        analyses: analyses_of_file(&history, pdf_path).into_iter().cloned().collect(),
+    })
+}
+
+pub fn read_report(path: &Path) -> Result<ShoruiReport, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("レポート読み込みエラー: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("レポートの形式が正しくありません: {}", e))
+}
+
+/// 解析結果を .shorui レポートとして書き出す（保存先を返す）
+#[tauri::command]
+pub fn export_shorui_report(path: String, out_path: Option<String>) -> Result<String, String> {
+    record_access("export_shorui_report", &path);
+    let out_path = out_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&path).with_extension(REPORT_EXTENSION));
+    let report = build_report(&path)?;
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&out_path, json).map_err(|e| format!("レポート保存エラー: {}", e))?;
+    Ok(out_path.to_string_lossy().to_string())
+}
+
+/// .shorui レポートを開く
+#[tauri::command]
+pub fn open_shorui_report(path: String) -> Result<ShoruiReport, String> {
+    record_access("open_shorui_report", &path);
+    read_report(Path::new(&path))
+}
+
+/// 起動時に開くレポート（.shorui をダブルクリックして起動した場合）
+#[tauri::command]
+pub fn get_startup_report() -> Option<String> {
+    std::env::var("OPEN_REPORT").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn association_values_register_open_command_and_pdf_verb() {
+        let values = association_values(Path::new(r"C:\Apps\shoruichecker.exe"));
+        let open = values
+            .iter()
+            .find(|v| v.key.ends_with(r"ShoruiChecker.Report\shell\open\command"))
+            .expect("open command");
+        assert_eq!(open.data, r#""C:\Apps\shoruichecker.exe" "%1""#);
+        assert!(values
+            .iter()
+            .any(|v| v.key == r"HKCU\Software\Classes\.shorui" && v.data == REPORT_PROG_ID));
+        assert!(values
+            .iter()
+            .all(|v| v.key.starts_with(r"HKCU\Software\Classes\")));
+
+        let args = reg_add_args(&values[0]);
+        assert_eq!(
+            args,
+            vec!["add", r"HKCU\Software\Classes\.shorui", "/ve", "/t", "REG_SZ", "/d", REPORT_PROG_ID, "/f"]
+        );
+        let icon = values.iter().find(|v| v.name.is_some()).expect("named value");
+        assert_eq!(&reg_add_args(icon)[2..4], ["/v", "Icon"]);
+        assert!(values
+            .iter()
+            .any(|v| v.key == r"HKCU\Software\Classes\shoruichecker" && v.name.as_deref() == Some("URL Protocol")));
+    }
+}
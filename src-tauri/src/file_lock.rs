@@ -0,0 +1,142 @@
+//! Cross-process safety for files in the config directory
+//!
+//! The GUI and a headless run (`--headless`) can update the same history and
+//! job journal at the same time. Read-modify-write cycles hold a lock file
+//! (`<name>.lock`, created exclusively), and files are replaced atomically
+//! (written to a temp file, then renamed) so a reader never sees a torn
+//! file.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long to wait for another process to release a lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Locks older than this were left by a crashed process
+///
+/// Locks are only held for a single load-modify-save, never across an
+/// analysis, so this is far above any legitimate hold time.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(60);
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Holds the lock of a file until dropped
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn sibling_path(target: &Path, suffix: &str) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    target.with_file_name(format!("{}{}", name, suffix))
+}
+
+fn is_stale(lock_path: &Path) -> bool {
+    fs::metadata(lock_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .map(|age| age > STALE_LOCK_AGE)
+        .unwrap_or(false)
+}
+
+fn lock_file_within(target: &Path, timeout: Duration) -> Result<FileLock, String> {
+    let path = sibling_path(target, ".lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let deadline = Instant::now() + timeout;
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let _ = write!(file, "{}", std::process::id());
+                return Ok(FileLock { path });
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                if is_stale(&path) {
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+                if Instant::now() >= deadline {
+                    return Err(format!(
+                        "他のプロセスが使用中です: {}",
+                        target.display()
+                    ));
+                }
+                thread::sleep(RETRY_INTERVAL);
+            }
+            Err(e) => return Err(format!("ロックファイル作成エラー: {}", e)),
+        }
+    }
+}
+
+/// Lock a file against other processes (and threads) for a read-modify-write
+pub fn lock_file(target: &Path) -> Result<FileLock, String> {
+    lock_file_within(target, LOCK_TIMEOUT)
+}
+
+/// Replace a file's contents atomically
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let temp = sibling_path(
+        path,
+        &format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+    fs::write(&temp, contents).map_err(|e| e.to_string())?;
+    fs::rename(&temp, path).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        e.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let dir = create_temp_dir(".shoruichecker_test_lock").expect("create dir");
+        let target = dir.join("history.json");
+
+        let lock = lock_file(&target).expect("first lock");
+        assert!(lock_file_within(&target, Duration::from_millis(100)).is_err());
+        drop(lock);
+        assert!(lock_file_within(&target, Duration::from_millis(100)).is_ok());
+
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        let dir = create_temp_dir(".shoruichecker_test_atomic").expect("create dir");
+        let target = dir.join("settings.json");
+
+        write_atomic(&target, "{}").expect("write");
+        write_atomic(&target, "{\"a\":1}").expect("rewrite");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{\"a\":1}");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        cleanup_temp_dir(&dir);
+    }
+}
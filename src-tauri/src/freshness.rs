@@ -0,0 +1,222 @@
+//! Drift detection between a stored result and the current document
+//!
+//! Before final submission the stored check result must still describe the
+//! file: the file hash recorded at analysis time is compared with the
+//! current one, and an optional fresh re-analysis is diffed against the
+//! stored findings. Two recorded analyses of a file can be diffed the same
+//! way to verify that a corrected re-submission fixed what was flagged.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::analysis::reanalyze_without_saving;
+use crate::archive::file_sha256;
+use crate::audit::record_access;
+use crate::history::{analyses_of_file, create_history_entry, load_history};
+use crate::project_settings::project_folder_for;
+use crate::result_store::load_result_data;
+use crate::settings::{load_settings, DEFAULT_MODEL};
+use crate::tasks::run_blocking;
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileState {
+    /// Same hash as when the result was stored
+    Unchanged,
+    /// The file changed after the result was stored
+    Modified,
+    /// No hash was recorded (analyzed by an older version or elsewhere)
+    Unknown,
+}
+
+/// Findings of the stored result vs. a fresh analysis
+#[derive(Clone, Debug, Serialize, Default, PartialEq, Eq)]
+pub struct FindingsDiff {
+    /// Only in the fresh analysis
+    pub added: Vec<String>,
+    /// Only in the stored result
+    pub removed: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+impl FindingsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct FreshnessReport {
+    pub path: String,
+    pub file_state: FileState,
+    pub recorded_hash: Option<String>,
+    pub current_hash: String,
+    /// Date of the stored result
+    pub analyzed_at: Option<String>,
+    /// Present when a re-analysis was requested
+    pub diff: Option<FindingsDiff>,
+}
+
+/// Compare the recorded hash with the current one
+pub fn file_state(recorded: Option<&str>, current: &str) -> FileState {
+    match recorded {
+        None => FileState::Unknown,
+        Some(hash) if hash == current => FileState::Unchanged,
+        Some(_) => FileState::Modified,
+    }
+}
+
+/// Findings (⚠ lines) of a result text, extracted like the history does
+pub fn findings_of(result: &str) -> Vec<String> {
+    create_history_entry("", "", result).issues
+}
+
+pub(crate) fn normalize_finding(finding: &str) -> String {
+    finding
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '⚠' | '・' | '-' | '*'))
+        .collect()
+}
+
+/// Diff two finding lists, ignoring markers and whitespace
+pub fn diff_findings(stored: &[String], fresh: &[String]) -> FindingsDiff {
+    let stored_keys: Vec<String> = stored.iter().map(|f| normalize_finding(f)).collect();
+    let fresh_keys: Vec<String> = fresh.iter().map(|f| normalize_finding(f)).collect();
+
+    let mut diff = FindingsDiff::default();
+    for (finding, key) in fresh.iter().zip(&fresh_keys) {
+        if stored_keys.contains(key) {
+            diff.unchanged.push(finding.clone());
+        } else {
+            diff.added.push(finding.clone());
+        }
+    }
+    for (finding, key) in stored.iter().zip(&stored_keys) {
+        if !fresh_keys.contains(key) {
+            diff.removed.push(finding.clone());
+        }
+    }
+    diff
+}
+
+/// Check a document's stored result; re-analyze when `reanalyze` is set
+pub fn check_freshness(path: &str, reanalyze: bool) -> Result<FreshnessReport, String> {
+    let current_hash =
+        file_sha256(Path::new(path)).map_err(|e| format!("ファイル読み込みエラー: {}", e))?;
+    let entry = load_history(&project_folder_for(path))?
+        .entries
+        .into_iter()
+        .find(|e| e.file_path == path);
+    let recorded_hash = entry.as_ref().and_then(|e| e.file_hash.clone());
+    let stored = load_result_data(path);
+
+    let diff = if reanalyze {
+        let stored_findings = stored
+            .as_ref()
+            .map(|d| findings_of(&d.result))
+            .or_else(|| entry.as_ref().map(|e| e.issues.clone()))
+            .unwrap_or_default();
+        let model = load_settings()
+            .model
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+        let fresh = reanalyze_without_saving(path, "freshness", &model, "")?;
+        Some(diff_findings(&stored_findings, &findings_of(&fresh)))
+    } else {
+        None
+    };
+
+    Ok(FreshnessReport {
+        path: path.to_string(),
+        file_state: file_state(recorded_hash.as_deref(), &current_hash),
+        recorded_hash,
+        current_hash,
+        analyzed_at: stored.map(|d| d.date).or_else(|| entry.map(|e| e.analyzed_at)),
+        diff,
+    })
+}
+
+/// Findings of two analyses of the same file
+#[derive(Clone, Serialize)]
+pub struct ResultDiff {
+    pub path: String,
+    pub old_entry_id: String,
+    pub old_analyzed_at: String,
+    pub new_entry_id: String,
+    pub new_analyzed_at: String,
+    /// Flagged in the old analysis, gone in the new one
+    pub resolved: Vec<String>,
+    /// Only flagged in the new analysis
+    pub new_issues: Vec<String>,
+    /// Flagged in both
+    pub persistent: Vec<String>,
+}
+
+/// Diff two recorded analyses (history entry IDs) of a file
+pub fn diff_recorded_results(path: &str, old_entry_id: &str, new_entry_id: &str) -> Result<ResultDiff, String> {
+    let history = load_history(&project_folder_for(path))?;
+    let analyses = analyses_of_file(&history, path);
+    let find = |id: &str| {
+        analyses
+            .iter()
+            .find(|e| e.id == id)
+            .copied()
+            .ok_or_else(|| format!("解析履歴が見つかりません: {}", id))
+    };
+    let (old, new) = (find(old_entry_id)?, find(new_entry_id)?);
+    let diff = diff_findings(&old.issues, &new.issues);
+    Ok(ResultDiff {
+        path: path.to_string(),
+        old_entry_id: old.id.clone(),
+        old_analyzed_at: old.analyzed_at.clone(),
+        new_entry_id: new.id.clone(),
+        new_analyzed_at: new.analyzed_at.clone(),
+        resolved: diff.removed,
+        new_issues: diff.added,
+        persistent: diff.unchanged,
+    })
+}
+
+/// 同じファイルの2回の解析結果を比較し、解消・新規・継続の指摘を返す（ID は get_file_analyses の id）
+#[tauri::command]
+pub fn diff_results(path: String, old_entry_id: String, new_entry_id: String) -> Result<ResultDiff, String> {
+    record_access("diff_results", &path);
+    diff_recorded_results(&path, &old_entry_id, &new_entry_id)
+}
+
+/// 保存済みの解析結果が現在のファイルに対して有効か確認（reanalyze で再解析して差分を出す）
+#[tauri::command]
+pub async fn verify_result_freshness(
+    path: String,
+    reanalyze: Option<bool>,
+) -> Result<FreshnessReport, String> {
+    let label = path.clone();
+    run_blocking("freshness", &label, move || {
+        check_freshness(&path, reanalyze.unwrap_or(false))
+    })
+    .await
+    .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_state_compares_hashes() {
+        assert_eq!(file_state(None, "aa"), FileState::Unknown);
+        assert_eq!(file_state(Some("aa"), "aa"), FileState::Unchanged);
+        assert_eq!(file_state(Some("aa"), "bb"), FileState::Modified);
+    }
+
+    #[test]
+    fn diff_findings_ignores_markers_and_spacing() {
+        let stored = vec!["⚠ 金額不整合".to_string(), "⚠ 押印漏れ".to_string()];
+        let fresh = vec!["⚠金額不整合".to_string(), "⚠ 日付矛盾".to_string()];
+        let diff = diff_findings(&stored, &fresh);
+        assert_eq!(diff.added, vec!["⚠ 日付矛盾".to_string()]);
+        assert_eq!(diff.removed, vec!["⚠ 押印漏れ".to_string()]);
+        assert_eq!(diff.unchanged, vec!["⚠金額不整合".to_string()]);
+        assert!(!diff.is_empty());
+    }
+}
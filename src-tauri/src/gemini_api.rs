@@ -0,0 +1,293 @@
+//! Gemini REST API (Generative Language API) client
+//!
+//! Alternative to spawning PowerShell and the Gemini CLI, which often fails
+//! on locked-down PCs without Node/npm. Attached PDFs and images are uploaded
+//! through the Files API, referenced in `generateContent` and deleted again
+//! afterwards. The API key is kept in the OS keychain (`GEMINI_API_KEY`
+//! overrides it).
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::gemini_cli::GeminiRequest;
+use crate::keychain::{read_secret, replace_secret};
+use crate::usage::note_token_usage;
+
+const API_BASE: &str = "https://generativelanguage.googleapis.com";
+const API_KEY_ENV: &str = "GEMINI_API_KEY";
+
+const KEYRING_USER: &str = "gemini-api-key";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Uploaded files are processed before they can be referenced
+const FILE_ACTIVE_TIMEOUT: Duration = Duration::from_secs(120);
+const FILE_POLL: Duration = Duration::from_secs(2);
+
+/// API key from the environment or the keychain
+pub fn api_key() -> Option<String> {
+    std::env::var(API_KEY_ENV)
+        .ok()
+        .or_else(|| read_secret(KEYRING_USER))
+        .filter(|k| !k.trim().is_empty())
+}
+
+/// Store the API key (None or empty removes it)
+pub fn store_api_key(key: Option<&str>) -> Result<(), String> {
+    replace_secret(KEYRING_USER, key.map(str::trim))
+}
+
+fn mime_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name).extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "pdf" => Some("application/pdf"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// A file uploaded through the Files API
+pub struct UploadedFile {
+    /// `files/<id>`, used to delete it
+    pub name: String,
+    pub uri: String,
+    pub mime_type: String,
+}
+
+/// An attached file: uploaded, or inlined as text
+pub enum Attachment {
+    Uploaded(UploadedFile),
+    Text { name: String, text: String },
+}
+
+fn api_error(response: &Value) -> Option<String> {
+    let error = response.get("error")?;
+    Some(
+        error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error")
+            .to_string(),
+    )
+}
+
+struct Client {
+    agent: ureq::Agent,
+    key: String,
+}
+
+impl Client {
+    fn json(&self, result: Result<ureq::Response, ureq::Error>) -> AppResult<Value> {
+        let response: Value = match result {
+            Ok(r) => r.into_json()?,
+            Err(ureq::Error::Status(code, r)) => r
+                .into_json()
+                .unwrap_or_else(|_| json!({ "error": { "message": format!("HTTP {}", code) } })),
+            Err(e) => return Err(AppError::Process(format!("Gemini API に接続できません: {}", e))),
+        };
+        match api_error(&response) {
+            Some(message) => Err(AppError::Process(format!("Gemini API エラー: {}", message))),
+            None => Ok(response),
+        }
+    }
+
+    /// Resumable upload: start, then upload and finalize in one request
+    fn upload(&self, path: &Path, mime_type: &str) -> AppResult<UploadedFile> {
+        let bytes = fs::read(path)?;
+        let display_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let start = self
+            .agent
+            .post(&format!("{}/upload/v1beta/files", API_BASE))
+            .set("x-goog-api-key", &self.key)
+            .set("X-Goog-Upload-Protocol", "resumable")
+            .set("X-Goog-Upload-Command", "start")
+            .set("X-Goog-Upload-Header-Content-Length", &bytes.len().to_string())
+            .set("X-Goog-Upload-Header-Content-Type", mime_type)
+            .send_json(json!({ "file": { "display_name": display_name } }))
+            .map_err(|e| AppError::Process(format!("Gemini API へのアップロードに失敗しました: {}", e)))?;
+        let upload_url = start
+            .header("x-goog-upload-url")
+            .ok_or_else(|| AppError::Process("アップロード先URLがありません".to_string()))?
+            .to_string();
+
+        let uploaded = self.json(
+            self.agent
+                .post(&upload_url)
+                .set("X-Goog-Upload-Offset", "0")
+                .set("X-Goog-Upload-Command", "upload, finalize")
+                .send_bytes(&bytes),
+        )?;
+        let file = &uploaded["file"];
+        let uploaded = UploadedFile {
+            name: file["name"].as_str().unwrap_or_default().to_string(),
+            uri: file["uri"].as_str().unwrap_or_default().to_string(),
+            mime_type: mime_type.to_string(),
+        };
+        self.wait_until_active(&uploaded.name, file["state"].as_str())?;
+        Ok(uploaded)
+    }
+
+    fn wait_until_active(&self, name: &str, state: Option<&str>) -> AppResult<()> {
+        let deadline = Instant::now() + FILE_ACTIVE_TIMEOUT;
+        let mut state = state.unwrap_or("PROCESSING").to_string();
+        while state == "PROCESSING" {
+            if Instant::now() >= deadline {
+                return Err(AppError::Process(format!("アップロードしたファイルの処理が終わりません: {}", name)));
+            }
+            thread::sleep(FILE_POLL);
+            let file = self.json(
+                self.agent
+                    .get(&format!("{}/v1beta/{}", API_BASE, name))
+                    .set("x-goog-api-key", &self.key)
+                    .call(),
+            )?;
+            state = file["state"].as_str().unwrap_or("ACTIVE").to_string();
+        }
+        if state == "FAILED" {
+            return Err(AppError::Process(format!("Gemini API がファイルを処理できませんでした: {}", name)));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, file: &UploadedFile) {
+        let _ = self
+            .agent
+            .delete(&format!("{}/v1beta/{}", API_BASE, file.name))
+            .set("x-goog-api-key", &self.key)
+            .call();
+    }
+}
+
+/// `generateContent` request body: the files first, then the prompt
+pub fn request_body(attachments: &[Attachment], prompt: &str, json_output: bool) -> Value {
+    let mut parts: Vec<Value> = attachments
+        .iter()
+        .map(|a| match a {
+            Attachment::Uploaded(file) => json!({
+                "file_data": { "mime_type": file.mime_type, "file_uri": file.uri }
+            }),
+            Attachment::Text { name, text } => json!({ "text": format!("--- {} ---\n{}", name, text) }),
+        })
+        .collect();
+    parts.push(json!({ "text": prompt }));
+    let mut body = json!({ "contents": [{ "role": "user", "parts": parts }] });
+    if json_output {
+        body["generationConfig"] = json!({ "responseMimeType": "application/json" });
+    }
+    body
+}
+
+/// Text of a `generateContent` response
+pub fn response_text(response: &Value) -> AppResult<String> {
+    if let Some(message) = api_error(response) {
+        return Err(AppError::Process(format!("Gemini API エラー: {}", message)));
+    }
+    let candidate = &response["candidates"][0];
+    let text: Vec<&str> = candidate["content"]["parts"]
+        .as_array()
+        .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect())
+        .unwrap_or_default();
+    if text.is_empty() {
+        let reason = candidate["finishReason"]
+            .as_str()
+            .or_else(|| response["promptFeedback"]["blockReason"].as_str())
+            .unwrap_or("EMPTY");
+        return Err(AppError::Process(format!("Gemini API の応答にテキストがありません ({})", reason)));
+    }
+    Ok(text.concat())
+}
+
+/// Run a request against the Gemini REST API
+pub fn run_gemini_api(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+    let key = api_key().ok_or_else(|| AppError::Process("Gemini API キーが設定されていません".to_string()))?;
+    let client = Client {
+        agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+        key,
+    };
+
+    let mut attachments = Vec::new();
+    let mut outcome = Ok(());
+    for name in request.files.unwrap_or_default() {
+        let path = temp_dir.join(name);
+        let attachment = match mime_type(name) {
+            Some(mime_type) => client.upload(&path, mime_type).map(Attachment::Uploaded),
+            None => fs::read_to_string(&path)
+                .map(|text| Attachment::Text { name: name.clone(), text })
+                .map_err(AppError::from),
+        };
+        match attachment {
+            Ok(a) => attachments.push(a),
+            Err(e) => {
+                outcome = Err(e);
+                break;
+            }
+        }
+    }
+
+    let result = outcome.and_then(|_| {
+        let body = request_body(&attachments, request.prompt, request.output_format == "json");
+        let response = client.json(
+            client
+                .agent
+                .post(&format!("{}/v1beta/models/{}:generateContent", API_BASE, request.model))
+                .set("x-goog-api-key", &client.key)
+                .send_json(body),
+        )?;
+        note_token_usage(
+            request.model,
+            response["usageMetadata"]["promptTokenCount"].as_i64(),
+            response["usageMetadata"]["candidatesTokenCount"].as_i64(),
+        );
+        response_text(&response)
+    });
+    for attachment in &attachments {
+        if let Attachment::Uploaded(file) = attachment {
+            client.delete(file);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_references_uploaded_files_before_the_prompt() {
+        let attachments = vec![
+            Attachment::Uploaded(UploadedFile {
+                name: "files/abc".to_string(),
+                uri: "https://generativelanguage.googleapis.com/v1beta/files/abc".to_string(),
+                mime_type: "application/pdf".to_string(),
+            }),
+            Attachment::Text { name: "notes.txt".to_string(), text: "メモ".to_string() },
+        ];
+        let body = request_body(&attachments, "チェックして", true);
+        let parts = &body["contents"][0]["parts"];
+        assert_eq!(parts[0]["file_data"]["mime_type"], "application/pdf");
+        assert_eq!(parts[1]["text"], "--- notes.txt ---\nメモ");
+        assert_eq!(parts[2]["text"], "チェックして");
+        assert_eq!(body["generationConfig"]["responseMimeType"], "application/json");
+        assert!(request_body(&[], "x", false).get("generationConfig").is_none());
+    }
+
+    #[test]
+    fn response_text_reports_blocked_and_failed_requests() {
+        let ok = json!({ "candidates": [{ "content": { "parts": [{ "text": "## 書類" }, { "text": "タイプ" }] } }] });
+        assert_eq!(response_text(&ok).unwrap(), "## 書類タイプ");
+        let blocked = json!({ "promptFeedback": { "blockReason": "SAFETY" } });
+        assert!(response_text(&blocked).unwrap_err().to_string().contains("SAFETY"));
+        let error = json!({ "error": { "code": 400, "message": "API key not valid" } });
+        assert!(response_text(&error).unwrap_err().to_string().contains("API key not valid"));
+    }
+}
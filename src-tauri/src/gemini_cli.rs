@@ -1,9 +1,15 @@
+use std::cell::RefCell;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::LazyLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
@@ -11,10 +17,46 @@ use std::os::windows::process::CommandExt;
 #[cfg(target_os = "windows")]
 use crate::CREATE_NO_WINDOW;
 
+use crate::batch::current_batch_aborted;
 use crate::error::{AppError, AppResult};
+use crate::ai_provider::{attempted_backend, current_provider, note_attempted_backend, AiProvider};
+use crate::diagnostics::{
+    clear_process_failure, last_process_stderr, note_process_failure, record_failure,
+};
+use crate::events::{emit_app_event, emit_app_log, AnalysisStreamEvent};
+use crate::messages::tr;
+use crate::processes::{
+    is_process_alive, isolate_process_group, register_child, wait_with_timeout, LineSink,
+};
+use crate::retry::{backoff_delay, is_retryable, jitter_random};
+use crate::settings::{load_settings, save_settings, DEFAULT_GEMINI_TIMEOUT_SECS};
+use crate::shutdown::is_shutting_down;
+use crate::storage::ensure_temp_space;
+use crate::usage::{clear_token_usage, record_usage, usage_record};
 
 static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
+/// Prefix shared by all analysis temp directories
+pub const TEMP_DIR_PREFIX: &str = ".shoruichecker_temp_";
+
+/// Temp dirs younger than this may still belong to a running job (dirs
+/// without an owner PID in their name, from older versions)
+pub const STALE_TEMP_MIN_AGE: Duration = Duration::from_secs(30 * 60);
+
+/// Longest path (in bytes) Win32 APIs accept without the `\\?\` prefix
+const MAX_PATH_LEN: usize = 259;
+
+/// Maximum length (in chars) of a sanitized temp file name stem
+const MAX_TEMP_NAME_CHARS: usize = 80;
+
+/// Characters that break CLI arguments or the `cmd.exe` command line that
+/// starts `gemini.cmd`. Typographic quotes are kept out as well; shells and
+/// terminals treat some of them as ordinary quotes.
+const UNSAFE_NAME_CHARS: &[char] = &[
+    '\'', '"', '`', '$', '&', ';', '|', '<', '>', '@', '‘', '’', '‚', '‛', '“', '”', '„',
+    '＆', '＄', '｀', '＂', '＇',
+];
+
 pub fn gemini_cmd_path() -> String {
     // 環境変数で明示的に指定されていればそれを使用
     if let Ok(path) = std::env::var("GEMINI_CMD_PATH") {
@@ -24,15 +66,34 @@ pub fn gemini_cmd_path() -> String {
     if cfg!(target_os = "windows") {
         "gemini.cmd".to_string()
     } else {
-        "gemini".to_string()
+        // Apps started from Finder / the desktop do not get the shell PATH,
+        // so the usual npm global bin folders are searched as well
+        let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).collect())
+            .unwrap_or_default();
+        if let Some(home) = dirs::home_dir() {
+            dirs.extend([".npm-global/bin", ".volta/bin", ".local/bin"].map(|d| home.join(d)));
+        }
+        dirs.extend(["/opt/homebrew/bin", "/usr/local/bin"].map(PathBuf::from));
+        find_executable("gemini", &dirs)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| "gemini".to_string())
     }
 }
 
+/// First `dir/name` that exists as a file
+fn find_executable(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file())
+}
+
 pub struct GeminiRequest<'a> {
     pub prompt: &'a str,
     pub model: &'a str,
     pub files: Option<&'a [String]>,
     pub output_format: &'a str,
+    /// Send the output as `analysis-stream` events while it arrives (only
+    /// within `with_output_stream`)
+    pub stream: bool,
 }
 
 impl<'a> GeminiRequest<'a> {
@@ -42,6 +103,7 @@ impl<'a> GeminiRequest<'a> {
             model,
             files: None,
             output_format: "text",
+            stream: false,
         }
     }
 
@@ -51,6 +113,7 @@ impl<'a> GeminiRequest<'a> {
             model,
             files: Some(files),
             output_format: "text",
+            stream: false,
         }
     }
 
@@ -60,33 +123,219 @@ impl<'a> GeminiRequest<'a> {
             model,
             files: None,
             output_format: "json",
+            stream: false,
         }
     }
+
+    /// The same request with its output streamed
+    pub fn streamed(self) -> Self {
+        Self { stream: true, ..self }
+    }
+}
+
+/// Analysis the streamed output of a thread belongs to
+#[derive(Clone)]
+struct StreamTarget {
+    analysis_id: String,
+    file_name: String,
+}
+
+thread_local! {
+    static STREAM_TARGET: RefCell<Option<StreamTarget>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous stream target of the thread when dropped
+struct StreamReset(Option<StreamTarget>);
+
+impl Drop for StreamReset {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        STREAM_TARGET.with(|target| *target.borrow_mut() = previous);
+    }
+}
+
+/// Run `f` with the output of its streamed requests sent as
+/// `analysis-stream` events of the analysis (batch) and file
+pub fn with_output_stream<R>(analysis_id: &str, file_name: &str, f: impl FnOnce() -> R) -> R {
+    let target = StreamTarget {
+        analysis_id: analysis_id.to_string(),
+        file_name: file_name.to_string(),
+    };
+    let previous = STREAM_TARGET.with(|t| t.replace(Some(target)));
+    let _reset = StreamReset(previous);
+    f()
+}
+
+/// Sink sending the lines that survive `clean_gemini_output` to the frontend;
+/// the first event of a run has `first` set (a retry starts over)
+fn stream_sink(target: StreamTarget) -> LineSink {
+    let patterns = compile_patterns(&noise_patterns());
+    let mut first = true;
+    Box::new(move |line: &str| {
+        let line = strip_ansi(line.trim_end_matches(['\r', '\n']));
+        if is_noise(&line, &patterns) {
+            return;
+        }
+        emit_app_event(
+            "analysis-stream",
+            AnalysisStreamEvent {
+                analysis_id: target.analysis_id.clone(),
+                file_name: target.file_name.clone(),
+                chunk: format!("{}\n", line),
+                first,
+            },
+        );
+        first = false;
+    })
 }
 
+/// Run an AI request with the configured provider
+///
+/// After a timeout the temp dir is removed right away: the killed CLI may
+/// have left partial output behind.
 pub fn run_gemini(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
-    let prompt_file = temp_dir.join("prompt.txt");
-    fs::write(&prompt_file, request.prompt)?;
+    run_gemini_with(current_provider().as_ref(), temp_dir, request)
+}
 
-    let gemini_path = gemini_cmd_path();
-    let ps_script = build_ps_script(&gemini_path, request);
-
-    let script_file = temp_dir.join("run.ps1");
-    fs::write(&script_file, &ps_script).map_err(|e| e.to_string())?;
-
-    let mut cmd = Command::new("powershell");
-    cmd.args([
-        "-NoProfile",
-        "-ExecutionPolicy",
-        "Bypass",
-        "-File",
-        &script_file.to_string_lossy(),
-    ])
-    .current_dir(temp_dir);
+/// Run an AI request with a given provider (e.g. the second opinion of the
+/// consensus mode), with the same sandbox check and failure diagnostics
+pub fn run_gemini_with(
+    provider: &dyn AiProvider,
+    temp_dir: &Path,
+    request: &GeminiRequest<'_>,
+) -> AppResult<String> {
+    check_sandbox(temp_dir, request.files.unwrap_or_default(), &temp_root())?;
+    let retry = load_settings().retry;
+    let started = Instant::now();
+    let mut attempt = 1;
+    let result = loop {
+        clear_process_failure();
+        clear_token_usage();
+        note_attempted_backend(provider.backend());
+        match provider.run(temp_dir, request) {
+            Err(e)
+                if attempt < retry.max_attempts
+                    && is_retryable(&e, last_process_stderr().as_deref())
+                    && !is_shutting_down()
+                    && !current_batch_aborted() =>
+            {
+                let delay = backoff_delay(&retry, attempt, jitter_random());
+                attempt += 1;
+                emit_app_log(
+                    &tr("provider.retry", &[&delay.as_secs(), &attempt, &retry.max_attempts, &e]),
+                    "wave",
+                );
+                std::thread::sleep(delay);
+                // Aborted or shut down while waiting
+                if is_shutting_down() || current_batch_aborted() {
+                    break Err(e);
+                }
+            }
+            result => break result,
+        }
+    };
+    // In a fallback chain the backend that answered (or failed last), not
+    // the first of the chain
+    let backend = attempted_backend().unwrap_or_else(|| provider.backend());
+    let result = result.map_err(|e| record_failure(temp_dir, request, backend, e));
+    record_usage(&usage_record(
+        backend,
+        request.model,
+        request.prompt,
+        request.files.map_or(0, |f| f.len()),
+        started.elapsed(),
+        result.as_deref().ok(),
+    ));
+    if matches!(result, Err(AppError::Timeout(_))) {
+        cleanup_temp_dir(temp_dir);
+    }
+    result
+}
+
+/// Time limit of a CLI run (`gemini_timeout_secs`, 0 disables it)
+pub fn gemini_timeout() -> Option<Duration> {
+    let secs = load_settings()
+        .gemini_timeout_secs
+        .unwrap_or(DEFAULT_GEMINI_TIMEOUT_SECS);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// CLI arguments of a request; the prompt itself is not an argument
+///
+/// The CLI runs in the temp dir, which is its workspace; the attached files
+/// are passed by their names inside it.
+fn cli_args(request: &GeminiRequest<'_>) -> Vec<String> {
+    let mut args = vec![
+        "-m".to_string(),
+        request.model.to_string(),
+        "-o".to_string(),
+        request.output_format.to_string(),
+    ];
+    args.extend(request.files.unwrap_or_default().iter().cloned());
+    args
+}
+
+/// Command starting the CLI for a request
+///
+/// The binary (`gemini.cmd` on Windows) is started directly in the temp dir;
+/// the prompt goes to its stdin, so nothing but the copied documents is
+/// written to the temp dir.
+fn cli_command(gemini_path: &str, temp_dir: &Path, request: &GeminiRequest<'_>) -> Command {
+    let mut cmd = Command::new(gemini_path);
+    cmd.args(cli_args(request)).current_dir(temp_dir);
     #[cfg(target_os = "windows")]
     cmd.creation_flags(CREATE_NO_WINDOW);
+    cmd
+}
+
+/// Write the prompt to the CLI's stdin and close it
+///
+/// Written from a separate thread so a CLI that already prints while reading
+/// cannot block on a full stdout pipe. A CLI that exits early closes the pipe;
+/// its exit status reports the actual error.
+fn feed_prompt(stdin: std::process::ChildStdin, prompt: &str) -> JoinHandle<()> {
+    let prompt = prompt.to_string();
+    std::thread::spawn(move || {
+        let mut stdin = stdin;
+        let _ = stdin.write_all(prompt.as_bytes());
+    })
+}
+
+/// Run a request with the Gemini CLI in the temp dir
+pub fn run_gemini_cli(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+    let gemini_path = gemini_cmd_path();
+    let mut cmd = cli_command(&gemini_path, temp_dir, request);
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    isolate_process_group(&mut cmd);
 
-    let output = cmd.output().map_err(AppError::from)?;
+    let mut child = cmd.spawn().map_err(|e| {
+        AppError::Process(format!("Gemini CLI を起動できません ({}): {}", gemini_path, e))
+    })?;
+    let _guard = register_child(child.id(), "gemini");
+    let writer = child.stdin.take().map(|stdin| feed_prompt(stdin, request.prompt));
+    let timeout = gemini_timeout();
+    let on_line = (request.stream && request.output_format == "text")
+        .then(|| STREAM_TARGET.with(|target| target.borrow().clone()))
+        .flatten()
+        .map(stream_sink);
+    let waited = wait_with_timeout(child, timeout, on_line).map_err(AppError::from)?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let output = match waited {
+        Ok(output) => output,
+        Err(stderr) => {
+            note_process_failure(None, &stderr);
+            let secs = timeout.map(|t| t.as_secs()).unwrap_or_default();
+            let detail = format!(
+                "Gemini CLI が {} 秒以内に応答しなかったため中断しました。再試行してください",
+                secs
+            );
+            return Err(AppError::Timeout(detail));
+        }
+    };
     if output.status.success() {
         let result = String::from_utf8_lossy(&output.stdout).to_string();
         Ok(clean_gemini_output(&result))
@@ -98,6 +347,7 @@ pub fn run_gemini(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<Str
             .unwrap_or_else(|| "terminated".to_string());
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        note_process_failure(output.status.code(), &stderr);
         let detail = if stdout.trim().is_empty() {
             format!("{}: {}", status, stderr)
         } else {
@@ -130,10 +380,97 @@ pub fn run_gemini_with_prompt(
     run_gemini(temp_dir, &request)
 }
 
+/// Default root for analysis temp dirs (`%TEMP%/shoruichecker`)
+pub fn default_temp_root() -> PathBuf {
+    std::env::temp_dir().join("shoruichecker")
+}
+
+/// Root directory under which all analysis temp dirs are created
+///
+/// This is the CLI sandbox: each call runs in its own temp dir below it and
+/// may only see files inside that dir (see `check_sandbox`). Uses the
+/// `temp_root` setting when configured.
+pub fn temp_root() -> PathBuf {
+    load_settings()
+        .temp_root
+        .map(PathBuf::from)
+        .unwrap_or_else(default_temp_root)
+}
+
+/// Whether a path is inside a OneDrive-synced folder
+///
+/// Sync clients lock and re-upload temp files, which races with temp dir
+/// deletion after each analysis.
+pub fn is_onedrive_path(path: &Path, env: impl Fn(&str) -> Option<String>) -> bool {
+    let under_sync_root = ["OneDrive", "OneDriveCommercial", "OneDriveConsumer"]
+        .iter()
+        .filter_map(|key| env(key))
+        .filter(|root| !root.is_empty())
+        .any(|root| path.starts_with(root));
+    under_sync_root
+        || path
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with("OneDrive"))
+}
+
+/// Check that a folder can be used as the temp root
+///
+/// The folder must be absolute, outside OneDrive, writable, and usable as the
+/// working directory of the gemini CLI. A CLI that does not run at all is a
+/// failure too: the folder could not be checked.
+pub fn validate_temp_root(path: &Path) -> Result<(), String> {
+    check_temp_root(path, |dir| {
+        let mut cmd = Command::new(gemini_cmd_path());
+        cmd.arg("--version");
+        if let Some(dir) = dir {
+            cmd.current_dir(dir);
+        }
+        #[cfg(target_os = "windows")]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+        cmd.output().map(|o| o.status.success()).unwrap_or(false)
+    })
+}
+
+/// `validate_temp_root` with the CLI run given (`gemini_runs_in(Some(dir))`
+/// runs it in the folder, `None` in the app's working directory)
+fn check_temp_root(path: &Path, gemini_runs_in: impl Fn(Option<&Path>) -> bool) -> Result<(), String> {
+    if !path.is_absolute() {
+        return Err("作業フォルダは絶対パスで指定してください".to_string());
+    }
+    if dirs::home_dir().is_some_and(|home| home.starts_with(path)) {
+        return Err("ユーザーフォルダ全体を含むフォルダは作業フォルダにできません".to_string());
+    }
+    if is_onedrive_path(path, |k| std::env::var(k).ok()) {
+        return Err(
+            "OneDrive の同期対象フォルダは指定できません（同期と一時ファイルの削除が競合します）"
+                .to_string(),
+        );
+    }
+
+    let probe = path.join(format!("{}probe-{}", TEMP_DIR_PREFIX, unique_suffix()));
+    fs::create_dir_all(&probe)
+        .and_then(|_| fs::write(probe.join("prompt.txt"), b"ok"))
+        .map_err(|e| format!("作業フォルダに書き込めません: {}", e))?;
+
+    let accessible = gemini_runs_in(Some(&probe));
+    cleanup_temp_dir(&probe);
+
+    if accessible {
+        Ok(())
+    } else if gemini_runs_in(None) {
+        Err("gemini CLI からこのフォルダにアクセスできません".to_string())
+    } else {
+        Err("gemini CLI を実行できないため作業フォルダを確認できません（環境診断で確認してください）".to_string())
+    }
+}
+
+/// Create a unique temp dir; its name carries the PID of this process
+/// (`<prefix>-p<pid>-<unique>`) so other instances leave it alone while the
+/// process runs
 pub fn create_temp_dir(prefix: &str) -> AppResult<PathBuf> {
-    let base_dir = std::env::temp_dir();
+    let base_dir = temp_root();
     let unique = unique_suffix();
-    let dir_name = format!("{}-{}", prefix, unique);
+    let dir_name = format!("{}-p{}-{}", prefix, std::process::id(), unique);
     let temp_dir = base_dir.join(dir_name);
     fs::create_dir_all(&temp_dir)?;
     Ok(temp_dir)
@@ -143,46 +480,281 @@ pub fn cleanup_temp_dir(temp_dir: &Path) {
     let _ = fs::remove_dir_all(temp_dir);
 }
 
-fn build_ps_script(gemini_path: &str, request: &GeminiRequest<'_>) -> String {
-    let gemini_path = gemini_path.replace("'", "''");
-    let model = request.model;
-    let output_format = request.output_format;
-    if let Some(files) = request.files {
-        let file_array = files
-            .iter()
-            .map(|f| format!("    '{}'", f.replace("'", "''")))
-            .collect::<Vec<_>>()
-            .join(",\n");
-        format!(
-            r#"$OutputEncoding = [Console]::OutputEncoding = [Text.Encoding]::UTF8
-$files = @(
-{}
-)
-Get-Content -Raw -Encoding UTF8 'prompt.txt' | & '{}' -m {} -o {} $files
-"#,
-            file_array, gemini_path, model, output_format
-        )
+/// PID of the process that created a temp dir (None for older names)
+fn temp_dir_owner(name: &str) -> Option<u32> {
+    // `<prefix>-p<pid>-<millis>-<counter>`
+    name.rsplitn(4, '-').nth(2)?.strip_prefix('p')?.parse().ok()
+}
+
+/// Remove stale temp dirs from the app temp root and legacy locations
+///
+/// Dirs whose owner process is still running (another instance, e.g. a
+/// headless run next to the GUI) are kept however old they are; the CLI can
+/// run for a long time without touching them. Dirs without an owner are
+/// removed once older than `min_age`. Legacy locations are the system temp
+/// dir and the user home, where older versions created
+/// `.shoruichecker_temp_*` directly. Returns the number of removed
+/// directories.
+pub fn sweep_stale_temp_dirs(min_age: Duration) -> usize {
+    let mut roots = vec![temp_root(), std::env::temp_dir()];
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home);
+    }
+    roots.dedup();
+    let is_alive = |pid: u32| pid == std::process::id() || is_process_alive(pid);
+    roots.iter().map(|root| sweep_dir(root, min_age, &is_alive)).sum()
+}
+
+fn sweep_dir(root: &Path, min_age: Duration, is_alive: &dyn Fn(u32) -> bool) -> usize {
+    let Ok(entries) = fs::read_dir(root) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(TEMP_DIR_PREFIX) || !entry.path().is_dir() {
+            continue;
+        }
+        let stale = match temp_dir_owner(&name) {
+            Some(pid) => !is_alive(pid),
+            None => {
+                let age = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| now.duration_since(t).ok())
+                    .unwrap_or_default();
+                age >= min_age
+            }
+        };
+        if stale && fs::remove_dir_all(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Convert an absolute Windows path to its `\\?\` extended-length form
+pub fn to_extended_length_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") {
+        path.to_string()
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
     } else {
-        format!(
-            r#"$OutputEncoding = [Console]::OutputEncoding = [Text.Encoding]::UTF8
-Get-Content -Raw -Encoding UTF8 'prompt.txt' | & '{}' -m {} -o {}
-"#,
-            gemini_path, model, output_format
-        )
+        format!(r"\\?\{}", path)
     }
 }
 
-pub fn clean_gemini_output(output: &str) -> String {
-    output
-        .lines()
-        .filter(|line| {
-            !line.contains("Loaded cached credentials")
-                && !line.contains("Hook registry initialized")
+/// Use the extended-length form for paths over MAX_PATH on Windows
+pub fn long_path(path: &Path) -> PathBuf {
+    if cfg!(target_os = "windows") && path.is_absolute() && path.as_os_str().len() > MAX_PATH_LEN {
+        let normalized = path.to_string_lossy().replace('/', "\\");
+        PathBuf::from(to_extended_length_path(&normalized))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Make a file name safe for the temp copy passed to the CLI
+///
+/// Replaces quote/shell metacharacters with `_` and shortens long names while
+/// keeping the extension.
+pub fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if UNSAFE_NAME_CHARS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
         })
+        .collect();
+
+    let (stem, ext) = match cleaned.rfind('.') {
+        Some(i) if i > 0 => (&cleaned[..i], &cleaned[i..]),
+        _ => (cleaned.as_str(), ""),
+    };
+    let stem: String = stem.chars().take(MAX_TEMP_NAME_CHARS).collect();
+    let stem = stem.trim_end_matches(['.', ' ']);
+    let stem = if stem.is_empty() { "file" } else { stem };
+    format!("{}{}", stem, ext)
+}
+
+/// Sanitized name not yet taken in the temp dir
+///
+/// Files with the same name from different folders (a comparison set, or a
+/// reference document) would overwrite each other; the later ones get `_2`,
+/// `_3`, … before the extension.
+fn available_temp_name(temp_dir: &Path, file_name: &str) -> String {
+    let name = sanitize_file_name(file_name);
+    let taken = |name: &str| long_path(&temp_dir.join(name)).exists();
+    if !taken(&name) {
+        return name;
+    }
+    let (stem, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name.as_str(), ""),
+    };
+    (2..)
+        .map(|i| format!("{}_{}{}", stem, i, ext))
+        .find(|candidate| !taken(candidate))
+        .expect("unbounded suffixes")
+}
+
+/// Copy a source file into the temp dir under a sanitized, unused name
+///
+/// Returns the temp file name to pass to the CLI.
+pub fn copy_into_temp(temp_dir: &Path, source: &str, file_name: &str) -> AppResult<String> {
+    let temp_name = available_temp_name(temp_dir, file_name);
+    let dest_path = temp_dir.join(&temp_name);
+    let size = fs::metadata(long_path(Path::new(source)))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    ensure_temp_space(size).map_err(AppError::Io)?;
+    fs::copy(long_path(Path::new(source)), long_path(&dest_path))
+        .map_err(|e| AppError::Io(format!("ファイルコピーエラー: {}", e)))?;
+    Ok(temp_name)
+}
+
+/// Whether `path` lies inside `root` (both resolved, so `..` and links count)
+fn is_within(path: &Path, root: &Path) -> bool {
+    match (path.canonicalize(), root.canonicalize()) {
+        (Ok(path), Ok(root)) => path.starts_with(root),
+        _ => false,
+    }
+}
+
+/// Make a source file available to the CLI without copying when possible
+///
+/// Large PDFs under the user home are not duplicated: a hard link under the
+/// sanitized name is tried first (same volume, no extra disk use). Everything
+/// else is copied like `copy_into_temp`, so the CLI never gets a path
+/// outside its temp dir. Returns the file name to pass to the CLI.
+pub fn stage_into_temp(temp_dir: &Path, source: &str, file_name: &str) -> AppResult<String> {
+    stage_with_home(temp_dir, source, file_name, dirs::home_dir().as_deref())
+}
+
+fn stage_with_home(
+    temp_dir: &Path,
+    source: &str,
+    file_name: &str,
+    home: Option<&Path>,
+) -> AppResult<String> {
+    let source_path = Path::new(source);
+    if home.is_some_and(|home| is_within(source_path, home)) {
+        let temp_name = available_temp_name(temp_dir, file_name);
+        if fs::hard_link(long_path(source_path), long_path(&temp_dir.join(&temp_name))).is_ok() {
+            return Ok(temp_name);
+        }
+    }
+    copy_into_temp(temp_dir, source, file_name)
+}
+
+/// Check that a CLI call only exposes its own temp dir in the sandbox
+///
+/// The working directory must be a directory below `root`, and every file
+/// passed to the CLI must resolve inside the working directory (relative
+/// names are taken from it; `..` and links are resolved).
+pub fn check_sandbox(temp_dir: &Path, files: &[String], root: &Path) -> AppResult<()> {
+    let inside_root = match (temp_dir.canonicalize(), root.canonicalize()) {
+        (Ok(dir), Ok(root)) => dir != root && dir.starts_with(root),
+        _ => false,
+    };
+    if !inside_root {
+        return Err(AppError::Process(format!(
+            "作業フォルダがサンドボックス外です: {}",
+            temp_dir.display()
+        )));
+    }
+    for file in files {
+        if !is_within(&temp_dir.join(file), temp_dir) {
+            return Err(AppError::Process(format!(
+                "サンドボックス外のファイルは Gemini CLI に渡せません: {}",
+                file
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Noise lines the CLI writes to stdout (regexes; `output_noise_patterns`
+/// replaces this list)
+pub const DEFAULT_NOISE_PATTERNS: &[&str] = &[
+    "Loaded cached credentials",
+    "Hook registry initialized",
+    r"^\s*(Gemini CLI )?[Uu]pdate available",
+    r"^\s*Run `?npm install -g @google/gemini-cli",
+    r"(?i)^\s*(configured|connected to|connecting to|loading) (\d+ )?MCP server",
+    r"^\s*Data collection is disabled",
+];
+
+/// Noise patterns in use: the setting, else the defaults
+pub fn noise_patterns() -> Vec<String> {
+    load_settings().output_noise_patterns.unwrap_or_else(|| {
+        DEFAULT_NOISE_PATTERNS.iter().map(|p| p.to_string()).collect()
+    })
+}
+
+/// ANSI escape sequences (colors, cursor moves, terminal titles)
+static ANSI_ESCAPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b[@-Z\\^_]")
+        .expect("valid pattern")
+});
+
+/// Remove ANSI escape sequences from CLI output
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").to_string()
+}
+
+/// Noise patterns as regexes (invalid patterns are ignored)
+fn compile_patterns(patterns: &[String]) -> Vec<Regex> {
+    patterns.iter().filter_map(|p| Regex::new(p).ok()).collect()
+}
+
+fn is_noise(line: &str, patterns: &[Regex]) -> bool {
+    patterns.iter().any(|re| re.is_match(line))
+}
+
+/// Output without ANSI sequences and lines matching a noise pattern
+/// (invalid patterns are ignored)
+pub fn clean_output(output: &str, patterns: &[String]) -> String {
+    let patterns = compile_patterns(patterns);
+    strip_ansi(output)
+        .lines()
+        .filter(|line| !is_noise(line, &patterns))
         .collect::<Vec<_>>()
         .join("\n")
 }
 
+pub fn clean_gemini_output(output: &str) -> String {
+    clean_output(output, &noise_patterns())
+}
+
+/// Gemini CLI の出力から除く行のパターン（正規表現）
+#[tauri::command]
+pub fn get_output_noise_patterns() -> Vec<String> {
+    noise_patterns()
+}
+
+/// 除外パターンを設定（None で既定に戻す）
+#[tauri::command]
+pub fn set_output_noise_patterns(patterns: Option<Vec<String>>) -> Result<(), String> {
+    let patterns = patterns.map(|list| {
+        list.into_iter()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+    });
+    for pattern in patterns.iter().flatten() {
+        Regex::new(pattern).map_err(|e| format!("正規表現が不正です ({}): {}", pattern, e))?;
+    }
+    let mut settings = load_settings();
+    settings.output_noise_patterns = patterns;
+    save_settings(&settings)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,9 +773,212 @@ mod tests {
         assert!(!dir1.exists());
         assert!(!dir2.exists());
     }
+
+    #[test]
+    fn sweep_dir_removes_only_prefixed_dirs() {
+        let root = std::env::temp_dir().join(format!("shoruichecker_sweep_test-{}", unique_suffix()));
+        let stale = root.join(format!("{}task_0", TEMP_DIR_PREFIX));
+        let unrelated = root.join("my_documents");
+        fs::create_dir_all(&stale).expect("create stale");
+        fs::create_dir_all(&unrelated).expect("create unrelated");
+
+        assert_eq!(sweep_dir(&root, Duration::ZERO, &|_| false), 1);
+        assert!(!stale.exists());
+        assert!(unrelated.exists());
+
+        cleanup_temp_dir(&root);
+    }
+
+    #[test]
+    fn sweep_dir_keeps_dirs_of_running_instances() {
+        let root = std::env::temp_dir().join(format!("shoruichecker_sweep_test-{}", unique_suffix()));
+        let running = root.join(format!("{}task_0-p100-1700000000000-0", TEMP_DIR_PREFIX));
+        let crashed = root.join(format!("{}task_1-p200-1700000000000-1", TEMP_DIR_PREFIX));
+        let legacy = root.join(format!("{}task_2-1700000000000-2", TEMP_DIR_PREFIX));
+        for dir in [&running, &crashed, &legacy] {
+            fs::create_dir_all(dir).expect("create dir");
+        }
+        assert_eq!(temp_dir_owner(&running.file_name().unwrap().to_string_lossy()), Some(100));
+        assert_eq!(temp_dir_owner(&legacy.file_name().unwrap().to_string_lossy()), None);
+
+        // However old, a running owner's dir stays; a fresh legacy dir too
+        assert_eq!(sweep_dir(&root, Duration::from_secs(3600), &|pid| pid == 100), 1);
+        assert!(running.exists());
+        assert!(!crashed.exists());
+        assert!(legacy.exists());
+
+        cleanup_temp_dir(&root);
+    }
+
+    #[test]
+    fn sanitize_file_name_replaces_unsafe_chars() {
+        let name = sanitize_file_name("“見積”O'Neil＆工事 $1 & 2.pdf");
+        assert_eq!(name, "_見積_O_Neil_工事 _1 _ 2.pdf");
+        assert_eq!(sanitize_file_name("契約書.pdf"), "契約書.pdf");
+        assert_eq!(sanitize_file_name("...pdf"), "file.pdf");
+    }
+
+    #[test]
+    fn sanitize_file_name_shortens_long_names() {
+        let long_name = format!("{}.pdf", "長".repeat(300));
+        let name = sanitize_file_name(&long_name);
+        assert_eq!(name.chars().count(), MAX_TEMP_NAME_CHARS + 4);
+        assert!(name.ends_with(".pdf"));
+    }
+
+    #[test]
+    fn to_extended_length_path_handles_drive_and_unc() {
+        assert_eq!(to_extended_length_path(r"C:\a\b.pdf"), r"\\?\C:\a\b.pdf");
+        assert_eq!(to_extended_length_path(r"\\srv\share\b.pdf"), r"\\?\UNC\srv\share\b.pdf");
+        assert_eq!(to_extended_length_path(r"\\?\C:\a.pdf"), r"\\?\C:\a.pdf");
+    }
+
+    #[test]
+    fn clean_output_strips_ansi_and_noise_lines() {
+        let output = "Loaded cached credentials.\n\x1b[33mUpdate available! 0.9.0 → 0.10.0\x1b[0m\nConfigured 2 MCP servers\n\x1b]0;gemini\x07## 書類タイプ\n\x1b[1m⚠ 金額不整合\x1b[22m";
+        let defaults: Vec<String> = DEFAULT_NOISE_PATTERNS.iter().map(|p| p.to_string()).collect();
+        assert_eq!(clean_output(output, &defaults), "## 書類タイプ\n⚠ 金額不整合");
+        // Custom lists replace the defaults; invalid patterns are skipped
+        let custom = vec!["^Loaded".to_string(), "(".to_string()];
+        assert_eq!(
+            clean_output("Loaded x\nConfigured 2 MCP servers", &custom),
+            "Configured 2 MCP servers"
+        );
+    }
+
+    #[test]
+    fn cli_args_pass_files_by_name() {
+        let files = vec!["契約書 O'Neil.pdf".to_string()];
+        let request = GeminiRequest::text_with_files("p", "gemini-2.5-pro", &files);
+        assert_eq!(
+            cli_args(&request),
+            vec!["-m", "gemini-2.5-pro", "-o", "text", "契約書 O'Neil.pdf"]
+        );
+        assert_eq!(cli_args(&GeminiRequest::json("p", "m")).len(), 4);
+    }
+
+    #[test]
+    fn find_executable_searches_the_dirs_in_order() {
+        let first = create_temp_dir(".shoruichecker_test_bin1").expect("create dir");
+        let second = create_temp_dir(".shoruichecker_test_bin2").expect("create dir");
+        fs::write(second.join("gemini"), b"#!/bin/sh").expect("write");
+        let dirs = vec![first.clone(), second.clone()];
+        assert_eq!(find_executable("gemini", &dirs), Some(second.join("gemini")));
+        assert_eq!(find_executable("npm", &dirs), None);
+        cleanup_temp_dir(&first);
+        cleanup_temp_dir(&second);
+    }
+
+    #[test]
+    fn check_sandbox_rejects_paths_outside_the_temp_dir() {
+        let dir = create_temp_dir(".shoruichecker_test_sandbox").expect("create dir");
+        fs::write(dir.join("a.pdf"), b"%PDF-1.4").expect("write");
+        let outside = create_temp_dir(".shoruichecker_test_outside").expect("create dir");
+        fs::write(outside.join("b.pdf"), b"%PDF-1.4").expect("write");
+        let root = temp_root();
+
+        assert!(check_sandbox(&dir, &["a.pdf".to_string()], &root).is_ok());
+        let absolute = outside.join("b.pdf").to_string_lossy().to_string();
+        assert!(check_sandbox(&dir, &[absolute], &root).is_err());
+        let escaped = format!("../{}/b.pdf", outside.file_name().unwrap().to_string_lossy());
+        assert!(check_sandbox(&dir, &[escaped], &root).is_err());
+        // The root itself and folders outside it are not sandboxes
+        assert!(check_sandbox(&root, &[], &root).is_err());
+        assert!(check_sandbox(&std::env::temp_dir(), &[], &root).is_err());
+
+        cleanup_temp_dir(&dir);
+        cleanup_temp_dir(&outside);
+    }
+
+    #[test]
+    fn copy_into_temp_handles_special_names() {
+        let src_dir = create_temp_dir(".shoruichecker_test_src").expect("create src");
+        let dest_dir = create_temp_dir(".shoruichecker_test_dest").expect("create dest");
+        let name = "“注文書”＆請求 O'Brien.pdf";
+        let src = src_dir.join(name);
+        fs::write(&src, b"%PDF-1.4").expect("write src");
+
+        let temp_name =
+            copy_into_temp(&dest_dir, &src.to_string_lossy(), name).expect("copy into temp");
+        assert_eq!(temp_name, sanitize_file_name(name));
+        assert!(dest_dir.join(&temp_name).exists());
+
+        cleanup_temp_dir(&src_dir);
+        cleanup_temp_dir(&dest_dir);
+    }
+
+    #[test]
+    fn files_with_the_same_name_get_their_own_temp_copy() {
+        let src_dir = create_temp_dir(".shoruichecker_test_src").expect("create src");
+        let dest_dir = create_temp_dir(".shoruichecker_test_dest").expect("create dest");
+        let mut staged = Vec::new();
+        for (folder, content) in [("A社", "%PDF-a"), ("B社", "%PDF-b"), ("C社", "%PDF-c")] {
+            fs::create_dir_all(src_dir.join(folder)).unwrap();
+            let src = src_dir.join(folder).join("請求書.pdf");
+            fs::write(&src, content).unwrap();
+            staged.push(copy_into_temp(&dest_dir, &src.to_string_lossy(), "請求書.pdf").expect("copy"));
+        }
+        assert_eq!(staged, vec!["請求書.pdf", "請求書_2.pdf", "請求書_3.pdf"]);
+        assert_eq!(fs::read(dest_dir.join("請求書_2.pdf")).unwrap(), b"%PDF-b");
+
+        cleanup_temp_dir(&src_dir);
+        cleanup_temp_dir(&dest_dir);
+    }
+
+    #[test]
+    fn onedrive_paths_are_detected() {
+        let env = |k: &str| (k == "OneDriveCommercial").then(|| "/users/me/Contoso".to_string());
+        assert!(is_onedrive_path(Path::new("/users/me/Contoso/temp"), env));
+        assert!(is_onedrive_path(Path::new("/users/me/OneDrive - Contoso/temp"), |_| None));
+        assert!(!is_onedrive_path(Path::new("/data/shoruichecker"), env));
+    }
+
+    #[test]
+    fn validate_temp_root_rejects_relative_and_accepts_writable_dir() {
+        assert!(validate_temp_root(Path::new("relative/dir")).is_err());
+
+        let dir = create_temp_dir(".shoruichecker_test_root").expect("create dir");
+        assert!(check_temp_root(&dir, |_| true).is_ok());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn temp_root_fails_when_the_cli_cannot_run_there() {
+        let dir = create_temp_dir(".shoruichecker_test_root").expect("create dir");
+        let blocked = check_temp_root(&dir, |dir| dir.is_none()).unwrap_err();
+        assert!(blocked.contains("アクセスできません"));
+        let missing = check_temp_root(&dir, |_| false).unwrap_err();
+        assert!(missing.contains("実行できない"));
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn stage_into_temp_links_files_under_home() {
+        let src_dir = create_temp_dir(".shoruichecker_test_src").expect("create src");
+        let dest_dir = create_temp_dir(".shoruichecker_test_dest").expect("create dest");
+        let name = "竣工図書’.pdf";
+        let src = src_dir.join(name);
+        fs::write(&src, b"%PDF-1.4").expect("write src");
+        let source = src.to_string_lossy().to_string();
+
+        let staged = stage_with_home(&dest_dir, &source, name, Some(&src_dir)).expect("stage");
+        assert_eq!(staged, sanitize_file_name(name));
+        fs::write(&src, b"%PDF-1.7").expect("rewrite src");
+        assert_eq!(fs::read(dest_dir.join(&staged)).unwrap(), b"%PDF-1.7");
+
+        // Outside the home: plain copy
+        fs::remove_file(dest_dir.join(&staged)).unwrap();
+        let staged = stage_with_home(&dest_dir, &source, name, None).expect("stage");
+        fs::write(&src, b"%PDF-2.0").expect("rewrite src");
+        assert_eq!(fs::read(dest_dir.join(&staged)).unwrap(), b"%PDF-1.7");
+
+        cleanup_temp_dir(&src_dir);
+        cleanup_temp_dir(&dest_dir);
+    }
 }
 
-fn unique_suffix() -> String {
+pub(crate) fn unique_suffix() -> String {
     let millis = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_millis())
@@ -0,0 +1,393 @@
+//! Regression check of the analysis against a golden sample set
+//!
+//! The user keeps a folder of sample PDFs, each with `<name>.expected.json`
+//! listing the findings the analysis should report. The current prompt and
+//! model are run over every sample without saving anything, the findings are
+//! matched against the expected ones and precision/recall are compared with
+//! the baseline run of the folder, so prompt edits and model switches can be
+//! checked before they are rolled out. A run becomes the new baseline only
+//! when every sample was analyzed and nothing regressed, or when it is
+//! accepted explicitly; otherwise a bad run would hide the regression from
+//! the next one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{reanalyze_without_saving, resolve_preset};
+use crate::freshness::{findings_of, normalize_finding};
+use crate::tasks::run_blocking;
+
+const EXPECTED_SUFFIX: &str = ".expected.json";
+/// Summary of the baseline run, kept in the sample folder
+const LAST_RUN_FILE: &str = ".golden_last_run.json";
+
+/// `<name>.expected.json`: findings the analysis of `<name>.pdf` should report
+///
+/// Each finding is a key phrase; a reported finding containing it counts as a
+/// hit. An empty list means the document has no problems.
+#[derive(Deserialize)]
+pub struct ExpectedFindings {
+    #[serde(default)]
+    pub findings: Vec<String>,
+}
+
+/// Findings of one sample matched against the expected ones
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct SampleScore {
+    /// Expected findings that were reported
+    pub matched: Vec<String>,
+    /// Expected findings that were not reported
+    pub missed: Vec<String>,
+    /// Reported findings matching no expected one
+    pub unexpected: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SampleResult {
+    pub file_name: String,
+    pub score: SampleScore,
+    /// Analysis error (the sample is left out of the metrics)
+    pub error: Option<String>,
+}
+
+/// Precision/recall over a set of samples
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Metrics {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub precision: f64,
+    pub recall: f64,
+}
+
+/// What is kept of a run to compare the next one with
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GoldenRun {
+    pub run_at: String,
+    pub model: String,
+    pub preset: Option<String>,
+    pub metrics: Metrics,
+    /// Missed expected findings per sample
+    #[serde(default)]
+    pub missed: Vec<(String, Vec<String>)>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct GoldenReport {
+    pub folder: String,
+    pub run: GoldenRun,
+    pub samples: Vec<SampleResult>,
+    pub previous: Option<GoldenRun>,
+    /// Change since the previous run (None on the first run)
+    pub precision_change: Option<f64>,
+    pub recall_change: Option<f64>,
+    /// Expected findings found last time but missed now ("sample: finding")
+    pub regressions: Vec<String>,
+    /// The run was saved as the new baseline
+    pub baseline_saved: bool,
+}
+
+impl GoldenReport {
+    /// Precision or recall dropped since the previous run
+    pub fn is_regression(&self) -> bool {
+        self.precision_change.is_some_and(|c| c < 0.0)
+            || self.recall_change.is_some_and(|c| c < 0.0)
+            || !self.regressions.is_empty()
+    }
+
+    /// Every sample was analyzed and nothing regressed
+    pub fn is_clean(&self) -> bool {
+        self.samples.iter().all(|s| s.error.is_none()) && !self.is_regression()
+    }
+}
+
+/// Sample PDFs of a folder with their expectation files, by name
+pub fn golden_samples(folder: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let mut samples: Vec<(PathBuf, PathBuf)> = fs::read_dir(folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")))
+        .filter_map(|pdf| {
+            let stem = pdf.file_stem()?.to_string_lossy().to_string();
+            let expected = folder.join(format!("{}{}", stem, EXPECTED_SUFFIX));
+            expected.is_file().then_some((pdf, expected))
+        })
+        .collect();
+    samples.sort();
+    samples
+}
+
+/// Match reported findings to expected key phrases, each used at most once
+pub fn score_sample(expected: &[String], reported: &[String]) -> SampleScore {
+    let reported_keys: Vec<String> = reported.iter().map(|f| normalize_finding(f)).collect();
+    let mut used = vec![false; reported.len()];
+    let mut score = SampleScore::default();
+    for finding in expected {
+        let key = normalize_finding(finding);
+        let hit = reported_keys
+            .iter()
+            .enumerate()
+            .position(|(i, r)| !used[i] && !key.is_empty() && r.contains(&key));
+        match hit {
+            Some(i) => {
+                used[i] = true;
+                score.matched.push(finding.clone());
+            }
+            None => score.missed.push(finding.clone()),
+        }
+    }
+    score.unexpected = reported
+        .iter()
+        .zip(&used)
+        .filter(|(_, used)| !**used)
+        .map(|(f, _)| f.clone())
+        .collect();
+    score
+}
+
+/// Precision and recall over the scored samples (1.0 when nothing to measure)
+pub fn metrics(scores: &[&SampleScore]) -> Metrics {
+    let tp: usize = scores.iter().map(|s| s.matched.len()).sum();
+    let fp: usize = scores.iter().map(|s| s.unexpected.len()).sum();
+    let fn_: usize = scores.iter().map(|s| s.missed.len()).sum();
+    let ratio = |n: usize, d: usize| if d == 0 { 1.0 } else { n as f64 / d as f64 };
+    Metrics {
+        true_positives: tp,
+        false_positives: fp,
+        false_negatives: fn_,
+        precision: ratio(tp, tp + fp),
+        recall: ratio(tp, tp + fn_),
+    }
+}
+
+/// Expected findings missed now that were found in the previous run
+fn regressions(previous: &GoldenRun, samples: &[SampleResult]) -> Vec<String> {
+    samples
+        .iter()
+        .filter(|s| s.error.is_none())
+        .flat_map(|sample| {
+            let missed_before = previous
+                .missed
+                .iter()
+                .find(|(name, _)| name == &sample.file_name)
+                .map(|(_, missed)| missed.as_slice());
+            sample
+                .score
+                .missed
+                .iter()
+                .filter(move |f| missed_before.is_some_and(|before| !before.contains(f)))
+                .map(move |f| format!("{}: {}", sample.file_name, f))
+        })
+        .collect()
+}
+
+fn load_last_run(folder: &Path) -> Option<GoldenRun> {
+    fs::read_to_string(folder.join(LAST_RUN_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
+/// Run the current prompt/model over every sample of the folder
+///
+/// The run replaces the baseline when it is clean (see the module doc) or
+/// when `accept` is set.
+pub fn run_golden_samples(folder: &str, preset: Option<&str>, accept: bool) -> Result<GoldenReport, String> {
+    let dir = Path::new(folder);
+    let samples = golden_samples(dir);
+    if samples.is_empty() {
+        return Err(format!(
+            "サンプルがありません（<名前>.pdf と <名前>{} を置いてください）: {}",
+            EXPECTED_SUFFIX, folder
+        ));
+    }
+    let (_, model, custom) = resolve_preset(preset, "single".to_string(), "")?;
+
+    let mut results = Vec::new();
+    for (pdf, expected_path) in samples {
+        let file_name = pdf
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let expected: ExpectedFindings = fs::read_to_string(&expected_path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            .map_err(|e| format!("期待値ファイルを読み込めません: {}: {}", expected_path.display(), e))?;
+        let analyzed = reanalyze_without_saving(&pdf.to_string_lossy(), "golden", &model, &custom);
+        results.push(match analyzed {
+            Ok(result) => SampleResult {
+                file_name,
+                score: score_sample(&expected.findings, &findings_of(&result)),
+                error: None,
+            },
+            Err(e) => SampleResult {
+                file_name,
+                score: SampleScore::default(),
+                error: Some(e),
+            },
+        });
+    }
+
+    let scored: Vec<&SampleScore> = results
+        .iter()
+        .filter(|r| r.error.is_none())
+        .map(|r| &r.score)
+        .collect();
+    let run = GoldenRun {
+        run_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        model,
+        preset: preset.map(str::to_string),
+        metrics: metrics(&scored),
+        missed: results
+            .iter()
+            .filter(|r| r.error.is_none())
+            .map(|r| (r.file_name.clone(), r.score.missed.clone()))
+            .collect(),
+    };
+    let previous = load_last_run(dir);
+    let mut report = GoldenReport {
+        folder: folder.to_string(),
+        precision_change: previous.as_ref().map(|p| run.metrics.precision - p.metrics.precision),
+        recall_change: previous.as_ref().map(|p| run.metrics.recall - p.metrics.recall),
+        regressions: previous.as_ref().map(|p| regressions(p, &results)).unwrap_or_default(),
+        run,
+        samples: results,
+        previous,
+        baseline_saved: false,
+    };
+    if accept || report.is_clean() {
+        let json = serde_json::to_string_pretty(&report.run).map_err(|e| e.to_string())?;
+        fs::write(dir.join(LAST_RUN_FILE), json).map_err(|e| e.to_string())?;
+        report.baseline_saved = true;
+    }
+    Ok(report)
+}
+
+fn format_change(change: Option<f64>) -> String {
+    change
+        .map(|c| format!(" ({:+.1}pt)", c * 100.0))
+        .unwrap_or_default()
+}
+
+/// CLI: `shoruichecker golden <folder> [--preset <name>] [--accept]`
+///
+/// Returns false when the run failed or regressed against the baseline.
+pub fn run_golden_cli(folder: &str, preset: Option<&str>, accept: bool) -> bool {
+    let report = match run_golden_samples(folder, preset, accept) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+    for sample in &report.samples {
+        match &sample.error {
+            Some(e) => println!("[NG]   {}: {}", sample.file_name, e),
+            None => println!(
+                "{} {}: 検出 {} / 見逃し {} / 過剰 {}",
+                if sample.score.missed.is_empty() { "[OK]  " } else { "[MISS]" },
+                sample.file_name,
+                sample.score.matched.len(),
+                sample.score.missed.len(),
+                sample.score.unexpected.len()
+            ),
+        }
+    }
+    let m = report.run.metrics;
+    println!("モデル: {}", report.run.model);
+    println!("適合率: {:.1}%{}", m.precision * 100.0, format_change(report.precision_change));
+    println!("再現率: {:.1}%{}", m.recall * 100.0, format_change(report.recall_change));
+    for regression in &report.regressions {
+        println!("⚠ 前回は検出できた指摘を見逃しました: {}", regression);
+    }
+    if report.baseline_saved {
+        println!("今回の結果を基準として保存しました");
+    } else {
+        println!("基準は更新していません（この結果を基準にするには --accept を指定）");
+    }
+    report.is_clean()
+}
+
+/// ゴールデンサンプル（期待される指摘付きのPDF）で現在のプロンプト・モデルを評価
+///
+/// `accept` で悪化していても今回の結果を基準として保存する。
+#[tauri::command]
+pub async fn run_golden_check(
+    folder: String,
+    preset: Option<String>,
+    accept: Option<bool>,
+) -> Result<GoldenReport, String> {
+    let label = folder.clone();
+    run_blocking("golden", &label, move || {
+        run_golden_samples(&folder, preset.as_deref(), accept.unwrap_or(false))
+    })
+    .await
+    .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn expected_phrases_match_reported_findings_once() {
+        let expected = strings(&["金額不整合", "押印漏れ", "金額不整合"]);
+        let reported = strings(&["⚠ 請求書と契約書の 金額不整合", "⚠ 日付の矛盾"]);
+        let score = score_sample(&expected, &reported);
+        assert_eq!(score.matched, strings(&["金額不整合"]));
+        assert_eq!(score.missed, strings(&["押印漏れ", "金額不整合"]));
+        assert_eq!(score.unexpected, strings(&["⚠ 日付の矛盾"]));
+
+        let m = metrics(&[&score]);
+        assert_eq!((m.true_positives, m.false_positives, m.false_negatives), (1, 1, 2));
+        assert!((m.precision - 0.5).abs() < 1e-9);
+        assert!((m.recall - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(metrics(&[]).precision, 1.0);
+    }
+
+    #[test]
+    fn findings_missed_only_now_are_regressions() {
+        let previous = GoldenRun {
+            run_at: String::new(),
+            model: String::new(),
+            preset: None,
+            metrics: Metrics::default(),
+            missed: vec![("a.pdf".to_string(), strings(&["押印漏れ"]))],
+        };
+        let sample = SampleResult {
+            file_name: "a.pdf".to_string(),
+            score: SampleScore {
+                missed: strings(&["押印漏れ", "金額不整合"]),
+                ..Default::default()
+            },
+            error: None,
+        };
+        assert_eq!(regressions(&previous, &[sample]), strings(&["a.pdf: 金額不整合"]));
+    }
+
+    #[test]
+    fn samples_need_an_expectation_file() {
+        let dir = create_temp_dir(".shoruichecker_test_golden").expect("create dir");
+        fs::write(dir.join("b.pdf"), b"%PDF").unwrap();
+        fs::write(dir.join("b.expected.json"), b"{\"findings\": []}").unwrap();
+        fs::write(dir.join("a.PDF"), b"%PDF").unwrap();
+        fs::write(dir.join("a.expected.json"), b"{}").unwrap();
+        fs::write(dir.join("c.pdf"), b"%PDF").unwrap();
+
+        let names: Vec<String> = golden_samples(&dir)
+            .iter()
+            .map(|(pdf, _)| pdf.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, strings(&["a.PDF", "b.pdf"]));
+        cleanup_temp_dir(&dir);
+    }
+}
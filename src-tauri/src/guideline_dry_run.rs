@@ -0,0 +1,273 @@
+//! Guideline dry run
+//!
+//! Runs only the deterministic layer of an analysis against one document:
+//! document type detection (stored classification, project rules, built-in
+//! keywords), selection of the guideline items that would go into the prompt,
+//! and the rule-based checks (text extraction, terms quoted in guideline
+//! items, expected values). No AI request is made, so a freshly edited
+//! `.guidelines.json` can be validated in a second.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::archive::file_sha256;
+use crate::classify::load_stored_metadata;
+use crate::database::open_db;
+use crate::expected_values::{
+    document_text, resolve_expected_values, text_contains_value, ExpectedValue, ExpectedValuesSource,
+};
+use crate::guidelines::{
+    detect_document_type, guideline_item_id, load_guidelines_json, match_type_rules, Guidelines,
+    GUIDELINE_ITEMS_PER_SECTION,
+};
+use crate::project_settings::{load_project_settings, project_folder_for};
+use crate::tasks::run_blocking;
+
+/// Category name of the common items
+const COMMON_CATEGORY: &str = "共通";
+
+/// How the document types were determined
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeSource {
+    /// Stored result of the first-page classification
+    Classification,
+    /// File-name rule of the project settings
+    ProjectRule,
+    /// Built-in file-name keywords
+    Keyword,
+    None,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DryRunItem {
+    pub id: String,
+    pub category: String,
+    pub text: String,
+    /// Whether the item would be included in the analysis prompt
+    pub applicable: bool,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DryRunCheck {
+    pub name: String,
+    /// None when the check could not run (e.g. scanned document)
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GuidelineDryRun {
+    pub file_name: String,
+    pub doc_types: Vec<String>,
+    pub type_source: TypeSource,
+    pub items: Vec<DryRunItem>,
+    pub checks: Vec<DryRunCheck>,
+}
+
+/// Document types the analysis would use, without classifying anew
+fn detect_types(project_folder: &str, path: &str, file_name: &str) -> (Vec<String>, TypeSource) {
+    let stored = file_sha256(Path::new(path))
+        .ok()
+        .zip(open_db().ok())
+        .and_then(|(hash, conn)| load_stored_metadata(&conn, path, &hash))
+        .and_then(|m| m.doc_type);
+    if let Some(doc_type) = stored {
+        return (vec![doc_type], TypeSource::Classification);
+    }
+    let types = match_type_rules(&load_project_settings(project_folder).document_type_rules, file_name);
+    if !types.is_empty() {
+        return (types, TypeSource::ProjectRule);
+    }
+    let types = detect_document_type(file_name);
+    let source = if types.is_empty() { TypeSource::None } else { TypeSource::Keyword };
+    (types, source)
+}
+
+fn section_items(category: &str, items: &[String], matched: bool) -> Vec<DryRunItem> {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let (applicable, reason) = if !matched {
+                (false, "書類タイプが一致しません".to_string())
+            } else if i >= GUIDELINE_ITEMS_PER_SECTION {
+                (false, format!("{}件の上限を超えるため省略されます", GUIDELINE_ITEMS_PER_SECTION))
+            } else {
+                (true, "解析プロンプトに含まれます".to_string())
+            };
+            DryRunItem {
+                id: guideline_item_id(item),
+                category: category.to_string(),
+                text: item.clone(),
+                applicable,
+                reason,
+            }
+        })
+        .collect()
+}
+
+/// All guideline items with whether they apply to the document types
+pub fn guideline_items(guidelines: &Guidelines, doc_types: &[String]) -> Vec<DryRunItem> {
+    let mut items = section_items(COMMON_CATEGORY, &guidelines.common, true);
+    let mut categories: Vec<&String> = guidelines.categories.keys().collect();
+    categories.sort_by_key(|c| (!doc_types.contains(c), c.as_str()));
+    for category in categories {
+        items.extend(section_items(
+            category,
+            &guidelines.categories[category],
+            doc_types.contains(category),
+        ));
+    }
+    items
+}
+
+/// Terms quoted with 「」 in a guideline item (e.g. 「現場代理人」の記載)
+pub fn quoted_terms(item: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut rest = item;
+    while let Some(start) = rest.find('「') {
+        let after = &rest[start + '「'.len_utf8()..];
+        let Some(end) = after.find('」') else {
+            break;
+        };
+        let term = after[..end].trim();
+        if !term.is_empty() && !terms.iter().any(|t: &String| t == term) {
+            terms.push(term.to_string());
+        }
+        rest = &after[end + '」'.len_utf8()..];
+    }
+    terms
+}
+
+/// Rule-based checks on the document text (None: text not extractable)
+pub fn rule_checks(text: Option<&str>, items: &[DryRunItem], expected: &[ExpectedValue]) -> Vec<DryRunCheck> {
+    let Some(text) = text else {
+        return vec![DryRunCheck {
+            name: "テキスト抽出".to_string(),
+            passed: None,
+            detail: "スキャン書類のため自動判定できません（解析時は画像として読み取ります）".to_string(),
+        }];
+    };
+    let mut checks = vec![DryRunCheck {
+        name: "テキスト抽出".to_string(),
+        passed: Some(true),
+        detail: format!("{}文字", text.chars().count()),
+    }];
+    for item in items.iter().filter(|i| i.applicable) {
+        for term in quoted_terms(&item.text) {
+            let found = text_contains_value(text, &term);
+            checks.push(DryRunCheck {
+                name: format!("[{}] 「{}」の記載", item.id, term),
+                passed: Some(found),
+                detail: if found {
+                    "書類中に見つかりました".to_string()
+                } else {
+                    "書類中に見つかりません".to_string()
+                },
+            });
+        }
+    }
+    for value in expected {
+        let found = text_contains_value(text, &value.value);
+        checks.push(DryRunCheck {
+            name: format!("基準値: {}", value.field),
+            passed: Some(found),
+            detail: if found {
+                value.value.clone()
+            } else {
+                format!("{} が書類中に見つかりません", value.value)
+            },
+        });
+    }
+    checks
+}
+
+/// Dry run of the guideline layer for one document
+pub fn dry_run(path: &str, expected: &[ExpectedValue]) -> Result<GuidelineDryRun, String> {
+    if !Path::new(path).is_file() {
+        return Err(format!("ファイルが見つかりません: {}", path));
+    }
+    let project_folder = project_folder_for(path);
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (doc_types, type_source) = detect_types(&project_folder, path, &file_name);
+    let items = load_guidelines_json(&project_folder)
+        .map(|g| guideline_items(&g, &doc_types))
+        .unwrap_or_default();
+    let text = document_text(&[path.to_string()]);
+    let checks = rule_checks(text.as_deref(), &items, expected);
+    Ok(GuidelineDryRun {
+        file_name,
+        doc_types,
+        type_source,
+        items,
+        checks,
+    })
+}
+
+/// AIを使わずにガイドライン・ルール層だけを書類に適用した結果
+#[tauri::command]
+pub async fn dry_run_guidelines(
+    path: String,
+    expected_values: Option<ExpectedValuesSource>,
+) -> Result<GuidelineDryRun, String> {
+    let expected = resolve_expected_values(expected_values)?;
+    let label = path.clone();
+    run_blocking("guidelines", &label, move || dry_run(&path, &expected))
+        .await
+        .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn guidelines() -> Guidelines {
+        let mut categories = HashMap::new();
+        categories.insert(
+            "契約書".to_string(),
+            (1..=6).map(|i| format!("契約項目{}", i)).collect(),
+        );
+        categories.insert("請求書".to_string(), vec!["「請求金額」を確認".to_string()]);
+        Guidelines {
+            categories,
+            common: vec!["「現場代理人」と「工期」の記載".to_string()],
+        }
+    }
+
+    #[test]
+    fn items_apply_by_type_up_to_the_section_limit() {
+        let items = guideline_items(&guidelines(), &["契約書".to_string()]);
+        assert_eq!(items.len(), 8);
+        assert_eq!(items[0].category, "共通");
+        assert!(items[0].applicable);
+        assert_eq!(items[1].category, "契約書");
+        assert_eq!(items.iter().filter(|i| i.category == "契約書" && i.applicable).count(), 5);
+        assert!(!items[6].applicable && items[6].reason.contains("上限"));
+        assert_eq!(items[7].category, "請求書");
+        assert!(!items[7].applicable);
+    }
+
+    #[test]
+    fn quoted_terms_are_checked_in_applicable_items() {
+        assert_eq!(quoted_terms("「現場代理人」と「工期」と「工期」"), vec!["現場代理人", "工期"]);
+        assert!(quoted_terms("「閉じていない").is_empty());
+
+        let items = guideline_items(&guidelines(), &["契約書".to_string()]);
+        let expected = vec![ExpectedValue { field: "請負代金額".into(), value: "1,100,000".into() }];
+        let checks = rule_checks(Some("現場代理人 山田太郎\n請負代金額 1100000円"), &items, &expected);
+        let passed: Vec<Option<bool>> = checks.iter().map(|c| c.passed).collect();
+        assert_eq!(passed, vec![Some(true), Some(true), Some(false), Some(true)]);
+        assert!(checks[2].name.contains("「工期」"));
+
+        let scanned = rule_checks(None, &items, &expected);
+        assert_eq!(scanned.len(), 1);
+        assert_eq!(scanned[0].passed, None);
+    }
+}
@@ -1,13 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use regex::RegexBuilder;
 use serde::{Deserialize, Serialize};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 
-use crate::events::emit_log;
+use crate::access::{ensure_allowed, Operation};
+use crate::events::{emit_app_log, emit_log};
+use crate::file_lock::write_atomic;
 use crate::gemini_cli::{run_gemini_in_temp, GeminiRequest};
-use crate::pdf_embed::{read_embedded_data_from_pdf, PdfEmbeddedData};
+use crate::history::path_hash;
+use crate::pdf_embed::PdfEmbeddedData;
+use crate::project_settings::{load_project_settings, DocumentTypeRule};
+use crate::result_store::load_result_data;
 use crate::settings::{load_settings, DEFAULT_MODEL};
+use crate::tasks::run_blocking;
+use crate::usage::with_usage_project;
 
 /// ガイドラインをJSON形式で保存（カテゴリ別）
 #[derive(Clone, Serialize, Deserialize, Default)]
@@ -18,7 +27,56 @@ pub struct Guidelines {
     pub common: Vec<String>,
 }
 
-/// ファイル名から書類タイプを推定
+/// ガイドラインファイル名（各工事フォルダに置く）
+pub const GUIDELINES_FILE: &str = ".guidelines.json";
+
+/// 最後に検証したガイドラインファイルの内容のハッシュ（同じ内容で重ねて通知しない）
+static CHECKED_GUIDELINES: Mutex<Option<HashMap<PathBuf, u64>>> = Mutex::new(None);
+
+/// ガイドラインファイルの検証結果（`guidelines-changed` イベント）
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct GuidelinesCheck {
+    pub path: String,
+    pub valid: bool,
+    pub items: usize,
+    /// 不正なJSONの場合のエラー（行・列を含む）
+    pub error: Option<String>,
+}
+
+/// 解析プロンプトに含める1セクションあたりのガイドライン項目数
+pub const GUIDELINE_ITEMS_PER_SECTION: usize = 5;
+
+/// 組み込みの書類タイプ（AI分類の候補）
+pub const KNOWN_DOCUMENT_TYPES: [&str; 6] =
+    ["契約書", "見積書", "請求書", "交通誘導員", "測量図面", "施工計画"];
+
+/// プロジェクトの独自ルールでファイル名から書類タイプを判定（不正な正規表現は無視）
+pub fn match_type_rules(rules: &[DocumentTypeRule], file_name: &str) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    for rule in rules {
+        let matched = RegexBuilder::new(&rule.pattern)
+            .case_insensitive(true)
+            .build()
+            .map(|re| re.is_match(file_name))
+            .unwrap_or(false);
+        if matched && !types.contains(&rule.doc_type) {
+            types.push(rule.doc_type.clone());
+        }
+    }
+    types
+}
+
+/// プロジェクトの独自ルール、組み込みキーワードの順にファイル名から書類タイプを推定
+pub fn detect_document_type_in(folder: &str, file_name: &str) -> Vec<String> {
+    let types = match_type_rules(&load_project_settings(folder).document_type_rules, file_name);
+    if types.is_empty() {
+        detect_document_type(file_name)
+    } else {
+        types
+    }
+}
+
+/// ファイル名から書類タイプを推定（組み込みキーワード）
 pub fn detect_document_type(file_name: &str) -> Vec<String> {
     let name = file_name.to_lowercase();
     let mut types = Vec::new();
@@ -47,7 +105,56 @@ pub fn detect_document_type(file_name: &str) -> Vec<String> {
 
 /// ガイドラインファイルのパス
 pub fn get_guidelines_path(folder: &str) -> PathBuf {
-    Path::new(folder).join(".guidelines.json")
+    Path::new(folder).join(GUIDELINES_FILE)
+}
+
+pub fn is_guidelines_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == GUIDELINES_FILE)
+}
+
+/// ガイドラインファイルの内容を検証
+pub fn inspect_guidelines(path: &Path, text: &str) -> GuidelinesCheck {
+    let parsed = serde_json::from_str::<Guidelines>(text);
+    GuidelinesCheck {
+        path: path.to_string_lossy().to_string(),
+        valid: parsed.is_ok(),
+        items: parsed.as_ref().map(guidelines_item_count).unwrap_or(0),
+        error: parsed.err().map(|e| e.to_string()),
+    }
+}
+
+/// 変更されたガイドラインファイルを検証し、ログと `guidelines-changed` イベントで通知
+///
+/// ガイドラインはプロンプト作成のたびに読み込まれるため、正しい内容は次の解析から
+/// 反映される。不正なJSONは黙って無視せず、修正を促すエラーとして通知する。
+pub fn check_guidelines_file(app: &AppHandle, path: &Path) {
+    let Ok(text) = fs::read_to_string(path) else {
+        return;
+    };
+    {
+        let mut checked = CHECKED_GUIDELINES.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = path_hash(&text);
+        if checked.get_or_insert_with(HashMap::new).insert(path.to_path_buf(), hash) == Some(hash) {
+            return;
+        }
+    }
+    let check = inspect_guidelines(path, &text);
+    match &check.error {
+        None => emit_log(
+            app,
+            &format!("ガイドラインの変更を反映します: {}（{} 項目）", check.path, check.items),
+            "info",
+        ),
+        Some(error) => emit_log(
+            app,
+            &format!(
+                "ガイドラインファイルの形式が不正です。修正するまでガイドラインなしで解析されます: {}（{}）",
+                check.path, error
+            ),
+            "error",
+        ),
+    }
+    let _ = app.emit("guidelines-changed", check);
 }
 
 /// ガイドラインを読み込む
@@ -58,24 +165,78 @@ pub fn load_guidelines_json(folder: &str) -> Option<Guidelines> {
         .and_then(|s| serde_json::from_str(&s).ok())
 }
 
-/// ファイルに関連するガイドラインだけを取得
-pub fn get_relevant_guidelines(folder: &str, file_name: &str) -> Option<String> {
-    let guidelines = load_guidelines_json(folder)?;
-    let doc_types = detect_document_type(file_name);
+/// ガイドライン統計ファイルのパス
+pub fn get_guideline_stats_path(folder: &str) -> PathBuf {
+    Path::new(folder).join(".guidelines_stats.json")
+}
+
+/// ガイドライン項目のID（項目テキストから決定的に生成）
+pub fn guideline_item_id(item: &str) -> String {
+    format!("G{:06x}", path_hash(item) & 0xff_ffff)
+}
+
+/// テキスト中に現れるガイドライン項目ID（`[Gxxxxxx]`）を出現順に抽出
+pub fn extract_guideline_ids(text: &str) -> Vec<String> {
+    let bytes = text.as_bytes();
+    let mut ids: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i + 9 <= bytes.len() {
+        if bytes[i] == b'['
+            && bytes[i + 1] == b'G'
+            && bytes[i + 8] == b']'
+            && bytes[i + 2..i + 8].iter().all(|b| b.is_ascii_hexdigit())
+        {
+            let id = text[i + 1..i + 8].to_string();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+            i += 9;
+        } else {
+            i += 1;
+        }
+    }
+    ids
+}
+
+/// 指定した書類タイプに関連するガイドラインだけを取得（各項目にIDを付与）
+pub fn get_relevant_guidelines_for_types(folder: &str, doc_types: &[String]) -> Option<String> {
+    let path = get_guidelines_path(folder);
+    let text = fs::read_to_string(&path).ok()?;
+    let guidelines: Guidelines = match serde_json::from_str(&text) {
+        Ok(guidelines) => guidelines,
+        Err(e) => {
+            emit_app_log(
+                &format!("ガイドラインファイルの形式が不正なため使用しません: {}（{}）", path.display(), e),
+                "warn",
+            );
+            return None;
+        }
+    };
 
     let mut relevant = Vec::new();
 
     // 共通事項は常に含める（短いので）
     if !guidelines.common.is_empty() {
         relevant.push("【共通】".to_string());
-        relevant.extend(guidelines.common.iter().take(5).cloned());
+        relevant.extend(
+            guidelines
+                .common
+                .iter()
+                .take(GUIDELINE_ITEMS_PER_SECTION)
+                .map(|item| format_item(item)),
+        );
     }
 
     // 該当カテゴリのガイドラインだけ追加
-    for doc_type in &doc_types {
+    for doc_type in doc_types {
         if let Some(items) = guidelines.categories.get(doc_type) {
             relevant.push(format!("【{}】", doc_type));
-            relevant.extend(items.iter().take(5).cloned());
+            relevant.extend(
+                items
+                    .iter()
+                    .take(GUIDELINE_ITEMS_PER_SECTION)
+                    .map(|item| format_item(item)),
+            );
         }
     }
 
@@ -86,6 +247,125 @@ pub fn get_relevant_guidelines(folder: &str, file_name: &str) -> Option<String>
     }
 }
 
+fn format_item(item: &str) -> String {
+    format!("[{}] {}", guideline_item_id(item), item)
+}
+
+/// ガイドライン項目ごとの適用・指摘実績
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct GuidelineItemStats {
+    pub id: String,
+    pub text: String,
+    /// プロンプトに含めた回数
+    pub applied: u32,
+    /// 解析結果でIDが引用された回数
+    pub hits: u32,
+    pub last_hit_at: Option<String>,
+}
+
+/// プロジェクトのガイドライン統計（IDをキーとする）
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct GuidelineStats {
+    pub items: HashMap<String, GuidelineItemStats>,
+}
+
+/// ガイドライン統計を読み込む
+pub fn load_guideline_stats(folder: &str) -> GuidelineStats {
+    fs::read_to_string(get_guideline_stats_path(folder))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_guideline_stats(folder: &str, stats: &GuidelineStats) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(stats).map_err(|e| e.to_string())?;
+    write_atomic(&get_guideline_stats_path(folder), json)
+}
+
+/// 適用したガイドライン項目と、解析結果で引用された項目を統計に反映
+pub fn apply_guideline_usage(
+    stats: &mut GuidelineStats,
+    guidelines: &Guidelines,
+    applied_ids: &[String],
+    result: &str,
+) {
+    let texts: HashMap<String, String> = guidelines
+        .common
+        .iter()
+        .chain(guidelines.categories.values().flatten())
+        .map(|item| (guideline_item_id(item), item.clone()))
+        .collect();
+    let hit_ids = extract_guideline_ids(result);
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    for id in applied_ids {
+        let entry = stats.items.entry(id.clone()).or_insert_with(|| GuidelineItemStats {
+            id: id.clone(),
+            ..Default::default()
+        });
+        if let Some(text) = texts.get(id) {
+            entry.text = text.clone();
+        }
+        entry.applied += 1;
+        if hit_ids.contains(id) {
+            entry.hits += 1;
+            entry.last_hit_at = Some(now.clone());
+        }
+    }
+}
+
+/// 解析1回分のガイドライン利用状況を記録（applied_ids はプロンプトに含めた項目ID、エラーは無視）
+pub fn record_guideline_usage(folder: &str, applied_ids: &[String], result: &str) {
+    if applied_ids.is_empty() {
+        return;
+    }
+    let Some(guidelines) = load_guidelines_json(folder) else {
+        return;
+    };
+    let mut stats = load_guideline_stats(folder);
+    apply_guideline_usage(&mut stats, &guidelines, applied_ids, result);
+    let _ = save_guideline_stats(folder, &stats);
+}
+
+/// ガイドライン項目の有効性統計を取得（現行ガイドラインの項目のみ、指摘回数の多い順）
+#[tauri::command]
+pub fn get_guideline_stats(folder: String) -> Vec<GuidelineItemStats> {
+    let Some(guidelines) = load_guidelines_json(&folder) else {
+        return vec![];
+    };
+    let stats = load_guideline_stats(&folder);
+
+    let mut seen = HashSet::new();
+    let mut items: Vec<GuidelineItemStats> = guidelines
+        .common
+        .iter()
+        .chain(guidelines.categories.values().flatten())
+        .filter(|item| seen.insert(guideline_item_id(item)))
+        .map(|item| {
+            let id = guideline_item_id(item);
+            let mut entry = stats.items.get(&id).cloned().unwrap_or_default();
+            entry.id = id;
+            entry.text = item.clone();
+            entry
+        })
+        .collect();
+    items.sort_by(|a, b| b.hits.cmp(&a.hits).then(b.applied.cmp(&a.applied)));
+    items
+}
+
+/// 生成プロンプト用に既存項目の実績を整形
+fn format_stats_for_prompt(folder: &str) -> String {
+    let items = get_guideline_stats(folder.to_string());
+    if items.iter().all(|i| i.applied == 0) {
+        return "（実績なし）".to_string();
+    }
+    items
+        .iter()
+        .map(|i| format!("- {}: 適用{}回 / 指摘{}回", i.text, i.applied, i.hits))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 /// ガイドラインを生成（Gemini使用）
 #[tauri::command]
 pub async fn generate_guidelines(
@@ -94,10 +374,12 @@ pub async fn generate_guidelines(
     folder: String,
     custom_instruction: Option<String>,
 ) -> Result<String, String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+
     // Collect embedded data from specified files only
     let mut collected: Vec<(String, PdfEmbeddedData)> = Vec::new();
     for path in &paths {
-        if let Some(data) = read_embedded_data_from_pdf(path) {
+        if let Some(data) = load_result_data(path) {
             let file_name = Path::new(path)
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
@@ -159,15 +441,58 @@ pub async fn generate_guidelines(
     // Detect document types from file names
     let mut detected_types: Vec<String> = Vec::new();
     for (file_name, _) in &collected {
-        for t in detect_document_type(file_name) {
+        for t in detect_document_type_in(&folder, file_name) {
             if !detected_types.contains(&t) {
                 detected_types.push(t);
             }
         }
     }
 
+    emit_log(&app, "Geminiで要約中...", "wave");
+
+    let outcome = run_blocking("guidelines", "ガイドライン生成", move || {
+        regenerate_guidelines(&folder, &all_issues, &all_instructions, &detected_types)
+    })
+    .await
+    .and_then(|r| r);
+    match outcome {
+        Ok(RegenerationOutcome::Saved(guidelines)) => {
+            emit_log(
+                &app,
+                &format!("✓ ガイドライン生成完了 ({} 項目)", guidelines_item_count(&guidelines)),
+                "success",
+            );
+            // Return human-readable summary
+            Ok(format_guidelines_summary(&guidelines))
+        }
+        Ok(RegenerationOutcome::Raw { result, parse_error }) => {
+            emit_log(&app, &format!("JSON解析エラー: {} - 生データ保存", parse_error), "info");
+            Ok(result)
+        }
+        Err(error) => {
+            emit_log(&app, &format!("エラー: {}", error), "error");
+            Err(error)
+        }
+    }
+}
+
+/// ガイドライン再生成の結果
+pub enum RegenerationOutcome {
+    /// JSONとして解析でき、`.guidelines.json` に保存した
+    Saved(Guidelines),
+    /// JSONとして解析できず、生データを `.guidelines.md` に保存した
+    Raw { result: String, parse_error: String },
+}
+
+/// 既存ガイドラインと新しい問題・観点からガイドラインを再生成して保存（Gemini使用）
+pub fn regenerate_guidelines(
+    folder: &str,
+    all_issues: &[String],
+    all_instructions: &[String],
+    detected_types: &[String],
+) -> Result<RegenerationOutcome, String> {
     // Load existing guidelines
-    let existing_guidelines = load_guidelines_json(&folder);
+    let existing_guidelines = load_guidelines_json(folder);
     let existing_json = existing_guidelines
         .as_ref()
         .map(|g| serde_json::to_string_pretty(g).unwrap_or_default())
@@ -192,11 +517,15 @@ pub async fn generate_guidelines(
 ## 対象書類タイプ
 {}
 
+## 既存項目の実績（適用回数 / 解析結果で指摘に結びついた回数）
+{}
+
 ## タスク
 1. 既存ガイドラインの有用な項目は保持
 2. 新しい問題パターンがあれば追加
 3. 重複は統合、古くなった項目は更新
 4. 各カテゴリ最大10項目まで（重要度順）
+5. 指摘実績の多い項目を優先し、何度も適用されて指摘ゼロの項目は削除を検討
 
 ## 出力形式（厳守）
 JSON形式のみ出力。説明文不要。
@@ -222,72 +551,195 @@ JSON形式のみ出力。説明文不要。
         } else {
             all_instructions.join("\n")
         },
-        detected_types.join(", ")
+        detected_types.join(", "),
+        format_stats_for_prompt(folder)
     );
 
-    emit_log(&app, "Geminiで要約中...", "wave");
-
     let model = load_settings()
         .model
         .unwrap_or_else(|| DEFAULT_MODEL.to_string());
     let request = GeminiRequest::json(&prompt, &model);
-    let output = run_gemini_in_temp(".shoruichecker_temp_guidelines", &request);
-
-    match output {
-        Ok(result) => {
-            // Extract JSON from response (may be wrapped in ```json ... ```)
-            let json_str = if let Some(start) = result.find('{') {
-                if let Some(end) = result.rfind('}') {
-                    &result[start..=end]
-                } else {
-                    &result
-                }
-            } else {
-                &result
-            };
-
-            // Parse and save as JSON
-            let guidelines_path = get_guidelines_path(&folder);
-            match serde_json::from_str::<Guidelines>(json_str) {
-                Ok(guidelines) => {
-                    let json = serde_json::to_string_pretty(&guidelines).unwrap_or_default();
-                    let _ = fs::write(&guidelines_path, &json);
-
-                    let count = guidelines.common.len()
-                        + guidelines.categories.values().map(|v| v.len()).sum::<usize>();
-                    emit_log(
-                        &app,
-                        &format!("✓ ガイドライン生成完了 ({} 項目)", count),
-                        "success",
-                    );
-
-                    // Return human-readable summary
-                    let mut summary = String::from("## ガイドライン\n\n");
-                    if !guidelines.common.is_empty() {
-                        summary.push_str("### 共通\n");
-                        for item in &guidelines.common {
-                            summary.push_str(&format!("- {}\n", item));
-                        }
-                    }
-                    for (cat, items) in &guidelines.categories {
-                        summary.push_str(&format!("\n### {}\n", cat));
-                        for item in items {
-                            summary.push_str(&format!("- {}\n", item));
-                        }
-                    }
-                    Ok(summary)
-                }
-                Err(e) => {
-                    emit_log(&app, &format!("JSON解析エラー: {} - 生データ保存", e), "info");
-                    // Fallback: save raw result
-                    let _ = fs::write(&guidelines_path.with_extension("md"), &result);
-                    Ok(result)
-                }
-            }
+    let result = with_usage_project(folder, || {
+        run_gemini_in_temp(".shoruichecker_temp_guidelines", &request)
+    })
+    .map_err(|e| e.to_string())?;
+
+    // Extract JSON from response (may be wrapped in ```json ... ```)
+    let json_str = if let Some(start) = result.find('{') {
+        if let Some(end) = result.rfind('}') {
+            &result[start..=end]
+        } else {
+            &result
         }
-        Err(error) => {
-            emit_log(&app, &format!("エラー: {}", error), "error");
-            Err(error.to_string())
+    } else {
+        &result
+    };
+
+    // Parse and save as JSON
+    let guidelines_path = get_guidelines_path(folder);
+    match serde_json::from_str::<Guidelines>(json_str) {
+        Ok(guidelines) => {
+            let json = serde_json::to_string_pretty(&guidelines).unwrap_or_default();
+            let _ = fs::write(&guidelines_path, &json);
+            Ok(RegenerationOutcome::Saved(guidelines))
         }
+        Err(e) => {
+            // Fallback: save raw result
+            let _ = fs::write(guidelines_path.with_extension("md"), &result);
+            Ok(RegenerationOutcome::Raw {
+                result,
+                parse_error: e.to_string(),
+            })
+        }
+    }
+}
+
+/// ガイドラインの項目数
+pub fn guidelines_item_count(guidelines: &Guidelines) -> usize {
+    guidelines.common.len() + guidelines.categories.values().map(|v| v.len()).sum::<usize>()
+}
+
+/// ガイドラインを人が読める形式に整形
+pub fn format_guidelines_summary(guidelines: &Guidelines) -> String {
+    let mut summary = String::from("## ガイドライン\n\n");
+    if !guidelines.common.is_empty() {
+        summary.push_str("### 共通\n");
+        for item in &guidelines.common {
+            summary.push_str(&format!("- {}\n", item));
+        }
+    }
+    for (cat, items) in &guidelines.categories {
+        summary.push_str(&format!("\n### {}\n", cat));
+        for item in items {
+            summary.push_str(&format!("- {}\n", item));
+        }
+    }
+    summary
+}
+
+/// ガイドラインの差分（追加・削除された項目）
+#[derive(Clone, Serialize, Default)]
+pub struct GuidelineDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl GuidelineDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// 通知用の短い要約
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!("追加 {} 件 / 削除 {} 件", self.added.len(), self.removed.len())];
+        lines.extend(self.added.iter().take(3).map(|i| format!("+ {}", i)));
+        lines.extend(self.removed.iter().take(3).map(|i| format!("- {}", i)));
+        lines.join("\n")
+    }
+}
+
+/// 2つのガイドラインの項目差分を計算（カテゴリ名を付けて比較）
+pub fn diff_guidelines(old: &Guidelines, new: &Guidelines) -> GuidelineDiff {
+    fn labeled(g: &Guidelines) -> Vec<String> {
+        let mut items: Vec<String> = g.common.iter().map(|i| format!("【共通】{}", i)).collect();
+        let mut cats: Vec<_> = g.categories.iter().collect();
+        cats.sort_by(|a, b| a.0.cmp(b.0));
+        for (cat, list) in cats {
+            items.extend(list.iter().map(|i| format!("【{}】{}", cat, i)));
+        }
+        items
+    }
+    let old_items = labeled(old);
+    let new_items = labeled(new);
+    GuidelineDiff {
+        added: new_items.iter().filter(|i| !old_items.contains(i)).cloned().collect(),
+        removed: old_items.iter().filter(|i| !new_items.contains(i)).cloned().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_rules_map_naming_conventions() {
+        let rules = vec![
+            DocumentTypeRule {
+                pattern: r"^K-0\d".to_string(),
+                doc_type: "交通誘導員".to_string(),
+            },
+            DocumentTypeRule {
+                pattern: "[".to_string(),
+                doc_type: "壊れたルール".to_string(),
+            },
+        ];
+        assert_eq!(match_type_rules(&rules, "k-02_5月分.pdf"), vec!["交通誘導員".to_string()]);
+        assert!(match_type_rules(&rules, "A-02.pdf").is_empty());
+    }
+
+    #[test]
+    fn guideline_item_id_is_stable_and_short() {
+        let id1 = guideline_item_id("税込/税抜の混在に注意");
+        let id2 = guideline_item_id("税込/税抜の混在に注意");
+        assert_eq!(id1, id2);
+        assert_eq!(id1.len(), 7);
+        assert!(id1.starts_with('G'));
+    }
+
+    #[test]
+    fn extract_guideline_ids_finds_unique_ids() {
+        let text = "⚠ 税率の誤り [G0a1b2c]\n⚠ 日付 [G0a1b2c] [Gffffff]\n[Gxyz] [G12345]";
+        assert_eq!(extract_guideline_ids(text), vec!["G0a1b2c", "Gffffff"]);
+    }
+
+    #[test]
+    fn apply_guideline_usage_counts_applied_and_hits() {
+        let item = "工期の日付順序".to_string();
+        let id = guideline_item_id(&item);
+        let guidelines = Guidelines {
+            categories: HashMap::new(),
+            common: vec![item.clone()],
+        };
+        let mut stats = GuidelineStats::default();
+
+        apply_guideline_usage(&mut stats, &guidelines, &[id.clone()], "✓ 問題なし");
+        apply_guideline_usage(&mut stats, &guidelines, &[id.clone()], &format!("⚠ 着工日が逆 [{}]", id));
+
+        let entry = &stats.items[&id];
+        assert_eq!(entry.text, item);
+        assert_eq!(entry.applied, 2);
+        assert_eq!(entry.hits, 1);
+        assert!(entry.last_hit_at.is_some());
+    }
+
+    #[test]
+    fn malformed_guideline_edits_are_reported() {
+        let path = Path::new("/p/工事/.guidelines.json");
+        assert!(is_guidelines_file(path));
+        assert!(!is_guidelines_file(Path::new("/p/工事/.guidelines_stats.json")));
+
+        let check = inspect_guidelines(path, r#"{"categories": {"請求書": ["押印"]}, "common": ["日付"]}"#);
+        assert!(check.valid);
+        assert_eq!(check.items, 2);
+
+        let check = inspect_guidelines(path, "{\n  \"categories\": {},\n  \"common\": [\"日付\",]\n}");
+        assert!(!check.valid);
+        assert!(check.error.unwrap().contains("line 3"));
+    }
+
+    #[test]
+    fn diff_guidelines_reports_added_and_removed() {
+        let old = Guidelines {
+            categories: HashMap::from([("契約書".to_string(), vec!["工期".to_string()])]),
+            common: vec!["税込/税抜".to_string()],
+        };
+        let new = Guidelines {
+            categories: HashMap::from([("契約書".to_string(), vec!["印紙".to_string()])]),
+            common: vec!["税込/税抜".to_string()],
+        };
+        let diff = diff_guidelines(&old, &new);
+        assert_eq!(diff.added, vec!["【契約書】印紙"]);
+        assert_eq!(diff.removed, vec!["【契約書】工期"]);
+        assert!(diff.summary().starts_with("追加 1 件 / 削除 1 件"));
     }
 }
@@ -4,22 +4,84 @@
 //! organized by project folder.
 
 use std::collections::hash_map::DefaultHasher;
-use std::fs;
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use chrono::Local;
 use serde::{Deserialize, Serialize};
 
+use crate::audit::record_access;
+use crate::classify::DocumentMetadata;
+use crate::database::open_db;
+use crate::history_query::index_history;
+use crate::history_store::history_store;
+use crate::project_settings::project_folder_for;
+use crate::shutdown::critical_section;
+
 /// Analysis history entry for a single file
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AnalysisHistoryEntry {
+    /// Identifies one analysis of the file (`%Y%m%d%H%M%S%3f`)
+    #[serde(default)]
+    pub id: String,
     pub file_name: String,
     pub file_path: String,
     pub analyzed_at: String,
     pub document_type: Option<String>,
     pub summary: String,
     pub issues: Vec<String>,
+    /// Issues of the previous analysis of the same file that no longer appear
+    #[serde(default)]
+    pub resolved_issues: Vec<String>,
+    /// Gemini CLI version used for the analysis
+    #[serde(default)]
+    pub cli_version: Option<String>,
+    /// SHA-256 of the PDF after the result was stored
+    #[serde(default)]
+    pub file_hash: Option<String>,
+    /// When the user opened the document after this analysis
+    #[serde(default)]
+    pub reviewed_at: Option<String>,
+    /// Mail the document arrived with (Outlook intake)
+    #[serde(default)]
+    pub mail: Option<MailOrigin>,
+    /// Result waiting for approval before it is written into the document
+    /// (projects with `require_approval`)
+    #[serde(default)]
+    pub pending: Option<PendingResult>,
+    /// When the result was approved and written into the document
+    #[serde(default)]
+    pub approved_at: Option<String>,
+    /// Why the result could not be written into the document (read-only
+    /// mode, a failed write); it is then only in the history
+    #[serde(default)]
+    pub store_error: Option<String>,
+}
+
+/// Draft result held in the history until `approve_result`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingResult {
+    pub result: String,
+    #[serde(default)]
+    pub instruction: String,
+    /// Mail the intake recipients once approved (routed intake files)
+    #[serde(default)]
+    pub notify_intake: bool,
+    /// Guideline items included in the prompt, counted on approval
+    #[serde(default)]
+    pub guideline_ids: Vec<String>,
+    /// Classification of the document, for its fields on approval
+    #[serde(default)]
+    pub metadata: Option<DocumentMetadata>,
+}
+
+/// Metadata of the mail a document was saved from
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MailOrigin {
+    pub message_id: String,
+    pub from: String,
+    pub subject: String,
+    pub received_at: String,
 }
 
 /// Analysis history for a project folder
@@ -27,19 +89,75 @@ pub struct AnalysisHistoryEntry {
 pub struct AnalysisHistory {
     pub project_folder: String,
     pub entries: Vec<AnalysisHistoryEntry>,
+    /// Earlier analyses replaced by a newer one of the same file (oldest first)
+    #[serde(default)]
+    pub revisions: Vec<AnalysisHistoryEntry>,
+    /// Counts of documents dropped from `entries` (over `MAX_HISTORY_ENTRIES`)
+    #[serde(default)]
+    pub dropped: Vec<DroppedEntry>,
 }
 
-/// Get the history file path for a project folder
+/// What the project summaries need of an entry dropped from the history
 ///
-/// The history is stored in the user's config directory under
-/// `shoruichecker/history/{folder_hash}.json`
-pub fn get_history_path(project_folder: &str) -> PathBuf {
+/// The history keeps only the latest analyses; the totals of a project
+/// still include its older documents until they are analyzed again.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DroppedEntry {
+    pub file_path: String,
+    pub analyzed_at: String,
+    pub issue_count: usize,
+    pub resolved_count: usize,
+    pub unreviewed: bool,
+}
+
+impl DroppedEntry {
+    fn of(entry: &AnalysisHistoryEntry) -> Self {
+        DroppedEntry {
+            file_path: entry.file_path.clone(),
+            analyzed_at: entry.analyzed_at.clone(),
+            issue_count: entry.issues.len(),
+            resolved_count: entry.resolved_issues.len(),
+            unreviewed: !entry.issues.is_empty() && entry.reviewed_at.is_none(),
+        }
+    }
+}
+
+/// Documents directly in the project folder (no 工種 subfolder)
+pub const UNCATEGORIZED_WORK_TYPE: &str = "（工種なし）";
+
+/// Roll-up of one 工種 (first-level subfolder of a project)
+#[derive(Clone, Serialize)]
+pub struct WorkTypeSummary {
+    pub work_type: String,
+    pub document_count: usize,
+    pub issue_count: usize,
+    pub resolved_count: usize,
+    /// Documents with findings nobody has opened yet
+    pub unreviewed_count: usize,
+    /// Latest analysis in this 工種
+    pub last_analyzed_at: Option<String>,
+    pub entries: Vec<AnalysisHistoryEntry>,
+}
+
+/// Roll-up of a project (工事) over its 工種
+#[derive(Clone, Serialize)]
+pub struct ProjectSummary {
+    pub project_folder: String,
+    pub project_name: String,
+    pub document_count: usize,
+    pub issue_count: usize,
+    pub resolved_count: usize,
+    pub unreviewed_count: usize,
+    pub work_types: Vec<WorkTypeSummary>,
+}
+
+/// Directory of the local history files
+///
+/// Each project is stored as `shoruichecker/history/{folder_hash}.json` in
+/// the user's config directory.
+pub fn get_history_dir() -> PathBuf {
     let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    let folder_hash = format!("{:x}", path_hash(project_folder));
-    config_dir
-        .join("shoruichecker")
-        .join("history")
-        .join(format!("{}.json", folder_hash))
+    config_dir.join("shoruichecker").join("history")
 }
 
 /// Simple hash function to generate a unique filename from a folder path
@@ -51,40 +169,73 @@ pub fn path_hash(s: &str) -> u64 {
     hasher.finish()
 }
 
+/// Entries written before IDs existed are identified by their date
+fn fill_entry_ids(history: &mut AnalysisHistory) {
+    for entry in history.entries.iter_mut().chain(history.revisions.iter_mut()) {
+        if entry.id.is_empty() {
+            entry.id = entry.analyzed_at.chars().filter(|c| c.is_ascii_digit()).collect();
+        }
+    }
+}
+
 /// Load analysis history for a project folder
 ///
-/// Returns an empty history if the file doesn't exist or can't be parsed.
-pub fn load_history(project_folder: &str) -> AnalysisHistory {
-    let path = get_history_path(project_folder);
-    if path.exists() {
-        fs::read_to_string(&path)
-            .ok()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_else(|| AnalysisHistory {
-                project_folder: project_folder.to_string(),
-                entries: vec![],
-            })
-    } else {
-        AnalysisHistory {
+/// Returns an empty history if none is stored or it can't be read.
+pub fn load_history(project_folder: &str) -> Result<AnalysisHistory, String> {
+    let mut history = history_store()
+        .load(project_folder)?
+        .unwrap_or_else(|| AnalysisHistory {
             project_folder: project_folder.to_string(),
-            entries: vec![],
-        }
-    }
+            ..Default::default()
+        });
+    fill_entry_ids(&mut history);
+    Ok(history)
+}
+
+/// Whether a history is stored for the project
+pub fn history_exists(project_folder: &str) -> Result<bool, String> {
+    Ok(history_store().load(project_folder)?.is_some())
 }
 
-/// Save analysis history to disk
+/// Save analysis history to the configured store
 ///
-/// Creates the history directory if it doesn't exist.
+/// The history is replaced as a whole; use `update_history` for
+/// read-modify-write. The query index in the database is refreshed
+/// afterwards (best effort).
 pub fn save_history(history: &AnalysisHistory) -> Result<(), String> {
-    let path = get_history_path(&history.project_folder);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
-    let json = serde_json::to_string_pretty(history).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
+    let _critical = critical_section()?;
+    history_store().save(history)?;
+    let _ = open_db().and_then(|conn| index_history(&conn, history));
     Ok(())
 }
 
+/// Load, modify and save a project history while holding its lock
+///
+/// Another process (the GUI, a headless run or another office sharing the
+/// database) may record entries at the same time; without the lock one of
+/// the updates would be lost.
+pub fn update_history<R>(
+    project_folder: &str,
+    f: impl FnOnce(&mut AnalysisHistory) -> R,
+) -> Result<R, String> {
+    let _critical = critical_section()?;
+    let mut f = Some(f);
+    let mut result = None;
+    let history = history_store().update(project_folder, &mut |history| {
+        fill_entry_ids(history);
+        if let Some(f) = f.take() {
+            result = Some(f(history));
+        }
+    })?;
+    let _ = open_db().and_then(|conn| index_history(&conn, &history));
+    result.ok_or_else(|| format!("履歴を更新できませんでした: {}", project_folder))
+}
+
+/// ID for a new history entry
+pub fn new_entry_id() -> String {
+    Local::now().format("%Y%m%d%H%M%S%3f").to_string()
+}
+
 /// Create a history entry from analysis results
 ///
 /// Extracts document type, issues, and summary from the analysis result text.
@@ -118,12 +269,131 @@ pub fn create_history_entry(file_name: &str, file_path: &str, result: &str) -> A
     let summary: String = result.lines().take(10).collect::<Vec<_>>().join("\n");
 
     AnalysisHistoryEntry {
+        id: new_entry_id(),
         file_name: file_name.to_string(),
         file_path: file_path.to_string(),
         analyzed_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
         document_type,
         summary,
         issues,
+        resolved_issues: Vec::new(),
+        cli_version: None,
+        file_hash: None,
+        reviewed_at: None,
+        mail: None,
+        pending: None,
+        approved_at: None,
+        store_error: None,
+    }
+}
+
+/// Max number of entries kept per project
+const MAX_HISTORY_ENTRIES: usize = 50;
+
+/// Max number of earlier analyses kept per file
+const MAX_REVISIONS_PER_FILE: usize = 5;
+
+/// Move the recorded analyses of a file to its new path (possibly another project)
+pub fn move_file_history(old_path: &str, new_path: &str) -> Result<(), String> {
+    let source = load_history(&project_folder_for(old_path))?;
+    if !source.entries.iter().chain(&source.revisions).any(|e| e.file_path == old_path) {
+        return Ok(());
+    }
+    let (entries, revisions) = update_history(&project_folder_for(old_path), |history| {
+        let (entries, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut history.entries)
+            .into_iter()
+            .partition(|e| e.file_path == old_path);
+        history.entries = kept;
+        let (revisions, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut history.revisions)
+            .into_iter()
+            .partition(|e| e.file_path == old_path);
+        history.revisions = kept;
+        (entries, revisions)
+    })?;
+    if entries.is_empty() && revisions.is_empty() {
+        return Ok(());
+    }
+
+    let file_name = Path::new(new_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let relocate = |mut entry: AnalysisHistoryEntry| {
+        entry.file_path = new_path.to_string();
+        entry.file_name = file_name.clone();
+        entry
+    };
+    update_history(&project_folder_for(new_path), |history| {
+        history.revisions.extend(revisions.into_iter().map(relocate));
+        history.entries.extend(entries.into_iter().map(relocate));
+    })
+}
+
+/// Add an entry, replacing the previous entry of the same file
+///
+/// Issues of the replaced entry that are gone from the new one are recorded
+/// as resolved. The replaced entry is kept in `revisions` so analyses can be
+/// compared later.
+pub fn record_history_entry(history: &mut AnalysisHistory, mut entry: AnalysisHistoryEntry) {
+    if let Some(pos) = history.entries.iter().position(|e| e.file_path == entry.file_path) {
+        let previous = history.entries.remove(pos);
+        entry.resolved_issues = previous
+            .issues
+            .iter()
+            .filter(|issue| !entry.issues.contains(issue))
+            .cloned()
+            .collect();
+        if entry.mail.is_none() {
+            entry.mail = previous.mail.clone();
+        }
+        history.revisions.push(previous);
+
+        let kept = history
+            .revisions
+            .iter()
+            .filter(|r| r.file_path == entry.file_path)
+            .count();
+        for _ in MAX_REVISIONS_PER_FILE..kept {
+            if let Some(oldest) = history.revisions.iter().position(|r| r.file_path == entry.file_path) {
+                history.revisions.remove(oldest);
+            }
+        }
+    }
+    history.dropped.retain(|d| d.file_path != entry.file_path);
+    history.entries.push(entry);
+    if history.entries.len() > MAX_HISTORY_ENTRIES {
+        let kept = history
+            .entries
+            .split_off(history.entries.len() - MAX_HISTORY_ENTRIES);
+        let dropped = std::mem::replace(&mut history.entries, kept);
+        history.dropped.extend(dropped.iter().map(DroppedEntry::of));
+        let entries = &history.entries;
+        history
+            .revisions
+            .retain(|r| entries.iter().any(|e| e.file_path == r.file_path));
+    }
+}
+
+/// All recorded analyses of a file, oldest first
+pub fn analyses_of_file<'a>(history: &'a AnalysisHistory, file_path: &str) -> Vec<&'a AnalysisHistoryEntry> {
+    let mut analyses: Vec<&AnalysisHistoryEntry> = history
+        .revisions
+        .iter()
+        .chain(history.entries.iter())
+        .filter(|e| e.file_path == file_path)
+        .collect();
+    analyses.sort_by(|a, b| a.analyzed_at.cmp(&b.analyzed_at));
+    analyses
+}
+
+/// Mark the entry of a file as reviewed; false if the file has no entry
+pub fn mark_entry_reviewed(history: &mut AnalysisHistory, file_path: &str) -> bool {
+    match history.entries.iter_mut().find(|e| e.file_path == file_path) {
+        Some(entry) => {
+            entry.reviewed_at = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+            true
+        }
+        None => false,
     }
 }
 
@@ -164,29 +434,117 @@ pub fn build_history_context(history: &AnalysisHistory) -> String {
     context
 }
 
-/// 全履歴を取得（フロントエンド用）
-#[tauri::command]
-pub fn get_all_history() -> Vec<AnalysisHistoryEntry> {
-    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    let history_dir = config_dir.join("shoruichecker").join("history");
-
-    if !history_dir.exists() {
-        return vec![];
-    }
+/// Load the histories of all projects
+pub fn load_all_histories() -> Vec<AnalysisHistory> {
+    history_store().load_all().unwrap_or_default()
+}
 
-    let mut all_entries: Vec<AnalysisHistoryEntry> = vec![];
+/// 工種 of a document: its first-level subfolder under the project folder
+pub fn work_type_of(project_folder: &str, file_path: &str) -> Option<String> {
+    let parent = Path::new(file_path).parent()?;
+    let relative = parent.strip_prefix(project_folder).ok()?;
+    relative
+        .components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+}
 
-    if let Ok(entries) = fs::read_dir(&history_dir) {
-        for entry in entries.flatten() {
-            if entry.path().extension().map(|e| e == "json").unwrap_or(false) {
-                if let Ok(content) = fs::read_to_string(entry.path()) {
-                    if let Ok(history) = serde_json::from_str::<AnalysisHistory>(&content) {
-                        all_entries.extend(history.entries);
-                    }
-                }
+/// Roll up a project history per 工種 (sorted by name, 工種なし last)
+///
+/// Documents dropped from the history still count (without their entries).
+pub fn summarize_project_history(history: &AnalysisHistory) -> ProjectSummary {
+    let mut groups: Vec<WorkTypeSummary> = Vec::new();
+    let counted = history
+        .dropped
+        .iter()
+        .cloned()
+        .map(|dropped| (dropped, None))
+        .chain(history.entries.iter().map(|e| (DroppedEntry::of(e), Some(e))));
+    for (counts, entry) in counted {
+        let work_type = work_type_of(&history.project_folder, &counts.file_path)
+            .unwrap_or_else(|| UNCATEGORIZED_WORK_TYPE.to_string());
+        let index = match groups.iter().position(|g| g.work_type == work_type) {
+            Some(i) => i,
+            None => {
+                groups.push(WorkTypeSummary {
+                    work_type,
+                    document_count: 0,
+                    issue_count: 0,
+                    resolved_count: 0,
+                    unreviewed_count: 0,
+                    last_analyzed_at: None,
+                    entries: Vec::new(),
+                });
+                groups.len() - 1
             }
+        };
+        let group = &mut groups[index];
+        group.document_count += 1;
+        group.issue_count += counts.issue_count;
+        group.resolved_count += counts.resolved_count;
+        if counts.unreviewed {
+            group.unreviewed_count += 1;
         }
+        if group.last_analyzed_at.as_deref() < Some(counts.analyzed_at.as_str()) {
+            group.last_analyzed_at = Some(counts.analyzed_at);
+        }
+        group.entries.extend(entry.cloned());
+    }
+    groups.sort_by(|a, b| {
+        (a.work_type == UNCATEGORIZED_WORK_TYPE, &a.work_type)
+            .cmp(&(b.work_type == UNCATEGORIZED_WORK_TYPE, &b.work_type))
+    });
+
+    ProjectSummary {
+        project_folder: history.project_folder.clone(),
+        project_name: Path::new(&history.project_folder)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| history.project_folder.clone()),
+        document_count: groups.iter().map(|g| g.document_count).sum(),
+        issue_count: groups.iter().map(|g| g.issue_count).sum(),
+        resolved_count: groups.iter().map(|g| g.resolved_count).sum(),
+        unreviewed_count: groups.iter().map(|g| g.unreviewed_count).sum(),
+        work_types: groups,
     }
+}
+
+/// 工事ごとの集計（工種別の内訳付き）
+#[tauri::command]
+pub fn get_project_summary(folder: String) -> Result<ProjectSummary, String> {
+    record_access("get_project_summary", &folder);
+    Ok(summarize_project_history(&load_history(&folder)?))
+}
+
+/// 全工事の集計
+#[tauri::command]
+pub fn get_all_project_summaries() -> Vec<ProjectSummary> {
+    let mut summaries: Vec<ProjectSummary> = load_all_histories()
+        .iter()
+        .map(summarize_project_history)
+        .collect();
+    summaries.sort_by(|a, b| a.project_name.cmp(&b.project_name));
+    summaries
+}
+
+/// ファイルの解析履歴（過去の解析を含む、古い順）
+#[tauri::command]
+pub fn get_file_analyses(path: String) -> Result<Vec<AnalysisHistoryEntry>, String> {
+    record_access("get_file_analyses", &path);
+    Ok(analyses_of_file(&load_history(&project_folder_for(&path))?, &path)
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+/// 全履歴を取得（フロントエンド用）
+#[tauri::command]
+pub fn get_all_history() -> Vec<AnalysisHistoryEntry> {
+    record_access("get_all_history", "");
+    let mut all_entries: Vec<AnalysisHistoryEntry> = load_all_histories()
+        .into_iter()
+        .flat_map(|h| h.entries)
+        .collect();
 
     // Sort by analyzed_at descending
     all_entries.sort_by(|a, b| b.analyzed_at.cmp(&a.analyzed_at));
@@ -220,11 +578,97 @@ mod tests {
         assert!(!entry.issues.is_empty());
     }
 
+    #[test]
+    fn test_record_history_entry_tracks_resolved_issues() {
+        let mut history = AnalysisHistory::default();
+        record_history_entry(
+            &mut history,
+            create_history_entry("a.pdf", "/p/a.pdf", "⚠ 金額不整合\n⚠ 日付矛盾"),
+        );
+        record_history_entry(&mut history, create_history_entry("a.pdf", "/p/a.pdf", "⚠ 日付矛盾"));
+
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].resolved_issues, vec!["⚠ 金額不整合".to_string()]);
+        assert_eq!(history.revisions.len(), 1);
+        assert_eq!(analyses_of_file(&history, "/p/a.pdf").len(), 2);
+    }
+
+    #[test]
+    fn test_record_history_entry_caps_revisions_per_file() {
+        let mut history = AnalysisHistory::default();
+        for i in 0..MAX_REVISIONS_PER_FILE + 3 {
+            let mut entry = create_history_entry("a.pdf", "/p/a.pdf", "⚠ 金額不整合");
+            entry.id = format!("a{}", i);
+            record_history_entry(&mut history, entry);
+        }
+        record_history_entry(&mut history, create_history_entry("b.pdf", "/p/b.pdf", "問題なし"));
+
+        assert_eq!(history.revisions.len(), MAX_REVISIONS_PER_FILE);
+        assert_eq!(history.revisions[0].id, "a2");
+        assert_eq!(history.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_summarize_project_history_by_work_type() {
+        let folder = Path::new("/工事A").to_string_lossy().to_string();
+        let path = |rel: &str| Path::new("/工事A").join(rel).to_string_lossy().to_string();
+        let mut history = AnalysisHistory {
+            project_folder: folder,
+            ..Default::default()
+        };
+        for (rel, result) in [
+            ("舗装工/出来形.pdf", "⚠ 寸法不足"),
+            ("舗装工/写真/01.pdf", "問題なし"),
+            ("区画線工/出来形.pdf", "⚠ 日付矛盾\n⚠ 押印漏れ"),
+            ("施工計画書.pdf", "問題なし"),
+        ] {
+            let file_name = Path::new(rel).file_name().unwrap().to_string_lossy().to_string();
+            record_history_entry(&mut history, create_history_entry(&file_name, &path(rel), result));
+        }
+
+        let summary = summarize_project_history(&history);
+        assert_eq!(summary.project_name, "工事A");
+        assert_eq!(summary.document_count, 4);
+        assert_eq!(summary.issue_count, 3);
+        let names: Vec<_> = summary.work_types.iter().map(|g| g.work_type.as_str()).collect();
+        assert_eq!(names, vec!["区画線工", "舗装工", UNCATEGORIZED_WORK_TYPE]);
+        assert_eq!(summary.work_types[1].document_count, 2);
+        assert_eq!(summary.unreviewed_count, 2);
+
+        assert!(mark_entry_reviewed(&mut history, &path("区画線工/出来形.pdf")));
+        assert!(!mark_entry_reviewed(&mut history, &path("missing.pdf")));
+        assert_eq!(summarize_project_history(&history).unreviewed_count, 1);
+    }
+
+    #[test]
+    fn summaries_count_documents_dropped_from_the_history() {
+        let mut history = AnalysisHistory {
+            project_folder: "/p".to_string(),
+            ..Default::default()
+        };
+        for i in 0..MAX_HISTORY_ENTRIES + 2 {
+            let name = format!("{}.pdf", i);
+            record_history_entry(&mut history, create_history_entry(&name, &format!("/p/{}", name), "⚠ 押印漏れ"));
+        }
+        assert_eq!(history.entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(history.dropped.len(), 2);
+        let summary = summarize_project_history(&history);
+        assert_eq!(summary.document_count, MAX_HISTORY_ENTRIES + 2);
+        assert_eq!(summary.issue_count, MAX_HISTORY_ENTRIES + 2);
+        assert_eq!(summary.work_types[0].entries.len(), MAX_HISTORY_ENTRIES);
+
+        // Analyzed again: counted once, from the new entry
+        record_history_entry(&mut history, create_history_entry("0.pdf", "/p/0.pdf", "問題なし"));
+        let summary = summarize_project_history(&history);
+        assert_eq!(summary.document_count, MAX_HISTORY_ENTRIES + 2);
+        assert_eq!(summary.issue_count, MAX_HISTORY_ENTRIES + 1);
+    }
+
     #[test]
     fn test_build_history_context_empty() {
         let history = AnalysisHistory {
             project_folder: "test".to_string(),
-            entries: vec![],
+            ..Default::default()
         };
 
         let context = build_history_context(&history);
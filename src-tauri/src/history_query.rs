@@ -0,0 +1,425 @@
+//! Paginated, filterable queries over the analysis history
+//!
+//! The JSON history files stay the source of truth. Every saved history is
+//! mirrored into the `history_entries` / `history_findings` tables so the
+//! result viewer can page through a year of data with SQL filters instead
+//! of loading every entry of every project. Indexed projects are tracked in
+//! `history_index_state`; projects missing from it (older databases, shared
+//! histories) are indexed on the first query of a session.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::Local;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::record_access;
+use crate::database::open_db;
+use crate::encryption::{encryption_enabled, open_text, seal_text};
+use crate::export::issue_severity;
+use crate::history::{load_all_histories, AnalysisHistory};
+use crate::spec_clauses::clause_reference;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// All histories were checked against the index in this session
+static INDEX_CHECKED: AtomicBool = AtomicBool::new(false);
+
+/// Filters of a history query (all optional)
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default)]
+    pub project: Option<String>,
+    /// `YYYY-MM-DD` (inclusive)
+    #[serde(default)]
+    pub date_from: Option<String>,
+    /// `YYYY-MM-DD` (inclusive)
+    #[serde(default)]
+    pub date_to: Option<String>,
+    /// 高 / 中 / 低 (findings only)
+    #[serde(default)]
+    pub severity: Option<String>,
+    #[serde(default)]
+    pub document_type: Option<String>,
+    /// 未解決 / 解消 (findings only)
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Substring of the finding/summary or file name
+    #[serde(default)]
+    pub text: Option<String>,
+    /// 0-based page
+    #[serde(default)]
+    pub page: usize,
+    #[serde(default)]
+    pub page_size: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Page<T> {
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    pub items: Vec<T>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FindingRow {
+    pub project_folder: String,
+    pub entry_id: String,
+    pub file_path: String,
+    pub file_name: String,
+    pub document_type: Option<String>,
+    pub analyzed_at: String,
+    pub severity: String,
+    pub status: String,
+    pub issue: String,
+    /// Specification clause cited in the issue
+    pub clause: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct EntryRow {
+    pub project_folder: String,
+    pub entry_id: String,
+    pub file_path: String,
+    pub file_name: String,
+    pub document_type: Option<String>,
+    pub analyzed_at: String,
+    pub summary: String,
+    pub issue_count: usize,
+}
+
+/// Replace the indexed rows of a project with its current history entries
+pub fn index_history(conn: &Connection, history: &AnalysisHistory) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let folder = &history.project_folder;
+    tx.execute("DELETE FROM history_entries WHERE project_folder = ?1", params![folder])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM history_findings WHERE project_folder = ?1", params![folder])
+        .map_err(|e| e.to_string())?;
+
+    for entry in &history.entries {
+        tx.execute(
+            "INSERT OR REPLACE INTO history_entries
+             (project_folder, entry_id, file_path, file_name, document_type, analyzed_at, summary, issue_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                folder,
+                entry.id,
+                entry.file_path,
+                entry.file_name,
+                entry.document_type,
+                entry.analyzed_at,
+                seal_text(&entry.summary)?,
+                entry.issues.len() as i64
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let open = entry.issues.iter().map(|i| (i, "未解決"));
+        let resolved = entry.resolved_issues.iter().map(|i| (i, "解消"));
+        for (issue, status) in open.chain(resolved) {
+            tx.execute(
+                "INSERT INTO history_findings
+                 (project_folder, entry_id, file_path, file_name, document_type, analyzed_at, severity, status, issue)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    folder,
+                    entry.id,
+                    entry.file_path,
+                    entry.file_name,
+                    entry.document_type,
+                    entry.analyzed_at,
+                    issue_severity(issue),
+                    status,
+                    seal_text(issue)?
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.execute(
+        "INSERT OR REPLACE INTO history_index_state (project_folder, indexed_at) VALUES (?1, ?2)",
+        params![folder, Local::now().format("%Y-%m-%d %H:%M:%S").to_string()],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Index the histories of projects that were never indexed
+pub fn index_missing(conn: &Connection, histories: &[AnalysisHistory]) -> Result<(), String> {
+    for history in histories {
+        let indexed = conn
+            .query_row(
+                "SELECT 1 FROM history_index_state WHERE project_folder = ?1",
+                params![history.project_folder],
+                |_| Ok(()),
+            )
+            .is_ok();
+        if !indexed {
+            index_history(conn, history)?;
+        }
+    }
+    Ok(())
+}
+
+/// Index missing projects once per session (later saves index themselves)
+fn ensure_indexed(conn: &Connection) -> Result<(), String> {
+    if INDEX_CHECKED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    index_missing(conn, &load_all_histories())?;
+    INDEX_CHECKED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Inclusive upper bound for `analyzed_at` (`%Y-%m-%d %H:%M:%S`)
+fn end_of_day(date: &str) -> String {
+    if date.len() == 10 {
+        format!("{} 23:59:59", date)
+    } else {
+        date.to_string()
+    }
+}
+
+fn page_bounds(query: &HistoryQuery) -> (usize, usize) {
+    let size = query
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    (size, query.page * size)
+}
+
+/// Run a filtered query with SQL paging
+///
+/// Encrypted text cannot be searched in SQL, so with encryption enabled a
+/// text filter is applied after decryption and the page is cut in memory.
+fn paged<T>(
+    conn: &Connection,
+    select: &str,
+    count: &str,
+    query: &HistoryQuery,
+    map_row: impl Fn(&Row) -> rusqlite::Result<T>,
+    matches_text: impl Fn(&T, &str) -> bool,
+) -> Result<Page<T>, String> {
+    let (page_size, offset) = page_bounds(query);
+    let text = query.text.as_deref().filter(|t| !t.is_empty());
+    let filter_in_memory = text.is_some() && encryption_enabled();
+    let sql_text = if filter_in_memory { None } else { text };
+    let date_to = query.date_to.as_deref().map(end_of_day);
+    let filters = params![
+        query.project,
+        query.date_from,
+        date_to,
+        query.severity,
+        query.document_type,
+        query.status,
+        sql_text
+    ];
+
+    if filter_in_memory {
+        let mut stmt = conn
+            .prepare(&format!("{} LIMIT -1", select))
+            .map_err(|e| e.to_string())?;
+        let rows: Vec<T> = stmt
+            .query_map(filters, |row| map_row(row))
+            .map_err(|e| e.to_string())?
+            .flatten()
+            .filter(|item| matches_text(item, text.unwrap_or_default()))
+            .collect();
+        let total = rows.len();
+        let items = rows.into_iter().skip(offset).take(page_size).collect();
+        return Ok(Page { total, page: query.page, page_size, items });
+    }
+
+    let total: i64 = conn
+        .query_row(count, filters, |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!("{} LIMIT {} OFFSET {}", select, page_size, offset))
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map(filters, |row| map_row(row))
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .collect();
+    Ok(Page {
+        total: total as usize,
+        page: query.page,
+        page_size,
+        items,
+    })
+}
+
+const FINDING_FILTERS: &str = "WHERE (?1 IS NULL OR project_folder = ?1)
+      AND (?2 IS NULL OR analyzed_at >= ?2)
+      AND (?3 IS NULL OR analyzed_at <= ?3)
+      AND (?4 IS NULL OR severity = ?4)
+      AND (?5 IS NULL OR document_type = ?5)
+      AND (?6 IS NULL OR status = ?6)
+      AND (?7 IS NULL OR instr(issue, ?7) > 0 OR instr(file_name, ?7) > 0)";
+
+// Severity and status (?4, ?6) do not apply to entries and are ignored
+const ENTRY_FILTERS: &str = "WHERE (?1 IS NULL OR project_folder = ?1)
+      AND (?2 IS NULL OR analyzed_at >= ?2)
+      AND (?3 IS NULL OR analyzed_at <= ?3)
+      AND (?5 IS NULL OR document_type = ?5)
+      AND (?7 IS NULL OR instr(summary, ?7) > 0 OR instr(file_name, ?7) > 0)";
+
+pub fn find_findings(conn: &Connection, query: &HistoryQuery) -> Result<Page<FindingRow>, String> {
+    paged(
+        conn,
+        &format!(
+            "SELECT project_folder, entry_id, file_path, file_name, document_type, analyzed_at, severity, status, issue
+             FROM history_findings {} ORDER BY analyzed_at DESC, id",
+            FINDING_FILTERS
+        ),
+        &format!("SELECT COUNT(*) FROM history_findings {}", FINDING_FILTERS),
+        query,
+        |row| {
+            let issue = open_text(row.get(8)?).unwrap_or_default();
+            Ok(FindingRow {
+                clause: clause_reference(&issue),
+                project_folder: row.get(0)?,
+                entry_id: row.get(1)?,
+                file_path: row.get(2)?,
+                file_name: row.get(3)?,
+                document_type: row.get(4)?,
+                analyzed_at: row.get(5)?,
+                severity: row.get(6)?,
+                status: row.get(7)?,
+                issue,
+            })
+        },
+        |row, text| row.issue.contains(text) || row.file_name.contains(text),
+    )
+}
+
+pub fn find_entries(conn: &Connection, query: &HistoryQuery) -> Result<Page<EntryRow>, String> {
+    paged(
+        conn,
+        &format!(
+            "SELECT project_folder, entry_id, file_path, file_name, document_type, analyzed_at, summary, issue_count
+             FROM history_entries {} ORDER BY analyzed_at DESC, file_path",
+            ENTRY_FILTERS
+        ),
+        &format!("SELECT COUNT(*) FROM history_entries {}", ENTRY_FILTERS),
+        query,
+        |row| {
+            Ok(EntryRow {
+                project_folder: row.get(0)?,
+                entry_id: row.get(1)?,
+                file_path: row.get(2)?,
+                file_name: row.get(3)?,
+                document_type: row.get(4)?,
+                analyzed_at: row.get(5)?,
+                summary: open_text(row.get(6)?).unwrap_or_default(),
+                issue_count: row.get::<_, i64>(7)? as usize,
+            })
+        },
+        |row, text| row.summary.contains(text) || row.file_name.contains(text),
+    )
+}
+
+/// 指摘の検索（工事・期間・重要度・書類タイプ・状態・文字列で絞り込み、ページ単位）
+#[tauri::command]
+pub fn query_findings(query: HistoryQuery) -> Result<Page<FindingRow>, String> {
+    record_access("query_findings", query.project.as_deref().unwrap_or(""));
+    let conn = open_db()?;
+    ensure_indexed(&conn)?;
+    find_findings(&conn, &query)
+}
+
+/// 解析履歴の検索（工事・期間・書類タイプ・文字列で絞り込み、ページ単位）
+#[tauri::command]
+pub fn query_history(query: HistoryQuery) -> Result<Page<EntryRow>, String> {
+    record_access("query_history", query.project.as_deref().unwrap_or(""));
+    let conn = open_db()?;
+    ensure_indexed(&conn)?;
+    find_entries(&conn, &query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+    use crate::history::create_history_entry;
+
+    fn history(folder: &str, entries: &[(&str, &str, &str)]) -> AnalysisHistory {
+        AnalysisHistory {
+            project_folder: folder.to_string(),
+            entries: entries
+                .iter()
+                .enumerate()
+                .map(|(i, (name, date, result))| {
+                    let mut entry = create_history_entry(name, &format!("{}/{}", folder, name), result);
+                    entry.id = format!("e{}", i);
+                    entry.analyzed_at = date.to_string();
+                    entry
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn findings_are_filtered_and_paged() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        index_history(
+            &conn,
+            &history(
+                "/p",
+                &[
+                    ("契約書.pdf", "2026-04-01 10:00:00", "契約書\n⚠ 金額不整合\n⚠ 押印漏れ（重大）"),
+                    ("請求書.pdf", "2026-04-30 18:00:00", "請求書\n⚠ 日付の誤記"),
+                ],
+            ),
+        )
+        .unwrap();
+        index_history(&conn, &history("/q", &[("見積書.pdf", "2026-05-02 09:00:00", "見積書\n⚠ 単価")])).unwrap();
+
+        let all = find_findings(&conn, &HistoryQuery::default()).unwrap();
+        assert_eq!(all.total, 4);
+        assert_eq!(all.items[0].file_name, "見積書.pdf");
+
+        let april = HistoryQuery {
+            project: Some("/p".to_string()),
+            date_to: Some("2026-04-30".to_string()),
+            page_size: Some(2),
+            page: 1,
+            ..Default::default()
+        };
+        let page = find_findings(&conn, &april).unwrap();
+        assert_eq!((page.total, page.items.len()), (3, 1));
+
+        let high = HistoryQuery {
+            severity: Some("高".to_string()),
+            ..Default::default()
+        };
+        let page = find_findings(&conn, &high).unwrap();
+        assert_eq!(page.total, 1);
+        assert!(page.items[0].issue.contains("押印漏れ"));
+
+        // Re-indexing a project replaces its rows
+        index_history(&conn, &history("/p", &[])).unwrap();
+        assert_eq!(find_findings(&conn, &HistoryQuery::default()).unwrap().total, 1);
+        assert_eq!(find_entries(&conn, &HistoryQuery::default()).unwrap().total, 1);
+    }
+
+    #[test]
+    fn only_projects_never_indexed_are_indexed() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        index_history(&conn, &history("/p", &[("契約書.pdf", "2026-04-01 10:00:00", "契約書\n⚠ 金額")])).unwrap();
+
+        let histories = vec![
+            history("/p", &[]),
+            history("/q", &[("見積書.pdf", "2026-05-02 09:00:00", "見積書\n⚠ 単価")]),
+        ];
+        index_missing(&conn, &histories).unwrap();
+        let all = find_findings(&conn, &HistoryQuery::default()).unwrap();
+        assert_eq!(all.total, 2);
+    }
+}
@@ -0,0 +1,476 @@
+//! Storage backend of the analysis histories
+//!
+//! Histories are stored per project through a [`HistoryStore`]. The default
+//! is the local store (sealed JSON files in the config directory, mirrored
+//! into the local SQLite query index). Branch offices can instead share one
+//! central PostgreSQL database (cargo feature `shared-history`); the
+//! connection string is kept in the OS keychain, not in settings.json.
+//! Connections to the database always use TLS, and with `encrypt_at_rest`
+//! the stored histories are sealed like the local files (every office
+//! sharing the database then needs the same key).
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::{read_string, seal_bytes};
+use crate::file_lock::{lock_file, write_atomic};
+use crate::history::{get_history_dir, path_hash, AnalysisHistory, AnalysisHistoryEntry};
+use crate::keychain::{read_secret, store_secret};
+use crate::settings::{load_settings, save_settings, HistoryBackend};
+
+const KEYRING_USER: &str = "history-database-url";
+
+/// Store selected from the settings (reset when they change)
+static STORE: Mutex<Option<Arc<dyn HistoryStore>>> = Mutex::new(None);
+
+/// Where project histories are read from and written to
+pub trait HistoryStore: Send + Sync {
+    fn backend(&self) -> HistoryBackend;
+
+    /// History of a project (None if none was stored; an error if the
+    /// stored one cannot be read)
+    fn load(&self, project_folder: &str) -> Result<Option<AnalysisHistory>, String>;
+
+    /// Replace the history of a project
+    fn save(&self, history: &AnalysisHistory) -> Result<(), String>;
+
+    /// Read-modify-write under a lock held against other writers
+    ///
+    /// `f` gets the stored history (or an empty one); the saved result is
+    /// returned.
+    fn update(
+        &self,
+        project_folder: &str,
+        f: &mut dyn FnMut(&mut AnalysisHistory),
+    ) -> Result<AnalysisHistory, String>;
+
+    /// Histories of all projects
+    fn load_all(&self) -> Result<Vec<AnalysisHistory>, String>;
+}
+
+fn empty_history(project_folder: &str) -> AnalysisHistory {
+    AnalysisHistory {
+        project_folder: project_folder.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Sealed JSON files, one per project
+pub struct LocalHistoryStore {
+    root: PathBuf,
+}
+
+impl LocalHistoryStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn path(&self, project_folder: &str) -> PathBuf {
+        self.root.join(format!("{:x}.json", path_hash(project_folder)))
+    }
+}
+
+impl Default for LocalHistoryStore {
+    fn default() -> Self {
+        Self::new(get_history_dir())
+    }
+}
+
+impl HistoryStore for LocalHistoryStore {
+    fn backend(&self) -> HistoryBackend {
+        HistoryBackend::Local
+    }
+
+    fn load(&self, project_folder: &str) -> Result<Option<AnalysisHistory>, String> {
+        let path = self.path(project_folder);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = read_string(&path).ok_or_else(|| format!("履歴を読み込めません: {}", project_folder))?;
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| format!("履歴が正しくありません: {}: {}", project_folder, e))
+    }
+
+    fn save(&self, history: &AnalysisHistory) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(history).map_err(|e| e.to_string())?;
+        write_atomic(&self.path(&history.project_folder), seal_bytes(json.as_bytes())?)
+    }
+
+    fn update(
+        &self,
+        project_folder: &str,
+        f: &mut dyn FnMut(&mut AnalysisHistory),
+    ) -> Result<AnalysisHistory, String> {
+        let _lock = lock_file(&self.path(project_folder))?;
+        let mut history = self.load(project_folder)?.unwrap_or_else(|| empty_history(project_folder));
+        f(&mut history);
+        self.save(&history)?;
+        Ok(history)
+    }
+
+    fn load_all(&self) -> Result<Vec<AnalysisHistory>, String> {
+        let Ok(entries) = fs::read_dir(&self.root) else {
+            return Ok(Vec::new());
+        };
+        Ok(entries
+            .flatten()
+            .filter(|e| e.path().extension().map(|x| x == "json").unwrap_or(false))
+            .filter_map(|e| read_string(&e.path()))
+            .filter_map(|s| serde_json::from_str(&s).ok())
+            .collect())
+    }
+}
+
+#[cfg(feature = "shared-history")]
+mod postgres_store {
+    use std::sync::Mutex;
+
+    use native_tls::TlsConnector;
+    use postgres::config::SslMode;
+    use postgres::{Client, Config};
+    use postgres_native_tls::MakeTlsConnector;
+
+    use super::{empty_history, HistoryStore};
+    use crate::encryption::{open_text, seal_text};
+    use crate::history::AnalysisHistory;
+    use crate::settings::HistoryBackend;
+
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS shoruichecker_history (
+        project_folder TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )";
+
+    /// One row per project in a shared PostgreSQL database
+    pub struct PostgresHistoryStore {
+        url: String,
+        /// Connection reused across calls (the schema is created on connect)
+        client: Mutex<Option<Client>>,
+    }
+
+    impl PostgresHistoryStore {
+        pub fn new(url: String) -> Self {
+            Self {
+                url,
+                client: Mutex::new(None),
+            }
+        }
+
+        fn connect(&self) -> Result<Client, String> {
+            let mut config: Config = self
+                .url
+                .parse()
+                .map_err(|e| format!("共有履歴DBの接続文字列が正しくありません: {}", e))?;
+            // Histories hold contract data; never fall back to cleartext
+            config.ssl_mode(SslMode::Require);
+            let tls = TlsConnector::new().map_err(|e| format!("TLSを初期化できません: {}", e))?;
+            let mut client = config
+                .connect(MakeTlsConnector::new(tls))
+                .map_err(|e| format!("共有履歴DBに接続できません: {}", e))?;
+            client.batch_execute(SCHEMA).map_err(|e| e.to_string())?;
+            Ok(client)
+        }
+
+        /// Run `f` on the shared connection, connecting first if needed
+        fn with_client<R>(&self, f: impl FnOnce(&mut Client) -> Result<R, String>) -> Result<R, String> {
+            let mut slot = self.client.lock().unwrap_or_else(|e| e.into_inner());
+            let connected = slot.as_ref().is_some_and(|c| !c.is_closed());
+            if !connected {
+                *slot = Some(self.connect()?);
+            }
+            let client = slot.as_mut().expect("connected above");
+            let result = f(client);
+            if client.is_closed() {
+                *slot = None;
+            }
+            result
+        }
+    }
+
+    fn parse(data: String) -> Result<AnalysisHistory, String> {
+        let json = open_text(data)?;
+        serde_json::from_str(&json).map_err(|e| format!("共有履歴DBのデータが正しくありません: {}", e))
+    }
+
+    fn serialize(history: &AnalysisHistory) -> Result<String, String> {
+        seal_text(&serde_json::to_string(history).map_err(|e| e.to_string())?)
+    }
+
+    impl HistoryStore for PostgresHistoryStore {
+        fn backend(&self) -> HistoryBackend {
+            HistoryBackend::Postgres
+        }
+
+        fn load(&self, project_folder: &str) -> Result<Option<AnalysisHistory>, String> {
+            let row = self.with_client(|client| {
+                client
+                    .query_opt(
+                        "SELECT data FROM shoruichecker_history WHERE project_folder = $1",
+                        &[&project_folder],
+                    )
+                    .map_err(|e| e.to_string())
+            })?;
+            row.map(|r| parse(r.get(0))).transpose()
+        }
+
+        fn save(&self, history: &AnalysisHistory) -> Result<(), String> {
+            let data = serialize(history)?;
+            self.with_client(|client| {
+                client
+                    .execute(
+                        "INSERT INTO shoruichecker_history (project_folder, data) VALUES ($1, $2)
+                         ON CONFLICT (project_folder) DO UPDATE SET data = EXCLUDED.data, updated_at = now()",
+                        &[&history.project_folder, &data],
+                    )
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            })
+        }
+
+        fn update(
+            &self,
+            project_folder: &str,
+            f: &mut dyn FnMut(&mut AnalysisHistory),
+        ) -> Result<AnalysisHistory, String> {
+            let empty = serialize(&empty_history(project_folder))?;
+            self.with_client(|client| {
+                let mut tx = client.transaction().map_err(|e| e.to_string())?;
+                // A row must exist to be locked by FOR UPDATE
+                tx.execute(
+                    "INSERT INTO shoruichecker_history (project_folder, data) VALUES ($1, $2)
+                     ON CONFLICT (project_folder) DO NOTHING",
+                    &[&project_folder, &empty],
+                )
+                .map_err(|e| e.to_string())?;
+                let row = tx
+                    .query_one(
+                        "SELECT data FROM shoruichecker_history WHERE project_folder = $1 FOR UPDATE",
+                        &[&project_folder],
+                    )
+                    .map_err(|e| e.to_string())?;
+                let mut history = parse(row.get(0))?;
+                f(&mut history);
+                let data = serialize(&history)?;
+                tx.execute(
+                    "UPDATE shoruichecker_history SET data = $2, updated_at = now() WHERE project_folder = $1",
+                    &[&project_folder, &data],
+                )
+                .map_err(|e| e.to_string())?;
+                tx.commit().map_err(|e| e.to_string())?;
+                Ok(history)
+            })
+        }
+
+        fn load_all(&self) -> Result<Vec<AnalysisHistory>, String> {
+            let rows = self.with_client(|client| {
+                client
+                    .query("SELECT data FROM shoruichecker_history ORDER BY project_folder", &[])
+                    .map_err(|e| e.to_string())
+            })?;
+            Ok(rows.iter().filter_map(|r| parse(r.get(0)).ok()).collect())
+        }
+    }
+}
+
+fn shared_database_url() -> Option<String> {
+    read_secret(KEYRING_USER)
+}
+
+/// Store for a backend (fails when it is not available in this build or
+/// not configured)
+fn open_store(backend: HistoryBackend, url: Option<String>) -> Result<Arc<dyn HistoryStore>, String> {
+    match backend {
+        HistoryBackend::Local => Ok(Arc::new(LocalHistoryStore::default())),
+        #[cfg(feature = "shared-history")]
+        HistoryBackend::Postgres => {
+            let url = url.ok_or_else(|| "共有履歴DBの接続文字列が設定されていません".to_string())?;
+            Ok(Arc::new(postgres_store::PostgresHistoryStore::new(url)))
+        }
+        #[cfg(not(feature = "shared-history"))]
+        HistoryBackend::Postgres => {
+            let _ = url;
+            Err("このビルドは共有履歴DBに対応していません（shared-history 機能）".to_string())
+        }
+    }
+}
+
+/// The configured history store
+///
+/// Falls back to the local store when the shared one cannot be opened, so
+/// analyses keep working; `get_history_backend` reports the active store.
+pub fn history_store() -> Arc<dyn HistoryStore> {
+    let mut store = STORE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(store) = store.as_ref() {
+        return store.clone();
+    }
+    let opened = open_store(load_settings().history_backend, shared_database_url())
+        .unwrap_or_else(|_| Arc::new(LocalHistoryStore::default()));
+    *store = Some(opened.clone());
+    opened
+}
+
+fn reset_store() {
+    *STORE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Add the entries of `source` that `target` does not have yet (by ID)
+pub fn merge_history(target: &mut AnalysisHistory, source: &AnalysisHistory) -> usize {
+    let known = |list: &[AnalysisHistoryEntry], entry: &AnalysisHistoryEntry| {
+        list.iter().any(|e| e.id == entry.id && e.file_path == entry.file_path)
+    };
+    let mut added = 0;
+    for entry in &source.entries {
+        if known(&target.entries, entry) || known(&target.revisions, entry) {
+            continue;
+        }
+        match target.entries.iter().position(|e| e.file_path == entry.file_path) {
+            // Keep the newer analysis as the current entry
+            Some(pos) if target.entries[pos].analyzed_at >= entry.analyzed_at => {
+                target.revisions.push(entry.clone())
+            }
+            Some(pos) => {
+                let older = std::mem::replace(&mut target.entries[pos], entry.clone());
+                target.revisions.push(older);
+            }
+            None => target.entries.push(entry.clone()),
+        }
+        added += 1;
+    }
+    for entry in &source.revisions {
+        if !known(&target.entries, entry) && !known(&target.revisions, entry) {
+            target.revisions.push(entry.clone());
+            added += 1;
+        }
+    }
+    added
+}
+
+#[derive(Clone, Serialize)]
+pub struct HistoryBackendStatus {
+    /// Backend in the settings
+    pub configured: HistoryBackend,
+    /// Backend in use (local when the shared one is unavailable)
+    pub active: HistoryBackend,
+    pub connection_configured: bool,
+    pub shared_supported: bool,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct HistoryBackendRequest {
+    pub backend: HistoryBackend,
+    /// e.g. `host=db.example.local user=shorui password=… dbname=shorui`
+    /// (None: keep the stored one)
+    #[serde(default)]
+    pub connection_string: Option<String>,
+}
+
+/// 解析履歴の保存先（ローカル / 共有DB）
+#[tauri::command]
+pub fn get_history_backend() -> HistoryBackendStatus {
+    HistoryBackendStatus {
+        configured: load_settings().history_backend,
+        active: history_store().backend(),
+        connection_configured: shared_database_url().is_some(),
+        shared_supported: cfg!(feature = "shared-history"),
+    }
+}
+
+/// 履歴の保存先を切り替え（共有DBは接続を確認してから保存）
+#[tauri::command]
+pub fn set_history_backend(request: HistoryBackendRequest) -> Result<HistoryBackendStatus, String> {
+    let url = match request.connection_string.filter(|u| !u.trim().is_empty()) {
+        Some(url) => Some(url),
+        None => shared_database_url(),
+    };
+    let store = open_store(request.backend, url.clone())?;
+    store.load_all()?;
+    if let (HistoryBackend::Postgres, Some(url)) = (request.backend, &url) {
+        store_secret(KEYRING_USER, url)?;
+    }
+    let mut settings = load_settings();
+    settings.history_backend = request.backend;
+    save_settings(&settings)?;
+    reset_store();
+    Ok(get_history_backend())
+}
+
+/// ローカルの履歴を共有DBへ取り込み（取り込んだ解析件数）
+#[tauri::command]
+pub async fn upload_local_history() -> Result<usize, String> {
+    crate::tasks::run_blocking("history", "履歴の共有DB取り込み", || {
+        let store = history_store();
+        if store.backend() == HistoryBackend::Local {
+            return Err("共有履歴DBが有効になっていません".to_string());
+        }
+        let mut added = 0;
+        for local in LocalHistoryStore::default().load_all()? {
+            store.update(&local.project_folder, &mut |shared| {
+                added += merge_history(shared, &local);
+            })?;
+        }
+        Ok(added)
+    })
+    .await
+    .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+    use crate::history::create_history_entry;
+
+    #[test]
+    fn local_store_round_trips_and_updates() {
+        let dir = create_temp_dir(".shoruichecker_test_history_store").expect("create dir");
+        let store = LocalHistoryStore::new(dir.clone());
+        assert!(store.load("C:/p1").unwrap().is_none());
+
+        let updated = store
+            .update("C:/p1", &mut |h| h.entries.push(create_history_entry("a.pdf", "C:/p1/a.pdf", "⚠ 金額")))
+            .expect("update");
+        assert_eq!(updated.entries.len(), 1);
+        store.save(&empty_history("C:/p2")).expect("save");
+
+        let loaded = store.load("C:/p1").unwrap().expect("stored");
+        assert_eq!(loaded.entries[0].issues, vec!["⚠ 金額".to_string()]);
+        assert_eq!(store.load_all().unwrap().len(), 2);
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn unreadable_history_is_an_error_not_an_empty_one() {
+        let dir = create_temp_dir(".shoruichecker_test_history_store_broken").expect("create dir");
+        let store = LocalHistoryStore::new(dir.clone());
+        std::fs::write(store.path("C:/p1"), b"{ broken").unwrap();
+
+        assert!(store.load("C:/p1").is_err());
+        assert!(store.update("C:/p1", &mut |_| {}).is_err());
+        assert_eq!(std::fs::read(store.path("C:/p1")).unwrap(), b"{ broken");
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn merge_adds_only_unknown_analyses() {
+        let mut old = create_history_entry("a.pdf", "C:/p/a.pdf", "⚠ 旧");
+        old.id = "1".to_string();
+        old.analyzed_at = "2026-01-01 09:00:00".to_string();
+        let mut new = create_history_entry("a.pdf", "C:/p/a.pdf", "✓");
+        new.id = "2".to_string();
+        new.analyzed_at = "2026-02-01 09:00:00".to_string();
+
+        let mut shared = empty_history("C:/p");
+        shared.entries.push(old);
+        let mut local = empty_history("C:/p");
+        local.entries.push(new);
+
+        assert_eq!(merge_history(&mut shared, &local), 1);
+        assert_eq!(shared.entries[0].id, "2");
+        assert_eq!(shared.revisions[0].id, "1");
+        assert_eq!(merge_history(&mut shared, &local), 0);
+    }
+}
@@ -0,0 +1,240 @@
+//! User-configured external commands around an analysis batch
+//!
+//! Pre-analysis hooks run before a batch starts (e.g. a check that the
+//! documents may be sent out); post-analysis hooks run after it finished
+//! (e.g. a company script uploading the results to an internal system).
+//! Each hook receives the batch as one JSON object on stdin and is killed
+//! when it exceeds its timeout.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "windows")]
+use crate::CREATE_NO_WINDOW;
+
+use crate::history::{load_history, AnalysisHistoryEntry};
+use crate::messages::tr;
+use crate::processes::{isolate_process_group, register_child, wait_with_timeout};
+use crate::project_settings::project_folder_for;
+use crate::settings::{load_settings, save_settings};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HookStage {
+    Pre,
+    Post,
+    /// Files extracted from an intake archive, before they are analyzed
+    /// (e.g. a virus scanner); a failure always rejects the files
+    Scan,
+}
+
+/// An external command run before or after each analysis batch
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AnalysisHook {
+    pub name: String,
+    pub stage: HookStage,
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Pre hooks only: cancel the analysis when the hook fails
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn default_timeout_secs() -> u64 {
+    60
+}
+
+/// Outcome of the batch, passed to post hooks
+#[derive(Clone, Serialize)]
+pub struct HookOutcome {
+    pub success: bool,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    /// Latest history entry of each analyzed file (type, summary, findings)
+    pub reports: Vec<AnalysisHistoryEntry>,
+}
+
+/// JSON object written to the hook's stdin
+#[derive(Clone, Serialize)]
+pub struct HookPayload {
+    pub stage: HookStage,
+    pub batch_id: String,
+    pub mode: String,
+    pub paths: Vec<String>,
+    pub custom_instruction: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<HookOutcome>,
+}
+
+/// Structured reports of the analyzed files from their project histories
+/// (files whose history cannot be read are left out)
+pub fn latest_reports(paths: &[String]) -> Vec<AnalysisHistoryEntry> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            load_history(&project_folder_for(path))
+                .ok()?
+                .entries
+                .into_iter()
+                .find(|e| &e.file_path == path)
+        })
+        .collect()
+}
+
+/// Run one hook with the payload on stdin; returns its stdout
+pub fn run_hook(hook: &AnalysisHook, payload: &HookPayload) -> Result<String, String> {
+    let input = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+
+    let mut cmd = Command::new(&hook.program);
+    cmd.args(&hook.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    isolate_process_group(&mut cmd);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| tr("hook.spawn_error", &[&hook.name, &e]))?;
+    let _guard = register_child(child.id(), &format!("hook: {}", hook.name));
+
+    // stdin is written from a thread so a hook that ignores it cannot block
+    // the timeout loop; the output pipes are served by `wait_with_timeout`
+    let mut stdin = child.stdin.take();
+    let writer = thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            let _ = stdin.write_all(&input);
+        }
+    });
+    let output = wait_with_timeout(child, Some(Duration::from_secs(hook.timeout_secs)), None)
+        .map_err(|e| e.to_string())?
+        .map_err(|_| tr("hook.timeout", &[&hook.name, &hook.timeout_secs]))?;
+    let _ = writer.join();
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        let code = output
+            .status
+            .code()
+            .map(|c| format!("exit code {}", c))
+            .unwrap_or_else(|| "terminated".to_string());
+        let err = String::from_utf8_lossy(&output.stderr);
+        Err(tr("hook.failed", &[&hook.name, &format!("{}: {}", code, err.trim())]))
+    }
+}
+
+/// Run the configured hooks of a stage in order
+///
+/// Returns the failures of optional hooks as warnings; a failing required
+/// pre hook or any failing scan hook stops the remaining hooks and is
+/// returned as the error.
+pub fn run_stage_hooks(hooks: &[AnalysisHook], payload: &HookPayload) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    for hook in hooks.iter().filter(|h| h.stage == payload.stage) {
+        if let Err(e) = run_hook(hook, payload) {
+            if (hook.required && hook.stage == HookStage::Pre) || hook.stage == HookStage::Scan {
+                return Err(e);
+            }
+            warnings.push(e);
+        }
+    }
+    Ok(warnings)
+}
+
+/// Configured hooks
+pub fn configured_hooks() -> Vec<AnalysisHook> {
+    load_settings().hooks
+}
+
+#[tauri::command]
+pub fn get_analysis_hooks() -> Vec<AnalysisHook> {
+    configured_hooks()
+}
+
+/// 解析前後に実行する外部コマンドを設定
+#[tauri::command]
+pub fn set_analysis_hooks(hooks: Vec<AnalysisHook>) -> Result<(), String> {
+    for hook in &hooks {
+        if hook.name.trim().is_empty() || hook.program.trim().is_empty() {
+            return Err("フック名とコマンドを指定してください".to_string());
+        }
+        if hook.timeout_secs == 0 {
+            return Err(format!("タイムアウトは1秒以上にしてください: {}", hook.name));
+        }
+    }
+    let mut settings = load_settings();
+    settings.hooks = hooks;
+    save_settings(&settings)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn hook(stage: HookStage, program: &str, args: &[&str], required: bool) -> AnalysisHook {
+        AnalysisHook {
+            name: program.to_string(),
+            stage,
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            timeout_secs: 1,
+            required,
+        }
+    }
+
+    fn payload(stage: HookStage) -> HookPayload {
+        HookPayload {
+            stage,
+            batch_id: "job-1".to_string(),
+            mode: "single".to_string(),
+            paths: vec!["/p/契約書.pdf".to_string()],
+            custom_instruction: String::new(),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn hook_receives_payload_on_stdin() {
+        let out = run_hook(&hook(HookStage::Pre, "cat", &[], false), &payload(HookStage::Pre))
+            .expect("run cat");
+        let json: serde_json::Value = serde_json::from_str(&out).expect("json");
+        assert_eq!(json["stage"], "pre");
+        assert_eq!(json["paths"][0], "/p/契約書.pdf");
+        assert!(json.get("outcome").is_none());
+    }
+
+    #[test]
+    fn slow_hook_is_killed_after_timeout() {
+        let started = Instant::now();
+        let result = run_hook(&hook(HookStage::Post, "sleep", &["30"], false), &payload(HookStage::Post));
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn only_required_pre_hooks_stop_the_batch() {
+        let hooks = vec![
+            hook(HookStage::Pre, "false", &[], false),
+            hook(HookStage::Post, "false", &[], true),
+        ];
+        let warnings = run_stage_hooks(&hooks, &payload(HookStage::Pre)).expect("optional");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(run_stage_hooks(&hooks, &payload(HookStage::Post)).expect("post").len(), 1);
+
+        let required = vec![hook(HookStage::Pre, "false", &[], true)];
+        assert!(run_stage_hooks(&required, &payload(HookStage::Pre)).is_err());
+    }
+}
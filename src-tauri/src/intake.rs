@@ -0,0 +1,492 @@
+//! Managed intake folder for the print-to-PDF driver
+//!
+//! The app creates `ShoruiChecker受付` under the user's documents folder and
+//! shows it as the save location to configure in the PDF printer. The folder
+//! has its own watcher: every PDF printed into it is analyzed and then moved,
+//! with its result and history, into the chosen project folder. ZIPs dropped
+//! into it are expanded first (see `zip_intake`).
+//!
+//! A shared office scanner can feed several projects through one folder:
+//! routes map a file name prefix or a subfolder of the intake folder to a
+//! project. A routed file is moved into its project before the analysis, so
+//! the project's guidelines, history and settings apply, and the result is
+//! mailed to the project's intake recipients.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tokio::sync::mpsc::unbounded_channel;
+
+use crate::access::{ensure_allowed, Operation};
+use crate::analysis::analyze_pdfs;
+use crate::approval::{is_held, notify_on_approval};
+use crate::events::emit_log;
+use crate::history::move_file_history;
+use crate::intake_dedup::{accept_new_file, release_file};
+use crate::mail::{send_mail, smtp_settings};
+use crate::messages::tr;
+use crate::project_settings::load_project_settings;
+use crate::result_store::{load_result_data, sidecar_path};
+use crate::settings::{load_settings, save_settings};
+use crate::tasks;
+use crate::zip_intake::{is_released, is_zip, process_intake_archive};
+
+static INTAKE_WATCHER: Mutex<Option<notify::RecommendedWatcher>> = Mutex::new(None);
+
+/// Printers write the PDF progressively; wait this long for it to be complete
+const STABLE_TIMEOUT: Duration = Duration::from_secs(120);
+const STABLE_POLL: Duration = Duration::from_millis(500);
+
+/// Intake folder configuration
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct IntakeSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Intake folder (None: `default_intake_folder`)
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Project folder analyzed files are moved to (None: they stay in intake)
+    #[serde(default)]
+    pub target_project: Option<String>,
+    /// Preset (ID or name) used for the analysis
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Per-sender routes of a shared scanner (first match wins; files no
+    /// route matches go to `target_project`)
+    #[serde(default)]
+    pub routes: Vec<IntakeRoute>,
+}
+
+/// File name prefix and/or intake subfolder mapped to a project folder
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntakeRoute {
+    /// Start of the file name (case-insensitive), e.g. the scanner's user code
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Subfolder of the intake folder the scanner saves to (e.g. "山田")
+    #[serde(default)]
+    pub subfolder: Option<String>,
+    pub project_folder: String,
+}
+
+impl IntakeRoute {
+    /// Whether a file in the intake folder matches every criterion of the route
+    fn matches(&self, intake: &Path, path: &Path) -> bool {
+        let prefix = self.prefix.as_deref().filter(|p| !p.is_empty());
+        let subfolder = self.subfolder.as_deref().filter(|s| !s.is_empty());
+        if prefix.is_none() && subfolder.is_none() {
+            return false;
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let prefix_ok = match prefix {
+            Some(p) => name.starts_with(&p.to_lowercase()),
+            None => true,
+        };
+        let subfolder_ok = match subfolder {
+            Some(s) => path
+                .parent()
+                .and_then(|dir| dir.strip_prefix(intake).ok())
+                .is_some_and(|dir| dir.starts_with(s)),
+            None => true,
+        };
+        prefix_ok && subfolder_ok
+    }
+}
+
+/// Intake folder and the printer setup hint shown to the user
+#[derive(Clone, Serialize)]
+pub struct IntakeStatus {
+    pub enabled: bool,
+    pub folder: String,
+    pub target_project: Option<String>,
+    pub preset: Option<String>,
+    pub routes: Vec<IntakeRoute>,
+    pub printer_guidance: String,
+}
+
+pub fn default_intake_folder() -> PathBuf {
+    dirs::document_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ShoruiChecker受付")
+}
+
+pub fn intake_folder(settings: &IntakeSettings) -> PathBuf {
+    settings
+        .folder
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(default_intake_folder)
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false)
+}
+
+/// First route matching a file in the intake folder
+pub fn route_for<'a>(settings: &'a IntakeSettings, path: &Path) -> Option<&'a IntakeRoute> {
+    let intake = intake_folder(settings);
+    settings.routes.iter().find(|route| route.matches(&intake, path))
+}
+
+/// Whether a path lies in a hidden folder below the intake folder (such as
+/// the `.unpacking` quarantine)
+fn in_hidden_folder(intake: &Path, path: &Path) -> bool {
+    path.parent()
+        .and_then(|dir| dir.strip_prefix(intake).ok())
+        .is_some_and(|dir| {
+            dir.components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        })
+}
+
+/// Wait until the printer has finished writing the file (size unchanged)
+pub(crate) fn wait_until_stable(path: &Path) -> bool {
+    let deadline = Instant::now() + STABLE_TIMEOUT;
+    let mut last_size = None;
+    while Instant::now() < deadline {
+        let size = fs::metadata(path).ok().map(|m| m.len());
+        if size.is_some_and(|s| s > 0) && size == last_size && fs::File::open(path).is_ok() {
+            return true;
+        }
+        last_size = size;
+        thread::sleep(STABLE_POLL);
+    }
+    false
+}
+
+/// Path in `folder` for `file_name` that does not exist yet (`name_2.pdf`, ...)
+pub fn unique_destination(folder: &Path, file_name: &str) -> PathBuf {
+    let candidate = folder.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let path = Path::new(file_name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|e| e.to_string_lossy().to_string());
+    (2..)
+        .map(|i| match &ext {
+            Some(ext) => folder.join(format!("{}_{}.{}", stem, i, ext)),
+            None => folder.join(format!("{}_{}", stem, i)),
+        })
+        .find(|p| !p.exists())
+        .expect("unbounded range")
+}
+
+/// Move an analyzed file with its sidecar and history into the project
+pub fn move_into_project(path: &Path, project: &Path) -> Result<PathBuf, String> {
+    ensure_allowed(Operation::Move)?;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| "ファイル名がありません".to_string())?;
+    fs::create_dir_all(project).map_err(|e| e.to_string())?;
+    let destination = unique_destination(project, &file_name);
+    fs::rename(path, &destination)
+        .or_else(|_| fs::copy(path, &destination).and_then(|_| fs::remove_file(path)))
+        .map_err(|e| tr("intake.move_error", &[&file_name, &e]))?;
+
+    let old = path.to_string_lossy().to_string();
+    let new = destination.to_string_lossy().to_string();
+    let sidecar = sidecar_path(&old);
+    if sidecar.exists() {
+        let _ = fs::rename(&sidecar, sidecar_path(&new));
+    }
+    let _ = move_file_history(&old, &new);
+    Ok(destination)
+}
+
+/// Mail the result of a routed file to the project's intake recipients
+pub(crate) fn notify_project(project: &Path, path: &Path) -> Result<(), String> {
+    let recipients = load_project_settings(&project.to_string_lossy()).intake_notify_to;
+    if recipients.is_empty() {
+        return Ok(());
+    }
+    let smtp = smtp_settings().ok_or_else(|| "メール送信設定（SMTP）がありません".to_string())?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let project_name = project
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let result = load_result_data(&path.to_string_lossy())
+        .map(|data| data.result)
+        .unwrap_or_default();
+    send_mail(
+        &smtp,
+        &recipients,
+        &format!("【書類チェック】受付 {} {}", project_name, name),
+        &format!("{}\n\n{}", path.display(), result),
+        &[],
+    )
+}
+
+/// Analyze a printed or scanned PDF: routed files are moved into their
+/// project first, the others after the analysis into the target project
+pub(crate) async fn process_intake_file(app: AppHandle, path: PathBuf) {
+    let ready = {
+        let path = path.clone();
+        tasks::run_blocking("intake", "受付ファイル待機", move || wait_until_stable(&path)).await
+    };
+    if !matches!(ready, Ok(true)) || !path.exists() {
+        return;
+    }
+    let accepted = {
+        let (app, path) = (app.clone(), path.clone());
+        tasks::run_blocking("intake", "受付ファイル照合", move || accept_new_file(&app, &path)).await
+    };
+    if matches!(accepted, Ok(false)) {
+        // Same content as an earlier file; left in the intake folder
+        return;
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    emit_log(&app, &tr("intake.received", &[&name]), "info");
+
+    let settings = load_settings().intake;
+    let route = route_for(&settings, &path).cloned();
+    let path = match &route {
+        Some(route) => {
+            let project = PathBuf::from(&route.project_folder);
+            let moved = tasks::run_blocking("intake", &name, move || move_into_project(&path, &project))
+                .await
+                .and_then(|r| r);
+            match moved {
+                Ok(destination) => {
+                    emit_log(
+                        &app,
+                        &tr("intake.routed", &[&name, &route.project_folder]),
+                        "info",
+                    );
+                    destination
+                }
+                Err(e) => {
+                    emit_log(&app, &e, "error");
+                    return;
+                }
+            }
+        }
+        None => path,
+    };
+    let path_str = path.to_string_lossy().to_string();
+    let analyzed = analyze_pdfs(
+        app.clone(),
+        vec![path_str],
+        "single".to_string(),
+        None,
+        settings.preset.clone(),
+        None,
+        None,
+    )
+    .await;
+    if analyzed.is_err() {
+        // Left in the intake (or routed project) folder so it can be
+        // re-analyzed from there
+        release_file(&path);
+        return;
+    }
+
+    if let Some(route) = route {
+        // A draft is mailed once it is approved (see `approval`)
+        let notified = tasks::run_blocking("intake", &name, move || {
+            let path_str = path.to_string_lossy().to_string();
+            if is_held(&path_str) {
+                return notify_on_approval(&path_str).map(|_| false);
+            }
+            notify_project(Path::new(&route.project_folder), &path).map(|_| true)
+        })
+        .await
+        .and_then(|r| r);
+        match notified {
+            Ok(true) => {}
+            Ok(false) => emit_log(&app, &tr("intake.notify_on_approval", &[&name]), "info"),
+            Err(e) => emit_log(&app, &tr("intake.notify_failed", &[&name, &e]), "warn"),
+        }
+    } else if let Some(project) = settings.target_project {
+        let moved = tasks::run_blocking("intake", &name, move || {
+            move_into_project(&path, Path::new(&project))
+        })
+        .await
+        .and_then(|r| r);
+        match moved {
+            Ok(destination) => emit_log(
+                &app,
+                &tr("intake.moved", &[&name, &destination.display()]),
+                "success",
+            ),
+            Err(e) => emit_log(&app, &e, "error"),
+        }
+    }
+}
+
+pub fn stop_intake_watcher() {
+    if let Ok(mut handle) = INTAKE_WATCHER.lock() {
+        *handle = None;
+    }
+}
+
+/// Create the intake folder with the subfolders of the routes and watch it
+/// (hidden folders such as the ZIP quarantine are skipped)
+pub(crate) fn start_intake_watcher(app: AppHandle) -> Result<(), String> {
+    stop_intake_watcher();
+    let settings = load_settings().intake;
+    let folder = intake_folder(&settings);
+    fs::create_dir_all(&folder).map_err(|e| format!("受付フォルダを作成できません: {}", e))?;
+    for subfolder in settings.routes.iter().filter_map(|r| r.subfolder.as_deref()) {
+        if !subfolder.is_empty() {
+            let _ = fs::create_dir_all(folder.join(subfolder));
+        }
+    }
+
+    let (tx, mut rx) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&folder, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+    *INTAKE_WATCHER.lock().map_err(|e| e.to_string())? = Some(watcher);
+
+    let label = folder.to_string_lossy().to_string();
+    tasks::spawn("intake", &label, async move {
+        while let Some(event) = rx.recv().await {
+            if let EventKind::Create(_) = event.kind {
+                // Files released from a ZIP are processed by the archive intake
+                for path in event
+                    .paths
+                    .into_iter()
+                    .filter(|p| !in_hidden_folder(&folder, p) && !is_released(p))
+                {
+                    let app = app.clone();
+                    if is_pdf(&path) {
+                        tasks::spawn("intake", &path.to_string_lossy(), process_intake_file(app, path));
+                    } else if is_zip(&path) {
+                        tasks::spawn("intake", &path.to_string_lossy(), process_intake_archive(app, path));
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+fn intake_status(settings: &IntakeSettings) -> IntakeStatus {
+    let folder = intake_folder(settings).to_string_lossy().to_string();
+    IntakeStatus {
+        enabled: settings.enabled,
+        printer_guidance: tr("intake.printer_guidance", &[&folder]),
+        folder,
+        target_project: settings.target_project.clone(),
+        preset: settings.preset.clone(),
+        routes: settings.routes.clone(),
+    }
+}
+
+/// 受付フォルダ（PDFプリンタの保存先）の設定
+#[tauri::command]
+pub fn get_intake_status() -> IntakeStatus {
+    intake_status(&load_settings().intake)
+}
+
+/// 受付フォルダを設定して監視を開始/停止
+#[tauri::command]
+pub fn set_intake(app: AppHandle, intake: IntakeSettings) -> Result<IntakeStatus, String> {
+    if let Some(project) = &intake.target_project {
+        if !Path::new(project).is_dir() {
+            return Err(tr("intake.project_missing", &[project]));
+        }
+    }
+    for route in &intake.routes {
+        let empty = |s: &Option<String>| s.as_deref().unwrap_or("").trim().is_empty();
+        if empty(&route.prefix) && empty(&route.subfolder) {
+            return Err(tr("intake.route_empty", &[&route.project_folder]));
+        }
+        if !Path::new(&route.project_folder).is_dir() {
+            return Err(tr("intake.project_missing", &[&route.project_folder]));
+        }
+    }
+    let mut settings = load_settings();
+    settings.intake = intake;
+    save_settings(&settings)?;
+
+    if settings.intake.enabled {
+        start_intake_watcher(app)?;
+    } else {
+        stop_intake_watcher();
+    }
+    Ok(intake_status(&settings.intake))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn printed_file_moves_with_sidecar_without_overwriting() {
+        let dir = create_temp_dir(".shoruichecker_test_intake").expect("create dir");
+        let intake = dir.join("受付");
+        let project = dir.join("○○工事");
+        fs::create_dir_all(&intake).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("請求書.pdf"), b"%PDF old").unwrap();
+        let printed = intake.join("請求書.pdf");
+        fs::write(&printed, b"%PDF new").unwrap();
+        fs::write(sidecar_path(&printed.to_string_lossy()), b"{}").unwrap();
+
+        let moved = move_into_project(&printed, &project).expect("move");
+        assert_eq!(moved, project.join("請求書_2.pdf"));
+        assert_eq!(fs::read(&moved).unwrap(), b"%PDF new");
+        assert!(project.join("請求書_2.shorui.json").exists());
+        assert!(!printed.exists());
+        assert_eq!(fs::read(project.join("請求書.pdf")).unwrap(), b"%PDF old");
+
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn scanner_files_are_routed_by_prefix_and_subfolder() {
+        let intake = PathBuf::from("C:/scan");
+        let route = |prefix: Option<&str>, subfolder: Option<&str>, project: &str| IntakeRoute {
+            prefix: prefix.map(str::to_string),
+            subfolder: subfolder.map(str::to_string),
+            project_folder: project.to_string(),
+        };
+        let settings = IntakeSettings {
+            folder: Some("C:/scan".to_string()),
+            routes: vec![
+                route(Some("A01_"), Some("山田"), "C:/projects/○○線"),
+                route(Some("a01_"), None, "C:/projects/△△橋"),
+                route(None, Some("佐藤"), "C:/projects/□□川"),
+                route(None, None, "C:/projects/未使用"),
+            ],
+            ..Default::default()
+        };
+        let project = |path: PathBuf| route_for(&settings, &path).map(|r| r.project_folder.as_str());
+        assert_eq!(project(intake.join("山田").join("A01_請求書.pdf")), Some("C:/projects/○○線"));
+        assert_eq!(project(intake.join("A01_請求書.pdf")), Some("C:/projects/△△橋"));
+        assert_eq!(project(intake.join("佐藤").join("2026").join("scan.pdf")), Some("C:/projects/□□川"));
+        assert_eq!(project(intake.join("scan.pdf")), None);
+
+        assert!(in_hidden_folder(&intake, &intake.join(".unpacking").join("a.pdf")));
+        assert!(!in_hidden_folder(&intake, &intake.join("佐藤").join("a.pdf")));
+    }
+}
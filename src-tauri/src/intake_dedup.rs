@@ -0,0 +1,166 @@
+//! Duplicate detection for incoming PDFs
+//!
+//! People often "just re-send" a scan, so the same PDF lands in the watch or
+//! intake folder again under another name (or the same one). The SHA-256 of
+//! every detected PDF is kept in the database across watch sessions; a file
+//! whose content was already processed is skipped without a notification or
+//! analysis. Contents whose analysis or expansion failed are forgotten again,
+//! so a re-sent copy is processed. `force_intake` forgets the hash and
+//! processes the file anyway.
+
+use std::path::Path;
+
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::archive::file_sha256;
+use crate::database::open_db;
+use crate::events::emit_log;
+use crate::intake::{intake_folder, process_intake_file};
+use crate::messages::tr;
+use crate::settings::load_settings;
+use crate::watcher::emit_pdf_detected;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// First detection of a file content
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ProcessedFile {
+    pub file_hash: String,
+    pub first_path: String,
+    pub first_seen_at: String,
+    /// Number of times the content arrived again
+    pub duplicates: u32,
+}
+
+/// Payload of the `intake-duplicate` event
+#[derive(Clone, Serialize)]
+pub struct DuplicateEvent {
+    pub path: String,
+    pub original: ProcessedFile,
+}
+
+fn load_processed(conn: &Connection, file_hash: &str) -> Result<Option<ProcessedFile>, String> {
+    conn.query_row(
+        "SELECT file_hash, first_path, first_seen_at, duplicates FROM processed_files WHERE file_hash = ?1",
+        params![file_hash],
+        |row| {
+            Ok(ProcessedFile {
+                file_hash: row.get(0)?,
+                first_path: row.get(1)?,
+                first_seen_at: row.get(2)?,
+                duplicates: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Record a detected content; returns its first detection if it is a duplicate
+pub fn register_content(conn: &Connection, file_hash: &str, path: &str) -> Result<Option<ProcessedFile>, String> {
+    if let Some(original) = load_processed(conn, file_hash)? {
+        conn.execute(
+            "UPDATE processed_files SET duplicates = duplicates + 1, last_path = ?2, last_seen_at = ?3
+             WHERE file_hash = ?1",
+            params![file_hash, path, Local::now().format(TIMESTAMP_FORMAT).to_string()],
+        )
+        .map_err(|e| e.to_string())?;
+        return Ok(Some(original));
+    }
+    let now = Local::now().format(TIMESTAMP_FORMAT).to_string();
+    conn.execute(
+        "INSERT INTO processed_files (file_hash, first_path, first_seen_at, last_path, last_seen_at, duplicates)
+         VALUES (?1, ?2, ?3, ?2, ?3, 0)",
+        params![file_hash, path, now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(None)
+}
+
+/// Forget a content so it is processed again when it arrives
+pub fn forget_content(conn: &Connection, file_hash: &str) -> Result<bool, String> {
+    conn.execute("DELETE FROM processed_files WHERE file_hash = ?1", params![file_hash])
+        .map(|n| n > 0)
+        .map_err(|e| e.to_string())
+}
+
+/// Whether a newly arrived file should be processed
+///
+/// Duplicates are logged and reported with an `intake-duplicate` event (the
+/// UI offers `force_intake` from it). Errors while hashing or recording never
+/// block a file.
+pub fn accept_new_file(app: &AppHandle, path: &Path) -> bool {
+    let Ok(hash) = file_sha256(path) else {
+        return true;
+    };
+    let path_str = path.to_string_lossy().to_string();
+    let original = match open_db().and_then(|conn| register_content(&conn, &hash, &path_str)) {
+        Ok(Some(original)) => original,
+        _ => return true,
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    emit_log(
+        app,
+        &tr("intake.duplicate", &[&name, &original.first_path, &original.first_seen_at]),
+        "info",
+    );
+    let _ = app.emit("intake-duplicate", DuplicateEvent { path: path_str, original });
+    false
+}
+
+/// Forget the content of a file after its processing failed
+pub fn release_file(path: &Path) {
+    if let Ok(hash) = file_sha256(path) {
+        let _ = open_db().and_then(|conn| forget_content(&conn, &hash));
+    }
+}
+
+/// 重複として取り込まれなかったPDFを強制的に解析（記録した内容を忘れて処理し直す）
+#[tauri::command]
+pub async fn force_intake(app: AppHandle, path: String) -> Result<(), String> {
+    let file = Path::new(&path).to_path_buf();
+    if !file.is_file() {
+        return Err(format!("ファイルが見つかりません: {}", path));
+    }
+    let hash = file_sha256(&file).map_err(|e| e.to_string())?;
+    forget_content(&open_db()?, &hash)?;
+    if file.parent() == Some(intake_folder(&load_settings().intake).as_path()) {
+        process_intake_file(app, file).await;
+    } else if accept_new_file(&app, &file) {
+        emit_pdf_detected(&app, &file);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    #[test]
+    fn same_content_is_a_duplicate_until_forgotten() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+
+        assert_eq!(register_content(&conn, "abc", "C:/watch/scan.pdf").unwrap(), None);
+        let original = register_content(&conn, "abc", "C:/watch/scan (1).pdf")
+            .unwrap()
+            .expect("duplicate");
+        assert_eq!(original.first_path, "C:/watch/scan.pdf");
+        assert_eq!(original.duplicates, 0);
+        assert_eq!(
+            register_content(&conn, "abc", "C:/watch/scan (2).pdf").unwrap().map(|o| o.duplicates),
+            Some(1)
+        );
+        assert_eq!(register_content(&conn, "def", "C:/watch/other.pdf").unwrap(), None);
+
+        assert!(forget_content(&conn, "abc").unwrap());
+        assert_eq!(register_content(&conn, "abc", "C:/watch/scan (3).pdf").unwrap(), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! Unified view of background work
+//!
+//! Analysis batches, registered background tasks (guideline generation,
+//! intake, scheduler…) and the code review watcher are listed as jobs with a
+//! common ID scheme so the UI can show and cancel them in one place:
+//! `batch:<job ID>`, `task:<task ID>` and `code_review`.
+
+use serde::Serialize;
+
+use crate::batch::{request_abort, running_batches, BatchStatus};
+use crate::code_review::{running_code_watcher, stop_code_watcher};
+use crate::messages::tr;
+use crate::processes::kill_children_in_scope;
+use crate::tasks::{cancel_task, running_tasks, TaskInfo};
+
+/// Tasks of this kind belong to an analysis batch and are shown through it
+const BATCH_TASK_KIND: &str = "analysis";
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    /// Waiting for a worker thread
+    Queued,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct JobProgress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Job {
+    pub id: String,
+    /// Subsystem, e.g. "analysis", "guidelines", "code_review"
+    pub kind: String,
+    pub label: String,
+    pub status: JobStatus,
+    pub started_at: Option<String>,
+    pub progress: Option<JobProgress>,
+    pub cancellable: bool,
+}
+
+fn batch_job(batch: &BatchStatus) -> Job {
+    Job {
+        id: format!("batch:{}", batch.batch_id),
+        kind: BATCH_TASK_KIND.to_string(),
+        label: tr("jobs.batch_label", &[&batch.mode, &batch.total]),
+        status: JobStatus::Running,
+        started_at: Some(batch.started_at.clone()),
+        progress: Some(JobProgress {
+            completed: batch.completed,
+            total: batch.total,
+        }),
+        cancellable: !batch.aborted,
+    }
+}
+
+fn task_job(task: &TaskInfo) -> Job {
+    Job {
+        id: format!("task:{}", task.id),
+        kind: task.kind.clone(),
+        label: task.label.clone(),
+        status: if task.running { JobStatus::Running } else { JobStatus::Queued },
+        started_at: Some(task.started_at.clone()),
+        progress: None,
+        // Blocking tasks are cancelled by killing their CLI processes
+        cancellable: true,
+    }
+}
+
+/// All jobs: batches, then other tasks (oldest first), then the code watcher
+pub fn collect_jobs(batches: &[BatchStatus], tasks: &[TaskInfo], code_watcher: Option<&str>) -> Vec<Job> {
+    let mut jobs: Vec<Job> = batches.iter().map(batch_job).collect();
+    jobs.extend(
+        tasks
+            .iter()
+            .filter(|t| t.kind != BATCH_TASK_KIND)
+            .map(task_job),
+    );
+    if let Some(folder) = code_watcher {
+        jobs.push(Job {
+            id: "code_review".to_string(),
+            kind: "code_review".to_string(),
+            label: tr("jobs.code_review_label", &[&folder]),
+            status: JobStatus::Running,
+            started_at: None,
+            progress: None,
+            cancellable: true,
+        });
+    }
+    jobs
+}
+
+/// Cancel a job by its ID
+pub fn cancel(job_id: &str) -> Result<(), String> {
+    let not_found = || tr("jobs.not_found", &[&job_id]);
+    if let Some(batch_id) = job_id.strip_prefix("batch:") {
+        if !request_abort(batch_id) {
+            return Err(not_found());
+        }
+        kill_children_in_scope(batch_id);
+        return Ok(());
+    }
+    if let Some(task_id) = job_id.strip_prefix("task:") {
+        let id: u64 = task_id.parse().map_err(|_| not_found())?;
+        return if cancel_task(id) {
+            Ok(())
+        } else {
+            Err(tr("jobs.not_cancellable", &[&job_id]))
+        };
+    }
+    if job_id == "code_review" {
+        return stop_code_watcher();
+    }
+    Err(not_found())
+}
+
+/// 実行中・待機中のバックグラウンド処理（解析・ガイドライン生成・コードレビューなど）
+#[tauri::command]
+pub fn get_jobs() -> Vec<Job> {
+    collect_jobs(
+        &running_batches(),
+        &running_tasks(),
+        running_code_watcher().as_deref(),
+    )
+}
+
+/// バックグラウンド処理を中止（ID は get_jobs の id）
+#[tauri::command]
+pub fn cancel_job(job_id: String) -> Result<(), String> {
+    cancel(&job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: u64, kind: &str, running: bool) -> TaskInfo {
+        TaskInfo {
+            id,
+            kind: kind.to_string(),
+            label: format!("{}-{}", kind, id),
+            started_at: "2026-03-10 09:00:00".to_string(),
+            abortable: false,
+            running,
+        }
+    }
+
+    #[test]
+    fn batches_replace_their_worker_tasks() {
+        let batches = vec![BatchStatus {
+            batch_id: "job1".to_string(),
+            mode: "single".to_string(),
+            total: 4,
+            completed: 1,
+            started_at: "2026-03-10 09:00:00".to_string(),
+            aborted: false,
+        }];
+        let tasks = vec![
+            task(1, "analysis", true),
+            task(2, "analysis", false),
+            task(3, "guidelines", false),
+        ];
+        let jobs = collect_jobs(&batches, &tasks, Some("C:/repo"));
+        let ids: Vec<&str> = jobs.iter().map(|j| j.id.as_str()).collect();
+        assert_eq!(ids, vec!["batch:job1", "task:3", "code_review"]);
+        assert_eq!(jobs[0].progress, Some(JobProgress { completed: 1, total: 4 }));
+        assert_eq!(jobs[1].status, JobStatus::Queued);
+    }
+
+    #[test]
+    fn unknown_jobs_cannot_be_cancelled() {
+        assert!(cancel("batch:missing").is_err());
+        assert!(cancel("task:abc").is_err());
+        assert!(cancel("other").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! Secrets in the OS keychain
+//!
+//! API keys, passwords, tokens and the encryption key are stored under the
+//! `ShoruiChecker` service, one entry per secret, so they never end up in
+//! settings.json.
+
+const SERVICE: &str = "ShoruiChecker";
+
+fn entry(user: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, user).map_err(|e| format!("キーチェーンにアクセスできません: {}", e))
+}
+
+/// Stored secret (None when there is no entry)
+pub fn load_secret(user: &str) -> Result<Option<String>, String> {
+    match entry(user)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("キーチェーンエラー: {}", e)),
+    }
+}
+
+/// Stored secret, None when missing, blank or the keychain is unavailable
+pub fn read_secret(user: &str) -> Option<String> {
+    load_secret(user)
+        .ok()
+        .flatten()
+        .filter(|s| !s.trim().is_empty())
+}
+
+pub fn store_secret(user: &str, secret: &str) -> Result<(), String> {
+    entry(user)?
+        .set_password(secret)
+        .map_err(|e| format!("キーチェーンへの保存エラー: {}", e))
+}
+
+/// Remove a secret; a missing entry is not an error
+pub fn delete_secret(user: &str) -> Result<(), String> {
+    match entry(user)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("キーチェーンエラー: {}", e)),
+    }
+}
+
+/// Store a secret, or remove it when None or blank
+pub fn replace_secret(user: &str, secret: Option<&str>) -> Result<(), String> {
+    match secret.filter(|s| !s.trim().is_empty()) {
+        Some(secret) => store_secret(user, secret),
+        None => delete_secret(user),
+    }
+}
@@ -2,44 +2,143 @@ use std::thread;
 use std::time::Duration;
 
 
+mod access;
+mod ai_provider;
 mod analysis;
+mod analysis_queue;
+mod approval;
+mod archive;
+mod assignments;
+mod audit;
+mod batch;
+mod classify;
+mod claude_api;
+mod cli_setup;
+mod clipboard;
 mod code_review;
+mod consensus;
+mod database;
+mod diagnostics;
+mod doctor;
+mod document_fields;
+mod embed_upgrade;
+mod encryption;
 mod events;
+mod export;
+mod expected_values;
+mod expiry;
+mod file_actions;
+mod file_association;
+mod file_lock;
+mod freshness;
 mod error;
 mod gemini;
+mod gemini_api;
 mod gemini_cli;
+mod golden;
+mod guideline_dry_run;
 mod guidelines;
 mod history;
+mod history_query;
+mod history_store;
+mod hooks;
+mod intake;
+mod intake_dedup;
+mod jobs;
+mod keychain;
+mod mail;
+mod mail_intake;
+mod messages;
+mod mock_backend;
+mod notes;
+mod openai_compat;
 mod pdf_embed;
+mod pdf_text;
+mod postprocess;
+mod processes;
+mod project_compare;
+mod project_init;
+mod project_settings;
+mod rag;
+mod readiness;
+mod record_link;
+mod recovery;
+mod report;
+mod result_cache;
+mod result_store;
+mod retry;
+mod review_digest;
+mod review_metrics;
+mod scheduler;
+mod seal;
 mod settings;
+mod shutdown;
+mod spec_clauses;
+mod storage;
+mod structured_report;
+mod survey;
+mod tasks;
+mod traffic_guard;
+mod usage;
 mod watcher;
+mod workspace_review;
+mod zip_intake;
 
 #[cfg(target_os = "windows")]
 pub(crate) const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 pub use analysis::analyze_headless;
+pub use doctor::run_doctor_cli;
+pub use golden::run_golden_cli;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     gui_shell::install_plugins(tauri::Builder::default())
         .setup(|app| {
             let _tray = gui_shell::setup_tray(&app.handle())?;
+            events::set_app_handle(app.handle());
+
+            // Clean up after a previous crash and report interrupted analyses
+            let app_handle = app.handle().clone();
+            tasks::spawn_blocking("startup", "復旧処理", move || {
+                thread::sleep(Duration::from_secs(1));
+                recovery::recover_on_startup(&app_handle);
+                cli_setup::check_cli_version_on_startup(&app_handle);
+            });
+
+            // Periodic background tasks (guideline regeneration etc.)
+            scheduler::start_scheduler(app.handle().clone());
 
             // Start watcher if folder is configured
             let settings = settings::load_settings();
             if let Some(folder) = settings.watch_folder.clone() {
                 let app_handle = app.handle().clone();
-                thread::spawn(move || {
+                tasks::spawn_blocking("startup", "フォルダ監視開始", move || {
                     thread::sleep(Duration::from_secs(1));
                     let _ = watcher::start_watcher(app_handle, &folder);
                 });
             }
 
+            // Intake folder for the print-to-PDF driver
+            if settings.intake.enabled {
+                let app_handle = app.handle().clone();
+                tasks::spawn_blocking("startup", "受付フォルダ監視開始", move || {
+                    thread::sleep(Duration::from_secs(1));
+                    let _ = intake::start_intake_watcher(app_handle);
+                });
+            }
+
+
+            // Outlook inbox polled for PDF attachments
+            if settings.mail_intake.enabled {
+                mail_intake::start_mail_poller(app.handle().clone());
+            }
+
             // Start code watcher if enabled and folder is configured
             if settings.code_review_enabled {
                 if let Some(folder) = settings.code_watch_folder {
                     let app_handle = app.handle().clone();
-                    thread::spawn(move || {
+                    tasks::spawn_blocking("startup", "コード監視開始", move || {
                         thread::sleep(Duration::from_secs(2));
                         let _ = code_review::start_code_watcher(app_handle, &folder);
                     });
@@ -50,24 +149,169 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             analysis::analyze_pdfs,
+            analysis_queue::enqueue_analysis,
+            analysis_queue::get_queue,
+            analysis_queue::reorder_queue,
+            analysis_queue::remove_from_queue,
+            result_cache::clear_result_cache,
+            clipboard::analyze_clipboard_image,
             watcher::get_startup_file,
+            file_association::get_startup_report,
+            file_association::open_shorui_report,
+            file_association::export_shorui_report,
+            file_association::get_file_association_status,
+            file_association::register_file_associations,
+            file_association::unregister_file_associations,
             watcher::get_watch_folder,
             watcher::set_watch_folder,
             watcher::stop_watching,
+            watcher::get_watcher_events,
+            intake::get_intake_status,
+            intake::set_intake,
+            intake_dedup::force_intake,
+            zip_intake::unlock_archive,
+            mail_intake::get_mail_intake,
+            mail_intake::set_mail_intake,
+            mail_intake::start_mail_sign_in,
+            mail_intake::sign_out_mail,
+            mail_intake::poll_mail_now,
             gemini::open_gemini_auth,
             gemini::check_gemini_auth,
             settings::get_model,
             settings::set_model,
+            settings::get_temp_root,
+            settings::set_temp_root,
+            settings::get_gemini_timeout_secs,
+            settings::set_gemini_timeout_secs,
+            settings::get_max_parallel_analyses,
+            settings::set_max_parallel_analyses,
+            consensus::get_consensus_settings,
+            consensus::set_consensus_settings,
+            retry::get_retry_settings,
+            retry::set_retry_settings,
+            usage::get_usage_stats,
+            gemini_cli::get_output_noise_patterns,
+            gemini_cli::set_output_noise_patterns,
+            settings::get_text_mode,
+            settings::set_text_mode,
+            settings::get_result_storage,
+            settings::set_result_storage,
+            ai_provider::get_provider,
+            ai_provider::set_provider,
+            settings::get_language,
+            settings::set_language,
+            encryption::get_encryption_status,
+            encryption::set_encrypt_at_rest,
+            settings::get_smtp_settings,
+            settings::set_smtp_settings,
+            settings::get_project_root_strategy,
+            settings::set_project_root_strategy,
+            settings::get_presets,
+            settings::save_preset,
+            settings::delete_preset,
+            hooks::get_analysis_hooks,
+            hooks::set_analysis_hooks,
             history::get_all_history,
+            history::get_project_summary,
+            history::get_all_project_summaries,
+            history::get_file_analyses,
+            history_query::query_findings,
+            history_query::query_history,
+            expiry::get_expiring_documents,
+            document_fields::query_document_fields,
+            document_fields::list_document_parties,
+            approval::get_pending_results,
+            approval::approve_result,
+            archive::export_embedded_archive,
+            archive::reimport_embedded_archive,
+            embed_upgrade::upgrade_embedded_results,
+            freshness::verify_result_freshness,
+            golden::run_golden_check,
+            freshness::diff_results,
+            seal::extract_seal_impressions,
+            file_actions::reveal_in_explorer,
+            file_actions::open_file,
             pdf_embed::embed_pdf_result,
             pdf_embed::read_pdf_result,
+            project_settings::get_project_settings,
+            project_settings::set_project_settings,
+            project_settings::list_reference_documents,
+            spec_clauses::register_specification,
+            project_settings::add_reference_document,
+            project_settings::remove_reference_document,
+            project_settings::set_document_type_rules,
+            project_settings::set_anchor_priority,
+            project_settings::set_required_documents,
+            postprocess::set_output_rules,
+            postprocess::preview_output_rules,
+            classify::get_document_metadata,
+            notes::add_file_note,
+            notes::get_file_notes,
+            notes::delete_file_note,
+            project_compare::compare_projects,
+            rag::rebuild_rag_index,
+            rag::semantic_search,
+            report::summarize_project,
+            report::generate_monthly_report,
+            readiness::get_project_readiness,
+            report::export_check_sheet,
+            record_link::resolve_record_link,
+            record_link::get_startup_record_link,
+            export::export_issues_csv,
+            audit::export_audit_log,
+            diagnostics::list_diagnostics,
+            diagnostics::export_diagnostics,
+            assignments::assign_finding,
+            assignments::complete_finding,
+            assignments::get_my_open_items,
+            doctor::diagnose_environment,
+            access::get_access_status,
+            storage::get_storage_status,
+            tasks::get_background_tasks,
+            jobs::get_jobs,
+            jobs::cancel_job,
+            processes::get_child_processes,
+            events::get_recent_logs,
+            processes::kill_all_background_work,
+            batch::abort_batch,
+            batch::cancel_analysis,
+            cli_setup::get_gemini_cli_status,
+            cli_setup::install_gemini_cli,
+            cli_setup::update_gemini_cli,
+            recovery::get_interrupted_analyses,
+            recovery::resume_interrupted_analysis,
+            recovery::discard_interrupted_analyses,
             guidelines::generate_guidelines,
+            guidelines::get_guideline_stats,
+            history_store::get_history_backend,
+            history_store::set_history_backend,
+            history_store::upload_local_history,
+            guideline_dry_run::dry_run_guidelines,
             code_review::get_code_watch_folder,
             code_review::is_code_review_enabled,
             code_review::set_code_watch_folder,
             code_review::set_code_review_enabled,
-            code_review::stop_code_watching
+            code_review::stop_code_watching,
+            code_review::get_review_context,
+            workspace_review::review_workspace,
+            review_metrics::get_review_stats,
+            review_digest::get_review_digest_settings,
+            review_digest::set_review_digest_settings,
+            review_digest::send_review_digest_now
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            // Finish embeds and history writes before exiting (tray 終了 etc.)
+            tauri::RunEvent::ExitRequested { api, .. } => {
+                if shutdown::begin_shutdown(app) {
+                    api.prevent_exit();
+                }
+            }
+            // Do not leave CLI processes running after the app is closed
+            tauri::RunEvent::Exit => {
+                processes::kill_all_children();
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,123 @@
+//! Outgoing mail for reports
+//!
+//! Uses the SMTP server configured in the app settings; its password is kept
+//! in the OS keychain, not in settings.json.
+
+use std::fs;
+use std::path::Path;
+
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::keychain::{read_secret, replace_secret};
+use crate::settings::{load_settings, save_settings, SmtpSettings};
+
+const KEYRING_USER: &str = "smtp-password";
+
+/// Store the SMTP password (None or empty removes it)
+pub fn store_smtp_password(password: Option<&str>) -> Result<(), String> {
+    replace_secret(KEYRING_USER, password)
+}
+
+/// The configured SMTP server with its password from the keychain
+///
+/// A password still in settings.json (older versions) is moved to the
+/// keychain first.
+pub fn smtp_settings() -> Option<SmtpSettings> {
+    let mut smtp = load_settings().smtp?;
+    match read_secret(KEYRING_USER) {
+        Some(password) => smtp.password = password,
+        None if !smtp.password.is_empty() => {
+            // Saving drops the password from settings.json
+            if store_smtp_password(Some(&smtp.password)).is_ok() {
+                let _ = save_settings(&load_settings());
+            }
+        }
+        None => {}
+    }
+    Some(smtp)
+}
+
+/// Send a plain text mail with optional file attachments
+pub fn send_mail(
+    smtp: &SmtpSettings,
+    to: &[String],
+    subject: &str,
+    body: &str,
+    attachments: &[&Path],
+) -> Result<(), String> {
+    if to.is_empty() {
+        return Err("宛先がありません".to_string());
+    }
+
+    let mut builder = Message::builder()
+        .from(smtp.from.parse().map_err(|e| format!("送信元アドレスが不正です: {}", e))?)
+        .subject(subject);
+    for address in to {
+        builder = builder.to(address
+            .parse()
+            .map_err(|e| format!("宛先アドレスが不正です ({}): {}", address, e))?);
+    }
+
+    let mut multipart = MultiPart::mixed().singlepart(SinglePart::plain(body.to_string()));
+    for path in attachments {
+        let bytes = fs::read(path).map_err(|e| format!("添付ファイル読み込みエラー: {}", e))?;
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let content_type = if file_name.to_lowercase().ends_with(".pdf") {
+            ContentType::parse("application/pdf").map_err(|e| e.to_string())?
+        } else {
+            ContentType::TEXT_PLAIN
+        };
+        multipart = multipart.singlepart(Attachment::new(file_name).body(bytes, content_type));
+    }
+    let message = builder.multipart(multipart).map_err(|e| e.to_string())?;
+
+    let mut transport = SmtpTransport::starttls_relay(&smtp.host)
+        .map_err(|e| format!("SMTP接続エラー: {}", e))?
+        .port(smtp.port);
+    if !smtp.username.is_empty() {
+        transport =
+            transport.credentials(Credentials::new(smtp.username.clone(), smtp.password.clone()));
+    }
+    transport
+        .build()
+        .send(&message)
+        .map(|_| ())
+        .map_err(|e| format!("メール送信エラー: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_mail_requires_recipients() {
+        let smtp = SmtpSettings {
+            host: "localhost".to_string(),
+            port: 587,
+            username: String::new(),
+            password: String::new(),
+            from: "checker@example.com".to_string(),
+        };
+        assert!(send_mail(&smtp, &[], "件名", "本文", &[]).is_err());
+    }
+
+    #[test]
+    fn send_mail_rejects_invalid_address() {
+        let smtp = SmtpSettings {
+            host: "localhost".to_string(),
+            port: 587,
+            username: String::new(),
+            password: String::new(),
+            from: "not an address".to_string(),
+        };
+        let err = send_mail(&smtp, &["qa@example.com".to_string()], "件名", "本文", &[])
+            .unwrap_err();
+        assert!(err.contains("送信元"));
+    }
+}
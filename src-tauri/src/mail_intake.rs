@@ -0,0 +1,714 @@
+//! Outlook (Microsoft Graph) inbox monitoring for document intake
+//!
+//! When enabled, a designated mail folder is polled through the Graph API.
+//! PDF attachments of new mails are saved into the project folder chosen by
+//! the subject rules and analyzed; the mail (sender, subject, received time)
+//! is recorded on the resulting history entries. Sign-in uses the OAuth
+//! device code flow and the refresh token is kept in the OS keychain.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use base64::{engine::general_purpose, Engine as _};
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter};
+
+use crate::analysis::analyze_pdfs;
+use crate::events::emit_log;
+use crate::file_lock::write_atomic;
+use crate::history::{update_history, MailOrigin};
+use crate::intake::unique_destination;
+use crate::keychain::{delete_secret, read_secret, store_secret};
+use crate::messages::tr;
+use crate::project_settings::project_folder_for;
+use crate::settings::{get_settings_path, load_settings, save_settings};
+use crate::storage::ensure_space_for;
+use crate::tasks;
+
+const GRAPH_BASE: &str = "https://graph.microsoft.com/v1.0";
+const SCOPE: &str = "offline_access Mail.Read";
+const KEYRING_USER: &str = "graph-refresh-token";
+/// Processed message IDs remembered to skip mails seen in an earlier poll
+const MAX_PROCESSED_IDS: usize = 500;
+/// Messages fetched per Graph page
+const PAGE_SIZE: &str = "50";
+
+static POLLER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Subject pattern mapped to the project folder its attachments are saved to
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SubjectRule {
+    /// Regular expression matched against the subject (case-insensitive)
+    pub pattern: String,
+    pub project_folder: String,
+}
+
+/// Outlook intake configuration
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MailIntakeSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Application (client) ID registered in Entra ID
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    /// Mail folder as a path from the top (e.g. "Inbox/書類")
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    #[serde(default = "default_poll_minutes")]
+    pub poll_minutes: u32,
+    /// First matching rule wins
+    #[serde(default)]
+    pub rules: Vec<SubjectRule>,
+    /// Project folder for mails no rule matches (None: such mails are skipped)
+    #[serde(default)]
+    pub default_project: Option<String>,
+    /// Preset (ID or name) used for the analysis
+    #[serde(default)]
+    pub preset: Option<String>,
+}
+
+impl Default for MailIntakeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            client_id: String::new(),
+            tenant: default_tenant(),
+            folder: default_folder(),
+            poll_minutes: default_poll_minutes(),
+            rules: Vec::new(),
+            default_project: None,
+            preset: None,
+        }
+    }
+}
+
+fn default_tenant() -> String {
+    "common".to_string()
+}
+
+fn default_folder() -> String {
+    "Inbox".to_string()
+}
+
+fn default_poll_minutes() -> u32 {
+    10
+}
+
+/// Progress of the polling between runs
+#[derive(Clone, Serialize, Deserialize, Default)]
+struct MailIntakeState {
+    /// Received time of the newest processed mail (RFC 3339)
+    #[serde(default)]
+    last_received: Option<String>,
+    #[serde(default)]
+    processed_ids: VecDeque<String>,
+}
+
+impl MailIntakeState {
+    fn is_processed(&self, id: &str) -> bool {
+        self.processed_ids.iter().any(|p| p == id)
+    }
+
+    fn mark_processed(&mut self, id: &str, received_at: &str) {
+        if !self.is_processed(id) {
+            self.processed_ids.push_back(id.to_string());
+        }
+        while self.processed_ids.len() > MAX_PROCESSED_IDS {
+            self.processed_ids.pop_front();
+        }
+        if self.last_received.as_deref().is_none_or(|last| received_at > last) {
+            self.last_received = Some(received_at.to_string());
+        }
+    }
+}
+
+/// Sign-in status shown in the settings screen
+#[derive(Clone, Serialize)]
+pub struct MailIntakeStatus {
+    pub settings: MailIntakeSettings,
+    pub signed_in: bool,
+    pub last_received: Option<String>,
+}
+
+/// Code the user enters at the verification page to sign in
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceCode {
+    pub user_code: String,
+    pub verification_uri: String,
+    pub message: String,
+    #[serde(skip_serializing)]
+    device_code: String,
+    #[serde(skip_serializing)]
+    interval: u64,
+    #[serde(skip_serializing)]
+    expires_in: u64,
+}
+
+/// A mail with PDF attachments
+#[derive(Clone, Debug)]
+pub struct MailMessage {
+    pub id: String,
+    pub subject: String,
+    pub from: String,
+    pub received_at: String,
+}
+
+fn state_path() -> PathBuf {
+    get_settings_path().with_file_name("mail_intake_state.json")
+}
+
+fn load_state() -> MailIntakeState {
+    fs::read_to_string(state_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &MailIntakeState) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    write_atomic(&state_path(), json)
+}
+
+fn stored_refresh_token() -> Option<String> {
+    read_secret(KEYRING_USER)
+}
+
+/// Project folder for a mail subject: first matching rule, then the default
+pub fn project_for_subject(settings: &MailIntakeSettings, subject: &str) -> Option<String> {
+    settings
+        .rules
+        .iter()
+        .find(|rule| {
+            RegexBuilder::new(&rule.pattern)
+                .case_insensitive(true)
+                .build()
+                .map(|re| re.is_match(subject))
+                .unwrap_or(false)
+        })
+        .map(|rule| rule.project_folder.clone())
+        .or_else(|| settings.default_project.clone())
+}
+
+/// File name safe to create on Windows
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if "\\/:*?\"<>|".contains(c) || c.is_control() { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+    if cleaned.is_empty() {
+        "attachment.pdf".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// PDF attachments (file name, bytes) of a Graph attachments response
+pub fn pdf_attachments(response: &Value) -> Vec<(String, Vec<u8>)> {
+    response["value"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|a| a["@odata.type"] == "#microsoft.graph.fileAttachment")
+        .filter(|a| {
+            let name = a["name"].as_str().unwrap_or_default().to_lowercase();
+            a["contentType"] == "application/pdf" || name.ends_with(".pdf")
+        })
+        .filter_map(|a| {
+            let bytes = general_purpose::STANDARD
+                .decode(a["contentBytes"].as_str()?)
+                .ok()?;
+            Some((sanitize_file_name(a["name"].as_str().unwrap_or_default()), bytes))
+        })
+        .collect()
+}
+
+fn parse_message(value: &Value) -> Option<MailMessage> {
+    let from = &value["from"]["emailAddress"];
+    let from = match (from["name"].as_str(), from["address"].as_str()) {
+        (Some(name), Some(address)) if !name.is_empty() => format!("{} <{}>", name, address),
+        (_, Some(address)) => address.to_string(),
+        _ => String::new(),
+    };
+    Some(MailMessage {
+        id: value["id"].as_str()?.to_string(),
+        subject: value["subject"].as_str().unwrap_or_default().to_string(),
+        from,
+        received_at: value["receivedDateTime"].as_str()?.to_string(),
+    })
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(60))
+        .build()
+}
+
+fn token_endpoint(settings: &MailIntakeSettings, path: &str) -> String {
+    format!(
+        "https://login.microsoftonline.com/{}/oauth2/v2.0/{}",
+        settings.tenant, path
+    )
+}
+
+/// Error message of a failed Graph/OAuth call
+fn http_error(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            tr("mail_intake.http_error", &[&code, &body.chars().take(300).collect::<String>()])
+        }
+        other => tr("mail_intake.http_error", &[&"-", &other]),
+    }
+}
+
+/// Access token from the stored refresh token (the rotated token is stored back)
+fn access_token(settings: &MailIntakeSettings) -> Result<String, String> {
+    let refresh = stored_refresh_token().ok_or_else(|| tr("mail_intake.not_signed_in", &[]))?;
+    let response: Value = agent()
+        .post(&token_endpoint(settings, "token"))
+        .send_form(&[
+            ("client_id", settings.client_id.as_str()),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh.as_str()),
+            ("scope", SCOPE),
+        ])
+        .map_err(http_error)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    if let Some(rotated) = response["refresh_token"].as_str() {
+        let _ = store_secret(KEYRING_USER, rotated);
+    }
+    response["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| tr("mail_intake.not_signed_in", &[]))
+}
+
+fn graph_get(token: &str, url: &str, query: &[(&str, &str)]) -> Result<Value, String> {
+    let mut request = agent()
+        .get(url)
+        .set("Authorization", &format!("Bearer {}", token));
+    for (key, value) in query {
+        request = request.query(key, value);
+    }
+    request.call().map_err(http_error)?.into_json().map_err(|e| e.to_string())
+}
+
+/// ID of a folder given as a path of display names ("Inbox" and the other
+/// well-known names are accepted for the first segment)
+fn resolve_folder_id(token: &str, folder: &str) -> Result<String, String> {
+    let mut parent: Option<String> = None;
+    for segment in folder.split('/').map(str::trim).filter(|s| !s.is_empty()) {
+        let url = match &parent {
+            None => format!("{}/me/mailFolders", GRAPH_BASE),
+            Some(id) => format!("{}/me/mailFolders/{}/childFolders", GRAPH_BASE, id),
+        };
+        let filter = format!("displayName eq '{}'", segment.replace('\'', "''"));
+        let found = graph_get(token, &url, &[("$filter", &filter), ("$select", "id")])?["value"][0]["id"]
+            .as_str()
+            .map(str::to_string);
+        parent = match (found, &parent) {
+            (Some(id), _) => Some(id),
+            // Well-known names (inbox, archive…) work regardless of the UI language
+            (None, None) => graph_get(
+                token,
+                &format!("{}/me/mailFolders/{}", GRAPH_BASE, segment.to_lowercase()),
+                &[],
+            )
+                .ok()
+                .and_then(|v| v["id"].as_str().map(str::to_string)),
+            (None, Some(_)) => None,
+        };
+        if parent.is_none() {
+            return Err(tr("mail_intake.folder_missing", &[&folder]));
+        }
+    }
+    parent.ok_or_else(|| tr("mail_intake.folder_missing", &[&folder]))
+}
+
+/// Mails with attachments received at or after `since`, oldest first
+pub fn select_new_messages(values: &[Value], since: Option<&str>) -> Vec<MailMessage> {
+    let mut messages: Vec<MailMessage> = values
+        .iter()
+        .filter(|v| v["hasAttachments"].as_bool() == Some(true))
+        .filter_map(parse_message)
+        .filter(|m| !since.is_some_and(|since| m.received_at.as_str() < since))
+        .collect();
+    messages.sort_by(|a, b| a.received_at.cmp(&b.received_at));
+    messages
+}
+
+/// Mails with attachments received since the last poll, oldest first
+///
+/// Graph rejects filtering on `hasAttachments` together with ordering by
+/// `receivedDateTime` on many mailboxes, so the folder is read newest first
+/// page by page (following `@odata.nextLink`) until a page holds only older
+/// mails, and filtered and sorted here.
+fn fetch_messages(token: &str, folder_id: &str, since: Option<&str>) -> Result<Vec<MailMessage>, String> {
+    let mut values = Vec::new();
+    let mut response = graph_get(
+        token,
+        &format!("{}/me/mailFolders/{}/messages", GRAPH_BASE, folder_id),
+        &[
+            ("$select", "id,subject,from,receivedDateTime,hasAttachments"),
+            ("$orderby", "receivedDateTime desc"),
+            ("$top", PAGE_SIZE),
+        ],
+    )?;
+    loop {
+        let page = response["value"].as_array().cloned().unwrap_or_default();
+        let reached_older = since.is_some_and(|since| {
+            page.iter()
+                .all(|v| v["receivedDateTime"].as_str().is_some_and(|r| r < since))
+        });
+        values.extend(page);
+        let next = response["@odata.nextLink"].as_str().map(str::to_string);
+        match next {
+            Some(next) if !reached_older => response = graph_get(token, &next, &[])?,
+            _ => break,
+        }
+    }
+    Ok(select_new_messages(&values, since))
+}
+
+/// Save the PDF attachments of a mail into the project folder
+fn save_attachments(token: &str, message: &MailMessage, project: &Path) -> Result<Vec<String>, String> {
+    let response = graph_get(
+        token,
+        &format!("{}/me/messages/{}/attachments", GRAPH_BASE, message.id),
+        &[],
+    )?;
+    fs::create_dir_all(project).map_err(|e| e.to_string())?;
+    let mut saved = Vec::new();
+    for (name, bytes) in pdf_attachments(&response) {
+        ensure_space_for(project, bytes.len() as u64)?;
+        let destination = unique_destination(project, &name);
+        fs::write(&destination, &bytes).map_err(|e| tr("mail_intake.save_error", &[&name, &e]))?;
+        saved.push(destination.to_string_lossy().to_string());
+    }
+    Ok(saved)
+}
+
+/// Record the mail on the history entries of the analyzed attachments
+fn record_mail_origin(paths: &[String], message: &MailMessage) {
+    let origin = MailOrigin {
+        message_id: message.id.clone(),
+        from: message.from.clone(),
+        subject: message.subject.clone(),
+        received_at: message.received_at.clone(),
+    };
+    for path in paths {
+        let _ = update_history(&project_folder_for(path), |history| {
+            if let Some(entry) = history.entries.iter_mut().rev().find(|e| &e.file_path == path) {
+                entry.mail = Some(origin.clone());
+            }
+        });
+    }
+}
+
+/// Fetch new mails, save their PDFs and analyze them
+pub async fn poll_once(app: &AppHandle) -> Result<usize, String> {
+    let settings = load_settings().mail_intake;
+    let fetch_settings = settings.clone();
+    let mut state = load_state();
+    let since = state.last_received.clone();
+    let (token, messages) = tasks::run_blocking("mail_intake", "メール取得", move || {
+        let token = access_token(&fetch_settings)?;
+        let folder_id = resolve_folder_id(&token, &fetch_settings.folder)?;
+        let messages = fetch_messages(&token, &folder_id, since.as_deref())?;
+        Ok::<_, String>((token, messages))
+    })
+    .await
+    .and_then(|r| r)?;
+
+    let mut saved_count = 0;
+    for message in messages.into_iter().filter(|m| !state.is_processed(&m.id)) {
+        let Some(project) = project_for_subject(&settings, &message.subject) else {
+            emit_log(app, &tr("mail_intake.no_rule", &[&message.subject]), "warn");
+            state.mark_processed(&message.id, &message.received_at);
+            continue;
+        };
+        let saved = {
+            let (token, label, message) = (token.clone(), message.subject.clone(), message.clone());
+            tasks::run_blocking("mail_intake", &label, move || {
+                save_attachments(&token, &message, Path::new(&project))
+            })
+            .await
+            .and_then(|r| r)
+        };
+        let saved = match saved {
+            Ok(saved) => saved,
+            Err(e) => {
+                // Not marked: retried on the next poll
+                emit_log(app, &e, "error");
+                break;
+            }
+        };
+        state.mark_processed(&message.id, &message.received_at);
+        save_state(&state)?;
+        if saved.is_empty() {
+            continue;
+        }
+        emit_log(
+            app,
+            &tr("mail_intake.received", &[&message.subject, &message.from, &saved.len()]),
+            "info",
+        );
+        saved_count += saved.len();
+        let analyzed = analyze_pdfs(
+            app.clone(),
+            saved.clone(),
+            "single".to_string(),
+            None,
+            settings.preset.clone(),
+            None,
+            None,
+        )
+        .await;
+        if analyzed.is_ok() {
+            record_mail_origin(&saved, &message);
+        }
+    }
+    save_state(&state)?;
+    Ok(saved_count)
+}
+
+/// Poll the mail folder at the configured interval while enabled
+pub(crate) fn start_mail_poller(app: AppHandle) {
+    if POLLER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tasks::spawn("mail_intake", "メール受付", async move {
+        loop {
+            let settings = load_settings().mail_intake;
+            if !settings.enabled {
+                break;
+            }
+            if let Err(e) = poll_once(&app).await {
+                emit_log(&app, &e, "error");
+            }
+            let minutes = u64::from(settings.poll_minutes.max(1));
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        }
+        POLLER_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
+
+fn mail_intake_status(settings: MailIntakeSettings) -> MailIntakeStatus {
+    MailIntakeStatus {
+        settings,
+        signed_in: stored_refresh_token().is_some(),
+        last_received: load_state().last_received,
+    }
+}
+
+/// Poll the token endpoint until the user has entered the code
+fn wait_for_sign_in(settings: &MailIntakeSettings, code: &DeviceCode) -> Result<(), String> {
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = code.interval.max(1);
+    while Instant::now() < deadline {
+        thread::sleep(Duration::from_secs(interval));
+        let response = agent()
+            .post(&token_endpoint(settings, "token"))
+            .send_form(&[
+                ("client_id", settings.client_id.as_str()),
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("device_code", code.device_code.as_str()),
+            ]);
+        let body: Value = match response {
+            Ok(r) => r.into_json().map_err(|e| e.to_string())?,
+            Err(ureq::Error::Status(_, r)) => r.into_json().map_err(|e| e.to_string())?,
+            Err(e) => return Err(e.to_string()),
+        };
+        if let Some(refresh) = body["refresh_token"].as_str() {
+            return store_secret(KEYRING_USER, refresh);
+        }
+        match body["error"].as_str() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += 5,
+            _ => {
+                return Err(tr(
+                    "mail_intake.sign_in_failed",
+                    &[&body["error_description"].as_str().unwrap_or_default()],
+                ))
+            }
+        }
+    }
+    Err(tr("mail_intake.sign_in_failed", &[&"timeout"]))
+}
+
+/// Outlookメール受付の設定と状態
+#[tauri::command]
+pub fn get_mail_intake() -> MailIntakeStatus {
+    mail_intake_status(load_settings().mail_intake)
+}
+
+/// Outlookメール受付を設定（有効ならポーリングを開始）
+#[tauri::command]
+pub fn set_mail_intake(app: AppHandle, mail_intake: MailIntakeSettings) -> Result<MailIntakeStatus, String> {
+    for rule in &mail_intake.rules {
+        RegexBuilder::new(&rule.pattern)
+            .build()
+            .map_err(|e| tr("mail_intake.invalid_rule", &[&rule.pattern, &e]))?;
+        if !Path::new(&rule.project_folder).is_dir() {
+            return Err(tr("intake.project_missing", &[&rule.project_folder]));
+        }
+    }
+    let mut settings = load_settings();
+    settings.mail_intake = mail_intake;
+    save_settings(&settings)?;
+    if settings.mail_intake.enabled {
+        start_mail_poller(app);
+    }
+    Ok(mail_intake_status(settings.mail_intake))
+}
+
+/// Microsoftアカウントのサインインを開始（完了時に mail-sign-in イベント）
+#[tauri::command]
+pub async fn start_mail_sign_in(app: AppHandle) -> Result<DeviceCode, String> {
+    let settings = load_settings().mail_intake;
+    if settings.client_id.trim().is_empty() {
+        return Err(tr("mail_intake.no_client_id", &[]));
+    }
+    let request_settings = settings.clone();
+    let code: DeviceCode = tasks::run_blocking("mail_intake", "サインイン", move || {
+        agent()
+            .post(&token_endpoint(&request_settings, "devicecode"))
+            .send_form(&[("client_id", request_settings.client_id.as_str()), ("scope", SCOPE)])
+            .map_err(http_error)?
+            .into_json::<DeviceCode>()
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .and_then(|r| r)?;
+
+    let pending = code.clone();
+    tasks::spawn_blocking("mail_intake", "サインイン待機", move || {
+        let result = wait_for_sign_in(&settings, &pending);
+        if let Err(e) = &result {
+            emit_log(&app, e, "error");
+        }
+        let _ = app.emit("mail-sign-in", json!({ "success": result.is_ok() }));
+        if result.is_ok() && settings.enabled {
+            start_mail_poller(app.clone());
+        }
+    });
+    Ok(code)
+}
+
+/// Microsoftアカウントからサインアウト
+#[tauri::command]
+pub fn sign_out_mail() -> Result<(), String> {
+    delete_secret(KEYRING_USER)
+}
+
+/// 今すぐメールを確認して添付PDFを取り込む（取り込んだ件数）
+#[tauri::command]
+pub async fn poll_mail_now(app: AppHandle) -> Result<usize, String> {
+    poll_once(&app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> MailIntakeSettings {
+        MailIntakeSettings {
+            rules: vec![
+                SubjectRule {
+                    pattern: r"○○線.*(工事|舗装)".to_string(),
+                    project_folder: "C:/projects/○○線".to_string(),
+                },
+                SubjectRule {
+                    pattern: "invoice".to_string(),
+                    project_folder: "C:/projects/請求".to_string(),
+                },
+            ],
+            default_project: Some("C:/projects/未分類".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn subject_rules_pick_the_first_match_and_fall_back() {
+        let settings = settings();
+        assert_eq!(
+            project_for_subject(&settings, "【○○線道路改良工事】施工計画書の送付").as_deref(),
+            Some("C:/projects/○○線")
+        );
+        assert_eq!(
+            project_for_subject(&settings, "FW: INVOICE 2024-04").as_deref(),
+            Some("C:/projects/請求")
+        );
+        assert_eq!(
+            project_for_subject(&settings, "打合せのご案内").as_deref(),
+            Some("C:/projects/未分類")
+        );
+        let strict = MailIntakeSettings { default_project: None, ..settings };
+        assert_eq!(project_for_subject(&strict, "打合せのご案内"), None);
+    }
+
+    #[test]
+    fn only_pdf_file_attachments_are_decoded() {
+        let pdf = general_purpose::STANDARD.encode(b"%PDF-1.7");
+        let response = json!({ "value": [
+            { "@odata.type": "#microsoft.graph.fileAttachment", "name": "見積:書.pdf",
+              "contentType": "application/octet-stream", "contentBytes": pdf },
+            { "@odata.type": "#microsoft.graph.fileAttachment", "name": "写真.jpg",
+              "contentType": "image/jpeg", "contentBytes": pdf },
+            { "@odata.type": "#microsoft.graph.itemAttachment", "name": "転送.pdf" },
+            { "@odata.type": "#microsoft.graph.fileAttachment", "name": "壊れ.pdf",
+              "contentType": "application/pdf", "contentBytes": "***" }
+        ]});
+        let attachments = pdf_attachments(&response);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].0, "見積_書.pdf");
+        assert_eq!(attachments[0].1, b"%PDF-1.7");
+    }
+
+    #[test]
+    fn state_skips_processed_mails_and_keeps_the_newest_time() {
+        let mut state = MailIntakeState::default();
+        state.mark_processed("a", "2024-04-02T09:00:00Z");
+        state.mark_processed("b", "2024-04-01T09:00:00Z");
+        state.mark_processed("a", "2024-04-02T09:00:00Z");
+        assert!(state.is_processed("a") && state.is_processed("b"));
+        assert_eq!(state.processed_ids.len(), 2);
+        assert_eq!(state.last_received.as_deref(), Some("2024-04-02T09:00:00Z"));
+
+        for i in 0..MAX_PROCESSED_IDS {
+            state.mark_processed(&i.to_string(), "2024-04-03T00:00:00Z");
+        }
+        assert_eq!(state.processed_ids.len(), MAX_PROCESSED_IDS);
+        assert!(!state.is_processed("a"));
+    }
+
+    #[test]
+    fn new_mails_with_attachments_are_selected_oldest_first() {
+        let mail = |id: &str, received: &str, attachments: bool| {
+            json!({ "id": id, "subject": id, "receivedDateTime": received, "hasAttachments": attachments,
+                    "from": { "emailAddress": { "name": "", "address": "a@example.jp" } } })
+        };
+        let values = vec![
+            mail("c", "2024-04-03T09:00:00Z", true),
+            mail("b", "2024-04-02T09:00:00Z", false),
+            mail("a", "2024-04-02T08:00:00Z", true),
+            mail("old", "2024-03-31T09:00:00Z", true),
+        ];
+        let ids: Vec<String> = select_new_messages(&values, Some("2024-04-01T00:00:00Z"))
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert_eq!(select_new_messages(&values, None).len(), 3);
+    }
+}
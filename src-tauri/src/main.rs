@@ -4,26 +4,60 @@
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
+    if args.get(1).map(|a| a == "doctor").unwrap_or(false) {
+        // 環境診断: チェックリストを表示して終了
+        let ok = shoruichecker_lib::run_doctor_cli();
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    if args.get(1).map(|a| a == "golden").unwrap_or(false) {
+        // ゴールデンサンプル照合: 前回より悪化したら失敗で終了
+        let Some(folder) = args.get(2) else {
+            eprintln!("Usage: shoruichecker golden <samples-folder> [--preset <name>] [--accept]");
+            std::process::exit(1);
+        };
+        let preset = args
+            .iter()
+            .position(|a| a == "--preset")
+            .and_then(|i| args.get(i + 1));
+        let accept = args.iter().any(|a| a == "--accept");
+        let ok = shoruichecker_lib::run_golden_cli(folder, preset.map(String::as_str), accept);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     let mut headless = false;
     let mut pdf_path: Option<String> = None;
+    let mut preset: Option<String> = None;
+    let mut expected_csv: Option<String> = None;
+    let mut report_path: Option<String> = None;
+    let mut record_link: Option<String> = None;
 
-    for arg in args.iter().skip(1) {
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
         if arg == "--headless" || arg == "-h" {
             headless = true;
+        } else if arg == "--preset" {
+            preset = iter.next().cloned();
+        } else if arg == "--expected" {
+            expected_csv = iter.next().cloned();
         } else if arg.to_lowercase().ends_with(".pdf") {
             pdf_path = Some(arg.clone());
+        } else if arg.to_lowercase().ends_with(".shorui") {
+            report_path = Some(arg.clone());
+        } else if arg.starts_with("shoruichecker://") {
+            record_link = Some(arg.clone());
         }
     }
 
     if headless {
         if let Some(path) = pdf_path {
             // ヘッドレスモード: GUIなしで解析して終了
-            if let Err(e) = shoruichecker_lib::analyze_headless(&path) {
+            if let Err(e) = shoruichecker_lib::analyze_headless(&path, preset.as_deref(), expected_csv.as_deref()) {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
         } else {
-            eprintln!("Usage: shoruichecker --headless <file.pdf>");
+            eprintln!("Usage: shoruichecker --headless [--preset <name>] [--expected <values.csv>] <file.pdf>");
             std::process::exit(1);
         }
     } else {
@@ -31,6 +65,14 @@ fn main() {
         if let Some(path) = pdf_path {
             std::env::set_var("ANALYZE_FILE", path);
         }
+        // 関連付けから起動: レポートをビューアで開く
+        if let Some(path) = report_path {
+            std::env::set_var("OPEN_REPORT", path);
+        }
+        // 印刷物のQRコードから起動: 解析記録を開く
+        if let Some(link) = record_link {
+            std::env::set_var("OPEN_RECORD_LINK", link);
+        }
         shoruichecker_lib::run()
     }
 }
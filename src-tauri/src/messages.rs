@@ -0,0 +1,301 @@
+//! Message catalog for backend logs and errors
+//!
+//! Japanese is the default; the `language` setting switches the backend's
+//! user-facing messages to English for non-Japanese staff. Messages are
+//! looked up by key and `{0}`, `{1}`, ... are replaced by the arguments.
+//! Analysis prompts and results stay Japanese.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::load_settings;
+
+/// Language of backend messages
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Ja,
+    En,
+}
+
+/// (key, Japanese, English)
+const CATALOG: &[(&str, &str, &str)] = &[
+    ("analysis.no_files", "ファイルが指定されていません", "No files specified"),
+    (
+        "analysis.compare_start",
+        "=== PDF照合解析開始 ({0} ファイル) ===",
+        "=== PDF comparison started ({0} files) ===",
+    ),
+    ("analysis.custom_instruction", "カスタム指示: {0}", "Custom instruction: {0}"),
+    ("analysis.comparing", "{0} で照合中...", "Comparing with {0}..."),
+    (
+        "analysis.compare_chunked",
+        "書類が多いため {0} グループに分けて照合します",
+        "Too many documents for one call: comparing in {0} groups",
+    ),
+    ("analysis.compare_anchor", "基準書類: {0}", "Reference document: {0}"),
+    (
+        "analysis.compare_group_failed",
+        "照合グループ {0} の解析に失敗しました（他のグループの結果で続行）: {1}",
+        "Comparison group {0} failed (continuing with the other groups): {1}",
+    ),
+    ("analysis.compare_merging", "照合結果を統合中...", "Merging comparison results..."),
+    ("analysis.compare_done", "✓ 照合完了", "✓ Comparison finished"),
+    ("analysis.compare_error", "照合エラー: {0}", "Comparison error: {0}"),
+    (
+        "analysis.single_start",
+        "=== PDF個別解析開始 ({0} ファイル) ===",
+        "=== PDF analysis started ({0} files) ===",
+    ),
+    ("analysis.analyzing", "{0} を解析中...", "Analyzing {0}..."),
+    (
+        "analysis.parallel",
+        "{0} で {1} ファイルを並列解析中...",
+        "Analyzing {1} files in parallel with {0}...",
+    ),
+    (
+        "analysis.queued",
+        "同時に解析するのは {0} ファイルまでです。残り {1} ファイルは順番待ちになります",
+        "Analyzing up to {0} files at once; {1} files are waiting in the queue",
+    ),
+    ("analysis.done", "✓ 解析完了", "✓ Analysis finished"),
+    (
+        "analysis.cached",
+        "{0} は前回から変更がないため、保存済みの解析結果を使用しました",
+        "{0} is unchanged; using the cached result",
+    ),
+    (
+        "analysis.awaiting_approval",
+        "{0} の解析結果は承認待ちです（承認するまで原本には書き込みません）",
+        "The result of {0} awaits approval (it is not written into the document until approved)",
+    ),
+    (
+        "analysis.not_stored",
+        "{0} の解析結果を書き込めませんでした（履歴にのみ残ります）: {1}",
+        "Could not write the result of {0} (it is kept in the history only): {1}",
+    ),
+    ("analysis.done_count", "✓ 解析完了 ({0}/{1})", "✓ Analysis finished ({0}/{1})"),
+    ("analysis.error", "解析エラー: {0}", "Analysis error: {0}"),
+    ("diagnostics.id", "（診断ID: {0}）", "(diagnostics ID: {0})"),
+    (
+        "provider.fallback",
+        "{0} で失敗しました（{1}）。{2} で再試行します",
+        "{0} failed ({1}); retrying with {2}",
+    ),
+    ("provider.fallback_done", "{0} で解析しました", "Answered by {0}"),
+    (
+        "provider.retry",
+        "一時的なエラーのため {0} 秒後に再試行します（{1}/{2} 回目）: {3}",
+        "Transient error; retrying in {0}s (attempt {1}/{2}): {3}",
+    ),
+    ("consensus.start", "高額契約のため {0} でも解析して照合します", "High-value contract: cross-checking with {0}"),
+    ("consensus.failed", "{0} での照合解析に失敗しました: {1}", "Cross-check with {0} failed: {1}"),
+    (
+        "analysis.aborted_summary",
+        "⏹ 解析を中断しました (完了 {0}/{1}、未実行 {2})",
+        "⏹ Analysis aborted ({0}/{1} finished, {2} not started)",
+    ),
+    ("preset.not_found", "プリセットが見つかりません: {0}", "Preset not found: {0}"),
+    ("batch.aborted", "中断されました", "Aborted"),
+    ("batch.cancelled", "⏹ 解析をキャンセルしました ({0})", "⏹ Analysis cancelled ({0})"),
+    ("batch.not_found", "実行中のバッチが見つかりません: {0}", "No running batch: {0}"),
+    ("watcher.folder_missing", "フォルダが存在しません", "The folder does not exist"),
+    ("watcher.pdf_detected", "PDF検出", "PDF detected"),
+    ("watcher.new_pdf", "新しいPDF: {0}", "New PDF: {0}"),
+    ("project.detected_title", "新規プロジェクト検出", "New project detected"),
+    ("project.detected", "新規プロジェクト検出: {0}（ガイドラインと設定を作成しました）", "New project detected: {0} (guidelines and settings created)"),
+    ("project.init_error", "プロジェクトを初期化できません: {0}: {1}", "Failed to initialize project {0}: {1}"),
+    ("file.not_found", "ファイルが見つかりません: {0}", "File not found: {0}"),
+    ("file.launch_error", "起動エラー: {0}", "Failed to launch: {0}"),
+    (
+        "recovery.temp_cleaned",
+        "前回の一時フォルダを削除しました ({0} 件)",
+        "Removed temp folders left by the previous run ({0})",
+    ),
+    (
+        "recovery.interrupted",
+        "中断された解析があります ({0} 件)",
+        "There are interrupted analyses ({0})",
+    ),
+    ("recovery.resuming", "中断された解析を再開: {0}", "Resuming interrupted analysis: {0}"),
+    ("recovery.not_found", "中断された解析が見つかりません", "Interrupted analysis not found"),
+    ("hook.spawn_error", "フック「{0}」を起動できません: {1}", "Failed to start hook \"{0}\": {1}"),
+    (
+        "hook.timeout",
+        "フック「{0}」が {1} 秒以内に終了しないため停止しました",
+        "Stopped hook \"{0}\" after {1} seconds",
+    ),
+    ("hook.failed", "フック「{0}」が失敗しました ({1})", "Hook \"{0}\" failed ({1})"),
+    (
+        "storage.disk_full",
+        "空き容量が不足しています: {0}（必要 {1} MB、空き {2} MB）",
+        "Not enough disk space: {0} ({1} MB needed, {2} MB free)",
+    ),
+    (
+        "storage.temp_quota",
+        "一時フォルダの使用量が上限を超えます: {0}（使用中 {1} MB、上限 {2} MB）",
+        "Temp folder would exceed its quota: {0} ({1} MB used, {2} MB limit)",
+    ),
+    (
+        "clipboard.no_image",
+        "クリップボードに画像がありません（スクリーンショットをコピーしてください）",
+        "No image on the clipboard (copy a screenshot first)",
+    ),
+    (
+        "clipboard.no_folder",
+        "保存先フォルダがありません（監視フォルダを設定してください）",
+        "No folder to save to (set a watch folder)",
+    ),
+    ("clipboard.saved", "クリップボードの画像を保存: {0}", "Saved clipboard image: {0}"),
+    ("intake.received", "受付フォルダにPDFが届きました: {0}", "PDF received in the intake folder: {0}"),
+    ("intake.moved", "✓ {0} を {1} に移動しました", "✓ Moved {0} to {1}"),
+    ("intake.move_error", "{0} を工事フォルダに移動できません: {1}", "Failed to move {0} to the project folder: {1}"),
+    (
+        "intake.duplicate",
+        "{0} は {1}（{2}）と同じ内容のため処理しませんでした",
+        "Skipped {0}: same content as {1} ({2})",
+    ),
+    ("intake.project_missing", "移動先の工事フォルダがありません: {0}", "Project folder not found: {0}"),
+    ("intake.routed", "{0} を振り分けルールにより {1} に移動しました", "Routed {0} to {1}"),
+    (
+        "intake.route_empty",
+        "振り分けルール（{0}）にファイル名の先頭文字かサブフォルダを指定してください",
+        "The route to {0} needs a file name prefix or a subfolder",
+    ),
+    ("intake.notify_failed", "{0} の解析結果を通知できません: {1}", "Failed to send the result of {0}: {1}"),
+    (
+        "intake.notify_on_approval",
+        "{0} の解析結果は承認後に通知します",
+        "The result of {0} will be sent once it is approved",
+    ),
+    (
+        "intake.archive_password",
+        "{0} はパスワード付きZIPです。パスワードを入力してください",
+        "{0} is password-protected; enter the password to expand it",
+    ),
+    ("intake.archive_wrong_password", "{0} のパスワードが違います", "Wrong password for {0}"),
+    ("intake.archive_error", "{0} を展開できません: {1}", "Failed to expand {0}: {1}"),
+    ("intake.archive_extracted", "✓ {0} から {1} 件の書類を展開しました", "✓ Extracted {1} document(s) from {0}"),
+    ("intake.archive_empty", "{0} に解析できる書類がありません", "{0} contains no supported documents"),
+    (
+        "intake.printer_guidance",
+        "PDFプリンタの保存先を「{0}」に設定すると、印刷した書類が自動で解析されます",
+        "Set the PDF printer's save location to \"{0}\" to analyze printed documents automatically",
+    ),
+    ("mail_intake.received", "メール「{0}」（{1}）から{2}件のPDFを取り込みました", "Saved {2} PDF(s) from mail \"{0}\" ({1})"),
+    ("mail_intake.no_rule", "振り分け先のない件名のため取り込みません: {0}", "No project rule matches the subject, skipped: {0}"),
+    ("mail_intake.save_error", "添付ファイル {0} を保存できません: {1}", "Failed to save attachment {0}: {1}"),
+    ("mail_intake.folder_missing", "メールフォルダが見つかりません: {0}", "Mail folder not found: {0}"),
+    ("mail_intake.not_signed_in", "Microsoftアカウントにサインインしていません", "Not signed in to a Microsoft account"),
+    ("mail_intake.no_client_id", "アプリケーション(クライアント)IDが設定されていません", "No application (client) ID is configured"),
+    ("mail_intake.sign_in_failed", "サインインできませんでした: {0}", "Sign-in failed: {0}"),
+    ("mail_intake.invalid_rule", "件名ルールが正しくありません: {0}: {1}", "Invalid subject rule {0}: {1}"),
+    ("mail_intake.http_error", "メールサーバーとの通信エラー ({0}): {1}", "Mail server request failed ({0}): {1}"),
+    ("jobs.batch_label", "PDF解析（{0}・{1}件）", "PDF analysis ({0}, {1} files)"),
+    ("jobs.code_review_label", "コードレビュー監視: {0}", "Code review watcher: {0}"),
+    ("jobs.not_found", "実行中の処理が見つかりません: {0}", "No running job: {0}"),
+    (
+        "jobs.not_cancellable",
+        "この処理は中止できません（停止できる外部プロセスがありません）: {0}",
+        "This job cannot be cancelled (no external process to stop): {0}",
+    ),
+    ("queue.enqueued", "解析キューに{0}件追加しました（待機中 {1}件）", "Queued {0} analysis job(s) ({1} waiting)"),
+    ("queue.label_many", "{0} ほか{1}件", "{0} and {1} more"),
+    ("queue.worker_label", "解析キュー", "Analysis queue"),
+    ("queue.not_found", "キューに待機中のジョブが見つかりません: {0}", "No waiting job in the queue: {0}"),
+    ("queue.failed", "キューの解析に失敗しました（{0}）: {1}", "Queued analysis failed ({0}): {1}"),
+    (
+        "access.read_only",
+        "閲覧専用モードのため{0}はできません（管理者の設定）",
+        "{0} is disabled in read-only mode (set by the administrator)",
+    ),
+    ("access.embed", "解析結果の書き込み", "Writing results into documents"),
+    ("access.move", "ファイルの移動", "Moving files"),
+    ("access.edit_guidelines", "ガイドライン・チェック設定の編集", "Editing guidelines and check settings"),
+    ("shutdown.started", "終了処理中: 書き込み中の結果を保存しています…", "Shutting down: finishing pending writes…"),
+    (
+        "shutdown.timeout",
+        "書き込みの完了を待てなかったため終了します",
+        "Exiting without waiting for pending writes (timed out)",
+    ),
+    ("shutdown.in_progress", "終了処理中のため解析を開始できません", "Cannot start an analysis while shutting down"),
+    ("shutdown.write_refused", "終了処理中のため保存できません", "Cannot save while shutting down"),
+    ("hook.warning", "⚠ {0}", "⚠ {0}"),
+    ("hook.cancelled", "解析前フックにより解析を中止しました: {0}", "Analysis cancelled by a pre-analysis hook: {0}"),
+    ("expiry.title", "書類の期限", "Document expiry"),
+    (
+        "expiry.soon",
+        "⚠ {0} の{1}（{2}）まであと{3}日です。更新後の書類を再解析してください",
+        "⚠ {1} of {0} ({2}) is in {3} days. Re-analyze the renewed document",
+    ),
+    (
+        "expiry.expired",
+        "⚠ {0} の{1}（{2}）が過ぎています",
+        "⚠ {1} of {0} ({2}) has passed",
+    ),
+];
+
+/// Replace `{0}`, `{1}`, ... with the arguments
+pub fn format_message(template: &str, args: &[&dyn Display]) -> String {
+    let mut message = template.to_string();
+    for (i, arg) in args.iter().enumerate() {
+        message = message.replace(&format!("{{{}}}", i), &arg.to_string());
+    }
+    message
+}
+
+/// Message in the given language (the key itself if it is not in the catalog)
+pub fn message(language: Language, key: &str, args: &[&dyn Display]) -> String {
+    match CATALOG.iter().find(|(k, _, _)| *k == key) {
+        Some((_, ja, en)) => {
+            let template = match language {
+                Language::Ja => ja,
+                Language::En => en,
+            };
+            format_message(template, args)
+        }
+        None => key.to_string(),
+    }
+}
+
+/// Message in the configured language
+pub fn tr(key: &str, args: &[&dyn Display]) -> String {
+    message(load_settings().language, key, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(template: &str) -> Vec<usize> {
+        let mut found: Vec<usize> = (0..10)
+            .filter(|i| template.contains(&format!("{{{}}}", i)))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn catalog_entries_are_unique_and_consistent() {
+        for (i, (key, ja, en)) in CATALOG.iter().enumerate() {
+            assert!(!ja.is_empty() && !en.is_empty(), "{}", key);
+            assert_eq!(placeholders(ja), placeholders(en), "{}", key);
+            assert!(CATALOG[i + 1..].iter().all(|(k, _, _)| k != key), "duplicate {}", key);
+        }
+    }
+
+    #[test]
+    fn message_fills_positional_arguments() {
+        assert_eq!(
+            message(Language::En, "analysis.parallel", &[&"gemini-2.5-pro", &3]),
+            "Analyzing 3 files in parallel with gemini-2.5-pro..."
+        );
+        assert_eq!(
+            message(Language::Ja, "analysis.done_count", &[&2, &3]),
+            "✓ 解析完了 (2/3)"
+        );
+        assert_eq!(message(Language::Ja, "missing.key", &[]), "missing.key");
+    }
+}
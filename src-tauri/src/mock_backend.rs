@@ -0,0 +1,222 @@
+//! Mock AI backend for development, demos and integration tests
+//!
+//! With `ai_backend: "mock"` (or `SHORUICHECKER_MOCK=1` or
+//! `SHORUICHECKER_AI_BACKEND=mock` for CI and demos, which also bypass the
+//! fallback chain and the consensus provider) every AI request is answered
+//! from fixtures instead of a real provider, so the rest of the pipeline
+//! (history, embedding, index, events, reports) runs offline. A fixture file lists
+//! responses chosen by prompt/file substrings; a fixtures folder holds such
+//! files and plain `<name>.md`/`.txt` responses for files whose name contains
+//! `<name>`. Requests matching none get a built-in canned result in the format
+//! the parsers expect.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::{AppError, AppResult};
+use crate::gemini_cli::GeminiRequest;
+use crate::settings::load_settings;
+
+/// One canned response; all given conditions must match
+#[derive(Clone, Deserialize)]
+pub struct MockFixture {
+    /// Substring of the prompt
+    #[serde(default)]
+    pub prompt_contains: Option<String>,
+    /// Substring of an attached file name
+    #[serde(default)]
+    pub file_contains: Option<String>,
+    #[serde(default)]
+    pub response: String,
+    /// Fail the request with this message instead of answering
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Fixture file: `{"fixtures": [...]}`, first match wins
+#[derive(Clone, Deserialize, Default)]
+pub struct MockFixtures {
+    #[serde(default)]
+    pub fixtures: Vec<MockFixture>,
+}
+
+/// Turns the mock backend on regardless of the settings (`1` or `true`)
+pub const MOCK_ENV: &str = "SHORUICHECKER_MOCK";
+
+/// Fixture file or folder used instead of the `mock_fixtures` setting
+pub const MOCK_FIXTURES_ENV: &str = "SHORUICHECKER_MOCK_FIXTURES";
+
+/// Fixtures of a file, or of every file in a folder (in name order)
+pub fn load_fixtures(path: &str) -> Result<MockFixtures, String> {
+    if Path::new(path).is_dir() {
+        return load_fixture_folder(Path::new(path));
+    }
+    load_fixture_file(path)
+}
+
+fn load_fixture_folder(folder: &Path) -> Result<MockFixtures, String> {
+    let mut files: Vec<_> = fs::read_dir(folder)
+        .map_err(|e| format!("モックの応答フォルダを読み込めません: {}: {}", folder.display(), e))?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+
+    let mut fixtures = Vec::new();
+    for file in files {
+        let extension = file
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "json" => fixtures.extend(load_fixture_file(&file.to_string_lossy())?.fixtures),
+            "md" | "txt" => {
+                let response = fs::read_to_string(&file).map_err(|e| {
+                    format!("モックの応答を読み込めません: {}: {}", file.display(), e)
+                })?;
+                fixtures.push(MockFixture {
+                    prompt_contains: None,
+                    file_contains: file.file_stem().map(|s| s.to_string_lossy().to_string()),
+                    response,
+                    error: None,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(MockFixtures { fixtures })
+}
+
+fn load_fixture_file(path: &str) -> Result<MockFixtures, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("モックの応答定義を読み込めません: {}: {}", path, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("モックの応答定義が正しくありません: {}: {}", path, e))
+}
+
+fn fixture_matches(fixture: &MockFixture, request: &GeminiRequest<'_>) -> bool {
+    let prompt_ok = fixture
+        .prompt_contains
+        .as_deref()
+        .is_none_or(|s| request.prompt.contains(s));
+    let file_ok = fixture.file_contains.as_deref().is_none_or(|s| {
+        request.files.unwrap_or_default().iter().any(|f| f.contains(s))
+    });
+    prompt_ok && file_ok
+}
+
+/// Built-in answer shaped like a real one for the kind of request
+pub fn default_response(request: &GeminiRequest<'_>) -> String {
+    if request.prompt.contains("JSON配列") {
+        return "[]".to_string();
+    }
+    if request.output_format == "json"
+        || request.prompt.contains("JSONだけ")
+        || request.prompt.contains("JSON形式")
+    {
+        return "{}".to_string();
+    }
+    let files = request.files.unwrap_or_default();
+    let target = if files.is_empty() {
+        "テキスト入力".to_string()
+    } else {
+        files.join("、")
+    };
+    format!(
+        "## 書類タイプ\nその他（モック応答）\n\n## チェック結果\n✓ 対象: {}\n✓ 書類の体裁\n⚠ モック応答のため実際の内容は確認していません\n",
+        target
+    )
+}
+
+/// Answer a request from the fixtures
+pub fn respond(fixtures: &MockFixtures, request: &GeminiRequest<'_>) -> AppResult<String> {
+    match fixtures.fixtures.iter().find(|f| fixture_matches(f, request)) {
+        Some(MockFixture { error: Some(error), .. }) => Err(AppError::Process(error.clone())),
+        Some(fixture) => Ok(fixture.response.clone()),
+        None => Ok(default_response(request)),
+    }
+}
+
+/// Answer a request with the configured fixtures
+pub fn run_mock(request: &GeminiRequest<'_>) -> AppResult<String> {
+    let path = std::env::var(MOCK_FIXTURES_ENV)
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .or_else(|| load_settings().mock_fixtures);
+    let fixtures = match path {
+        Some(path) => load_fixtures(&path)?,
+        None => MockFixtures::default(),
+    };
+    respond(&fixtures, request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::MockProvider;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir, run_gemini_with};
+
+    fn fixtures() -> MockFixtures {
+        serde_json::from_str(
+            r#"{"fixtures": [
+                {"prompt_contains": "分類", "response": "{\"doc_type\": \"契約書\"}"},
+                {"file_contains": "請求書", "response": "⚠ 金額不整合"},
+                {"file_contains": "壊れ", "error": "exit code 1"}
+            ]}"#,
+        )
+        .expect("fixtures")
+    }
+
+    #[test]
+    fn first_matching_fixture_answers() {
+        let files = vec!["請求書.pdf".to_string()];
+        let request = GeminiRequest::text_with_files("整合性をチェック", "m", &files);
+        assert_eq!(respond(&fixtures(), &request).unwrap(), "⚠ 金額不整合");
+
+        let request = GeminiRequest::text_with_files("書類を分類", "m", &files);
+        assert!(respond(&fixtures(), &request).unwrap().contains("契約書"));
+
+        let broken = vec!["壊れ.pdf".to_string()];
+        let request = GeminiRequest::text_with_files("整合性をチェック", "m", &broken);
+        assert!(respond(&fixtures(), &request).is_err());
+    }
+
+    #[test]
+    fn gemini_requests_are_answered_offline() {
+        // The provider is passed in: setting the backend variable would leak
+        // into the other tests of the process
+        let dir = create_temp_dir(".shoruichecker_test_mock").expect("create dir");
+        fs::write(dir.join("a.pdf"), b"%PDF").unwrap();
+        let files = vec!["a.pdf".to_string()];
+        let request = GeminiRequest::text_with_files("整合性をチェック", "m", &files);
+        let output = run_gemini_with(&MockProvider, &dir, &request);
+        cleanup_temp_dir(&dir);
+        assert!(output.expect("mock output").contains("モック応答"));
+    }
+
+    #[test]
+    fn fixture_folders_combine_lists_and_response_files() {
+        let dir = create_temp_dir(".shoruichecker_test_mock_folder").expect("create dir");
+        fs::write(dir.join("a_list.json"), r#"{"fixtures": [{"prompt_contains": "分類", "response": "{}"}]}"#).unwrap();
+        fs::write(dir.join("見積書.md"), "## 書類タイプ\n見積書\n\n✓ 合計金額").unwrap();
+        fs::write(dir.join("readme.pdf"), b"%PDF").unwrap();
+        let fixtures = load_fixtures(&dir.to_string_lossy()).expect("fixtures");
+        cleanup_temp_dir(&dir);
+
+        assert_eq!(fixtures.fixtures.len(), 2);
+        let files = vec!["2026_見積書_A社.pdf".to_string()];
+        let request = GeminiRequest::text_with_files("整合性をチェック", "m", &files);
+        assert!(respond(&fixtures, &request).unwrap().contains("✓ 合計金額"));
+    }
+
+    #[test]
+    fn unmatched_requests_get_parseable_defaults() {
+        let files = vec!["a.pdf".to_string()];
+        let analysis = respond(&fixtures(), &GeminiRequest::text_with_files("整合性", "m", &files)).unwrap();
+        assert!(analysis.contains("⚠") && analysis.contains("a.pdf"));
+        assert_eq!(default_response(&GeminiRequest::text("次のJSON配列だけを出力", "m")), "[]");
+        assert_eq!(default_response(&GeminiRequest::json("要約", "m")), "{}");
+    }
+}
@@ -0,0 +1,177 @@
+//! Persistent user notes on files
+//!
+//! Free-form notes ("この工事は税率8%の経過措置対象") are stored in the
+//! database only: writing them into the PDF would change a document whose
+//! hash is recorded in the history and the archive. Whenever the file or
+//! another file of its project is analyzed, the project's notes are included
+//! in the prompt.
+
+use std::path::Path;
+
+use chrono::Local;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::database::open_db;
+use crate::encryption::{open_text, seal_text};
+use crate::project_settings::project_folder_for;
+
+/// A note attached to a file
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FileNote {
+    pub id: i64,
+    pub file_path: String,
+    pub project_folder: String,
+    pub note: String,
+    pub created_at: String,
+}
+
+fn query_notes(conn: &Connection, sql: &str, key: &str) -> Result<Vec<FileNote>, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![key], |row| {
+            Ok(FileNote {
+                id: row.get(0)?,
+                file_path: row.get(1)?,
+                project_folder: row.get(2)?,
+                note: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .flatten()
+        .filter_map(|mut note| {
+            note.note = open_text(note.note).ok()?;
+            Some(note)
+        })
+        .collect())
+}
+
+/// Notes of a file, oldest first
+pub fn notes_of_file(conn: &Connection, file_path: &str) -> Result<Vec<FileNote>, String> {
+    query_notes(
+        conn,
+        "SELECT id, file_path, project_folder, note, created_at FROM file_notes
+         WHERE file_path = ?1 ORDER BY id",
+        file_path,
+    )
+}
+
+/// Notes of all files of a project, oldest first
+pub fn notes_of_project(conn: &Connection, project_folder: &str) -> Result<Vec<FileNote>, String> {
+    query_notes(
+        conn,
+        "SELECT id, file_path, project_folder, note, created_at FROM file_notes
+         WHERE project_folder = ?1 ORDER BY id",
+        project_folder,
+    )
+}
+
+pub fn insert_note(
+    conn: &Connection,
+    file_path: &str,
+    project_folder: &str,
+    note: &str,
+) -> Result<FileNote, String> {
+    let created_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT INTO file_notes (file_path, project_folder, note, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![file_path, project_folder, seal_text(note)?, created_at],
+    )
+    .map_err(|e| format!("メモの保存エラー: {}", e))?;
+    Ok(FileNote {
+        id: conn.last_insert_rowid(),
+        file_path: file_path.to_string(),
+        project_folder: project_folder.to_string(),
+        note: note.to_string(),
+        created_at,
+    })
+}
+
+fn file_name_of(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Prompt section with the notes of the project
+///
+/// Notes on the analysis targets come first.
+pub fn format_notes_section(notes: &[FileNote], targets: &[String]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for path in targets {
+        for note in notes.iter().filter(|n| &n.file_path == path) {
+            lines.push(format!("- [{}] {}", file_name_of(path), note.note));
+        }
+    }
+    for note in notes.iter().filter(|n| !targets.contains(&n.file_path)) {
+        lines.push(format!("- [同じ工事の {}] {}", file_name_of(&note.file_path), note.note));
+    }
+
+    if lines.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n## ユーザーのメモ\n以下はユーザーが書類に付けたメモです。前提条件として考慮してください：\n{}\n",
+            lines.join("\n")
+        )
+    }
+}
+
+/// Notes section for analyzing `targets` of a project
+pub fn notes_section(project_folder: &str, targets: &[String]) -> String {
+    let notes = open_db()
+        .and_then(|conn| notes_of_project(&conn, project_folder))
+        .unwrap_or_default();
+    format_notes_section(&notes, targets)
+}
+
+/// ファイルにメモを追加（以後の解析のプロンプトに含める）
+#[tauri::command]
+pub fn add_file_note(path: String, note: String) -> Result<FileNote, String> {
+    let note = note.trim();
+    if note.is_empty() {
+        return Err("メモが空です".to_string());
+    }
+    insert_note(&open_db()?, &path, &project_folder_for(&path), note)
+}
+
+/// ファイルのメモ一覧
+#[tauri::command]
+pub fn get_file_notes(path: String) -> Result<Vec<FileNote>, String> {
+    notes_of_file(&open_db()?, &path)
+}
+
+/// メモを削除
+#[tauri::command]
+pub fn delete_file_note(id: i64) -> Result<(), String> {
+    open_db()?
+        .execute("DELETE FROM file_notes WHERE id = ?1", params![id])
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    #[test]
+    fn project_notes_are_included_with_target_notes_first() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        insert_note(&conn, "/p/請求書.pdf", "/p", "端数は切り捨て").unwrap();
+        insert_note(&conn, "/p/契約書.pdf", "/p", "この工事は税率8%の経過措置対象").unwrap();
+        insert_note(&conn, "/q/契約書.pdf", "/q", "別工事").unwrap();
+
+        let notes = notes_of_project(&conn, "/p").unwrap();
+        assert_eq!(notes.len(), 2);
+        let section = format_notes_section(&notes, &["/p/契約書.pdf".to_string()]);
+        let own = section.find("[契約書.pdf] この工事は税率8%").expect("own note");
+        let other = section.find("[同じ工事の 請求書.pdf] 端数は切り捨て").expect("project note");
+        assert!(own < other);
+        assert!(!section.contains("別工事"));
+    }
+}
@@ -0,0 +1,248 @@
+//! OpenAI-compatible chat completions client for on-prem LLM servers
+//!
+//! Ollama, LM Studio, vLLM and similar servers expose `/v1/chat/completions`,
+//! so documents never leave the office network. Local models rarely read
+//! PDFs, so attached PDFs are sent as their extracted text; images go as data
+//! URLs for vision models. Base URL and model come from the settings, the
+//! (often unused) API key from the OS keychain (`OPENAI_API_KEY` overrides it).
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::gemini_cli::GeminiRequest;
+use crate::keychain::{read_secret, replace_secret};
+use crate::pdf_text::extract_pdf_text;
+use crate::settings::load_settings;
+use crate::usage::note_token_usage;
+
+const API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+const KEYRING_USER: &str = "openai-api-key";
+
+/// Local models on office PCs are slow
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// Server of the OpenAI-compatible provider
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct OpenAiCompatSettings {
+    /// e.g. `http://localhost:11434/v1` (Ollama) or `http://localhost:1234/v1` (LM Studio)
+    pub base_url: String,
+    pub model: String,
+}
+
+impl OpenAiCompatSettings {
+    pub fn is_configured(&self) -> bool {
+        !self.base_url.trim().is_empty() && !self.model.trim().is_empty()
+    }
+
+    pub fn completions_url(&self) -> String {
+        format!(
+            "{}/chat/completions",
+            self.base_url.trim().trim_end_matches('/')
+        )
+    }
+}
+
+/// API key from the environment or the keychain
+pub fn api_key() -> Option<String> {
+    std::env::var(API_KEY_ENV)
+        .ok()
+        .or_else(|| read_secret(KEYRING_USER))
+        .filter(|k| !k.trim().is_empty())
+}
+
+/// Store the API key (None or empty removes it)
+pub fn store_api_key(key: Option<&str>) -> Result<(), String> {
+    replace_secret(KEYRING_USER, key.map(str::trim))
+}
+
+fn image_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn is_pdf(name: &str) -> bool {
+    name.to_lowercase().ends_with(".pdf")
+}
+
+/// Message content of a request: the files first, then the prompt
+///
+/// A plain string when there are no images, which every server accepts.
+pub fn message_content(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<Value> {
+    let mut texts = Vec::new();
+    let mut images = Vec::new();
+    for name in request.files.unwrap_or_default() {
+        let path = temp_dir.join(name);
+        if let Some(image_type) = image_type(name) {
+            let data = BASE64.encode(fs::read(&path)?);
+            images.push(json!({
+                "type": "image_url",
+                "image_url": { "url": format!("data:{};base64,{}", image_type, data) }
+            }));
+        } else if is_pdf(name) {
+            let text = extract_pdf_text(&path.to_string_lossy()).map_err(AppError::Process)?;
+            if text.trim().is_empty() {
+                return Err(AppError::Process(format!(
+                    "{} からテキストを抽出できません（スキャンPDFはOCR後に解析してください）",
+                    name
+                )));
+            }
+            texts.push(format!("--- {} ---\n{}", name, text));
+        } else {
+            texts.push(format!("--- {} ---\n{}", name, fs::read_to_string(&path)?));
+        }
+    }
+    texts.push(request.prompt.to_string());
+    let text = texts.join("\n\n");
+    if images.is_empty() {
+        return Ok(json!(text));
+    }
+    images.push(json!({ "type": "text", "text": text }));
+    Ok(Value::Array(images))
+}
+
+/// Chat completions request body
+pub fn request_body(model: &str, content: Value, json_output: bool) -> Value {
+    let mut body = json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": content }],
+        "stream": false
+    });
+    if json_output {
+        body["response_format"] = json!({ "type": "json_object" });
+    }
+    body
+}
+
+/// Text of a chat completions response
+pub fn response_text(response: &Value) -> AppResult<String> {
+    if let Some(error) = response.get("error") {
+        // Some servers send the message as a plain string
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .or_else(|| error.as_str())
+            .unwrap_or("unknown error");
+        return Err(AppError::Process(format!(
+            "LLMサーバーのエラー: {}",
+            message
+        )));
+    }
+    match response["choices"][0]["message"]["content"].as_str() {
+        Some(text) if !text.trim().is_empty() => Ok(text.to_string()),
+        _ => Err(AppError::Process(
+            "LLMサーバーの応答にテキストがありません".to_string(),
+        )),
+    }
+}
+
+/// Run a request against the configured OpenAI-compatible server
+pub fn run_openai_compat(temp_dir: &Path, request: &GeminiRequest<'_>) -> AppResult<String> {
+    let server = load_settings().openai_compat.unwrap_or_default();
+    if !server.is_configured() {
+        return Err(AppError::Process(
+            "LLMサーバーのURLとモデルが設定されていません".to_string(),
+        ));
+    }
+    let body = request_body(
+        server.model.trim(),
+        message_content(temp_dir, request)?,
+        request.output_format == "json",
+    );
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let mut call = agent.post(&server.completions_url());
+    if let Some(key) = api_key() {
+        call = call.set("Authorization", &format!("Bearer {}", key));
+    }
+    let response: Value = match call.send_json(body) {
+        Ok(r) => r.into_json()?,
+        Err(ureq::Error::Status(code, r)) => r
+            .into_json()
+            .unwrap_or_else(|_| json!({ "error": { "message": format!("HTTP {}", code) } })),
+        Err(e) => {
+            return Err(AppError::Process(format!(
+                "LLMサーバーに接続できません ({}): {}",
+                server.base_url, e
+            )))
+        }
+    };
+    note_token_usage(
+        server.model.trim(),
+        response["usage"]["prompt_tokens"].as_i64(),
+        response["usage"]["completion_tokens"].as_i64(),
+    );
+    response_text(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn content_is_plain_text_unless_images_are_attached() {
+        let dir = create_temp_dir(".shoruichecker_test_openai").expect("create dir");
+        fs::write(dir.join("notes.txt"), "メモ").unwrap();
+        fs::write(dir.join("photo.JPG"), b"jpg").unwrap();
+        let text_only = vec!["notes.txt".to_string()];
+        let with_image = vec!["notes.txt".to_string(), "photo.JPG".to_string()];
+        let plain = message_content(
+            &dir,
+            &GeminiRequest::text_with_files("チェックして", "gemini-2.5-pro", &text_only),
+        );
+        let mixed = message_content(
+            &dir,
+            &GeminiRequest::text_with_files("チェックして", "gemini-2.5-pro", &with_image),
+        );
+        cleanup_temp_dir(&dir);
+
+        assert_eq!(
+            plain.unwrap(),
+            json!("--- notes.txt ---\nメモ\n\nチェックして")
+        );
+        let mixed = mixed.unwrap();
+        assert_eq!(
+            mixed[0]["image_url"]["url"],
+            format!("data:image/jpeg;base64,{}", BASE64.encode(b"jpg"))
+        );
+        assert_eq!(mixed[1]["text"], "--- notes.txt ---\nメモ\n\nチェックして");
+
+        let body = request_body("qwen2.5:14b", json!("x"), true);
+        assert_eq!(body["response_format"]["type"], "json_object");
+        let server = OpenAiCompatSettings {
+            base_url: "http://localhost:11434/v1/ ".to_string(),
+            model: "qwen2.5:14b".to_string(),
+        };
+        assert_eq!(
+            server.completions_url(),
+            "http://localhost:11434/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn response_text_reads_the_first_choice() {
+        let ok = json!({ "choices": [{ "message": { "role": "assistant", "content": "## 書類タイプ" } }] });
+        assert_eq!(response_text(&ok).unwrap(), "## 書類タイプ");
+        let error = json!({ "error": "model 'llama3' not found" });
+        assert!(response_text(&error)
+            .unwrap_err()
+            .to_string()
+            .contains("not found"));
+        assert!(response_text(&json!({ "choices": [] })).is_err());
+    }
+}
@@ -3,10 +3,26 @@
 //! This module provides functionality to embed analysis results and custom instructions
 //! into PDF metadata, as well as read them back.
 
+use std::path::Path;
+
 use base64::{Engine as _, engine::general_purpose};
 use serde::{Serialize, Deserialize};
 use lopdf::{Document, Object, StringFormat};
 
+use crate::archive::file_sha256;
+use crate::audit::record_access;
+use crate::classify::rekey_metadata;
+use crate::database::open_db;
+use crate::file_lock::write_atomic;
+use crate::project_settings::ensure_original_modifiable;
+use crate::result_store::{load_result_data, store_result};
+use crate::shutdown::critical_section;
+use crate::structured_report::parse_report;
+
+/// Format of the embedded data; 2.0 adds the structured findings
+/// (`ShoruiCheckerFindings`) next to the result text
+pub const EMBED_SCHEMA_VERSION: &str = "2.0";
+
 /// PDF embedded data structure
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PdfEmbeddedData {
@@ -17,6 +33,19 @@ pub struct PdfEmbeddedData {
 
 /// Embed analysis result and custom instruction into PDF metadata
 pub fn embed_result_in_pdf_with_instruction(pdf_path: &str, result: &str, custom_instruction: &str) -> Result<(), String> {
+    let data = PdfEmbeddedData {
+        result: result.to_string(),
+        instruction: (!custom_instruction.is_empty()).then(|| custom_instruction.to_string()),
+        date: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    write_embedded_data(pdf_path, &data)
+}
+
+/// Write embedded data in the current format, keeping its date
+pub fn write_embedded_data(pdf_path: &str, data: &PdfEmbeddedData) -> Result<(), String> {
+    ensure_original_modifiable(pdf_path)?;
+    let _critical = critical_section()?;
+
     let mut doc = Document::load(pdf_path).map_err(|e| format!("PDF読み込みエラー: {}", e))?;
 
     // Get or create Info dictionary
@@ -33,37 +62,45 @@ pub fn embed_result_in_pdf_with_instruction(pdf_path: &str, result: &str, custom
     // Add custom metadata
     if let Ok(Object::Dictionary(ref mut info)) = doc.get_object_mut(info_id) {
         // Store analysis result (base64 encoded to avoid encoding issues)
-        let encoded = base64_encode(result);
+        let encoded = base64_encode(&data.result);
         info.set("ShoruiCheckerResult", Object::String(encoded.into_bytes(), StringFormat::Literal));
 
         // Store custom instruction if provided
-        if !custom_instruction.is_empty() {
-            let encoded_instruction = base64_encode(custom_instruction);
+        if let Some(instruction) = data.instruction.as_deref().filter(|i| !i.is_empty()) {
+            let encoded_instruction = base64_encode(instruction);
             info.set("ShoruiCheckerInstruction", Object::String(encoded_instruction.into_bytes(), StringFormat::Literal));
         }
 
+        // Store structured findings for tools that read the metadata
+        let findings = serde_json::to_string(&parse_report("", &data.result).findings).map_err(|e| e.to_string())?;
+        info.set("ShoruiCheckerFindings", Object::String(base64_encode(&findings).into_bytes(), StringFormat::Literal));
+
         // Store analysis timestamp
-        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        info.set("ShoruiCheckerDate", Object::String(timestamp.into_bytes(), StringFormat::Literal));
+        info.set("ShoruiCheckerDate", Object::String(data.date.clone().into_bytes(), StringFormat::Literal));
 
         // Store version
-        info.set("ShoruiCheckerVersion", Object::String(b"1.0".to_vec(), StringFormat::Literal));
+        info.set("ShoruiCheckerVersion", Object::String(EMBED_SCHEMA_VERSION.as_bytes().to_vec(), StringFormat::Literal));
     }
 
-    doc.save(pdf_path).map_err(|e| format!("PDF保存エラー: {}", e))?;
-    Ok(())
+    save_document(&mut doc, pdf_path)
 }
 
-/// Wrapper for backward compatibility (embeds result without custom instruction)
-pub fn embed_result_in_pdf(pdf_path: &str, result: &str) -> Result<(), String> {
-    embed_result_in_pdf_with_instruction(pdf_path, result, "")
-}
-
-/// Read embedded analysis result from PDF
-/// Returns (result, date) tuple if found
-pub fn read_result_from_pdf(pdf_path: &str) -> Option<(String, String)> {
-    let data = read_embedded_data_from_pdf(pdf_path)?;
-    Some((data.result, data.date))
+/// Save a PDF through a temp file, so an interrupted save leaves the
+/// original intact
+///
+/// Only the metadata changed, so the stored classification of the file is
+/// moved to the new content hash instead of being redone.
+fn save_document(doc: &mut Document, pdf_path: &str) -> Result<(), String> {
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).map_err(|e| format!("PDF保存エラー: {}", e))?;
+    let old_hash = file_sha256(Path::new(pdf_path)).ok();
+    write_atomic(Path::new(pdf_path), bytes).map_err(|e| format!("PDF保存エラー: {}", e))?;
+    if let (Some(old_hash), Ok(new_hash), Ok(conn)) =
+        (old_hash, file_sha256(Path::new(pdf_path)), open_db())
+    {
+        let _ = rekey_metadata(&conn, pdf_path, &old_hash, &new_hash);
+    }
+    Ok(())
 }
 
 /// Read all embedded data from PDF
@@ -108,6 +145,39 @@ pub fn read_embedded_data_from_pdf(pdf_path: &str) -> Option<PdfEmbeddedData> {
     None
 }
 
+/// Format version of the data embedded in a PDF (None: no result embedded)
+///
+/// Results embedded before the version was recorded count as 1.0. A PDF
+/// that cannot be loaded is an error, not a PDF without a result.
+pub fn read_embedded_version(pdf_path: &str) -> Result<Option<String>, String> {
+    let doc = Document::load(pdf_path).map_err(|e| format!("PDFを読み込めません: {}", e))?;
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|o| o.as_reference())
+        .and_then(|id| doc.get_object(id))
+        .and_then(|o| o.as_dict());
+    let Ok(info) = info else {
+        return Ok(None);
+    };
+    if info.get(b"ShoruiCheckerResult").is_err() {
+        return Ok(None);
+    }
+    Ok(Some(
+        info.get(b"ShoruiCheckerVersion")
+            .ok()
+            .and_then(|o| o.as_str().ok())
+            .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+            .unwrap_or_else(|| "1.0".to_string()),
+    ))
+}
+
+/// Whether a format version is older than the current one
+pub fn is_older_schema(version: &str) -> bool {
+    let parse = |v: &str| -> Vec<u32> { v.trim().split('.').map(|p| p.parse().unwrap_or(0)).collect() };
+    parse(version) < parse(EMBED_SCHEMA_VERSION)
+}
+
 /// Base64 encode a string
 pub fn base64_encode(s: &str) -> String {
     general_purpose::STANDARD.encode(s)
@@ -122,14 +192,15 @@ pub fn base64_decode(s: &str) -> Option<String> {
 }
 
 /// Collect embedded data from all PDFs in a folder
-/// PDFに解析結果を埋め込む（コマンド）
+/// PDFに解析結果を埋め込む（コマンド、保存先設定に従う）
 #[tauri::command]
 pub fn embed_pdf_result(path: String, result: String) -> Result<(), String> {
-    embed_result_in_pdf(&path, &result)
+    store_result(&path, &result, "").map(|_| ())
 }
 
-/// PDFから解析結果を読み取る（コマンド）
+/// PDFから解析結果を読み取る（コマンド、サイドカーにもフォールバック）
 #[tauri::command]
 pub fn read_pdf_result(path: String) -> Option<(String, String)> {
-    read_result_from_pdf(&path)
+    record_access("read_pdf_result", &path);
+    load_result_data(&path).map(|data| (data.result, data.date))
 }
@@ -0,0 +1,194 @@
+//! Local PDF text extraction (born-digital PDFs only, no OCR)
+
+use std::path::Path;
+
+use lopdf::Document;
+
+/// Extract the text of each page, in page order
+pub fn extract_page_texts(pdf_path: &str) -> Result<Vec<String>, String> {
+    let doc = Document::load(pdf_path).map_err(|e| format!("PDF読み込みエラー: {}", e))?;
+    Ok(doc
+        .get_pages()
+        .keys()
+        .map(|page| doc.extract_text(&[*page]).unwrap_or_default())
+        .collect())
+}
+
+/// Main language of a document's text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentLanguage {
+    Japanese,
+    English,
+    Vietnamese,
+}
+
+impl DocumentLanguage {
+    /// Japanese name used in prompts
+    pub fn label(self) -> &'static str {
+        match self {
+            DocumentLanguage::Japanese => "日本語",
+            DocumentLanguage::English => "英語",
+            DocumentLanguage::Vietnamese => "ベトナム語",
+        }
+    }
+}
+
+/// Vietnamese-only letters (ă â đ ê ô ơ ư and precomposed tone marks)
+fn is_vietnamese_letter(c: char) -> bool {
+    matches!(c, 'ă' | 'â' | 'đ' | 'ê' | 'ô' | 'ơ' | 'ư' | 'Ă' | 'Â' | 'Đ' | 'Ê' | 'Ô' | 'Ơ' | 'Ư')
+        || ('\u{1EA0}'..='\u{1EF9}').contains(&c)
+}
+
+/// Detect the main language from extracted text
+///
+/// Japanese unless Latin letters clearly dominate (one kana/kanji carries
+/// about as much as two Latin letters); too little text counts as Japanese.
+pub fn detect_language(text: &str) -> DocumentLanguage {
+    let mut japanese = 0usize;
+    let mut latin = 0usize;
+    let mut vietnamese = 0usize;
+    for c in text.chars() {
+        if ('\u{3040}'..='\u{30FF}').contains(&c) || ('\u{4E00}'..='\u{9FFF}').contains(&c) {
+            japanese += 1;
+        } else if is_vietnamese_letter(c) {
+            vietnamese += 1;
+            latin += 1;
+        } else if c.is_alphabetic() && (c.is_ascii() || ('\u{00C0}'..='\u{024F}').contains(&c)) {
+            latin += 1;
+        }
+    }
+    if latin < MIN_TEXT_CHARS_PER_PAGE || japanese * 2 >= latin {
+        DocumentLanguage::Japanese
+    } else if vietnamese * 30 >= latin {
+        DocumentLanguage::Vietnamese
+    } else {
+        DocumentLanguage::English
+    }
+}
+
+/// Pages averaging fewer extracted chars than this are treated as scanned
+pub const MIN_TEXT_CHARS_PER_PAGE: usize = 50;
+
+/// Number of non-whitespace chars
+pub fn meaningful_char_count(text: &str) -> usize {
+    text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+/// Whether extraction yielded enough text to analyze without vision
+pub fn has_enough_text(pages: &[String]) -> bool {
+    let total: usize = pages.iter().map(|p| meaningful_char_count(p)).sum();
+    !pages.is_empty() && total >= MIN_TEXT_CHARS_PER_PAGE * pages.len()
+}
+
+/// Whether a page has extractable text (otherwise it is treated as scanned)
+pub fn is_text_page(text: &str) -> bool {
+    meaningful_char_count(text) >= MIN_TEXT_CHARS_PER_PAGE
+}
+
+/// How a document is sent to the model
+#[derive(Debug, PartialEq)]
+pub enum DocumentRoute {
+    /// Every page has text: text-only prompt (page number, text)
+    Text(Vec<(u32, String)>),
+    /// Mixed: text of the text pages, scanned pages (1-based) attached as a PDF
+    Hybrid {
+        text_pages: Vec<(u32, String)>,
+        scanned_pages: Vec<u32>,
+    },
+    /// Scanned document: the whole PDF is attached
+    Vision,
+}
+
+/// Route a document by classifying each page as text or scanned
+pub fn route_pages(pages: Vec<String>) -> DocumentRoute {
+    let mut text_pages = Vec::new();
+    let mut scanned_pages = Vec::new();
+    for (i, text) in pages.into_iter().enumerate() {
+        let number = i as u32 + 1;
+        if is_text_page(&text) {
+            text_pages.push((number, text));
+        } else {
+            scanned_pages.push(number);
+        }
+    }
+
+    if text_pages.is_empty() {
+        DocumentRoute::Vision
+    } else if scanned_pages.is_empty() {
+        DocumentRoute::Text(text_pages)
+    } else {
+        DocumentRoute::Hybrid {
+            text_pages,
+            scanned_pages,
+        }
+    }
+}
+
+/// Write a copy of the PDF containing only the given pages (1-based)
+pub fn extract_pages_to_pdf(pdf_path: &str, pages: &[u32], dest: &Path) -> Result<(), String> {
+    let mut doc = Document::load(pdf_path).map_err(|e| format!("PDF読み込みエラー: {}", e))?;
+    let delete: Vec<u32> = doc
+        .get_pages()
+        .keys()
+        .copied()
+        .filter(|page| !pages.contains(page))
+        .collect();
+    doc.delete_pages(&delete);
+    doc.prune_objects();
+    doc.save(dest)
+        .map(|_| ())
+        .map_err(|e| format!("PDF保存エラー: {}", e))
+}
+
+/// Extract the text of the whole document
+pub fn extract_pdf_text(pdf_path: &str) -> Result<String, String> {
+    Ok(extract_page_texts(pdf_path)?.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_language_by_script() {
+        assert_eq!(
+            detect_language("工事請負契約書 発注者 株式会社○○ 受注者 △△建設 Tel 03-1234-5678"),
+            DocumentLanguage::Japanese
+        );
+        assert_eq!(
+            detect_language("Safety training record. Attendees signed the daily toolbox meeting sheet before work."),
+            DocumentLanguage::English
+        );
+        assert_eq!(
+            detect_language("Biên bản huấn luyện an toàn lao động cho công nhân trước khi bắt đầu công việc"),
+            DocumentLanguage::Vietnamese
+        );
+        assert_eq!(detect_language("No. 12"), DocumentLanguage::Japanese);
+    }
+
+    #[test]
+    fn has_enough_text_requires_average_chars_per_page() {
+        let full = "請負代金額".repeat(20);
+        assert!(has_enough_text(&[full.clone(), full.clone()]));
+        assert!(!has_enough_text(&[full[..30].to_string(), " \n ".to_string()]));
+        assert!(!has_enough_text(&[]));
+        assert_eq!(meaningful_char_count(" 工 事\n"), 2);
+    }
+
+    #[test]
+    fn route_pages_splits_text_and_scanned_pages() {
+        let text = "請負代金額".repeat(20);
+        assert_eq!(
+            route_pages(vec![text.clone(), text.clone()]),
+            DocumentRoute::Text(vec![(1, text.clone()), (2, text.clone())])
+        );
+        assert_eq!(route_pages(vec![String::new(), " ".to_string()]), DocumentRoute::Vision);
+        assert_eq!(
+            route_pages(vec![text.clone(), String::new(), text.clone()]),
+            DocumentRoute::Hybrid {
+                text_pages: vec![(1, text.clone()), (3, text)],
+                scanned_pages: vec![2],
+            }
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Project post-processing rules for model output
+//!
+//! Applied in order to every analysis result of the project before it is
+//! stored, e.g. to normalize 全角 digits or to enforce the company's standard
+//! terminology. The rules are part of the project settings (`output_rules`).
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::access::{ensure_allowed, Operation};
+use crate::project_settings::{load_project_settings, save_project_settings};
+
+/// A post-processing rule
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OutputRule {
+    /// Literal find/replace (e.g. 工事請負契約書 → 請負契約書)
+    Replace { find: String, replace: String },
+    /// Regex replace, `$1` etc. refer to groups
+    Regex { pattern: String, replace: String },
+    /// ０-９ → 0-9
+    HalfwidthDigits,
+    /// Fullwidth digits, Latin letters and the space → halfwidth
+    HalfwidthAlphanumeric,
+}
+
+fn to_halfwidth(c: char, letters: bool) -> char {
+    match c {
+        '０'..='９' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        'Ａ'..='Ｚ' | 'ａ'..='ｚ' if letters => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' if letters => ' ',
+        _ => c,
+    }
+}
+
+/// Error of a rule that cannot be applied (invalid regex)
+pub fn validate_rule(rule: &OutputRule) -> Result<(), String> {
+    match rule {
+        OutputRule::Replace { find, .. } if find.is_empty() => {
+            Err("置換ルールの検索文字列が空です".to_string())
+        }
+        OutputRule::Regex { pattern, .. } => Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("正規表現が不正です ({}): {}", pattern, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Apply the rules in order (invalid rules are skipped)
+pub fn apply_rules(text: &str, rules: &[OutputRule]) -> String {
+    let mut text = text.to_string();
+    for rule in rules {
+        text = match rule {
+            OutputRule::Replace { find, replace } if !find.is_empty() => text.replace(find, replace),
+            OutputRule::Replace { .. } => text,
+            OutputRule::Regex { pattern, replace } => match Regex::new(pattern) {
+                Ok(re) => re.replace_all(&text, replace.as_str()).to_string(),
+                Err(_) => text,
+            },
+            OutputRule::HalfwidthDigits => text.chars().map(|c| to_halfwidth(c, false)).collect(),
+            OutputRule::HalfwidthAlphanumeric => {
+                text.chars().map(|c| to_halfwidth(c, true)).collect()
+            }
+        };
+    }
+    text
+}
+
+/// Result post-processed with the rules of a project
+pub fn apply_output_rules(project_folder: &str, result: String) -> String {
+    let rules = load_project_settings(project_folder).output_rules;
+    if rules.is_empty() {
+        result
+    } else {
+        apply_rules(&result, &rules)
+    }
+}
+
+/// 解析結果の後処理ルールを設定（上から順に適用、空で無効）
+#[tauri::command]
+pub fn set_output_rules(folder: String, rules: Vec<OutputRule>) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    for rule in &rules {
+        validate_rule(rule)?;
+    }
+    let mut settings = load_project_settings(&folder);
+    settings.output_rules = rules;
+    save_project_settings(&folder, &settings)
+}
+
+/// 後処理ルールの適用結果を確認（保存はしない）
+#[tauri::command]
+pub fn preview_output_rules(text: String, rules: Vec<OutputRule>) -> Result<String, String> {
+    for rule in &rules {
+        validate_rule(rule)?;
+    }
+    Ok(apply_rules(&text, &rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_are_applied_in_order() {
+        let rules = vec![
+            OutputRule::HalfwidthDigits,
+            OutputRule::Replace {
+                find: "請負代金額".to_string(),
+                replace: "契約金額".to_string(),
+            },
+            OutputRule::Regex {
+                pattern: r"(\d+)円".to_string(),
+                replace: "¥$1".to_string(),
+            },
+        ];
+        let text = "⚠ 請負代金額が１２０００円と記載　ＡＢＣ工区";
+        assert_eq!(apply_rules(text, &rules), "⚠ 契約金額が¥12000と記載　ＡＢＣ工区");
+        assert_eq!(
+            apply_rules(text, &[OutputRule::HalfwidthAlphanumeric]),
+            "⚠ 請負代金額が12000円と記載 ABC工区"
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected_and_skipped() {
+        let broken = OutputRule::Regex {
+            pattern: "(".to_string(),
+            replace: String::new(),
+        };
+        assert!(validate_rule(&broken).is_err());
+        assert!(validate_rule(&OutputRule::Replace {
+            find: String::new(),
+            replace: "x".to_string()
+        })
+        .is_err());
+        assert_eq!(apply_rules("そのまま", &[broken]), "そのまま");
+    }
+}
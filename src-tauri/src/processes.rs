@@ -0,0 +1,327 @@
+//! Supervision of spawned child processes
+//!
+//! Every gemini/powershell/npm process the app starts is registered by PID
+//! while it runs, so it can be killed on request or when the app exits
+//! instead of being left running in the background. Children with a time
+//! limit are waited on with `wait_with_timeout`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Output};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use chrono::Local;
+use serde::Serialize;
+
+#[cfg(target_os = "windows")]
+use crate::CREATE_NO_WINDOW;
+
+/// How often a running child is checked against its timeout
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Receiver of the stdout lines of a running child
+pub type LineSink = Box<dyn FnMut(&str) + Send>;
+
+// Global state for running child processes
+static CHILDREN: Mutex<Option<HashMap<u32, ChildInfo>>> = Mutex::new(None);
+
+thread_local! {
+    /// Scope (e.g. batch ID) given to children started on this thread
+    static CHILD_SCOPE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// A running child process
+#[derive(Clone, Serialize)]
+pub struct ChildInfo {
+    pub pid: u32,
+    pub label: String,
+    pub started_at: String,
+    /// Batch the process belongs to, if started within `with_child_scope`
+    pub scope: Option<String>,
+}
+
+/// Keeps a child registered until dropped (process finished)
+pub struct ChildGuard(u32);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let mut children = CHILDREN.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(map) = children.as_mut() {
+            map.remove(&self.0);
+        }
+    }
+}
+
+/// Register a spawned child process
+pub fn register_child(pid: u32, label: &str) -> ChildGuard {
+    let info = ChildInfo {
+        pid,
+        label: label.to_string(),
+        started_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        scope: CHILD_SCOPE.with(|scope| scope.borrow().clone()),
+    };
+    let mut children = CHILDREN.lock().unwrap_or_else(|e| e.into_inner());
+    children.get_or_insert_with(HashMap::new).insert(pid, info);
+    ChildGuard(pid)
+}
+
+/// Restores the previous scope of the thread when dropped
+struct ScopeReset(Option<String>);
+
+impl Drop for ScopeReset {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CHILD_SCOPE.with(|scope| *scope.borrow_mut() = previous);
+    }
+}
+
+/// Run `f` with the children it starts tagged with `scope`
+pub fn with_child_scope<R>(scope: &str, f: impl FnOnce() -> R) -> R {
+    let previous = CHILD_SCOPE.with(|s| s.replace(Some(scope.to_string())));
+    let _reset = ScopeReset(previous);
+    f()
+}
+
+/// Scope of the children started on this thread
+pub fn current_child_scope() -> Option<String> {
+    CHILD_SCOPE.with(|scope| scope.borrow().clone())
+}
+
+/// Currently running child processes
+pub fn running_children() -> Vec<ChildInfo> {
+    let children = CHILDREN.lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<ChildInfo> = children
+        .as_ref()
+        .map(|map| map.values().cloned().collect())
+        .unwrap_or_default();
+    list.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    list
+}
+
+/// Put the child in its own process group so the whole tree can be killed
+pub fn isolate_process_group(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt as _;
+        cmd.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// Whether a process with the PID is running (e.g. another instance)
+pub fn is_process_alive(pid: u32) -> bool {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("tasklist");
+        cmd.args(["/FI", format!("PID eq {}", pid).as_str(), "/NH", "/FO", "CSV"]);
+        cmd
+    } else {
+        // Signal 0 only checks that the process exists
+        let mut cmd = Command::new("kill");
+        cmd.args(["-0", pid.to_string().as_str()]);
+        cmd
+    };
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    match cmd.output() {
+        // tasklist succeeds without a match too; a match lists the PID
+        Ok(output) if cfg!(target_os = "windows") => {
+            String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+        }
+        Ok(output) => output.status.success(),
+        Err(_) => false,
+    }
+}
+
+/// Kill a process and its descendants (the CLI runs node under cmd.exe)
+pub fn kill_process_tree(pid: u32) -> bool {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/PID", pid.to_string().as_str(), "/T", "/F"]);
+        cmd
+    } else {
+        // Negative PID: the process group created by `isolate_process_group`
+        let mut cmd = Command::new("kill");
+        cmd.args(["-KILL", format!("-{}", pid).as_str()]);
+        cmd
+    };
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    cmd.output().map(|o| o.status.success()).unwrap_or(false)
+}
+
+/// Kill all registered child processes; returns how many were killed
+pub fn kill_all_children() -> usize {
+    running_children()
+        .iter()
+        .filter(|child| kill_process_tree(child.pid))
+        .count()
+}
+
+/// Kill the registered children of a scope; returns how many were killed
+pub fn kill_children_in_scope(scope: &str) -> usize {
+    running_children()
+        .iter()
+        .filter(|child| child.scope.as_deref() == Some(scope))
+        .filter(|child| kill_process_tree(child.pid))
+        .count()
+}
+
+/// 実行中の外部プロセス（gemini など）をすべて停止
+#[tauri::command]
+pub fn kill_all_background_work() -> usize {
+    kill_all_children()
+}
+
+/// 実行中の外部プロセスの一覧
+#[tauri::command]
+pub fn get_child_processes() -> Vec<ChildInfo> {
+    running_children()
+}
+
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+/// Read a pipe to the end, passing every line to `on_line` as it arrives
+fn read_lines(pipe: impl Read + Send + 'static, mut on_line: LineSink) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut bytes = Vec::new();
+        let mut line = Vec::new();
+        while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+            on_line(&String::from_utf8_lossy(&line));
+            bytes.append(&mut line);
+        }
+        bytes
+    })
+}
+
+/// Wait for the child, killing its process tree when the timeout passes
+///
+/// Used for the CLI runs and the hooks. After a kill the output read until then is discarded except for stderr,
+/// which is returned as the error. `on_line` gets the stdout lines while the
+/// child runs.
+pub fn wait_with_timeout(
+    mut child: Child,
+    timeout: Option<Duration>,
+    on_line: Option<LineSink>,
+) -> std::io::Result<Result<Output, String>> {
+    if timeout.is_none() && on_line.is_none() {
+        return child.wait_with_output().map(Ok);
+    }
+    let stdout = child.stdout.take().map(|pipe| match on_line {
+        Some(on_line) => read_lines(pipe, on_line),
+        None => read_pipe(pipe),
+    });
+    let stderr = child.stderr.take().map(read_pipe);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            kill_process_tree(child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(WAIT_POLL_INTERVAL);
+    };
+    let collect = |pipe: Option<JoinHandle<Vec<u8>>>| {
+        pipe.and_then(|handle| handle.join().ok()).unwrap_or_default()
+    };
+    let (stdout, stderr) = (collect(stdout), collect(stderr));
+    Ok(match status {
+        Some(status) => Ok(Output { status, stdout, stderr }),
+        None => Err(String::from_utf8_lossy(&stderr).to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    #[test]
+    fn child_is_unregistered_when_guard_drops() {
+        let guard = register_child(u32::MAX - 1, "test");
+        assert!(running_children().iter().any(|c| c.pid == u32::MAX - 1));
+        drop(guard);
+        assert!(!running_children().iter().any(|c| c.pid == u32::MAX - 1));
+    }
+
+    #[test]
+    fn children_are_tagged_with_thread_scope() {
+        let guard = with_child_scope("batch-1", || register_child(u32::MAX - 2, "test"));
+        let unscoped = register_child(u32::MAX - 3, "test");
+        let children = running_children();
+        let scope_of = |pid| children.iter().find(|c| c.pid == pid).and_then(|c| c.scope.clone());
+        assert_eq!(scope_of(u32::MAX - 2).as_deref(), Some("batch-1"));
+        assert_eq!(scope_of(u32::MAX - 3), None);
+        drop((guard, unscoped));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_process_tree_stops_child() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+        isolate_process_group(&mut cmd);
+        let mut child = cmd.spawn().expect("spawn sleep");
+        let _guard = register_child(child.id(), "sleep");
+
+        assert!(kill_process_tree(child.id()));
+        let status = child.wait().expect("wait");
+        assert!(!status.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_with_timeout_kills_a_hung_process() {
+        let spawn = |secs: &str| {
+            let mut cmd = Command::new("sleep");
+            cmd.arg(secs).stdout(Stdio::piped()).stderr(Stdio::piped());
+            isolate_process_group(&mut cmd);
+            cmd.spawn().expect("spawn sleep")
+        };
+        let started = Instant::now();
+        let waited = wait_with_timeout(spawn("30"), Some(Duration::from_millis(300)), None).unwrap();
+        assert!(waited.is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let waited = wait_with_timeout(spawn("0"), Some(Duration::from_secs(10)), None).unwrap();
+        assert!(waited.unwrap().status.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streamed_lines_arrive_before_the_output_is_returned() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf '## 書類タイプ\\n請求書\\n'; printf '⚠ 押印なし'"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let lines = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = {
+            let lines = lines.clone();
+            Box::new(move |line: &str| lines.lock().unwrap().push(line.to_string())) as LineSink
+        };
+        let output = wait_with_timeout(cmd.spawn().expect("spawn sh"), None, Some(sink))
+            .unwrap()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "## 書類タイプ\n請求書\n⚠ 押印なし");
+        assert_eq!(*lines.lock().unwrap(), vec!["## 書類タイプ\n", "請求書\n", "⚠ 押印なし"]);
+    }
+}
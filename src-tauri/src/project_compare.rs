@@ -0,0 +1,313 @@
+//! Comparison of two project folders (変更前/変更後)
+//!
+//! For 設計変更 paperwork the documents of the original and the changed
+//! folder are paired by content hash, then by document type and file name.
+//! Paired documents with different content are reported with the fields
+//! that differ: the classification (type, date, parties), the amounts in
+//! the text and the findings of the stored results. No model is called;
+//! only stored classifications and results are used.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::archive::{file_sha256, list_pdfs, name_similarity, MIN_NAME_SIMILARITY};
+use crate::classify::load_stored_metadata;
+use crate::database::open_db;
+use crate::freshness::findings_of;
+use crate::guidelines::detect_document_type_in;
+use crate::pdf_text::extract_pdf_text;
+use crate::result_store::load_result_data;
+use crate::tasks::run_blocking;
+
+/// Compared fields of a document (field name → value)
+pub type DocumentFields = BTreeMap<String, String>;
+
+/// A document of one folder with the fields used for comparison
+#[derive(Clone, Debug)]
+pub struct ProjectDocument {
+    pub file_name: String,
+    pub sha256: String,
+    pub doc_type: Option<String>,
+    pub fields: DocumentFields,
+}
+
+/// A field that differs between the paired documents
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    /// None: the field is only in the changed document
+    pub before: Option<String>,
+    /// None: the field is only in the original document
+    pub after: Option<String>,
+}
+
+/// A document present in both folders with different content
+#[derive(Clone, Serialize)]
+pub struct DocumentChange {
+    pub before: String,
+    pub after: String,
+    pub doc_type: Option<String>,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Clone, Serialize, Default)]
+pub struct ProjectComparison {
+    pub folder_a: String,
+    pub folder_b: String,
+    /// Only in folder B
+    pub added: Vec<String>,
+    /// Only in folder A
+    pub removed: Vec<String>,
+    pub changed: Vec<DocumentChange>,
+    /// Same content in both folders
+    pub unchanged: Vec<String>,
+}
+
+/// Yen amounts in a text, normalized to ASCII digits without separators
+pub fn amounts_in(text: &str) -> Vec<String> {
+    let re = Regex::new(r"([0-9０-９][0-9０-９,，]*)\s*円").expect("valid regex");
+    let mut amounts: Vec<String> = Vec::new();
+    for cap in re.captures_iter(text) {
+        let digits: String = cap[1]
+            .chars()
+            .filter_map(|c| match c {
+                '0'..='9' => Some(c),
+                '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32),
+                _ => None,
+            })
+            .collect();
+        let amount = format!("{}円", digits);
+        if !amounts.contains(&amount) {
+            amounts.push(amount);
+        }
+    }
+    amounts
+}
+
+/// Load a folder's documents with their comparison fields
+fn load_documents(folder: &str) -> Vec<ProjectDocument> {
+    let conn = open_db().ok();
+    list_pdfs(Path::new(folder))
+        .into_iter()
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_string_lossy().to_string();
+            let path_str = path.to_string_lossy().to_string();
+            let sha256 = file_sha256(&path).ok()?;
+            let metadata = conn
+                .as_ref()
+                .and_then(|c| load_stored_metadata(c, &path_str, &sha256))
+                .unwrap_or_default();
+
+            let doc_type = metadata
+                .doc_type
+                .clone()
+                .or_else(|| detect_document_type_in(folder, &file_name).into_iter().next());
+            let mut fields = DocumentFields::new();
+            if let Some(doc_type) = &doc_type {
+                fields.insert("種類".to_string(), doc_type.clone());
+            }
+            if let Some(date) = metadata.date {
+                fields.insert("日付".to_string(), date);
+            }
+            if !metadata.parties.is_empty() {
+                fields.insert("当事者".to_string(), metadata.parties.join("、"));
+            }
+            let amounts = extract_pdf_text(&path_str)
+                .map(|text| amounts_in(&text))
+                .unwrap_or_default();
+            if !amounts.is_empty() {
+                fields.insert("金額".to_string(), amounts.join("、"));
+            }
+            if let Some(data) = load_result_data(&path_str) {
+                let findings = findings_of(&data.result);
+                if !findings.is_empty() {
+                    fields.insert("指摘".to_string(), findings.join("\n"));
+                }
+            }
+
+            Some(ProjectDocument {
+                file_name,
+                sha256,
+                doc_type,
+                fields,
+            })
+        })
+        .collect()
+}
+
+/// Fields whose values differ (or exist on one side only)
+pub fn diff_fields(before: &DocumentFields, after: &DocumentFields) -> Vec<FieldChange> {
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| before.get(*name) != after.get(*name))
+        .map(|name| FieldChange {
+            field: name.clone(),
+            before: before.get(name).cloned(),
+            after: after.get(name).cloned(),
+        })
+        .collect()
+}
+
+/// Pair score of two documents, None if they should not be paired
+///
+/// Documents of different known types are never paired; a type that occurs
+/// once on each side pairs regardless of the names (e.g. 契約書 and
+/// 変更契約書).
+fn pair_score(a: &ProjectDocument, b: &ProjectDocument, unique_type: bool) -> Option<f64> {
+    let similarity = name_similarity(&a.file_name, &b.file_name);
+    match (&a.doc_type, &b.doc_type) {
+        (Some(ta), Some(tb)) if ta != tb => None,
+        (Some(_), Some(_)) if unique_type => Some(similarity + 1.0),
+        _ => (similarity >= MIN_NAME_SIMILARITY).then_some(similarity),
+    }
+}
+
+/// Compare the documents of two folders
+pub fn compare_documents(
+    folder_a: &str,
+    folder_b: &str,
+    docs_a: &[ProjectDocument],
+    docs_b: &[ProjectDocument],
+) -> ProjectComparison {
+    let mut report = ProjectComparison {
+        folder_a: folder_a.to_string(),
+        folder_b: folder_b.to_string(),
+        ..Default::default()
+    };
+    let mut left: Vec<&ProjectDocument> = Vec::new();
+    let mut right: Vec<&ProjectDocument> = docs_b.iter().collect();
+
+    // Same content first
+    for a in docs_a {
+        match right.iter().position(|b| b.sha256 == a.sha256) {
+            Some(i) => {
+                right.remove(i);
+                report.unchanged.push(a.file_name.clone());
+            }
+            None => left.push(a),
+        }
+    }
+
+    // Then the best remaining pairs by type and name
+    let type_count = |docs: &[&ProjectDocument], t: &Option<String>| {
+        docs.iter().filter(|d| t.is_some() && d.doc_type == *t).count()
+    };
+    let mut candidates: Vec<(usize, usize, f64)> = Vec::new();
+    for (i, a) in left.iter().enumerate() {
+        for (j, b) in right.iter().enumerate() {
+            let unique_type =
+                type_count(&left, &a.doc_type) == 1 && type_count(&right, &b.doc_type) == 1;
+            if let Some(score) = pair_score(a, b, unique_type) {
+                candidates.push((i, j, score));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.2.total_cmp(&x.2));
+
+    let mut used_a = vec![false; left.len()];
+    let mut used_b = vec![false; right.len()];
+    for (i, j, _) in candidates {
+        if used_a[i] || used_b[j] {
+            continue;
+        }
+        used_a[i] = true;
+        used_b[j] = true;
+        let (a, b) = (left[i], right[j]);
+        report.changed.push(DocumentChange {
+            before: a.file_name.clone(),
+            after: b.file_name.clone(),
+            doc_type: b.doc_type.clone().or_else(|| a.doc_type.clone()),
+            changes: diff_fields(&a.fields, &b.fields),
+        });
+    }
+
+    report.removed = left
+        .iter()
+        .zip(&used_a)
+        .filter(|(_, used)| !**used)
+        .map(|(d, _)| d.file_name.clone())
+        .collect();
+    report.added = right
+        .iter()
+        .zip(&used_b)
+        .filter(|(_, used)| !**used)
+        .map(|(d, _)| d.file_name.clone())
+        .collect();
+    report
+}
+
+/// 変更前・変更後の2つのフォルダの書類を照合し、追加・削除・変更された書類と項目の差分を返す
+#[tauri::command]
+pub async fn compare_projects(folder_a: String, folder_b: String) -> Result<ProjectComparison, String> {
+    for folder in [&folder_a, &folder_b] {
+        if !Path::new(folder).is_dir() {
+            return Err(format!("フォルダが見つかりません: {}", folder));
+        }
+    }
+    let label = format!("{} ⇔ {}", folder_a, folder_b);
+    run_blocking("compare_projects", &label, move || {
+        let docs_a = load_documents(&folder_a);
+        let docs_b = load_documents(&folder_b);
+        compare_documents(&folder_a, &folder_b, &docs_a, &docs_b)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(name: &str, hash: &str, doc_type: Option<&str>, fields: &[(&str, &str)]) -> ProjectDocument {
+        ProjectDocument {
+            file_name: name.to_string(),
+            sha256: hash.to_string(),
+            doc_type: doc_type.map(str::to_string),
+            fields: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn amounts_are_normalized() {
+        assert_eq!(
+            amounts_in("請負代金額 １，１００，０００円（うち消費税 100,000 円）"),
+            vec!["1100000円".to_string(), "100000円".to_string()]
+        );
+    }
+
+    #[test]
+    fn compare_documents_pairs_by_hash_type_and_name() {
+        let a = vec![
+            doc("工程表.pdf", "h1", None, &[]),
+            doc("契約書.pdf", "h2", Some("契約書"), &[("金額", "1000000円")]),
+            doc("施工計画書.pdf", "h3", None, &[]),
+        ];
+        let b = vec![
+            doc("工程表_コピー.pdf", "h1", None, &[]),
+            doc("変更契約書.pdf", "h4", Some("契約書"), &[("金額", "1200000円")]),
+            doc("交通誘導員配置実績.pdf", "h5", Some("交通誘導員配置実績"), &[]),
+        ];
+        let report = compare_documents("a", "b", &a, &b);
+        assert_eq!(report.unchanged, vec!["工程表.pdf".to_string()]);
+        assert_eq!(report.changed.len(), 1);
+        assert_eq!(report.changed[0].after, "変更契約書.pdf");
+        assert_eq!(
+            report.changed[0].changes,
+            vec![FieldChange {
+                field: "金額".to_string(),
+                before: Some("1000000円".to_string()),
+                after: Some("1200000円".to_string()),
+            }]
+        );
+        assert_eq!(report.removed, vec!["施工計画書.pdf".to_string()]);
+        assert_eq!(report.added, vec!["交通誘導員配置実績.pdf".to_string()]);
+    }
+}
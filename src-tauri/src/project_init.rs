@@ -0,0 +1,215 @@
+//! Automatic setup of new project folders
+//!
+//! When a 工事 subfolder appears under the watch folder (created, or
+//! renamed to its final name) it is initialized: guidelines are seeded from
+//! the template, the project settings file and an (empty) history are
+//! created, and the frontend is told with a `new-project-detected` event.
+//! A project renamed after its setup keeps its settings file, which moves
+//! with the folder, and gets a history under its new name.
+//!
+//! The template is `.guidelines.json` in the watch folder when present,
+//! otherwise a built-in set of basic checkpoints.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::Local;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::events::emit_log;
+use crate::guidelines::{get_guidelines_path, Guidelines};
+use crate::history::{history_exists, save_history, AnalysisHistory};
+use crate::messages::tr;
+use crate::project_settings::{get_project_settings_path, load_project_settings, save_project_settings};
+use crate::settings::ProjectRootStrategy;
+
+/// Built-in guidelines for projects without a template
+pub fn default_guidelines_template() -> Guidelines {
+    let categories = [
+        ("契約書", vec!["収入印紙の貼付と金額", "契約日と工期の前後関係"]),
+        ("見積書", vec!["見積有効期限の記載", "数量×単価と金額の一致"]),
+        ("請求書", vec!["請求金額と契約・見積金額の一致", "振込先の記載"]),
+        ("交通誘導員", vec!["配置人数と伝票枚数の一致"]),
+        ("測量図面", vec!["測点間距離の累計と延長の一致"]),
+        ("施工計画", vec!["工程表と契約工期の一致"]),
+    ];
+    Guidelines {
+        categories: categories
+            .into_iter()
+            .map(|(doc_type, items)| {
+                (doc_type.to_string(), items.into_iter().map(str::to_string).collect())
+            })
+            .collect::<HashMap<_, _>>(),
+        common: vec!["和暦と西暦の混在に注意".to_string()],
+    }
+}
+
+/// Whether a folder created under the watch root is a project folder
+///
+/// Projects are the direct subfolders of the watch root, or the folders at
+/// the configured depth with the `Depth` strategy. Hidden folders (app data)
+/// are never projects, and with the `Marker` strategy the user marks
+/// projects explicitly.
+pub fn is_project_dir(dir: &Path, watch_root: &Path, strategy: &ProjectRootStrategy) -> bool {
+    let Ok(relative) = dir.strip_prefix(watch_root) else {
+        return false;
+    };
+    if matches!(strategy, ProjectRootStrategy::Marker { .. }) {
+        return false;
+    }
+    let hidden = relative
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+    let depth = match strategy {
+        ProjectRootStrategy::Depth { depth } if *depth > 0 => *depth,
+        _ => 1,
+    };
+    !hidden && relative.components().count() == depth
+}
+
+/// Seed the project's guidelines (existing guidelines are kept)
+///
+/// Returns whether a file was written.
+pub fn seed_guidelines(folder: &Path, template: Option<&Path>) -> Result<bool, String> {
+    let path = get_guidelines_path(&folder.to_string_lossy());
+    if path.exists() {
+        return Ok(false);
+    }
+    let guidelines = template
+        .and_then(|t| fs::read_to_string(t).ok())
+        .and_then(|s| serde_json::from_str::<Guidelines>(&s).ok())
+        .unwrap_or_else(default_guidelines_template);
+    let json = serde_json::to_string_pretty(&guidelines).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("ガイドライン保存エラー: {}", e))?;
+    Ok(true)
+}
+
+/// Create the project settings file (no-op for known projects)
+///
+/// Returns false when the folder was already a project.
+pub fn create_project_record(folder: &Path) -> Result<bool, String> {
+    let folder = folder.to_string_lossy().to_string();
+    if get_project_settings_path(&folder).exists() {
+        return Ok(false);
+    }
+    let mut settings = load_project_settings(&folder);
+    settings.initialized_at = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+    save_project_settings(&folder, &settings)?;
+    Ok(true)
+}
+
+#[derive(Clone, Serialize)]
+pub struct NewProjectEvent {
+    pub folder: String,
+    pub name: String,
+    pub guidelines_seeded: bool,
+}
+
+/// Initialize a new (or renamed) project folder and notify the frontend
+pub fn initialize_project(app: &AppHandle, folder: &Path, watch_root: &Path) {
+    let created = match create_project_record(folder) {
+        Ok(created) => created,
+        Err(e) => {
+            emit_log(app, &tr("project.init_error", &[&folder.display(), &e]), "error");
+            return;
+        }
+    };
+    let folder_str = folder.to_string_lossy().to_string();
+    let has_history = history_exists(&folder_str);
+    // Known projects have a history under their path; a renamed one does not
+    if !created && !matches!(has_history, Ok(false)) {
+        return;
+    }
+    let template = watch_root.join(".guidelines.json");
+    let guidelines_seeded = seed_guidelines(folder, Some(&template).filter(|t| t.is_file())).unwrap_or(false);
+
+    // An empty history makes the project appear in summaries and scheduled tasks.
+    // Only when the store says there is none: a failed read must not be
+    // overwritten with an empty history
+    match has_history {
+        Ok(false) => {
+            let empty = AnalysisHistory {
+                project_folder: folder_str.clone(),
+                ..Default::default()
+            };
+            if let Err(e) = save_history(&empty) {
+                emit_log(app, &tr("project.init_error", &[&folder.display(), &e]), "error");
+            }
+        }
+        Ok(true) => {}
+        Err(e) => emit_log(app, &tr("project.init_error", &[&folder.display(), &e]), "error"),
+    }
+
+    let name = folder
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| folder_str.clone());
+    emit_log(app, &tr("project.detected", &[&name]), "info");
+    let _ = app.emit(
+        "new-project-detected",
+        NewProjectEvent {
+            folder: folder_str.clone(),
+            name: name.clone(),
+            guidelines_seeded,
+        },
+    );
+    let _ = app.emit(
+        "show-notification",
+        serde_json::json!({
+            "title": tr("project.detected_title", &[]),
+            "body": name,
+            "path": folder_str
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn is_project_dir_follows_the_root_strategy() {
+        let root = Path::new("/watch");
+        let parent = ProjectRootStrategy::Parent;
+        assert!(is_project_dir(Path::new("/watch/市道1号線"), root, &parent));
+        assert!(!is_project_dir(Path::new("/watch/市道1号線/契約"), root, &parent));
+        assert!(!is_project_dir(Path::new("/watch/.shoruichecker_seals"), root, &parent));
+        assert!(!is_project_dir(Path::new("/other/x"), root, &parent));
+
+        let depth = ProjectRootStrategy::Depth { depth: 2 };
+        assert!(is_project_dir(Path::new("/watch/2026/市道1号線"), root, &depth));
+        assert!(!is_project_dir(Path::new("/watch/2026"), root, &depth));
+
+        let marker = ProjectRootStrategy::Marker { marker: "工事.txt".to_string() };
+        assert!(!is_project_dir(Path::new("/watch/市道1号線"), root, &marker));
+    }
+
+    #[test]
+    fn new_project_gets_record_and_template_once() {
+        let root = create_temp_dir(".shoruichecker_test_projects").expect("create dir");
+        let project = root.join("市道1号線");
+        fs::create_dir_all(&project).unwrap();
+
+        assert!(create_project_record(&project).unwrap());
+        assert!(!create_project_record(&project).unwrap());
+        assert!(load_project_settings(&project.to_string_lossy()).initialized_at.is_some());
+
+        let template = root.join(".guidelines.json");
+        fs::write(&template, r#"{"categories": {"契約書": ["独自の確認"]}, "common": []}"#).unwrap();
+        assert!(seed_guidelines(&project, Some(&template)).unwrap());
+        assert!(!seed_guidelines(&project, None).unwrap());
+        let seeded = fs::read_to_string(get_guidelines_path(&project.to_string_lossy())).unwrap();
+        assert!(seeded.contains("独自の確認"));
+
+        let other = root.join("県道2号線");
+        fs::create_dir_all(&other).unwrap();
+        assert!(seed_guidelines(&other, None).unwrap());
+        let seeded = fs::read_to_string(get_guidelines_path(&other.to_string_lossy())).unwrap();
+        assert!(seeded.contains("収入印紙"));
+
+        cleanup_temp_dir(&root);
+    }
+}
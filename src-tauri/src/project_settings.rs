@@ -0,0 +1,413 @@
+//! Per-project settings
+//!
+//! Stored as `.shoruichecker.json` directly under the project folder, next to
+//! `.guidelines.json`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::access::{ensure_allowed, Operation};
+use crate::database::open_db;
+use crate::file_lock::write_atomic;
+use crate::postprocess::OutputRule;
+use crate::rag::{remove_passages, KIND_CLAUSE};
+use crate::settings::{load_settings, ProjectRootStrategy};
+
+/// Settings that apply to a single project folder
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct ProjectSettings {
+    /// 原本保護: never modify source PDFs (no embed/stamp/rename)
+    #[serde(default)]
+    pub protect_originals: bool,
+    /// 承認制: 解析結果は承認（`approve_result`）されるまで履歴の下書きに留める
+    #[serde(default)]
+    pub require_approval: bool,
+    /// 定期的なガイドライン自動再生成（None で無効）
+    #[serde(default)]
+    pub auto_guidelines: Option<AutoGuidelineConfig>,
+    /// 照合用の常備参照資料（標準仕様書・単価合意書など）
+    #[serde(default)]
+    pub reference_documents: Vec<ReferenceDocument>,
+    /// 月次報告の自動作成（None で無効）
+    #[serde(default)]
+    pub monthly_report: Option<MonthlyReportConfig>,
+    /// ファイル名から書類タイプを判定する独自ルール（上から順に評価）
+    #[serde(default)]
+    pub document_type_rules: Vec<DocumentTypeRule>,
+    /// 照合モードで基準書類にする書類タイプの優先順（空なら既定の順）
+    #[serde(default)]
+    pub anchor_priority: Vec<String>,
+    /// 提出に必要な書類タイプ（提出準備度の算出に使用）
+    #[serde(default)]
+    pub required_documents: Vec<String>,
+    /// 解析結果を保存する前に上から順に適用する後処理ルール
+    #[serde(default)]
+    pub output_rules: Vec<OutputRule>,
+    /// 監視フォルダに作成されて自動で初期化された日時
+    #[serde(default)]
+    pub initialized_at: Option<String>,
+    /// 受付フォルダから振り分けた書類の解析結果を送るメールアドレス
+    #[serde(default)]
+    pub intake_notify_to: Vec<String>,
+}
+
+/// Default anchor priority: the contract is the reference for everything else
+pub const DEFAULT_ANCHOR_PRIORITY: [&str; 3] = ["契約書", "見積書", "請求書"];
+
+/// Anchor priority of a project (the default when not configured)
+pub fn anchor_priority(settings: &ProjectSettings) -> Vec<String> {
+    if settings.anchor_priority.is_empty() {
+        DEFAULT_ANCHOR_PRIORITY.iter().map(|t| t.to_string()).collect()
+    } else {
+        settings.anchor_priority.clone()
+    }
+}
+
+/// File-name rule: a regex (case-insensitive) mapped to a document type
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DocumentTypeRule {
+    pub pattern: String,
+    pub doc_type: String,
+}
+
+/// Scheduled monthly report of the previous month
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct MonthlyReportConfig {
+    /// Also export the report as PDF
+    #[serde(default)]
+    pub pdf: bool,
+    /// Recipients (empty = no mail)
+    #[serde(default)]
+    pub email_to: Vec<String>,
+    /// Last reported month, `%Y-%m`
+    #[serde(default)]
+    pub last_report_month: Option<String>,
+}
+
+/// A standing reference document included in analyses of matching types
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReferenceDocument {
+    pub path: String,
+    pub label: String,
+    /// Document types this reference applies to (empty = all)
+    #[serde(default)]
+    pub doc_types: Vec<String>,
+    /// 仕様書: cited by clause from the RAG index instead of being attached
+    #[serde(default)]
+    pub specification: bool,
+}
+
+/// Reference documents applicable to the given document types
+///
+/// Documents listed in `exclude_paths` (e.g. the analysis targets
+/// themselves) and specifications (see `spec_clauses`) are skipped.
+pub fn matching_references(
+    settings: &ProjectSettings,
+    doc_types: &[String],
+    exclude_paths: &[String],
+) -> Vec<ReferenceDocument> {
+    settings
+        .reference_documents
+        .iter()
+        .filter(|r| !r.specification && !exclude_paths.contains(&r.path))
+        .filter(|r| r.doc_types.is_empty() || r.doc_types.iter().any(|t| doc_types.contains(t)))
+        .cloned()
+        .collect()
+}
+
+/// Scheduled guideline regeneration from recent analyses
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AutoGuidelineConfig {
+    /// Regeneration interval in days
+    #[serde(default = "default_interval_days")]
+    pub interval_days: u32,
+    /// Number of most recent history entries to learn from
+    #[serde(default = "default_last_n")]
+    pub last_n: usize,
+    /// Last (attempted) regeneration, `%Y-%m-%d %H:%M:%S`
+    #[serde(default)]
+    pub last_generated_at: Option<String>,
+}
+
+fn default_interval_days() -> u32 {
+    7
+}
+
+fn default_last_n() -> usize {
+    20
+}
+
+/// Project settings file path
+pub fn get_project_settings_path(folder: &str) -> PathBuf {
+    Path::new(folder).join(".shoruichecker.json")
+}
+
+/// Load project settings (defaults if missing or malformed)
+pub fn load_project_settings(folder: &str) -> ProjectSettings {
+    fs::read_to_string(get_project_settings_path(folder))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_project_settings(folder: &str, settings: &ProjectSettings) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    write_atomic(&get_project_settings_path(folder), json)
+}
+
+/// Project folder of a document, per the configured `ProjectRootStrategy`
+pub fn project_folder_for(path: &str) -> String {
+    let settings = load_settings();
+    resolve_project_root(
+        Path::new(path),
+        &settings.project_root,
+        settings.watch_folder.as_deref().map(Path::new),
+    )
+    .to_string_lossy()
+    .to_string()
+}
+
+/// Resolve the project folder of a document
+///
+/// Falls back to the document's parent folder when the strategy does not
+/// apply (document outside the watch folder, too shallow, or no marker).
+/// Marker lookup does not go above the watch folder.
+pub fn resolve_project_root(
+    path: &Path,
+    strategy: &ProjectRootStrategy,
+    watch_folder: Option<&Path>,
+) -> PathBuf {
+    let parent = path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    match strategy {
+        ProjectRootStrategy::Parent => parent,
+        ProjectRootStrategy::Depth { depth } => {
+            let Some(root) = watch_folder else {
+                return parent;
+            };
+            let Ok(relative) = parent.strip_prefix(root) else {
+                return parent;
+            };
+            let components: Vec<_> = relative.components().collect();
+            if *depth == 0 || components.len() < *depth {
+                return parent;
+            }
+            components[..*depth]
+                .iter()
+                .fold(root.to_path_buf(), |dir, c| dir.join(c))
+        }
+        ProjectRootStrategy::Marker { marker } => {
+            for dir in parent.ancestors() {
+                if dir.join(marker).is_file() {
+                    return dir.to_path_buf();
+                }
+                if watch_folder == Some(dir) {
+                    break;
+                }
+            }
+            parent
+        }
+    }
+}
+
+/// Fail if the document belongs to a project with 原本保護 enabled
+pub fn ensure_original_modifiable(path: &str) -> Result<(), String> {
+    if load_project_settings(&project_folder_for(path)).protect_originals {
+        Err("原本保護が有効なため、元のPDFは変更できません".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn get_project_settings(folder: String) -> ProjectSettings {
+    load_project_settings(&folder)
+}
+
+#[tauri::command]
+pub fn set_project_settings(folder: String, settings: ProjectSettings) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    save_project_settings(&folder, &settings)
+}
+
+/// 参照資料の一覧
+#[tauri::command]
+pub fn list_reference_documents(folder: String) -> Vec<ReferenceDocument> {
+    load_project_settings(&folder).reference_documents
+}
+
+/// 参照資料を登録（同じパスは上書き）
+#[tauri::command]
+pub fn add_reference_document(
+    folder: String,
+    path: String,
+    label: Option<String>,
+    doc_types: Vec<String>,
+) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    if !Path::new(&path).is_file() {
+        return Err("参照資料が見つかりません".to_string());
+    }
+    let label = label.filter(|l| !l.is_empty()).unwrap_or_else(|| {
+        Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    let mut settings = load_project_settings(&folder);
+    settings.reference_documents.retain(|r| r.path != path);
+    settings.reference_documents.push(ReferenceDocument {
+        path,
+        label,
+        doc_types,
+        specification: false,
+    });
+    save_project_settings(&folder, &settings)
+}
+
+/// 書類タイプ判定ルールを設定（正規表現を検証して保存）
+#[tauri::command]
+pub fn set_document_type_rules(folder: String, rules: Vec<DocumentTypeRule>) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    for rule in &rules {
+        if rule.doc_type.trim().is_empty() {
+            return Err(format!("書類タイプが空です: {}", rule.pattern));
+        }
+        regex::Regex::new(&rule.pattern)
+            .map_err(|e| format!("正規表現が不正です（{}）: {}", rule.pattern, e))?;
+    }
+    let mut settings = load_project_settings(&folder);
+    settings.document_type_rules = rules;
+    save_project_settings(&folder, &settings)
+}
+
+/// 照合モードの基準書類の優先順を設定（空で既定に戻す）
+#[tauri::command]
+pub fn set_anchor_priority(folder: String, doc_types: Vec<String>) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    let mut settings = load_project_settings(&folder);
+    settings.anchor_priority = doc_types
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    save_project_settings(&folder, &settings)
+}
+
+/// 提出に必要な書類タイプを設定（空で必要書類の判定をしない）
+#[tauri::command]
+pub fn set_required_documents(folder: String, doc_types: Vec<String>) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    let mut settings = load_project_settings(&folder);
+    settings.required_documents = doc_types
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    save_project_settings(&folder, &settings)
+}
+
+/// 参照資料の登録を解除
+#[tauri::command]
+pub fn remove_reference_document(folder: String, path: String) -> Result<(), String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    let mut settings = load_project_settings(&folder);
+    settings.reference_documents.retain(|r| r.path != path);
+    save_project_settings(&folder, &settings)?;
+    // Its clauses are no longer put into prompts
+    remove_passages(&open_db()?, &folder, &path, KIND_CLAUSE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn protected_project_rejects_modification() {
+        let dir = create_temp_dir(".shoruichecker_test_project").expect("create dir");
+        let folder = dir.to_string_lossy().to_string();
+        let pdf = dir.join("契約書.pdf").to_string_lossy().to_string();
+
+        assert!(ensure_original_modifiable(&pdf).is_ok());
+
+        let settings = ProjectSettings {
+            protect_originals: true,
+            ..Default::default()
+        };
+        save_project_settings(&folder, &settings).expect("save");
+        assert!(ensure_original_modifiable(&pdf).is_err());
+
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn project_root_by_depth_under_watch_folder() {
+        let root = Path::new("/watch");
+        let strategy = ProjectRootStrategy::Depth { depth: 1 };
+        let doc = Path::new("/watch/○○線改良工事/写真/01.pdf");
+        assert_eq!(
+            resolve_project_root(doc, &strategy, Some(root)),
+            PathBuf::from("/watch/○○線改良工事")
+        );
+        // Too shallow or outside the watch folder: parent folder
+        assert_eq!(
+            resolve_project_root(Path::new("/watch/a.pdf"), &strategy, Some(root)),
+            PathBuf::from("/watch")
+        );
+        assert_eq!(
+            resolve_project_root(Path::new("/other/x/a.pdf"), &strategy, Some(root)),
+            PathBuf::from("/other/x")
+        );
+    }
+
+    #[test]
+    fn project_root_by_marker_file() {
+        let dir = create_temp_dir(".shoruichecker_test_project").expect("create dir");
+        let nested = dir.join("工事A").join("出来形");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.join("工事A").join(".project"), "").unwrap();
+        let strategy = ProjectRootStrategy::Marker {
+            marker: ".project".to_string(),
+        };
+
+        let doc = nested.join("a.pdf");
+        assert_eq!(resolve_project_root(&doc, &strategy, Some(&dir)), dir.join("工事A"));
+        fs::remove_file(dir.join("工事A").join(".project")).unwrap();
+        assert_eq!(resolve_project_root(&doc, &strategy, Some(&dir)), nested);
+
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn matching_references_filters_by_type_and_exclusion() {
+        let reference = |path: &str, types: &[&str]| ReferenceDocument {
+            path: path.to_string(),
+            label: path.to_string(),
+            doc_types: types.iter().map(|t| t.to_string()).collect(),
+            specification: false,
+        };
+        let settings = ProjectSettings {
+            reference_documents: vec![
+                reference("仕様書.pdf", &[]),
+                reference("単価合意書.pdf", &["見積書", "請求書"]),
+                reference("契約書.pdf", &["契約書"]),
+            ],
+            ..Default::default()
+        };
+
+        let refs = matching_references(&settings, &["請求書".to_string()], &[]);
+        let paths: Vec<_> = refs.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["仕様書.pdf", "単価合意書.pdf"]);
+
+        let refs = matching_references(&settings, &["契約書".to_string()], &["契約書.pdf".to_string()]);
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].path, "仕様書.pdf");
+    }
+}
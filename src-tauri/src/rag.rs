@@ -0,0 +1,526 @@
+//! Local RAG index over project documents
+//!
+//! Extracted document text and analysis results are split into passages and
+//! stored in the SQLite database together with a lightweight local embedding
+//! (hashed character bigrams). At analysis time the passages most similar to
+//! the target document are put into the prompt instead of the plain "last 10
+//! history entries" context.
+//!
+//! The bigrams are hashed with FNV-1a, which is stable across builds and
+//! Rust versions. Each passage records the `EMBEDDING_VERSION` it was
+//! embedded with; passages of another version are not compared and the
+//! project is re-indexed before its next retrieval.
+
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::audit::record_access;
+use crate::database::open_db;
+use crate::encryption::{open_text, seal_text};
+use crate::events::emit_log;
+use crate::history::{load_all_histories, AnalysisHistory};
+use crate::pdf_text::extract_pdf_text;
+use crate::project_settings::project_folder_for;
+use crate::result_store::load_result_data;
+use crate::spec_clauses::index_specifications;
+use crate::tasks::run_blocking;
+
+/// Embedding vector size
+pub const EMBEDDING_DIM: usize = 256;
+
+/// Version of `embed_text`; bump it whenever the embedding changes
+/// (1: std `DefaultHasher`, 2: FNV-1a)
+pub const EMBEDDING_VERSION: i64 = 2;
+
+/// Passage length in chars
+const CHUNK_CHARS: usize = 400;
+
+/// Overlap between consecutive passages in chars
+const CHUNK_OVERLAP: usize = 50;
+
+/// Number of passages put into the prompt
+const TOP_K: usize = 6;
+
+/// Passages scoring below this are not considered relevant
+const MIN_SCORE: f32 = 0.15;
+
+/// Kind of indexed text
+pub const KIND_DOCUMENT: &str = "document";
+pub const KIND_RESULT: &str = "result";
+/// Clause of a registered specification (only retrieved for clause citations)
+pub const KIND_CLAUSE: &str = "clause";
+
+/// A retrieved passage
+#[derive(Clone, Serialize)]
+pub struct RagPassage {
+    pub file_path: String,
+    pub kind: String,
+    pub text: String,
+    pub score: f32,
+}
+
+/// 64-bit FNV-1a hash
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Compute a normalized embedding from hashed character bigrams
+///
+/// Whitespace is ignored so line breaks from PDF extraction do not matter.
+pub fn embed_text(text: &str) -> Vec<f32> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    let mut vector = vec![0f32; EMBEDDING_DIM];
+    for pair in chars.windows(2) {
+        let bigram: String = pair.iter().collect();
+        vector[(fnv1a(bigram.as_bytes()) % EMBEDDING_DIM as u64) as usize] += 1.0;
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+/// Cosine similarity of two normalized embeddings
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn encode_embedding(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+/// Split text into overlapping passages
+pub fn chunk_text(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + CHUNK_CHARS).min(chars.len());
+        let chunk: String = chars[start..end].iter().collect();
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+/// Replace the indexed passages of one file and kind
+pub fn index_text(
+    conn: &Connection,
+    project_folder: &str,
+    file_path: &str,
+    kind: &str,
+    text: &str,
+) -> Result<usize, String> {
+    index_passages(conn, project_folder, file_path, kind, &chunk_text(text))
+}
+
+/// Remove the passages of one kind of a file from a project's index
+pub fn remove_passages(
+    conn: &Connection,
+    project_folder: &str,
+    file_path: &str,
+    kind: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM rag_chunks WHERE project_folder = ?1 AND file_path = ?2 AND kind = ?3",
+        params![project_folder, file_path, kind],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// Replace the indexed passages of one file and kind with pre-split passages
+pub fn index_passages(
+    conn: &Connection,
+    project_folder: &str,
+    file_path: &str,
+    kind: &str,
+    chunks: &[String],
+) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM rag_chunks WHERE file_path = ?1 AND kind = ?2",
+        params![file_path, kind],
+    )
+    .map_err(|e| e.to_string())?;
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO rag_chunks (project_folder, file_path, kind, chunk_index, text, embedding,
+                 embedding_version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                project_folder,
+                file_path,
+                kind,
+                i as i64,
+                seal_text(chunk)?,
+                encode_embedding(&embed_text(chunk)),
+                EMBEDDING_VERSION
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(chunks.len())
+}
+
+/// Retrieve the passages most similar to the query
+///
+/// Searches one project, or all projects when `project_folder` is `None`.
+/// Passages of `exclude_files` (the analysis targets themselves) are skipped.
+/// Specification clauses are left out, see [`retrieve_clauses`].
+pub fn retrieve(
+    conn: &Connection,
+    project_folder: Option<&str>,
+    query: &str,
+    exclude_files: &[String],
+    top_k: usize,
+) -> Result<Vec<RagPassage>, String> {
+    retrieve_passages(conn, project_folder, false, query, exclude_files, top_k)
+}
+
+/// Retrieve the specification clauses of a project most similar to the query
+pub fn retrieve_clauses(
+    conn: &Connection,
+    project_folder: &str,
+    query: &str,
+    top_k: usize,
+) -> Result<Vec<RagPassage>, String> {
+    retrieve_passages(conn, Some(project_folder), true, query, &[], top_k)
+}
+
+fn retrieve_passages(
+    conn: &Connection,
+    project_folder: Option<&str>,
+    clauses: bool,
+    query: &str,
+    exclude_files: &[String],
+    top_k: usize,
+) -> Result<Vec<RagPassage>, String> {
+    let query_vector = embed_text(query);
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, kind, text, embedding FROM rag_chunks
+             WHERE (?1 IS NULL OR project_folder = ?1) AND (kind = ?2) = ?3
+               AND embedding_version = ?4",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![project_folder, KIND_CLAUSE, clauses, EMBEDDING_VERSION], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut passages: Vec<RagPassage> = rows
+        .flatten()
+        .filter(|(file_path, _, _, _)| !exclude_files.contains(file_path))
+        .filter_map(|(file_path, kind, text, embedding)| {
+            Some(RagPassage {
+                score: similarity(&query_vector, &decode_embedding(&embedding)),
+                file_path,
+                kind,
+                text: open_text(text).ok()?,
+            })
+        })
+        .filter(|p| p.score >= MIN_SCORE)
+        .collect();
+    passages.sort_by(|a, b| b.score.total_cmp(&a.score));
+    passages.truncate(top_k);
+    Ok(passages)
+}
+
+/// Projects with passages embedded by another `EMBEDDING_VERSION`
+pub fn outdated_projects(conn: &Connection, project_folder: Option<&str>) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT project_folder FROM rag_chunks
+             WHERE (?1 IS NULL OR project_folder = ?1) AND embedding_version != ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![project_folder, EMBEDDING_VERSION], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    Ok(rows.filter_map(Result::ok).collect())
+}
+
+/// Build the prompt section of relevant past passages
+///
+/// A project indexed with an older embedding is re-indexed first. Returns
+/// `None` when the project has no relevant indexed passages, so the caller
+/// can fall back to the history context.
+pub fn build_rag_context(project_folder: &str, query: &str, exclude_files: &[String]) -> Option<String> {
+    let conn = open_db().ok()?;
+    if !outdated_projects(&conn, Some(project_folder)).ok()?.is_empty() {
+        let _ = rebuild_index(project_folder);
+    }
+    let passages = retrieve(&conn, Some(project_folder), query, exclude_files, TOP_K).ok()?;
+    if passages.is_empty() {
+        return None;
+    }
+
+    let mut context = String::from("\n\n## 関連する過去資料（抜粋・参考情報）\n");
+    context.push_str(
+        "同じプロジェクトの他の書類・過去の解析結果から、今回の書類に関連性の高い箇所を抜粋しました。整合性チェック時に参照してください。\n\n",
+    );
+    for passage in passages {
+        let file_name = Path::new(&passage.file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let label = if passage.kind == KIND_RESULT { "解析結果" } else { "本文" };
+        context.push_str(&format!("### {}（{}）\n{}\n\n", file_name, label, passage.text));
+    }
+    Some(context)
+}
+
+/// Index a document's text and its analysis result (errors are ignored)
+pub fn index_analyzed_document(project_folder: &str, file_path: &str, result: &str) {
+    let Ok(conn) = open_db() else {
+        return;
+    };
+    if let Ok(text) = extract_pdf_text(file_path) {
+        let _ = index_text(&conn, project_folder, file_path, KIND_DOCUMENT, &text);
+    }
+    let _ = index_text(&conn, project_folder, file_path, KIND_RESULT, result);
+}
+
+/// Text used as the retrieval query for a document (its first pages)
+pub fn query_text_for(file_path: &str) -> String {
+    extract_pdf_text(file_path)
+        .map(|text| text.chars().take(CHUNK_CHARS * 4).collect())
+        .unwrap_or_default()
+}
+
+/// A semantic search hit
+#[derive(Clone, Serialize)]
+pub struct SearchHit {
+    pub project_folder: String,
+    pub file_name: String,
+    pub file_path: String,
+    pub analyzed_at: Option<String>,
+    /// "summary", "finding" or an indexed passage kind
+    pub kind: String,
+    pub text: String,
+    pub score: f32,
+}
+
+/// Score history summaries and findings against the query embedding
+fn rank_history_hits(histories: &[AnalysisHistory], query_vector: &[f32]) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    for history in histories {
+        for entry in &history.entries {
+            let texts = std::iter::once(("summary", &entry.summary))
+                .chain(entry.issues.iter().map(|issue| ("finding", issue)));
+            for (kind, text) in texts {
+                let score = similarity(query_vector, &embed_text(text));
+                if score >= MIN_SCORE {
+                    hits.push(SearchHit {
+                        project_folder: history.project_folder.clone(),
+                        file_name: entry.file_name.clone(),
+                        file_path: entry.file_path.clone(),
+                        analyzed_at: Some(entry.analyzed_at.clone()),
+                        kind: kind.to_string(),
+                        text: text.clone(),
+                        score,
+                    });
+                }
+            }
+        }
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits
+}
+
+/// 履歴の要約・指摘とインデックス済み資料を意味的に検索
+///
+/// Projects indexed with an older embedding are re-indexed first, as their
+/// passages would not be found otherwise.
+#[tauri::command]
+pub fn semantic_search(query: String, limit: Option<usize>) -> Result<Vec<SearchHit>, String> {
+    record_access("semantic_search", "");
+    let limit = limit.unwrap_or(20);
+    let query_vector = embed_text(&query);
+    let mut hits = rank_history_hits(&load_all_histories(), &query_vector);
+
+    if let Ok(conn) = open_db() {
+        for project in outdated_projects(&conn, None)? {
+            let _ = rebuild_index(&project);
+        }
+        for passage in retrieve(&conn, None, &query, &[], limit)? {
+            let file_name = Path::new(&passage.file_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            hits.push(SearchHit {
+                project_folder: project_folder_for(&passage.file_path),
+                file_name,
+                file_path: passage.file_path,
+                analyzed_at: None,
+                kind: passage.kind,
+                text: passage.text,
+                score: passage.score,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Re-index every PDF directly under the project folder
+fn rebuild_index(folder: &str) -> Result<usize, String> {
+    let conn = open_db()?;
+    conn.execute("DELETE FROM rag_chunks WHERE project_folder = ?1", params![folder])
+        .map_err(|e| e.to_string())?;
+
+    let entries = fs::read_dir(&folder).map_err(|e| e.to_string())?;
+    let mut indexed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_pdf = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("pdf"))
+            .unwrap_or(false);
+        if !is_pdf {
+            continue;
+        }
+        let path_str = path.to_string_lossy().to_string();
+        if let Ok(text) = extract_pdf_text(&path_str) {
+            indexed += index_text(&conn, folder, &path_str, KIND_DOCUMENT, &text)?;
+        }
+        if let Some(data) = load_result_data(&path_str) {
+            indexed += index_text(&conn, folder, &path_str, KIND_RESULT, &data.result)?;
+        }
+    }
+    indexed += index_specifications(&conn, folder);
+    Ok(indexed)
+}
+
+/// プロジェクトフォルダ内の全PDFをRAGインデックスに登録し直す
+#[tauri::command]
+pub async fn rebuild_rag_index(app: AppHandle, folder: String) -> Result<usize, String> {
+    let indexed = run_blocking("rag", "RAGインデックス再構築", move || rebuild_index(&folder))
+        .await
+        .and_then(|r| r)?;
+    emit_log(&app, &format!("✓ RAGインデックス再構築 ({} 件)", indexed), "success");
+    Ok(indexed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+    use crate::history::AnalysisHistoryEntry;
+
+    #[test]
+    fn similar_texts_score_higher() {
+        let query = embed_text("請負代金額 消費税 工事価格");
+        let related = embed_text("工事価格に消費税を加えた請負代金額");
+        let unrelated = embed_text("交通誘導員の配置人数と氏名");
+        assert!(similarity(&query, &related) > similarity(&query, &unrelated));
+    }
+
+    #[test]
+    fn chunk_text_overlaps() {
+        let text = "あ".repeat(CHUNK_CHARS + 100);
+        let chunks = chunk_text(&text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].chars().count(), 100 + CHUNK_OVERLAP);
+    }
+
+    #[test]
+    fn index_and_retrieve_excludes_targets() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+
+        index_text(&conn, "p", "契約書.pdf", KIND_DOCUMENT, "請負代金額 11,000,000円 工期 令和8年4月1日から").unwrap();
+        index_text(&conn, "p", "見積書.pdf", KIND_DOCUMENT, "見積金額 10,000,000円 消費税 1,000,000円").unwrap();
+        index_text(&conn, "other", "x.pdf", KIND_DOCUMENT, "請負代金額 11,000,000円").unwrap();
+
+        let passages = retrieve(&conn, Some("p"), "請負代金額 11,000,000円", &[], 5).unwrap();
+        assert_eq!(passages[0].file_path, "契約書.pdf");
+        assert!(passages.iter().all(|p| p.file_path != "x.pdf"));
+
+        let exclude = vec!["契約書.pdf".to_string()];
+        let passages = retrieve(&conn, Some("p"), "請負代金額 11,000,000円", &exclude, 5).unwrap();
+        assert!(passages.iter().all(|p| p.file_path != "契約書.pdf"));
+
+        let passages = retrieve(&conn, None, "請負代金額 11,000,000円", &[], 5).unwrap();
+        assert!(passages.iter().any(|p| p.file_path == "x.pdf"));
+    }
+
+    #[test]
+    fn outdated_embeddings_are_not_compared() {
+        // FNV-1a test vector: the embedding does not depend on the build
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        index_text(&conn, "p", "契約書.pdf", KIND_DOCUMENT, "請負代金額 11,000,000円").unwrap();
+        assert!(outdated_projects(&conn, None).unwrap().is_empty());
+
+        conn.execute("UPDATE rag_chunks SET embedding_version = 1", []).unwrap();
+        assert_eq!(outdated_projects(&conn, Some("p")).unwrap(), vec!["p".to_string()]);
+        assert!(retrieve(&conn, Some("p"), "請負代金額 11,000,000円", &[], 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rank_history_hits_finds_reworded_findings() {
+        let entry = AnalysisHistoryEntry {
+            id: "1".to_string(),
+            file_name: "仮設計画書.pdf".to_string(),
+            file_path: "/p/仮設計画書.pdf".to_string(),
+            analyzed_at: "2026-01-01 10:00:00".to_string(),
+            document_type: None,
+            summary: "施工計画書の確認".to_string(),
+            issues: vec![
+                "⚠ 仮設足場の数量が内訳書と一致しない".to_string(),
+                "⚠ 押印がない".to_string(),
+            ],
+            resolved_issues: vec![],
+            cli_version: None,
+            file_hash: None,
+            reviewed_at: None,
+            mail: None,
+            pending: None,
+            approved_at: None,
+            store_error: None,
+        };
+        let history = AnalysisHistory {
+            project_folder: "/p".to_string(),
+            entries: vec![entry],
+            ..Default::default()
+        };
+        let hits = rank_history_hits(&[history], &embed_text("仮設工の数量に関する指摘"));
+        assert_eq!(hits[0].kind, "finding");
+        assert!(hits[0].text.contains("仮設足場"));
+    }
+}
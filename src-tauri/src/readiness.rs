@@ -0,0 +1,318 @@
+//! Submission readiness of a project
+//!
+//! One score (0–100) and a badge for management, combined from:
+//! - coverage of the required document types (project setting),
+//! - share of documents without unresolved findings (completed assignments
+//!   are resolved; a result still waiting for approval is not ready),
+//! - share of results still matching their file (hash at analysis time).
+//!
+//! Without configured required documents the coverage part is left out and
+//! the other two are weighted up.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::archive::file_sha256;
+use crate::assignments::{finding_id, load_assignments};
+use crate::freshness::{file_state, FileState};
+use crate::guidelines::detect_document_type_in;
+use crate::history::load_history;
+use crate::project_settings::load_project_settings;
+use crate::tasks::run_blocking;
+
+const COVERAGE_WEIGHT: f64 = 40.0;
+const ISSUES_WEIGHT: f64 = 40.0;
+const FRESHNESS_WEIGHT: f64 = 20.0;
+
+/// Scores from this value on are ready to submit
+const READY_SCORE: u32 = 90;
+/// Scores from this value on need a final review
+const REVIEW_SCORE: u32 = 70;
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessBadge {
+    Ready,
+    Review,
+    NotReady,
+}
+
+impl ReadinessBadge {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReadinessBadge::Ready => "提出可",
+            ReadinessBadge::Review => "要確認",
+            ReadinessBadge::NotReady => "未完了",
+        }
+    }
+
+    fn for_score(score: u32) -> Self {
+        if score >= READY_SCORE {
+            ReadinessBadge::Ready
+        } else if score >= REVIEW_SCORE {
+            ReadinessBadge::Review
+        } else {
+            ReadinessBadge::NotReady
+        }
+    }
+}
+
+/// State of one analyzed document
+#[derive(Clone, Debug)]
+pub struct DocumentState {
+    pub doc_type: Option<String>,
+    pub open_issues: usize,
+    /// The result is a draft waiting for approval
+    pub pending: bool,
+    pub file_state: FileState,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct ProjectReadiness {
+    pub score: u32,
+    pub badge: ReadinessBadge,
+    pub badge_label: String,
+    pub document_count: usize,
+    /// Required document types without an analyzed document
+    pub missing_documents: Vec<String>,
+    /// None when no required documents are configured
+    pub coverage: Option<f64>,
+    pub documents_with_issues: usize,
+    pub open_issues: usize,
+    /// Results waiting for approval
+    pub pending_drafts: usize,
+    /// Results of files changed since their analysis
+    pub stale_results: usize,
+}
+
+/// Readiness from the required types and the analyzed documents
+pub fn compute_readiness(required: &[String], documents: &[DocumentState]) -> ProjectReadiness {
+    let missing_documents: Vec<String> = required
+        .iter()
+        .filter(|t| {
+            !documents
+                .iter()
+                .any(|d| d.doc_type.as_deref() == Some(t.as_str()))
+        })
+        .cloned()
+        .collect();
+    let coverage = (!required.is_empty())
+        .then(|| (required.len() - missing_documents.len()) as f64 / required.len() as f64);
+
+    let documents_with_issues = documents.iter().filter(|d| d.open_issues > 0).count();
+    let pending_drafts = documents.iter().filter(|d| d.pending).count();
+    let not_ready = documents
+        .iter()
+        .filter(|d| d.open_issues > 0 || d.pending)
+        .count();
+    let stale_results = documents
+        .iter()
+        .filter(|d| d.file_state == FileState::Modified)
+        .count();
+    let (clean, fresh) = if documents.is_empty() {
+        (0.0, 0.0)
+    } else {
+        let total = documents.len() as f64;
+        let fresh: f64 = documents
+            .iter()
+            .map(|d| match d.file_state {
+                FileState::Unchanged => 1.0,
+                // No hash recorded: the result may or may not match
+                FileState::Unknown => 0.5,
+                FileState::Modified => 0.0,
+            })
+            .sum();
+        (
+            (total - not_ready as f64) / total,
+            fresh / total,
+        )
+    };
+
+    let (weighted, weights) = match coverage {
+        Some(coverage) => (
+            coverage * COVERAGE_WEIGHT + clean * ISSUES_WEIGHT + fresh * FRESHNESS_WEIGHT,
+            COVERAGE_WEIGHT + ISSUES_WEIGHT + FRESHNESS_WEIGHT,
+        ),
+        None => (
+            clean * ISSUES_WEIGHT + fresh * FRESHNESS_WEIGHT,
+            ISSUES_WEIGHT + FRESHNESS_WEIGHT,
+        ),
+    };
+    let score = (weighted / weights * 100.0).round() as u32;
+    let badge = ReadinessBadge::for_score(score);
+    ProjectReadiness {
+        score,
+        badge,
+        badge_label: badge.label().to_string(),
+        document_count: documents.len(),
+        missing_documents,
+        coverage,
+        documents_with_issues,
+        open_issues: documents.iter().map(|d| d.open_issues).sum(),
+        pending_drafts,
+        stale_results,
+    }
+}
+
+/// Current state of the analyzed documents of a project (hashes the files)
+pub fn project_documents(folder: &str) -> Result<Vec<DocumentState>, String> {
+    let completed: HashSet<String> = load_assignments()
+        .into_iter()
+        .filter(|a| a.project_folder == folder && a.completed_at.is_some())
+        .map(|a| a.finding_id)
+        .collect();
+    Ok(load_history(folder)?
+        .entries
+        .iter()
+        .filter(|e| Path::new(&e.file_path).is_file())
+        .map(|e| DocumentState {
+            doc_type: e.document_type.clone().or_else(|| {
+                detect_document_type_in(folder, &e.file_name)
+                    .into_iter()
+                    .next()
+            }),
+            open_issues: e
+                .issues
+                .iter()
+                .filter(|issue| !completed.contains(&finding_id(folder, &e.file_name, issue)))
+                .count(),
+            pending: e.pending.is_some(),
+            file_state: match file_sha256(Path::new(&e.file_path)) {
+                Ok(current) => file_state(e.file_hash.as_deref(), &current),
+                Err(_) => FileState::Unknown,
+            },
+        })
+        .collect())
+}
+
+pub fn project_readiness(folder: &str) -> Result<ProjectReadiness, String> {
+    Ok(compute_readiness(
+        &load_project_settings(folder).required_documents,
+        &project_documents(folder)?,
+    ))
+}
+
+/// Section of the project report
+pub fn readiness_markdown(readiness: &ProjectReadiness) -> String {
+    let mut out = format!(
+        "## 提出準備度: {}点（{}）\n- 解析済み書類: {}件（未解決の指摘あり {}件・指摘 {}件）\n",
+        readiness.score,
+        readiness.badge_label,
+        readiness.document_count,
+        readiness.documents_with_issues,
+        readiness.open_issues
+    );
+    if !readiness.missing_documents.is_empty() {
+        out.push_str(&format!(
+            "- 未提出の必要書類: {}\n",
+            readiness.missing_documents.join("、")
+        ));
+    }
+    if readiness.pending_drafts > 0 {
+        out.push_str(&format!(
+            "- 承認待ちの解析結果: {}件\n",
+            readiness.pending_drafts
+        ));
+    }
+    if readiness.stale_results > 0 {
+        out.push_str(&format!(
+            "- 解析後に変更された書類: {}件\n",
+            readiness.stale_results
+        ));
+    }
+    out
+}
+
+/// Insert the readiness section after the report title
+pub fn with_readiness_section(report: &str, readiness: &ProjectReadiness) -> String {
+    let section = readiness_markdown(readiness);
+    match report.split_once('\n') {
+        Some((title, rest)) if title.starts_with("# ") => format!(
+            "{}\n\n{}\n{}",
+            title,
+            section,
+            rest.trim_start_matches('\n')
+        ),
+        _ => format!("{}\n{}", section, report),
+    }
+}
+
+/// 工事の提出準備度（必要書類の充足・未解決の指摘・解析結果の鮮度から算出）
+#[tauri::command]
+pub async fn get_project_readiness(folder: String) -> Result<ProjectReadiness, String> {
+    let label = folder.clone();
+    run_blocking("report", &label, move || project_readiness(&folder)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(doc_type: &str, open_issues: usize, file_state: FileState) -> DocumentState {
+        DocumentState {
+            doc_type: Some(doc_type.to_string()),
+            open_issues,
+            pending: false,
+            file_state,
+        }
+    }
+
+    #[test]
+    fn score_combines_coverage_issues_and_freshness() {
+        let required = vec![
+            "契約書".to_string(),
+            "見積書".to_string(),
+            "請求書".to_string(),
+        ];
+        let complete = vec![
+            document("契約書", 0, FileState::Unchanged),
+            document("見積書", 0, FileState::Unchanged),
+            document("請求書", 0, FileState::Unchanged),
+        ];
+        let readiness = compute_readiness(&required, &complete);
+        assert_eq!(readiness.score, 100);
+        assert_eq!(readiness.badge, ReadinessBadge::Ready);
+
+        let partial = vec![
+            document("契約書", 2, FileState::Unchanged),
+            document("見積書", 0, FileState::Modified),
+        ];
+        let readiness = compute_readiness(&required, &partial);
+        // coverage 2/3 * 40 + clean 1/2 * 40 + fresh 1/2 * 20 = 56.7
+        assert_eq!(readiness.score, 57);
+        assert_eq!(readiness.badge, ReadinessBadge::NotReady);
+        assert_eq!(readiness.missing_documents, vec!["請求書".to_string()]);
+        assert_eq!((readiness.open_issues, readiness.stale_results), (2, 1));
+
+        // Without required documents only issues and freshness count
+        let readiness = compute_readiness(&[], &[document("契約書", 0, FileState::Unknown)]);
+        assert_eq!(readiness.coverage, None);
+        assert_eq!(readiness.score, 83);
+        assert_eq!(readiness.badge, ReadinessBadge::Review);
+    }
+
+    #[test]
+    fn drafts_waiting_for_approval_are_not_ready() {
+        let draft = DocumentState {
+            pending: true,
+            ..document("契約書", 0, FileState::Unchanged)
+        };
+        let readiness = compute_readiness(&["契約書".to_string()], &[draft]);
+        // coverage 1 * 40 + clean 0 * 40 + fresh 1 * 20 = 60
+        assert_eq!(readiness.score, 60);
+        assert_eq!(readiness.badge, ReadinessBadge::NotReady);
+        assert_eq!((readiness.pending_drafts, readiness.documents_with_issues), (1, 0));
+        assert!(readiness_markdown(&readiness).contains("承認待ちの解析結果: 1件"));
+    }
+
+    #[test]
+    fn readiness_section_follows_the_report_title() {
+        let readiness = compute_readiness(&["契約書".to_string()], &[]);
+        let report = with_readiness_section("# 書類チェック総括報告\n## 概要\n…", &readiness);
+        assert!(report.starts_with("# 書類チェック総括報告\n\n## 提出準備度: 0点（未完了）"));
+        assert!(report.contains("未提出の必要書類: 契約書"));
+        assert!(report.ends_with("## 概要\n…"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Links from printed reports back to the check record
+//!
+//! Report PDFs carry a QR code with a `shoruichecker://record` link naming
+//! the project folder and, for the check sheet of a document, the history
+//! entry of the analysis. Scanning the printed copy and opening the link in
+//! the app (`resolve_record_link`) leads back to the digital record. Only
+//! projects with an analysis history are opened, so a crafted link cannot
+//! point the app at an arbitrary folder.
+
+use lopdf::content::Operation;
+use qrcode::{Color, QrCode};
+use serde::Serialize;
+
+use crate::audit::record_access;
+use crate::history::{analyses_of_file, history_exists, load_history, AnalysisHistoryEntry};
+
+/// URL scheme of record links
+pub const RECORD_SCHEME: &str = "shoruichecker";
+
+const RECORD_PREFIX: &str = "shoruichecker://record?";
+
+/// Project (and analysis) a record link points to
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct RecordLink {
+    pub project: String,
+    pub entry_id: Option<String>,
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// `shoruichecker://record?project=…[&entry=…]`
+pub fn record_link(project: &str, entry_id: Option<&str>) -> String {
+    let mut link = format!("{}project={}", RECORD_PREFIX, percent_encode(project));
+    if let Some(id) = entry_id.filter(|id| !id.is_empty()) {
+        link.push_str(&format!("&entry={}", percent_encode(id)));
+    }
+    link
+}
+
+pub fn parse_record_link(link: &str) -> Option<RecordLink> {
+    let query = link.trim().strip_prefix(RECORD_PREFIX)?;
+    let mut project = None;
+    let mut entry_id = None;
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("project", value)) => project = percent_decode(value),
+            Some(("entry", value)) => entry_id = percent_decode(value),
+            _ => {}
+        }
+    }
+    Some(RecordLink {
+        project: project.filter(|p| !p.is_empty())?,
+        entry_id,
+    })
+}
+
+/// Link of the latest analysis of a document
+pub fn document_record_link(project: &str, file_path: &str) -> Result<String, String> {
+    let history = load_history(project)?;
+    let entry_id = analyses_of_file(&history, file_path)
+        .last()
+        .map(|e| e.id.clone());
+    Ok(record_link(project, entry_id.as_deref()))
+}
+
+/// PDF drawing operations of a QR code with its lower left corner at (x, y)
+///
+/// The modules are filled rectangles, so no image or font is needed; keep a
+/// blank margin of about four modules around it when placing it.
+pub fn qr_operations(data: &str, x: f32, y: f32, size: f32) -> Result<Vec<Operation>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| format!("QRコードを作成できません: {}", e))?;
+    let width = code.width();
+    let module = size / width as f32;
+    let colors = code.to_colors();
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("rg", vec![0.into(), 0.into(), 0.into()]),
+    ];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (col, row) = (i % width, i / width);
+        operations.push(Operation::new(
+            "re",
+            vec![
+                (x + col as f32 * module).into(),
+                // Rows run top to bottom, PDF y upwards
+                (y + (width - 1 - row) as f32 * module).into(),
+                module.into(),
+                module.into(),
+            ],
+        ));
+    }
+    operations.push(Operation::new("f", vec![]));
+    operations.push(Operation::new("Q", vec![]));
+    Ok(operations)
+}
+
+/// Result of opening a record link
+#[derive(Clone, Serialize)]
+pub struct ResolvedRecord {
+    pub link: RecordLink,
+    /// None for project links or when the entry is no longer in the history
+    pub entry: Option<AnalysisHistoryEntry>,
+}
+
+/// 印刷物のQRコード（shoruichecker://record リンク）から解析記録を開く
+#[tauri::command]
+pub fn resolve_record_link(link: String) -> Result<ResolvedRecord, String> {
+    let link = parse_record_link(&link).ok_or_else(|| "書類チェッカーのリンクではありません".to_string())?;
+    if !history_exists(&link.project)? {
+        return Err(format!("解析記録のない工事のリンクです: {}", link.project));
+    }
+    record_access("resolve_record_link", &link.project);
+    let entry = match &link.entry_id {
+        Some(id) => {
+            let history = load_history(&link.project)?;
+            history
+                .revisions
+                .into_iter()
+                .chain(history.entries)
+                .find(|e| &e.id == id)
+        }
+        None => None,
+    };
+    Ok(ResolvedRecord { link, entry })
+}
+
+/// 起動時に開く解析記録（shoruichecker:// リンクから起動した場合）
+#[tauri::command]
+pub fn get_startup_record_link() -> Option<String> {
+    std::env::var("OPEN_RECORD_LINK").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_links_round_trip() {
+        let link = record_link(r"C:\工事\R8 舗装&補修", Some("20260401101500123"));
+        assert!(link.starts_with("shoruichecker://record?project=C%3A%5C"));
+        assert!(!link.contains(' '));
+        assert_eq!(
+            parse_record_link(&link),
+            Some(RecordLink {
+                project: r"C:\工事\R8 舗装&補修".to_string(),
+                entry_id: Some("20260401101500123".to_string()),
+            })
+        );
+        assert_eq!(parse_record_link(&record_link("/p", None)).unwrap().entry_id, None);
+        assert_eq!(parse_record_link("https://example.com/?project=x"), None);
+        assert_eq!(parse_record_link("shoruichecker://record?entry=1"), None);
+    }
+
+    #[test]
+    fn qr_code_is_drawn_inside_its_square() {
+        let ops = qr_operations(&record_link("/p", Some("1")), 100.0, 50.0, 60.0).unwrap();
+        let rects: Vec<&Operation> = ops.iter().filter(|o| o.operator == "re").collect();
+        assert!(!rects.is_empty());
+        for rect in rects {
+            let x = rect.operands[0].as_float().unwrap();
+            let y = rect.operands[1].as_float().unwrap();
+            assert!((100.0..160.0).contains(&x) && (50.0..110.0).contains(&y));
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Crash recovery for interrupted analyses
+//!
+//! Running analyses are journaled to `shoruichecker/jobs.json` in the config
+//! directory. Entries left behind by a process that is no longer running
+//! are reported as interrupted on startup and can be resumed or discarded;
+//! jobs of another running instance (e.g. a headless run) are left alone.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::analysis::analyze_pdfs;
+use crate::events::emit_log;
+use crate::file_lock::{lock_file, write_atomic};
+use crate::messages::tr;
+use crate::processes::is_process_alive;
+use crate::gemini_cli::{sweep_stale_temp_dirs, unique_suffix, STALE_TEMP_MIN_AGE};
+
+/// A journaled analysis job
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingJob {
+    pub id: String,
+    pub pid: u32,
+    pub paths: Vec<String>,
+    pub mode: String,
+    pub custom_instruction: String,
+    pub started_at: String,
+}
+
+/// Get the job journal path
+pub fn get_journal_path() -> PathBuf {
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("jobs.json")
+}
+
+fn load_journal() -> Vec<PendingJob> {
+    fs::read_to_string(get_journal_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_journal(jobs: &[PendingJob]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(jobs).map_err(|e| e.to_string())?;
+    write_atomic(&get_journal_path(), json)
+}
+
+/// Record the start of an analysis job and return its ID
+pub fn begin_job(paths: &[String], mode: &str, custom_instruction: &str) -> String {
+    let id = format!("job-{}", unique_suffix());
+    // Best effort: journaling must not block the analysis itself
+    let _lock = lock_file(&get_journal_path()).ok();
+    let mut jobs = load_journal();
+    jobs.push(PendingJob {
+        id: id.clone(),
+        pid: std::process::id(),
+        paths: paths.to_vec(),
+        mode: mode.to_string(),
+        custom_instruction: custom_instruction.to_string(),
+        started_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    });
+    let _ = save_journal(&jobs);
+    id
+}
+
+/// Remove a finished (successful or failed) job from the journal
+pub fn finish_job(id: &str) {
+    let _lock = lock_file(&get_journal_path()).ok();
+    let mut jobs = load_journal();
+    jobs.retain(|j| j.id != id);
+    let _ = save_journal(&jobs);
+}
+
+/// Jobs started by another process that is no longer running
+pub fn interrupted_jobs(
+    jobs: &[PendingJob],
+    current_pid: u32,
+    is_alive: impl Fn(u32) -> bool,
+) -> Vec<PendingJob> {
+    jobs.iter()
+        .filter(|j| j.pid != current_pid && !is_alive(j.pid))
+        .cloned()
+        .collect()
+}
+
+fn is_interrupted(job: &PendingJob) -> bool {
+    job.pid != std::process::id() && !is_process_alive(job.pid)
+}
+
+/// Clean up after a previous crash and notify the frontend of interrupted jobs
+pub fn recover_on_startup(app: &AppHandle) {
+    let removed = sweep_stale_temp_dirs(STALE_TEMP_MIN_AGE);
+    if removed > 0 {
+        emit_log(
+            app,
+            &tr("recovery.temp_cleaned", &[&removed]),
+            "info",
+        );
+    }
+
+    let jobs = interrupted_jobs(&load_journal(), std::process::id(), is_process_alive);
+    if !jobs.is_empty() {
+        emit_log(
+            app,
+            &tr("recovery.interrupted", &[&jobs.len()]),
+            "info",
+        );
+        let _ = app.emit("interrupted-analyses", jobs);
+    }
+}
+
+/// 中断された解析の一覧を取得
+#[tauri::command]
+pub fn get_interrupted_analyses() -> Vec<PendingJob> {
+    interrupted_jobs(&load_journal(), std::process::id(), is_process_alive)
+}
+
+/// 中断された解析を再実行
+#[tauri::command]
+pub async fn resume_interrupted_analysis(app: AppHandle, id: String) -> Result<String, String> {
+    let job = {
+        let _lock = lock_file(&get_journal_path())?;
+        let mut jobs = load_journal();
+        let pos = jobs
+            .iter()
+            .position(|j| j.id == id && is_interrupted(j))
+            .ok_or_else(|| tr("recovery.not_found", &[]))?;
+        let job = jobs.remove(pos);
+        save_journal(&jobs)?;
+        job
+    };
+
+    emit_log(&app, &tr("recovery.resuming", &[&job.started_at]), "info");
+    analyze_pdfs(app, job.paths, job.mode, Some(job.custom_instruction), None, None, None).await
+}
+
+/// 中断された解析をすべて破棄
+#[tauri::command]
+pub fn discard_interrupted_analyses() -> Result<(), String> {
+    let _lock = lock_file(&get_journal_path())?;
+    let mut jobs = load_journal();
+    jobs.retain(|j| !is_interrupted(j));
+    save_journal(&jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, pid: u32) -> PendingJob {
+        PendingJob {
+            id: id.to_string(),
+            pid,
+            paths: vec!["a.pdf".to_string()],
+            mode: "single".to_string(),
+            custom_instruction: String::new(),
+            started_at: "2026-01-01 00:00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn interrupted_jobs_excludes_current_process() {
+        let jobs = vec![job("old", 100), job("current", 200)];
+        let interrupted = interrupted_jobs(&jobs, 200, |_| false);
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].id, "old");
+    }
+
+    #[test]
+    fn jobs_of_running_instances_are_not_interrupted() {
+        let jobs = vec![job("crashed", 100), job("headless", 300)];
+        let interrupted = interrupted_jobs(&jobs, 200, |pid| pid == 300);
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].id, "crashed");
+        assert!(is_process_alive(std::process::id()));
+    }
+}
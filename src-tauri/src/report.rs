@@ -0,0 +1,587 @@
+//! Project reports
+//!
+//! - Executive summary: aggregates the stored analysis results of a project
+//!   folder and asks the model for a summary, saved as a dated markdown file.
+//! - Monthly report: statistics of a month from the project history, saved as
+//!   markdown (and optionally PDF) and optionally emailed.
+//! - Check sheet: the stored result of one document as a printable PDF.
+//!
+//! Report PDFs carry a QR code linking back to the record (see `record_link`).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Local, NaiveDate};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream, StringFormat};
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::audit::record_access;
+use crate::database::open_db;
+use crate::events::emit_log;
+use crate::gemini_cli::{run_gemini_in_temp, GeminiRequest};
+use crate::history::{load_history, AnalysisHistory};
+use crate::mail::{send_mail, smtp_settings};
+use crate::pdf_embed::PdfEmbeddedData;
+use crate::project_settings::{load_project_settings, project_folder_for, MonthlyReportConfig};
+use crate::readiness::{project_readiness, with_readiness_section};
+use crate::record_link::{document_record_link, parse_record_link, qr_operations, record_link};
+use crate::result_store::load_result_data;
+use crate::settings::{load_settings, DEFAULT_MODEL};
+use crate::storage::ensure_space_for;
+use crate::tasks::run_blocking;
+use crate::usage::{project_requests_in_month, with_usage_project};
+
+/// Max chars of a single result put into the summary prompt
+const MAX_RESULT_CHARS: usize = 3000;
+
+/// A saved report
+#[derive(Clone, Serialize)]
+pub struct ProjectReport {
+    pub path: String,
+    pub content: String,
+}
+
+/// Stored results of all PDFs directly under the folder, sorted by file name
+pub fn collect_project_results(folder: &str) -> Vec<(String, PdfEmbeddedData)> {
+    let mut pdfs: Vec<PathBuf> = fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.extension()
+                        .map(|e| e.eq_ignore_ascii_case("pdf"))
+                        .unwrap_or(false)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    pdfs.sort();
+
+    pdfs.iter()
+        .filter_map(|path| {
+            let data = load_result_data(&path.to_string_lossy())?;
+            let file_name = path.file_name()?.to_string_lossy().to_string();
+            Some((file_name, data))
+        })
+        .collect()
+}
+
+/// Build the executive summary prompt from the collected results
+pub fn build_summary_prompt(project_name: &str, results: &[(String, PdfEmbeddedData)]) -> String {
+    let mut sections = String::new();
+    for (file_name, data) in results {
+        let result: String = data.result.chars().take(MAX_RESULT_CHARS).collect();
+        sections.push_str(&format!("### {}（解析日: {}）\n{}\n\n", file_name, data.date, result));
+    }
+
+    format!(
+        r#"あなたは建設工事の書類チェックの責任者です。
+プロジェクト「{}」の全書類の解析結果をもとに、管理者向けの総括報告を作成してください。
+
+## 各書類の解析結果
+{}
+## 出力形式（Markdown）
+# 書類チェック総括報告
+## 概要
+（対象書類数と全体の状況を2〜3文で）
+## 未解決の重大指摘
+（金額・数量・日付の不整合など、対応が必要な指摘を書類名付きで箇条書き）
+## 傾向
+（複数の書類に共通する間違いのパターン）
+## 推奨アクション
+（優先度順に具体的な対応を箇条書き）"#,
+        project_name, sections
+    )
+}
+
+/// Report file path for a date (`書類チェック総括_YYYYMMDD.md`)
+pub fn report_path(folder: &str, date: NaiveDate) -> PathBuf {
+    Path::new(folder).join(format!("書類チェック総括_{}.md", date.format("%Y%m%d")))
+}
+
+/// プロジェクト全体の総括報告を作成（Gemini使用）
+#[tauri::command]
+pub async fn summarize_project(app: AppHandle, folder: String) -> Result<ProjectReport, String> {
+    record_access("summarize_project", &folder);
+    let results = collect_project_results(&folder);
+    if results.is_empty() {
+        return Err("フォルダ内に解析済みの書類がありません".to_string());
+    }
+
+    emit_log(
+        &app,
+        &format!("=== プロジェクト総括 ({} ファイル) ===", results.len()),
+        "info",
+    );
+    emit_log(&app, "Geminiで総括中...", "wave");
+
+    let project_name = Path::new(&folder)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| folder.clone());
+    let prompt = build_summary_prompt(&project_name, &results);
+    let model = load_settings()
+        .model
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let readiness_folder = folder.clone();
+    let output = run_blocking("report", "プロジェクト総括", move || {
+        let request = GeminiRequest::text(&prompt, &model);
+        let summary = with_usage_project(&readiness_folder, || {
+            run_gemini_in_temp(".shoruichecker_temp_report", &request)
+        })
+        .map_err(|e| e.to_string())?;
+        Ok::<_, String>(with_readiness_section(&summary, &project_readiness(&readiness_folder)?))
+    })
+    .await
+    .and_then(|r| r);
+    let content = output.inspect_err(|e| emit_log(&app, &format!("エラー: {}", e), "error"))?;
+
+    let path = report_path(&folder, Local::now().date_naive());
+    ensure_space_for(&path, content.len() as u64)?;
+    fs::write(&path, &content).map_err(|e| format!("報告書保存エラー: {}", e))?;
+    emit_log(&app, &format!("✓ 総括報告を保存: {}", path.display()), "success");
+
+    Ok(ProjectReport {
+        path: path.to_string_lossy().to_string(),
+        content,
+    })
+}
+
+/// Statistics of one month of a project
+#[derive(Clone, Serialize, Default)]
+pub struct MonthlyStats {
+    /// `%Y-%m`
+    pub month: String,
+    pub documents: usize,
+    pub issues_found: usize,
+    pub issues_resolved: usize,
+    /// AI requests recorded for the project (None when unknown)
+    pub ai_requests: Option<usize>,
+    /// Documents per type (`その他` when unknown)
+    pub by_type: BTreeMap<String, usize>,
+}
+
+/// Aggregate the history entries analyzed in `month` (`%Y-%m`)
+pub fn monthly_stats(history: &AnalysisHistory, month: &str) -> MonthlyStats {
+    let mut stats = MonthlyStats {
+        month: month.to_string(),
+        ..Default::default()
+    };
+    for entry in history
+        .entries
+        .iter()
+        .filter(|e| e.analyzed_at.starts_with(month))
+    {
+        stats.documents += 1;
+        stats.issues_found += entry.issues.len();
+        stats.issues_resolved += entry.resolved_issues.len();
+        let doc_type = entry
+            .document_type
+            .clone()
+            .unwrap_or_else(|| "その他".to_string());
+        *stats.by_type.entry(doc_type).or_default() += 1;
+    }
+    stats
+}
+
+/// The month before `today`, `%Y-%m`
+pub fn previous_month(today: NaiveDate) -> String {
+    let first = today.with_day(1).unwrap_or(today);
+    (first - chrono::Duration::days(1)).format("%Y-%m").to_string()
+}
+
+/// Monthly report as markdown
+pub fn format_monthly_report(project_name: &str, stats: &MonthlyStats) -> String {
+    let mut report = format!("# 月次報告 {}（{}）\n\n", stats.month, project_name);
+    report.push_str("## 実績\n");
+    report.push_str(&format!("- チェックした書類: {} 件\n", stats.documents));
+    report.push_str(&format!("- 検出した指摘: {} 件\n", stats.issues_found));
+    report.push_str(&format!("- 解消した指摘: {} 件\n", stats.issues_resolved));
+    if let Some(requests) = stats.ai_requests {
+        report.push_str(&format!("- AI解析の利用: {} 回\n", requests));
+    }
+    if !stats.by_type.is_empty() {
+        report.push_str("\n## 書類タイプ別\n");
+        for (doc_type, count) in &stats.by_type {
+            report.push_str(&format!("- {}: {} 件\n", doc_type, count));
+        }
+    }
+    report
+}
+
+/// Report file path for a month (`月次報告_YYYY-MM.md`)
+pub fn monthly_report_path(folder: &str, month: &str) -> PathBuf {
+    Path::new(folder).join(format!("月次報告_{}.md", month))
+}
+
+/// Display width of a char in half-width units
+fn char_width(c: char) -> usize {
+    if c.is_ascii() {
+        1
+    } else {
+        2
+    }
+}
+
+/// Wrap a line to at most `max_width` half-width units
+fn wrap_line(line: &str, max_width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + char_width(c) > max_width {
+            lines.push(std::mem::take(&mut current));
+            width = 0;
+        }
+        current.push(c);
+        width += char_width(c);
+    }
+    lines.push(current);
+    lines
+}
+
+/// Caption printed next to the QR code of a record link
+fn link_caption(link: &str) -> String {
+    match parse_record_link(link) {
+        Some(record) => match record.entry_id {
+            Some(id) => format!("書類チェッカー 記録ID {}", id),
+            None => format!(
+                "書類チェッカー 工事 {}",
+                Path::new(&record.project)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or(record.project)
+            ),
+        },
+        None => link.to_string(),
+    }
+}
+
+fn hex_text(text: &str) -> Object {
+    let bytes: Vec<u8> = text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+/// Write plain text as an A4 PDF using a non-embedded Japanese font
+///
+/// With a record link, every page gets its QR code in the lower right corner
+/// (pages of a printout get separated) and fewer lines to make room for it.
+pub fn write_text_pdf(path: &Path, text: &str, link: Option<&str>) -> Result<(), String> {
+    const FONT_SIZE: i64 = 10;
+    const LEADING: i64 = 15;
+    const MAX_WIDTH: usize = 100;
+    const QR_SIZE: f32 = 60.0;
+    let lines_per_page: usize = if link.is_some() { 46 } else { 50 };
+
+    let lines: Vec<String> = text
+        .lines()
+        .map(|l| l.trim_start_matches('#').trim_start())
+        .flat_map(|l| wrap_line(l, MAX_WIDTH))
+        .collect();
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let descriptor_id = doc.add_object(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => "MS-Gothic",
+        "Flags" => 4,
+        "FontBBox" => vec![0.into(), (-141).into(), 1000.into(), 859.into()],
+        "ItalicAngle" => 0,
+        "Ascent" => 859,
+        "Descent" => -141,
+        "CapHeight" => 769,
+        "StemV" => 80,
+    });
+    let cid_font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType2",
+        "BaseFont" => "MS-Gothic",
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("Japan1"),
+            "Supplement" => 6,
+        },
+        "FontDescriptor" => descriptor_id,
+        "DW" => 1000,
+        // Half-width roman glyphs selected by UniJIS-UCS2-HW-H
+        "W" => vec![231.into(), 325.into(), 500.into()],
+    });
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "MS-Gothic",
+        "Encoding" => "UniJIS-UCS2-HW-H",
+        "DescendantFonts" => vec![cid_font_id.into()],
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    });
+
+    let mut kids: Vec<Object> = Vec::new();
+    let chunks: Vec<&[String]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(lines_per_page).collect()
+    };
+    for page_lines in chunks {
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), FONT_SIZE.into()]),
+            Operation::new("TL", vec![LEADING.into()]),
+            Operation::new("Td", vec![50.into(), 790.into()]),
+        ];
+        for line in page_lines {
+            operations.push(Operation::new("Tj", vec![hex_text(line)]));
+            operations.push(Operation::new("T*", vec![]));
+        }
+        operations.push(Operation::new("ET", vec![]));
+        if let Some(link) = link {
+            operations.extend(qr_operations(link, 485.0, 30.0, QR_SIZE)?);
+            operations.extend([
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 7.into()]),
+                Operation::new("Td", vec![50.into(), 40.into()]),
+                Operation::new("Tj", vec![hex_text(&link_caption(link))]),
+                Operation::new("ET", vec![]),
+            ]);
+        }
+
+        let content = Content { operations };
+        let content_id = doc.add_object(Stream::new(
+            dictionary! {},
+            content.encode().map_err(|e| e.to_string())?,
+        ));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.compress();
+    doc.save(path)
+        .map(|_| ())
+        .map_err(|e| format!("PDF保存エラー: {}", e))
+}
+
+/// Write the monthly report files and mail them if configured
+///
+/// Returns the markdown report.
+pub fn write_monthly_report(
+    folder: &str,
+    month: &str,
+    config: &MonthlyReportConfig,
+) -> Result<ProjectReport, String> {
+    let project_name = Path::new(folder)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| folder.to_string());
+    let mut stats = monthly_stats(&load_history(folder)?, month);
+    stats.ai_requests = open_db()
+        .and_then(|conn| project_requests_in_month(&conn, folder, month))
+        .ok();
+    let content = format_monthly_report(&project_name, &stats);
+
+    let md_path = monthly_report_path(folder, month);
+    ensure_space_for(&md_path, content.len() as u64)?;
+    fs::write(&md_path, &content).map_err(|e| format!("報告書保存エラー: {}", e))?;
+
+    let pdf_path = md_path.with_extension("pdf");
+    if config.pdf {
+        write_text_pdf(&pdf_path, &content, Some(&record_link(folder, None)))?;
+    }
+
+    if !config.email_to.is_empty() {
+        let smtp = smtp_settings().ok_or_else(|| "メール送信設定（SMTP）がありません".to_string())?;
+        let attachments: Vec<&Path> = if config.pdf {
+            vec![pdf_path.as_path()]
+        } else {
+            vec![md_path.as_path()]
+        };
+        send_mail(
+            &smtp,
+            &config.email_to,
+            &format!("【書類チェック】月次報告 {} {}", month, project_name),
+            &content,
+            &attachments,
+        )?;
+    }
+
+    Ok(ProjectReport {
+        path: md_path.to_string_lossy().to_string(),
+        content,
+    })
+}
+
+/// Printable check sheet of a document: its stored result and a QR code
+/// linking to the analysis in the history
+pub fn write_check_sheet(pdf_path: &str, out_path: &Path) -> Result<(), String> {
+    let data = load_result_data(pdf_path).ok_or_else(|| format!("解析結果がありません: {}", pdf_path))?;
+    let file_name = Path::new(pdf_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let content = format!(
+        "# 書類チェック結果票\n\nファイル: {}\n解析日: {}\n\n{}",
+        file_name, data.date, data.result
+    );
+    let link = document_record_link(&project_folder_for(pdf_path), pdf_path)?;
+    ensure_space_for(out_path, content.len() as u64 * 4)?;
+    write_text_pdf(out_path, &content, Some(&link))
+}
+
+/// 解析結果をQRコード付きのチェック結果票（PDF）として書き出す
+#[tauri::command]
+pub fn export_check_sheet(path: String, out_path: String) -> Result<String, String> {
+    record_access("export_check_sheet", &path);
+    write_check_sheet(&path, Path::new(&out_path))?;
+    Ok(out_path)
+}
+
+/// 月次報告を作成（month 省略時は前月）
+#[tauri::command]
+pub fn generate_monthly_report(
+    app: AppHandle,
+    folder: String,
+    month: Option<String>,
+) -> Result<ProjectReport, String> {
+    record_access("generate_monthly_report", &folder);
+    let month = month.unwrap_or_else(|| previous_month(Local::now().date_naive()));
+    let config = load_project_settings(&folder)
+        .monthly_report
+        .unwrap_or_default();
+    let report = write_monthly_report(&folder, &month, &config)?;
+    emit_log(&app, &format!("✓ 月次報告を保存: {}", report.path), "success");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::create_history_entry;
+
+    #[test]
+    fn summary_prompt_lists_each_result() {
+        let results = vec![
+            (
+                "契約書.pdf".to_string(),
+                PdfEmbeddedData {
+                    result: "⚠ 工期が見積書と異なる".to_string(),
+                    instruction: None,
+                    date: "2026-01-01 10:00:00".to_string(),
+                },
+            ),
+            (
+                "請求書.pdf".to_string(),
+                PdfEmbeddedData {
+                    result: "✓ 問題なし".to_string(),
+                    instruction: None,
+                    date: "2026-01-02 10:00:00".to_string(),
+                },
+            ),
+        ];
+        let prompt = build_summary_prompt("○○道路改良工事", &results);
+        assert!(prompt.contains("○○道路改良工事"));
+        assert!(prompt.contains("### 契約書.pdf（解析日: 2026-01-01 10:00:00）"));
+        assert!(prompt.contains("⚠ 工期が見積書と異なる"));
+        assert!(prompt.contains("### 請求書.pdf"));
+        assert!(prompt.contains("未解決の重大指摘"));
+    }
+
+    #[test]
+    fn monthly_stats_counts_entries_of_the_month() {
+        let mut old = create_history_entry("見積書.pdf", "/p/見積書.pdf", "⚠ 単価不整合");
+        old.analyzed_at = "2026-02-27 10:00:00".to_string();
+        let mut a = create_history_entry("契約書.pdf", "/p/契約書.pdf", "契約書\n⚠ 金額不整合\n⚠ 日付矛盾");
+        a.analyzed_at = "2026-03-02 10:00:00".to_string();
+        let mut b = create_history_entry("請求書.pdf", "/p/請求書.pdf", "請求書 問題なし");
+        b.analyzed_at = "2026-03-15 10:00:00".to_string();
+        b.resolved_issues = vec!["⚠ 消費税の計算誤り".to_string()];
+        let history = AnalysisHistory {
+            project_folder: "/p".to_string(),
+            entries: vec![old, a, b],
+            ..Default::default()
+        };
+
+        let stats = monthly_stats(&history, "2026-03");
+        assert_eq!(stats.documents, 2);
+        assert_eq!(stats.issues_found, 2);
+        assert_eq!(stats.issues_resolved, 1);
+        assert_eq!(stats.by_type.get("契約書"), Some(&1));
+        assert_eq!(stats.by_type.get("請求書"), Some(&1));
+
+        let report = format_monthly_report("工事A", &stats);
+        assert!(report.contains("# 月次報告 2026-03（工事A）"));
+        assert!(report.contains("- 解消した指摘: 1 件"));
+        assert!(!report.contains("AI解析の利用"));
+        let stats = MonthlyStats {
+            ai_requests: Some(5),
+            ..stats
+        };
+        assert!(format_monthly_report("工事A", &stats).contains("- AI解析の利用: 5 回"));
+    }
+
+    #[test]
+    fn previous_month_wraps_year() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+        assert_eq!(previous_month(today), "2025-12");
+        let today = NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        assert_eq!(previous_month(today), "2026-02");
+    }
+
+    #[test]
+    fn wrap_line_counts_full_width_as_two() {
+        assert_eq!(wrap_line("あいう", 4), vec!["あい", "う"]);
+        assert_eq!(wrap_line("abcde", 4), vec!["abcd", "e"]);
+        assert_eq!(wrap_line("", 4), vec![""]);
+    }
+
+    #[test]
+    fn write_text_pdf_creates_readable_pdf() {
+        let dir = crate::gemini_cli::create_temp_dir(".shoruichecker_test_report").expect("dir");
+        let path = dir.join("月次報告.pdf");
+        let text = (0..60).map(|i| format!("行 {}", i)).collect::<Vec<_>>().join("\n");
+        write_text_pdf(&path, &text, None).expect("write pdf");
+
+        let doc = Document::load(&path).expect("load pdf");
+        assert_eq!(doc.get_pages().len(), 2);
+
+        let link = record_link("/work/工事A", Some("20260401101500123"));
+        write_text_pdf(&path, &text, Some(&link)).expect("write pdf with link");
+        let doc = Document::load(&path).expect("load pdf");
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+        let content = doc.get_and_decode_page_content(pages[&1]).expect("content");
+        assert!(content.operations.iter().any(|o| o.operator == "re"));
+        assert_eq!(link_caption(&link), "書類チェッカー 記録ID 20260401101500123");
+        crate::gemini_cli::cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn report_path_is_dated() {
+        let date = NaiveDate::from_ymd_opt(2026, 3, 5).unwrap();
+        let path = report_path("/work/工事A", date);
+        assert_eq!(path, PathBuf::from("/work/工事A/書類チェック総括_20260305.md"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Cache of single-file analysis results
+//!
+//! Results are keyed by the SHA-256 of the PDF bytes and of the effective
+//! prompt (model included). Re-analyzing an unchanged file with unchanged
+//! guidelines, instructions and references returns the stored result without
+//! running the AI. The cached text is the AI output before the local
+//! post-processing (expected values, placement check, output rules), which
+//! is applied again on every hit. With `encrypt_at_rest` the cached text is
+//! sealed like the other stored results.
+
+use std::path::Path;
+
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::archive::file_sha256;
+use crate::database::open_db;
+use crate::encryption::{open_text, seal_text};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Hash of the prompt sent to a model
+pub fn prompt_hash(model: &str, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(prompt.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn lookup_cached(conn: &Connection, file_hash: &str, prompt_hash: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT result FROM analysis_cache WHERE file_hash = ?1 AND prompt_hash = ?2",
+        params![file_hash, prompt_hash],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .map(open_text)
+    .transpose()
+}
+
+pub fn store_cached(
+    conn: &Connection,
+    file_hash: &str,
+    prompt_hash: &str,
+    model: &str,
+    result: &str,
+    created_at: &str,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO analysis_cache (file_hash, prompt_hash, model, result, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![file_hash, prompt_hash, model, seal_text(result)?, created_at],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// Drop the results of a file content; returns the number of entries
+pub fn forget_cached(conn: &Connection, file_hash: &str) -> Result<usize, String> {
+    conn.execute("DELETE FROM analysis_cache WHERE file_hash = ?1", params![file_hash])
+        .map_err(|e| e.to_string())
+}
+
+/// Cached result of a file and prompt (best effort)
+pub fn cached_result(file_hash: &str, prompt_hash: &str) -> Option<String> {
+    lookup_cached(&open_db().ok()?, file_hash, prompt_hash).ok().flatten()
+}
+
+/// Cache a result under each content the file had (before and after the
+/// result was embedded into it); best effort
+pub fn cache_result(file_hashes: &[Option<String>], prompt_hash: &str, model: &str, result: &str) {
+    let Ok(conn) = open_db() else {
+        return;
+    };
+    let now = Local::now().format(TIMESTAMP_FORMAT).to_string();
+    let mut hashes: Vec<&String> = file_hashes.iter().flatten().collect();
+    hashes.dedup();
+    for hash in hashes {
+        let _ = store_cached(&conn, hash, prompt_hash, model, result, &now);
+    }
+}
+
+/// Drop the cached results of the files so they are analyzed again
+/// (`force` of `analyze_pdfs`)
+pub fn forget_cached_results(paths: &[String]) {
+    let Ok(conn) = open_db() else {
+        return;
+    };
+    for path in paths {
+        if let Ok(hash) = file_sha256(Path::new(path)) {
+            let _ = forget_cached(&conn, &hash);
+        }
+    }
+}
+
+/// 解析結果のキャッシュをすべて削除（削除した件数）
+#[tauri::command]
+pub fn clear_result_cache() -> Result<usize, String> {
+    open_db()?
+        .execute("DELETE FROM analysis_cache", [])
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    #[test]
+    fn results_are_found_by_file_and_prompt() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let prompt = prompt_hash("gemini-2.5-pro", "請求書をチェックしてください");
+        assert_ne!(prompt, prompt_hash("gemini-2.5-flash", "請求書をチェックしてください"));
+
+        store_cached(&conn, "abc", &prompt, "gemini-2.5-pro", "✓ 問題なし", "2026-04-01 10:00:00").unwrap();
+        assert_eq!(lookup_cached(&conn, "abc", &prompt).unwrap().as_deref(), Some("✓ 問題なし"));
+        assert_eq!(lookup_cached(&conn, "def", &prompt).unwrap(), None);
+        let other_prompt = prompt_hash("gemini-2.5-pro", "契約書をチェックしてください");
+        assert_eq!(lookup_cached(&conn, "abc", &other_prompt).unwrap(), None);
+
+        store_cached(&conn, "abc", &prompt, "gemini-2.5-pro", "⚠ 金額が異なる", "2026-04-02 10:00:00").unwrap();
+        assert_eq!(lookup_cached(&conn, "abc", &prompt).unwrap().as_deref(), Some("⚠ 金額が異なる"));
+        assert_eq!(forget_cached(&conn, "abc").unwrap(), 1);
+        assert_eq!(lookup_cached(&conn, "abc", &prompt).unwrap(), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Result storage: PDF embedding and/or sidecar JSON files
+//!
+//! Embedding fails on read-only folders, signed PDFs and some malformed files.
+//! Results can therefore also be written to a `<name>.shorui.json` sidecar next
+//! to the PDF, and all readers fall back to it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::access::{ensure_allowed, Operation};
+use crate::encryption::{read_string, seal_bytes};
+use crate::pdf_embed::{
+    embed_result_in_pdf_with_instruction, read_embedded_data_from_pdf, PdfEmbeddedData,
+};
+use crate::project_settings::{load_project_settings, project_folder_for};
+use crate::settings::{load_settings, ResultStorage};
+
+/// Sidecar file path for a PDF (`foo.pdf` → `foo.shorui.json`)
+pub fn sidecar_path(pdf_path: &str) -> PathBuf {
+    Path::new(pdf_path).with_extension("shorui.json")
+}
+
+/// Write result data to the sidecar file
+pub fn write_sidecar(pdf_path: &str, data: &PdfEmbeddedData) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+    fs::write(sidecar_path(pdf_path), seal_bytes(json.as_bytes())?)
+        .map_err(|e| format!("サイドカー保存エラー: {}", e))
+}
+
+/// Read result data from the sidecar file
+pub fn read_sidecar(pdf_path: &str) -> Option<PdfEmbeddedData> {
+    read_string(&sidecar_path(pdf_path))
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
+/// Store an analysis result according to the `result_storage` setting;
+/// returns where it was actually written
+///
+/// In embed mode a failed embed falls back to the sidecar so the result is
+/// not lost. Projects with 原本保護 always use the sidecar. Nothing is
+/// written in read-only mode.
+pub fn store_result(pdf_path: &str, result: &str, custom_instruction: &str) -> Result<ResultStorage, String> {
+    ensure_allowed(Operation::Embed)?;
+    let storage = if load_project_settings(&project_folder_for(pdf_path)).protect_originals {
+        ResultStorage::Sidecar
+    } else {
+        load_settings().result_storage
+    };
+    let data = PdfEmbeddedData {
+        result: result.to_string(),
+        instruction: if custom_instruction.is_empty() {
+            None
+        } else {
+            Some(custom_instruction.to_string())
+        },
+        date: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+
+    match storage {
+        ResultStorage::Embed => {
+            match embed_result_in_pdf_with_instruction(pdf_path, result, custom_instruction) {
+                Ok(()) => Ok(ResultStorage::Embed),
+                Err(embed_err) => write_sidecar(pdf_path, &data)
+                    .map(|_| ResultStorage::Sidecar)
+                    .map_err(|e| format!("{} / {}", embed_err, e)),
+            }
+        }
+        ResultStorage::Sidecar => write_sidecar(pdf_path, &data).map(|_| ResultStorage::Sidecar),
+        ResultStorage::Both => {
+            let embedded =
+                embed_result_in_pdf_with_instruction(pdf_path, result, custom_instruction);
+            let sidecar = write_sidecar(pdf_path, &data);
+            match (embedded, sidecar) {
+                (Err(a), Err(b)) => Err(format!("{} / {}", a, b)),
+                (Ok(()), Ok(())) => Ok(ResultStorage::Both),
+                (Ok(()), Err(_)) => Ok(ResultStorage::Embed),
+                (Err(_), Ok(())) => Ok(ResultStorage::Sidecar),
+            }
+        }
+    }
+}
+
+/// Pick the newer of two stored results (dates are `%Y-%m-%d %H:%M:%S`)
+pub fn pick_latest(
+    embedded: Option<PdfEmbeddedData>,
+    sidecar: Option<PdfEmbeddedData>,
+) -> Option<PdfEmbeddedData> {
+    match (embedded, sidecar) {
+        (Some(e), Some(s)) => Some(if s.date > e.date { s } else { e }),
+        (e, s) => e.or(s),
+    }
+}
+
+/// Load the stored result of a PDF from its metadata or sidecar
+pub fn load_result_data(pdf_path: &str) -> Option<PdfEmbeddedData> {
+    pick_latest(read_embedded_data_from_pdf(pdf_path), read_sidecar(pdf_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    fn data(result: &str, date: &str) -> PdfEmbeddedData {
+        PdfEmbeddedData {
+            result: result.to_string(),
+            instruction: None,
+            date: date.to_string(),
+        }
+    }
+
+    #[test]
+    fn sidecar_path_replaces_extension() {
+        let path = sidecar_path("/work/契約書.pdf");
+        assert_eq!(path, PathBuf::from("/work/契約書.shorui.json"));
+    }
+
+    #[test]
+    fn sidecar_roundtrip() {
+        let dir = create_temp_dir(".shoruichecker_test_sidecar").expect("create dir");
+        let pdf = dir.join("a.pdf").to_string_lossy().to_string();
+
+        write_sidecar(&pdf, &data("✓ 問題なし", "2026-01-01 10:00:00")).expect("write");
+        let loaded = load_result_data(&pdf).expect("load");
+        assert_eq!(loaded.result, "✓ 問題なし");
+
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn pick_latest_prefers_newer_date() {
+        let old = data("old", "2026-01-01 10:00:00");
+        let new = data("new", "2026-02-01 10:00:00");
+        assert_eq!(pick_latest(Some(old.clone()), Some(new.clone())).unwrap().result, "new");
+        assert_eq!(pick_latest(Some(new), Some(old.clone())).unwrap().result, "new");
+        assert_eq!(pick_latest(None, Some(old)).unwrap().result, "old");
+    }
+}
@@ -0,0 +1,140 @@
+//! Retries of AI requests on transient errors
+//!
+//! Quota errors (429 / RESOURCE_EXHAUSTED), overloaded servers and network
+//! failures usually pass after a short wait, so a failed request whose error
+//! matches `RETRYABLE_PATTERNS` is run again after an exponential backoff with
+//! jitter. Only the stderr of a failed CLI run (its stdout may quote the
+//! documents) or the HTTP error of an API is matched. Timeouts, sandbox
+//! errors and aborted runs are not retried.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::settings::{load_settings, save_settings};
+
+/// Error text (stderr, HTTP status) of failures worth another attempt
+const RETRYABLE_PATTERNS: &[&str] = &[
+    "429",
+    "resource_exhausted",
+    "rate limit",
+    "ratelimit",
+    "quota",
+    "too many requests",
+    "503",
+    "unavailable",
+    "overloaded",
+    "econnreset",
+    "etimedout",
+    "enotfound",
+    "eai_again",
+    "socket hang up",
+    "fetch failed",
+    "network error",
+];
+
+/// Retry policy (settings key `retry`)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RetrySettings {
+    /// Attempts including the first one (1 disables retries)
+    pub max_attempts: u32,
+    /// Wait before the second attempt; doubled for every further one
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Random share of the wait (0.0 - 1.0) so parallel runs do not retry in step
+    pub jitter: f64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 5_000,
+            max_delay_ms: 60_000,
+            jitter: 0.3,
+        }
+    }
+}
+
+/// Whether a failed request may succeed when run again; `stderr` is that of
+/// the failed CLI run, if any (then the error message is not looked at)
+pub fn is_retryable(error: &AppError, stderr: Option<&str>) -> bool {
+    let message = match (error, stderr) {
+        (AppError::Json(_) | AppError::Pdf(_) | AppError::Timeout(_), _) => return false,
+        (_, Some(stderr)) => stderr.to_lowercase(),
+        (AppError::Process(msg) | AppError::Io(msg), None) => msg.to_lowercase(),
+    };
+    RETRYABLE_PATTERNS.iter().any(|p| message.contains(p))
+}
+
+/// Wait before attempt `attempt + 1` (`attempt` starts at 1); `random` is
+/// in `0.0..1.0` and takes up to `jitter` of the wait off
+pub fn backoff_delay(settings: &RetrySettings, attempt: u32, random: f64) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let delay = settings
+        .initial_delay_ms
+        .saturating_mul(1u64 << exponent)
+        .min(settings.max_delay_ms);
+    let jitter = settings.jitter.clamp(0.0, 1.0) * random.clamp(0.0, 1.0);
+    Duration::from_millis((delay as f64 * (1.0 - jitter)).round() as u64)
+}
+
+/// Cheap random number in `0.0..1.0` for the jitter
+pub fn jitter_random() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    f64::from(nanos % 1000) / 1000.0
+}
+
+#[tauri::command]
+pub fn get_retry_settings() -> RetrySettings {
+    load_settings().retry
+}
+
+/// AIリクエストの再試行設定（回数・待ち時間）を保存
+#[tauri::command]
+pub fn set_retry_settings(retry: RetrySettings) -> Result<(), String> {
+    if retry.max_attempts == 0 || retry.max_attempts > 10 {
+        return Err("試行回数は1～10回で指定してください".to_string());
+    }
+    if retry.initial_delay_ms > retry.max_delay_ms {
+        return Err("最大待ち時間は初回の待ち時間以上にしてください".to_string());
+    }
+    let mut settings = load_settings();
+    settings.retry = RetrySettings {
+        jitter: retry.jitter.clamp(0.0, 1.0),
+        ..retry
+    };
+    save_settings(&settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let quota = AppError::Process("exit code 1: [API Error: 429 RESOURCE_EXHAUSTED]".to_string());
+        let network = AppError::Process("Claude API に接続できません: ECONNRESET".to_string());
+        let auth = AppError::Process("exit code 41: Please set an Auth method".to_string());
+        assert!(is_retryable(&quota, Some("[API Error: 429 RESOURCE_EXHAUSTED]")));
+        assert!(is_retryable(&network, None));
+        assert!(!is_retryable(&auth, Some("Please set an Auth method")));
+        assert!(!is_retryable(&AppError::Timeout("429".to_string()), None));
+        // Words in the CLI's stdout do not count
+        let stdout = AppError::Process("exit code 1: bad flag\n契約の quota は 429 件".to_string());
+        assert!(!is_retryable(&stdout, Some("bad flag")));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let settings = RetrySettings::default();
+        assert_eq!(backoff_delay(&settings, 1, 0.0), Duration::from_secs(5));
+        assert_eq!(backoff_delay(&settings, 2, 0.0), Duration::from_secs(10));
+        assert_eq!(backoff_delay(&settings, 10, 0.0), Duration::from_secs(60));
+        assert_eq!(backoff_delay(&settings, 1, 1.0), Duration::from_millis(3_500));
+    }
+}
@@ -0,0 +1,307 @@
+//! Daily digest of code review findings
+//!
+//! In digest mode reviews with issues are not notified on every save. They
+//! are collected in `review_digest.json` next to the settings and, once a day
+//! at the configured hour, summarized into one report grouped by file with
+//! repeated findings merged. The report is written to `review_digests/` next
+//! to the settings (not into the watched repository) and posted as a
+//! notification. The collected reviews are cleared only once the report is
+//! written, and the day counts as sent only when that succeeded.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{Local, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::events::emit_log;
+use crate::file_lock::write_atomic;
+use crate::freshness::normalize_finding;
+use crate::settings::{get_settings_path, load_settings, save_settings};
+
+/// Serializes appends from concurrent review callbacks
+static PENDING_LOCK: Mutex<()> = Mutex::new(());
+
+/// Digest mode of the code review
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReviewDigestSettings {
+    /// Hour of the day (0-23) the digest is produced
+    #[serde(default = "default_digest_hour")]
+    pub hour: u32,
+    /// Date (YYYY-MM-DD) of the last digest
+    #[serde(default)]
+    pub last_sent_on: Option<String>,
+}
+
+fn default_digest_hour() -> u32 {
+    18
+}
+
+/// A review with issues waiting for the digest
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingReview {
+    pub path: String,
+    pub name: String,
+    pub timestamp: String,
+    pub review: String,
+}
+
+/// A finding in the digest with how often it was reported
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DigestFinding {
+    pub text: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DigestFile {
+    pub path: String,
+    pub name: String,
+    pub reviews: usize,
+    pub findings: Vec<DigestFinding>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ReviewDigest {
+    pub date: String,
+    pub files: Vec<DigestFile>,
+    /// Written report
+    pub report_path: String,
+}
+
+fn pending_path() -> PathBuf {
+    get_settings_path().with_file_name("review_digest.json")
+}
+
+fn report_path(date: &str) -> PathBuf {
+    get_settings_path()
+        .with_file_name("review_digests")
+        .join(format!("code-review-digest-{}.md", date))
+}
+
+fn load_pending() -> Vec<PendingReview> {
+    fs::read_to_string(pending_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Whether reviews are collected for the digest instead of notified
+pub fn digest_enabled() -> bool {
+    load_settings().review_digest.is_some()
+}
+
+/// Keep a review with issues for the next digest
+pub fn add_pending_review(review: PendingReview) -> Result<(), String> {
+    let _guard = PENDING_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut pending = load_pending();
+    pending.push(review);
+    let json = serde_json::to_string_pretty(&pending).map_err(|e| e.to_string())?;
+    write_atomic(&pending_path(), json)
+}
+
+/// Finding lines of a review text (⚠ lines and list items)
+pub fn review_findings(review: &str) -> Vec<String> {
+    let findings: Vec<String> = review
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with('⚠') || l.starts_with("- ") || l.starts_with("* "))
+        .map(|l| l.trim_start_matches(['⚠', '-', '*', ' ']).trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+    if findings.is_empty() {
+        review
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| vec![l.to_string()])
+            .unwrap_or_default()
+    } else {
+        findings
+    }
+}
+
+/// Group pending reviews by file, merging repeated findings
+pub fn build_digest(pending: &[PendingReview]) -> Vec<DigestFile> {
+    let mut files: Vec<DigestFile> = Vec::new();
+    for review in pending {
+        let index = match files.iter().position(|f| f.path == review.path) {
+            Some(index) => index,
+            None => {
+                files.push(DigestFile {
+                    path: review.path.clone(),
+                    name: review.name.clone(),
+                    reviews: 0,
+                    findings: Vec::new(),
+                });
+                files.len() - 1
+            }
+        };
+        let file = &mut files[index];
+        file.reviews += 1;
+        for finding in review_findings(&review.review) {
+            let key = normalize_finding(&finding);
+            match file.findings.iter_mut().find(|f| normalize_finding(&f.text) == key) {
+                Some(existing) => existing.count += 1,
+                None => file.findings.push(DigestFinding { text: finding, count: 1 }),
+            }
+        }
+    }
+    files.sort_by(|a, b| b.findings.len().cmp(&a.findings.len()).then(a.path.cmp(&b.path)));
+    files
+}
+
+pub fn digest_markdown(date: &str, files: &[DigestFile]) -> String {
+    let total: usize = files.iter().map(|f| f.findings.len()).sum();
+    let mut out = format!(
+        "# コードレビュー日次まとめ {}\n\n{}ファイル・{}件の指摘\n",
+        date,
+        files.len(),
+        total
+    );
+    for file in files {
+        out.push_str(&format!("\n## {}（レビュー{}回）\n", file.path, file.reviews));
+        for finding in &file.findings {
+            if finding.count > 1 {
+                out.push_str(&format!("- {}（{}回）\n", finding.text, finding.count));
+            } else {
+                out.push_str(&format!("- {}\n", finding.text));
+            }
+        }
+    }
+    out
+}
+
+/// Whether the digest is due: past the hour and not yet sent today
+pub fn is_digest_due(settings: &ReviewDigestSettings, now: NaiveDateTime) -> bool {
+    let today = now.format("%Y-%m-%d").to_string();
+    now.hour() >= settings.hour && settings.last_sent_on.as_deref() != Some(today.as_str())
+}
+
+/// Summarize and clear the pending reviews, then notify
+pub fn send_digest(app: &AppHandle) -> Result<Option<ReviewDigest>, String> {
+    let date = Local::now().format("%Y-%m-%d").to_string();
+    let (files, markdown, report_path) = {
+        // Held until the reviews are cleared, so none added meanwhile is lost
+        let _guard = PENDING_LOCK.lock().map_err(|e| e.to_string())?;
+        let pending = load_pending();
+        if pending.is_empty() {
+            return Ok(None);
+        }
+        let files = build_digest(&pending);
+        let markdown = digest_markdown(&date, &files);
+        let report_path = report_path(&date);
+        write_atomic(&report_path, &markdown)?;
+        fs::remove_file(pending_path()).map_err(|e| e.to_string())?;
+        (files, markdown, report_path.to_string_lossy().to_string())
+    };
+
+    let digest = ReviewDigest { date, files, report_path };
+    let _ = app.emit("code-review-digest", digest.clone());
+    let _ = app.emit(
+        "show-notification",
+        serde_json::json!({
+            "title": "コードレビュー日次まとめ",
+            "body": markdown.lines().nth(2).unwrap_or_default(),
+            "path": digest.report_path.clone()
+        }),
+    );
+    Ok(Some(digest))
+}
+
+/// Produce the digest when due (run by the scheduler)
+pub fn send_due_digest(app: &AppHandle) {
+    let mut settings = load_settings();
+    let Some(mut digest) = settings.review_digest.clone() else {
+        return;
+    };
+    let now = Local::now().naive_local();
+    if !is_digest_due(&digest, now) {
+        return;
+    }
+    if let Err(e) = send_digest(app) {
+        // Tried again on the next check
+        emit_log(app, &format!("コードレビューまとめエラー: {}", e), "error");
+        return;
+    }
+    digest.last_sent_on = Some(now.format("%Y-%m-%d").to_string());
+    settings.review_digest = Some(digest);
+    let _ = save_settings(&settings);
+}
+
+/// コードレビューの日次まとめ設定（None: 保存ごとに通知）
+#[tauri::command]
+pub fn get_review_digest_settings() -> Option<ReviewDigestSettings> {
+    load_settings().review_digest
+}
+
+#[tauri::command]
+pub fn set_review_digest_settings(digest: Option<ReviewDigestSettings>) -> Result<(), String> {
+    if let Some(d) = &digest {
+        if d.hour > 23 {
+            return Err("時刻は0〜23で指定してください".to_string());
+        }
+    }
+    let mut settings = load_settings();
+    settings.review_digest = digest;
+    save_settings(&settings)
+}
+
+/// 溜まっているレビュー指摘を今すぐまとめて通知
+#[tauri::command]
+pub fn send_review_digest_now(app: AppHandle) -> Result<Option<ReviewDigest>, String> {
+    send_digest(&app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn review(path: &str, text: &str) -> PendingReview {
+        PendingReview {
+            path: path.to_string(),
+            name: Path::new(path).file_name().unwrap().to_string_lossy().to_string(),
+            timestamp: String::new(),
+            review: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn digest_groups_by_file_and_merges_repeats() {
+        let pending = vec![
+            review("src/a.rs", "## 指摘\n⚠ unwrap でパニックの可能性\n- 関数が長すぎる"),
+            review("src/b.rs", "この変更は別モジュールに置くべきです"),
+            review("src/a.rs", "⚠ unwrap でパニックの 可能性"),
+        ];
+        let files = build_digest(&pending);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/a.rs");
+        assert_eq!(files[0].reviews, 2);
+        assert_eq!(
+            files[0].findings,
+            vec![
+                DigestFinding { text: "unwrap でパニックの可能性".to_string(), count: 2 },
+                DigestFinding { text: "関数が長すぎる".to_string(), count: 1 },
+            ]
+        );
+        assert_eq!(files[1].findings[0].text, "この変更は別モジュールに置くべきです");
+
+        let markdown = digest_markdown("2026-03-10", &files);
+        assert!(markdown.contains("2ファイル・3件の指摘"));
+        assert!(markdown.contains("- unwrap でパニックの可能性（2回）"));
+    }
+
+    #[test]
+    fn digest_is_due_once_a_day_after_the_hour() {
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        let mut settings = ReviewDigestSettings { hour: 18, last_sent_on: None };
+        assert!(!is_digest_due(&settings, at("2026-03-10 17:59:00")));
+        assert!(is_digest_due(&settings, at("2026-03-10 18:00:00")));
+        settings.last_sent_on = Some("2026-03-10".to_string());
+        assert!(!is_digest_due(&settings, at("2026-03-10 22:00:00")));
+        assert!(is_digest_due(&settings, at("2026-03-11 18:30:00")));
+    }
+}
@@ -0,0 +1,239 @@
+//! Code review metrics over time
+//!
+//! Every review of the code watcher is recorded in the `code_reviews` table
+//! with the number of findings. The stats show the issues per week, the files
+//! flagged most often and the resolution rate: the share of flagged files
+//! whose latest review came back clean.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::database::open_db;
+use crate::review_digest::review_findings;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Weeks shown when the frontend does not ask for a period
+const DEFAULT_WEEKS: u32 = 12;
+
+/// Files listed in the most-flagged ranking
+const TOP_FILES: usize = 10;
+
+/// Reviews of one week
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct WeeklyReviews {
+    /// Monday of the week (YYYY-MM-DD)
+    pub week: String,
+    pub reviews: usize,
+    /// Reviews with issues
+    pub flagged: usize,
+    pub issues: usize,
+}
+
+/// A file flagged in the period
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FlaggedFile {
+    pub path: String,
+    pub name: String,
+    pub flagged_reviews: usize,
+    pub issues: usize,
+    /// The latest review had no issues
+    pub resolved: bool,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct ReviewStats {
+    pub weekly: Vec<WeeklyReviews>,
+    /// Most flagged files first
+    pub top_files: Vec<FlaggedFile>,
+    pub flagged_files: usize,
+    pub resolved_files: usize,
+    /// `resolved_files / flagged_files` (None when nothing was flagged)
+    pub resolution_rate: Option<f64>,
+}
+
+/// Store the outcome of a review
+pub fn store_review(
+    conn: &Connection,
+    repo_folder: &str,
+    file_path: &str,
+    file_name: &str,
+    reviewed_at: &str,
+    has_issues: bool,
+    review: &str,
+) -> Result<(), String> {
+    let issues = if has_issues {
+        review_findings(review).len().max(1)
+    } else {
+        0
+    };
+    conn.execute(
+        "INSERT INTO code_reviews (repo_folder, file_path, file_name, reviewed_at, has_issues, issue_count)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![repo_folder, file_path, file_name, reviewed_at, has_issues, issues as i64],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// Record a review of the code watcher (best effort)
+pub fn record_review(repo_folder: &str, file_path: &str, file_name: &str, has_issues: bool, review: &str) {
+    if let Ok(conn) = open_db() {
+        let now = Local::now().format(TIMESTAMP_FORMAT).to_string();
+        let _ = store_review(&conn, repo_folder, file_path, file_name, &now, has_issues, review);
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// Stats of the last `weeks` weeks up to `today` (all repositories when
+/// `repo_folder` is None); weeks without reviews are included
+pub fn review_stats(
+    conn: &Connection,
+    repo_folder: Option<&str>,
+    today: NaiveDate,
+    weeks: u32,
+) -> Result<ReviewStats, String> {
+    let first_week = week_start(today) - Duration::weeks(i64::from(weeks.max(1)) - 1);
+    let mut weekly: BTreeMap<NaiveDate, WeeklyReviews> = BTreeMap::new();
+    let mut week = first_week;
+    while week <= today {
+        weekly.insert(
+            week,
+            WeeklyReviews {
+                week: week.format("%Y-%m-%d").to_string(),
+                reviews: 0,
+                flagged: 0,
+                issues: 0,
+            },
+        );
+        week += Duration::weeks(1);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, file_name, reviewed_at, has_issues, issue_count FROM code_reviews
+             WHERE (?1 IS NULL OR repo_folder = ?1) AND reviewed_at >= ?2
+             ORDER BY reviewed_at, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![repo_folder, first_week.format("%Y-%m-%d").to_string()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut files: BTreeMap<String, FlaggedFile> = BTreeMap::new();
+    for (path, name, reviewed_at, has_issues, issues) in rows.filter_map(Result::ok) {
+        let issues = issues.max(0) as usize;
+        let date = reviewed_at
+            .get(..10)
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        if let Some(week) = date.and_then(|d| weekly.get_mut(&week_start(d))) {
+            week.reviews += 1;
+            week.flagged += usize::from(has_issues);
+            week.issues += issues;
+        }
+        match files.get_mut(&path) {
+            Some(file) => {
+                file.flagged_reviews += usize::from(has_issues);
+                file.issues += issues;
+                file.resolved = !has_issues;
+            }
+            // Files are counted from their first flagged review
+            None if has_issues => {
+                files.insert(
+                    path.clone(),
+                    FlaggedFile {
+                        path,
+                        name,
+                        flagged_reviews: 1,
+                        issues,
+                        resolved: false,
+                    },
+                );
+            }
+            None => {}
+        }
+    }
+
+    let flagged_files = files.len();
+    let resolved_files = files.values().filter(|f| f.resolved).count();
+    let mut top_files: Vec<FlaggedFile> = files.into_values().collect();
+    top_files.sort_by(|a, b| {
+        b.flagged_reviews
+            .cmp(&a.flagged_reviews)
+            .then(b.issues.cmp(&a.issues))
+            .then(a.path.cmp(&b.path))
+    });
+    top_files.truncate(TOP_FILES);
+    Ok(ReviewStats {
+        weekly: weekly.into_values().collect(),
+        top_files,
+        flagged_files,
+        resolved_files,
+        resolution_rate: (flagged_files > 0).then(|| resolved_files as f64 / flagged_files as f64),
+    })
+}
+
+/// コードレビューの推移（週ごとの指摘数・指摘の多いファイル・解消率）
+#[tauri::command]
+pub fn get_review_stats(folder: Option<String>, weeks: Option<u32>) -> Result<ReviewStats, String> {
+    let weeks = weeks.unwrap_or(DEFAULT_WEEKS).clamp(1, 104);
+    review_stats(&open_db()?, folder.as_deref(), Local::now().date_naive(), weeks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    #[test]
+    fn stats_count_weekly_issues_and_resolved_files() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let reviews = [
+            ("/repo/src/a.rs", "2026-03-31 10:00:00", true, "- ⚠ unwrap が多い\n- ⚠ 命名が不統一"),
+            ("/repo/src/b.rs", "2026-04-01 11:00:00", true, "- ⚠ エラーを握りつぶしている"),
+            ("/repo/src/a.rs", "2026-04-07 09:00:00", false, "問題ありません"),
+            ("/repo/src/b.rs", "2026-04-08 09:00:00", true, "- ⚠ エラーを握りつぶしている"),
+            ("/repo/src/c.rs", "2026-04-08 10:00:00", false, "問題ありません"),
+        ];
+        for (path, at, has_issues, review) in reviews {
+            let name = path.rsplit('/').next().unwrap();
+            store_review(&conn, "/repo", path, name, at, has_issues, review).unwrap();
+        }
+        store_review(&conn, "/other", "/other/x.rs", "x.rs", "2026-04-08 10:00:00", true, "- ⚠ x").unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2026, 4, 9).unwrap();
+        let stats = review_stats(&conn, Some("/repo"), today, 3).unwrap();
+        let weeks: Vec<(&str, usize, usize)> = stats
+            .weekly
+            .iter()
+            .map(|w| (w.week.as_str(), w.reviews, w.issues))
+            .collect();
+        assert_eq!(weeks, vec![("2026-03-23", 0, 0), ("2026-03-30", 2, 3), ("2026-04-06", 3, 1)]);
+
+        let top: Vec<(&str, usize, bool)> = stats
+            .top_files
+            .iter()
+            .map(|f| (f.name.as_str(), f.flagged_reviews, f.resolved))
+            .collect();
+        assert_eq!(top, vec![("b.rs", 2, false), ("a.rs", 1, true)]);
+        assert_eq!(stats.resolution_rate, Some(0.5));
+
+        let all = review_stats(&conn, None, today, 3).unwrap();
+        assert_eq!(all.flagged_files, 3);
+    }
+}
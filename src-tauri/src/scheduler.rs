@@ -0,0 +1,184 @@
+//! Background scheduler for periodic tasks
+//!
+//! A single task wakes up hourly and runs every task that is due.
+
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime};
+use tauri::{AppHandle, Emitter};
+
+use crate::events::emit_log;
+use crate::expiry::warn_expiring_documents;
+use crate::guidelines::{
+    detect_document_type_in, diff_guidelines, load_guidelines_json, regenerate_guidelines,
+    RegenerationOutcome,
+};
+use crate::history::load_all_histories;
+use crate::project_settings::{load_project_settings, save_project_settings};
+use crate::report::{previous_month, write_monthly_report};
+use crate::review_digest::send_due_digest;
+use crate::tasks::{self, run_blocking};
+
+/// Delay before the first run so startup work finishes first
+const STARTUP_DELAY: Duration = Duration::from_secs(60);
+
+/// Interval between checks for due tasks
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Start the scheduler task
+pub fn start_scheduler(app: AppHandle) {
+    tasks::spawn("scheduler", "定期タスク", async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        loop {
+            let app = app.clone();
+            let _ = run_blocking("scheduler", "定期タスク実行", move || run_due_tasks(&app)).await;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+fn run_due_tasks(app: &AppHandle) {
+    regenerate_due_guidelines(app);
+    write_due_monthly_reports(app);
+    warn_expiring_documents(app);
+    send_due_digest(app);
+}
+
+/// Whether a task last run at `last_run` is due again after `interval_days`
+pub fn is_due(last_run: Option<&str>, interval_days: u32, now: NaiveDateTime) -> bool {
+    match last_run.and_then(|s| NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT).ok()) {
+        Some(last) => now - last >= chrono::Duration::days(i64::from(interval_days)),
+        None => true,
+    }
+}
+
+/// Regenerate guidelines of projects with `auto_guidelines` enabled and due
+fn regenerate_due_guidelines(app: &AppHandle) {
+    let now = Local::now().naive_local();
+
+    for history in load_all_histories() {
+        let folder = history.project_folder.clone();
+        if !Path::new(&folder).is_dir() {
+            continue;
+        }
+        let mut project = load_project_settings(&folder);
+        let Some(mut config) = project.auto_guidelines.clone() else {
+            continue;
+        };
+        if !is_due(config.last_generated_at.as_deref(), config.interval_days, now) {
+            continue;
+        }
+
+        // Learn from the last N analyses
+        let mut issues: Vec<String> = Vec::new();
+        let mut doc_types: Vec<String> = Vec::new();
+        for entry in history.entries.iter().rev().take(config.last_n) {
+            for issue in &entry.issues {
+                let formatted = format!("[{}] {}", entry.file_name, issue);
+                if !issues.contains(&formatted) {
+                    issues.push(formatted);
+                }
+            }
+            for t in detect_document_type_in(&folder, &entry.file_name) {
+                if !doc_types.contains(&t) {
+                    doc_types.push(t);
+                }
+            }
+        }
+        if issues.is_empty() {
+            continue;
+        }
+
+        emit_log(app, &format!("ガイドライン定期再生成: {}", folder), "wave");
+        let old = load_guidelines_json(&folder).unwrap_or_default();
+        match regenerate_guidelines(&folder, &issues, &[], &doc_types) {
+            Ok(RegenerationOutcome::Saved(new)) => {
+                let diff = diff_guidelines(&old, &new);
+                emit_log(app, "✓ ガイドライン定期再生成完了", "success");
+                if !diff.is_empty() {
+                    let _ = app.emit(
+                        "show-notification",
+                        serde_json::json!({
+                            "title": "ガイドライン更新",
+                            "body": diff.summary(),
+                            "path": folder
+                        }),
+                    );
+                }
+            }
+            Ok(RegenerationOutcome::Raw { parse_error, .. }) => {
+                emit_log(app, &format!("ガイドラインJSON解析エラー: {}", parse_error), "error");
+            }
+            Err(e) => {
+                emit_log(app, &format!("ガイドライン再生成エラー: {}", e), "error");
+            }
+        }
+
+        // Record the attempt either way so a failing project is not retried hourly
+        config.last_generated_at = Some(now.format(TIMESTAMP_FORMAT).to_string());
+        project.auto_guidelines = Some(config);
+        let _ = save_project_settings(&folder, &project);
+    }
+}
+
+/// Write last month's report for projects with `monthly_report` enabled
+fn write_due_monthly_reports(app: &AppHandle) {
+    let month = previous_month(Local::now().date_naive());
+
+    for history in load_all_histories() {
+        let folder = history.project_folder.clone();
+        if !Path::new(&folder).is_dir() {
+            continue;
+        }
+        let mut project = load_project_settings(&folder);
+        let Some(mut config) = project.monthly_report.clone() else {
+            continue;
+        };
+        if config.last_report_month.as_deref() == Some(month.as_str()) {
+            continue;
+        }
+
+        match write_monthly_report(&folder, &month, &config) {
+            Ok(report) => {
+                emit_log(app, &format!("✓ 月次報告を作成: {}", report.path), "success");
+                let _ = app.emit(
+                    "show-notification",
+                    serde_json::json!({
+                        "title": format!("月次報告 {}", month),
+                        "body": report.content.lines().skip(2).take(4).collect::<Vec<_>>().join("\n"),
+                        "path": report.path
+                    }),
+                );
+            }
+            Err(e) => {
+                emit_log(app, &format!("月次報告エラー: {}", e), "error");
+            }
+        }
+
+        // Record the attempt either way so a failing project is not retried hourly
+        config.last_report_month = Some(month.clone());
+        project.monthly_report = Some(config);
+        let _ = save_project_settings(&folder, &project);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn is_due_respects_interval() {
+        let now = at("2026-03-10 09:00:00");
+        assert!(is_due(None, 7, now));
+        assert!(is_due(Some("2026-03-03 09:00:00"), 7, now));
+        assert!(!is_due(Some("2026-03-05 09:00:00"), 7, now));
+        assert!(is_due(Some("broken"), 7, now));
+    }
+}
@@ -0,0 +1,362 @@
+//! 印影 (seal impression) extraction and cross-document comparison
+//!
+//! Seals are found as compact clusters of vermilion pixels in the page images
+//! of scanned PDFs and cut out into the app data folder (one folder per
+//! project), so the project folder itself is never written to. Compare mode
+//! checks deterministically that every document carries a seal and lists
+//! where the seals are; whether two impressions come from the same 印鑑 is
+//! left to the reader, with the cut-outs side by side.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{imageops, ImageFormat, RgbImage};
+use lopdf::Document;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::project_settings::project_folder_for;
+
+/// Page images are scaled down to this longest side before detection
+const MAX_DETECTION_SIDE: u32 = 1000;
+
+/// Red pixels are merged on a grid of this cell size (joins seal strokes)
+const GRID_CELL: u32 = 4;
+
+/// A seal impression found in a document
+#[derive(Clone, Serialize)]
+pub struct SealImpression {
+    pub file_name: String,
+    /// 1-based page number
+    pub page: u32,
+    /// Bounding box in the page image (x, y, width, height)
+    pub bbox: (u32, u32, u32, u32),
+    /// Where on the page the seal is (e.g. 右下)
+    pub location: String,
+    /// Saved cut-out image
+    pub image_path: Option<String>,
+}
+
+/// Vermilion ink (朱肉) as found on scans
+fn is_seal_red(pixel: &image::Rgb<u8>) -> bool {
+    let [r, g, b] = pixel.0;
+    r >= 140 && r as i32 - g as i32 >= 60 && r as i32 - b as i32 >= 50
+}
+
+/// Bounding boxes (in image pixels) of seal-like red clusters
+pub fn find_seal_regions(img: &RgbImage) -> Vec<(u32, u32, u32, u32)> {
+    let (grid_w, grid_h) = (img.width().div_ceil(GRID_CELL), img.height().div_ceil(GRID_CELL));
+    let mut grid = vec![false; (grid_w * grid_h) as usize];
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if is_seal_red(pixel) {
+            grid[((y / GRID_CELL) * grid_w + x / GRID_CELL) as usize] = true;
+        }
+    }
+
+    let mut seen = vec![false; grid.len()];
+    let mut regions = Vec::new();
+    for start in 0..grid.len() {
+        if !grid[start] || seen[start] {
+            continue;
+        }
+        // Flood fill (8-neighbourhood) over red cells
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+        let mut cells = 0u32;
+        let mut queue = VecDeque::from([start]);
+        seen[start] = true;
+        while let Some(index) = queue.pop_front() {
+            let (cx, cy) = (index as u32 % grid_w, index as u32 / grid_w);
+            cells += 1;
+            min_x = min_x.min(cx);
+            min_y = min_y.min(cy);
+            max_x = max_x.max(cx);
+            max_y = max_y.max(cy);
+            for dy in -1i32..=1 {
+                for dx in -1i32..=1 {
+                    let (nx, ny) = (cx as i32 + dx, cy as i32 + dy);
+                    if nx < 0 || ny < 0 || nx >= grid_w as i32 || ny >= grid_h as i32 {
+                        continue;
+                    }
+                    let next = (ny as u32 * grid_w + nx as u32) as usize;
+                    if grid[next] && !seen[next] {
+                        seen[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        let (w, h) = (max_x - min_x + 1, max_y - min_y + 1);
+        let aspect = w as f64 / h as f64;
+        let fill = cells as f64 / (w * h) as f64;
+        let max_side = grid_w.min(grid_h) / 3;
+        let plausible = w >= 4
+            && h >= 4
+            && w <= max_side
+            && h <= max_side
+            && (0.5..=2.0).contains(&aspect)
+            && fill >= 0.1;
+        if plausible {
+            regions.push(tighten(
+                img,
+                (
+                    min_x * GRID_CELL,
+                    min_y * GRID_CELL,
+                    (w * GRID_CELL).min(img.width() - min_x * GRID_CELL),
+                    (h * GRID_CELL).min(img.height() - min_y * GRID_CELL),
+                ),
+            ));
+        }
+    }
+    regions
+}
+
+/// Shrink a grid-aligned box to the red pixels inside it
+fn tighten(img: &RgbImage, (x0, y0, w, h): (u32, u32, u32, u32)) -> (u32, u32, u32, u32) {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for y in y0..y0 + h {
+        for x in x0..x0 + w {
+            if is_seal_red(img.get_pixel(x, y)) {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+    if min_x == u32::MAX {
+        return (x0, y0, w, h);
+    }
+    (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+}
+
+/// Decode the RGB page images of a PDF (JPEG and 8-bit Flate RGB)
+fn page_images(pdf_path: &str) -> Result<Vec<(u32, RgbImage)>, String> {
+    let doc = Document::load(pdf_path).map_err(|e| format!("PDF読み込みエラー: {}", e))?;
+    let mut images = Vec::new();
+    for (page_number, page_id) in doc.get_pages() {
+        let Ok(page_images) = doc.get_page_images(page_id) else {
+            continue;
+        };
+        for pdf_image in page_images {
+            let filters = pdf_image.filters.clone().unwrap_or_default();
+            let decoded = if filters.iter().any(|f| f == "DCTDecode") {
+                image::load_from_memory_with_format(pdf_image.content, ImageFormat::Jpeg)
+                    .ok()
+                    .map(|img| img.to_rgb8())
+            } else if pdf_image.color_space.as_deref() == Some("DeviceRGB")
+                && pdf_image.bits_per_component == Some(8)
+            {
+                doc.get_object(pdf_image.id)
+                    .and_then(|o| o.as_stream())
+                    .ok()
+                    .and_then(|s| s.decompressed_content().ok())
+                    .and_then(|raw| {
+                        RgbImage::from_raw(pdf_image.width as u32, pdf_image.height as u32, raw)
+                    })
+            } else {
+                None
+            };
+            if let Some(img) = decoded {
+                images.push((page_number, img));
+            }
+        }
+    }
+    Ok(images)
+}
+
+fn scale_for_detection(img: RgbImage) -> RgbImage {
+    let longest = img.width().max(img.height());
+    if longest <= MAX_DETECTION_SIDE {
+        return img;
+    }
+    let (w, h) = (
+        img.width() * MAX_DETECTION_SIDE / longest,
+        img.height() * MAX_DETECTION_SIDE / longest,
+    );
+    imageops::resize(&img, w.max(1), h.max(1), imageops::FilterType::Triangle)
+}
+
+/// Seal folder (in the app data folder) of the project a document belongs to
+pub fn seal_dir_for(pdf_path: &str) -> PathBuf {
+    let project = project_folder_for(pdf_path);
+    let key: String = Sha256::digest(project.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("seals").join(key)
+}
+
+/// Page area of a bounding box in thirds (e.g. 右下, 上中央)
+pub fn location_label(bbox: (u32, u32, u32, u32), width: u32, height: u32) -> String {
+    let (x, y, w, h) = bbox;
+    const LABELS: [[&str; 3]; 3] = [
+        ["左上", "上中央", "右上"],
+        ["左中央", "中央", "右中央"],
+        ["左下", "下中央", "右下"],
+    ];
+    let third = |center: u32, len: u32| (center * 3 / len.max(1)).min(2) as usize;
+    LABELS[third(y + h / 2, height)][third(x + w / 2, width)].to_string()
+}
+
+/// Find the seals of a PDF; with `save_dir`, cut-outs are saved as PNG
+pub fn extract_seals(
+    pdf_path: &str,
+    save_dir: Option<&Path>,
+) -> Result<Vec<SealImpression>, String> {
+    let file_name = Path::new(pdf_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = Path::new(&file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(dir) = save_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("印影保存フォルダ作成エラー: {}", e))?;
+    }
+
+    let mut seals = Vec::new();
+    for (page, img) in page_images(pdf_path)? {
+        let img = scale_for_detection(img);
+        for bbox in find_seal_regions(&img) {
+            let image_path = save_dir.and_then(|dir| {
+                let path = dir.join(format!("{}_p{}_{}.png", stem, page, seals.len() + 1));
+                let (x, y, w, h) = bbox;
+                imageops::crop_imm(&img, x, y, w, h)
+                    .to_image()
+                    .save(&path)
+                    .ok()
+                    .map(|_| path.to_string_lossy().to_string())
+            });
+            seals.push(SealImpression {
+                file_name: file_name.clone(),
+                page,
+                bbox,
+                location: location_label(bbox, img.width(), img.height()),
+                image_path,
+            });
+        }
+    }
+    Ok(seals)
+}
+
+/// Deterministic findings for compare mode (⚠ or ✓ per document, then a note)
+///
+/// Flags documents without any seal while others have one and lists where
+/// each document is sealed. Seal images are not judged against each other:
+/// a ring of red ink looks alike for most 印鑑, so a match would say nothing.
+pub fn compare_seals(documents: &[(String, Vec<SealImpression>)]) -> Vec<String> {
+    let with_seals = documents.iter().filter(|(_, seals)| !seals.is_empty()).count();
+    if documents.len() < 2 || with_seals == 0 {
+        return Vec::new();
+    }
+
+    let mut findings = Vec::new();
+    for (name, seals) in documents {
+        if seals.is_empty() {
+            findings.push(format!(
+                "⚠ 印影照合: {} に印影が見つかりません（他の書類には押印があります）",
+                name
+            ));
+            continue;
+        }
+        let positions: Vec<String> = seals
+            .iter()
+            .map(|s| format!("{}ページ {}", s.page, s.location))
+            .collect();
+        findings.push(format!("✓ 印影照合: {} の押印位置: {}", name, positions.join("、")));
+    }
+    if with_seals >= 2 {
+        findings.push(
+            "※ 印影が同じ印鑑のものかは自動判定していません。切り出した印影を並べて目視で確認してください"
+                .to_string(),
+        );
+    }
+    findings
+}
+
+/// 書類から印影を切り出して保存
+#[tauri::command]
+pub fn extract_seal_impressions(path: String) -> Result<Vec<SealImpression>, String> {
+    extract_seals(&path, Some(&seal_dir_for(&path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// White page with a red ring (radius r) at (cx, cy)
+    fn page_with_seal(cx: i32, cy: i32, r: i32, square: bool) -> RgbImage {
+        let mut img = RgbImage::from_pixel(400, 400, image::Rgb([255, 255, 255]));
+        for y in 0..400 {
+            for x in 0..400 {
+                let (dx, dy) = (x - cx, y - cy);
+                let on_shape = if square {
+                    dx.abs().max(dy.abs()) <= r && dx.abs().max(dy.abs()) >= r - 4
+                } else {
+                    let d = ((dx * dx + dy * dy) as f64).sqrt();
+                    d <= r as f64 && d >= (r - 4) as f64
+                };
+                if on_shape {
+                    img.put_pixel(x as u32, y as u32, image::Rgb([220, 40, 40]));
+                }
+            }
+        }
+        img
+    }
+
+    fn seal(name: &str, img: &RgbImage) -> SealImpression {
+        let bbox = find_seal_regions(img)[0];
+        SealImpression {
+            file_name: name.to_string(),
+            page: 1,
+            bbox,
+            location: location_label(bbox, img.width(), img.height()),
+            image_path: None,
+        }
+    }
+
+    #[test]
+    fn finds_red_ring_but_not_black_text() {
+        let mut img = page_with_seal(300, 300, 30, false);
+        for x in 20..200 {
+            img.put_pixel(x, 50, image::Rgb([0, 0, 0]));
+        }
+        let regions = find_seal_regions(&img);
+        assert_eq!(regions.len(), 1);
+        let (x, y, w, h) = regions[0];
+        assert!(x <= 270 && y <= 270 && x + w >= 330 && y + h >= 330);
+        assert_eq!(location_label(regions[0], 400, 400), "右下");
+    }
+
+    #[test]
+    fn compare_flags_missing_seals_and_lists_positions() {
+        let round = page_with_seal(200, 200, 30, false);
+        let square = page_with_seal(330, 330, 30, true);
+
+        let documents = vec![
+            ("見積書.pdf".to_string(), vec![seal("見積書.pdf", &round)]),
+            ("契約書.pdf".to_string(), vec![seal("契約書.pdf", &square)]),
+            ("請求書.pdf".to_string(), vec![]),
+        ];
+        let findings = compare_seals(&documents);
+        assert!(findings.iter().any(|f| f.contains("請求書.pdf に印影が見つかりません")));
+        assert!(findings.iter().any(|f| f.contains("見積書.pdf の押印位置: 1ページ 中央")));
+        assert!(findings.iter().any(|f| f.contains("契約書.pdf の押印位置: 1ページ 右下")));
+        // Different 印鑑 are not claimed to match or differ
+        assert!(!findings.iter().any(|f| f.contains("一致")));
+    }
+
+    #[test]
+    fn seals_are_saved_outside_the_project_folder() {
+        let project = std::env::temp_dir().join("A工事");
+        let pdf = |name: &str| project.join(name).to_string_lossy().to_string();
+        let dir = seal_dir_for(&pdf("見積書.pdf"));
+        assert!(!dir.starts_with(&project));
+        assert_eq!(dir, seal_dir_for(&pdf("契約書.pdf")));
+    }
+}
@@ -1,15 +1,240 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use serde::{Serialize, Deserialize};
 
+use crate::consensus::ConsensusSettings;
+use crate::file_lock::write_atomic;
+use crate::gemini_cli::{temp_root, validate_temp_root};
+use crate::history::{load_all_histories, AnalysisHistory};
+use crate::hooks::AnalysisHook;
+use crate::intake::IntakeSettings;
+use crate::mail::store_smtp_password;
+use crate::mail_intake::MailIntakeSettings;
+use crate::messages::Language;
+use crate::openai_compat::OpenAiCompatSettings;
+use crate::project_settings::resolve_project_root;
+use crate::retry::RetrySettings;
+use crate::review_digest::ReviewDigestSettings;
+
 pub const DEFAULT_MODEL: &str = "gemini-2.5-pro";
 
+/// Cheaper model used for テキストモード (text-only prompts)
+pub const DEFAULT_TEXT_MODEL: &str = "gemini-2.5-flash";
+
+/// Time a single Gemini CLI run may take before it is killed
+pub const DEFAULT_GEMINI_TIMEOUT_SECS: u64 = 900;
+
+/// Documents analyzed at once; more Gemini CLI processes mostly time out
+pub const DEFAULT_MAX_PARALLEL_ANALYSES: usize = 3;
+
+/// Where analysis results are stored
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultStorage {
+    /// PDF metadata (falls back to the sidecar if embedding fails)
+    #[default]
+    Embed,
+    /// `<name>.shorui.json` next to the PDF
+    Sidecar,
+    /// Both PDF metadata and sidecar
+    Both,
+}
+
+/// What answers the AI requests
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AiBackend {
+    /// Gemini CLI
+    #[default]
+    Gemini,
+    /// Gemini REST API without the CLI (key in the keychain)
+    #[serde(rename = "gemini_api")]
+    GeminiApi,
+    /// Anthropic Messages API (key in the keychain)
+    Claude,
+    /// OpenAI-compatible server (Ollama, LM Studio, vLLM) in the office network
+    #[serde(rename = "openai")]
+    OpenAiCompat,
+    /// Canned responses without network or CLI (development, demos, tests)
+    Mock,
+}
+
+/// Where the analysis histories are stored
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    /// JSON files in the config directory
+    #[default]
+    Local,
+    /// Shared PostgreSQL database (connection string in the keychain)
+    Postgres,
+}
+
+/// How the project folder of a document is determined
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ProjectRootStrategy {
+    /// The document's parent folder
+    #[default]
+    Parent,
+    /// The folder `depth` levels below the watch folder (1 = its subfolders)
+    Depth { depth: usize },
+    /// The nearest ancestor folder containing the marker file
+    Marker { marker: String },
+}
+
 #[derive(Clone, Serialize, Deserialize, Default)]
 pub struct AppSettings {
     pub watch_folder: Option<String>,
     pub model: Option<String>,
     pub code_watch_folder: Option<String>,
     pub code_review_enabled: bool,
+    /// Root for analysis temp dirs (defaults to `%TEMP%/shoruichecker`)
+    pub temp_root: Option<String>,
+    #[serde(default)]
+    pub result_storage: ResultStorage,
+    /// Outgoing mail server for report delivery
+    #[serde(default)]
+    pub smtp: Option<SmtpSettings>,
+    /// テキストモード: send locally extracted text instead of the PDF; only
+    /// scanned pages go to the vision model
+    #[serde(default)]
+    pub text_mode: bool,
+    /// Model for テキストモード (defaults to `DEFAULT_TEXT_MODEL`)
+    #[serde(default)]
+    pub text_model: Option<String>,
+    /// Project folder resolution under the watch folder
+    #[serde(default)]
+    pub project_root: ProjectRootStrategy,
+    /// Named analysis presets
+    #[serde(default)]
+    pub presets: Vec<AnalysisPreset>,
+    /// Language of backend logs and error messages
+    #[serde(default)]
+    pub language: Language,
+    /// Encrypt history, sidecars and database text (key in the OS keychain)
+    #[serde(default)]
+    pub encrypt_at_rest: bool,
+    /// External commands run before/after each analysis batch
+    #[serde(default)]
+    pub hooks: Vec<AnalysisHook>,
+    /// Intake folder for the print-to-PDF driver
+    #[serde(default)]
+    pub intake: IntakeSettings,
+    /// Days before a document's expiry date a warning is shown (`None` = 30)
+    #[serde(default)]
+    pub expiry_warning_days: Option<u32>,
+    /// Outlook inbox polled for PDF attachments
+    #[serde(default)]
+    pub mail_intake: MailIntakeSettings,
+    #[serde(default)]
+    pub ai_backend: AiBackend,
+    /// Fixture file or folder of the mock backend (None: built-in responses)
+    #[serde(default)]
+    pub mock_fixtures: Option<String>,
+    /// Code review findings summarized daily (None: notified on every save)
+    #[serde(default)]
+    pub review_digest: Option<ReviewDigestSettings>,
+    #[serde(default)]
+    pub history_backend: HistoryBackend,
+    /// Model of the Claude provider (None: `DEFAULT_CLAUDE_MODEL`)
+    #[serde(default)]
+    pub claude_model: Option<String>,
+    /// Server of the OpenAI-compatible provider
+    #[serde(default)]
+    pub openai_compat: Option<OpenAiCompatSettings>,
+    /// Providers tried in order when the selected one fails (empty: no fallback)
+    #[serde(default)]
+    pub provider_fallback: Vec<AiBackend>,
+    /// Seconds before a Gemini CLI run is killed (None: `DEFAULT_GEMINI_TIMEOUT_SECS`, 0: never)
+    #[serde(default)]
+    pub gemini_timeout_secs: Option<u64>,
+    /// Second analysis of high-value contracts (None: disabled)
+    #[serde(default)]
+    pub consensus: Option<ConsensusSettings>,
+    /// Retries of AI requests on transient errors
+    #[serde(default)]
+    pub retry: RetrySettings,
+    /// Regexes of CLI output lines to drop (None: `DEFAULT_NOISE_PATTERNS`)
+    #[serde(default)]
+    pub output_noise_patterns: Option<Vec<String>>,
+    /// Analyses run at once across all batches (None: `DEFAULT_MAX_PARALLEL_ANALYSES`)
+    #[serde(default)]
+    pub max_parallel_analyses: Option<usize>,
+}
+
+/// A named combination of mode, instruction, model and checklist
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AnalysisPreset {
+    pub id: String,
+    pub name: String,
+    /// "single" or "compare"
+    pub mode: String,
+    #[serde(default)]
+    pub custom_instruction: String,
+    /// Model override (`None` = the configured model)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Check items added to the instruction as a list
+    #[serde(default)]
+    pub checklist: Vec<String>,
+}
+
+impl AnalysisPreset {
+    /// Custom instruction with the checklist and an extra instruction appended
+    pub fn instruction_with(&self, extra: &str) -> String {
+        let mut parts = Vec::new();
+        if !self.custom_instruction.trim().is_empty() {
+            parts.push(self.custom_instruction.trim().to_string());
+        }
+        if !self.checklist.is_empty() {
+            parts.push(
+                self.checklist
+                    .iter()
+                    .map(|item| format!("- {}", item.trim()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+        }
+        if !extra.trim().is_empty() {
+            parts.push(extra.trim().to_string());
+        }
+        parts.join("\n")
+    }
+}
+
+/// Find a preset by ID or name
+pub fn find_preset<'a>(presets: &'a [AnalysisPreset], key: &str) -> Option<&'a AnalysisPreset> {
+    presets
+        .iter()
+        .find(|p| p.id == key)
+        .or_else(|| presets.iter().find(|p| p.name == key))
+}
+
+/// テキストモードの設定
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TextModeSettings {
+    pub enabled: bool,
+    pub model: String,
+}
+
+/// SMTP server used to email reports
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub username: String,
+    /// Only passed in by `set_smtp_settings`; kept in the OS keychain and
+    /// never written to settings.json or sent to the frontend
+    #[serde(default, skip_serializing)]
+    pub password: String,
+    pub from: String,
+}
+
+fn default_smtp_port() -> u16 {
+    587
 }
 
 pub fn get_settings_path() -> PathBuf {
@@ -30,13 +255,8 @@ pub fn load_settings() -> AppSettings {
 }
 
 pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
-    let path = get_settings_path();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
     let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
-    Ok(())
+    write_atomic(&get_settings_path(), json)
 }
 
 #[tauri::command]
@@ -54,9 +274,233 @@ pub fn set_model(model: String) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+pub fn get_gemini_timeout_secs() -> u64 {
+    load_settings()
+        .gemini_timeout_secs
+        .unwrap_or(DEFAULT_GEMINI_TIMEOUT_SECS)
+}
+
+/// Gemini CLI のタイムアウト（秒）を設定（0 で無制限、None で既定値）
+#[tauri::command]
+pub fn set_gemini_timeout_secs(secs: Option<u64>) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.gemini_timeout_secs = secs;
+    save_settings(&settings)
+}
+
+#[tauri::command]
+pub fn get_max_parallel_analyses() -> usize {
+    load_settings()
+        .max_parallel_analyses
+        .unwrap_or(DEFAULT_MAX_PARALLEL_ANALYSES)
+}
+
+/// 同時に解析する書類数を設定（None で既定値）
+#[tauri::command]
+pub fn set_max_parallel_analyses(count: Option<usize>) -> Result<(), String> {
+    if count.is_some_and(|c| c == 0 || c > 16) {
+        return Err("同時解析数は1～16で指定してください".to_string());
+    }
+    let mut settings = load_settings();
+    settings.max_parallel_analyses = count;
+    save_settings(&settings)
+}
+
+#[tauri::command]
+pub fn get_temp_root() -> String {
+    temp_root().to_string_lossy().to_string()
+}
+
+/// Set the temp root (`None` restores the default)
+///
+/// The folder is validated first (absolute, outside OneDrive, writable and
+/// accessible to the gemini CLI).
+#[tauri::command]
+pub fn set_temp_root(path: Option<String>) -> Result<(), String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(path) = &path {
+        validate_temp_root(std::path::Path::new(path))?;
+    }
+    let mut settings = load_settings();
+    settings.temp_root = path;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_language() -> Language {
+    load_settings().language
+}
+
+/// ログ・エラーメッセージの言語を設定（"ja" / "en"）
+#[tauri::command]
+pub fn set_language(language: Language) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.language = language;
+    save_settings(&settings)
+}
+
+#[tauri::command]
+pub fn get_result_storage() -> ResultStorage {
+    load_settings().result_storage
+}
+
+#[tauri::command]
+pub fn set_result_storage(storage: ResultStorage) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.result_storage = storage;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_text_mode() -> TextModeSettings {
+    let settings = load_settings();
+    TextModeSettings {
+        enabled: settings.text_mode,
+        model: settings
+            .text_model
+            .unwrap_or_else(|| DEFAULT_TEXT_MODEL.to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn set_text_mode(text_mode: TextModeSettings) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.text_mode = text_mode.enabled;
+    settings.text_model = Some(text_mode.model).filter(|m| !m.trim().is_empty());
+    save_settings(&settings)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_project_root_strategy() -> ProjectRootStrategy {
+    load_settings().project_root
+}
+
+/// Analyses whose project folder would be a different one under a strategy
+///
+/// Histories, notes, project settings and the database are kept per project
+/// folder; such analyses would be cut off from their records.
+pub fn analyses_moved_by(
+    histories: &[AnalysisHistory],
+    strategy: &ProjectRootStrategy,
+    watch_folder: Option<&Path>,
+) -> usize {
+    histories
+        .iter()
+        .map(|history| {
+            history
+                .entries
+                .iter()
+                .filter(|e| {
+                    resolve_project_root(Path::new(&e.file_path), strategy, watch_folder)
+                        != Path::new(&history.project_folder)
+                })
+                .count()
+        })
+        .sum()
+}
+
+/// 工事フォルダの判定方法を変更（既存の解析の工事フォルダが変わる場合は変更しない）
+#[tauri::command]
+pub fn set_project_root_strategy(strategy: ProjectRootStrategy) -> Result<(), String> {
+    match &strategy {
+        ProjectRootStrategy::Depth { depth: 0 } => {
+            return Err("階層の深さは1以上を指定してください".to_string())
+        }
+        ProjectRootStrategy::Marker { marker } if marker.trim().is_empty() => {
+            return Err("目印ファイル名を指定してください".to_string())
+        }
+        _ => {}
+    }
+    let mut settings = load_settings();
+    if settings.project_root == strategy {
+        return Ok(());
+    }
+    let watch_folder = settings.watch_folder.as_deref().map(Path::new);
+    let moved = analyses_moved_by(&load_all_histories(), &strategy, watch_folder);
+    if moved > 0 {
+        return Err(format!(
+            "解析済みの書類 {}件の工事フォルダが変わるため変更できません（履歴・メモ・工事設定は工事フォルダごとに保存されています）",
+            moved
+        ));
+    }
+    settings.project_root = strategy;
+    save_settings(&settings)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_presets() -> Vec<AnalysisPreset> {
+    load_settings().presets
+}
+
+/// Add or update a preset (an empty ID creates a new preset)
+#[tauri::command]
+pub fn save_preset(mut preset: AnalysisPreset) -> Result<AnalysisPreset, String> {
+    if preset.name.trim().is_empty() {
+        return Err("プリセット名を指定してください".to_string());
+    }
+    if preset.mode != "single" && preset.mode != "compare" {
+        return Err(format!("不明なモードです: {}", preset.mode));
+    }
+    let mut settings = load_settings();
+    if settings
+        .presets
+        .iter()
+        .any(|p| p.name == preset.name && p.id != preset.id)
+    {
+        return Err(format!("同じ名前のプリセットがあります: {}", preset.name));
+    }
+    if preset.id.is_empty() {
+        preset.id = format!("preset-{}", chrono::Local::now().format("%Y%m%d%H%M%S%3f"));
+    }
+    match settings.presets.iter_mut().find(|p| p.id == preset.id) {
+        Some(existing) => *existing = preset.clone(),
+        None => settings.presets.push(preset.clone()),
+    }
+    save_settings(&settings)?;
+    Ok(preset)
+}
+
+#[tauri::command]
+pub fn delete_preset(id: String) -> Result<(), String> {
+    let mut settings = load_settings();
+    settings.presets.retain(|p| p.id != id);
+    save_settings(&settings)
+}
+
+#[tauri::command]
+pub fn get_smtp_settings() -> Option<SmtpSettings> {
+    load_settings().smtp
+}
+
+/// Set the SMTP server (`None` disables report mail)
+///
+/// An empty password keeps the stored one, as it is never sent back.
+#[tauri::command]
+pub fn set_smtp_settings(smtp: Option<SmtpSettings>) -> Result<(), String> {
+    match &smtp {
+        Some(smtp) if smtp.password.is_empty() => {}
+        Some(smtp) => store_smtp_password(Some(&smtp.password))?,
+        None => store_smtp_password(None)?,
+    }
+    let mut settings = load_settings();
+    settings.smtp = smtp;
+    save_settings(&settings)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::DEFAULT_MODEL;
+    use super::{
+        analyses_moved_by, find_preset, AnalysisPreset, AppSettings, ProjectRootStrategy,
+        ResultStorage, DEFAULT_MODEL,
+    };
+    use crate::history::{create_history_entry, AnalysisHistory};
+    use std::path::Path;
 
     #[test]
     fn default_model_is_set() {
@@ -67,4 +511,68 @@ mod tests {
     fn default_model_is_gemini() {
         assert!(DEFAULT_MODEL.contains("gemini"));
     }
+
+    #[test]
+    fn result_storage_defaults_to_embed_for_old_settings() {
+        let settings: AppSettings =
+            serde_json::from_str(r#"{"watch_folder":null,"code_review_enabled":false}"#)
+                .expect("parse settings");
+        assert_eq!(settings.result_storage, ResultStorage::Embed);
+
+        let settings: AppSettings = serde_json::from_str(
+            r#"{"code_review_enabled":false,"result_storage":"sidecar"}"#,
+        )
+        .expect("parse settings");
+        assert_eq!(settings.result_storage, ResultStorage::Sidecar);
+    }
+
+    #[test]
+    fn preset_lookup_and_instruction() {
+        let preset = AnalysisPreset {
+            id: "preset-1".to_string(),
+            name: "月例請求書チェック".to_string(),
+            mode: "single".to_string(),
+            custom_instruction: "請求額を前月と比較".to_string(),
+            model: None,
+            checklist: vec!["出来高との整合".to_string(), "振込先".to_string()],
+        };
+        let presets = vec![preset];
+        assert!(find_preset(&presets, "preset-1").is_some());
+        assert!(find_preset(&presets, "月例請求書チェック").is_some());
+        assert!(find_preset(&presets, "契約時セット照合").is_none());
+
+        assert_eq!(
+            presets[0].instruction_with("今月分のみ"),
+            "請求額を前月と比較\n- 出来高との整合\n- 振込先\n今月分のみ"
+        );
+    }
+
+    #[test]
+    fn project_root_strategy_is_tagged_by_mode() {
+        let settings: AppSettings = serde_json::from_str(
+            r#"{"code_review_enabled":false,"project_root":{"mode":"depth","depth":1}}"#,
+        )
+        .expect("parse settings");
+        assert_eq!(settings.project_root, ProjectRootStrategy::Depth { depth: 1 });
+
+        let settings: AppSettings =
+            serde_json::from_str(r#"{"code_review_enabled":false}"#).expect("parse settings");
+        assert_eq!(settings.project_root, ProjectRootStrategy::Parent);
+    }
+
+    #[test]
+    fn strategy_changes_that_move_analyses_are_detected() {
+        let history = AnalysisHistory {
+            project_folder: "/watch/工事A/契約".to_string(),
+            entries: vec![create_history_entry("a.pdf", "/watch/工事A/契約/a.pdf", "✓")],
+            ..Default::default()
+        };
+        let histories = [history];
+        let watch = Some(Path::new("/watch"));
+        assert_eq!(analyses_moved_by(&histories, &ProjectRootStrategy::Parent, watch), 0);
+        let depth = ProjectRootStrategy::Depth { depth: 1 };
+        assert_eq!(analyses_moved_by(&histories, &depth, watch), 1);
+        let depth = ProjectRootStrategy::Depth { depth: 2 };
+        assert_eq!(analyses_moved_by(&histories, &depth, watch), 0);
+    }
 }
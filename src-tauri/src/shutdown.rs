@@ -0,0 +1,119 @@
+//! Graceful shutdown
+//!
+//! Exiting while a result is being embedded rewrites the PDF only halfway
+//! and corrupts it. Writes that must not be interrupted (PDF embeds, history
+//! updates) run inside a [`critical_section`]. On exit the app stops taking
+//! new work (watchers, intake, batches) and refuses new sections, waits for
+//! the open ones with a timeout, kills the remaining CLI processes and only
+//! then exits.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::AppHandle;
+
+use crate::batch::{request_abort, running_batches};
+use crate::code_review::stop_code_watcher;
+use crate::events::emit_log;
+use crate::intake::stop_intake_watcher;
+use crate::messages::tr;
+use crate::processes::kill_all_children;
+use crate::tasks::{self, abort_tasks};
+use crate::watcher::stop_watching;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static OPEN_SECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Longest wait for open writes before exiting anyway
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(20);
+const FLUSH_POLL: Duration = Duration::from_millis(100);
+
+/// Background loops that only pick up new work; aborted right away
+const INTAKE_TASK_KINDS: &[&str] = &["watcher", "intake", "mail_intake", "scheduler"];
+
+/// Keeps a write registered as in flight until dropped
+pub struct CriticalSection(());
+
+impl Drop for CriticalSection {
+    fn drop(&mut self) {
+        OPEN_SECTIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Mark a write that must complete before the app exits; refused once the
+/// shutdown has started, as the exit may no longer wait for it
+pub fn critical_section() -> Result<CriticalSection, String> {
+    OPEN_SECTIONS.fetch_add(1, Ordering::SeqCst);
+    // Registered first, so a shutdown starting now still waits for it
+    let section = CriticalSection(());
+    if is_shutting_down() {
+        return Err(tr("shutdown.write_refused", &[]));
+    }
+    Ok(section)
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Wait until no critical section is open; false on timeout
+pub fn wait_for_open_writes(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while OPEN_SECTIONS.load(Ordering::SeqCst) > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(FLUSH_POLL);
+    }
+    true
+}
+
+/// Start the shutdown sequence; returns false if it is already running
+///
+/// The caller prevents the exit; the sequence exits the app when done.
+pub fn begin_shutdown(app: &AppHandle) -> bool {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    emit_log(app, &tr("shutdown.started", &[]), "info");
+
+    // Stop taking new work
+    let _ = stop_watching();
+    stop_intake_watcher();
+    let _ = stop_code_watcher();
+    for kind in INTAKE_TASK_KINDS {
+        abort_tasks(kind);
+    }
+    for batch in running_batches() {
+        request_abort(&batch.batch_id);
+    }
+
+    let app = app.clone();
+    tasks::spawn_blocking("shutdown", "終了処理", move || {
+        if !wait_for_open_writes(FLUSH_TIMEOUT) {
+            emit_log(&app, &tr("shutdown.timeout", &[]), "warn");
+        }
+        kill_all_children();
+        app.exit(0);
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_writes_are_waited_for() {
+        let section = critical_section().expect("not shutting down");
+        assert!(!wait_for_open_writes(Duration::from_millis(150)));
+
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(section);
+        });
+        assert!(wait_for_open_writes(Duration::from_secs(5)));
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+//! 仕様書条項との突合
+//!
+//! Registered specifications (標準仕様書・特記仕様書) are split into clauses
+//! by their clause numbers (`1-1-1-3`, `第12条`) and indexed in the RAG index
+//! as `clause` passages. At analysis time the clauses closest to the document
+//! are put into the prompt and the model cites them as `［根拠: 仕様書 条項］`;
+//! the citation is carried as the clause reference of a finding. The
+//! specifications themselves are not attached to the request, they are
+//! usually hundreds of pages.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::access::{ensure_allowed, Operation};
+use crate::database::open_db;
+use crate::pdf_text::extract_pdf_text;
+use crate::postprocess::{apply_rules, OutputRule};
+use crate::project_settings::{load_project_settings, save_project_settings, ReferenceDocument};
+use crate::rag::{chunk_text, index_passages, retrieve_clauses, KIND_CLAUSE};
+use crate::tasks::run_blocking;
+
+/// Clauses put into the prompt
+const TOP_CLAUSES: usize = 5;
+
+/// Clause number at the start of a line (`第12条`, `1-1-1-3`) and its title
+static CLAUSE_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(第\d+(?:編|章|節|条)|\d{1,2}(?:-\d{1,3}){2,3})(?:\s+|　|$)(.*)$")
+        .expect("valid pattern")
+});
+
+/// Clause citation in a finding (`［根拠: 土木工事共通仕様書 1-1-1-3］`)
+static CLAUSE_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[［\[]根拠[:：]\s*([^］\]]+)[］\]]").expect("valid pattern"));
+
+/// Clause titles longer than this are body text that starts with a number
+const MAX_TITLE_CHARS: usize = 40;
+
+/// A numbered clause of a specification
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct SpecClause {
+    pub number: String,
+    pub title: String,
+    pub text: String,
+}
+
+fn clause_header(line: &str) -> Option<(String, String)> {
+    let caps = CLAUSE_HEADER.captures(line)?;
+    let title = caps[2].trim().to_string();
+    (title.chars().count() <= MAX_TITLE_CHARS).then(|| (caps[1].to_string(), title))
+}
+
+/// Clauses of a specification text (text before the first clause is skipped)
+pub fn split_clauses(text: &str) -> Vec<SpecClause> {
+    // 全角の数字・ハイフンで書かれた条項番号も拾う
+    let text = apply_rules(text, &[OutputRule::HalfwidthDigits]).replace(['－', '‐', '−'], "-");
+    let mut clauses: Vec<SpecClause> = Vec::new();
+    for line in text.lines() {
+        if let Some((number, title)) = clause_header(line) {
+            clauses.push(SpecClause {
+                number,
+                title,
+                text: String::new(),
+            });
+        } else if let Some(clause) = clauses.last_mut() {
+            let line = line.trim();
+            if !line.is_empty() {
+                clause.text.push_str(line);
+                clause.text.push('\n');
+            }
+        }
+    }
+    clauses.retain(|c| !c.text.trim().is_empty() || !c.title.is_empty());
+    clauses
+}
+
+/// Indexed passages of a clause; every passage starts with the citation
+pub fn clause_passages(label: &str, clause: &SpecClause) -> Vec<String> {
+    let header = format!("〔{} {} {}〕", label, clause.number, clause.title);
+    let chunks = chunk_text(&clause.text);
+    if chunks.is_empty() {
+        return vec![header.trim_end().to_string()];
+    }
+    chunks
+        .into_iter()
+        .map(|chunk| format!("{}\n{}", header, chunk))
+        .collect()
+}
+
+/// Index the clauses of a specification; returns the number of clauses
+pub fn index_specification(
+    conn: &Connection,
+    project_folder: &str,
+    reference: &ReferenceDocument,
+) -> Result<usize, String> {
+    let clauses = split_clauses(&extract_pdf_text(&reference.path)?);
+    if clauses.is_empty() {
+        return Err(format!(
+            "{} から条項番号を読み取れません（テキストを含むPDFを登録してください）",
+            reference.label
+        ));
+    }
+    let passages: Vec<String> = clauses
+        .iter()
+        .flat_map(|c| clause_passages(&reference.label, c))
+        .collect();
+    index_passages(conn, project_folder, &reference.path, KIND_CLAUSE, &passages)?;
+    Ok(clauses.len())
+}
+
+/// Re-index every registered specification of a project (errors are skipped)
+pub fn index_specifications(conn: &Connection, project_folder: &str) -> usize {
+    load_project_settings(project_folder)
+        .reference_documents
+        .iter()
+        .filter(|r| r.specification)
+        .filter_map(|r| index_specification(conn, project_folder, r).ok())
+        .sum()
+}
+
+/// Prompt section with the clauses relevant to the document
+pub fn clause_section(project_folder: &str, query: &str) -> String {
+    let passages = open_db()
+        .and_then(|conn| retrieve_clauses(&conn, project_folder, query, TOP_CLAUSES))
+        .unwrap_or_default();
+    if passages.is_empty() {
+        return String::new();
+    }
+    let clauses: Vec<String> = passages.into_iter().map(|p| p.text).collect();
+    format!(
+        "\n## 仕様書の関連条項\n以下は登録された仕様書のうち、この書類に関連する条項の抜粋です。指摘が仕様書の規定に基づく場合は、その指摘の行末に「［根拠: 仕様書名 条項番号］」の形式で条項を引用してください（抜粋にない条項番号は書かないこと）：\n{}\n",
+        clauses.join("\n\n")
+    )
+}
+
+/// Clause cited in a finding (`［根拠: 土木工事共通仕様書 1-1-1-3］`)
+pub fn clause_reference(issue: &str) -> Option<String> {
+    CLAUSE_REFERENCE
+        .captures(issue)
+        .map(|caps| caps[1].trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+/// 仕様書を参照資料として登録し、条項を索引（戻り値は条項数）
+#[tauri::command]
+pub async fn register_specification(
+    folder: String,
+    path: String,
+    label: Option<String>,
+) -> Result<usize, String> {
+    ensure_allowed(Operation::EditGuidelines)?;
+    if !Path::new(&path).is_file() {
+        return Err("仕様書が見つかりません".to_string());
+    }
+    let label = label.filter(|l| !l.trim().is_empty()).unwrap_or_else(|| {
+        Path::new(&path)
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let task_label = label.clone();
+    run_blocking("rag", &task_label, move || {
+        let reference = ReferenceDocument {
+            path: path.clone(),
+            label,
+            doc_types: Vec::new(),
+            specification: true,
+        };
+        let count = index_specification(&open_db()?, &folder, &reference)?;
+        let mut settings = load_project_settings(&folder);
+        settings.reference_documents.retain(|r| r.path != path);
+        settings.reference_documents.push(reference);
+        save_project_settings(&folder, &settings)?;
+        Ok(count)
+    })
+    .await
+    .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    const SPEC: &str = "土木工事共通仕様書\n第1編 共通編\n１－１－１－３ 設計図書の照査等\n受注者は施工前に設計図書を照査し、\n相違がある場合は監督職員に通知しなければならない。\n1-1-1-4 施工計画書\n受注者は工事着手前に施工計画書を提出しなければならない。\n第12条 検査\n検査は監督職員の立会いのもと行う。";
+
+    #[test]
+    fn clauses_are_split_by_their_numbers() {
+        let clauses = split_clauses(SPEC);
+        let numbers: Vec<&str> = clauses.iter().map(|c| c.number.as_str()).collect();
+        assert_eq!(numbers, vec!["第1編", "1-1-1-3", "1-1-1-4", "第12条"]);
+        assert_eq!(clauses[1].title, "設計図書の照査等");
+        assert!(clauses[1].text.contains("監督職員に通知"));
+        assert_eq!(
+            clause_passages("共通仕様書", &clauses[2])[0],
+            "〔共通仕様書 1-1-1-4 施工計画書〕\n受注者は工事着手前に施工計画書を提出しなければならない。"
+        );
+    }
+
+    #[test]
+    fn clauses_are_retrieved_separately_and_cited() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let passages: Vec<String> = split_clauses(SPEC)
+            .iter()
+            .flat_map(|c| clause_passages("共通仕様書", c))
+            .collect();
+        index_passages(&conn, "C:/工事", "C:/仕様書.pdf", KIND_CLAUSE, &passages).unwrap();
+
+        let hits = retrieve_clauses(&conn, "C:/工事", "施工計画書の提出", 1).unwrap();
+        assert!(hits[0].text.starts_with("〔共通仕様書 1-1-1-4"));
+        assert!(crate::rag::retrieve(&conn, Some("C:/工事"), "施工計画書の提出", &[], 5)
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            clause_reference("⚠ 施工計画書の提出日が着工後 ［根拠: 共通仕様書 1-1-1-4］").as_deref(),
+            Some("共通仕様書 1-1-1-4")
+        );
+        assert_eq!(clause_reference("⚠ 押印なし"), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Disk space guard
+//!
+//! Staging PDFs into temp and writing reports failed silently on nearly-full
+//! laptops (truncated copies, empty reports). Writers check the free space of
+//! the target volume and the total size of the analysis temp dirs first and
+//! refuse with a specific error instead.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::gemini_cli::{temp_root, TEMP_DIR_PREFIX};
+use crate::messages::tr;
+use crate::settings::load_settings;
+
+/// Free space always left on a volume (Windows and the CLI need headroom)
+pub const MIN_FREE_BYTES: u64 = 500 * 1024 * 1024;
+
+/// Upper limit of the analysis temp dirs in total
+pub const TEMP_QUOTA_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+const MB: u64 = 1024 * 1024;
+
+/// Free bytes on the volume of `path` (the nearest existing ancestor)
+pub fn available_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    fs2::available_space(existing).ok()
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+/// Total size of the analysis temp dirs under a temp root
+pub fn temp_usage_in(root: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(root) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(TEMP_DIR_PREFIX))
+        .map(|e| dir_size(&e.path()))
+        .sum()
+}
+
+/// Check a write of `needed` bytes against the free space
+pub fn check_free_space(needed: u64, available: u64, target: &Path) -> Result<(), String> {
+    if available < needed.saturating_add(MIN_FREE_BYTES) {
+        return Err(tr(
+            "storage.disk_full",
+            &[&target.display(), &needed.div_ceil(MB), &(available / MB)],
+        ));
+    }
+    Ok(())
+}
+
+/// Refuse writing `needed` bytes to `target` on a nearly-full volume
+///
+/// Passes when the free space cannot be determined (network drives etc.).
+pub fn ensure_space_for(target: &Path, needed: u64) -> Result<(), String> {
+    match available_space(target) {
+        Some(available) => check_free_space(needed, available, target),
+        None => Ok(()),
+    }
+}
+
+/// Refuse staging `needed` bytes into temp on a full volume or over the quota
+pub fn ensure_temp_space(needed: u64) -> Result<(), String> {
+    let root = temp_root();
+    let usage = temp_usage_in(&root);
+    if usage.saturating_add(needed) > TEMP_QUOTA_BYTES {
+        return Err(tr(
+            "storage.temp_quota",
+            &[&root.display(), &(usage / MB), &(TEMP_QUOTA_BYTES / MB)],
+        ));
+    }
+    ensure_space_for(&root, needed)
+}
+
+/// Free space and temp usage
+#[derive(Clone, Serialize)]
+pub struct StorageStatus {
+    pub temp_root: String,
+    pub temp_free_bytes: Option<u64>,
+    pub temp_usage_bytes: u64,
+    pub temp_quota_bytes: u64,
+    pub config_free_bytes: Option<u64>,
+    pub watch_folder_free_bytes: Option<u64>,
+    /// Any checked volume is below the reserve
+    pub low_space: bool,
+}
+
+/// 空き容量と一時フォルダの使用量
+#[tauri::command]
+pub fn get_storage_status() -> StorageStatus {
+    let root = temp_root();
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    let temp_free_bytes = available_space(&root);
+    let config_free_bytes = available_space(&config_dir);
+    let watch_folder_free_bytes = load_settings()
+        .watch_folder
+        .and_then(|f| available_space(Path::new(&f)));
+    let low_space = [temp_free_bytes, config_free_bytes, watch_folder_free_bytes]
+        .iter()
+        .flatten()
+        .any(|free| *free < MIN_FREE_BYTES);
+
+    StorageStatus {
+        temp_root: root.to_string_lossy().to_string(),
+        temp_free_bytes,
+        temp_usage_bytes: temp_usage_in(&root),
+        temp_quota_bytes: TEMP_QUOTA_BYTES,
+        config_free_bytes,
+        watch_folder_free_bytes,
+        low_space,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    #[test]
+    fn free_space_keeps_reserve() {
+        let target = Path::new("/tmp/report.md");
+        assert!(check_free_space(10 * MB, MIN_FREE_BYTES + 20 * MB, target).is_ok());
+        assert!(check_free_space(10 * MB, MIN_FREE_BYTES + 5 * MB, target).is_err());
+    }
+
+    #[test]
+    fn temp_usage_counts_only_analysis_dirs() {
+        let root = create_temp_dir(".shoruichecker_test_storage").expect("create dir");
+        let job = root.join(format!("{}job", TEMP_DIR_PREFIX));
+        fs::create_dir_all(job.join("nested")).unwrap();
+        fs::write(job.join("a.pdf"), vec![0u8; 100]).unwrap();
+        fs::write(job.join("nested").join("b.pdf"), vec![0u8; 50]).unwrap();
+        fs::write(root.join("other.pdf"), vec![0u8; 1000]).unwrap();
+
+        assert_eq!(temp_usage_in(&root), 150);
+        cleanup_temp_dir(&root);
+    }
+}
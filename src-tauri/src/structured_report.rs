@@ -0,0 +1,258 @@
+//! Structured views of analysis results for the frontend
+//!
+//! Analysis results are markdown written for people. The types here carry
+//! the same checks, findings, extracted fields and compare verdicts as data
+//! and are emitted in the `analysis-report` and `compare-matrix` events, so
+//! the result viewer can build tables without re-parsing the text.
+
+use std::path::Path;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::export::issue_severity;
+use crate::history::create_history_entry;
+use crate::spec_clauses::clause_reference;
+
+/// Labels longer than this are sentences, not field names
+const MAX_FIELD_LABEL_CHARS: usize = 20;
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Finding {
+    /// 高 / 中 / 低
+    pub severity: String,
+    pub text: String,
+    /// Cited specification clause (`土木工事共通仕様書 1-1-1-3`)
+    pub clause: Option<String>,
+}
+
+/// A `項目: 値` line of the result (金額, 工期, 契約日...)
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ExtractedField {
+    pub label: String,
+    pub value: String,
+}
+
+/// Payload of the `analysis-report` event (one per analyzed file)
+#[derive(Clone, Debug, Serialize)]
+pub struct AnalysisReport {
+    pub path: String,
+    pub file_name: String,
+    pub document_type: Option<String>,
+    /// Items marked ✓
+    pub checks: Vec<String>,
+    pub findings: Vec<Finding>,
+    pub fields: Vec<ExtractedField>,
+    /// 整合 / 要確認 / 不整合 when the result has a 総合判定 line
+    pub verdict: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CompareMatrixRow {
+    pub document: String,
+    /// 1-based comparison group (always 1 when the set was compared at once;
+    /// 0 for the anchor of a chunked comparison, which is in every group)
+    pub group: usize,
+    pub verdict: String,
+    pub findings: Vec<String>,
+}
+
+/// Payload of the `compare-matrix` event: every document against the anchor
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CompareMatrix {
+    pub anchor: String,
+    pub rows: Vec<CompareMatrixRow>,
+}
+
+impl CompareMatrix {
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "## 照合マトリクス（基準: {}）\n| 書類 | 照合グループ | 判定 | 指摘 |\n|---|---|---|---|\n",
+            self.anchor
+        );
+        for row in &self.rows {
+            let group = match row.group {
+                0 => "基準".to_string(),
+                group => group.to_string(),
+            };
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                row.document,
+                group,
+                row.verdict,
+                row.findings.len()
+            ));
+        }
+        markdown
+    }
+}
+
+/// Verdict of a result (its 総合判定 line)
+pub fn result_verdict(result: &str) -> Option<&'static str> {
+    let line = result.lines().find(|l| l.contains("総合判定"))?;
+    // 不整合 contains 整合, so it is checked first
+    ["不整合", "要確認", "整合"]
+        .into_iter()
+        .find(|v| line.contains(v))
+}
+
+/// Line text without list markers, check marks and emphasis
+fn strip_markers(line: &str) -> String {
+    let line = line.trim();
+    // Numbered list item (`1. `)
+    let line = line
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .strip_prefix(". ")
+        .unwrap_or(line);
+    line.trim_start_matches(['-', '*', '•', '・', '✓', '⚠', ' '])
+        .trim()
+        .replace("**", "")
+}
+
+/// `項目: 値` lines of a result (first occurrence of each label)
+pub fn extract_fields(result: &str) -> Vec<ExtractedField> {
+    let mut fields: Vec<ExtractedField> = Vec::new();
+    for line in result.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') || trimmed.starts_with('|') {
+            continue;
+        }
+        let line = strip_markers(line);
+        let Some((label, value)) = line.split_once(['：', ':']) else {
+            continue;
+        };
+        let (label, value) = (label.trim(), value.trim());
+        if label.is_empty()
+            || value.is_empty()
+            || label.chars().count() > MAX_FIELD_LABEL_CHARS
+            || label.contains("http")
+            || fields.iter().any(|f| f.label == label)
+        {
+            continue;
+        }
+        fields.push(ExtractedField {
+            label: label.to_string(),
+            value: value.to_string(),
+        });
+    }
+    fields
+}
+
+/// Structured report of a single-file result
+pub fn parse_report(path: &str, result: &str) -> AnalysisReport {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    let entry = create_history_entry(&file_name, path, result);
+    AnalysisReport {
+        path: path.to_string(),
+        file_name,
+        document_type: entry.document_type,
+        checks: result
+            .lines()
+            .filter(|l| l.contains('✓'))
+            .map(strip_markers)
+            .collect(),
+        findings: entry
+            .issues
+            .iter()
+            .map(|issue| Finding {
+                severity: issue_severity(issue).to_string(),
+                text: strip_markers(issue),
+                clause: clause_reference(issue),
+            })
+            .collect(),
+        fields: extract_fields(result),
+        verdict: result_verdict(result).map(str::to_string),
+    }
+}
+
+/// Rows of the 照合表 the model writes in a comparison result
+/// (`| 書類 | 判定 | 指摘 |`)
+pub fn parse_compare_table(result: &str) -> Vec<CompareMatrixRow> {
+    let mut rows = Vec::new();
+    let mut in_table = false;
+    for line in result.lines().map(str::trim) {
+        if !line.starts_with('|') {
+            in_table = false;
+            continue;
+        }
+        let cells: Vec<&str> = line.trim_matches('|').split('|').map(str::trim).collect();
+        if cells.first().is_some_and(|c| c.replace("**", "") == "書類") {
+            in_table = true;
+            continue;
+        }
+        if !in_table || cells.len() < 3 || cells.iter().all(|c| c.chars().all(|ch| matches!(ch, '-' | ':'))) {
+            continue;
+        }
+        let findings = match cells[2] {
+            "" | "-" | "なし" | "特になし" => Vec::new(),
+            text => text
+                .split(['、', '；', ';'])
+                .flat_map(|s| s.split("<br>"))
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+        };
+        rows.push(CompareMatrixRow {
+            document: cells[0].replace("**", ""),
+            group: 1,
+            verdict: ["不整合", "要確認", "整合"]
+                .into_iter()
+                .find(|v| cells[1].contains(v))
+                .unwrap_or(cells[1])
+                .to_string(),
+            findings,
+        });
+    }
+    rows
+}
+
+/// Emit the structured report of an analyzed file
+pub fn emit_analysis_report(app: &AppHandle, path: &str, result: &str) {
+    let _ = app.emit("analysis-report", parse_report(path, result));
+}
+
+pub fn emit_compare_matrix(app: &AppHandle, matrix: &CompareMatrix) {
+    let _ = app.emit("compare-matrix", matrix);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_report_collects_checks_findings_and_fields() {
+        let result = "## 書類タイプ: 契約書\n- **契約金額**: 1,100,000円\n- 工期：2026-04-01〜2026-09-30\n✓ 押印あり\n⚠ 印紙の金額が不足（誤り）\n総合判定: 要確認";
+        let report = parse_report("/p/契約書.pdf", result);
+        assert_eq!(report.file_name, "契約書.pdf");
+        assert_eq!(report.document_type.as_deref(), Some("契約書"));
+        assert_eq!(report.checks, vec!["押印あり".to_string()]);
+        assert_eq!(
+            report.findings,
+            vec![Finding {
+                severity: "高".to_string(),
+                text: "印紙の金額が不足（誤り）".to_string(),
+                clause: None,
+            }]
+        );
+        assert_eq!(report.fields[0].label, "契約金額");
+        assert_eq!(report.fields[0].value, "1,100,000円");
+        assert_eq!(report.fields[1].value, "2026-04-01〜2026-09-30");
+        assert_eq!(report.verdict.as_deref(), Some("要確認"));
+    }
+
+    #[test]
+    fn parse_compare_table_reads_model_table() {
+        let result = "概要\n| 書類 | 判定 | 指摘 |\n|---|---|---|\n| 見積書.pdf | ✓ 整合 | なし |\n| **請求書.pdf** | ⚠ 不整合 | 金額が異なる、日付が異なる |\n\n総合判定: 不整合";
+        let rows = parse_compare_table(result);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].verdict, "整合");
+        assert!(rows[0].findings.is_empty());
+        assert_eq!(rows[1].document, "請求書.pdf");
+        assert_eq!(rows[1].verdict, "不整合");
+        assert_eq!(rows[1].findings.len(), 2);
+    }
+}
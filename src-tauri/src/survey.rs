@@ -0,0 +1,400 @@
+//! Numeric cross-check of survey drawings (縦断図・横断図)
+//!
+//! 測点ごとの計画高・地盤高を各図面から読み取り、同じ測点の値が図面間で
+//! 一致するかをローカルで照合する。AIの読み取りに任せると桁の誤りを見逃す
+//! ことがあるため、結果は自動判定の節として解析結果に追記する。
+//!
+//! Values come from the PDF text layer (CAD output); drawings without one
+//! are read by the text model as JSON, and only the comparison is trusted
+//! to Rust.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::gemini_cli::{
+    cleanup_temp_dir, create_temp_dir, run_gemini_with_prompt, stage_into_temp, TEMP_DIR_PREFIX,
+};
+use crate::pdf_text::{extract_page_texts, has_enough_text};
+use crate::settings::{load_settings, DEFAULT_TEXT_MODEL};
+
+/// Heights printed in mm precision are equal within half a millimetre
+const HEIGHT_TOLERANCE: f64 = 0.0005;
+
+/// Heights read at one station of a drawing
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct SurveyPoint {
+    /// Normalized station (`No.3`, `No.3+12.5`)
+    pub station: String,
+    /// 計画高
+    #[serde(default)]
+    pub planned: Option<f64>,
+    /// 地盤高
+    #[serde(default)]
+    pub ground: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HeightKind {
+    Planned,
+    Ground,
+}
+
+impl HeightKind {
+    fn label(self) -> &'static str {
+        match self {
+            HeightKind::Planned => "計画高",
+            HeightKind::Ground => "地盤高",
+        }
+    }
+}
+
+/// Station token normalized to `No.<n>` / `No.<n>+<offset>`
+pub fn parse_station(token: &str) -> Option<String> {
+    let token = token.trim().trim_start_matches("測点");
+    let upper = token.to_ascii_uppercase();
+    let rest = upper
+        .strip_prefix("NO.")
+        .or_else(|| upper.strip_prefix("NO"))?
+        .trim_start_matches(['.', ' ']);
+    let (main, offset) = match rest.split_once('+') {
+        Some((main, offset)) => (main, Some(offset)),
+        None => (rest, None),
+    };
+    let main: u32 = main.parse().ok()?;
+    match offset.map(|o| o.parse::<f64>()) {
+        None => Some(format!("No.{}", main)),
+        Some(Ok(o)) if o == 0.0 => Some(format!("No.{}", main)),
+        Some(Ok(o)) => Some(format!("No.{}+{}", main, o)),
+        Some(Err(_)) => None,
+    }
+}
+
+fn height_label(token: &str) -> Option<HeightKind> {
+    let label = token.trim().replace(['.', ' '], "").to_ascii_uppercase();
+    if label.starts_with("計画") || matches!(label.as_str(), "FH" | "PH") {
+        Some(HeightKind::Planned)
+    } else if label.contains("地盤高") || matches!(label.as_str(), "GH" | "GL") {
+        Some(HeightKind::Ground)
+    } else {
+        None
+    }
+}
+
+fn parse_height(token: &str) -> Option<f64> {
+    token.trim().trim_end_matches('m').parse().ok()
+}
+
+fn set_height(points: &mut Vec<SurveyPoint>, station: &str, kind: HeightKind, value: f64) {
+    let index = match points.iter().position(|p| p.station == station) {
+        Some(i) => i,
+        None => {
+            points.push(SurveyPoint {
+                station: station.to_string(),
+                ..Default::default()
+            });
+            points.len() - 1
+        }
+    };
+    let point = &mut points[index];
+    let slot = match kind {
+        HeightKind::Planned => &mut point.planned,
+        HeightKind::Ground => &mut point.ground,
+    };
+    slot.get_or_insert(value);
+}
+
+/// Tokens of a line with `label=value` / `label:value` split apart
+fn tokens(line: &str) -> Vec<String> {
+    line.split_whitespace()
+        .flat_map(|t| t.split(['=', ':', '：']))
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Stations and heights in drawing text
+///
+/// Reads both the profile table of a 縦断図 (a 測点 row followed by rows of
+/// heights per column) and labelled values next to a station
+/// (`No.3 計画高 12.345 地盤高 11.200`, `No.3 FH=12.345 GH=11.200`).
+pub fn parse_survey_points(text: &str) -> Vec<SurveyPoint> {
+    let mut points = Vec::new();
+    let mut columns: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+
+    for line in text.lines() {
+        let tokens = tokens(line);
+        let stations: Vec<String> = tokens.iter().filter_map(|t| parse_station(t)).collect();
+        if stations.len() >= 2 {
+            columns = stations;
+            current = None;
+            continue;
+        }
+
+        // Row of the profile table: label followed by one value per column
+        if let Some(kind) = tokens.first().and_then(|t| height_label(t)) {
+            let values: Vec<f64> = tokens[1..].iter().filter_map(|t| parse_height(t)).collect();
+            if stations.is_empty() && !columns.is_empty() && values.len() == columns.len() {
+                for (station, value) in columns.iter().zip(values) {
+                    set_height(&mut points, station, kind, value);
+                }
+                continue;
+            }
+        }
+
+        let mut pending: Option<HeightKind> = None;
+        for token in &tokens {
+            if let Some(station) = parse_station(token) {
+                current = Some(station);
+                pending = None;
+            } else if let Some(kind) = height_label(token) {
+                pending = Some(kind);
+            } else if let (Some(kind), Some(station), Some(value)) =
+                (pending, current.as_ref(), parse_height(token))
+            {
+                set_height(&mut points, station, kind, value);
+                pending = None;
+            }
+        }
+    }
+    points
+}
+
+/// Points read by the text model from a drawing without a text layer
+fn extract_points_with_ai(path: &str) -> Option<Vec<SurveyPoint>> {
+    let file_name = Path::new(path).file_name()?.to_string_lossy().to_string();
+    let temp_dir = create_temp_dir(&format!("{}survey", TEMP_DIR_PREFIX)).ok()?;
+    let Ok(temp_name) = stage_into_temp(&temp_dir, path, &file_name) else {
+        cleanup_temp_dir(&temp_dir);
+        return None;
+    };
+    let prompt = r#"添付の測量図面（縦断図または横断図）から、測点ごとの計画高と地盤高を読み取り、次のJSON配列だけを出力してください（説明文は不要）。
+[{"station": "No.3", "planned": 12.345, "ground": 11.200}]
+- station: 測点名（No.3、No.3+12.5 など）
+- planned: 計画高（m、読み取れない場合は null）
+- ground: 地盤高（m、読み取れない場合は null）
+- 数値は図面の表記どおり、桁を変えずに記載すること"#;
+    let model = load_settings()
+        .text_model
+        .unwrap_or_else(|| DEFAULT_TEXT_MODEL.to_string());
+    let output = run_gemini_with_prompt(&temp_dir, prompt, &model, Some(&[temp_name]));
+    cleanup_temp_dir(&temp_dir);
+
+    let output = output.ok()?;
+    let (start, end) = output
+        .find('[')
+        .zip(output.rfind(']'))
+        .filter(|(start, end)| start < end)?;
+    let points: Vec<SurveyPoint> = serde_json::from_str(&output[start..=end]).ok()?;
+    Some(
+        points
+            .into_iter()
+            .filter_map(|p| {
+                Some(SurveyPoint {
+                    station: parse_station(&p.station)?,
+                    ..p
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Stations and heights of a drawing (text layer first, then the text model)
+pub fn read_survey_points(path: &str) -> Vec<SurveyPoint> {
+    let from_text = extract_page_texts(path)
+        .ok()
+        .filter(|pages| has_enough_text(pages))
+        .map(|pages| parse_survey_points(&pages.join("\n")))
+        .unwrap_or_default();
+    if !from_text.is_empty() {
+        return from_text;
+    }
+    extract_points_with_ai(path).unwrap_or_default()
+}
+
+/// One height that differs between drawings at the same station
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurveyMismatch {
+    pub station: String,
+    pub kind: &'static str,
+    /// (drawing, value) of every drawing that has the value
+    pub values: Vec<(String, f64)>,
+    /// Values differ by a factor of 10^n (a shifted decimal point)
+    pub digit_error: bool,
+}
+
+/// Whether `a` is `b` with the decimal point shifted
+fn is_digit_shift(a: f64, b: f64) -> bool {
+    if a == 0.0 || b == 0.0 {
+        return false;
+    }
+    let ratio = (a / b).abs().log10();
+    ratio.abs() >= 0.5 && (ratio - ratio.round()).abs() < 0.001
+}
+
+/// Compare the heights of stations found in more than one drawing
+///
+/// Returns the number of compared stations and the mismatches.
+pub fn compare_survey_points(drawings: &[(String, Vec<SurveyPoint>)]) -> (usize, Vec<SurveyMismatch>) {
+    let mut stations: Vec<&str> = Vec::new();
+    for (_, points) in drawings {
+        for point in points {
+            if !stations.contains(&point.station.as_str()) {
+                stations.push(&point.station);
+            }
+        }
+    }
+
+    let mut compared = 0;
+    let mut mismatches = Vec::new();
+    for station in stations {
+        let mut in_drawings = 0;
+        for kind in [HeightKind::Planned, HeightKind::Ground] {
+            let values: Vec<(String, f64)> = drawings
+                .iter()
+                .filter_map(|(name, points)| {
+                    let point = points.iter().find(|p| p.station == station)?;
+                    let value = match kind {
+                        HeightKind::Planned => point.planned,
+                        HeightKind::Ground => point.ground,
+                    }?;
+                    Some((name.clone(), value))
+                })
+                .collect();
+            if values.len() < 2 {
+                continue;
+            }
+            in_drawings = values.len();
+            let first = values[0].1;
+            if values.iter().any(|(_, v)| (v - first).abs() > HEIGHT_TOLERANCE) {
+                let digit_error = values.iter().any(|(_, v)| is_digit_shift(*v, first));
+                mismatches.push(SurveyMismatch {
+                    station: station.to_string(),
+                    kind: kind.label(),
+                    values,
+                    digit_error,
+                });
+            }
+        }
+        if in_drawings >= 2 {
+            compared += 1;
+        }
+    }
+    (compared, mismatches)
+}
+
+/// Result section of the cross-check (None with fewer than two drawings)
+pub fn survey_check_section(drawings: &[(String, Vec<SurveyPoint>)]) -> Option<String> {
+    if drawings.len() < 2 {
+        return None;
+    }
+    let read: Vec<(String, Vec<SurveyPoint>)> = drawings
+        .iter()
+        .filter(|(_, points)| !points.is_empty())
+        .cloned()
+        .collect();
+    let mut lines = Vec::new();
+    if read.len() < 2 {
+        lines.push("（測点を読み取れた図面が2つ未満のため照合していません）".to_string());
+    } else {
+        let (compared, mismatches) = compare_survey_points(&read);
+        if compared == 0 {
+            lines.push("⚠ 図面間で共通する測点が見つかりません".to_string());
+        } else {
+            lines.push(format!(
+                "✓ 照合した測点: {} か所（不一致 {} 件）",
+                compared,
+                mismatches.len()
+            ));
+        }
+        for mismatch in &mismatches {
+            let values = mismatch
+                .values
+                .iter()
+                .map(|(name, value)| format!("{} = {:.3}", name, value))
+                .collect::<Vec<_>>()
+                .join(" / ");
+            lines.push(format!(
+                "⚠ {} {}: {}{}",
+                mismatch.station,
+                mismatch.kind,
+                values,
+                if mismatch.digit_error { "（桁誤りの可能性）" } else { "" }
+            ));
+        }
+    }
+    Some(format!("## 測量図面の数値照合（自動判定）\n{}", lines.join("\n")))
+}
+
+/// Append the cross-check of the given survey drawings (file name, path) to a result
+pub fn append_survey_check(result: String, drawings: &[(String, String)]) -> String {
+    if drawings.len() < 2 {
+        return result;
+    }
+    let read: Vec<(String, Vec<SurveyPoint>)> = drawings
+        .iter()
+        .map(|(name, path)| (name.clone(), read_survey_points(path)))
+        .collect();
+    match survey_check_section(&read) {
+        Some(section) => format!("{}\n\n{}", result.trim_end(), section),
+        None => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(station: &str, planned: Option<f64>, ground: Option<f64>) -> SurveyPoint {
+        SurveyPoint {
+            station: station.to_string(),
+            planned,
+            ground,
+        }
+    }
+
+    #[test]
+    fn parse_station_normalizes_notations() {
+        assert_eq!(parse_station("NO.3").as_deref(), Some("No.3"));
+        assert_eq!(parse_station("測点No.3+12.50").as_deref(), Some("No.3+12.5"));
+        assert_eq!(parse_station("No.3+0.00").as_deref(), Some("No.3"));
+        assert_eq!(parse_station("Note"), None);
+    }
+
+    #[test]
+    fn parse_survey_points_reads_profile_tables_and_labels() {
+        let profile = "縦断図\n測点 No.0 No.1 No.1+10.0\n計画高 10.000 10.250 10.400\n地盤高 9.800 9.950 10.120\n";
+        let points = parse_survey_points(profile);
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[2], point("No.1+10", Some(10.4), Some(10.12)));
+
+        let cross = "横断図 No.1\nFH=10.250 GH=9.950\n測点 No.0 計画高:10.000 地盤高 9.800";
+        let points = parse_survey_points(cross);
+        assert_eq!(points[0], point("No.1", Some(10.25), Some(9.95)));
+        assert_eq!(points[1], point("No.0", Some(10.0), Some(9.8)));
+    }
+
+    #[test]
+    fn compare_survey_points_flags_shifted_digits() {
+        let drawings = vec![
+            (
+                "縦断図.pdf".to_string(),
+                vec![point("No.1", Some(10.25), Some(9.95)), point("No.2", Some(10.5), None)],
+            ),
+            (
+                "横断図.pdf".to_string(),
+                vec![point("No.1", Some(102.5), Some(9.95)), point("No.3", Some(11.0), None)],
+            ),
+        ];
+        let (compared, mismatches) = compare_survey_points(&drawings);
+        assert_eq!(compared, 1);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].kind, "計画高");
+        assert!(mismatches[0].digit_error);
+
+        let section = survey_check_section(&drawings).expect("section");
+        assert!(section.contains("照合した測点: 1 か所（不一致 1 件）"));
+        assert!(section.contains("⚠ No.1 計画高: 縦断図.pdf = 10.250 / 横断図.pdf = 102.500（桁誤りの可能性）"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Central registry of background tasks
+//!
+//! All background work runs on the Tauri-managed tokio runtime: async work
+//! with `spawn`, blocking work (file and process IO) with `spawn_blocking`.
+//! Every task is registered while it runs so it can be listed and, for async
+//! tasks, aborted.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde::Serialize;
+use tauri::async_runtime::{self, JoinHandle};
+use tokio::task::AbortHandle;
+
+use crate::processes::{kill_children_in_scope, with_child_scope};
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+// Global state for running tasks
+static TASKS: Mutex<Option<HashMap<u64, TaskEntry>>> = Mutex::new(None);
+
+/// A running background task
+#[derive(Clone, Serialize)]
+pub struct TaskInfo {
+    pub id: u64,
+    /// Subsystem, e.g. "analysis", "watcher", "scheduler"
+    pub kind: String,
+    pub label: String,
+    pub started_at: String,
+    /// Whether the task can be aborted (async tasks only)
+    pub abortable: bool,
+    /// False while a blocking task waits for a free worker thread
+    pub running: bool,
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    abort: Option<AbortHandle>,
+}
+
+/// Keeps a task registered until dropped (task finished or aborted)
+struct Registration(u64);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(map) = tasks.as_mut() {
+            map.remove(&self.0);
+        }
+    }
+}
+
+fn register(kind: &str, label: &str) -> Registration {
+    let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+    let info = TaskInfo {
+        id,
+        kind: kind.to_string(),
+        label: label.to_string(),
+        started_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        abortable: false,
+        running: false,
+    };
+    let mut tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+    tasks
+        .get_or_insert_with(HashMap::new)
+        .insert(id, TaskEntry { info, abort: None });
+    Registration(id)
+}
+
+fn mark_running(id: u64) {
+    let mut tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = tasks.as_mut().and_then(|map| map.get_mut(&id)) {
+        entry.info.running = true;
+    }
+}
+
+/// Child process scope of a blocking task (see [`cancel_task`])
+pub fn task_scope(id: u64) -> String {
+    format!("task:{}", id)
+}
+
+fn set_abort_handle(id: u64, handle: AbortHandle) {
+    let mut tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = tasks.as_mut().and_then(|map| map.get_mut(&id)) {
+        entry.info.abortable = true;
+        entry.abort = Some(handle);
+    }
+}
+
+/// Spawn an async task on the managed runtime
+pub fn spawn<F>(kind: &str, label: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let registration = register(kind, label);
+    let id = registration.0;
+    let handle = async_runtime::spawn(async move {
+        let _registration = registration;
+        mark_running(id);
+        future.await
+    });
+    set_abort_handle(id, handle.inner().abort_handle());
+    handle
+}
+
+/// Run blocking work (process or file IO) on the runtime's blocking pool
+pub fn spawn_blocking<F, R>(kind: &str, label: &str, f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let registration = register(kind, label);
+    let id = registration.0;
+    async_runtime::spawn_blocking(move || {
+        let _registration = registration;
+        mark_running(id);
+        with_child_scope(&task_scope(id), f)
+    })
+}
+
+/// Run blocking work and wait for it from async code
+pub async fn run_blocking<F, R>(kind: &str, label: &str, f: F) -> Result<R, String>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    spawn_blocking(kind, label, f)
+        .await
+        .map_err(|e| format!("バックグラウンド処理エラー: {}", e))
+}
+
+/// Currently running tasks, oldest first
+pub fn running_tasks() -> Vec<TaskInfo> {
+    let tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<TaskInfo> = tasks
+        .as_ref()
+        .map(|map| map.values().map(|e| e.info.clone()).collect())
+        .unwrap_or_default();
+    list.sort_by_key(|t| t.id);
+    list
+}
+
+/// Abort the async tasks of a kind; returns how many were aborted
+pub fn abort_tasks(kind: &str) -> usize {
+    // Abort outside the lock: cancelled tasks unregister themselves
+    let handles: Vec<AbortHandle> = {
+        let tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+        tasks
+            .as_ref()
+            .map(|map| {
+                map.values()
+                    .filter(|e| e.info.kind == kind)
+                    .filter_map(|e| e.abort.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    for handle in &handles {
+        handle.abort();
+    }
+    handles.len()
+}
+
+/// Cancel one task: abort it (async) or kill its CLI processes (blocking)
+///
+/// Returns false if the task is not running or cannot be cancelled.
+pub fn cancel_task(id: u64) -> bool {
+    let abort = {
+        let tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+        match tasks.as_ref().and_then(|map| map.get(&id)) {
+            Some(entry) => entry.abort.clone(),
+            None => return false,
+        }
+    };
+    match abort {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => kill_children_in_scope(&task_scope(id)) > 0,
+    }
+}
+
+/// 実行中のバックグラウンド処理の一覧
+#[tauri::command]
+pub fn get_background_tasks() -> Vec<TaskInfo> {
+    running_tasks()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(kind: &str) -> usize {
+        running_tasks().iter().filter(|t| t.kind == kind).count()
+    }
+
+    #[test]
+    fn blocking_task_is_registered_while_running() {
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let handle = spawn_blocking("test_blocking", "wait", move || {
+            rx.recv().unwrap();
+            42
+        });
+        assert_eq!(registered("test_blocking"), 1);
+
+        tx.send(()).unwrap();
+        assert_eq!(async_runtime::block_on(handle).unwrap(), 42);
+        assert_eq!(registered("test_blocking"), 0);
+    }
+
+    #[test]
+    fn async_task_can_be_aborted() {
+        let handle = spawn("test_abort", "pending", std::future::pending::<()>());
+        assert_eq!(registered("test_abort"), 1);
+        assert_eq!(abort_tasks("test_abort"), 1);
+
+        assert!(async_runtime::block_on(handle).is_err());
+        assert_eq!(registered("test_abort"), 0);
+    }
+}
@@ -0,0 +1,343 @@
+//! Deterministic checks of 交通誘導員配置実績
+//!
+//! 配置実績表の日付ごとの人数・氏名と、警備会社の伝票の日付・人数・時間を
+//! 構造化して読み取り、照合はRustで行う。人数と氏名数の不一致や伝票との
+//! 食い違いはAIの回答に埋もれやすいため、件数付きの節として必ず追記する。
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::gemini_cli::{
+    cleanup_temp_dir, create_temp_dir, run_gemini_with_prompt, stage_into_temp, TEMP_DIR_PREFIX,
+};
+use crate::pdf_text::{extract_page_texts, has_enough_text};
+use crate::settings::{load_settings, DEFAULT_TEXT_MODEL};
+
+/// Document text sent for extraction is cut to this many chars
+const MAX_EXTRACT_TEXT_CHARS: usize = 20000;
+
+/// Whether the document types call for the placement check
+pub fn is_traffic_guard_document(doc_types: &[String]) -> bool {
+    doc_types.iter().any(|t| t.contains("交通誘導"))
+}
+
+/// Kind of a record read from the documents
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum RecordKind {
+    /// A day of the 配置実績表
+    #[serde(rename = "実績")]
+    Placement,
+    /// A 伝票 of the security company
+    #[serde(rename = "伝票")]
+    Slip,
+}
+
+/// One day of placement or one slip
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct GuardRecord {
+    pub kind: RecordKind,
+    /// YYYY-MM-DD
+    pub date: String,
+    /// Value of the 人数 column
+    #[serde(default)]
+    pub count: Option<u32>,
+    /// Listed guard names (実績 only)
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// HH:MM
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub end: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Extraction {
+    #[serde(default)]
+    records: Vec<GuardRecord>,
+}
+
+/// Records from the model output (JSON object with `records`)
+pub fn parse_records(output: &str) -> Option<Vec<GuardRecord>> {
+    let (start, end) = output
+        .find('{')
+        .zip(output.rfind('}'))
+        .filter(|(start, end)| start < end)?;
+    let extraction: Extraction = serde_json::from_str(&output[start..=end]).ok()?;
+    Some(
+        extraction
+            .records
+            .into_iter()
+            .map(|mut r| {
+                r.names = r
+                    .names
+                    .into_iter()
+                    .map(|n| n.trim().to_string())
+                    .filter(|n| !n.is_empty())
+                    .collect();
+                r.start = r.start.as_deref().and_then(normalize_time);
+                r.end = r.end.as_deref().and_then(normalize_time);
+                r
+            })
+            .collect(),
+    )
+}
+
+/// `8:00`, `８：００`, `8時` → `08:00`
+pub fn normalize_time(time: &str) -> Option<String> {
+    let digits: Vec<u32> = time
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            _ => c,
+        })
+        .collect::<String>()
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    match digits[..] {
+        [h] if h <= 24 => Some(format!("{:02}:00", h)),
+        [h, m, ..] if h <= 24 && m < 60 => Some(format!("{:02}:{:02}", h, m)),
+        _ => None,
+    }
+}
+
+fn extraction_prompt(text: Option<&str>) -> String {
+    let source = match text {
+        Some(text) => format!(
+            "以下は書類から抽出したテキストです。\n---\n{}\n---",
+            text.chars().take(MAX_EXTRACT_TEXT_CHARS).collect::<String>()
+        ),
+        None => "添付のPDFの書類を読み取ってください。".to_string(),
+    };
+    format!(
+        r#"{}
+
+この書類は交通誘導員の配置実績表、または警備会社の伝票です。
+記載されている内容を、次のJSONだけで出力してください（説明文は不要、判断や照合はしないこと）。
+{{"records": [{{"kind": "実績", "date": "YYYY-MM-DD", "count": 2, "names": ["氏名"], "start": "08:00", "end": "17:00"}}]}}
+- kind: 配置実績表の日ごとの行は「実績」、伝票は1枚ごとに「伝票」
+- date: 日付（和暦は西暦に直す）
+- count: 人数欄の数値（書かれたとおり。なければ null）
+- names: その日に記載された誘導員の氏名（書かれたとおり全員。伝票で氏名がなければ空配列）
+- start / end: 開始・終了時刻（なければ null）"#,
+        source
+    )
+}
+
+/// Records of a document read by the text model
+pub fn extract_records(path: &str) -> Option<Vec<GuardRecord>> {
+    let file_name = Path::new(path).file_name()?.to_string_lossy().to_string();
+    let text = extract_page_texts(path)
+        .ok()
+        .filter(|pages| has_enough_text(pages))
+        .map(|pages| pages.join("\n"));
+    let temp_dir = create_temp_dir(&format!("{}traffic", TEMP_DIR_PREFIX)).ok()?;
+    let attachments = if text.is_none() {
+        match stage_into_temp(&temp_dir, path, &file_name) {
+            Ok(name) => vec![name],
+            Err(_) => {
+                cleanup_temp_dir(&temp_dir);
+                return None;
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    let model = load_settings()
+        .text_model
+        .unwrap_or_else(|| DEFAULT_TEXT_MODEL.to_string());
+    let prompt = extraction_prompt(text.as_deref());
+    let pdfs = (!attachments.is_empty()).then_some(attachments.as_slice());
+    let output = run_gemini_with_prompt(&temp_dir, &prompt, &model, pdfs);
+    cleanup_temp_dir(&temp_dir);
+    output.ok().and_then(|out| parse_records(&out))
+}
+
+/// Findings of the placement check
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlacementCheck {
+    /// Days of the 配置実績表
+    pub days: usize,
+    pub slips: usize,
+    /// 人数欄と氏名数の不一致
+    pub count_mismatches: Vec<String>,
+    /// 伝票との日付・人数・時間の不一致
+    pub slip_mismatches: Vec<String>,
+}
+
+fn time_range(record: &GuardRecord) -> String {
+    format!(
+        "{}〜{}",
+        record.start.as_deref().unwrap_or("?"),
+        record.end.as_deref().unwrap_or("?")
+    )
+}
+
+/// Check the placement days against their names and the slips
+pub fn check_placements(records: &[GuardRecord]) -> PlacementCheck {
+    let placements: Vec<&GuardRecord> = records.iter().filter(|r| r.kind == RecordKind::Placement).collect();
+    let slips: Vec<&GuardRecord> = records.iter().filter(|r| r.kind == RecordKind::Slip).collect();
+    let mut check = PlacementCheck {
+        days: placements.len(),
+        slips: slips.len(),
+        ..Default::default()
+    };
+
+    for day in &placements {
+        if let Some(count) = day.count {
+            if !day.names.is_empty() && day.names.len() != count as usize {
+                check.count_mismatches.push(format!(
+                    "{}: 人数欄 {} 人 / 氏名 {} 名（{}）",
+                    day.date,
+                    count,
+                    day.names.len(),
+                    day.names.join("、")
+                ));
+            }
+        }
+    }
+
+    // Without slips there is nothing to reconcile
+    if slips.is_empty() {
+        return check;
+    }
+    for day in &placements {
+        let on_date: Vec<&&GuardRecord> = slips.iter().filter(|s| s.date == day.date).collect();
+        if on_date.is_empty() {
+            check.slip_mismatches.push(format!("{}: 伝票がありません", day.date));
+            continue;
+        }
+        let slip_count: u32 = on_date.iter().map(|s| s.count.unwrap_or(1)).sum();
+        let day_count = day.count.unwrap_or(day.names.len() as u32);
+        if slip_count != day_count {
+            check.slip_mismatches.push(format!(
+                "{}: 実績 {} 人 / 伝票 {} 人",
+                day.date, day_count, slip_count
+            ));
+        }
+        if day.start.is_some() || day.end.is_some() {
+            for slip in on_date.iter().filter(|s| s.start.is_some() || s.end.is_some()) {
+                if slip.start != day.start || slip.end != day.end {
+                    check.slip_mismatches.push(format!(
+                        "{}: 時間が異なります（実績 {} / 伝票 {}）",
+                        day.date,
+                        time_range(day),
+                        time_range(slip)
+                    ));
+                }
+            }
+        }
+    }
+    let mut extra_dates: Vec<&str> = slips
+        .iter()
+        .filter(|s| !placements.iter().any(|d| d.date == s.date))
+        .map(|s| s.date.as_str())
+        .collect();
+    extra_dates.sort_unstable();
+    extra_dates.dedup();
+    for date in extra_dates {
+        check.slip_mismatches.push(format!("{}: 伝票はあるが実績表に記載がありません", date));
+    }
+    check
+}
+
+/// Result section of the check (always reported, with counts)
+pub fn placement_check_section(check: Option<&PlacementCheck>) -> String {
+    let Some(check) = check else {
+        return "## 交通誘導員配置の機械照合（自動判定）\n（配置実績を読み取れなかったため照合していません）".to_string();
+    };
+    let mut lines = vec![format!(
+        "照合件数: 実績 {} 日 / 伝票 {} 枚 — 人数と氏名数の不一致 {} 件、伝票との不一致 {} 件",
+        check.days,
+        check.slips,
+        check.count_mismatches.len(),
+        check.slip_mismatches.len()
+    )];
+    if check.count_mismatches.is_empty() && check.days > 0 {
+        lines.push("✓ 全日の人数欄と氏名数が一致".to_string());
+    }
+    lines.extend(check.count_mismatches.iter().map(|m| format!("⚠ 人数不一致 {}", m)));
+    if check.slips == 0 {
+        lines.push("（伝票が見つからないため伝票との照合は行っていません）".to_string());
+    } else if check.slip_mismatches.is_empty() {
+        lines.push("✓ 伝票と日付・人数・時間が一致".to_string());
+    }
+    lines.extend(check.slip_mismatches.iter().map(|m| format!("⚠ 伝票不一致 {}", m)));
+    format!("## 交通誘導員配置の機械照合（自動判定）\n{}", lines.join("\n"))
+}
+
+/// Append the placement check over the given documents to a result
+pub fn append_placement_check(result: String, paths: &[String]) -> String {
+    if paths.is_empty() {
+        return result;
+    }
+    let mut records = Vec::new();
+    let mut read_any = false;
+    for path in paths {
+        if let Some(found) = extract_records(path) {
+            read_any = true;
+            records.extend(found);
+        }
+    }
+    let check = (read_any && !records.is_empty()).then(|| check_placements(&records));
+    format!("{}\n\n{}", result.trim_end(), placement_check_section(check.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_records_normalizes_names_and_times() {
+        let output = "```json\n{\"records\": [{\"kind\": \"実績\", \"date\": \"2026-05-01\", \"count\": 2, \"names\": [\" 山田 \", \"\", \"佐藤\"], \"start\": \"８：００\", \"end\": \"17時\"}]}\n```";
+        let records = parse_records(output).expect("records");
+        assert_eq!(records[0].kind, RecordKind::Placement);
+        assert_eq!(records[0].names, vec!["山田".to_string(), "佐藤".to_string()]);
+        assert_eq!(records[0].start.as_deref(), Some("08:00"));
+        assert_eq!(records[0].end.as_deref(), Some("17:00"));
+    }
+
+    fn record(kind: RecordKind, date: &str, count: u32, names: &[&str], time: (&str, &str)) -> GuardRecord {
+        GuardRecord {
+            kind,
+            date: date.to_string(),
+            count: Some(count),
+            names: names.iter().map(|n| n.to_string()).collect(),
+            start: Some(time.0.to_string()),
+            end: Some(time.1.to_string()),
+        }
+    }
+
+    #[test]
+    fn check_placements_counts_every_mismatch() {
+        let day = ("08:00", "17:00");
+        let records = vec![
+            record(RecordKind::Placement, "2026-05-01", 2, &["山田", "佐藤"], day),
+            record(RecordKind::Placement, "2026-05-02", 3, &["山田", "佐藤"], day),
+            record(RecordKind::Placement, "2026-05-03", 1, &["山田"], day),
+            record(RecordKind::Slip, "2026-05-01", 2, &[], day),
+            record(RecordKind::Slip, "2026-05-02", 3, &[], ("08:00", "12:00")),
+            record(RecordKind::Slip, "2026-05-04", 1, &[], day),
+        ];
+        let check = check_placements(&records);
+        assert_eq!((check.days, check.slips), (3, 3));
+        assert_eq!(check.count_mismatches.len(), 1);
+        assert!(check.count_mismatches[0].starts_with("2026-05-02: 人数欄 3 人 / 氏名 2 名"));
+        assert_eq!(
+            check.slip_mismatches,
+            vec![
+                "2026-05-02: 時間が異なります（実績 08:00〜17:00 / 伝票 08:00〜12:00）".to_string(),
+                "2026-05-03: 伝票がありません".to_string(),
+                "2026-05-04: 伝票はあるが実績表に記載がありません".to_string(),
+            ]
+        );
+
+        let section = placement_check_section(Some(&check));
+        assert!(section.contains("人数と氏名数の不一致 1 件、伝票との不一致 3 件"));
+        assert!(placement_check_section(None).contains("照合していません"));
+    }
+}
@@ -0,0 +1,400 @@
+//! Usage of the AI providers per project
+//!
+//! Every AI request (after its retries) is recorded in the `ai_usage` table:
+//! project, backend, model, prompt and response size, attachments, duration
+//! and whether it succeeded. The API providers also report the token counts
+//! of their responses; the Gemini CLI does not print them, so CLI requests
+//! only have the sizes. Requests made outside an analysis of a project are
+//! recorded without one.
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::ai_provider::backend_label;
+use crate::database::open_db;
+use crate::settings::AiBackend;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+thread_local! {
+    /// Project the AI requests of this thread are made for
+    static USAGE_PROJECT: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// Model and token counts reported by the last API response
+    static LAST_TOKENS: RefCell<Option<TokenUsage>> = const { RefCell::new(None) };
+}
+
+/// What an API response reports about itself
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TokenUsage {
+    model: String,
+    input: Option<i64>,
+    output: Option<i64>,
+}
+
+/// One AI request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageRecord {
+    pub project_folder: Option<String>,
+    pub backend: AiBackend,
+    pub model: String,
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub prompt_chars: usize,
+    pub attachments: usize,
+    pub response_chars: usize,
+    pub input_tokens: Option<i64>,
+    pub output_tokens: Option<i64>,
+    pub success: bool,
+}
+
+/// Period of `get_usage_stats`, counted back from today
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsagePeriod {
+    Day,
+    Week,
+    #[default]
+    Month,
+    All,
+}
+
+impl UsagePeriod {
+    /// First day of the period (None: everything)
+    pub fn since(self, today: NaiveDate) -> Option<NaiveDate> {
+        let days = match self {
+            UsagePeriod::Day => 0,
+            UsagePeriod::Week => 6,
+            UsagePeriod::Month => 29,
+            UsagePeriod::All => return None,
+        };
+        Some(today - chrono::Duration::days(days))
+    }
+}
+
+/// Usage of one model within a project
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ModelUsage {
+    pub backend: String,
+    pub model: String,
+    pub requests: usize,
+    pub failed: usize,
+    pub prompt_chars: u64,
+    pub response_chars: u64,
+    /// Requests with token counts (API providers)
+    pub metered_requests: usize,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub duration_secs: u64,
+}
+
+/// Usage of one project (None: requests outside a project)
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ProjectUsage {
+    pub project_folder: Option<String>,
+    pub requests: usize,
+    pub failed: usize,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub duration_secs: u64,
+    pub models: Vec<ModelUsage>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct UsageStats {
+    pub since: Option<String>,
+    /// Most requests first
+    pub projects: Vec<ProjectUsage>,
+}
+
+/// Restores the project of the enclosing scope, also when `f` panics
+struct ProjectReset(Option<String>);
+
+impl Drop for ProjectReset {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        USAGE_PROJECT.with(|p| *p.borrow_mut() = previous);
+    }
+}
+
+/// Run `f` with its AI requests counted for a project
+pub fn with_usage_project<R>(project_folder: &str, f: impl FnOnce() -> R) -> R {
+    let previous = USAGE_PROJECT.with(|p| p.replace(Some(project_folder.to_string())));
+    let _reset = ProjectReset(previous);
+    f()
+}
+
+fn current_usage_project() -> Option<String> {
+    USAGE_PROJECT.with(|p| p.borrow().clone())
+}
+
+/// Model actually used and token counts of an API response, read by
+/// `run_gemini_with` (the Claude API and LLM servers map or replace the
+/// requested model)
+pub fn note_token_usage(model: &str, input: Option<i64>, output: Option<i64>) {
+    let usage = TokenUsage {
+        model: model.to_string(),
+        input,
+        output,
+    };
+    LAST_TOKENS.with(|t| *t.borrow_mut() = Some(usage));
+}
+
+/// Forget the token counts of an earlier request on this thread
+pub fn clear_token_usage() {
+    LAST_TOKENS.with(|t| *t.borrow_mut() = None);
+}
+
+fn take_token_usage() -> Option<TokenUsage> {
+    LAST_TOKENS.with(|t| t.borrow_mut().take())
+}
+
+/// Record of a request finished on this thread (`response` is None when it
+/// failed)
+pub fn usage_record(
+    backend: AiBackend,
+    model: &str,
+    prompt: &str,
+    attachments: usize,
+    duration: Duration,
+    response: Option<&str>,
+) -> UsageRecord {
+    let tokens = take_token_usage();
+    UsageRecord {
+        project_folder: current_usage_project(),
+        backend,
+        model: tokens
+            .as_ref()
+            .map_or(model, |t| t.model.as_str())
+            .to_string(),
+        started_at: (Local::now() - chrono::Duration::milliseconds(duration.as_millis() as i64))
+            .format(TIMESTAMP_FORMAT)
+            .to_string(),
+        duration_ms: duration.as_millis() as u64,
+        prompt_chars: prompt.chars().count(),
+        attachments,
+        response_chars: response.map_or(0, |r| r.chars().count()),
+        input_tokens: tokens.as_ref().and_then(|t| t.input),
+        output_tokens: tokens.as_ref().and_then(|t| t.output),
+        success: response.is_some(),
+    }
+}
+
+pub fn store_usage(conn: &Connection, record: &UsageRecord) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO ai_usage (project_folder, backend, model, started_at, duration_ms, prompt_chars,
+             attachments, response_chars, input_tokens, output_tokens, success)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            record.project_folder,
+            backend_label(record.backend),
+            record.model,
+            record.started_at,
+            record.duration_ms as i64,
+            record.prompt_chars as i64,
+            record.attachments as i64,
+            record.response_chars as i64,
+            record.input_tokens,
+            record.output_tokens,
+            record.success,
+        ],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+/// Record a request (best effort)
+pub fn record_usage(record: &UsageRecord) {
+    if let Ok(conn) = open_db() {
+        let _ = store_usage(&conn, record);
+    }
+}
+
+/// Usage per project and model since a day (everything when None)
+pub fn usage_stats(conn: &Connection, since: Option<NaiveDate>) -> Result<UsageStats, String> {
+    let since = since.map(|d| d.format("%Y-%m-%d").to_string());
+    let mut stmt = conn
+        .prepare(
+            "SELECT project_folder, backend, model, COUNT(*), SUM(success = 0),
+                    SUM(prompt_chars), SUM(response_chars), COUNT(input_tokens),
+                    COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0), SUM(duration_ms)
+             FROM ai_usage
+             WHERE ?1 IS NULL OR started_at >= ?1
+             GROUP BY project_folder, backend, model
+             ORDER BY project_folder, COUNT(*) DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                ModelUsage {
+                    backend: row.get(1)?,
+                    model: row.get(2)?,
+                    requests: row.get::<_, i64>(3)? as usize,
+                    failed: row.get::<_, i64>(4)? as usize,
+                    prompt_chars: row.get::<_, i64>(5)? as u64,
+                    response_chars: row.get::<_, i64>(6)? as u64,
+                    metered_requests: row.get::<_, i64>(7)? as usize,
+                    input_tokens: row.get(8)?,
+                    output_tokens: row.get(9)?,
+                    duration_secs: (row.get::<_, i64>(10)? / 1000) as u64,
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut projects: Vec<ProjectUsage> = Vec::new();
+    for (project_folder, model) in rows.filter_map(Result::ok) {
+        let project = match projects
+            .iter_mut()
+            .position(|p| p.project_folder == project_folder)
+        {
+            Some(i) => &mut projects[i],
+            None => {
+                projects.push(ProjectUsage {
+                    project_folder,
+                    ..Default::default()
+                });
+                projects.last_mut().expect("just pushed")
+            }
+        };
+        project.requests += model.requests;
+        project.failed += model.failed;
+        project.input_tokens += model.input_tokens;
+        project.output_tokens += model.output_tokens;
+        project.duration_secs += model.duration_secs;
+        project.models.push(model);
+    }
+    projects.sort_by(|a, b| b.requests.cmp(&a.requests));
+    Ok(UsageStats { since, projects })
+}
+
+/// AI requests made for a project in a month (`%Y-%m`)
+pub fn project_requests_in_month(conn: &Connection, project_folder: &str, month: &str) -> Result<usize, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM ai_usage WHERE project_folder = ?1 AND substr(started_at, 1, 7) = ?2",
+        params![project_folder, month],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|n| n as usize)
+    .map_err(|e| e.to_string())
+}
+
+/// 工事ごとのAI利用量（リクエスト数・文字数・トークン数・所要時間）
+#[tauri::command]
+pub fn get_usage_stats(period: Option<UsagePeriod>) -> Result<UsageStats, String> {
+    let since = period.unwrap_or_default().since(Local::now().date_naive());
+    usage_stats(&open_db()?, since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrate;
+
+    fn record(
+        project: Option<&str>,
+        backend: AiBackend,
+        at: &str,
+        tokens: Option<(i64, i64)>,
+    ) -> UsageRecord {
+        UsageRecord {
+            project_folder: project.map(str::to_string),
+            backend,
+            model: "gemini-2.5-pro".to_string(),
+            started_at: at.to_string(),
+            duration_ms: 30_000,
+            prompt_chars: 1_000,
+            attachments: 1,
+            response_chars: 500,
+            input_tokens: tokens.map(|t| t.0),
+            output_tokens: tokens.map(|t| t.1),
+            success: true,
+        }
+    }
+
+    #[test]
+    fn usage_is_summed_per_project_and_model() {
+        let conn = Connection::open_in_memory().expect("open");
+        migrate(&conn).expect("migrate");
+        let records = [
+            record(
+                Some("/工事A"),
+                AiBackend::Gemini,
+                "2026-04-01 10:00:00",
+                None,
+            ),
+            record(
+                Some("/工事A"),
+                AiBackend::Gemini,
+                "2026-04-02 10:00:00",
+                None,
+            ),
+            record(
+                Some("/工事A"),
+                AiBackend::Claude,
+                "2026-04-02 11:00:00",
+                Some((1_200, 300)),
+            ),
+            record(
+                Some("/工事B"),
+                AiBackend::Claude,
+                "2026-03-01 09:00:00",
+                Some((800, 100)),
+            ),
+            record(None, AiBackend::Gemini, "2026-04-02 12:00:00", None),
+        ];
+        for r in &records {
+            store_usage(&conn, r).unwrap();
+        }
+
+        let stats = usage_stats(&conn, NaiveDate::from_ymd_opt(2026, 3, 20)).unwrap();
+        let summary: Vec<(Option<&str>, usize, i64)> = stats
+            .projects
+            .iter()
+            .map(|p| (p.project_folder.as_deref(), p.requests, p.input_tokens))
+            .collect();
+        assert_eq!(summary, vec![(Some("/工事A"), 3, 1_200), (None, 1, 0)]);
+        let project_a = &stats.projects[0];
+        assert_eq!(project_a.models.len(), 2);
+        assert_eq!(project_a.models[0].requests, 2);
+        assert_eq!(project_a.models[0].metered_requests, 0);
+        assert_eq!(project_a.duration_secs, 90);
+
+        assert_eq!(usage_stats(&conn, None).unwrap().projects.len(), 3);
+        assert_eq!(project_requests_in_month(&conn, "/工事A", "2026-04"), Ok(3));
+        assert_eq!(project_requests_in_month(&conn, "/工事B", "2026-04"), Ok(0));
+    }
+
+    #[test]
+    fn requests_are_attributed_to_the_project_in_scope() {
+        note_token_usage("claude-sonnet-4-5", Some(10), Some(5));
+        let inside = with_usage_project("/工事A", || {
+            usage_record(
+                AiBackend::Claude,
+                "gemini-2.5-pro",
+                "プロンプト",
+                0,
+                Duration::from_secs(2),
+                Some("応答"),
+            )
+        });
+        assert_eq!(inside.project_folder.as_deref(), Some("/工事A"));
+        assert_eq!(inside.model, "claude-sonnet-4-5");
+        assert_eq!(
+            (inside.input_tokens, inside.output_tokens),
+            (Some(10), Some(5))
+        );
+        assert_eq!((inside.prompt_chars, inside.response_chars), (5, 2));
+
+        let outside = usage_record(AiBackend::Gemini, "m", "p", 0, Duration::ZERO, None);
+        assert_eq!(outside.project_folder, None);
+        assert_eq!(outside.model, "m");
+        assert_eq!(outside.input_tokens, None);
+        assert!(!outside.success);
+    }
+}
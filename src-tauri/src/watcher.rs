@@ -1,17 +1,219 @@
-use std::path::PathBuf;
-use std::sync::mpsc::channel;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use chrono::Local;
+use notify::event::ModifyKind;
 use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc::unbounded_channel;
 
 use crate::events::PdfDetectedEvent;
+use crate::file_lock::write_atomic;
+use crate::guidelines::{check_guidelines_file, is_guidelines_file};
+use crate::intake::wait_until_stable;
+use crate::intake_dedup::accept_new_file;
+use crate::messages::tr;
+use crate::project_init::{initialize_project, is_project_dir};
+use crate::result_store::load_result_data;
 use crate::settings::{load_settings, save_settings};
+use crate::tasks;
 
 // Global state for watcher
 static WATCHER_HANDLE: Mutex<Option<notify::RecommendedWatcher>> = Mutex::new(None);
 
+/// Recent watcher events for debugging (oldest first)
+static RECENT_EVENTS: Mutex<VecDeque<WatcherEventRecord>> = Mutex::new(VecDeque::new());
+
+/// Number of watcher events kept in memory
+const MAX_RECENT_EVENTS: usize = 200;
+
+/// How long a new folder keeps its name before it is set up as a project
+/// (Explorer creates 新しいフォルダー and the user renames it right away)
+const NEW_FOLDER_SETTLE: Duration = Duration::from_secs(10);
+
+/// A watcher event as shown by `get_watcher_events`
+#[derive(Clone, Serialize)]
+pub struct WatcherEventRecord {
+    pub at: String,
+    /// "create", "modify", "remove", "other", "error" or "missed"
+    pub kind: String,
+    pub paths: Vec<String>,
+    pub detail: Option<String>,
+}
+
+/// Last time each watched folder was seen by the watcher (unix seconds)
+#[derive(Default, Serialize, Deserialize)]
+struct WatcherState {
+    #[serde(default)]
+    last_seen: BTreeMap<String, u64>,
+}
+
+/// Serializes read-modify-write access to the watcher state file
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+
+fn get_state_path() -> PathBuf {
+    let config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("shoruichecker").join("watcher_state.json")
+}
+
+fn load_state() -> WatcherState {
+    fs::read_to_string(get_state_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &WatcherState) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    write_atomic(&get_state_path(), json)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn last_seen(folder: &str) -> Option<u64> {
+    let _guard = STATE_LOCK.lock();
+    load_state().last_seen.get(folder).copied()
+}
+
+/// Persist that the watcher has seen everything in the folder up to now
+fn touch_last_seen(folder: &str) {
+    let _guard = STATE_LOCK.lock();
+    let mut state = load_state();
+    state
+        .last_seen
+        .insert(folder.to_string(), unix_secs(SystemTime::now()));
+    let _ = save_state(&state);
+}
+
+fn record_event(kind: &str, paths: Vec<String>, detail: Option<String>) {
+    if let Ok(mut events) = RECENT_EVENTS.lock() {
+        if events.len() >= MAX_RECENT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(WatcherEventRecord {
+            at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            kind: kind.to_string(),
+            paths,
+            detail,
+        });
+    }
+}
+
+fn event_kind_name(kind: &EventKind) -> &'static str {
+    match kind {
+        EventKind::Create(_) => "create",
+        EventKind::Modify(_) => "modify",
+        EventKind::Remove(_) => "remove",
+        _ => "other",
+    }
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false)
+}
+
+/// Time a file appeared: the later of creation and modification time
+/// (copies keep the source's modification time on Windows)
+fn arrival_time(path: &Path) -> Option<u64> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok().map(unix_secs);
+    let created = meta.created().ok().map(unix_secs);
+    modified.max(created)
+}
+
+/// PDFs under the folder that appeared after `since` (hidden folders skipped)
+pub fn find_missed_pdfs(folder: &Path, since: u64) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut dirs = vec![folder.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = path
+                .file_name()
+                .map(|n| n.to_string_lossy().starts_with('.'))
+                .unwrap_or(false);
+            if path.is_dir() {
+                if !hidden {
+                    dirs.push(path);
+                }
+            } else if is_pdf(&path) && arrival_time(&path).map(|t| t > since).unwrap_or(false) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+pub(crate) fn emit_pdf_detected(app: &AppHandle, path: &Path) {
+    let path_str = path.to_string_lossy().to_string();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unknown.pdf".to_string());
+
+    // Emit event to frontend
+    let _ = app.emit(
+        "pdf-detected",
+        PdfDetectedEvent {
+            path: path_str.clone(),
+            name: name.clone(),
+        },
+    );
+
+    // Show notification
+    let _ = app.emit(
+        "show-notification",
+        serde_json::json!({
+            "title": tr("watcher.pdf_detected", &[]),
+            "body": tr("watcher.new_pdf", &[&name]),
+            "path": path_str
+        }),
+    );
+}
+
+/// Emit PDFs that arrived while the app was not watching
+///
+/// On the first run for a folder nothing is emitted; only the timestamp is
+/// recorded. PDFs that already have a stored result or the content of an
+/// earlier PDF are not reported.
+fn recover_missed_events(app: &AppHandle, folder: &str) {
+    if let Some(since) = last_seen(folder) {
+        for path in find_missed_pdfs(Path::new(folder), since) {
+            if load_result_data(&path.to_string_lossy()).is_some() || !accept_new_file(app, &path) {
+                continue;
+            }
+            record_event(
+                "missed",
+                vec![path.to_string_lossy().to_string()],
+                Some("停止中に追加されたPDF".to_string()),
+            );
+            emit_pdf_detected(app, &path);
+        }
+    }
+    touch_last_seen(folder);
+}
+
+/// 直近の監視イベント（デバッグ用、古い順）
+#[tauri::command]
+pub fn get_watcher_events() -> Vec<WatcherEventRecord> {
+    RECENT_EVENTS
+        .lock()
+        .map(|events| events.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
 /// 起動時の解析対象ファイルを取得
 #[tauri::command]
 pub fn get_startup_file() -> Option<String> {
@@ -50,14 +252,21 @@ pub(crate) fn start_watcher(app: AppHandle, folder: &str) -> Result<(), String>
 
     let folder_path = PathBuf::from(folder);
     if !folder_path.exists() {
-        return Err("フォルダが存在しません".to_string());
+        return Err(tr("watcher.folder_missing", &[]));
     }
 
-    let (tx, rx) = channel();
+    let (tx, mut rx) = unbounded_channel();
 
     let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
-        if let Ok(event) = res {
-            let _ = tx.send(event);
+        match res {
+            Ok(event) => {
+                let _ = tx.send(event);
+            }
+            Err(e) => record_event(
+                "error",
+                e.paths.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+                Some(e.to_string()),
+            ),
         }
     })
     .map_err(|e| e.to_string())?;
@@ -72,42 +281,65 @@ pub(crate) fn start_watcher(app: AppHandle, folder: &str) -> Result<(), String>
         *handle = Some(watcher);
     }
 
-    // Spawn thread to handle events
+    // Files dropped while the app was closed (after the watcher is running,
+    // so nothing arriving during the scan is lost)
+    recover_missed_events(&app, folder);
+
+    // Handle events until the watcher (and with it the sender) is dropped
     let app_clone = app.clone();
-    thread::spawn(move || {
-        while let Ok(event) = rx.recv() {
+    let watched = folder.to_string();
+    tasks::spawn("watcher", folder, async move {
+        while let Some(event) = rx.recv().await {
+            record_event(
+                event_kind_name(&event.kind),
+                event
+                    .paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect(),
+                None,
+            );
             if let EventKind::Create(_) = event.kind {
-                for path in event.paths {
-                    if path
-                        .extension()
-                        .map(|e| e == "pdf" || e == "PDF")
-                        .unwrap_or(false)
-                    {
-                        let path_str = path.to_string_lossy().to_string();
-                        let name = path
-                            .file_name()
-                            .map(|n| n.to_string_lossy().to_string())
-                            .unwrap_or_else(|| "unknown.pdf".to_string());
-
-                        // Emit event to frontend
-                        let _ = app_clone.emit(
-                            "pdf-detected",
-                            PdfDetectedEvent {
-                                path: path_str.clone(),
-                                name: name.clone(),
-                            },
-                        );
-
-                        // Show notification
-                        let _ = app_clone.emit(
-                            "show-notification",
-                            serde_json::json!({
-                                "title": "PDF検出",
-                                "body": format!("新しいPDF: {}", name),
-                                "path": path_str
-                            }),
-                        );
-                    }
+                for path in event.paths.iter().filter(|p| is_pdf(p)) {
+                    // Hashed once fully written; re-sent copies are skipped
+                    let (app, path) = (app_clone.clone(), path.clone());
+                    tasks::spawn_blocking("watcher", &path.to_string_lossy(), move || {
+                        if wait_until_stable(&path) && accept_new_file(&app, &path) {
+                            emit_pdf_detected(&app, &path);
+                        }
+                    });
+                }
+                touch_last_seen(&watched);
+            }
+            // New project folders, set up under their final name: a folder
+            // renamed before it settles is skipped here and set up by the
+            // rename event (whose old path is no longer a folder)
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))) {
+                let strategy = load_settings().project_root;
+                let root = PathBuf::from(&watched);
+                for dir in event
+                    .paths
+                    .iter()
+                    .filter(|p| p.is_dir() && is_project_dir(p, &root, &strategy))
+                {
+                    let (app, dir, root) = (app_clone.clone(), dir.clone(), root.clone());
+                    tasks::spawn_blocking("watcher", &dir.to_string_lossy(), move || {
+                        std::thread::sleep(NEW_FOLDER_SETTLE);
+                        if dir.is_dir() {
+                            initialize_project(&app, &dir, &root);
+                        }
+                    });
+                }
+            }
+            // Guideline files edited by hand or synced by a teammate
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths.iter().filter(|p| is_guidelines_file(p)) {
+                    let (app, path) = (app_clone.clone(), path.clone());
+                    tasks::spawn_blocking("watcher", &path.to_string_lossy(), move || {
+                        if wait_until_stable(&path) {
+                            check_guidelines_file(&app, &path);
+                        }
+                    });
                 }
             }
         }
@@ -115,3 +347,27 @@ pub(crate) fn start_watcher(app: AppHandle, folder: &str) -> Result<(), String>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::unique_suffix;
+
+    #[test]
+    fn find_missed_pdfs_skips_hidden_folders_and_old_files() {
+        let root = std::env::temp_dir().join(format!("shoruichecker_watch_test-{}", unique_suffix()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(root.join(".shoruichecker_seals")).unwrap();
+        fs::write(root.join("a.pdf"), b"%PDF").unwrap();
+        fs::write(root.join("sub").join("b.PDF"), b"%PDF").unwrap();
+        fs::write(root.join("notes.txt"), b"x").unwrap();
+        fs::write(root.join(".shoruichecker_seals").join("c.pdf"), b"%PDF").unwrap();
+
+        let found = find_missed_pdfs(&root, 0);
+        assert_eq!(found, vec![root.join("a.pdf"), root.join("sub").join("b.PDF")]);
+        let future = unix_secs(SystemTime::now()) + 3600;
+        assert!(find_missed_pdfs(&root, future).is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
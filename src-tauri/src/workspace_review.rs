@@ -0,0 +1,326 @@
+//! Architecture-level review of a whole repository on demand
+//!
+//! The code watcher reviews files one at a time as they are saved.
+//! `review_workspace` looks at the repository as a whole: the source files
+//! not excluded by `.gitignore` / `.ignore` are grouped by folder, each group
+//! is reviewed in batches together with the architecture context of the
+//! repository, and a last request combines the module reviews into an
+//! overall report, saved as `.workspace-review.md` in the folder.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use ignore::WalkBuilder;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::code_review::{load_architecture_context, REVIEW_EXTENSIONS};
+use crate::events::emit_log;
+use crate::gemini_cli::{run_gemini_in_temp, GeminiRequest};
+use crate::messages::tr;
+use crate::settings::{load_settings, DEFAULT_MODEL};
+use crate::shutdown::is_shutting_down;
+use crate::tasks::run_blocking;
+
+const REPORT_FILE: &str = ".workspace-review.md";
+
+/// Files longer than this are cut (generated code, fixtures)
+const MAX_FILE_CHARS: usize = 30_000;
+
+/// Source text sent in one review request
+const MAX_BATCH_CHARS: usize = 120_000;
+
+/// Files reviewed at most; the rest are listed as skipped
+const MAX_FILES: usize = 400;
+
+/// A source file, path relative to the repository with `/` separators
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: String,
+    pub text: String,
+}
+
+/// Files of one module reviewed in one request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReviewBatch {
+    /// Folder of the files, with the part number when the folder is split
+    pub module: String,
+    pub files: Vec<SourceFile>,
+}
+
+/// Result of `review_workspace`
+#[derive(Clone, Serialize)]
+pub struct WorkspaceReview {
+    pub report: String,
+    pub report_path: String,
+    pub files: usize,
+    pub batches: usize,
+    /// Batches whose review failed (noted in the report)
+    pub failed: usize,
+}
+
+fn has_review_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| REVIEW_EXTENSIONS.contains(&e.as_str()))
+}
+
+fn truncate_source(text: String) -> String {
+    if text.chars().count() <= MAX_FILE_CHARS {
+        return text;
+    }
+    let mut cut: String = text.chars().take(MAX_FILE_CHARS).collect();
+    cut.push_str("\n…（以下省略）");
+    cut
+}
+
+/// Reviewed source files of a repository, sorted by path
+///
+/// Ignore files apply whether or not the folder is a git checkout; hidden
+/// files and folders are skipped.
+pub fn collect_source_files(root: &Path) -> Vec<SourceFile> {
+    let mut files: Vec<SourceFile> = WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter(|entry| has_review_extension(entry.path()))
+        .filter_map(|entry| {
+            let text = fs::read_to_string(entry.path()).ok()?;
+            let relative = entry.path().strip_prefix(root).ok()?;
+            Some(SourceFile {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                text: truncate_source(text),
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+fn module_of(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((folder, _)) => folder.to_string(),
+        None => "（ルート）".to_string(),
+    }
+}
+
+/// Group files by folder; folders over `max_chars` are split into parts
+pub fn review_batches(files: Vec<SourceFile>, max_chars: usize) -> Vec<ReviewBatch> {
+    let mut modules: BTreeMap<String, Vec<SourceFile>> = BTreeMap::new();
+    for file in files {
+        modules.entry(module_of(&file.path)).or_default().push(file);
+    }
+    let mut batches = Vec::new();
+    for (module, files) in modules {
+        let mut parts: Vec<Vec<SourceFile>> = vec![Vec::new()];
+        let mut size = 0;
+        for file in files {
+            let chars = file.text.chars().count();
+            if size + chars > max_chars && parts.last().is_some_and(|p| !p.is_empty()) {
+                parts.push(Vec::new());
+                size = 0;
+            }
+            size += chars;
+            if let Some(part) = parts.last_mut() {
+                part.push(file);
+            }
+        }
+        let count = parts.len();
+        for (i, files) in parts.into_iter().enumerate() {
+            let module = if count > 1 {
+                format!("{} ({}/{})", module, i + 1, count)
+            } else {
+                module.clone()
+            };
+            batches.push(ReviewBatch { module, files });
+        }
+    }
+    batches
+}
+
+fn context_section(context: Option<&str>) -> String {
+    context.map(|c| format!("\n{}\n", c)).unwrap_or_default()
+}
+
+/// Prompt reviewing the design of one module
+pub fn batch_prompt(batch: &ReviewBatch, context: Option<&str>) -> String {
+    let sources: Vec<String> = batch
+        .files
+        .iter()
+        .map(|f| format!("### {}\n```\n{}\n```", f.path, f.text.trim_end()))
+        .collect();
+    format!(
+        "あなたはソフトウェアアーキテクトです。以下はリポジトリのモジュール「{}」のソースコードです。\n個々の行の細かい指摘ではなく、設計レベルでレビューしてください：\n- モジュールの責務と凝集度（責務が混在していないか）\n- 依存の方向と結合度（他モジュールへの依存が妥当か）\n- 重複した処理や共通化すべき箇所\n- 命名・エラー処理・状態管理の一貫性\n- テストが不足している重要な箇所\n\n問題は「- ⚠ 」、良い点は「- ✓ 」で始まる箇条書きで、対象のファイル名を添えて簡潔に書いてください。\n{}\n## ソースコード\n{}\n",
+        batch.module,
+        context_section(context),
+        sources.join("\n\n")
+    )
+}
+
+/// Prompt combining the module reviews into the overall review
+pub fn summary_prompt(reviews: &[(String, String)], context: Option<&str>) -> String {
+    let sections: Vec<String> = reviews
+        .iter()
+        .map(|(module, review)| format!("### {}\n{}", module, review.trim()))
+        .collect();
+    format!(
+        "以下はリポジトリの各モジュールについての設計レビューです。これをもとに、リポジトリ全体のアーキテクチャレビューをまとめてください。\nモジュールをまたぐ問題（責務の重複、循環した依存、方針の不統一）を優先し、次の見出しで書いてください：\n## 全体構成の評価\n## 主要な問題（優先度順、「- ⚠ 」で始める）\n## 推奨するリファクタリング\n{}\n## モジュール別レビュー\n{}\n",
+        context_section(context),
+        sections.join("\n\n")
+    )
+}
+
+fn run_review(prompt: &str, model: &str) -> Result<String, String> {
+    let request = GeminiRequest::text(prompt, model);
+    run_gemini_in_temp(".shoruichecker_temp_workspace_review", &request).map_err(|e| e.to_string())
+}
+
+fn review_folder(app: &AppHandle, root: &Path) -> Result<WorkspaceReview, String> {
+    let mut files = collect_source_files(root);
+    if files.is_empty() {
+        return Err("レビュー対象のソースファイルがありません".to_string());
+    }
+    let skipped = files.len().saturating_sub(MAX_FILES);
+    files.truncate(MAX_FILES);
+    let file_count = files.len();
+    if skipped > 0 {
+        emit_log(
+            app,
+            &format!("ファイルが多いため先頭の {} ファイルのみレビューします（{} ファイルは対象外）", file_count, skipped),
+            "warn",
+        );
+    }
+
+    let context = load_architecture_context(root);
+    let model = load_settings()
+        .model
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let batches = review_batches(files, MAX_BATCH_CHARS);
+    emit_log(
+        app,
+        &format!("=== ワークスペースレビュー ({} ファイル / {} 回に分けて送信) ===", file_count, batches.len()),
+        "info",
+    );
+
+    let mut reviews = Vec::new();
+    let mut failed = 0;
+    for (i, batch) in batches.iter().enumerate() {
+        if is_shutting_down() {
+            return Err(tr("shutdown.in_progress", &[]));
+        }
+        emit_log(
+            app,
+            &format!("{} をレビュー中... ({}/{})", batch.module, i + 1, batches.len()),
+            "wave",
+        );
+        let review = run_review(&batch_prompt(batch, context.as_deref()), &model).unwrap_or_else(|e| {
+            failed += 1;
+            emit_log(app, &format!("{} のレビューに失敗しました: {}", batch.module, e), "error");
+            format!("⚠ レビューに失敗しました: {}", e)
+        });
+        reviews.push((batch.module.clone(), review));
+    }
+    if failed == batches.len() {
+        return Err("すべてのモジュールのレビューに失敗しました".to_string());
+    }
+
+    emit_log(app, "全体のレビューをまとめています...", "wave");
+    let summary = run_review(&summary_prompt(&reviews, context.as_deref()), &model)?;
+
+    let mut report = format!(
+        "# ワークスペースレビュー\n\n- 対象: {}\n- 作成: {}\n- ファイル数: {}{}\n\n{}\n\n# モジュール別レビュー\n",
+        root.display(),
+        Local::now().format("%Y-%m-%d %H:%M"),
+        file_count,
+        if skipped > 0 {
+            format!("（{} ファイルは対象外）", skipped)
+        } else {
+            String::new()
+        },
+        summary.trim()
+    );
+    for (module, review) in &reviews {
+        report.push_str(&format!("\n## {}\n{}\n", module, review.trim()));
+    }
+    let report_path = root.join(REPORT_FILE);
+    fs::write(&report_path, &report).map_err(|e| format!("レビュー結果を保存できません: {}", e))?;
+    emit_log(app, &format!("✓ ワークスペースレビュー完了: {}", report_path.display()), "success");
+
+    Ok(WorkspaceReview {
+        report,
+        report_path: report_path.to_string_lossy().to_string(),
+        files: file_count,
+        batches: batches.len(),
+        failed,
+    })
+}
+
+/// リポジトリ全体をモジュール単位でレビューし、設計レベルのレポートを作成
+#[tauri::command]
+pub async fn review_workspace(app: AppHandle, folder: String) -> Result<WorkspaceReview, String> {
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("フォルダが存在しません".to_string());
+    }
+    run_blocking("review", &folder, move || review_folder(&app, &root))
+        .await
+        .and_then(|r| r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+
+    fn source(path: &str, chars: usize) -> SourceFile {
+        SourceFile {
+            path: path.to_string(),
+            text: "x".repeat(chars),
+        }
+    }
+
+    #[test]
+    fn ignored_and_unsupported_files_are_not_reviewed() {
+        let dir = create_temp_dir(".shoruichecker_test_workspace_review").expect("create dir");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::write(dir.join(".gitignore"), "target/\n*.gen.rs\n").unwrap();
+        fs::write(dir.join("src/lib.rs"), "mod a;").unwrap();
+        fs::write(dir.join("src/a.rs"), "fn a() {}").unwrap();
+        fs::write(dir.join("src/schema.gen.rs"), "// generated").unwrap();
+        fs::write(dir.join("target/debug/build.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("main.js"), "init();").unwrap();
+        fs::write(dir.join("README.md"), "# repo").unwrap();
+
+        let paths: Vec<String> = collect_source_files(&dir).into_iter().map(|f| f.path).collect();
+        assert_eq!(paths, vec!["main.js", "src/a.rs", "src/lib.rs"]);
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn files_are_batched_by_module() {
+        let files = vec![
+            source("main.js", 10),
+            source("src/a.rs", 60),
+            source("src/b.rs", 60),
+            source("src/c.rs", 10),
+            source("src/ui/view.ts", 200),
+        ];
+        let batches = review_batches(files, 100);
+        let summary: Vec<(&str, usize)> = batches
+            .iter()
+            .map(|b| (b.module.as_str(), b.files.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("src (1/2)", 1), ("src (2/2)", 2), ("src/ui", 1), ("（ルート）", 1)]
+        );
+        let prompt = batch_prompt(&batches[1], Some("## このリポジトリの設計"));
+        assert!(prompt.contains("モジュール「src (2/2)」"));
+        assert!(prompt.contains("### src/b.rs\n```"));
+        assert!(prompt.contains("## このリポジトリの設計"));
+    }
+}
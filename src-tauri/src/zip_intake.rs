@@ -0,0 +1,367 @@
+//! Password-protected ZIP deliverables (PPAP) in the intake folder
+//!
+//! Subcontractors send documents as password-protected ZIPs with the
+//! password in a separate mail. A ZIP dropped into the intake folder is
+//! expanded into a quarantine subfolder (`.unpacking`), where the `scan`
+//! hooks (e.g. a virus scanner's command line) check the extracted files.
+//! Files that pass are moved next to the ZIP (so a route subfolder keeps its
+//! project) and go through the usual intake pipeline; the intake watcher
+//! skips them as they are processed here. When the ZIP needs a password, an
+//! `archive-password-required` event is emitted and `unlock_archive` expands
+//! it with the password the user entered.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::events::emit_log;
+use crate::hooks::{configured_hooks, run_stage_hooks, HookPayload, HookStage};
+use crate::intake::{intake_folder, process_intake_file, unique_destination, wait_until_stable};
+use crate::intake_dedup::{accept_new_file, release_file};
+use crate::messages::tr;
+use crate::settings::load_settings;
+use crate::tasks;
+
+/// Quarantine subfolder of the intake folder (not watched)
+const UNPACK_DIR: &str = ".unpacking";
+
+/// Extracted document types
+const SUPPORTED_EXTENSIONS: [&str; 1] = ["pdf"];
+
+/// Guard against ZIP bombs
+const MAX_EXTRACTED_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Released files being processed by the archive intake
+static RELEASED: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+fn set_released(path: &Path, released: bool) {
+    let mut paths = RELEASED.lock().unwrap_or_else(|e| e.into_inner());
+    if released {
+        paths.insert(path.to_path_buf());
+    } else {
+        paths.remove(path);
+    }
+}
+
+/// Whether a file was released from an archive (the watcher leaves it alone)
+pub(crate) fn is_released(path: &Path) -> bool {
+    RELEASED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(path)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArchiveError {
+    PasswordRequired,
+    WrongPassword,
+    Other(String),
+}
+
+impl ArchiveError {
+    fn message(&self, name: &str) -> String {
+        match self {
+            ArchiveError::PasswordRequired => tr("intake.archive_password", &[&name]),
+            ArchiveError::WrongPassword => tr("intake.archive_wrong_password", &[&name]),
+            ArchiveError::Other(e) => tr("intake.archive_error", &[&name, e]),
+        }
+    }
+}
+
+impl From<ZipError> for ArchiveError {
+    fn from(e: ZipError) -> Self {
+        match e {
+            ZipError::InvalidPassword => ArchiveError::WrongPassword,
+            ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => ArchiveError::PasswordRequired,
+            e => ArchiveError::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(e: io::Error) -> Self {
+        ArchiveError::Other(e.to_string())
+    }
+}
+
+/// Payload of the `archive-password-required` event
+#[derive(Clone, Serialize)]
+pub struct PasswordRequiredEvent {
+    pub path: String,
+    pub name: String,
+}
+
+pub fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false)
+}
+
+fn is_supported(name: &Path) -> bool {
+    name.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| SUPPORTED_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Extract the supported documents of a ZIP into `dest` (folders flattened)
+///
+/// Entries with unsafe names (`../`, absolute paths) are skipped. A wrong
+/// ZipCrypto password can pass the header check; the CRC check while reading
+/// then reports it as a wrong password too.
+pub fn extract_documents(
+    archive: &Path,
+    password: Option<&str>,
+    dest: &Path,
+) -> Result<Vec<PathBuf>, ArchiveError> {
+    extract_within(archive, password, dest, MAX_EXTRACTED_BYTES)
+}
+
+/// Extract with at most `limit` bytes written in total
+///
+/// The bytes actually read count against the limit, not the sizes the
+/// entries declare, and entries are streamed to disk.
+fn extract_within(
+    archive: &Path,
+    password: Option<&str>,
+    dest: &Path,
+    limit: u64,
+) -> Result<Vec<PathBuf>, ArchiveError> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+    fs::create_dir_all(dest)?;
+    let mut extracted = Vec::new();
+    let mut total: u64 = 0;
+    for i in 0..zip.len() {
+        let (file_name, encrypted) = {
+            let entry = zip.by_index_raw(i)?;
+            let file_name = entry
+                .enclosed_name()
+                .filter(|n| !entry.is_dir() && is_supported(n))
+                .and_then(|n| n.file_name().map(|f| f.to_string_lossy().to_string()));
+            (file_name, entry.encrypted())
+        };
+        let Some(file_name) = file_name else {
+            continue;
+        };
+        let mut entry = match (encrypted, password) {
+            (false, _) => zip.by_index(i)?,
+            (true, Some(password)) => zip.by_index_decrypt(i, password.as_bytes())?,
+            (true, None) => return Err(ArchiveError::PasswordRequired),
+        };
+        let target = unique_destination(dest, &file_name);
+        let mut file = fs::File::create(&target)?;
+        // One byte over the remaining budget tells an oversized entry apart
+        total += io::copy(&mut entry.by_ref().take(limit - total + 1), &mut file).map_err(|e| {
+            if encrypted && e.kind() == io::ErrorKind::InvalidData {
+                ArchiveError::WrongPassword
+            } else {
+                ArchiveError::from(e)
+            }
+        })?;
+        if total > limit {
+            return Err(ArchiveError::Other("展開後のサイズが大きすぎます".to_string()));
+        }
+        extracted.push(target);
+    }
+    Ok(extracted)
+}
+
+/// Run the scan hooks over extracted files; any failure rejects them all
+pub fn scan_extracted(archive_name: &str, files: &[PathBuf]) -> Result<(), String> {
+    let payload = HookPayload {
+        stage: HookStage::Scan,
+        batch_id: archive_name.to_string(),
+        mode: "archive".to_string(),
+        paths: files.iter().map(|f| f.to_string_lossy().to_string()).collect(),
+        custom_instruction: String::new(),
+        outcome: None,
+    };
+    run_stage_hooks(&configured_hooks(), &payload).map(|_| ())
+}
+
+/// Expand, scan and release the documents of an archive into its folder
+fn unpack_archive(archive: &Path, password: Option<&str>) -> Result<Vec<PathBuf>, ArchiveError> {
+    let intake = intake_folder(&load_settings().intake);
+    let folder = archive.parent().map(Path::to_path_buf).unwrap_or_else(|| intake.clone());
+    let stem = archive
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let quarantine = unique_destination(&intake.join(UNPACK_DIR), &stem);
+    let result = extract_documents(archive, password, &quarantine).and_then(|files| {
+        scan_extracted(&stem, &files).map_err(ArchiveError::Other)?;
+        let mut released = Vec::new();
+        for file in files {
+            let name = file
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let target = unique_destination(&folder, &name);
+            set_released(&target, true);
+            if let Err(e) = fs::rename(&file, &target) {
+                set_released(&target, false);
+                return Err(e.into());
+            }
+            released.push(target);
+        }
+        Ok(released)
+    });
+    // Rejected or partially extracted files never leave the quarantine
+    let _ = fs::remove_dir_all(&quarantine);
+    result
+}
+
+/// Expand an archive, process the released files and report the outcome
+async fn expand(app: &AppHandle, archive: PathBuf, password: Option<String>) -> Result<usize, String> {
+    let name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let path = archive.clone();
+    let outcome = tasks::run_blocking("intake", &name, move || {
+        unpack_archive(&path, password.as_deref())
+    })
+    .await?;
+    match outcome {
+        Ok(files) if files.is_empty() => {
+            emit_log(app, &tr("intake.archive_empty", &[&name]), "warn");
+            Ok(0)
+        }
+        Ok(files) => {
+            emit_log(app, &tr("intake.archive_extracted", &[&name, &files.len()]), "success");
+            for file in &files {
+                let (app, file) = (app.clone(), file.clone());
+                tasks::spawn("intake", &file.to_string_lossy(), async move {
+                    process_intake_file(app, file.clone()).await;
+                    set_released(&file, false);
+                });
+            }
+            Ok(files.len())
+        }
+        Err(ArchiveError::PasswordRequired) => {
+            emit_log(app, &ArchiveError::PasswordRequired.message(&name), "warn");
+            let _ = app.emit(
+                "archive-password-required",
+                PasswordRequiredEvent {
+                    path: archive.to_string_lossy().to_string(),
+                    name,
+                },
+            );
+            Ok(0)
+        }
+        Err(e) => {
+            let message = e.message(&name);
+            emit_log(app, &message, "error");
+            Err(message)
+        }
+    }
+}
+
+/// A ZIP arrived in the intake folder
+pub(crate) async fn process_intake_archive(app: AppHandle, path: PathBuf) {
+    let ready = {
+        let path = path.clone();
+        tasks::run_blocking("intake", "受付ファイル待機", move || wait_until_stable(&path)).await
+    };
+    if !matches!(ready, Ok(true)) || !path.exists() {
+        return;
+    }
+    let accepted = {
+        let (app, path) = (app.clone(), path.clone());
+        tasks::run_blocking("intake", "受付ファイル照合", move || accept_new_file(&app, &path)).await
+    };
+    if matches!(accepted, Ok(false)) {
+        return;
+    }
+    // Nothing taken in (password required, empty or broken): a re-sent
+    // archive is expanded again
+    if !matches!(expand(&app, path.clone(), None).await, Ok(n) if n > 0) {
+        release_file(&path);
+    }
+}
+
+/// パスワード付きZIPをパスワードで展開して取り込む（戻り値は展開した書類数）
+#[tauri::command]
+pub async fn unlock_archive(app: AppHandle, path: String, password: String) -> Result<usize, String> {
+    let archive = PathBuf::from(&path);
+    if !archive.is_file() {
+        return Err(format!("ファイルが見つかりません: {}", path));
+    }
+    if password.is_empty() {
+        return Err("パスワードを入力してください".to_string());
+    }
+    expand(&app, archive, Some(password)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini_cli::{cleanup_temp_dir, create_temp_dir};
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{AesMode, ZipWriter};
+
+    fn write_zip(path: &Path, password: Option<&str>) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        let options = match password {
+            Some(p) => options.with_aes_encryption(AesMode::Aes256, p),
+            None => options,
+        };
+        for (name, content) in [
+            ("納品/契約書.pdf", b"%PDF-contract".as_slice()),
+            ("納品/readme.txt", b"text".as_slice()),
+            ("../evil.pdf", b"%PDF-evil".as_slice()),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn extracts_only_safe_supported_documents() {
+        let dir = create_temp_dir(".shoruichecker_test_zip").expect("create dir");
+        let archive = dir.join("plain.zip");
+        write_zip(&archive, None);
+        let files = extract_documents(&archive, None, &dir.join("out")).expect("extract");
+        let names: Vec<String> = files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["契約書.pdf".to_string()]);
+        assert_eq!(fs::read(&files[0]).unwrap(), b"%PDF-contract");
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn encrypted_archive_needs_the_right_password() {
+        let dir = create_temp_dir(".shoruichecker_test_zip_pw").expect("create dir");
+        let archive = dir.join("ppap.zip");
+        write_zip(&archive, Some("himitsu"));
+        let out = dir.join("out");
+        assert_eq!(extract_documents(&archive, None, &out), Err(ArchiveError::PasswordRequired));
+        assert_eq!(extract_documents(&archive, Some("wrong"), &out), Err(ArchiveError::WrongPassword));
+        assert_eq!(extract_documents(&archive, Some("himitsu"), &out).map(|f| f.len()), Ok(1));
+        cleanup_temp_dir(&dir);
+    }
+
+    #[test]
+    fn extraction_stops_at_the_size_limit() {
+        let dir = create_temp_dir(".shoruichecker_test_zip_limit").expect("create dir");
+        let archive = dir.join("plain.zip");
+        write_zip(&archive, None);
+        assert!(matches!(
+            extract_within(&archive, None, &dir.join("small"), 12),
+            Err(ArchiveError::Other(_))
+        ));
+        assert_eq!(extract_within(&archive, None, &dir.join("exact"), 13).map(|f| f.len()), Ok(1));
+        cleanup_temp_dir(&dir);
+    }
+}